@@ -1,11 +1,75 @@
 package com.saentis_notes.app
 
+import android.content.Intent
+import android.net.Uri
 import android.os.Bundle
+import android.provider.OpenableColumns
+import android.util.Base64
 import androidx.activity.enableEdgeToEdge
+import androidx.core.content.IntentCompat
+import org.json.JSONArray
+import org.json.JSONObject
 
 class MainActivity : TauriActivity() {
   override fun onCreate(savedInstanceState: Bundle?) {
     enableEdgeToEdge()
     super.onCreate(savedInstanceState)
+    // Not again when the activity is recreated for a configuration change.
+    if (savedInstanceState == null) handleShare(intent)
   }
+
+  override fun onNewIntent(intent: Intent) {
+    super.onNewIntent(intent)
+    handleShare(intent)
+  }
+
+  override fun onStop() {
+    super.onStop()
+    SyncWorker.schedule(this)
+  }
+
+  /** Saves text, links and images shared from another app, see `share` in Rust. */
+  private external fun receiveShare(json: String)
+
+  private fun handleShare(intent: Intent?) {
+    if (intent == null) return
+    if (intent.action != Intent.ACTION_SEND && intent.action != Intent.ACTION_SEND_MULTIPLE) return
+    val items = JSONArray()
+    intent.getStringExtra(Intent.EXTRA_TEXT)?.trim()?.takeIf { it.isNotEmpty() }?.let { text ->
+      val item = JSONObject()
+      if (Uri.parse(text).scheme in listOf("http", "https") && !text.contains(Regex("\\s"))) {
+        item.put("kind", "url").put("url", text)
+        intent.getStringExtra(Intent.EXTRA_TITLE)?.let { item.put("title", it) }
+      } else {
+        item.put("kind", "text").put("text", text)
+      }
+      items.put(item)
+    }
+    val streams = when (intent.action) {
+      Intent.ACTION_SEND ->
+        listOfNotNull(IntentCompat.getParcelableExtra(intent, Intent.EXTRA_STREAM, Uri::class.java))
+      else ->
+        IntentCompat.getParcelableArrayListExtra(intent, Intent.EXTRA_STREAM, Uri::class.java).orEmpty()
+    }
+    for (uri in streams) {
+      val type = contentResolver.getType(uri) ?: continue
+      if (!type.startsWith("image/")) continue
+      val bytes = contentResolver.openInputStream(uri)?.use { it.readBytes() } ?: continue
+      items.put(
+        JSONObject()
+          .put("kind", "image")
+          .put("name", displayName(uri) ?: "shared.${type.substringAfter('/')}")
+          .put("data", Base64.encodeToString(bytes, Base64.NO_WRAP))
+      )
+    }
+    if (items.length() == 0) return
+    val payload = JSONObject().put("items", items)
+    intent.getStringExtra(Intent.EXTRA_SUBJECT)?.let { payload.put("subject", it) }
+    receiveShare(payload.toString())
+  }
+
+  private fun displayName(uri: Uri): String? =
+    contentResolver.query(uri, arrayOf(OpenableColumns.DISPLAY_NAME), null, null, null)?.use {
+      if (it.moveToFirst()) it.getString(0) else null
+    }
 }
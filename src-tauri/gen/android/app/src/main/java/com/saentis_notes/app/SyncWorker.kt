@@ -0,0 +1,51 @@
+package com.saentis_notes.app
+
+import android.content.Context
+import androidx.work.Constraints
+import androidx.work.ExistingPeriodicWorkPolicy
+import androidx.work.NetworkType
+import androidx.work.PeriodicWorkRequestBuilder
+import androidx.work.WorkManager
+import androidx.work.Worker
+import androidx.work.WorkerParameters
+import org.json.JSONObject
+import java.util.concurrent.TimeUnit
+
+/** Runs the vault's sync providers while the app is in the background, see `sync::background`. */
+class SyncWorker(context: Context, params: WorkerParameters) : Worker(context, params) {
+  override fun doWork(): Result = if (runBackgroundSync()) Result.success() else Result.retry()
+
+  companion object {
+    private const val WORK_NAME = "background-sync"
+
+    init {
+      System.loadLibrary("notesdesktop_lib")
+    }
+
+    @JvmStatic private external fun runBackgroundSync(): Boolean
+
+    @JvmStatic private external fun backgroundSyncSchedule(): String?
+
+    /** Brings the periodic work in line with the settings; called whenever the app goes to the background. */
+    fun schedule(context: Context) {
+      val json = backgroundSyncSchedule() ?: return
+      val schedule = JSONObject(json)
+      val work = WorkManager.getInstance(context)
+      if (!schedule.getBoolean("enabled")) {
+        work.cancelUniqueWork(WORK_NAME)
+        return
+      }
+      val constraints = Constraints.Builder()
+        .setRequiredNetworkType(
+          if (schedule.getBoolean("requiresUnmetered")) NetworkType.UNMETERED else NetworkType.CONNECTED
+        )
+        .setRequiresCharging(schedule.getBoolean("requiresCharging"))
+        .setRequiresBatteryNotLow(schedule.getBoolean("requiresBatteryNotLow"))
+        .build()
+      val request = PeriodicWorkRequestBuilder<SyncWorker>(
+        schedule.getLong("intervalMinutes"), TimeUnit.MINUTES
+      ).setConstraints(constraints).build()
+      work.enqueueUniquePeriodicWork(WORK_NAME, ExistingPeriodicWorkPolicy.UPDATE, request)
+    }
+  }
+}
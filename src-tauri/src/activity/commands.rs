@@ -0,0 +1,30 @@
+use super::Activity;
+use crate::error::Result;
+use crate::vaults::Current;
+
+/// When note `id` was last opened and changed, and from where.
+#[tauri::command]
+pub async fn note_activity(
+    vault: Current,
+    id: String,
+    limit: Option<usize>,
+) -> Result<Vec<Activity>> {
+    super::of_note(&vault.storage, &id, limit.unwrap_or(super::DEFAULT_LIMIT))
+}
+
+/// What happened across the vault lately. Pass the `at` of the last entry
+/// as `before` for the next page; openings come along with `opened`.
+#[tauri::command]
+pub async fn activity_feed(
+    vault: Current,
+    limit: Option<usize>,
+    before: Option<i64>,
+    opened: Option<bool>,
+) -> Result<Vec<Activity>> {
+    super::feed(
+        &vault.storage,
+        limit.unwrap_or(super::DEFAULT_LIMIT),
+        before,
+        opened.unwrap_or(false),
+    )
+}
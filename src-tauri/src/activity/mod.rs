@@ -0,0 +1,200 @@
+//! Each note's activity: when it was opened, saved, trashed or brought
+//! back, and where that happened. Changes a sync applies are put down to
+//! the provider that brought them and, over the LAN, to the device they
+//! came from; everything else to this device, under the name it shows on
+//! the network. The same thing done again from the same place soon after
+//! folds into one entry, so an editor saving every few seconds leaves one
+//! line rather than hundreds.
+
+pub mod commands;
+
+use std::cell::RefCell;
+
+use rusqlite::{params, Row};
+use serde::Serialize;
+
+use crate::error::Result;
+use crate::storage::{now_millis, Storage};
+use crate::sync::lan;
+
+/// Entries kept per note, newest first.
+const KEEP_PER_NOTE: usize = 200;
+/// Entries older than this are dropped whatever their note.
+const RETAIN_MS: i64 = 365 * 24 * 60 * 60 * 1000;
+pub const DEFAULT_LIMIT: usize = 50;
+
+thread_local! {
+    /// The sync applying changes on this thread, if one is.
+    static SYNCING: RefCell<Option<Origin>> = const { RefCell::new(None) };
+}
+
+#[derive(Clone)]
+struct Origin {
+    provider: &'static str,
+    device: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ActivityKind {
+    Opened,
+    Created,
+    Edited,
+    Trashed,
+    Restored,
+    Deleted,
+}
+
+impl ActivityKind {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Opened => "opened",
+            Self::Created => "created",
+            Self::Edited => "edited",
+            Self::Trashed => "trashed",
+            Self::Restored => "restored",
+            Self::Deleted => "deleted",
+        }
+    }
+
+    fn parse(name: &str) -> Self {
+        match name {
+            "opened" => Self::Opened,
+            "created" => Self::Created,
+            "trashed" => Self::Trashed,
+            "restored" => Self::Restored,
+            "deleted" => Self::Deleted,
+            _ => Self::Edited,
+        }
+    }
+
+    /// How close together two of these count as one.
+    fn fold_ms(self) -> Option<i64> {
+        match self {
+            Self::Opened => Some(60 * 1000),
+            Self::Edited => Some(5 * 60 * 1000),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Activity {
+    pub id: i64,
+    pub note_id: String,
+    /// The note's title at the time, which outlives the note.
+    pub title: String,
+    pub kind: ActivityKind,
+    /// The device it happened on, when known; a change synced through a
+    /// server only names its provider.
+    pub device: Option<String>,
+    /// The sync provider that applied it, for changes made elsewhere.
+    pub provider: Option<String>,
+    pub at: i64,
+}
+
+impl Activity {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(Self {
+            id: row.get(0)?,
+            note_id: row.get(1)?,
+            title: row.get(2)?,
+            kind: ActivityKind::parse(&row.get::<_, String>(3)?),
+            device: row.get(4)?,
+            provider: row.get(5)?,
+            at: row.get(6)?,
+        })
+    }
+}
+
+const COLUMNS: &str = "id, note_id, title, kind, device, provider, at";
+
+/// Runs `apply` with what it changes put down to sync `provider` and, when
+/// it is known, the device the changes came from.
+pub fn from_sync<T>(provider: &'static str, device: Option<&str>, apply: impl FnOnce() -> T) -> T {
+    struct Restore(Option<Origin>);
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            SYNCING.set(self.0.take());
+        }
+    }
+    let _restore = Restore(SYNCING.replace(Some(Origin {
+        provider,
+        device: device.map(str::to_owned),
+    })));
+    apply()
+}
+
+/// Records `kind` happening to note `id`, which must still exist.
+pub fn record(storage: &Storage, id: &str, kind: ActivityKind) -> Result<()> {
+    let (device, provider) = match SYNCING.with_borrow(Clone::clone) {
+        Some(origin) => (origin.device, Some(origin.provider)),
+        None => (Some(lan::config_of(storage)?.name), None),
+    };
+    let now = now_millis();
+    let conn = storage.conn();
+    if let Some(fold) = kind.fold_ms() {
+        let folded = conn.execute(
+            "UPDATE note_activity
+             SET at = ?5, title = COALESCE((SELECT title FROM notes WHERE id = ?1), title)
+             WHERE id = (SELECT id FROM note_activity WHERE note_id = ?1
+                         ORDER BY at DESC, id DESC LIMIT 1)
+               AND kind = ?2 AND device IS ?3 AND provider IS ?4 AND at >= ?6",
+            params![id, kind.name(), device, provider, now, now - fold],
+        )?;
+        if folded > 0 {
+            return Ok(());
+        }
+    }
+    conn.execute(
+        "INSERT INTO note_activity (note_id, title, kind, device, provider, at)
+         VALUES (?1, COALESCE((SELECT title FROM notes WHERE id = ?1), ''), ?2, ?3, ?4, ?5)",
+        params![id, kind.name(), device, provider, now],
+    )?;
+    conn.execute(
+        "DELETE FROM note_activity WHERE note_id = ?1 AND id NOT IN
+             (SELECT id FROM note_activity WHERE note_id = ?1
+              ORDER BY at DESC, id DESC LIMIT ?2)",
+        params![id, KEEP_PER_NOTE as i64],
+    )?;
+    conn.execute("DELETE FROM note_activity WHERE at < ?1", [now - RETAIN_MS])?;
+    Ok(())
+}
+
+/// Note `id`'s latest `limit` entries, newest first.
+pub fn of_note(storage: &Storage, id: &str, limit: usize) -> Result<Vec<Activity>> {
+    let conn = storage.conn();
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {COLUMNS} FROM note_activity WHERE note_id = ?1
+         ORDER BY at DESC, id DESC LIMIT ?2"
+    ))?;
+    let entries = stmt
+        .query_map(params![id, limit as i64], Activity::from_row)?
+        .collect::<rusqlite::Result<_>>()?;
+    Ok(entries)
+}
+
+/// The vault's latest `limit` entries across every note, newest first.
+/// With `before`, only those older than it, for fetching the next page;
+/// without `opened`, openings are left out.
+pub fn feed(
+    storage: &Storage,
+    limit: usize,
+    before: Option<i64>,
+    opened: bool,
+) -> Result<Vec<Activity>> {
+    let conn = storage.conn();
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {COLUMNS} FROM note_activity
+         WHERE at < ?1 AND (?2 OR kind != 'opened')
+         ORDER BY at DESC, id DESC LIMIT ?3"
+    ))?;
+    let entries = stmt
+        .query_map(
+            params![before.unwrap_or(i64::MAX), opened, limit as i64],
+            Activity::from_row,
+        )?
+        .collect::<rusqlite::Result<_>>()?;
+    Ok(entries)
+}
@@ -0,0 +1,44 @@
+use tauri::{AppHandle, Manager};
+
+use super::{Api, ApiStatus};
+use crate::error::Result;
+use crate::vaults;
+
+/// Starts the local API on `port`, or the last one used, and keeps it on
+/// across restarts.
+#[tauri::command]
+pub async fn api_enable(app: AppHandle, port: Option<u16>) -> Result<ApiStatus> {
+    let vault = vaults::primary(&app)?;
+    let storage = &vault.storage;
+    let mut config = super::config_of(storage)?;
+    if let Some(port) = port {
+        config.port = port;
+    }
+    app.state::<Api>().start(&app, config.port)?;
+    config.enabled = true;
+    super::set_config(storage, &config)?;
+    super::status(&app)
+}
+
+#[tauri::command]
+pub async fn api_disable(app: AppHandle) -> Result<ApiStatus> {
+    let vault = vaults::primary(&app)?;
+    let storage = &vault.storage;
+    let mut config = super::config_of(storage)?;
+    config.enabled = false;
+    super::set_config(storage, &config)?;
+    app.state::<Api>().stop();
+    super::status(&app)
+}
+
+/// Issues a new access token; clients holding the old one get 401s.
+#[tauri::command]
+pub async fn api_rotate_token(app: AppHandle) -> Result<ApiStatus> {
+    super::rotate_token(&vaults::primary(&app)?.storage)?;
+    super::status(&app)
+}
+
+#[tauri::command]
+pub async fn api_status(app: AppHandle) -> Result<ApiStatus> {
+    super::status(&app)
+}
@@ -0,0 +1,368 @@
+pub mod commands;
+
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tauri::{AppHandle, Manager};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use crate::error::{Error, Result};
+use crate::storage::{NotePatch, Storage};
+use crate::vault::Vault;
+use crate::{calendar, http, protected, vaults};
+
+const CONFIG_KEY: &str = "api.config";
+const TOKEN_KEY: &str = "api.token";
+/// A second token that only reads the calendar feed, for the URL calendar
+/// apps subscribe to.
+const CALENDAR_TOKEN_KEY: &str = "api.calendarToken";
+/// Requests larger than this are refused rather than read into memory.
+const MAX_BODY_BYTES: u64 = 16 * 1024 * 1024;
+const DEFAULT_SEARCH_LIMIT: usize = 20;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ApiConfig {
+    pub enabled: bool,
+    pub port: u16,
+}
+
+impl Default for ApiConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: 27_183,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiStatus {
+    pub running: bool,
+    pub port: u16,
+    /// Sent by clients as `Authorization: Bearer <token>`.
+    pub token: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct NewNote {
+    title: String,
+    #[serde(default)]
+    body: String,
+    #[serde(default)]
+    folder: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Clip {
+    title: Option<String>,
+    url: Option<String>,
+    /// Page or selection HTML, converted to Markdown.
+    html: Option<String>,
+    /// Plain text, used as is when there is no HTML.
+    text: Option<String>,
+    #[serde(default)]
+    folder: String,
+}
+
+/// The localhost REST API for scripts and browser extensions, managed as
+/// app state. Off until enabled; every request needs the access token.
+#[derive(Default)]
+pub struct Api {
+    running: Mutex<Option<Running>>,
+}
+
+struct Running {
+    server: Arc<Server>,
+    port: u16,
+    thread: JoinHandle<()>,
+}
+
+impl Api {
+    /// Starts serving on `127.0.0.1:port`, replacing any running server.
+    pub fn start(&self, app: &AppHandle, port: u16) -> Result<()> {
+        let mut running = self.lock();
+        if let Some(old) = running.take() {
+            old.stop();
+        }
+        let server = Server::http(("127.0.0.1", port))
+            .map_err(|err| Error::InvalidInput(format!("cannot listen on port {port}: {err}")))?;
+        let server = Arc::new(server);
+        let requests = Arc::clone(&server);
+        let app = app.clone();
+        let thread = thread::spawn(move || {
+            for request in requests.incoming_requests() {
+                handle(&app, request);
+            }
+        });
+        *running = Some(Running {
+            server,
+            port,
+            thread,
+        });
+        Ok(())
+    }
+
+    pub fn stop(&self) {
+        if let Some(running) = self.lock().take() {
+            running.stop();
+        }
+    }
+
+    pub fn port(&self) -> Option<u16> {
+        self.lock().as_ref().map(|running| running.port)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Option<Running>> {
+        self.running.lock().expect("api state poisoned")
+    }
+}
+
+impl Running {
+    fn stop(self) {
+        self.server.unblock();
+        let _ = self.thread.join();
+    }
+}
+
+pub fn config_of(storage: &Storage) -> Result<ApiConfig> {
+    match storage.meta(CONFIG_KEY)? {
+        Some(json) => Ok(serde_json::from_str(&json)?),
+        None => Ok(ApiConfig::default()),
+    }
+}
+
+pub fn set_config(storage: &Storage, config: &ApiConfig) -> Result<()> {
+    storage.set_meta(CONFIG_KEY, &serde_json::to_string(config)?)
+}
+
+/// The access token, made on first use. Kept in plain meta rather than the
+/// sealed secrets so the API can check it while the vault is locked.
+pub fn token(storage: &Storage) -> Result<String> {
+    match storage.meta(TOKEN_KEY)? {
+        Some(token) => Ok(token),
+        None => rotate_token(storage),
+    }
+}
+
+/// Replaces the access token, locking out every client holding the old one.
+pub fn rotate_token(storage: &Storage) -> Result<String> {
+    let token = hex::encode(rand::random::<[u8; 32]>());
+    storage.set_meta(TOKEN_KEY, &token)?;
+    Ok(token)
+}
+
+/// The token that reads `calendar.ics` and nothing else, made on first use.
+/// It ends up in calendar apps' settings, so it cannot be the access token.
+pub fn calendar_token(storage: &Storage) -> Result<String> {
+    match storage.meta(CALENDAR_TOKEN_KEY)? {
+        Some(token) => Ok(token),
+        None => rotate_calendar_token(storage),
+    }
+}
+
+/// Replaces the calendar token, ending every subscription to the feed.
+pub fn rotate_calendar_token(storage: &Storage) -> Result<String> {
+    let token = hex::encode(rand::random::<[u8; 32]>());
+    storage.set_meta(CALENDAR_TOKEN_KEY, &token)?;
+    Ok(token)
+}
+
+pub fn status(app: &AppHandle) -> Result<ApiStatus> {
+    let vault = vaults::primary(app)?;
+    let storage = &vault.storage;
+    let port = app.state::<Api>().port();
+    Ok(ApiStatus {
+        running: port.is_some(),
+        port: port.unwrap_or(config_of(storage)?.port),
+        token: token(storage)?,
+    })
+}
+
+/// Starts the API at launch if it was left enabled.
+pub fn start_if_enabled(app: &AppHandle) -> Result<()> {
+    let config = config_of(&vaults::primary(app)?.storage)?;
+    if config.enabled {
+        app.state::<Api>().start(app, config.port)?;
+    }
+    Ok(())
+}
+
+fn handle(app: &AppHandle, mut request: Request) {
+    // Browser extensions send a preflight without credentials.
+    let (status, body, content_type) = if *request.method() == Method::Options {
+        (204, String::new(), JSON)
+    } else {
+        match route(app, &mut request) {
+            Ok(Reply::Json(value)) => (200, value.to_string(), JSON),
+            Ok(Reply::Calendar(ics)) => (200, ics, "text/calendar; charset=utf-8"),
+            Err((status, err)) => (
+                status,
+                json!({ "error": err.to_string() }).to_string(),
+                JSON,
+            ),
+        }
+    };
+    let mut response = Response::from_string(body).with_status_code(status);
+    for (name, value) in [
+        ("Content-Type", content_type),
+        ("Access-Control-Allow-Origin", "*"),
+        (
+            "Access-Control-Allow-Headers",
+            "Authorization, Content-Type",
+        ),
+        ("Access-Control-Allow-Methods", "GET, POST, PATCH, OPTIONS"),
+    ] {
+        if let Ok(header) = Header::from_bytes(name, value) {
+            response.add_header(header);
+        }
+    }
+    let _ = request.respond(response);
+}
+
+const JSON: &str = "application/json";
+
+enum Reply {
+    Json(serde_json::Value),
+    Calendar(String),
+}
+
+type Routed = std::result::Result<Reply, (u16, Error)>;
+
+fn route(app: &AppHandle, request: &mut Request) -> Routed {
+    let vault = vaults::primary(app).map_err(|err| (500, err))?;
+    let url = url::Url::parse(&format!("http://localhost{}", request.url()))
+        .map_err(|err| (400, Error::InvalidInput(err.to_string())))?;
+    let query = |key: &str| {
+        url.query_pairs()
+            .find(|(name, _)| name == key)
+            .map(|(_, value)| value.into_owned())
+    };
+    let segments: Vec<String> = url
+        .path_segments()
+        .map(|segments| {
+            segments
+                .filter(|s| !s.is_empty())
+                .map(str::to_owned)
+                .collect()
+        })
+        .unwrap_or_default();
+    let segments: Vec<&str> = segments.iter().map(String::as_str).collect();
+    let method = request.method().clone();
+    // Calendar apps subscribe by URL and cannot send a header; they get a
+    // token of their own that reads the feed only.
+    let calendar = method == Method::Get && segments == ["calendar.ics"];
+    let feed = match query("token").filter(|_| calendar) {
+        Some(given) => {
+            let expected = calendar_token(&vault.storage).map_err(|err| (500, err))?;
+            http::constant_time_eq(given.as_bytes(), expected.as_bytes())
+        }
+        None => false,
+    };
+    if !feed {
+        authorize(&vault.storage, request)?;
+    }
+    if calendar {
+        return calendar::ics(&vault)
+            .map(Reply::Calendar)
+            .map_err(|err| (status_of(&err), err));
+    }
+
+    let value = match (method, segments.as_slice()) {
+        (Method::Get, ["notes"]) => to_json(vault.storage.list_notes(query("folder").as_deref())),
+        (Method::Post, ["notes"]) => {
+            let note: NewNote = read_json(request)?;
+            to_json(vault.create_note(&note.title, &note.body, &note.folder))
+        }
+        (Method::Get, ["notes", id]) => to_json(protected::readable(&vault, id)),
+        (Method::Patch, ["notes", id]) => {
+            let patch: NotePatch = read_json(request)?;
+            to_json(vault.edit_note(id, patch))
+        }
+        (Method::Get, ["search"]) => {
+            let q = query("q").unwrap_or_default();
+            let limit = query("limit")
+                .and_then(|limit| limit.parse().ok())
+                .unwrap_or(DEFAULT_SEARCH_LIMIT);
+            to_json(vault.search_notes(&q, limit, false))
+        }
+        (Method::Post, ["clip"]) => {
+            let clip: Clip = read_json(request)?;
+            clip_note(&vault, clip).and_then(|note| to_json(Ok(note)))
+        }
+        _ => return Err((404, Error::InvalidInput("no such endpoint".into()))),
+    };
+    value.map(Reply::Json).map_err(|err| (status_of(&err), err))
+}
+
+/// Checks the token of the `Authorization` header.
+fn authorize(storage: &Storage, request: &Request) -> std::result::Result<(), (u16, Error)> {
+    let expected = token(storage).map_err(|err| (500, err))?;
+    let given = request
+        .headers()
+        .iter()
+        .find(|header| header.field.equiv("Authorization"))
+        .and_then(|header| header.value.as_str().strip_prefix("Bearer "))
+        .unwrap_or_default();
+    if http::constant_time_eq(given.as_bytes(), expected.as_bytes()) {
+        Ok(())
+    } else {
+        Err((
+            401,
+            Error::InvalidInput("missing or wrong access token".into()),
+        ))
+    }
+}
+
+fn clip_note(vault: &Vault, clip: Clip) -> Result<crate::storage::Note> {
+    let mut body = match (clip.html, clip.text) {
+        (Some(html), _) => htmd::HtmlToMarkdown::builder()
+            .skip_tags(vec!["head", "style", "script", "noscript"])
+            .build()
+            .convert(&html)?,
+        (None, Some(text)) => text,
+        (None, None) => return Err(Error::InvalidInput("nothing to clip".into())),
+    };
+    if let Some(url) = &clip.url {
+        body = format!("Clipped from <{url}>\n\n{body}");
+    }
+    let title = clip
+        .title
+        .filter(|title| !title.trim().is_empty())
+        .or(clip.url)
+        .unwrap_or_else(|| "Clipping".into());
+    vault.create_note(&title, &body, &clip.folder)
+}
+
+fn read_json<T: serde::de::DeserializeOwned>(
+    request: &mut Request,
+) -> std::result::Result<T, (u16, Error)> {
+    let body = http::read_body(request, MAX_BODY_BYTES)
+        .map_err(|err| (400, err.into()))?
+        .ok_or_else(|| {
+            let limit = MAX_BODY_BYTES / (1024 * 1024);
+            (
+                413,
+                Error::InvalidInput(format!("requests are limited to {limit} MB")),
+            )
+        })?;
+    serde_json::from_slice(&body).map_err(|err| (400, err.into()))
+}
+
+fn to_json<T: Serialize>(result: Result<T>) -> Result<serde_json::Value> {
+    Ok(serde_json::to_value(result?)?)
+}
+
+fn status_of(err: &Error) -> u16 {
+    match err {
+        Error::NoteNotFound(_) => 404,
+        Error::VaultLocked | Error::NoteLocked(_) => 423,
+        Error::InvalidInput(_) | Error::Json(_) => 400,
+        _ => 500,
+    }
+}
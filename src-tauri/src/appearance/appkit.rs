@@ -0,0 +1,13 @@
+//! The accent colour chosen in System Settings.
+
+use objc2_app_kit::{NSColor, NSColorSpace};
+
+pub fn accent() -> Option<String> {
+    let color =
+        NSColor::controlAccentColor().colorUsingColorSpace(&NSColorSpace::sRGBColorSpace())?;
+    Some(super::hex(
+        color.redComponent(),
+        color.greenComponent(),
+        color.blueComponent(),
+    ))
+}
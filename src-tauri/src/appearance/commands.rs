@@ -0,0 +1,85 @@
+use std::path::PathBuf;
+
+use serde_json::json;
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use super::{ResolvedTheme, ThemeFile, Themes, CHANGED_EVENT};
+use crate::error::Result;
+use crate::settings::{self, SettingsStore, Theme};
+use crate::vaults::Current;
+
+/// The theme the calling window should be drawn in.
+#[tauri::command]
+pub async fn get_theme(
+    themes: State<'_, Themes>,
+    settings: State<'_, SettingsStore>,
+    vault: Current,
+) -> Result<ResolvedTheme> {
+    let theme = super::override_of(&vault.storage)?;
+    Ok(super::resolve(&themes, &settings.get(), &theme))
+}
+
+#[tauri::command]
+pub async fn list_themes(themes: State<'_, Themes>) -> Result<Vec<ThemeFile>> {
+    themes.list()
+}
+
+/// Copies a CSS file in as a custom theme.
+#[tauri::command]
+pub async fn import_theme(themes: State<'_, Themes>, path: String) -> Result<ThemeFile> {
+    themes.import(&PathBuf::from(path))
+}
+
+#[tauri::command]
+pub async fn remove_theme(themes: State<'_, Themes>, id: String) -> Result<()> {
+    themes.remove(&id)
+}
+
+/// Switches to custom theme `id`, or back to the built-in look without
+/// one. With `vaultOnly`, only for the calling window's vault.
+#[tauri::command]
+pub async fn apply_theme(
+    app: AppHandle,
+    vault: Current,
+    id: Option<String>,
+    vault_only: Option<bool>,
+) -> Result<ResolvedTheme> {
+    let themes = app.state::<Themes>();
+    if let Some(id) = &id {
+        themes.get(id)?;
+    }
+    let store = app.state::<SettingsStore>();
+    let mut theme = super::override_of(&vault.storage)?;
+    match vault_only.unwrap_or(false) {
+        true => {
+            theme.custom_theme = id;
+            super::set_override(&vault.storage, &theme)?;
+        }
+        false => {
+            let settings = store.update(&json!({ "appearance": { "customTheme": id } }))?;
+            app.emit(settings::CHANGED_EVENT, &settings)?;
+        }
+    }
+    app.emit(CHANGED_EVENT, themes.os())?;
+    Ok(super::resolve(&themes, &store.get(), &theme))
+}
+
+/// Gives the calling window's vault a light or dark mode of its own, or
+/// with `null` has it follow the settings again.
+#[tauri::command]
+pub async fn set_vault_theme(
+    app: AppHandle,
+    vault: Current,
+    theme: Option<Theme>,
+) -> Result<ResolvedTheme> {
+    let mut vault_theme = super::override_of(&vault.storage)?;
+    vault_theme.theme = theme;
+    super::set_override(&vault.storage, &vault_theme)?;
+    let themes = app.state::<Themes>();
+    app.emit(CHANGED_EVENT, themes.os())?;
+    Ok(super::resolve(
+        &themes,
+        &app.state::<SettingsStore>().get(),
+        &vault_theme,
+    ))
+}
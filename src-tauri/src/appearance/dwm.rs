@@ -0,0 +1,28 @@
+//! The accent colour Windows paints title bars and the Start menu with.
+
+use windows::core::w;
+use windows::Win32::Foundation::ERROR_SUCCESS;
+use windows::Win32::System::Registry::{RegGetValueW, HKEY_CURRENT_USER, RRF_RT_REG_DWORD};
+
+pub fn accent() -> Option<String> {
+    let mut value = 0u32;
+    let mut size = std::mem::size_of::<u32>() as u32;
+    // SAFETY: `value` and `size` outlive the call and describe a DWORD.
+    let status = unsafe {
+        RegGetValueW(
+            HKEY_CURRENT_USER,
+            w!("Software\\Microsoft\\Windows\\DWM"),
+            w!("AccentColor"),
+            RRF_RT_REG_DWORD,
+            None,
+            Some(&mut value as *mut u32 as *mut _),
+            Some(&mut size),
+        )
+    };
+    if status != ERROR_SUCCESS {
+        return None;
+    }
+    // Stored as 0xAABBGGRR.
+    let [red, green, blue, _] = value.to_le_bytes();
+    Some(format!("#{red:02x}{green:02x}{blue:02x}"))
+}
@@ -0,0 +1,265 @@
+//! The theme a window is drawn in: the OS's light or dark mode and accent
+//! colour, the app-wide choice in the settings, a vault's own override,
+//! and custom CSS themes kept as files in `themes/` in the app's data
+//! folder.
+
+pub mod commands;
+
+#[cfg_attr(target_os = "linux", path = "portal.rs")]
+#[cfg_attr(windows, path = "dwm.rs")]
+#[cfg_attr(target_os = "macos", path = "appkit.rs")]
+#[cfg_attr(
+    not(any(target_os = "linux", windows, target_os = "macos")),
+    path = "unsupported.rs"
+)]
+mod accent;
+
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager, WebviewWindow, Window, WindowEvent};
+
+use crate::error::{Error, Result};
+use crate::files;
+use crate::settings::{Settings, Theme};
+use crate::storage::Storage;
+
+/// Event carrying the [`OsTheme`] whenever the OS switches between light
+/// and dark or its accent colour changes, and after a custom theme is
+/// applied, so windows work out their theme again.
+pub const CHANGED_EVENT: &str = "theme-changed";
+
+const OVERRIDE_KEY: &str = "appearance.override";
+const THEMES_DIR: &str = "themes";
+const THEME_EXTENSION: &str = "css";
+const MAX_THEME_BYTES: u64 = 1024 * 1024;
+
+/// What the OS asks for.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OsTheme {
+    pub dark: bool,
+    /// `#rrggbb`, where the platform has one to read.
+    pub accent: Option<String>,
+}
+
+/// A vault's own choice, over the app-wide one.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ThemeOverride {
+    pub theme: Option<Theme>,
+    pub custom_theme: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ThemeFile {
+    /// The file name without `.css`.
+    pub id: String,
+    /// Taken from a leading `/* ... */` comment, else the id.
+    pub name: String,
+}
+
+/// What a window should look like.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResolvedTheme {
+    pub dark: bool,
+    pub accent: Option<String>,
+    /// The custom theme in use and its CSS.
+    pub custom_theme: Option<ThemeFile>,
+    pub css: Option<String>,
+}
+
+/// The theme files and latest OS theme, managed as app state.
+pub struct Themes {
+    dir: PathBuf,
+    os: Mutex<OsTheme>,
+}
+
+impl Themes {
+    pub fn new(app_dir: &Path) -> Self {
+        Self {
+            dir: app_dir.join(THEMES_DIR),
+            os: Mutex::new(OsTheme::default()),
+        }
+    }
+
+    pub fn os(&self) -> OsTheme {
+        self.lock().clone()
+    }
+
+    /// Records what the OS asks for, saying whether that changed.
+    fn set_os(&self, os: OsTheme) -> bool {
+        let mut current = self.lock();
+        let changed = *current != os;
+        *current = os;
+        changed
+    }
+
+    pub fn list(&self) -> Result<Vec<ThemeFile>> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err.into()),
+        };
+        let mut themes = Vec::new();
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some(THEME_EXTENSION) {
+                continue;
+            }
+            let Some(id) = path.file_stem().and_then(|stem| stem.to_str()) else {
+                continue;
+            };
+            let css = fs::read_to_string(&path).unwrap_or_default();
+            themes.push(ThemeFile {
+                name: name_of(&css).unwrap_or_else(|| id.to_owned()),
+                id: id.to_owned(),
+            });
+        }
+        themes.sort_by_key(|theme| theme.name.to_lowercase());
+        Ok(themes)
+    }
+
+    /// Theme `id` and its CSS.
+    pub fn get(&self, id: &str) -> Result<(ThemeFile, String)> {
+        let theme = self
+            .list()?
+            .into_iter()
+            .find(|theme| theme.id == id)
+            .ok_or_else(|| Error::InvalidInput(format!("no theme {id}")))?;
+        let css = fs::read_to_string(self.path_of(id))?;
+        Ok((theme, css))
+    }
+
+    /// Copies the CSS file at `src` in as a theme.
+    pub fn import(&self, src: &Path) -> Result<ThemeFile> {
+        if fs::metadata(src)?.len() > MAX_THEME_BYTES {
+            return Err(Error::InvalidInput("the theme file is too large".into()));
+        }
+        let css = fs::read_to_string(src)?;
+        let stem = src
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let id = files::sanitize(&stem);
+        if id.is_empty() {
+            return Err(Error::InvalidInput("the theme needs a file name".into()));
+        }
+        fs::create_dir_all(&self.dir)?;
+        crate::atomic::write(&self.path_of(&id), css.as_bytes())?;
+        Ok(ThemeFile {
+            name: name_of(&css).unwrap_or_else(|| id.clone()),
+            id,
+        })
+    }
+
+    pub fn remove(&self, id: &str) -> Result<()> {
+        let (theme, _) = self.get(id)?;
+        fs::remove_file(self.path_of(&theme.id))?;
+        Ok(())
+    }
+
+    fn path_of(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{id}.{THEME_EXTENSION}"))
+    }
+
+    fn lock(&self) -> MutexGuard<'_, OsTheme> {
+        self.os.lock().expect("os theme poisoned")
+    }
+}
+
+/// The first line of a leading `/* ... */` comment.
+fn name_of(css: &str) -> Option<String> {
+    let comment = css.trim_start().strip_prefix("/*")?;
+    let comment = &comment[..comment.find("*/")?];
+    let name = comment
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty())?;
+    Some(name.trim_start_matches('*').trim().to_owned()).filter(|name| !name.is_empty())
+}
+
+pub fn override_of(storage: &Storage) -> Result<ThemeOverride> {
+    match storage.meta(OVERRIDE_KEY)? {
+        Some(json) => Ok(serde_json::from_str(&json)?),
+        None => Ok(ThemeOverride::default()),
+    }
+}
+
+pub fn set_override(storage: &Storage, theme: &ThemeOverride) -> Result<()> {
+    storage.set_meta(OVERRIDE_KEY, &serde_json::to_string(theme)?)
+}
+
+/// The theme of a window showing a vault with `theme` as its override. A
+/// custom theme that has gone missing is left out.
+pub fn resolve(themes: &Themes, settings: &Settings, theme: &ThemeOverride) -> ResolvedTheme {
+    let os = themes.os();
+    let dark = match theme.theme.unwrap_or(settings.appearance.theme) {
+        Theme::System => os.dark,
+        Theme::Light => false,
+        Theme::Dark => true,
+    };
+    let custom = theme
+        .custom_theme
+        .as_deref()
+        .or(settings.appearance.custom_theme.as_deref())
+        .and_then(|id| themes.get(id).ok());
+    let (custom_theme, css) = match custom {
+        Some((theme, css)) => (Some(theme), Some(css)),
+        None => (None, None),
+    };
+    ResolvedTheme {
+        dark,
+        accent: os.accent,
+        custom_theme,
+        css,
+    }
+}
+
+/// Records whether the OS is in dark mode, keeping the last known if
+/// `dark` is `None`, and reads the accent colour again. Every window is
+/// told if either changed.
+fn detect(app: &AppHandle, dark: Option<bool>) {
+    let app = app.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        let themes = app.state::<Themes>();
+        let dark = dark.unwrap_or_else(|| themes.os().dark);
+        let os = OsTheme {
+            dark,
+            accent: accent::accent(),
+        };
+        if themes.set_os(os.clone()) {
+            let _ = app.emit(CHANGED_EVENT, os);
+        }
+    });
+}
+
+/// Picks up the theme of the first window, once it is up.
+pub fn start(window: &WebviewWindow) {
+    let dark = window.theme().ok().map(|theme| theme == tauri::Theme::Dark);
+    detect(window.app_handle(), dark);
+}
+
+/// Registered for every window. The OS reports light and dark switches;
+/// the accent colour has no event everywhere, so it is read again
+/// whenever a window comes to the front.
+pub fn window_event(window: &Window, event: &WindowEvent) {
+    match event {
+        WindowEvent::ThemeChanged(theme) => {
+            detect(window.app_handle(), Some(*theme == tauri::Theme::Dark));
+        }
+        WindowEvent::Focused(true) => detect(window.app_handle(), None),
+        _ => {}
+    }
+}
+
+#[cfg_attr(not(any(target_os = "linux", target_os = "macos")), allow(dead_code))]
+fn hex(red: f64, green: f64, blue: f64) -> String {
+    let byte = |c: f64| (c.clamp(0.0, 1.0) * 255.0).round() as u8;
+    format!("#{:02x}{:02x}{:02x}", byte(red), byte(green), byte(blue))
+}
@@ -0,0 +1,25 @@
+//! The accent colour from the desktop portal, which GNOME and KDE both
+//! fill in from their own settings.
+
+use zbus::blocking::Connection;
+use zbus::zvariant::OwnedValue;
+
+pub fn accent() -> Option<String> {
+    let connection = Connection::session().ok()?;
+    let reply = connection
+        .call_method(
+            Some("org.freedesktop.portal.Desktop"),
+            "/org/freedesktop/portal/desktop",
+            Some("org.freedesktop.portal.Settings"),
+            "ReadOne",
+            &("org.freedesktop.appearance", "accent-color"),
+        )
+        .ok()?;
+    let value: OwnedValue = reply.body().deserialize().ok()?;
+    let (red, green, blue) = <(f64, f64, f64)>::try_from(value).ok()?;
+    // Out of range means the user picked none.
+    [red, green, blue]
+        .iter()
+        .all(|c| (0.0..=1.0).contains(c))
+        .then(|| super::hex(red, green, blue))
+}
@@ -0,0 +1,5 @@
+//! Platforms whose accent colour this app cannot read.
+
+pub fn accent() -> Option<String> {
+    None
+}
@@ -0,0 +1,32 @@
+use std::path::Path;
+
+use super::ArchiveReport;
+use crate::error::Result;
+use crate::vaults::Current;
+
+/// Bundles the vault's notes and attachments into a zip at `dest`,
+/// encrypted when a password is given.
+#[tauri::command]
+pub async fn export_archive(
+    vault: Current,
+    dest: String,
+    password: Option<String>,
+) -> Result<ArchiveReport> {
+    tauri::async_runtime::spawn_blocking(move || {
+        super::export(&vault, Path::new(&dest), password.as_deref())
+    })
+    .await?
+}
+
+/// Imports an archive from [`export_archive`] after verifying its checksums.
+#[tauri::command]
+pub async fn import_archive(
+    vault: Current,
+    path: String,
+    password: Option<String>,
+) -> Result<ArchiveReport> {
+    tauri::async_runtime::spawn_blocking(move || {
+        super::import(&vault, Path::new(&path), password.as_deref())
+    })
+    .await?
+}
@@ -0,0 +1,240 @@
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use zip::result::ZipError;
+use zip::write::SimpleFileOptions;
+use zip::{AesMode, ZipArchive, ZipWriter};
+
+use crate::error::{Error, Result};
+use crate::files::ATTACHMENTS_DIR;
+use crate::storage::{now_millis, Note, NotePatch};
+use crate::vault::Vault;
+
+pub mod commands;
+
+const MANIFEST_ENTRY: &str = "manifest.json";
+const NOTES_ENTRY: &str = "notes.json";
+/// Bumped when the layout changes in a way older versions cannot read.
+const FORMAT_VERSION: u32 = 1;
+
+/// Lists every other entry of an archive with its SHA-256, so a damaged or
+/// tampered archive is refused before anything is imported.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Manifest {
+    version: u32,
+    created_at: i64,
+    /// Entry name to hex digest.
+    checksums: BTreeMap<String, String>,
+}
+
+/// A note as written to `notes.json`.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ArchivedNote {
+    id: String,
+    title: String,
+    body: String,
+    folder: String,
+    created_at: i64,
+    updated_at: i64,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ArchiveReport {
+    pub notes: usize,
+    pub attachments: usize,
+    /// Notes left alone because the vault's copy is as new or newer.
+    pub skipped: usize,
+}
+
+/// Writes every note and attachment to a zip at `dest` for moving a vault
+/// by hand. With a password, each entry is encrypted with AES-256.
+pub fn export(vault: &Vault, dest: &Path, password: Option<&str>) -> Result<ArchiveReport> {
+    if vault.storage.is_locked() {
+        return Err(Error::VaultLocked);
+    }
+    let options = match password.filter(|password| !password.is_empty()) {
+        Some(password) => {
+            SimpleFileOptions::default().with_aes_encryption(AesMode::Aes256, password)
+        }
+        None => SimpleFileOptions::default(),
+    };
+    let notes: Vec<ArchivedNote> = vault
+        .storage
+        .all_notes()?
+        .into_iter()
+        .map(|note| ArchivedNote {
+            id: note.id,
+            title: note.title,
+            body: note.body,
+            folder: note.folder,
+            created_at: note.created_at,
+            updated_at: note.updated_at,
+        })
+        .collect();
+
+    let mut manifest = Manifest {
+        version: FORMAT_VERSION,
+        created_at: now_millis(),
+        checksums: BTreeMap::new(),
+    };
+    let partial = dest.with_extension("partial");
+    let result = (|| -> Result<ArchiveReport> {
+        let mut zip = ZipWriter::new(File::create(&partial)?);
+        let json = serde_json::to_vec(&notes)?;
+        manifest
+            .checksums
+            .insert(NOTES_ENTRY.to_owned(), checksum(&json));
+        zip.start_file(NOTES_ENTRY, options)?;
+        zip.write_all(&json)?;
+
+        let mut attachments = 0;
+        let dir = vault.files.attachments_dir();
+        if dir.is_dir() {
+            for entry in fs::read_dir(&dir)? {
+                let entry = entry?;
+                if !entry.file_type()?.is_file() {
+                    continue;
+                }
+                let name = format!("{ATTACHMENTS_DIR}/{}", entry.file_name().to_string_lossy());
+                let content = fs::read(entry.path())?;
+                manifest.checksums.insert(name.clone(), checksum(&content));
+                zip.start_file(name, options)?;
+                zip.write_all(&content)?;
+                attachments += 1;
+            }
+        }
+
+        zip.start_file(MANIFEST_ENTRY, options)?;
+        zip.write_all(&serde_json::to_vec_pretty(&manifest)?)?;
+        zip.finish()?.sync_all()?;
+        Ok(ArchiveReport {
+            notes: notes.len(),
+            attachments,
+            skipped: 0,
+        })
+    })();
+    match result {
+        Ok(report) => {
+            fs::rename(&partial, dest)?;
+            Ok(report)
+        }
+        Err(err) => {
+            let _ = fs::remove_file(&partial);
+            Err(err)
+        }
+    }
+}
+
+/// Brings the notes and attachments of an archive written by [`export`]
+/// into the vault. Every checksum is verified first. Notes the vault lacks
+/// are added, and ones it has are replaced only if the archived copy was
+/// saved later.
+pub fn import(vault: &Vault, path: &Path, password: Option<&str>) -> Result<ArchiveReport> {
+    if vault.storage.is_locked() {
+        return Err(Error::VaultLocked);
+    }
+    let mut zip = ZipArchive::new(File::open(path)?)?;
+    let password = password.filter(|password| !password.is_empty());
+    let manifest: Manifest = serde_json::from_slice(&read(&mut zip, MANIFEST_ENTRY, password)?)
+        .map_err(|err| Error::InvalidInput(format!("not a notes archive: {err}")))?;
+    if manifest.version > FORMAT_VERSION {
+        return Err(Error::InvalidInput(
+            "the archive was written by a newer version of the app".into(),
+        ));
+    }
+
+    let mut entries = BTreeMap::new();
+    for (name, expected) in &manifest.checksums {
+        let content = read(&mut zip, name, password)?;
+        if checksum(&content) != *expected {
+            return Err(Error::InvalidInput(format!(
+                "the archive is damaged: {name} does not match its checksum"
+            )));
+        }
+        entries.insert(name.as_str(), content);
+    }
+    let notes: Vec<ArchivedNote> = match entries.get(NOTES_ENTRY) {
+        Some(json) => serde_json::from_slice(json)?,
+        None => return Err(Error::InvalidInput("the archive has no notes".into())),
+    };
+
+    let mut report = ArchiveReport::default();
+    let target = vault.files.attachments_dir();
+    for (name, content) in &entries {
+        let Some(file) = name.strip_prefix(&format!("{ATTACHMENTS_DIR}/")) else {
+            continue;
+        };
+        // Names come from the archive, so only plain file names are taken.
+        if file.is_empty() || file.contains(['/', '\\']) || file.starts_with('.') {
+            continue;
+        }
+        let dest = target.join(file);
+        if !dest.exists() {
+            fs::create_dir_all(&target)?;
+            fs::write(dest, content)?;
+            report.attachments += 1;
+        }
+    }
+
+    for note in notes {
+        if !vault.storage.has_note(&note.id)? {
+            vault.import_note(&Note {
+                id: note.id,
+                title: note.title,
+                body: note.body,
+                folder: note.folder,
+                created_at: note.created_at,
+                updated_at: note.updated_at,
+            })?;
+            report.notes += 1;
+            continue;
+        }
+        if vault.storage.get_note(&note.id)?.updated_at >= note.updated_at {
+            report.skipped += 1;
+            continue;
+        }
+        let patch = NotePatch {
+            title: Some(note.title),
+            body: Some(note.body),
+            folder: Some(note.folder),
+        };
+        vault.update_note(&note.id, patch)?;
+        report.notes += 1;
+    }
+
+    if report.notes > 0 {
+        vault.autocommit("Import archive")?;
+    }
+    Ok(report)
+}
+
+fn read(zip: &mut ZipArchive<File>, name: &str, password: Option<&str>) -> Result<Vec<u8>> {
+    let file = match password {
+        Some(password) => zip.by_name_decrypt(name, password.as_bytes()),
+        None => zip.by_name(name),
+    };
+    let mut file = file.map_err(|err| match err {
+        ZipError::InvalidPassword => Error::WrongPassword,
+        ZipError::UnsupportedArchive(ZipError::PASSWORD_REQUIRED) => {
+            Error::InvalidInput("the archive is encrypted; enter its password".into())
+        }
+        ZipError::FileNotFound => {
+            Error::InvalidInput(format!("the archive is damaged: {name} is missing"))
+        }
+        err => err.into(),
+    })?;
+    let mut content = Vec::new();
+    file.read_to_end(&mut content)?;
+    Ok(content)
+}
+
+fn checksum(content: &[u8]) -> String {
+    hex::encode(Sha256::digest(content))
+}
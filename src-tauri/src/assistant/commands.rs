@@ -0,0 +1,83 @@
+use serde::Serialize;
+use tauri::{AppHandle, Manager, Window};
+
+use super::{Answer, AssistantConfig, Backend};
+use crate::error::Result;
+use crate::vaults::{Current, Vaults};
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AssistantSettings {
+    #[serde(flatten)]
+    pub config: AssistantConfig,
+    /// Whether this build can run GGUF models itself.
+    pub local_available: bool,
+}
+
+/// Summarises note `id`. The summary also arrives piece by piece as
+/// `assistant-token` events tagged with `stream`.
+#[tauri::command]
+pub async fn summarize_note(
+    app: AppHandle,
+    vault: Current,
+    id: String,
+    stream: String,
+) -> Result<String> {
+    super::summarize(&app, &vault, &id, &stream).await
+}
+
+/// Answers `question` from the notes of the calling window's vault,
+/// streaming like [`summarize_note`].
+#[tauri::command]
+pub async fn ask_vault(
+    app: AppHandle,
+    window: Window,
+    question: String,
+    stream: String,
+) -> Result<Answer> {
+    let vault = app.state::<Vaults>().of_window(&app, window.label())?;
+    super::ask(&app, vault, &question, &stream).await
+}
+
+/// Changes the assistant settings given. Without arguments, returns them.
+/// An empty `apiKey` forgets the stored one.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn configure_assistant(
+    vault: Current,
+    backend: Option<Backend>,
+    endpoint: Option<String>,
+    model: Option<String>,
+    model_path: Option<String>,
+    context_notes: Option<usize>,
+    max_tokens: Option<u32>,
+    api_key: Option<String>,
+) -> Result<AssistantSettings> {
+    let mut config = super::config_of(&vault.storage)?;
+    if let Some(backend) = backend {
+        config.backend = backend;
+    }
+    if let Some(endpoint) = endpoint {
+        config.endpoint = endpoint.trim().to_owned();
+    }
+    if let Some(model) = model {
+        config.model = model;
+    }
+    if let Some(model_path) = model_path {
+        config.model_path = model_path;
+    }
+    if let Some(context_notes) = context_notes {
+        config.context_notes = context_notes;
+    }
+    if let Some(max_tokens) = max_tokens {
+        config.max_tokens = max_tokens;
+    }
+    super::set_config(&vault.storage, &config)?;
+    if let Some(api_key) = api_key {
+        config = super::set_api_key(&vault.storage, &api_key)?;
+    }
+    Ok(AssistantSettings {
+        config,
+        local_available: super::LOCAL_AVAILABLE,
+    })
+}
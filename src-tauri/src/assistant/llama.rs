@@ -0,0 +1,155 @@
+//! GGUF models run in process with llama.cpp.
+
+use std::fmt::Display;
+use std::num::NonZeroU32;
+use std::path::Path;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread;
+
+use llama_cpp_2::context::params::LlamaContextParams;
+use llama_cpp_2::llama_backend::LlamaBackend;
+use llama_cpp_2::llama_batch::LlamaBatch;
+use llama_cpp_2::model::params::LlamaModelParams;
+use llama_cpp_2::model::{LlamaChatMessage, LlamaChatTemplate, LlamaModel};
+use llama_cpp_2::sampling::LlamaSampler;
+
+use super::{AssistantConfig, Message};
+use crate::error::{Error, Result};
+use crate::jobs::JobContext;
+use crate::storage::now_millis;
+
+pub const AVAILABLE: bool = true;
+
+/// llama.cpp can only be set up once per process.
+static BACKEND: OnceLock<std::result::Result<LlamaBackend, String>> = OnceLock::new();
+/// The last model used and its path, kept loaded as reading a few
+/// gigabytes takes a while.
+static LOADED: Mutex<Option<(String, Arc<LlamaModel>)>> = Mutex::new(None);
+
+/// The reply to `messages` from the model at `config.model_path`, handing
+/// each piece to `on_token` as it is generated.
+pub fn generate(
+    config: &AssistantConfig,
+    messages: &[Message],
+    job: &JobContext,
+    on_token: impl Fn(&str),
+) -> Result<String> {
+    let backend = backend()?;
+    let model = model(backend, &config.model_path)?;
+    // Models without a template of their own mostly understand ChatML.
+    let template = match model.chat_template(None) {
+        Ok(template) => template,
+        Err(_) => LlamaChatTemplate::new("chatml").map_err(llama_error)?,
+    };
+    let chat = messages
+        .iter()
+        .map(|message| LlamaChatMessage::new(message.role.into(), message.content.clone()))
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(llama_error)?;
+    let prompt = model
+        .apply_chat_template(&template, &chat, true)
+        .map_err(llama_error)?;
+    let vocab = model.vocab();
+    let tokens = vocab.tokenize(prompt.as_bytes(), true, true);
+
+    let max_tokens = config.max_tokens.max(1);
+    let n_ctx = (tokens.len() as u32 + max_tokens).min(model.n_ctx_train());
+    if tokens.is_empty() || tokens.len() as u32 >= n_ctx {
+        return Err(Error::Assistant(
+            "the notes are too long for this model".into(),
+        ));
+    }
+    let params = LlamaContextParams::default()
+        .with_n_ctx(NonZeroU32::new(n_ctx))
+        .with_n_batch(n_ctx)
+        .with_n_threads(threads())
+        .with_n_threads_batch(threads());
+    let mut context = model.new_context(backend, params).map_err(llama_error)?;
+    let mut batch = LlamaBatch::new(n_ctx as usize, 1);
+    let last = tokens.len() - 1;
+    for (i, &token) in tokens.iter().enumerate() {
+        batch
+            .add(token, i as i32, &[0], i == last)
+            .map_err(llama_error)?;
+    }
+    context.decode(&mut batch).map_err(llama_error)?;
+
+    let mut sampler = LlamaSampler::chain_simple([
+        LlamaSampler::top_k(40),
+        LlamaSampler::top_p(0.95, 1),
+        LlamaSampler::min_p(0.05, 1),
+        LlamaSampler::temp(0.7),
+        LlamaSampler::dist(now_millis() as u32),
+    ]);
+    let mut text = String::new();
+    // Pieces can end inside a character, which waits for the next one.
+    let mut pending = Vec::new();
+    let mut position = tokens.len() as i32;
+    let limit = (n_ctx - tokens.len() as u32) as usize;
+    for generated in 0..limit {
+        job.check()?;
+        job.progress(generated, limit);
+        let token = sampler.sample(&context, batch.n_tokens() - 1);
+        if vocab.is_eog(token) {
+            break;
+        }
+        pending.extend(vocab.token_to_piece(token, false, None));
+        let valid = match std::str::from_utf8(&pending) {
+            Ok(piece) => piece.len(),
+            Err(err) => err.valid_up_to(),
+        };
+        if valid > 0 {
+            let piece = String::from_utf8_lossy(&pending[..valid]).into_owned();
+            pending.drain(..valid);
+            on_token(&piece);
+            text.push_str(&piece);
+        }
+        batch.clear();
+        batch
+            .add(token, position, &[0], true)
+            .map_err(llama_error)?;
+        position += 1;
+        context.decode(&mut batch).map_err(llama_error)?;
+    }
+    Ok(text)
+}
+
+fn backend() -> Result<&'static LlamaBackend> {
+    BACKEND
+        .get_or_init(|| {
+            let mut backend = LlamaBackend::init().map_err(|err| err.to_string())?;
+            backend.void_logs();
+            Ok(backend)
+        })
+        .as_ref()
+        .map_err(|err| Error::Assistant(err.clone()))
+}
+
+fn model(backend: &LlamaBackend, path: &str) -> Result<Arc<LlamaModel>> {
+    if !Path::new(path).is_file() {
+        return Err(Error::Assistant(format!("no model at {path}")));
+    }
+    let mut loaded = LOADED.lock().expect("llama model poisoned");
+    if let Some((loaded_path, model)) = loaded.as_ref() {
+        if loaded_path == path {
+            return Ok(Arc::clone(model));
+        }
+    }
+    // The old model goes first, so two never fill memory at once.
+    *loaded = None;
+    let model = LlamaModel::load_from_file(backend, path, &LlamaModelParams::default())
+        .map_err(llama_error)?;
+    let model = Arc::new(model);
+    *loaded = Some((path.to_owned(), Arc::clone(&model)));
+    Ok(model)
+}
+
+/// Leaves a core or two to the UI.
+fn threads() -> i32 {
+    let cores = thread::available_parallelism().map_or(4, |n| n.get());
+    cores.saturating_sub(1).clamp(1, 16) as i32
+}
+
+fn llama_error(err: impl Display) -> Error {
+    Error::Assistant(err.to_string())
+}
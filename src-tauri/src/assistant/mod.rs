@@ -0,0 +1,296 @@
+pub mod commands;
+mod remote;
+
+#[cfg_attr(feature = "llm", path = "llama.rs")]
+#[cfg_attr(not(feature = "llm"), path = "unsupported.rs")]
+mod local;
+
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::error::{Error, Result};
+use crate::jobs::{JobKind, Jobs, Priority};
+use crate::metadata::FrontMatter;
+use crate::storage::Storage;
+use crate::vault::Vault;
+use crate::{protected, security, trash};
+
+/// Event carrying a [`Token`] as each piece of an answer is generated.
+pub const TOKEN_EVENT: &str = "assistant-token";
+/// Whether this build can run GGUF models itself.
+pub const LOCAL_AVAILABLE: bool = local::AVAILABLE;
+
+const CONFIG_KEY: &str = "assistant.config";
+/// How much of a note goes to the model to be summarised.
+const MAX_NOTE_CHARS: usize = 12_000;
+/// How much of each retrieved note goes into the context of a question.
+const MAX_EXCERPT_CHARS: usize = 2_000;
+
+const SUMMARY_PROMPT: &str = "You summarise notes. Reply with a short summary of the note \
+     the user sends, in the note's language, as a few sentences or bullet points. Do not add \
+     anything the note does not say.";
+const ANSWER_PROMPT: &str = "You answer questions using only the notes below. Cite the notes \
+     you rely on by their number in square brackets, like [2]. If the notes do not contain the \
+     answer, say so instead of guessing.";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Backend {
+    /// A server speaking the OpenAI chat completions API, such as a local
+    /// Ollama or llama.cpp server.
+    #[default]
+    Remote,
+    /// A GGUF model run in this process.
+    Local,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct AssistantConfig {
+    pub backend: Backend,
+    /// Base URL of the API, up to and including `/v1`.
+    pub endpoint: String,
+    /// Model name sent to the endpoint.
+    pub model: String,
+    /// The GGUF file for [`Backend::Local`].
+    pub model_path: String,
+    /// Notes retrieved to answer a question.
+    pub context_notes: usize,
+    pub max_tokens: u32,
+    /// Names the API key in the keychain, once one has been set.
+    pub key_id: Option<String>,
+}
+
+impl Default for AssistantConfig {
+    fn default() -> Self {
+        Self {
+            backend: Backend::Remote,
+            endpoint: "http://localhost:11434/v1".into(),
+            model: String::new(),
+            model_path: String::new(),
+            context_notes: 6,
+            max_tokens: 1024,
+            key_id: None,
+        }
+    }
+}
+
+/// A piece of generated text for the answer tagged `stream`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Token {
+    pub stream: String,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Source {
+    pub id: String,
+    pub title: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Answer {
+    pub text: String,
+    /// The notes given to the model, numbered as it cites them from 1.
+    pub sources: Vec<Source>,
+}
+
+/// One turn of a chat, as both backends take it.
+#[derive(Debug, Clone, Serialize)]
+pub struct Message {
+    pub role: &'static str,
+    pub content: String,
+}
+
+impl Message {
+    fn system(content: &str) -> Self {
+        Self {
+            role: "system",
+            content: content.to_owned(),
+        }
+    }
+
+    fn user(content: String) -> Self {
+        Self {
+            role: "user",
+            content,
+        }
+    }
+}
+
+pub fn config_of(storage: &Storage) -> Result<AssistantConfig> {
+    match storage.meta(CONFIG_KEY)? {
+        Some(json) => Ok(serde_json::from_str(&json)?),
+        None => Ok(AssistantConfig::default()),
+    }
+}
+
+pub fn set_config(storage: &Storage, config: &AssistantConfig) -> Result<()> {
+    storage.set_meta(CONFIG_KEY, &serde_json::to_string(config)?)
+}
+
+/// Keeps the API key of the endpoint in the keychain, or forgets it when
+/// `key` is empty.
+pub fn set_api_key(storage: &Storage, key: &str) -> Result<AssistantConfig> {
+    let mut config = config_of(storage)?;
+    match (key.trim(), config.key_id.take()) {
+        ("", Some(id)) => security::delete_secret(&keychain_account(&id))?,
+        ("", None) => {}
+        (key, id) => {
+            let id = id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+            security::store_secret(&keychain_account(&id), key)?;
+            config.key_id = Some(id);
+        }
+    }
+    set_config(storage, &config)?;
+    Ok(config)
+}
+
+/// A summary of note `id`, streamed as it is written.
+pub async fn summarize(app: &AppHandle, vault: &Vault, id: &str, stream: &str) -> Result<String> {
+    let note = vault.storage.get_note(id)?;
+    if protected::is_protected(&note.body) {
+        return Err(Error::InvalidInput(
+            "protected notes are not sent to the assistant".into(),
+        ));
+    }
+    let (_, markdown) = FrontMatter::split(&note.body);
+    let prompt = format!(
+        "# {}\n\n{}",
+        note.title,
+        truncate(markdown.trim(), MAX_NOTE_CHARS)
+    );
+    let messages = vec![Message::system(SUMMARY_PROMPT), Message::user(prompt)];
+    generate(app, vault, messages, stream).await
+}
+
+/// Answers `question` from the notes that best match it, found with
+/// semantic search where its model is set up and full-text search
+/// otherwise.
+pub async fn ask(
+    app: &AppHandle,
+    vault: Arc<Vault>,
+    question: &str,
+    stream: &str,
+) -> Result<Answer> {
+    let (retrieving, query) = (Arc::clone(&vault), question.to_owned());
+    let context =
+        tauri::async_runtime::spawn_blocking(move || retrieve(&retrieving, &query)).await??;
+    let mut prompt = String::from(ANSWER_PROMPT);
+    for (i, (source, excerpt)) in context.iter().enumerate() {
+        prompt.push_str(&format!("\n\n[{}] {}\n{excerpt}", i + 1, source.title));
+    }
+    if context.is_empty() {
+        prompt.push_str("\n\nThere are no notes about this.");
+    }
+    let messages = vec![Message::system(&prompt), Message::user(question.to_owned())];
+    let text = generate(app, &vault, messages, stream).await?;
+    Ok(Answer {
+        text,
+        sources: context.into_iter().map(|(source, _)| source).collect(),
+    })
+}
+
+/// Runs the configured backend, emitting each piece as a [`Token`] for
+/// `stream`. Local runs go through the job queue, so they can be
+/// cancelled like any other job.
+async fn generate(
+    app: &AppHandle,
+    vault: &Vault,
+    messages: Vec<Message>,
+    stream: &str,
+) -> Result<String> {
+    let config = config_of(&vault.storage)?;
+    let handle = app.clone();
+    let stream = stream.to_owned();
+    let emit = move |text: &str| {
+        let _ = handle.emit(
+            TOKEN_EVENT,
+            Token {
+                stream: stream.clone(),
+                text: text.to_owned(),
+            },
+        );
+    };
+    match config.backend {
+        Backend::Remote => {
+            let key = match &config.key_id {
+                Some(id) => security::load_secret(&keychain_account(id))?,
+                None => None,
+            };
+            remote::generate(&config, key.as_deref().map(String::as_str), &messages, emit).await
+        }
+        Backend::Local => {
+            if config.model_path.trim().is_empty() {
+                return Err(Error::Assistant("no GGUF model has been chosen".into()));
+            }
+            app.state::<Jobs>()
+                .run(JobKind::Assistant, Priority::User, move |job| {
+                    local::generate(&config, &messages, job, emit)
+                })
+                .await
+        }
+    }
+}
+
+/// The notes that best match `question`, with the part of each the model
+/// gets to see.
+fn retrieve(vault: &Vault, question: &str) -> Result<Vec<(Source, String)>> {
+    let k = config_of(&vault.storage)?.context_notes.max(1);
+    // Extra hits make up for the ones skipped below.
+    let semantic = match vault.semantic.status(&vault.storage)? {
+        status if status.available && status.model_ready => vault
+            .semantic
+            .search(&vault.storage, question, k * 2)
+            .ok()
+            .map(|hits| hits.into_iter().map(|hit| hit.id).collect::<Vec<_>>()),
+        _ => None,
+    };
+    let ids = match semantic {
+        Some(ids) if !ids.is_empty() => ids,
+        _ => vault
+            .search_notes(question, k * 2, false)?
+            .into_iter()
+            .map(|hit| hit.id)
+            .collect(),
+    };
+    let mut context = Vec::new();
+    for id in ids {
+        if context.len() == k {
+            break;
+        }
+        if !vault.storage.has_note(&id)? || trash::is_trashed(&vault.storage, &id)? {
+            continue;
+        }
+        let note = vault.storage.get_note(&id)?;
+        if protected::is_protected(&note.body) {
+            continue;
+        }
+        let (_, markdown) = FrontMatter::split(&note.body);
+        let excerpt = truncate(markdown.trim(), MAX_EXCERPT_CHARS);
+        context.push((
+            Source {
+                id: note.id,
+                title: note.title,
+            },
+            excerpt,
+        ));
+    }
+    Ok(context)
+}
+
+fn truncate(text: &str, max_chars: usize) -> String {
+    match text.char_indices().nth(max_chars) {
+        Some((end, _)) => format!("{}…", &text[..end]),
+        None => text.to_owned(),
+    }
+}
+
+fn keychain_account(key_id: &str) -> String {
+    format!("assistant/{key_id}")
+}
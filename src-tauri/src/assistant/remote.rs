@@ -0,0 +1,97 @@
+//! Any server with the OpenAI chat completions API, read as a stream of
+//! server-sent events.
+
+use std::time::Duration;
+
+use reqwest::header::CONTENT_TYPE;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+
+use super::{AssistantConfig, Message};
+use crate::error::{Error, Result};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(15);
+
+#[derive(Serialize)]
+struct Request<'a> {
+    model: &'a str,
+    messages: &'a [Message],
+    max_tokens: u32,
+    stream: bool,
+}
+
+#[derive(Deserialize)]
+struct Chunk {
+    #[serde(default)]
+    choices: Vec<Choice>,
+}
+
+#[derive(Deserialize)]
+struct Choice {
+    #[serde(default)]
+    delta: Delta,
+}
+
+#[derive(Default, Deserialize)]
+struct Delta {
+    content: Option<String>,
+}
+
+/// The reply to `messages`, handing each piece to `on_token` as it comes.
+pub async fn generate(
+    config: &AssistantConfig,
+    api_key: Option<&str>,
+    messages: &[Message],
+    on_token: impl Fn(&str),
+) -> Result<String> {
+    if config.model.trim().is_empty() {
+        return Err(Error::Assistant("no model has been chosen".into()));
+    }
+    let body = serde_json::to_vec(&Request {
+        model: config.model.trim(),
+        messages,
+        max_tokens: config.max_tokens,
+        stream: true,
+    })?;
+    let url = format!("{}/chat/completions", config.endpoint.trim_end_matches('/'));
+    // No overall timeout: a long answer from a slow model can take minutes.
+    let client = Client::builder().connect_timeout(CONNECT_TIMEOUT).build()?;
+    let mut request = client
+        .post(url)
+        .header(CONTENT_TYPE, "application/json")
+        .body(body);
+    if let Some(key) = api_key {
+        request = request.bearer_auth(key);
+    }
+    let mut response = request.send().await?;
+    if !response.status().is_success() {
+        let status = response.status();
+        let detail = response.text().await.unwrap_or_default();
+        return Err(Error::Assistant(format!("{status}: {}", detail.trim())));
+    }
+
+    let mut text = String::new();
+    let mut pending = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        pending.extend_from_slice(&chunk);
+        // Events are complete lines; the rest waits for the next chunk.
+        while let Some(end) = pending.iter().position(|&byte| byte == b'\n') {
+            let line: Vec<u8> = pending.drain(..=end).collect();
+            let line = String::from_utf8_lossy(&line);
+            let Some(data) = line.trim().strip_prefix("data:") else {
+                continue;
+            };
+            let data = data.trim();
+            if data == "[DONE]" {
+                return Ok(text);
+            }
+            let chunk: Chunk = serde_json::from_str(data)
+                .map_err(|err| Error::Assistant(format!("unexpected reply: {err}")))?;
+            for piece in chunk.choices.into_iter().filter_map(|c| c.delta.content) {
+                on_token(&piece);
+                text.push_str(&piece);
+            }
+        }
+    }
+    Ok(text)
+}
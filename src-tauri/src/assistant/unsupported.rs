@@ -0,0 +1,18 @@
+//! Builds without the `llm` feature only talk to a server.
+
+use super::{AssistantConfig, Message};
+use crate::error::{Error, Result};
+use crate::jobs::JobContext;
+
+pub const AVAILABLE: bool = false;
+
+pub fn generate(
+    _config: &AssistantConfig,
+    _messages: &[Message],
+    _job: &JobContext,
+    _on_token: impl Fn(&str),
+) -> Result<String> {
+    Err(Error::Assistant(
+        "this build cannot run local models; use an endpoint instead".into(),
+    ))
+}
@@ -0,0 +1,97 @@
+use std::fs;
+use std::path::Path;
+
+use super::optimize::{self, OptimizeConfig, OptimizeReport, Target};
+use super::{Attachment, GcReport};
+use crate::error::{Error, Result};
+use crate::vaults::Current;
+
+/// Adds a file to the attachment store for a note, either from `path` on
+/// disk or from `bytes` with a file `name` for the extension. The returned
+/// `link` is what to put in the note.
+#[tauri::command]
+pub async fn import_attachment(
+    vault: Current,
+    note_id: String,
+    path: Option<String>,
+    bytes: Option<Vec<u8>>,
+    name: Option<String>,
+) -> Result<Attachment> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let (name, content) = match (path, bytes) {
+            (Some(path), None) => {
+                let content = fs::read(&path)?;
+                let name = Path::new(&path)
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+                (name, content)
+            }
+            (None, Some(bytes)) => (name.unwrap_or_default(), bytes),
+            _ => {
+                return Err(Error::InvalidInput(
+                    "pass either a path or bytes to import".into(),
+                ))
+            }
+        };
+        super::import(&vault, &note_id, &name, &content)
+    })
+    .await?
+}
+
+/// Absolute path of the thumbnail of an attachment, or `null` if it is not
+/// an image.
+#[tauri::command]
+pub async fn get_thumbnail(vault: Current, path: String) -> Result<Option<String>> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let thumbnail = vault.thumbnails.get(&vault.files, &path)?;
+        Ok(thumbnail.map(|path| path.to_string_lossy().into_owned()))
+    })
+    .await?
+}
+
+#[tauri::command]
+pub async fn gc_orphaned_attachments(vault: Current) -> Result<GcReport> {
+    super::gc_orphaned(&vault)
+}
+
+/// Optimises the image attachments in use as [`super::optimize`] describes,
+/// with the vault's settings, and reports the space saved.
+#[tauri::command]
+pub async fn optimize_attachments(vault: Current) -> Result<OptimizeReport> {
+    tauri::async_runtime::spawn_blocking(move || optimize::optimize_all(&vault)).await?
+}
+
+/// Changes the settings for optimising images. Without arguments, returns
+/// them.
+#[tauri::command]
+pub async fn configure_attachment_optimization(
+    vault: Current,
+    on_import: Option<bool>,
+    format: Option<Target>,
+    max_dimension: Option<u32>,
+    strip_metadata: Option<bool>,
+    min_bytes: Option<u64>,
+) -> Result<OptimizeConfig> {
+    let mut config = optimize::config_of(&vault.storage)?;
+    if let Some(on_import) = on_import {
+        config.on_import = on_import;
+    }
+    if let Some(format) = format {
+        config.format = format;
+    }
+    if let Some(max_dimension) = max_dimension {
+        if max_dimension == 0 {
+            return Err(Error::InvalidInput("maxDimension must be positive".into()));
+        }
+        config.max_dimension = max_dimension;
+    }
+    if let Some(strip_metadata) = strip_metadata {
+        config.strip_metadata = strip_metadata;
+    }
+    if let Some(min_bytes) = min_bytes {
+        config.min_bytes = min_bytes;
+    }
+    optimize::set_config(&vault.storage, &config)?;
+    Ok(config)
+}
@@ -0,0 +1,251 @@
+pub mod commands;
+pub mod optimize;
+pub mod text;
+
+use std::collections::HashSet;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use pulldown_cmark::{Event, Parser, Tag};
+use rusqlite::params;
+use serde::Serialize;
+
+use crate::clipper;
+use crate::error::{Error, Result};
+use crate::files::{NoteFiles, ATTACHMENTS_DIR};
+use crate::metadata::FrontMatter;
+use crate::storage::{Note, Storage};
+use crate::vault::Vault;
+
+/// Longest side of a generated thumbnail, in pixels.
+const THUMBNAIL_SIZE: u32 = 256;
+/// Attachments younger than this are never collected, so a file imported
+/// for a note that has not been saved with its link yet survives.
+const GC_GRACE: Duration = Duration::from_secs(24 * 60 * 60);
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Attachment {
+    /// Path in the mirror, `attachments/<sha256>.<ext>`.
+    pub path: String,
+    /// What to put in the note's Markdown to refer to it.
+    pub link: String,
+    pub size: u64,
+    /// Absolute path of a thumbnail, for images.
+    pub thumbnail: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GcReport {
+    pub removed: usize,
+    /// Bytes freed, thumbnails included.
+    pub bytes: u64,
+}
+
+/// Thumbnails of image attachments under `<vault>/thumbnails`, kept out of
+/// the mirror since they can always be made again.
+pub struct Thumbnails {
+    dir: PathBuf,
+}
+
+impl Thumbnails {
+    pub fn open(dir: &Path) -> Result<Self> {
+        fs::create_dir_all(dir)?;
+        Ok(Self {
+            dir: dir.to_owned(),
+        })
+    }
+
+    /// The thumbnail of the attachment `rel`, made on first use. `None` for
+    /// anything that is not a readable image. Like the attachment itself it
+    /// is not sealed in a vault with a password.
+    pub fn get(&self, files: &NoteFiles, rel: &str) -> Result<Option<PathBuf>> {
+        if !NoteFiles::is_attachment_path(rel) {
+            return Ok(None);
+        }
+        let path = self.path_of(rel);
+        if path.is_file() {
+            return Ok(Some(path));
+        }
+        let image = match image::open(files.absolute(rel)) {
+            Ok(image) => image,
+            Err(image::ImageError::Unsupported(_) | image::ImageError::Decoding(_)) => {
+                return Ok(None)
+            }
+            Err(err) => return Err(err.into()),
+        };
+        image
+            .thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE)
+            .save_with_format(&path, image::ImageFormat::Png)?;
+        Ok(Some(path))
+    }
+
+    fn remove(&self, rel: &str) -> u64 {
+        let path = self.path_of(rel);
+        let size = fs::metadata(&path).map(|meta| meta.len()).unwrap_or(0);
+        fs::remove_file(&path).map(|()| size).unwrap_or(0)
+    }
+
+    fn path_of(&self, rel: &str) -> PathBuf {
+        let name = rel.rsplit('/').next().unwrap_or(rel);
+        let hash = name.split('.').next().unwrap_or(name);
+        self.dir.join(format!("{hash}.png"))
+    }
+}
+
+/// Copies `content` into the attachment store for the note `note_id` and
+/// makes a thumbnail if it is an image. Identical files are stored once.
+/// Images may be optimised first; see [`optimize::on_import`].
+pub fn import(vault: &Vault, note_id: &str, name: &str, content: &[u8]) -> Result<Attachment> {
+    let note = vault.storage.get_note(note_id)?;
+    let optimized = optimize::on_import(&vault.storage, name, content);
+    let (name, content) = match &optimized {
+        Some((name, content)) => (name.as_str(), content.as_slice()),
+        None => (name, content),
+    };
+    let path = vault.files.save_attachment(name, content)?;
+    // A deduplicated file keeps its old timestamp; bump it so a collection
+    // before the note is saved does not take it.
+    fs::File::options()
+        .write(true)
+        .open(vault.files.absolute(&path))?
+        .set_modified(SystemTime::now())?;
+    let thumbnail = vault.thumbnails.get(&vault.files, &path)?;
+    Ok(Attachment {
+        link: NoteFiles::link_from(&note.folder, &path),
+        size: content.len() as u64,
+        thumbnail: thumbnail.map(|path| path.to_string_lossy().into_owned()),
+        path,
+    })
+}
+
+/// The attachments a note body links to or embeds, skipping code, and
+/// the archived page of a clipping with what that page loads, which its
+/// front matter lists.
+pub fn references(note: &Note) -> Vec<String> {
+    let (front_matter, markdown) = FrontMatter::split(&note.body);
+    let clipped: Vec<String> = front_matter
+        .map(|front_matter| {
+            let assets = match front_matter.fields.get(clipper::ASSETS_KEY) {
+                Some(serde_yaml_ng::Value::Sequence(links)) => links
+                    .iter()
+                    .filter_map(|link| link.as_str().map(str::to_owned))
+                    .collect(),
+                _ => Vec::new(),
+            };
+            front_matter
+                .str("archive")
+                .map(str::to_owned)
+                .into_iter()
+                .chain(assets)
+                .collect()
+        })
+        .unwrap_or_default();
+    let mut paths: Vec<String> = Parser::new(markdown)
+        .filter_map(|event| match event {
+            Event::Start(Tag::Link { dest_url, .. } | Tag::Image { dest_url, .. }) => {
+                NoteFiles::resolve_from(&note.folder, &dest_url)
+            }
+            _ => None,
+        })
+        .chain(
+            clipped
+                .iter()
+                .filter_map(|link| NoteFiles::resolve_from(&note.folder, link)),
+        )
+        .filter(|rel| NoteFiles::is_attachment_path(rel))
+        .collect();
+    paths.sort();
+    paths.dedup();
+    paths
+}
+
+/// Records which attachments `note` uses, replacing what it used before.
+pub fn track(storage: &Storage, note: &Note) -> Result<()> {
+    let mut conn = storage.conn();
+    let tx = conn.transaction()?;
+    set_references(&tx, note)?;
+    tx.commit()?;
+    Ok(())
+}
+
+/// Records the attachments of every note afresh.
+pub fn rebuild(storage: &Storage, notes: &[Note]) -> Result<()> {
+    let mut conn = storage.conn();
+    let tx = conn.transaction()?;
+    tx.execute("DELETE FROM attachment_refs", [])?;
+    for note in notes {
+        set_references(&tx, note)?;
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+fn set_references(tx: &rusqlite::Transaction, note: &Note) -> Result<()> {
+    tx.execute("DELETE FROM attachment_refs WHERE note_id = ?1", [&note.id])?;
+    for path in references(note) {
+        tx.execute(
+            "INSERT INTO attachment_refs (note_id, path) VALUES (?1, ?2)",
+            params![note.id, path],
+        )?;
+    }
+    Ok(())
+}
+
+/// Deletes attachments no note links to any more, trashed notes included,
+/// along with their thumbnails.
+///
+/// References are only known for notes whose bodies have been read, so an
+/// encrypted vault has to be unlocked. Old revisions do not count; restoring
+/// one may bring back a link to a collected file.
+pub fn gc_orphaned(vault: &Vault) -> Result<GcReport> {
+    if vault.storage.is_locked() {
+        return Err(Error::VaultLocked);
+    }
+    let used: HashSet<String> = {
+        let conn = vault.storage.conn();
+        // A drawing's strokes are kept while a note embeds its preview, the
+        // archive of a queued article while it is queued, and what a page
+        // clipped before its files were listed in the note loads while the
+        // page is kept.
+        let mut stmt = conn.prepare(
+            "WITH kept(path) AS (
+                 SELECT path FROM attachment_refs
+                 UNION SELECT archive FROM read_later WHERE archive IS NOT NULL
+             )
+             SELECT path FROM kept
+             UNION SELECT source FROM ink WHERE preview IN (SELECT path FROM kept)
+             UNION SELECT asset FROM archive_assets WHERE archive IN (SELECT path FROM kept)",
+        )?;
+        let paths = stmt
+            .query_map([], |row| row.get(0))?
+            .collect::<rusqlite::Result<_>>()?;
+        paths
+    };
+    let mut report = GcReport::default();
+    let entries = match fs::read_dir(vault.files.attachments_dir()) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(report),
+        Err(err) => return Err(err.into()),
+    };
+    let cutoff = SystemTime::now() - GC_GRACE;
+    for entry in entries {
+        let entry = entry?;
+        let meta = entry.metadata()?;
+        if !meta.is_file() || meta.modified()? > cutoff {
+            continue;
+        }
+        let rel = format!("{ATTACHMENTS_DIR}/{}", entry.file_name().to_string_lossy());
+        if used.contains(&rel) {
+            continue;
+        }
+        fs::remove_file(entry.path())?;
+        text::remove(&vault.storage, &rel)?;
+        report.removed += 1;
+        report.bytes += meta.len() + vault.thumbnails.remove(&rel);
+    }
+    Ok(report)
+}
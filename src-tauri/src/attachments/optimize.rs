@@ -0,0 +1,324 @@
+//! Shrinking image attachments: re-encoding screenshots and photos in a
+//! smaller format, scaling down oversized ones and dropping their EXIF
+//! data. Attachments are named by content, so an optimised file gets a new
+//! path and the notes using it are relinked.
+
+use std::fs;
+use std::io::{Cursor, ErrorKind};
+
+use image::codecs::avif::AvifEncoder;
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::webp::WebPEncoder;
+use image::imageops::FilterType;
+use image::metadata::Orientation;
+use image::{DynamicImage, ImageDecoder, ImageEncoder, ImageReader};
+use serde::{Deserialize, Serialize};
+
+use super::text;
+use crate::error::{Error, Result};
+use crate::files::ATTACHMENTS_DIR;
+use crate::storage::{NotePatch, Storage};
+use crate::vault::Vault;
+use crate::{protected, trash};
+
+const CONFIG_KEY: &str = "attachments.optimize";
+/// Formats that are decoded and may be re-encoded. GIFs are left alone so
+/// animations survive.
+const SOURCES: &[&str] = &["png", "jpg", "jpeg", "bmp", "webp"];
+const JPEG_QUALITY: u8 = 85;
+const AVIF_QUALITY: u8 = 75;
+/// rav1e's speed, 0 to 10; higher is faster and a little larger.
+const AVIF_SPEED: u8 = 6;
+
+/// What optimised images are stored as.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Target {
+    /// Lossless WebP for screenshots and graphics; photos stay JPEG.
+    #[default]
+    Webp,
+    /// AVIF for everything, lossy and much smaller, but slow to encode.
+    Avif,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct OptimizeConfig {
+    /// Optimise images as they are attached.
+    pub on_import: bool,
+    pub format: Target,
+    /// Longest side an image is scaled down to, in pixels.
+    pub max_dimension: u32,
+    /// Drop EXIF data, such as where and with what a photo was taken.
+    pub strip_metadata: bool,
+    /// Images smaller than this many bytes are kept as they are.
+    pub min_bytes: u64,
+}
+
+impl Default for OptimizeConfig {
+    fn default() -> Self {
+        Self {
+            on_import: true,
+            format: Target::default(),
+            max_dimension: 3840,
+            strip_metadata: true,
+            min_bytes: 256 * 1024,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OptimizeReport {
+    pub optimized: usize,
+    /// Bytes saved across the optimised files, once garbage collection has
+    /// taken the originals.
+    pub saved: u64,
+    /// Attachments that were left alone, each with the reason.
+    pub skipped: Vec<String>,
+}
+
+pub fn config_of(storage: &Storage) -> Result<OptimizeConfig> {
+    match storage.meta(CONFIG_KEY)? {
+        Some(json) => Ok(serde_json::from_str(&json)?),
+        None => Ok(OptimizeConfig::default()),
+    }
+}
+
+pub fn set_config(storage: &Storage, config: &OptimizeConfig) -> Result<()> {
+    storage.set_meta(CONFIG_KEY, &serde_json::to_string(config)?)
+}
+
+/// The file name and content to store an attachment under if it is
+/// optimised on import. `None` keeps it as given, as does any failure: a
+/// file that cannot be optimised can still be attached.
+pub(super) fn on_import(
+    storage: &Storage,
+    name: &str,
+    content: &[u8],
+) -> Option<(String, Vec<u8>)> {
+    if !SOURCES.contains(&extension(name).as_str()) {
+        return None;
+    }
+    let config = config_of(storage).ok()?;
+    match config.on_import {
+        true => optimize(&config, name, content).ok().flatten(),
+        false => None,
+    }
+}
+
+/// `content` optimised, with `name` given the new extension. A re-encode
+/// is only kept when it comes out smaller; otherwise a JPEG with EXIF data
+/// to drop loses it without being re-encoded. `None` if it is not an image
+/// this handles, is below [`OptimizeConfig::min_bytes`], or would come out
+/// no better.
+pub fn optimize(
+    config: &OptimizeConfig,
+    name: &str,
+    content: &[u8],
+) -> Result<Option<(String, Vec<u8>)>> {
+    let ext = extension(name);
+    if !SOURCES.contains(&ext.as_str()) || (content.len() as u64) < config.min_bytes {
+        return Ok(None);
+    }
+    let mut decoder = ImageReader::new(Cursor::new(content))
+        .with_guessed_format()?
+        .into_decoder()?;
+    let exif = decoder.exif_metadata()?;
+    let orientation = decoder.orientation()?;
+    let mut image = DynamicImage::from_decoder(decoder)?;
+    let stripped = config.strip_metadata && exif.is_some();
+    let exif = match config.strip_metadata {
+        // The orientation goes with the rest, so it is applied to the pixels.
+        true => {
+            image.apply_orientation(orientation);
+            None
+        }
+        false => exif,
+    };
+    let scaled = image.width().max(image.height()) > config.max_dimension;
+    if scaled {
+        image = image.resize(
+            config.max_dimension,
+            config.max_dimension,
+            FilterType::Lanczos3,
+        );
+    }
+    let photo = matches!(ext.as_str(), "jpg" | "jpeg");
+    let (new_ext, encoded) = encode(&image, config.format, photo, exif)?;
+    if encoded.len() < content.len() {
+        let stem = name.rsplit_once('.').map_or(name, |(stem, _)| stem);
+        return Ok(Some((format!("{stem}.{new_ext}"), encoded)));
+    }
+    match stripped && photo {
+        true => Ok(strip_exif(content, orientation).map(|stripped| (name.to_owned(), stripped))),
+        false => Ok(None),
+    }
+}
+
+/// JPEG `content` with its EXIF segments taken out and the pixels left as
+/// they are. What rotates the photo is put back on its own, so it still
+/// shows the right way up. `None` if the file does not parse as a JPEG.
+fn strip_exif(content: &[u8], orientation: Orientation) -> Option<Vec<u8>> {
+    let mut out = content
+        .get(..2)
+        .filter(|soi| *soi == [0xFF, 0xD8])?
+        .to_vec();
+    if orientation != Orientation::NoTransforms {
+        // "Exif", a big-endian TIFF header and one IFD holding only the
+        // orientation.
+        let mut exif = b"Exif\0\0MM\0\x2a\0\0\0\x08\0\x01\x01\x12\0\x03\0\0\0\x01".to_vec();
+        exif.extend_from_slice(&[0, orientation.to_exif(), 0, 0, 0, 0, 0, 0]);
+        out.extend_from_slice(&[0xFF, 0xE1]);
+        out.extend_from_slice(&(exif.len() as u16 + 2).to_be_bytes());
+        out.extend_from_slice(&exif);
+    }
+    let mut at = 2;
+    loop {
+        let marker = content.get(at..at + 2)?;
+        if marker[0] != 0xFF {
+            return None;
+        }
+        // From the start of the scan on, the rest is image data.
+        if marker[1] == 0xDA {
+            out.extend_from_slice(&content[at..]);
+            return Some(out);
+        }
+        let length = u16::from_be_bytes([*content.get(at + 2)?, *content.get(at + 3)?]) as usize;
+        let segment = content.get(at..at + 2 + length)?;
+        let exif = marker[1] == 0xE1 && segment.get(4..10) == Some(b"Exif\0\0".as_slice());
+        if !exif {
+            out.extend_from_slice(segment);
+        }
+        at += 2 + length;
+    }
+}
+
+fn encode(
+    image: &DynamicImage,
+    target: Target,
+    photo: bool,
+    exif: Option<Vec<u8>>,
+) -> Result<(&'static str, Vec<u8>)> {
+    // The encoders take 8-bit colour, and JPEG no alpha.
+    let image = match image.color().has_alpha() && !(photo && target == Target::Webp) {
+        true => DynamicImage::ImageRgba8(image.to_rgba8()),
+        false => DynamicImage::ImageRgb8(image.to_rgb8()),
+    };
+    let mut out = Vec::new();
+    let ext = match (target, photo) {
+        (Target::Avif, _) => {
+            let encoder = AvifEncoder::new_with_speed_quality(&mut out, AVIF_SPEED, AVIF_QUALITY);
+            write(encoder, &image, exif)?;
+            "avif"
+        }
+        (Target::Webp, true) => {
+            write(
+                JpegEncoder::new_with_quality(&mut out, JPEG_QUALITY),
+                &image,
+                exif,
+            )?;
+            "jpg"
+        }
+        (Target::Webp, false) => {
+            write(WebPEncoder::new_lossless(&mut out), &image, exif)?;
+            "webp"
+        }
+    };
+    Ok((ext, out))
+}
+
+fn write(
+    mut encoder: impl ImageEncoder,
+    image: &DynamicImage,
+    exif: Option<Vec<u8>>,
+) -> Result<()> {
+    if let Some(exif) = exif {
+        // Formats without room for it lose it either way.
+        let _ = encoder.set_exif_metadata(exif);
+    }
+    encoder.write_image(
+        image.as_bytes(),
+        image.width(),
+        image.height(),
+        image.color().into(),
+    )?;
+    Ok(())
+}
+
+/// Optimises every image attachment some note uses, relinking the notes.
+/// Those used by a protected or trashed note are skipped, as their notes
+/// cannot be rewritten. The notes using a file are relinked together, and
+/// the original is left for the revisions that still link it until
+/// garbage collection takes it.
+pub fn optimize_all(vault: &Vault) -> Result<OptimizeReport> {
+    if vault.storage.is_locked() {
+        return Err(Error::VaultLocked);
+    }
+    let config = config_of(&vault.storage)?;
+    let mut report = OptimizeReport::default();
+    let entries = match fs::read_dir(vault.files.attachments_dir()) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(report),
+        Err(err) => return Err(err.into()),
+    };
+    for entry in entries {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if !entry.file_type()?.is_file() || !SOURCES.contains(&extension(&name).as_str()) {
+            continue;
+        }
+        let rel = format!("{ATTACHMENTS_DIR}/{name}");
+        // Unused ones are for garbage collection, not this.
+        let users = text::notes_using(&vault.storage, &rel)?;
+        if users.is_empty() {
+            continue;
+        }
+        let content = fs::read(entry.path())?;
+        let (new_name, optimized) = match optimize(&config, &name, &content) {
+            Ok(Some(optimized)) => optimized,
+            Ok(None) => continue,
+            Err(err) => {
+                report.skipped.push(format!("{rel}: {err}"));
+                continue;
+            }
+        };
+        let mut notes = Vec::new();
+        for id in &users {
+            let note = vault.storage.get_note(id)?;
+            if protected::is_protected(&note.body) || trash::is_trashed(&vault.storage, id)? {
+                break;
+            }
+            notes.push(note);
+        }
+        if notes.len() < users.len() {
+            report
+                .skipped
+                .push(format!("{rel}: used by a protected or trashed note"));
+            continue;
+        }
+
+        let new_rel = vault.files.save_attachment(&new_name, &optimized)?;
+        let patches = notes
+            .into_iter()
+            .map(|note| {
+                let patch = NotePatch {
+                    body: Some(note.body.replace(&rel, &new_rel)),
+                    ..Default::default()
+                };
+                (note.id, patch)
+            })
+            .collect();
+        vault.update_notes(patches, &format!("Optimise {rel}"))?;
+        text::rename(&vault.storage, &rel, &new_rel)?;
+        report.optimized += 1;
+        report.saved += (content.len() as u64).saturating_sub(optimized.len() as u64);
+    }
+    Ok(report)
+}
+
+fn extension(name: &str) -> String {
+    name.rsplit_once('.')
+        .map(|(_, ext)| ext.to_ascii_lowercase())
+        .unwrap_or_default()
+}
@@ -0,0 +1,150 @@
+use rusqlite::{params, OptionalExtension};
+use serde::Serialize;
+use tokio::sync::futures::Notified;
+use tokio::sync::Notify;
+
+use crate::error::Result;
+use crate::storage::{now_millis, Storage};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TextStatus {
+    Pending,
+    Done,
+    Failed,
+}
+
+/// Text read out of an attachment by OCR and the like, which the search
+/// index adds to every note using it.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AttachmentText {
+    pub path: String,
+    pub status: TextStatus,
+    /// Characters of text found.
+    pub chars: usize,
+    pub error: Option<String>,
+}
+
+/// Wakes a background reader when there may be new attachments to read.
+/// Owned by the vault, so saving a note can signal it without an app handle.
+#[derive(Default)]
+pub struct TextQueue {
+    wake: Notify,
+}
+
+impl TextQueue {
+    pub fn wake(&self) {
+        self.wake.notify_one();
+    }
+
+    pub fn notified(&self) -> Notified<'_> {
+        self.wake.notified()
+    }
+}
+
+/// Records what was read from `path`, or why it could not be.
+pub fn set(storage: &Storage, path: &str, result: std::result::Result<&str, &str>) -> Result<()> {
+    let (text, error) = match result {
+        Ok(text) => (storage.seal_body(text)?, None),
+        Err(error) => (String::new(), Some(error)),
+    };
+    storage.conn().execute(
+        "INSERT OR REPLACE INTO attachment_text (path, text, error, updated_at)
+         VALUES (?1, ?2, ?3, ?4)",
+        params![path, text, error, now_millis()],
+    )?;
+    Ok(())
+}
+
+/// Where reading `path` stands.
+pub fn status(storage: &Storage, path: &str) -> Result<AttachmentText> {
+    let row: Option<(String, Option<String>)> = storage
+        .conn()
+        .query_row(
+            "SELECT text, error FROM attachment_text WHERE path = ?1",
+            [path],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()?;
+    let (status, chars, error) = match row {
+        None => (TextStatus::Pending, 0, None),
+        Some((_, Some(error))) => (TextStatus::Failed, 0, Some(error)),
+        Some((text, None)) => (
+            TextStatus::Done,
+            storage.open_body(text)?.chars().count(),
+            None,
+        ),
+    };
+    Ok(AttachmentText {
+        path: path.to_owned(),
+        status,
+        chars,
+        error,
+    })
+}
+
+/// The text of every attachment a note uses, for the search index.
+pub fn of_note(storage: &Storage, note_id: &str) -> Result<String> {
+    let stored: Vec<String> = {
+        let conn = storage.conn();
+        let mut stmt = conn.prepare(
+            "SELECT t.text FROM attachment_refs r JOIN attachment_text t ON t.path = r.path
+             WHERE r.note_id = ?1 AND t.error IS NULL
+             ORDER BY r.path",
+        )?;
+        let rows = stmt
+            .query_map([note_id], |row| row.get(0))?
+            .collect::<rusqlite::Result<_>>()?;
+        rows
+    };
+    let texts = stored
+        .into_iter()
+        .map(|text| storage.open_body(text))
+        .collect::<Result<Vec<_>>>()?;
+    Ok(texts.join("\n\n"))
+}
+
+/// Attachments some note uses that have not been read yet.
+pub fn pending(storage: &Storage) -> Result<Vec<String>> {
+    let conn = storage.conn();
+    let mut stmt = conn.prepare(
+        "SELECT DISTINCT path FROM attachment_refs
+         WHERE path NOT IN (SELECT path FROM attachment_text)
+         ORDER BY path",
+    )?;
+    let paths = stmt
+        .query_map([], |row| row.get(0))?
+        .collect::<rusqlite::Result<_>>()?;
+    Ok(paths)
+}
+
+/// Ids of the notes that use `path`.
+pub fn notes_using(storage: &Storage, path: &str) -> Result<Vec<String>> {
+    let conn = storage.conn();
+    let mut stmt = conn.prepare("SELECT note_id FROM attachment_refs WHERE path = ?1")?;
+    let ids = stmt
+        .query_map([path], |row| row.get(0))?
+        .collect::<rusqlite::Result<_>>()?;
+    Ok(ids)
+}
+
+/// Carries the text of `from` over to `to`, for a file stored anew under
+/// another path. Text already read for `to` is kept.
+pub fn rename(storage: &Storage, from: &str, to: &str) -> Result<()> {
+    let conn = storage.conn();
+    conn.execute(
+        "UPDATE OR IGNORE attachment_text SET path = ?2 WHERE path = ?1",
+        params![from, to],
+    )?;
+    conn.execute("DELETE FROM attachment_text WHERE path = ?1", [from])?;
+    Ok(())
+}
+
+/// Forgets the text of `path`, once the file itself is gone.
+pub fn remove(storage: &Storage, path: &str) -> Result<()> {
+    storage
+        .conn()
+        .execute("DELETE FROM attachment_text WHERE path = ?1", [path])?;
+    Ok(())
+}
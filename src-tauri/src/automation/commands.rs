@@ -0,0 +1,76 @@
+use tauri::{AppHandle, Manager, State, Window};
+
+use super::{Action, Automations, Rule, Trigger};
+use crate::error::{Error, Result};
+use crate::vaults::Vaults;
+
+#[tauri::command]
+pub async fn list_rules(
+    app: AppHandle,
+    window: Window,
+    automations: State<'_, Automations>,
+) -> Result<Vec<Rule>> {
+    let vault_id = app.state::<Vaults>().id_of(window.label());
+    Ok(automations.rules(&vault_id))
+}
+
+/// Adds an automation rule to the window's vault. Passing the `id` of an
+/// existing one replaces it, which is also how a rule is turned off and on.
+#[allow(clippy::too_many_arguments)]
+#[tauri::command]
+pub async fn add_rule(
+    app: AppHandle,
+    window: Window,
+    automations: State<'_, Automations>,
+    id: Option<String>,
+    name: String,
+    trigger: Trigger,
+    action: Action,
+    enabled: Option<bool>,
+) -> Result<Rule> {
+    let vault_id = app.state::<Vaults>().id_of(window.label());
+    automations.add(
+        &vault_id,
+        Rule {
+            id: id.unwrap_or_default(),
+            name,
+            enabled: enabled.unwrap_or(true),
+            trigger,
+            action,
+        },
+    )
+}
+
+#[tauri::command]
+pub async fn remove_rule(
+    app: AppHandle,
+    window: Window,
+    automations: State<'_, Automations>,
+    id: String,
+) -> Result<()> {
+    let vault_id = app.state::<Vaults>().id_of(window.label());
+    automations.remove(&vault_id, &id)
+}
+
+/// Runs rule `id` now, as if note `noteId` had set it off, to try it out.
+#[tauri::command]
+pub async fn run_rule(
+    app: AppHandle,
+    window: Window,
+    id: String,
+    note_id: Option<String>,
+) -> Result<()> {
+    let vaults = app.state::<Vaults>();
+    let vault = vaults.of_window(&app, window.label())?;
+    let rule = app
+        .state::<Automations>()
+        .rules(&vaults.id_of(window.label()))
+        .into_iter()
+        .find(|rule| rule.id == id)
+        .ok_or_else(|| Error::InvalidInput(format!("no rule {id}")))?;
+    let note = match note_id {
+        Some(id) => Some(vault.storage.get_note(&id)?),
+        None => None,
+    };
+    super::run(&app, &vault, &rule, note).await
+}
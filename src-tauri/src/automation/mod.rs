@@ -0,0 +1,460 @@
+//! Rules that run an action when something happens in a vault: a note is
+//! saved, a sync finishes, or a cron schedule comes round. An action
+//! exports the vault as HTML, runs a program or takes a backup.
+//!
+//! Rules live in `automation.json` beside the settings, by vault id, not in
+//! the vault, so a vault synced or handed over from elsewhere cannot bring
+//! programs to run with it. Even so, a program only runs once the user has
+//! allowed it in a native dialog, which the webview cannot answer.
+
+pub mod commands;
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use chrono::Local;
+use croner::Cron;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager};
+use tauri_plugin_dialog::{DialogExt, MessageDialogButtons, MessageDialogKind};
+use tauri_plugin_shell::ShellExt;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::oneshot;
+
+use crate::atomic;
+use crate::error::{Error, Result};
+use crate::export::site::{self, SiteOptions};
+use crate::storage::{Note, Storage};
+use crate::vault::Vault;
+
+/// Event carrying the message of a rule that failed.
+pub const ERROR_EVENT: &str = "automation-error";
+
+const RULES_FILE: &str = "automation.json";
+/// Where earlier builds kept a vault's rules, in its database.
+const LEGACY_RULES_KEY: &str = "automation.rules";
+/// How often schedules are checked; they fire at most this late.
+const TICK: Duration = Duration::from_secs(30);
+/// Saves within this long of each other are handled together.
+const SETTLE: Duration = Duration::from_secs(2);
+/// A note does not set off the same rule again before this has passed, so
+/// a program that edits the note it was run on does not loop.
+const COOLDOWN: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "kind")]
+pub enum Trigger {
+    /// A note was saved, from anywhere; only in `folder` and below unless
+    /// that is empty. Saves that come together, such as a rename retitling
+    /// links, set a rule off once; the note placeholders are only filled in
+    /// when that was a single note.
+    NoteSaved {
+        #[serde(default)]
+        folder: String,
+    },
+    SyncComplete,
+    /// A five-field cron expression in local time, such as `0 18 * * 1-5`.
+    Schedule {
+        cron: String,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "kind")]
+pub enum Action {
+    /// Exports the vault as a static site into `dir`.
+    ExportHtml {
+        dir: String,
+        #[serde(default)]
+        options: SiteOptions,
+    },
+    /// Runs `program` with `args`. In either, `{path}` stands for the file
+    /// of the note that set the rule off, `{id}` and `{title}` for its id
+    /// and title, and `{vault}` for the notes folder; without a note,
+    /// `{path}` is the notes folder too. Each program has to be allowed
+    /// before its first run.
+    Shell {
+        program: String,
+        #[serde(default)]
+        args: Vec<String>,
+    },
+    Backup,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Rule {
+    pub id: String,
+    pub name: String,
+    #[serde(default = "enabled")]
+    pub enabled: bool,
+    pub trigger: Trigger,
+    pub action: Action,
+}
+
+fn enabled() -> bool {
+    true
+}
+
+/// What `automation.json` holds.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Saved {
+    /// Rules by vault id.
+    #[serde(default)]
+    rules: BTreeMap<String, Vec<Rule>>,
+    /// Programs the user has let rules run, as they are run.
+    #[serde(default)]
+    allowed: BTreeSet<String>,
+}
+
+/// Every vault's rules, managed as app state.
+pub struct Automations {
+    file: PathBuf,
+    saved: Mutex<Saved>,
+}
+
+impl Automations {
+    pub fn load(config_dir: &Path) -> Result<Self> {
+        let file = config_dir.join(RULES_FILE);
+        let saved = match fs::read_to_string(&file) {
+            Ok(json) => serde_json::from_str(&json)?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Saved::default(),
+            Err(err) => return Err(err.into()),
+        };
+        Ok(Self {
+            file,
+            saved: Mutex::new(saved),
+        })
+    }
+
+    pub fn rules(&self, vault_id: &str) -> Vec<Rule> {
+        self.lock().rules.get(vault_id).cloned().unwrap_or_default()
+    }
+
+    /// Adds a rule to vault `vault_id`, or replaces the one with the same id.
+    pub fn add(&self, vault_id: &str, mut rule: Rule) -> Result<Rule> {
+        rule.name = rule.name.trim().to_owned();
+        if rule.name.is_empty() {
+            return Err(Error::InvalidInput("a rule needs a name".into()));
+        }
+        if let Trigger::Schedule { cron } = &rule.trigger {
+            schedule(cron)?;
+        }
+        match &rule.action {
+            Action::ExportHtml { dir, .. } if dir.trim().is_empty() => {
+                return Err(Error::InvalidInput("choose a folder to export to".into()));
+            }
+            Action::Shell { program, .. } if program.trim().is_empty() => {
+                return Err(Error::InvalidInput("choose a program to run".into()));
+            }
+            _ => {}
+        }
+        if rule.id.is_empty() {
+            rule.id = uuid::Uuid::new_v4().to_string();
+        }
+        self.change(|saved| {
+            let rules = saved.rules.entry(vault_id.to_owned()).or_default();
+            match rules.iter_mut().find(|existing| existing.id == rule.id) {
+                Some(existing) => *existing = rule.clone(),
+                None => rules.push(rule.clone()),
+            }
+            Ok(())
+        })?;
+        Ok(rule)
+    }
+
+    pub fn remove(&self, vault_id: &str, id: &str) -> Result<()> {
+        self.change(|saved| {
+            let rules = saved.rules.entry(vault_id.to_owned()).or_default();
+            let before = rules.len();
+            rules.retain(|rule| rule.id != id);
+            if rules.len() == before {
+                return Err(Error::InvalidInput(format!("no rule {id}")));
+            }
+            Ok(())
+        })
+    }
+
+    /// Takes over the rules an earlier build kept in the vault itself,
+    /// turned off, so none runs until the user looks at it here.
+    fn adopt_legacy(&self, vault_id: &str, storage: &Storage) -> Result<()> {
+        let Some(json) = storage.meta(LEGACY_RULES_KEY)? else {
+            return Ok(());
+        };
+        let legacy: Vec<Rule> = serde_json::from_str(&json).unwrap_or_default();
+        self.change(|saved| {
+            let rules = saved.rules.entry(vault_id.to_owned()).or_default();
+            for mut rule in legacy {
+                if rules.iter().all(|existing| existing.id != rule.id) {
+                    rule.enabled = false;
+                    rules.push(rule);
+                }
+            }
+            Ok(())
+        })?;
+        storage.delete_meta(LEGACY_RULES_KEY)
+    }
+
+    fn is_allowed(&self, program: &str) -> bool {
+        self.lock().allowed.contains(program)
+    }
+
+    fn allow(&self, program: &str) -> Result<()> {
+        self.change(|saved| {
+            saved.allowed.insert(program.to_owned());
+            Ok(())
+        })
+    }
+
+    /// Applies `f` and writes the result, keeping the old state if either
+    /// fails.
+    fn change(&self, f: impl FnOnce(&mut Saved) -> Result<()>) -> Result<()> {
+        let mut saved = self.lock();
+        let mut next = Saved {
+            rules: saved.rules.clone(),
+            allowed: saved.allowed.clone(),
+        };
+        f(&mut next)?;
+        if let Some(dir) = self.file.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        atomic::write(&self.file, serde_json::to_string_pretty(&next)?.as_bytes())?;
+        *saved = next;
+        Ok(())
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Saved> {
+        self.saved.lock().expect("automation rules poisoned")
+    }
+}
+
+/// Asks the user, in a native dialog, whether rule `rule` may run
+/// `program`, unless it was allowed before.
+async fn allowed(app: &AppHandle, rule: &Rule, program: &str) -> Result<()> {
+    let automations = app.state::<Automations>();
+    if automations.is_allowed(program) {
+        return Ok(());
+    }
+    let (sender, receiver) = oneshot::channel();
+    app.dialog()
+        .message(format!(
+            "The automation rule \"{}\" wants to run {program}. A program can do \
+             anything you can on this computer. Let rules run it from now on?",
+            rule.name
+        ))
+        .title("Run a program")
+        .kind(MessageDialogKind::Warning)
+        .buttons(MessageDialogButtons::OkCancelCustom(
+            "Allow".into(),
+            "Don't allow".into(),
+        ))
+        .show(move |allowed| {
+            let _ = sender.send(allowed);
+        });
+    if !receiver.await.unwrap_or(false) {
+        return Err(Error::Automation(format!(
+            "{program} was not allowed to run"
+        )));
+    }
+    automations.allow(program)
+}
+
+fn schedule(cron: &str) -> Result<Cron> {
+    Cron::from_str(cron).map_err(|err| Error::InvalidInput(format!("schedule {cron:?}: {err}")))
+}
+
+/// Runs the action of `rule`, for note `note` if one set it off.
+pub async fn run(
+    app: &AppHandle,
+    vault: &Arc<Vault>,
+    rule: &Rule,
+    note: Option<Note>,
+) -> Result<()> {
+    match &rule.action {
+        Action::ExportHtml { dir, options } => {
+            let (vault, dest, options) = (Arc::clone(vault), PathBuf::from(dir), options.clone());
+            tauri::async_runtime::spawn_blocking(move || {
+                site::export(&vault, &dest, &options, &mut |_| Ok(())).map(drop)
+            })
+            .await?
+        }
+        Action::Backup => {
+            let vault = Arc::clone(vault);
+            tauri::async_runtime::spawn_blocking(move || vault.backups.create(&vault).map(drop))
+                .await?
+        }
+        Action::Shell { program, args } => {
+            let root = vault.files.dir().to_string_lossy().into_owned();
+            let path = match &note {
+                Some(note) => vault
+                    .files
+                    .path_of(&vault.storage, &note.id)?
+                    .map(|rel| vault.files.absolute(&rel).to_string_lossy().into_owned()),
+                None => None,
+            };
+            let fill = |text: &str| {
+                text.replace("{path}", path.as_deref().unwrap_or(&root))
+                    .replace("{id}", note.as_ref().map_or("", |note| &note.id))
+                    .replace("{title}", note.as_ref().map_or("", |note| &note.title))
+                    .replace("{vault}", &root)
+            };
+            let program = fill(program);
+            allowed(app, rule, &program).await?;
+            let output = app
+                .shell()
+                .command(&program)
+                .args(args.iter().map(|arg| fill(arg)))
+                .current_dir(vault.files.dir())
+                .output()
+                .await
+                .map_err(|err| Error::Automation(format!("{program}: {err}")))?;
+            match output.status.success() {
+                true => Ok(()),
+                false => Err(Error::Automation(format!(
+                    "{program} failed: {}",
+                    String::from_utf8_lossy(&output.stderr).trim()
+                ))),
+            }
+        }
+    }
+}
+
+/// Runs every enabled rule of vault `vault_id` that `matches` picks,
+/// reporting failures through [`ERROR_EVENT`].
+async fn run_matching(
+    app: &AppHandle,
+    vault_id: &str,
+    vault: &Arc<Vault>,
+    note: Option<&Note>,
+    mut matches: impl FnMut(&Rule) -> bool,
+) {
+    let rules = app.state::<Automations>().rules(vault_id);
+    for rule in rules.iter().filter(|rule| rule.enabled && matches(rule)) {
+        report(app, rule, run(app, vault, rule, note.cloned()).await);
+    }
+}
+
+fn report(app: &AppHandle, rule: &Rule, result: Result<()>) {
+    if let Err(err) = result {
+        tracing::warn!(rule = %rule.id, error = %err, "automation rule failed");
+        let _ = app.emit(ERROR_EVENT, format!("{}: {err}", rule.name));
+    }
+}
+
+/// Starts watching vault `vault_id` for what its rules wait on, until it
+/// is closed.
+pub fn spawn(app: AppHandle, vault_id: String, vault: Arc<Vault>) {
+    let adopted = app
+        .state::<Automations>()
+        .adopt_legacy(&vault_id, &vault.storage);
+    if let Err(err) = adopted {
+        tracing::warn!(vault = %vault_id, error = %err, "could not move automation rules out of the vault");
+    }
+    spawn_saves(app.clone(), vault_id.clone(), Arc::clone(&vault));
+    spawn_syncs(app.clone(), vault_id.clone(), Arc::clone(&vault));
+    spawn_schedules(app, vault_id, vault);
+}
+
+fn spawn_saves(app: AppHandle, vault_id: String, vault: Arc<Vault>) {
+    let mut changes = vault.changes.subscribe();
+    tauri::async_runtime::spawn(async move {
+        let mut fired: HashMap<(String, String), Instant> = HashMap::new();
+        while !vault.is_closed() {
+            let mut saved = BTreeSet::new();
+            match changes.recv().await {
+                Ok(change) if !change.removed => saved.insert(change.note_id),
+                Ok(_) | Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => break,
+            };
+            let settled = tokio::time::Instant::now() + SETTLE;
+            while let Ok(change) = tokio::time::timeout_at(settled, changes.recv()).await {
+                match change {
+                    Ok(change) if !change.removed => {
+                        saved.insert(change.note_id);
+                    }
+                    Ok(_) | Err(RecvError::Lagged(_)) => {}
+                    Err(RecvError::Closed) => break,
+                }
+            }
+            fired.retain(|_, at| at.elapsed() < COOLDOWN);
+            let notes: Vec<Note> = saved
+                .iter()
+                .filter_map(|id| vault.storage.get_note(id).ok())
+                .collect();
+            let rules = app.state::<Automations>().rules(&vault_id);
+            for rule in rules.iter().filter(|rule| rule.enabled) {
+                let Trigger::NoteSaved { folder } = &rule.trigger else {
+                    continue;
+                };
+                let folder = folder.trim_matches('/');
+                let due: Vec<&Note> = notes
+                    .iter()
+                    .filter(|note| {
+                        folder.is_empty()
+                            || note.folder == folder
+                            || note.folder.starts_with(&format!("{folder}/"))
+                    })
+                    .filter(|note| !fired.contains_key(&(rule.id.clone(), note.id.clone())))
+                    .collect();
+                let note = match due.as_slice() {
+                    [] => continue,
+                    [note] => Some((*note).clone()),
+                    _ => None,
+                };
+                let now = Instant::now();
+                fired.extend(
+                    due.iter()
+                        .map(|note| ((rule.id.clone(), note.id.clone()), now)),
+                );
+                report(&app, rule, run(&app, &vault, rule, note).await);
+            }
+        }
+    });
+}
+
+fn spawn_syncs(app: AppHandle, vault_id: String, vault: Arc<Vault>) {
+    let mut synced = vault.synced.subscribe();
+    tauri::async_runtime::spawn(async move {
+        while !vault.is_closed() {
+            match synced.recv().await {
+                Ok(_) => {}
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => break,
+            }
+            run_matching(&app, &vault_id, &vault, None, |rule| {
+                matches!(rule.trigger, Trigger::SyncComplete)
+            })
+            .await;
+        }
+    });
+}
+
+/// Fires schedules whose next time after the previous check has come.
+/// Those missed while the app was not running are not caught up on.
+fn spawn_schedules(app: AppHandle, vault_id: String, vault: Arc<Vault>) {
+    tauri::async_runtime::spawn(async move {
+        let mut checked = Local::now();
+        loop {
+            tokio::time::sleep(TICK).await;
+            if vault.is_closed() {
+                break;
+            }
+            let now = Local::now();
+            run_matching(&app, &vault_id, &vault, None, |rule| match &rule.trigger {
+                Trigger::Schedule { cron } => schedule(cron)
+                    .and_then(|cron| {
+                        cron.find_next_occurrence(&checked, false)
+                            .map_err(|err| Error::InvalidInput(err.to_string()))
+                    })
+                    .is_ok_and(|next| next <= now),
+                _ => false,
+            })
+            .await;
+            checked = now;
+        }
+    });
+}
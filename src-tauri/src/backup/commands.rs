@@ -0,0 +1,38 @@
+use super::{BackupConfig, BackupInfo};
+use crate::error::Result;
+use crate::vaults::Current;
+
+#[tauri::command]
+pub async fn create_backup_now(vault: Current) -> Result<BackupInfo> {
+    tauri::async_runtime::spawn_blocking(move || vault.backups.create(&vault)).await?
+}
+
+#[tauri::command]
+pub async fn list_backups(vault: Current) -> Result<Vec<BackupInfo>> {
+    vault.backups.list()
+}
+
+/// Restores the backup `id`, after backing up the current state.
+#[tauri::command]
+pub async fn restore_backup(vault: Current, id: String) -> Result<()> {
+    tauri::async_runtime::spawn_blocking(move || vault.backups.restore(&vault, &id)).await?
+}
+
+/// Updates the schedule and retention policy. Omitted fields keep their
+/// current values, so calling it without any reads the settings.
+#[tauri::command]
+pub async fn configure_backups(
+    vault: Current,
+    interval_hours: Option<u64>,
+    keep_daily: Option<usize>,
+    keep_weekly: Option<usize>,
+) -> Result<BackupConfig> {
+    let current = super::config_of(&vault.storage)?;
+    let config = BackupConfig {
+        interval_hours: interval_hours.unwrap_or(current.interval_hours),
+        keep_daily: keep_daily.unwrap_or(current.keep_daily),
+        keep_weekly: keep_weekly.unwrap_or(current.keep_weekly),
+    };
+    super::set_config(&vault.storage, &config)?;
+    Ok(config)
+}
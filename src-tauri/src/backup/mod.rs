@@ -0,0 +1,377 @@
+use std::fs::{self, File};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use chrono::{DateTime, Datelike, Local, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter};
+
+use crate::error::{Error, Result};
+use crate::files::Staging;
+use crate::history;
+use crate::storage::{now_millis, Note, Storage};
+use crate::sync::rules::{SyncFilter, SyncTarget};
+use crate::vault::Vault;
+
+pub mod commands;
+
+/// Event carrying the message of a scheduled backup that failed.
+pub const ERROR_EVENT: &str = "backup-error";
+
+const CONFIG_KEY: &str = "backup.config";
+const EXTENSION: &str = ".tar.zst";
+/// Backup ids are their creation time in UTC, so they sort by age.
+const ID_FORMAT: &str = "%Y%m%d-%H%M%S";
+const ID_TIME_LEN: usize = 15;
+const DATABASE_ENTRY: &str = "notes.db";
+const ATTACHMENTS_ENTRY: &str = "attachments";
+/// How often the scheduler checks whether a backup is due.
+const CHECK_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupConfig {
+    /// Hours between automatic backups; 0 turns them off.
+    pub interval_hours: u64,
+    /// Number of days whose latest backup is kept.
+    pub keep_daily: usize,
+    /// Number of weeks whose latest backup is kept.
+    pub keep_weekly: usize,
+}
+
+impl Default for BackupConfig {
+    fn default() -> Self {
+        Self {
+            interval_hours: 24,
+            keep_daily: 7,
+            keep_weekly: 4,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupInfo {
+    pub id: String,
+    pub created_at: i64,
+    /// Compressed size in bytes.
+    pub size: u64,
+}
+
+/// Compressed snapshots of the database and attachments under
+/// `<vault>/backups`, one `<id>.tar.zst` each.
+///
+/// The note files are not included since they are rebuilt from the
+/// database. An encrypted vault's snapshots hold sealed bodies, so backups
+/// run while it is locked.
+pub struct Backups {
+    dir: PathBuf,
+    /// Held while a backup is written or restored.
+    running: Mutex<()>,
+}
+
+impl Backups {
+    pub fn open(dir: &Path) -> Result<Self> {
+        fs::create_dir_all(dir)?;
+        Ok(Self {
+            dir: dir.to_owned(),
+            running: Mutex::new(()),
+        })
+    }
+
+    /// Every backup, newest first.
+    pub fn list(&self) -> Result<Vec<BackupInfo>> {
+        let mut backups = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let Some(id) = name.strip_suffix(EXTENSION) else {
+                continue;
+            };
+            let Some(created_at) = created_at(id) else {
+                continue;
+            };
+            backups.push(BackupInfo {
+                id: id.to_owned(),
+                created_at,
+                size: entry.metadata()?.len(),
+            });
+        }
+        backups.sort_by(|a, b| (b.created_at, &b.id).cmp(&(a.created_at, &a.id)));
+        Ok(backups)
+    }
+
+    /// Takes a backup now and then prunes old ones per the retention policy.
+    pub fn create(&self, vault: &Vault) -> Result<BackupInfo> {
+        let _running = self.lock();
+        let backup = self.write(vault)?;
+        self.prune(&config_of(&vault.storage)?)?;
+        Ok(backup)
+    }
+
+    /// Replaces the database with the backup `id` and brings back any
+    /// attachments it had. The current state is backed up first, so a
+    /// restore can itself be undone. Notes the sync rules keep out of
+    /// backups stay as they are now.
+    pub fn restore(&self, vault: &Vault, id: &str) -> Result<()> {
+        if vault.storage.is_locked() {
+            return Err(Error::VaultLocked);
+        }
+        let _running = self.lock();
+        // Only listed ids, so `id` cannot point outside the directory.
+        if !self.list()?.iter().any(|backup| backup.id == id) {
+            return Err(Error::InvalidInput(format!("no backup {id}")));
+        }
+        self.write(vault)?;
+        let filter = SyncFilter::load(&vault.storage, SyncTarget::Backup)?;
+        let kept: Vec<Note> = match filter.is_empty() {
+            true => Vec::new(),
+            false => vault
+                .storage
+                .all_notes()?
+                .into_iter()
+                .filter(|note| !filter.allows_note(&note.folder, Some(note.body.len() as u64)))
+                .collect(),
+        };
+
+        let staging = Staging::new("restore")?;
+        let decoder = zstd::Decoder::new(File::open(self.path_of(id))?)?;
+        tar::Archive::new(decoder).unpack(&staging.0)?;
+        let database = staging.0.join(DATABASE_ENTRY);
+        if !database.is_file() {
+            return Err(Error::InvalidInput(format!("backup {id} has no database")));
+        }
+        // Attachments are content-addressed, so existing ones are identical.
+        let attachments = staging.0.join(ATTACHMENTS_ENTRY);
+        if attachments.is_dir() {
+            let target = vault.files.attachments_dir();
+            fs::create_dir_all(&target)?;
+            for entry in fs::read_dir(&attachments)? {
+                let entry = entry?;
+                let dest = target.join(entry.file_name());
+                if entry.file_type()?.is_file() && !dest.exists() {
+                    fs::copy(entry.path(), dest)?;
+                }
+            }
+        }
+        vault.restore_database(&database)?;
+        // Backups taken under the same rules never have these.
+        if !kept.is_empty() && !vault.storage.is_locked() {
+            for note in &kept {
+                if !vault.storage.has_note(&note.id)? {
+                    vault.import_note(note)?;
+                }
+            }
+            vault.autocommit("Keep notes left out of backups")?;
+        }
+        Ok(())
+    }
+
+    /// Whether the scheduler should take a backup now.
+    pub fn is_due(&self, storage: &Storage) -> Result<bool> {
+        let interval = config_of(storage)?.interval_hours;
+        if interval == 0 {
+            return Ok(false);
+        }
+        let interval_ms = i64::try_from(interval.saturating_mul(3_600_000)).unwrap_or(i64::MAX);
+        Ok(match self.list()?.first() {
+            Some(latest) => now_millis().saturating_sub(latest.created_at) >= interval_ms,
+            None => true,
+        })
+    }
+
+    fn write(&self, vault: &Vault) -> Result<BackupInfo> {
+        let now = Utc::now();
+        let mut id = now.format(ID_FORMAT).to_string();
+        for n in 2.. {
+            if !self.path_of(&id).exists() {
+                break;
+            }
+            id = format!("{}-{n}", now.format(ID_FORMAT));
+        }
+
+        let staging = Staging::new("backup")?;
+        let database = staging.0.join(DATABASE_ENTRY);
+        vault.storage.snapshot(&database)?;
+        let filter = SyncFilter::load(&vault.storage, SyncTarget::Backup)?;
+        let mut attachments = Vec::new();
+        let mut left_out = Vec::new();
+        let entries = match fs::read_dir(vault.files.attachments_dir()) {
+            Ok(entries) => Some(entries),
+            Err(err) if err.kind() == ErrorKind::NotFound => None,
+            Err(err) => return Err(err.into()),
+        };
+        for entry in entries.into_iter().flatten() {
+            let entry = entry?;
+            let meta = entry.metadata()?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let rel = format!("{ATTACHMENTS_ENTRY}/{name}");
+            if !meta.is_file() {
+                continue;
+            }
+            if filter.allows_path(&rel, Some(meta.len())) {
+                attachments.push((entry.path(), rel));
+            } else {
+                left_out.push(rel);
+            }
+        }
+        if !filter.is_empty() {
+            let notes = notes_left_out(&vault.storage, &filter)?;
+            leave_out(&database, &notes, &left_out)?;
+        }
+
+        // Written under a temporary name so a crash never leaves a
+        // truncated archive that looks like a backup.
+        let partial = self.dir.join(format!("{id}{EXTENSION}.partial"));
+        let result = (|| -> Result<()> {
+            let encoder =
+                zstd::Encoder::new(File::create(&partial)?, zstd::DEFAULT_COMPRESSION_LEVEL)?;
+            let mut archive = tar::Builder::new(encoder);
+            archive.append_path_with_name(&database, DATABASE_ENTRY)?;
+            for (path, rel) in &attachments {
+                archive.append_path_with_name(path, rel)?;
+            }
+            archive.into_inner()?.finish()?.sync_all()?;
+            Ok(())
+        })();
+        if let Err(err) = result {
+            let _ = fs::remove_file(&partial);
+            return Err(err);
+        }
+        let path = self.path_of(&id);
+        fs::rename(&partial, &path)?;
+        Ok(BackupInfo {
+            id,
+            created_at: now.timestamp_millis(),
+            size: fs::metadata(&path)?.len(),
+        })
+    }
+
+    /// Keeps the latest backup of each of the last `keep_daily` days and of
+    /// each of the last `keep_weekly` weeks that have one, plus the newest
+    /// backup whatever the policy, and deletes the rest.
+    fn prune(&self, config: &BackupConfig) -> Result<()> {
+        let mut days = Vec::new();
+        let mut weeks = Vec::new();
+        for (i, backup) in self.list()?.into_iter().enumerate() {
+            let Some(time) = DateTime::from_timestamp_millis(backup.created_at) else {
+                continue;
+            };
+            let time = time.with_timezone(&Local);
+            let day = time.date_naive();
+            let week = (time.iso_week().year(), time.iso_week().week());
+            let mut keep = i == 0;
+            if !days.contains(&day) && days.len() < config.keep_daily {
+                days.push(day);
+                keep = true;
+            }
+            if !weeks.contains(&week) && weeks.len() < config.keep_weekly {
+                weeks.push(week);
+                keep = true;
+            }
+            if !keep {
+                fs::remove_file(self.path_of(&backup.id))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn path_of(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{id}{EXTENSION}"))
+    }
+
+    fn lock(&self) -> MutexGuard<'_, ()> {
+        self.running.lock().expect("backup lock poisoned")
+    }
+}
+
+pub fn config_of(storage: &Storage) -> Result<BackupConfig> {
+    match storage.meta(CONFIG_KEY)? {
+        Some(json) => Ok(serde_json::from_str(&json)?),
+        None => Ok(BackupConfig::default()),
+    }
+}
+
+pub fn set_config(storage: &Storage, config: &BackupConfig) -> Result<()> {
+    storage.set_meta(CONFIG_KEY, &serde_json::to_string(config)?)
+}
+
+/// Takes a backup whenever the configured interval has passed since the
+/// latest one. Failures are reported through [`ERROR_EVENT`].
+pub fn spawn_periodic(app: AppHandle, vault: Arc<Vault>) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            tokio::time::sleep(CHECK_INTERVAL).await;
+            if vault.is_closed() {
+                break;
+            }
+            if !vault.backups.is_due(&vault.storage).unwrap_or(false) {
+                continue;
+            }
+            let vault = Arc::clone(&vault);
+            let result = tauri::async_runtime::spawn_blocking(move || {
+                vault.backups.create(&vault).map(drop)
+            })
+            .await
+            .unwrap_or_else(|err| Err(err.into()));
+            if let Err(err) = result {
+                tracing::warn!(error = %err, "scheduled backup failed");
+                let _ = app.emit(ERROR_EVENT, err.to_string());
+            }
+        }
+    });
+}
+
+/// The ids of the notes `filter` keeps out of backups. Sizes are the
+/// bytes of the body as sync measures them, so a rule on the size of notes
+/// needs an encrypted vault unlocked.
+fn notes_left_out(storage: &Storage, filter: &SyncFilter) -> Result<Vec<String>> {
+    let sized = filter.limits_note_size();
+    let notes: Vec<(String, String, Option<String>)> = {
+        let conn = storage.conn();
+        let mut stmt = conn.prepare("SELECT id, folder, CASE WHEN ?1 THEN body END FROM notes")?;
+        let rows = stmt
+            .query_map([sized], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+            .collect::<rusqlite::Result<_>>()?;
+        rows
+    };
+    let mut left_out = Vec::new();
+    for (id, folder, body) in notes {
+        let size = match body {
+            Some(body) => Some(storage.open_body(body)?.len() as u64),
+            None => None,
+        };
+        if !filter.allows_note(&folder, size) {
+            left_out.push(id);
+        }
+    }
+    Ok(left_out)
+}
+
+/// Takes the notes `notes`, with their history, and the text read from
+/// the attachments in `attachments` out of the snapshot at `database`.
+fn leave_out(database: &Path, notes: &[String], attachments: &[String]) -> Result<()> {
+    let storage = Storage::open(database)?;
+    {
+        let mut conn = storage.conn();
+        let tx = conn.transaction()?;
+        for id in notes {
+            tx.execute("DELETE FROM notes WHERE id = ?1", [id])?;
+        }
+        for path in attachments {
+            tx.execute("DELETE FROM attachment_text WHERE path = ?1", [path])?;
+        }
+        tx.commit()?;
+    }
+    history::prune_blobs(&storage)?;
+    // Rewritten so nothing of what was deleted is left in free pages.
+    storage.vacuum()?;
+    Ok(())
+}
+
+fn created_at(id: &str) -> Option<i64> {
+    let time = NaiveDateTime::parse_from_str(id.get(..ID_TIME_LEN)?, ID_FORMAT).ok()?;
+    Some(time.and_utc().timestamp_millis())
+}
@@ -0,0 +1,36 @@
+use tauri::{AppHandle, Manager, State};
+
+use super::{Beam, Beams, Offer};
+use crate::error::Result;
+use crate::storage::Note;
+use crate::vaults::Current;
+
+/// Offers note `note_id` to one other device on the network for a few
+/// minutes. Show the code or its QR code there; a `beam` event says when it
+/// was fetched or closed.
+#[tauri::command]
+pub async fn beam_note(app: AppHandle, vault: Current, note_id: String) -> Result<Beam> {
+    tauri::async_runtime::spawn_blocking(move || app.state::<Beams>().send(&app, &vault, &note_id))
+        .await?
+}
+
+/// Closes the beam showing `code` before it is fetched, returning whether
+/// it was still open.
+#[tauri::command]
+pub async fn cancel_beam(beams: State<'_, Beams>, code: String) -> Result<bool> {
+    Ok(beams.cancel(&code))
+}
+
+/// What another device is beaming at `code` and which device it is,
+/// without taking it in.
+#[tauri::command]
+pub async fn beam_offer(code: String) -> Result<Offer> {
+    super::offer(&code).await
+}
+
+/// Fetches the note another device is beaming at `code`, as typed or as the
+/// link in its QR code, into this vault.
+#[tauri::command]
+pub async fn receive_beam(vault: Current, code: String) -> Result<Note> {
+    super::receive(&vault, &code).await
+}
@@ -0,0 +1,516 @@
+//! Handing one note, with its attachments, to another device on the same
+//! network without syncing. The sender serves it over HTTP on a random port
+//! for a few minutes and shows a code; typed in or scanned from its QR
+//! code on the other device, the code says where to fetch the note and
+//! holds the secret that both authorises the fetch and decrypts what comes
+//! back. The first fetch that gets it closes the endpoint.
+//!
+//! Codes only lead to private network addresses, and the receiving side
+//! first asks what is on offer and from which device, so a code arriving
+//! through a link can be shown to the user before anything is taken in.
+
+pub mod commands;
+
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use reqwest::{Client, StatusCode};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tauri::{AppHandle, Emitter, Manager};
+use tiny_http::{Header, Request, Response, Server};
+
+use crate::attachments;
+use crate::error::{Error, Result};
+use crate::files::NoteFiles;
+use crate::http;
+use crate::protected;
+use crate::share::qr;
+use crate::storage::{now_millis, Note};
+use crate::sync::lan;
+use crate::vault::Vault;
+
+/// Event carrying a [`BeamEvent`] when a beam is fetched, runs out or is
+/// cancelled.
+pub const EVENT: &str = "beam";
+
+/// How long a beam waits to be fetched.
+const TTL: Duration = Duration::from_secs(5 * 60);
+/// Wrong secrets taken before the beam closes.
+const MAX_ATTEMPTS: usize = 10;
+/// Note and attachments together, before encoding.
+const MAX_BEAM_BYTES: usize = 256 * 1024 * 1024;
+/// The most a receiver takes in: the largest parcel once its files are in
+/// base64, with room for the JSON around them.
+const MAX_SEALED_BYTES: usize = MAX_BEAM_BYTES / 3 * 4 + 1024 * 1024;
+/// The most a receiver takes in when asking what is on offer.
+const MAX_OFFER_BYTES: usize = 64 * 1024;
+const OFFER_PATH: &str = "/offer";
+const FETCH_TIMEOUT: Duration = Duration::from_secs(120);
+/// How often the server checks whether it should stop.
+const POLL: Duration = Duration::from_millis(250);
+/// Bytes of secret in a code; with the address, the code is 24 characters.
+const SECRET_BYTES: usize = 9;
+/// Crockford's base32, which leaves out letters easily misread.
+const ALPHABET: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Beam {
+    /// What to type on the receiving device, in groups of four.
+    pub code: String,
+    /// The `notes://beam/<code>` link the QR code carries.
+    pub link: String,
+    /// The QR code as a PNG `data:` URL.
+    pub qr: String,
+    pub expires_at: i64,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BeamState {
+    Sent,
+    Expired,
+    Cancelled,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BeamEvent {
+    pub code: String,
+    pub state: BeamState,
+}
+
+/// What a beam holds, as the receiving side is shown it before taking it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Offer {
+    /// The sending device, by the name it shows on the network.
+    pub sender: String,
+    /// Where the sender is on the network.
+    #[serde(default)]
+    pub address: String,
+    pub title: String,
+    /// Note and attachments together, in bytes.
+    pub size: usize,
+}
+
+/// What travels, sealed.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Parcel {
+    title: String,
+    body: String,
+    folder: String,
+    created_at: i64,
+    updated_at: i64,
+    attachments: Vec<ParcelFile>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ParcelFile {
+    /// Mirror path on the sending device.
+    path: String,
+    /// Base64 of the file.
+    data: String,
+}
+
+/// Where a code leads and the secret it holds.
+struct Code {
+    address: SocketAddr,
+    secret: [u8; SECRET_BYTES],
+}
+
+impl Code {
+    fn encode(&self) -> Result<String> {
+        let IpAddr::V4(ip) = self.address.ip() else {
+            return Err(Error::Beam("beams only work over IPv4".into()));
+        };
+        let mut bytes = Vec::with_capacity(6 + SECRET_BYTES);
+        bytes.extend_from_slice(&ip.octets());
+        bytes.extend_from_slice(&self.address.port().to_be_bytes());
+        bytes.extend_from_slice(&self.secret);
+        let mut chars = Vec::new();
+        // Fifteen bytes are exactly twenty-four five-bit digits.
+        for chunk in bytes.chunks(5) {
+            let value = chunk
+                .iter()
+                .fold(0u64, |value, &byte| value << 8 | u64::from(byte));
+            for shift in (0..8).rev() {
+                chars.push(ALPHABET[(value >> (shift * 5)) as usize & 31] as char);
+            }
+        }
+        Ok(chars
+            .chunks(4)
+            .map(|group| group.iter().collect::<String>())
+            .collect::<Vec<_>>()
+            .join("-"))
+    }
+
+    /// Reads a code as typed, forgiving case, spacing and the letters
+    /// Crockford's alphabet reads as digits.
+    fn parse(code: &str) -> Result<Self> {
+        let code = code.trim();
+        let code = code
+            .strip_prefix("notes://beam/")
+            .unwrap_or(code)
+            .trim_end_matches('/');
+        let bad = || Error::Beam(format!("not a beam code: {code}"));
+        let digits = code
+            .chars()
+            .filter(|c| !matches!(c, '-' | ' '))
+            .map(|c| {
+                let c = match c.to_ascii_uppercase() {
+                    'O' => '0',
+                    'I' | 'L' => '1',
+                    c => c,
+                };
+                ALPHABET
+                    .iter()
+                    .position(|&a| a as char == c)
+                    .ok_or_else(bad)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        if digits.len() != 24 {
+            return Err(bad());
+        }
+        let mut bytes = Vec::with_capacity(15);
+        for chunk in digits.chunks(8) {
+            let value = chunk
+                .iter()
+                .fold(0u64, |value, &digit| value << 5 | digit as u64);
+            bytes.extend_from_slice(&value.to_be_bytes()[3..]);
+        }
+        let ip = Ipv4Addr::new(bytes[0], bytes[1], bytes[2], bytes[3]);
+        if !on_local_network(ip) {
+            return Err(Error::Beam(format!(
+                "the code leads to {ip}, which is not on a local network"
+            )));
+        }
+        let port = u16::from_be_bytes([bytes[4], bytes[5]]);
+        let mut secret = [0; SECRET_BYTES];
+        secret.copy_from_slice(&bytes[6..]);
+        Ok(Self {
+            address: SocketAddr::new(IpAddr::V4(ip), port),
+            secret,
+        })
+    }
+
+    /// What the fetch sends to prove it has the code.
+    fn token(&self) -> String {
+        hex::encode(derive(b"notesdesktop beam token", &self.secret))
+    }
+
+    fn cipher(&self) -> ChaCha20Poly1305 {
+        let key = derive(b"notesdesktop beam key", &self.secret);
+        ChaCha20Poly1305::new(&Key::from(key))
+    }
+
+    /// `plain` encrypted for whoever has the code, as nonce and ciphertext.
+    fn seal(&self, plain: &[u8]) -> Result<Vec<u8>> {
+        let nonce = rand::random::<[u8; 12]>();
+        let mut sealed = nonce.to_vec();
+        sealed.extend(
+            self.cipher()
+                .encrypt(&Nonce::from(nonce), plain)
+                .map_err(|err| Error::Crypto(err.to_string()))?,
+        );
+        Ok(sealed)
+    }
+
+    fn open(&self, sealed: &[u8]) -> Result<Vec<u8>> {
+        if sealed.len() < 12 {
+            return Err(Error::Beam("the sender sent nothing".into()));
+        }
+        let (nonce, ciphertext) = sealed.split_at(12);
+        let nonce = Nonce::try_from(nonce).map_err(|err| Error::Crypto(err.to_string()))?;
+        self.cipher()
+            .decrypt(&nonce, ciphertext)
+            .map_err(|_| Error::Beam("what came back could not be decrypted".into()))
+    }
+}
+
+/// Private, shared (carrier-grade NAT, as VPN meshes use) and link-local
+/// addresses: those a beam can come from.
+fn on_local_network(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    ip.is_private() || ip.is_link_local() || (a == 100 && (64..128).contains(&b))
+}
+
+fn derive(label: &[u8], secret: &[u8]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(label);
+    hasher.update(secret);
+    hasher.finalize().into()
+}
+
+/// Beams waiting to be fetched, managed as app state.
+#[derive(Default)]
+pub struct Beams {
+    open: Mutex<HashMap<String, Arc<AtomicBool>>>,
+}
+
+impl Beams {
+    /// Starts serving note `id` of `vault` and returns the code for it.
+    /// Reads and encrypts the note and its attachments, so it blocks.
+    pub fn send(&self, app: &AppHandle, vault: &Vault, id: &str) -> Result<Beam> {
+        if vault.storage.is_locked() {
+            return Err(Error::VaultLocked);
+        }
+        let (parcel, size) = pack(vault, id)?;
+        let offer = Offer {
+            sender: lan::config_of(&vault.storage)?.name,
+            address: String::new(),
+            title: parcel.title.clone(),
+            size,
+        };
+        let parcel = serde_json::to_vec(&parcel)?;
+        let server = Server::http((Ipv4Addr::UNSPECIFIED, 0))
+            .map_err(|err| Error::Beam(format!("cannot listen: {err}")))?;
+        let port = server
+            .server_addr()
+            .to_ip()
+            .map(|address| address.port())
+            .ok_or_else(|| Error::Beam("cannot listen on the network".into()))?;
+        let code = Code {
+            address: SocketAddr::new(local_address()?, port),
+            secret: rand::random(),
+        };
+        let sealed = Sealed {
+            offer: code.seal(&serde_json::to_vec(&offer)?)?,
+            parcel: code.seal(&parcel)?,
+        };
+        let text = code.encode()?;
+        let link = format!("notes://beam/{text}");
+        let qr = format!(
+            "data:image/png;base64,{}",
+            BASE64.encode(qr::render(&link)?)
+        );
+
+        let stop = Arc::new(AtomicBool::new(false));
+        self.lock().insert(text.clone(), Arc::clone(&stop));
+        let app = app.clone();
+        let token = code.token();
+        let shown = text.clone();
+        thread::spawn(move || {
+            let state = serve(&server, &token, &sealed, &stop);
+            app.state::<Beams>().lock().remove(&shown);
+            let _ = app.emit(EVENT, BeamEvent { code: shown, state });
+        });
+        Ok(Beam {
+            code: text,
+            link,
+            qr,
+            expires_at: now_millis() + TTL.as_millis() as i64,
+        })
+    }
+
+    /// Closes the beam showing `code`, if it is still open.
+    pub fn cancel(&self, code: &str) -> bool {
+        match self.lock().get(code.trim()) {
+            Some(stop) => {
+                stop.store(true, Ordering::Relaxed);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<String, Arc<AtomicBool>>> {
+        self.open.lock().expect("beams poisoned")
+    }
+}
+
+/// The offer and the parcel as they are served.
+struct Sealed {
+    offer: Vec<u8>,
+    parcel: Vec<u8>,
+}
+
+/// Answers fetches until one brings the right token, the beam runs out or
+/// it is cancelled. Asking for the offer does not close it.
+fn serve(server: &Server, token: &str, sealed: &Sealed, stop: &AtomicBool) -> BeamState {
+    let deadline = Instant::now() + TTL;
+    let mut attempts = 0;
+    while Instant::now() < deadline {
+        if stop.load(Ordering::Relaxed) {
+            return BeamState::Cancelled;
+        }
+        let request = match server.recv_timeout(POLL) {
+            Ok(Some(request)) => request,
+            Ok(None) => continue,
+            Err(_) => break,
+        };
+        if authorized(&request, token) {
+            let offer = request.url() == OFFER_PATH;
+            let body = match offer {
+                true => &sealed.offer,
+                false => &sealed.parcel,
+            };
+            let mut response = Response::from_data(body.clone());
+            if let Ok(header) = Header::from_bytes("Content-Type", "application/octet-stream") {
+                response.add_header(header);
+            }
+            if request.respond(response).is_ok() && !offer {
+                return BeamState::Sent;
+            }
+            continue;
+        }
+        let _ = request.respond(Response::empty(401));
+        attempts += 1;
+        if attempts >= MAX_ATTEMPTS {
+            return BeamState::Cancelled;
+        }
+    }
+    BeamState::Expired
+}
+
+fn authorized(request: &Request, token: &str) -> bool {
+    let given = request
+        .headers()
+        .iter()
+        .find(|header| header.field.equiv("Authorization"))
+        .and_then(|header| header.value.as_str().strip_prefix("Bearer "))
+        .unwrap_or_default();
+    http::constant_time_eq(given.as_bytes(), token.as_bytes())
+}
+
+/// The address other devices on the network reach this one at: the one
+/// the system would send from. Nothing is sent to find it.
+fn local_address() -> Result<IpAddr> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?;
+    socket
+        .connect((Ipv4Addr::new(192, 0, 2, 1), 9))
+        .and_then(|_| socket.local_addr())
+        .map(|address| address.ip())
+        .ok()
+        .filter(|ip| matches!(ip, IpAddr::V4(ip) if on_local_network(*ip)))
+        .ok_or_else(|| Error::Beam("this device is not on a local network".into()))
+}
+
+/// Note `id` and the attachments it uses, with a protected note's body in
+/// the clear, which only works while it is unlocked, and their size.
+fn pack(vault: &Vault, id: &str) -> Result<(Parcel, usize)> {
+    let status = protected::status(vault, id)?;
+    if status.protected && !status.unlocked {
+        return Err(Error::NoteLocked(id.to_owned()));
+    }
+    let note = protected::read(vault, id)?;
+    let mut size = note.body.len();
+    let mut files = Vec::new();
+    for path in attachments::references(&note) {
+        let Ok(data) = std::fs::read(vault.files.absolute(&path)) else {
+            continue;
+        };
+        size += data.len();
+        if size > MAX_BEAM_BYTES {
+            return Err(Error::Beam(format!(
+                "the note and its attachments are larger than {} MB",
+                MAX_BEAM_BYTES / (1024 * 1024)
+            )));
+        }
+        files.push(ParcelFile {
+            path,
+            data: BASE64.encode(data),
+        });
+    }
+    let parcel = Parcel {
+        title: note.title,
+        body: note.body,
+        folder: note.folder,
+        created_at: note.created_at,
+        updated_at: note.updated_at,
+        attachments: files,
+    };
+    Ok((parcel, size))
+}
+
+/// Asks the device `code` leads to what it is beaming, without taking it.
+pub async fn offer(code: &str) -> Result<Offer> {
+    let code = Code::parse(code)?;
+    let sealed = fetch(&code, OFFER_PATH, MAX_OFFER_BYTES).await?;
+    let mut offer: Offer = serde_json::from_slice(&code.open(&sealed)?)?;
+    offer.address = code.address.ip().to_string();
+    Ok(offer)
+}
+
+/// Fetches the note beamed at `code` into `vault` as a new note.
+pub async fn receive(vault: &Vault, code: &str) -> Result<Note> {
+    if vault.storage.is_locked() {
+        return Err(Error::VaultLocked);
+    }
+    let code = Code::parse(code)?;
+    let sealed = fetch(&code, "/", MAX_SEALED_BYTES).await?;
+    let parcel: Parcel = serde_json::from_slice(&code.open(&sealed)?)?;
+    unpack(vault, parcel)
+}
+
+/// `path` from the device `code` leads to, refusing anything over `limit`
+/// bytes rather than reading it all in.
+async fn fetch(code: &Code, path: &str, limit: usize) -> Result<Vec<u8>> {
+    let client = Client::builder().timeout(FETCH_TIMEOUT).build()?;
+    let mut response = client
+        .get(format!("http://{}{path}", code.address))
+        .bearer_auth(code.token())
+        .send()
+        .await
+        .map_err(|_| Error::Beam("nothing is being beamed at that code".into()))?;
+    match response.status() {
+        StatusCode::OK => {}
+        StatusCode::UNAUTHORIZED => {
+            return Err(Error::Beam("the code is wrong or has run out".into()))
+        }
+        status => return Err(Error::Beam(format!("the sender answered {status}"))),
+    }
+    let too_large = || Error::Beam("the sender sent more than a beam can hold".into());
+    if response
+        .content_length()
+        .is_some_and(|length| length > limit as u64)
+    {
+        return Err(too_large());
+    }
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        if body.len() + chunk.len() > limit {
+            return Err(too_large());
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok(body)
+}
+
+fn unpack(vault: &Vault, parcel: Parcel) -> Result<Note> {
+    let mut body = parcel.body;
+    for file in parcel.attachments {
+        if !NoteFiles::is_attachment_path(&file.path) {
+            continue;
+        }
+        let data = BASE64
+            .decode(&file.data)
+            .map_err(|err| Error::Beam(format!("bad attachment {}: {err}", file.path)))?;
+        let name = file.path.rsplit('/').next().unwrap_or(&file.path);
+        let saved = vault.files.save_attachment(name, &data)?;
+        if saved != file.path {
+            body = body.replace(&file.path, &saved);
+        }
+    }
+    let note = Note {
+        id: uuid::Uuid::new_v4().to_string(),
+        title: parcel.title,
+        body,
+        folder: parcel.folder.trim_matches('/').to_owned(),
+        created_at: parcel.created_at,
+        updated_at: parcel.updated_at,
+    };
+    vault.import_note(&note)?;
+    vault.autocommit(&format!("Receive \"{}\"", note.title))?;
+    Ok(note)
+}
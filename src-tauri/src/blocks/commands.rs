@@ -0,0 +1,9 @@
+use super::StoreStats;
+use crate::error::Result;
+use crate::vaults::Current;
+
+/// How much deduplication and compression save on history and attachments.
+#[tauri::command]
+pub async fn store_stats(vault: Current) -> Result<StoreStats> {
+    tauri::async_runtime::spawn_blocking(move || super::stats(&vault)).await?
+}
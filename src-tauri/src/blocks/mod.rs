@@ -0,0 +1,247 @@
+//! A content-addressed store of compressed blocks. Data is cut where a
+//! rolling hash over its last bytes hits a pattern rather than at fixed
+//! offsets, so an edit only changes the blocks around it and the others
+//! are shared with every earlier copy. Each distinct block is stored once,
+//! zstd-compressed and sealed like note bodies when the vault has a
+//! password.
+//!
+//! Revision bodies are kept here, see [`crate::history`]. Attachments are
+//! not: they are files in the Markdown mirror that other apps and sync
+//! read as they are, already stored once per content hash.
+
+pub mod commands;
+
+use std::collections::HashMap;
+use std::fs;
+
+use rusqlite::{params, Connection};
+use serde::Serialize;
+
+use crate::error::Result;
+use crate::storage::Storage;
+use crate::vault::Vault;
+
+/// No block is cut shorter, except the last.
+const MIN_SIZE: usize = 2 * 1024;
+/// Nor longer.
+const MAX_SIZE: usize = 64 * 1024;
+/// Bits of the hash that must be zero to cut, giving blocks of about
+/// `MIN_SIZE` plus 8 KiB.
+const CUT_BITS: u32 = 13;
+const CUT_MASK: u64 = !0 << (64 - CUT_BITS);
+const LEVEL: i32 = zstd::DEFAULT_COMPRESSION_LEVEL;
+
+/// Random values for the Gear hash, one per byte. Changing them moves every
+/// cut, so nothing stored before would be shared with what comes after.
+const GEAR: [u64; 256] = gear();
+
+const fn gear() -> [u64; 256] {
+    let mut table = [0; 256];
+    let mut state: u64 = 0;
+    let mut i = 0;
+    while i < table.len() {
+        // SplitMix64.
+        state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        table[i] = z ^ (z >> 31);
+        i += 1;
+    }
+    table
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StoreStats {
+    pub revisions: usize,
+    /// What the bodies of all revisions add up to.
+    pub revision_bytes: u64,
+    pub blocks: usize,
+    /// What the distinct blocks hold before compression.
+    pub block_bytes: u64,
+    /// What history takes in the database.
+    pub stored_bytes: u64,
+    /// Bodies saved before the block store, still whole until the vault
+    /// doctor repacks them.
+    pub unpacked: usize,
+    /// `revision_bytes` over `stored_bytes`; 1 means nothing is saved.
+    pub dedup_ratio: f64,
+    pub attachments: usize,
+    /// Attachment sizes counted once for every note linking them.
+    pub attachment_linked_bytes: u64,
+    pub attachment_bytes: u64,
+    pub attachment_dedup_ratio: f64,
+}
+
+/// Where to cut `data` into blocks.
+pub fn split(data: &[u8]) -> Vec<&[u8]> {
+    let mut blocks = Vec::new();
+    let mut rest = data;
+    while !rest.is_empty() {
+        let (block, tail) = rest.split_at(cut(rest));
+        blocks.push(block);
+        rest = tail;
+    }
+    blocks
+}
+
+/// Length of the first block of `data`.
+fn cut(data: &[u8]) -> usize {
+    if data.len() <= MIN_SIZE {
+        return data.len();
+    }
+    let end = data.len().min(MAX_SIZE);
+    let mut hash = 0u64;
+    for (i, &byte) in data.iter().enumerate().take(end).skip(MIN_SIZE) {
+        hash = (hash << 1).wrapping_add(GEAR[byte as usize]);
+        if hash & CUT_MASK == 0 {
+            return i + 1;
+        }
+    }
+    end
+}
+
+/// Stores `data`, writing only the blocks not stored yet, and returns the
+/// hashes of its blocks in order.
+pub fn put(conn: &Connection, storage: &Storage, data: &[u8]) -> Result<Vec<String>> {
+    let mut known = conn.prepare_cached("SELECT EXISTS(SELECT 1 FROM blocks WHERE hash = ?1)")?;
+    let mut insert =
+        conn.prepare_cached("INSERT INTO blocks (hash, data, size) VALUES (?1, ?2, ?3)")?;
+    let mut hashes = Vec::new();
+    for block in split(data) {
+        let hash = storage.content_hash(block)?;
+        if !known.query_row([&hash], |row| row.get::<_, bool>(0))? {
+            let compressed = zstd::bulk::compress(block, LEVEL)?;
+            insert.execute(params![
+                hash,
+                storage.seal_bytes(&compressed)?,
+                block.len() as i64
+            ])?;
+        }
+        hashes.push(hash);
+    }
+    Ok(hashes)
+}
+
+/// Puts the blocks `hashes` back together.
+pub fn read(conn: &Connection, storage: &Storage, hashes: &[String]) -> Result<Vec<u8>> {
+    let mut select = conn.prepare_cached("SELECT data, size FROM blocks WHERE hash = ?1")?;
+    let mut data = Vec::new();
+    for hash in hashes {
+        let (stored, size): (Vec<u8>, i64) =
+            select.query_row([hash], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        let compressed = storage.open_bytes(stored)?;
+        data.extend(zstd::bulk::decompress(&compressed, size as usize)?);
+    }
+    Ok(data)
+}
+
+/// Files every block under the name `digest` gives it; see
+/// [`crate::history::rehash`].
+pub(crate) fn rehash(
+    conn: &Connection,
+    storage: &Storage,
+    digest: &dyn Fn(&[u8]) -> String,
+) -> Result<()> {
+    let blocks: Vec<String> = {
+        let mut stmt = conn.prepare("SELECT hash FROM blocks")?;
+        let hashes = stmt
+            .query_map([], |row| row.get(0))?
+            .collect::<rusqlite::Result<_>>()?;
+        hashes
+    };
+    for old in blocks {
+        let new = digest(&read(conn, storage, std::slice::from_ref(&old))?);
+        if new == old {
+            continue;
+        }
+        conn.execute(
+            "INSERT OR IGNORE INTO blocks (hash, data, size)
+             SELECT ?2, data, size FROM blocks WHERE hash = ?1",
+            params![old, new],
+        )?;
+        conn.execute(
+            "UPDATE blob_blocks SET block = ?2 WHERE block = ?1",
+            params![old, new],
+        )?;
+        conn.execute("DELETE FROM blocks WHERE hash = ?1", [old])?;
+    }
+    Ok(())
+}
+
+/// Drops blocks no blob is made of any more.
+pub fn prune(conn: &Connection) -> Result<usize> {
+    let removed = conn.execute(
+        "DELETE FROM blocks WHERE hash NOT IN (SELECT block FROM blob_blocks)",
+        [],
+    )?;
+    Ok(removed)
+}
+
+/// How much space history and attachments take against what they hold.
+pub fn stats(vault: &Vault) -> Result<StoreStats> {
+    let ratio = |logical: u64, stored: u64| {
+        if stored == 0 {
+            1.0
+        } else {
+            logical as f64 / stored as f64
+        }
+    };
+    let conn = vault.storage.conn();
+    let (revisions, revision_bytes): (i64, i64) = conn.query_row(
+        "SELECT COUNT(*), COALESCE(SUM(size), 0) FROM revisions",
+        [],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+    let (blocks, block_bytes, block_stored): (i64, i64, i64) = conn.query_row(
+        "SELECT COUNT(*), COALESCE(SUM(size), 0), COALESCE(SUM(LENGTH(data)), 0) FROM blocks",
+        [],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+    )?;
+    let (unpacked, whole_stored): (i64, i64) = conn.query_row(
+        "SELECT COUNT(*), COALESCE(SUM(LENGTH(CAST(content AS BLOB))), 0)
+         FROM blobs WHERE NOT blocked",
+        [],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+    let linked: Vec<String> = {
+        let mut stmt = conn.prepare("SELECT path FROM attachment_refs")?;
+        let paths = stmt
+            .query_map([], |row| row.get(0))?
+            .collect::<rusqlite::Result<_>>()?;
+        paths
+    };
+    drop(conn);
+
+    let sizes: HashMap<String, u64> = fs::read_dir(vault.files.attachments_dir())
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|entry| {
+            let meta = entry.metadata().ok().filter(|meta| meta.is_file())?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            Some((name, meta.len()))
+        })
+        .collect();
+    let attachment_linked_bytes = linked
+        .iter()
+        .filter_map(|rel| sizes.get(rel.rsplit('/').next().unwrap_or(rel)))
+        .sum();
+    let attachment_bytes = sizes.values().sum();
+
+    let stored_bytes = (block_stored + whole_stored) as u64;
+    Ok(StoreStats {
+        revisions: revisions as usize,
+        revision_bytes: revision_bytes as u64,
+        blocks: blocks as usize,
+        block_bytes: block_bytes as u64,
+        stored_bytes,
+        unpacked: unpacked as usize,
+        dedup_ratio: ratio(revision_bytes as u64, stored_bytes),
+        attachments: sizes.len(),
+        attachment_linked_bytes,
+        attachment_bytes,
+        attachment_dedup_ratio: ratio(attachment_linked_bytes, attachment_bytes),
+    })
+}
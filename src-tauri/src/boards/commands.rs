@@ -0,0 +1,98 @@
+use std::sync::Arc;
+
+use tauri::{AppHandle, Manager, Window};
+
+use super::{Board, BoardSummary};
+use crate::error::Result;
+use crate::vault::Vault;
+use crate::vaults::{Current, Vaults};
+
+/// A new board with the given columns, or "To do", "Doing" and "Done".
+#[tauri::command]
+pub async fn create_board(
+    app: AppHandle,
+    window: Window,
+    name: String,
+    columns: Option<Vec<String>>,
+) -> Result<Board> {
+    let (vault_id, vault) = vault_of(&app, &window)?;
+    let board = super::create(&vault.storage, &name, &columns.unwrap_or_default())?;
+    super::changed(&app, &vault_id, &board.id, false);
+    Ok(board)
+}
+
+#[tauri::command]
+pub async fn list_boards(vault: Current) -> Result<Vec<BoardSummary>> {
+    super::list(&vault.storage)
+}
+
+#[tauri::command]
+pub async fn get_board(vault: Current, id: String) -> Result<Board> {
+    super::get(&vault.storage, &id)
+}
+
+#[tauri::command]
+pub async fn delete_board(app: AppHandle, window: Window, id: String) -> Result<()> {
+    let (vault_id, vault) = vault_of(&app, &window)?;
+    super::delete(&vault.storage, &id)?;
+    super::changed(&app, &vault_id, &id, true);
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn add_board_column(
+    app: AppHandle,
+    window: Window,
+    board_id: String,
+    name: String,
+) -> Result<Board> {
+    let (vault_id, vault) = vault_of(&app, &window)?;
+    let board = super::add_column(&vault.storage, &board_id, &name)?;
+    super::changed(&app, &vault_id, &board.id, false);
+    Ok(board)
+}
+
+#[tauri::command]
+pub async fn add_board_card(
+    app: AppHandle,
+    window: Window,
+    board_id: String,
+    column_id: String,
+    note_id: String,
+) -> Result<Board> {
+    let (vault_id, vault) = vault_of(&app, &window)?;
+    let board = super::add_card(&vault.storage, &board_id, &column_id, &note_id)?;
+    super::changed(&app, &vault_id, &board.id, false);
+    Ok(board)
+}
+
+/// Drops a card at 0-based `position` in a column of its board.
+#[tauri::command]
+pub async fn move_card(
+    app: AppHandle,
+    window: Window,
+    card_id: String,
+    column_id: String,
+    position: usize,
+) -> Result<Board> {
+    let (vault_id, vault) = vault_of(&app, &window)?;
+    let board = super::move_card(&vault.storage, &card_id, &column_id, position)?;
+    super::changed(&app, &vault_id, &board.id, false);
+    Ok(board)
+}
+
+#[tauri::command]
+pub async fn remove_board_card(app: AppHandle, window: Window, card_id: String) -> Result<Board> {
+    let (vault_id, vault) = vault_of(&app, &window)?;
+    let board = super::remove_card(&vault.storage, &card_id)?;
+    super::changed(&app, &vault_id, &board.id, false);
+    Ok(board)
+}
+
+/// The calling window's vault and its id, which change events carry.
+fn vault_of(app: &AppHandle, window: &Window) -> Result<(String, Arc<Vault>)> {
+    let vaults = app.state::<Vaults>();
+    let vault_id = vaults.id_of(window.label());
+    let vault = vaults.get(app, &vault_id)?;
+    Ok((vault_id, vault))
+}
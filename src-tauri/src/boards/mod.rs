@@ -0,0 +1,354 @@
+pub mod commands;
+
+use rusqlite::{params, OptionalExtension, Transaction};
+use serde::Serialize;
+use tauri::{AppHandle, Emitter};
+
+use crate::error::{Error, Result};
+use crate::storage::{now_millis, Storage};
+
+/// Event broadcast to every window with a [`BoardChange`] whenever a board
+/// is created, rearranged or deleted.
+pub const CHANGED_EVENT: &str = "board-changed";
+
+/// Columns of a board created without any.
+const DEFAULT_COLUMNS: [&str; 3] = ["To do", "Doing", "Done"];
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BoardChange {
+    pub vault_id: String,
+    pub board_id: String,
+    pub removed: bool,
+}
+
+/// A board as listed, without its columns.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BoardSummary {
+    pub id: String,
+    pub name: String,
+    pub card_count: usize,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Board {
+    pub id: String,
+    pub name: String,
+    /// Left to right.
+    pub columns: Vec<Column>,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Column {
+    pub id: String,
+    pub name: String,
+    /// Top to bottom.
+    pub cards: Vec<BoardCard>,
+}
+
+/// A note placed on a board. A note is on a board at most once, but can
+/// be on several boards.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BoardCard {
+    pub id: String,
+    pub note_id: String,
+    pub title: String,
+    pub folder: String,
+}
+
+pub fn create(storage: &Storage, name: &str, columns: &[String]) -> Result<Board> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(Error::InvalidInput("a board needs a name".into()));
+    }
+    let columns: Vec<&str> = match columns.is_empty() {
+        true => DEFAULT_COLUMNS.to_vec(),
+        false => columns.iter().map(|column| column.trim()).collect(),
+    };
+    if columns.iter().any(|column| column.is_empty()) {
+        return Err(Error::InvalidInput("a column needs a name".into()));
+    }
+    let id = uuid::Uuid::new_v4().to_string();
+    let now = now_millis();
+    let mut conn = storage.conn();
+    let tx = conn.transaction()?;
+    tx.execute(
+        "INSERT INTO boards (id, name, created_at, updated_at) VALUES (?1, ?2, ?3, ?3)",
+        params![id, name, now],
+    )?;
+    for (position, column) in columns.iter().enumerate() {
+        tx.execute(
+            "INSERT INTO board_columns (id, board_id, name, position) VALUES (?1, ?2, ?3, ?4)",
+            params![
+                uuid::Uuid::new_v4().to_string(),
+                id,
+                column,
+                position as i64
+            ],
+        )?;
+    }
+    tx.commit()?;
+    drop(conn);
+    get(storage, &id)
+}
+
+/// Every board by name.
+pub fn list(storage: &Storage) -> Result<Vec<BoardSummary>> {
+    let conn = storage.conn();
+    let mut stmt = conn.prepare(
+        "SELECT id, name, created_at, updated_at,
+                (SELECT COUNT(*) FROM board_cards c
+                 WHERE c.board_id = boards.id
+                   AND c.note_id NOT IN (SELECT note_id FROM trash))
+         FROM boards ORDER BY name COLLATE NOCASE, id",
+    )?;
+    let boards = stmt
+        .query_map([], |row| {
+            Ok(BoardSummary {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                created_at: row.get(2)?,
+                updated_at: row.get(3)?,
+                card_count: row.get::<_, i64>(4)? as usize,
+            })
+        })?
+        .collect::<rusqlite::Result<_>>()?;
+    Ok(boards)
+}
+
+/// A board with its columns and cards. Notes in the trash are left off
+/// until restored.
+pub fn get(storage: &Storage, id: &str) -> Result<Board> {
+    let conn = storage.conn();
+    let (name, created_at, updated_at) = conn
+        .query_row(
+            "SELECT name, created_at, updated_at FROM boards WHERE id = ?1",
+            [id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .optional()?
+        .ok_or_else(|| no_board(id))?;
+
+    let mut stmt = conn
+        .prepare("SELECT id, name FROM board_columns WHERE board_id = ?1 ORDER BY position, id")?;
+    let mut columns = stmt
+        .query_map([id], |row| {
+            Ok(Column {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                cards: Vec::new(),
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    let mut stmt = conn.prepare(
+        "SELECT c.column_id, c.id, c.note_id, n.title, n.folder
+         FROM board_cards c JOIN notes n ON n.id = c.note_id
+         WHERE c.board_id = ?1 AND c.note_id NOT IN (SELECT note_id FROM trash)
+         ORDER BY c.position, c.id",
+    )?;
+    let mut rows = stmt.query([id])?;
+    while let Some(row) = rows.next()? {
+        let column_id: String = row.get(0)?;
+        let card = BoardCard {
+            id: row.get(1)?,
+            note_id: row.get(2)?,
+            title: row.get(3)?,
+            folder: row.get(4)?,
+        };
+        if let Some(column) = columns.iter_mut().find(|column| column.id == column_id) {
+            column.cards.push(card);
+        }
+    }
+    Ok(Board {
+        id: id.to_owned(),
+        name,
+        columns,
+        created_at,
+        updated_at,
+    })
+}
+
+pub fn delete(storage: &Storage, id: &str) -> Result<()> {
+    let deleted = storage
+        .conn()
+        .execute("DELETE FROM boards WHERE id = ?1", [id])?;
+    if deleted == 0 {
+        return Err(no_board(id));
+    }
+    Ok(())
+}
+
+/// Adds a column on the right of board `board_id`.
+pub fn add_column(storage: &Storage, board_id: &str, name: &str) -> Result<Board> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(Error::InvalidInput("a column needs a name".into()));
+    }
+    let mut conn = storage.conn();
+    let tx = conn.transaction()?;
+    touch(&tx, board_id)?;
+    tx.execute(
+        "INSERT INTO board_columns (id, board_id, name, position)
+         SELECT ?1, ?2, ?3, COALESCE(MAX(position) + 1, 0) FROM board_columns WHERE board_id = ?2",
+        params![uuid::Uuid::new_v4().to_string(), board_id, name],
+    )?;
+    tx.commit()?;
+    drop(conn);
+    get(storage, board_id)
+}
+
+/// Puts note `note_id` at the bottom of a column.
+pub fn add_card(
+    storage: &Storage,
+    board_id: &str,
+    column_id: &str,
+    note_id: &str,
+) -> Result<Board> {
+    if !storage.has_note(note_id)? {
+        return Err(Error::NoteNotFound(note_id.to_owned()));
+    }
+    let mut conn = storage.conn();
+    let tx = conn.transaction()?;
+    touch(&tx, board_id)?;
+    check_column(&tx, board_id, column_id)?;
+    let on_board: bool = tx.query_row(
+        "SELECT EXISTS(SELECT 1 FROM board_cards WHERE board_id = ?1 AND note_id = ?2)",
+        params![board_id, note_id],
+        |row| row.get(0),
+    )?;
+    if on_board {
+        return Err(Error::InvalidInput(
+            "the note is already on this board".into(),
+        ));
+    }
+    tx.execute(
+        "INSERT INTO board_cards (id, board_id, column_id, note_id, position)
+         SELECT ?1, ?2, ?3, ?4, COALESCE(MAX(position) + 1, 0) FROM board_cards WHERE column_id = ?3",
+        params![uuid::Uuid::new_v4().to_string(), board_id, column_id, note_id],
+    )?;
+    tx.commit()?;
+    drop(conn);
+    get(storage, board_id)
+}
+
+/// Moves card `card_id` to 0-based `position` within column `column_id` of
+/// the same board, shifting the cards below it down. A position past the
+/// end puts it at the bottom.
+pub fn move_card(
+    storage: &Storage,
+    card_id: &str,
+    column_id: &str,
+    position: usize,
+) -> Result<Board> {
+    let mut conn = storage.conn();
+    let tx = conn.transaction()?;
+    let (board_id, from) = card_place(&tx, card_id)?;
+    check_column(&tx, &board_id, column_id)?;
+    touch(&tx, &board_id)?;
+    if from != column_id {
+        let rest = column_cards(&tx, &from, card_id)?;
+        arrange(&tx, &from, &rest)?;
+    }
+    let mut cards = column_cards(&tx, column_id, card_id)?;
+    cards.insert(position.min(cards.len()), card_id.to_owned());
+    arrange(&tx, column_id, &cards)?;
+    tx.commit()?;
+    drop(conn);
+    get(storage, &board_id)
+}
+
+/// Takes a card off its board; the note itself stays.
+pub fn remove_card(storage: &Storage, card_id: &str) -> Result<Board> {
+    let mut conn = storage.conn();
+    let tx = conn.transaction()?;
+    let (board_id, column_id) = card_place(&tx, card_id)?;
+    touch(&tx, &board_id)?;
+    tx.execute("DELETE FROM board_cards WHERE id = ?1", [card_id])?;
+    let rest = column_cards(&tx, &column_id, card_id)?;
+    arrange(&tx, &column_id, &rest)?;
+    tx.commit()?;
+    drop(conn);
+    get(storage, &board_id)
+}
+
+/// Tells every window about a change to board `board_id` of vault
+/// `vault_id`.
+pub fn changed(app: &AppHandle, vault_id: &str, board_id: &str, removed: bool) {
+    let change = BoardChange {
+        vault_id: vault_id.to_owned(),
+        board_id: board_id.to_owned(),
+        removed,
+    };
+    let _ = app.emit(CHANGED_EVENT, change);
+}
+
+/// Bumps a board's `updated_at`, failing if there is no such board.
+fn touch(tx: &Transaction, board_id: &str) -> Result<()> {
+    let updated = tx.execute(
+        "UPDATE boards SET updated_at = ?2 WHERE id = ?1",
+        params![board_id, now_millis()],
+    )?;
+    match updated {
+        0 => Err(no_board(board_id)),
+        _ => Ok(()),
+    }
+}
+
+fn check_column(tx: &Transaction, board_id: &str, column_id: &str) -> Result<()> {
+    let found: bool = tx.query_row(
+        "SELECT EXISTS(SELECT 1 FROM board_columns WHERE id = ?1 AND board_id = ?2)",
+        params![column_id, board_id],
+        |row| row.get(0),
+    )?;
+    match found {
+        true => Ok(()),
+        false => Err(Error::InvalidInput(format!(
+            "no column {column_id} on this board"
+        ))),
+    }
+}
+
+/// The board and column card `card_id` is in.
+fn card_place(tx: &Transaction, card_id: &str) -> Result<(String, String)> {
+    tx.query_row(
+        "SELECT board_id, column_id FROM board_cards WHERE id = ?1",
+        [card_id],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )
+    .optional()?
+    .ok_or_else(|| Error::InvalidInput(format!("no board card {card_id}")))
+}
+
+/// The cards of a column in order, leaving out `except`.
+fn column_cards(tx: &Transaction, column_id: &str, except: &str) -> Result<Vec<String>> {
+    let mut stmt = tx.prepare(
+        "SELECT id FROM board_cards WHERE column_id = ?1 AND id != ?2 ORDER BY position, id",
+    )?;
+    let ids = stmt
+        .query_map(params![column_id, except], |row| row.get(0))?
+        .collect::<rusqlite::Result<_>>()?;
+    Ok(ids)
+}
+
+/// Puts `cards` in column `column_id`, numbered from the top.
+fn arrange(tx: &Transaction, column_id: &str, cards: &[String]) -> Result<()> {
+    let mut stmt =
+        tx.prepare("UPDATE board_cards SET column_id = ?2, position = ?3 WHERE id = ?1")?;
+    for (position, id) in cards.iter().enumerate() {
+        stmt.execute(params![id, column_id, position as i64])?;
+    }
+    Ok(())
+}
+
+fn no_board(id: &str) -> Error {
+    Error::InvalidInput(format!("no board {id}"))
+}
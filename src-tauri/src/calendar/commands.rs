@@ -0,0 +1,57 @@
+use std::path::PathBuf;
+
+use tauri::{AppHandle, Manager};
+
+use super::CalendarConfig;
+use crate::api::{self, Api};
+use crate::error::{Error, Result};
+use crate::vaults::{self, Current};
+
+/// Writes reminders and dated tasks to an `.ics` file once.
+#[tauri::command]
+pub async fn export_calendar(vault: Current, path: PathBuf) -> Result<()> {
+    tauri::async_runtime::spawn_blocking(move || super::export(&vault, &path)).await?
+}
+
+#[tauri::command]
+pub async fn get_calendar_config(vault: Current) -> Result<CalendarConfig> {
+    super::config_of(&vault.storage)
+}
+
+/// Sets the `.ics` file kept up to date, writing it straight away.
+#[tauri::command]
+pub async fn set_calendar_config(vault: Current, config: CalendarConfig) -> Result<()> {
+    super::set_config(&vault.storage, &config)?;
+    if let Some(file) = config.file {
+        tauri::async_runtime::spawn_blocking(move || super::export(&vault, &file)).await??;
+    }
+    Ok(())
+}
+
+/// The URL a calendar app can subscribe to, served by the local API for the
+/// primary vault. It carries a token of its own, since calendar apps cannot
+/// send one as a header, which reads the feed and nothing else.
+#[tauri::command]
+pub async fn calendar_url(app: AppHandle) -> Result<String> {
+    let token = api::calendar_token(&vaults::primary(&app)?.storage)?;
+    feed_url(&app, &token)
+}
+
+/// Gives the calendar feed a new URL, ending the subscriptions to the old
+/// one.
+#[tauri::command]
+pub async fn rotate_calendar_url(app: AppHandle) -> Result<String> {
+    let token = api::rotate_calendar_token(&vaults::primary(&app)?.storage)?;
+    feed_url(&app, &token)
+}
+
+fn feed_url(app: &AppHandle, token: &str) -> Result<String> {
+    let Some(port) = app.state::<Api>().port() else {
+        return Err(Error::InvalidInput(
+            "turn on the local API to subscribe to the calendar".into(),
+        ));
+    };
+    Ok(format!(
+        "http://127.0.0.1:{port}/calendar.ics?token={token}"
+    ))
+}
@@ -0,0 +1,226 @@
+//! Reminders and open tasks with due dates as an iCalendar feed, so a
+//! system calendar can show them. The feed is written on request, kept up
+//! to date in a file of the user's choosing, and served by the local API
+//! at `/calendar.ics` for calendars that subscribe by URL.
+
+pub mod commands;
+
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tauri::{AppHandle, Emitter};
+
+use crate::error::Result;
+use crate::storage::Storage;
+use crate::tasks::{TaskFilter, DUE_FORMAT};
+use crate::vault::Vault;
+use crate::{atomic, links, reminders};
+
+/// Event carrying the message of a failed write of the calendar file.
+pub const ERROR_EVENT: &str = "calendar-error";
+
+const CONFIG_KEY: &str = "calendar.config";
+/// How often the calendar file is brought up to date.
+const INTERVAL: Duration = Duration::from_secs(60);
+/// How long a reminder's event lasts in the calendar.
+const REMINDER_MINUTES: i64 = 15;
+/// Lines longer than this many bytes are folded, as RFC 5545 asks.
+const MAX_LINE: usize = 75;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct CalendarConfig {
+    /// `.ics` file kept up to date with the vault, if any.
+    pub file: Option<PathBuf>,
+    /// Fired reminders stay in the calendar too.
+    pub include_fired: bool,
+}
+
+pub fn config_of(storage: &Storage) -> Result<CalendarConfig> {
+    match storage.meta(CONFIG_KEY)? {
+        Some(json) => Ok(serde_json::from_str(&json)?),
+        None => Ok(CalendarConfig::default()),
+    }
+}
+
+pub fn set_config(storage: &Storage, config: &CalendarConfig) -> Result<()> {
+    storage.set_meta(CONFIG_KEY, &serde_json::to_string(config)?)
+}
+
+/// The vault's reminders and open, dated tasks as an `.ics` document.
+/// Reminders are short timed events with an alarm; tasks are all-day
+/// events on their due date.
+pub fn ics(vault: &Vault) -> Result<String> {
+    let config = config_of(&vault.storage)?;
+    // Events are stamped with their note's last edit rather than the time
+    // of writing, so the feed only changes when something in it does.
+    let notes: HashMap<String, (String, i64)> = vault
+        .storage
+        .list_notes(None)?
+        .into_iter()
+        .map(|note| (note.id, (note.title, note.updated_at)))
+        .collect();
+
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_owned(),
+        "VERSION:2.0".to_owned(),
+        "PRODID:-//Notes Desktop//Reminders//EN".to_owned(),
+        "CALSCALE:GREGORIAN".to_owned(),
+        "X-WR-CALNAME:Notes".to_owned(),
+    ];
+    for reminder in reminders::list(&vault.storage, None, config.include_fired)? {
+        // Reminders of trashed notes stay behind until the note is purged.
+        let Some((title, updated_at)) = notes.get(&reminder.note_id) else {
+            continue;
+        };
+        let summary = match (reminder.message.trim(), &reminder.task) {
+            ("", Some(task)) => task.clone(),
+            ("", None) => title.clone(),
+            (message, _) => message.to_owned(),
+        };
+        lines.extend([
+            "BEGIN:VEVENT".to_owned(),
+            format!("UID:reminder-{}@notesdesktop", reminder.id),
+            format!("DTSTAMP:{}", timestamp(*updated_at)),
+            format!("DTSTART:{}", timestamp(reminder.due_at)),
+            format!(
+                "DTEND:{}",
+                timestamp(reminder.due_at + REMINDER_MINUTES * 60 * 1000)
+            ),
+            format!("SUMMARY:{}", escape(&summary)),
+            format!("DESCRIPTION:{}", escape(title)),
+            format!("URL:{}", links::note_url(&reminder.note_id, None)),
+            "BEGIN:VALARM".to_owned(),
+            "ACTION:DISPLAY".to_owned(),
+            format!("DESCRIPTION:{}", escape(&summary)),
+            "TRIGGER:PT0S".to_owned(),
+            "END:VALARM".to_owned(),
+            "END:VEVENT".to_owned(),
+        ]);
+    }
+    let filter = TaskFilter {
+        done: Some(false),
+        ..Default::default()
+    };
+    for task in vault.tasks.list(&filter) {
+        let Some(due) = task
+            .due
+            .as_deref()
+            .and_then(|due| NaiveDate::parse_from_str(due, DUE_FORMAT).ok())
+        else {
+            continue;
+        };
+        let updated_at = notes.get(&task.note_id).map_or(0, |(_, at)| *at);
+        // By note and text rather than line, so editing around a task keeps
+        // its event.
+        let uid = hex::encode(&Sha256::digest(format!("{}\0{}", task.note_id, task.text))[..16]);
+        lines.extend([
+            "BEGIN:VEVENT".to_owned(),
+            format!("UID:task-{uid}@notesdesktop"),
+            format!("DTSTAMP:{}", timestamp(updated_at)),
+            format!("DTSTART;VALUE=DATE:{}", due.format("%Y%m%d")),
+            format!(
+                "DTEND;VALUE=DATE:{}",
+                due.succ_opt().unwrap_or(due).format("%Y%m%d")
+            ),
+            format!("SUMMARY:{}", escape(&task.text)),
+            format!("DESCRIPTION:{}", escape(&task.note_title)),
+            format!("URL:{}", links::note_url(&task.note_id, None)),
+            "TRANSP:TRANSPARENT".to_owned(),
+            "END:VEVENT".to_owned(),
+        ]);
+    }
+    lines.push("END:VCALENDAR".to_owned());
+
+    let mut out = String::new();
+    for line in lines {
+        fold(&mut out, &line);
+    }
+    Ok(out)
+}
+
+/// Writes the feed to `path`.
+pub fn export(vault: &Vault, path: &Path) -> Result<()> {
+    atomic::write(path, ics(vault)?.as_bytes())?;
+    Ok(())
+}
+
+/// Keeps the configured calendar file up to date, writing it only when its
+/// content changed. Failures are reported through [`ERROR_EVENT`].
+pub fn spawn_periodic(app: AppHandle, vault: Arc<Vault>) {
+    tauri::async_runtime::spawn(async move {
+        let mut last = None;
+        while !vault.is_closed() {
+            let worker = Arc::clone(&vault);
+            let previous = last.clone();
+            let result = tauri::async_runtime::spawn_blocking(move || -> Result<_> {
+                let Some(file) = config_of(&worker.storage)?.file else {
+                    return Ok(None);
+                };
+                let written = Some((file, ics(&worker)?));
+                if written != previous {
+                    if let Some((file, ics)) = &written {
+                        atomic::write(file, ics.as_bytes())?;
+                    }
+                }
+                Ok(written)
+            })
+            .await
+            .unwrap_or_else(|err| Err(err.into()));
+            match result {
+                Ok(written) => last = written,
+                Err(err) => {
+                    let _ = app.emit(ERROR_EVENT, err.to_string());
+                }
+            }
+            tokio::time::sleep(INTERVAL).await;
+        }
+    });
+}
+
+fn timestamp(millis: i64) -> String {
+    DateTime::<Utc>::from_timestamp_millis(millis)
+        .unwrap_or_default()
+        .format("%Y%m%dT%H%M%SZ")
+        .to_string()
+}
+
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' | ';' | ',' => {
+                out.push('\\');
+                out.push(c);
+            }
+            '\n' => out.push_str("\\n"),
+            '\r' => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Appends `line` with CRLF, folded into continuation lines where it is
+/// longer than [`MAX_LINE`] bytes, never inside a character.
+fn fold(out: &mut String, line: &str) {
+    let mut start = 0;
+    let mut limit = MAX_LINE;
+    while line.len() - start > limit {
+        let mut end = start + limit;
+        while !line.is_char_boundary(end) {
+            end -= 1;
+        }
+        let _ = write!(out, "{}\r\n ", &line[start..end]);
+        start = end;
+        // The leading space counts towards the continuation's length.
+        limit = MAX_LINE - 1;
+    }
+    let _ = write!(out, "{}\r\n", &line[start..]);
+}
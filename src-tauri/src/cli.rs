@@ -0,0 +1,131 @@
+//! `notesdesktop --new <title> | --search <query> | --show <id> |
+//! --export pdf|epub <id>...`: note operations from a terminal, on the same
+//! vaults as the app, without opening a window.
+
+use std::path::PathBuf;
+
+use crate::error::{Error, Result};
+use crate::export::{self, commands::free_path, epub::EpubMetadata, pdf::PdfOptions};
+use crate::files;
+use crate::headless;
+use crate::protected;
+use crate::trash;
+
+const COMMANDS: &[&str] = &["--new", "--search", "--show", "--export", "--help"];
+const DEFAULT_LIMIT: usize = 20;
+
+const USAGE: &str = "\
+usage: notesdesktop [--vault <id>] <command>
+
+  --new <title> [--body <markdown>] [--folder <path>]
+                              create a note and print its id
+  --search <query> [--limit <n>]
+                              print the id and title of matching notes
+  --show <id>                 print the Markdown of a note
+  --export pdf|epub <id>... [--out <file>]
+                              write notes to a PDF or EPUB file
+  --help                      show this message
+
+Encrypted vaults are unlocked with the password in NOTES_PASSWORD.";
+
+/// Runs a command if one was given, returning the exit code. `None` starts
+/// the app as usual, so file paths and links still open in a window.
+pub fn run_from_args() -> Option<i32> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let command = args.iter().find(|arg| COMMANDS.contains(&arg.as_str()))?;
+    if command == "--help" {
+        println!("{USAGE}");
+        return Some(0);
+    }
+    Some(match run(command, &args) {
+        Ok(()) => 0,
+        Err(err) => {
+            eprintln!("notesdesktop: {err}");
+            1
+        }
+    })
+}
+
+fn run(command: &str, args: &[String]) -> Result<()> {
+    let vault = headless::open_vault(option(args, "--vault")?.as_deref())?;
+    match command {
+        "--new" => {
+            let title = required(args, "--new")?;
+            let body = option(args, "--body")?.unwrap_or_default();
+            let folder = option(args, "--folder")?.unwrap_or_default();
+            let note = vault.create_note(&title, &body, folder.trim_matches('/'))?;
+            println!("{}", note.id);
+        }
+        "--search" => {
+            let query = required(args, "--search")?;
+            let limit = match option(args, "--limit")? {
+                Some(limit) => limit
+                    .parse()
+                    .map_err(|_| Error::InvalidInput(format!("not a number: {limit}")))?,
+                None => DEFAULT_LIMIT,
+            };
+            for hit in vault.search_notes(&query, limit, false)? {
+                if !trash::is_trashed(&vault.storage, &hit.id)? {
+                    println!("{}\t{}", hit.id, hit.title);
+                }
+            }
+        }
+        "--show" => {
+            let id = required(args, "--show")?;
+            // Protected notes stay locked, as their passphrase can only be
+            // given in the app.
+            print!("{}", protected::readable(&vault, &id)?.body);
+        }
+        "--export" => {
+            let format = required(args, "--export")?;
+            let ids = positionals(args, "--export");
+            let first = ids
+                .first()
+                .ok_or_else(|| Error::InvalidInput("no notes to export".into()))?;
+            let dest = match option(args, "--out")? {
+                Some(out) => PathBuf::from(out),
+                None => {
+                    let stem = files::sanitize(&protected::readable(&vault, first)?.title);
+                    free_path(std::env::current_dir()?, &stem, &format)
+                }
+            };
+            match format.as_str() {
+                "pdf" => export::pdf::export(&vault, &ids, &PdfOptions::default(), &dest)?,
+                "epub" => export::epub::export(&vault, &ids, &EpubMetadata::default(), &dest)?,
+                format => {
+                    return Err(Error::InvalidInput(format!(
+                        "cannot export to {format}; use pdf or epub"
+                    )))
+                }
+            }
+            println!("{}", dest.display());
+        }
+        _ => unreachable!("commands are matched in run_from_args"),
+    }
+    Ok(())
+}
+
+/// The value after `flag`, if the flag was given.
+fn option(args: &[String], flag: &str) -> Result<Option<String>> {
+    match args.iter().position(|arg| arg == flag) {
+        None => Ok(None),
+        Some(i) => match args.get(i + 1) {
+            Some(value) if !value.starts_with("--") => Ok(Some(value.clone())),
+            _ => Err(Error::InvalidInput(format!("{flag} needs a value"))),
+        },
+    }
+}
+
+fn required(args: &[String], flag: &str) -> Result<String> {
+    option(args, flag)?.ok_or_else(|| Error::InvalidInput(format!("{flag} needs a value")))
+}
+
+/// The values following `flag`'s own, up to the next flag.
+fn positionals(args: &[String], flag: &str) -> Vec<String> {
+    args.iter()
+        .skip_while(|arg| *arg != flag)
+        .skip(2)
+        .take_while(|arg| !arg.starts_with("--"))
+        .cloned()
+        .collect()
+}
@@ -0,0 +1,11 @@
+use super::Paste;
+use crate::error::Result;
+use crate::vaults::Current;
+
+/// Reads the clipboard for a paste into note `note_id`, storing pasted
+/// images and files as its attachments. The `markdown` that comes back is
+/// what to insert.
+#[tauri::command]
+pub async fn paste_rich_content(vault: Current, note_id: String) -> Result<Paste> {
+    tauri::async_runtime::spawn_blocking(move || super::paste(&vault, &note_id)).await?
+}
@@ -0,0 +1,277 @@
+//! Pasting from the system clipboard into a note. Images and files become
+//! attachments and HTML becomes Markdown, so the editor only ever inserts
+//! Markdown.
+
+pub mod commands;
+
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use arboard::{Clipboard, ImageData};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use htmd::element_handler::Handlers;
+use htmd::{Element, HtmlToMarkdown};
+use image::{DynamicImage, ImageFormat, RgbaImage};
+use serde::Serialize;
+
+use crate::attachments::{self, Attachment};
+use crate::error::{Error, Result};
+use crate::vault::Vault;
+
+/// Largest image, by its file, taken from pasted HTML or files.
+const MAX_IMAGE_BYTES: usize = 20 * 1024 * 1024;
+/// Largest other file pasted as an attachment.
+const MAX_FILE_BYTES: u64 = 100 * 1024 * 1024;
+/// Largest image by pixels, about 8K by 6K.
+const MAX_PIXELS: usize = 50_000_000;
+/// Stands in for an inline image of pasted HTML until it is stored.
+const IMAGE_PLACEHOLDER: &str = "paste-image:";
+const IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "gif", "webp", "bmp", "svg"];
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Paste {
+    /// What to insert at the cursor.
+    pub markdown: String,
+    /// The attachments `markdown` refers to, stored already.
+    pub attachments: Vec<Attachment>,
+}
+
+/// An image inlined in pasted HTML as a `data:` URL.
+struct Inline {
+    alt: String,
+    content: Vec<u8>,
+    extension: String,
+}
+
+/// Turns what is on the clipboard into Markdown for note `note_id`. HTML
+/// wins over an image unless it is nothing but that image, as browsers put
+/// both when copying one; files come next and plain text last.
+pub fn paste(vault: &Vault, note_id: &str) -> Result<Paste> {
+    let mut clipboard = Clipboard::new()
+        .map_err(|err| Error::InvalidInput(format!("cannot reach the clipboard: {err}")))?;
+    let image = clipboard.get().image().ok();
+    if let Some(html) = clipboard
+        .get()
+        .html()
+        .ok()
+        .filter(|html| !html.trim().is_empty())
+    {
+        let (markdown, inline) = to_markdown(&html)?;
+        if image.is_none() || !is_lone_image(&markdown) {
+            return from_html(vault, note_id, markdown, inline);
+        }
+    }
+    if let Some(image) = image {
+        let attachment = save_pixels(vault, note_id, image)?;
+        return Ok(Paste {
+            markdown: format!("![]({})", attachment.link),
+            attachments: vec![attachment],
+        });
+    }
+    if let Some(files) = clipboard
+        .get()
+        .file_list()
+        .ok()
+        .filter(|files| !files.is_empty())
+    {
+        return from_files(vault, note_id, &files);
+    }
+    match clipboard.get().text() {
+        Ok(text) if !text.is_empty() => Ok(Paste {
+            markdown: text,
+            attachments: Vec::new(),
+        }),
+        _ => Err(Error::InvalidInput("the clipboard is empty".into())),
+    }
+}
+
+fn to_markdown(html: &str) -> Result<(String, Vec<Inline>)> {
+    let found = Arc::new(Mutex::new(Vec::new()));
+    let inline = Arc::clone(&found);
+    let converter = HtmlToMarkdown::builder()
+        .skip_tags(vec!["head", "style", "script", "noscript"])
+        .add_handler(
+            vec!["img"],
+            move |handlers: &dyn Handlers, element: Element| {
+                let Some((extension, content)) = attr(&element, "src").and_then(data_url) else {
+                    return handlers.fallback(element);
+                };
+                let alt = attr(&element, "alt")
+                    .unwrap_or_default()
+                    .chars()
+                    .filter(|c| !matches!(c, '[' | ']'))
+                    .collect();
+                let mut inline = inline.lock().expect("image list poisoned");
+                inline.push(Inline {
+                    alt,
+                    content,
+                    extension,
+                });
+                Some(format!("{IMAGE_PLACEHOLDER}{}", inline.len() - 1).into())
+            },
+        )
+        .build();
+    let markdown = converter.convert(html)?;
+    drop(converter);
+    let inline = std::mem::take(&mut *found.lock().expect("image list poisoned"));
+    Ok((markdown, inline))
+}
+
+/// The file extension and content of a base64 `data:image/...` URL.
+fn data_url(src: &str) -> Option<(String, Vec<u8>)> {
+    let (header, data) = src.strip_prefix("data:image/")?.split_once(',')?;
+    let subtype = header.strip_suffix(";base64")?;
+    let extension = match subtype {
+        "jpeg" => "jpg",
+        "svg+xml" => "svg",
+        other => other,
+    };
+    let content = BASE64.decode(data.trim()).ok()?;
+    Some((extension.to_owned(), content))
+}
+
+fn is_lone_image(markdown: &str) -> bool {
+    let markdown = markdown.trim();
+    markdown.starts_with("![") && markdown.ends_with(')') && !markdown.contains('\n')
+}
+
+/// Stores the inline images and puts their links in. One that cannot be
+/// stored leaves its alt text.
+fn from_html(
+    vault: &Vault,
+    note_id: &str,
+    mut markdown: String,
+    inline: Vec<Inline>,
+) -> Result<Paste> {
+    let mut attachments = Vec::new();
+    // Backwards, so `paste-image:1` is not taken for the start of `paste-image:10`.
+    for (i, image) in inline.into_iter().enumerate().rev() {
+        let placeholder = format!("{IMAGE_PLACEHOLDER}{i}");
+        let replacement = match save_image(vault, note_id, &image.content, &image.extension) {
+            Ok(attachment) => {
+                let link = format!("![{}]({})", image.alt, attachment.link);
+                attachments.push(attachment);
+                link
+            }
+            Err(_) => image.alt,
+        };
+        markdown = markdown.replace(&placeholder, &replacement);
+    }
+    attachments.reverse();
+    Ok(Paste {
+        markdown,
+        attachments,
+    })
+}
+
+fn from_files(vault: &Vault, note_id: &str, files: &[PathBuf]) -> Result<Paste> {
+    let mut links = Vec::new();
+    let mut attachments = Vec::new();
+    for path in files.iter().filter(|path| path.is_file()) {
+        let name: String = path
+            .file_name()
+            .map(|name| name.to_string_lossy())
+            .unwrap_or_default()
+            .chars()
+            .filter(|c| !matches!(c, '[' | ']'))
+            .collect();
+        if path.metadata()?.len() > MAX_FILE_BYTES {
+            return Err(Error::InvalidInput(format!(
+                "{name} is larger than {} MB",
+                MAX_FILE_BYTES / (1024 * 1024)
+            )));
+        }
+        let content = std::fs::read(path)?;
+        let extension = extension(path);
+        let attachment = match IMAGE_EXTENSIONS.contains(&extension.as_str()) {
+            true => {
+                let attachment = save_image(vault, note_id, &content, &extension)?;
+                links.push(format!("![{name}]({})", attachment.link));
+                attachment
+            }
+            false => {
+                let attachment = attachments::import(vault, note_id, &name, &content)?;
+                links.push(format!("[{name}]({})", attachment.link));
+                attachment
+            }
+        };
+        attachments.push(attachment);
+    }
+    if links.is_empty() {
+        return Err(Error::InvalidInput(
+            "only files can be pasted, not folders".into(),
+        ));
+    }
+    Ok(Paste {
+        markdown: links.join("\n\n"),
+        attachments,
+    })
+}
+
+fn extension(path: &Path) -> String {
+    path.extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+}
+
+/// Stores an image file. PNGs go in as lossless WebP when that is smaller,
+/// which for screenshots it nearly always is.
+fn save_image(vault: &Vault, note_id: &str, content: &[u8], extension: &str) -> Result<Attachment> {
+    if content.len() > MAX_IMAGE_BYTES {
+        return Err(Error::InvalidInput(format!(
+            "the image is larger than {} MB",
+            MAX_IMAGE_BYTES / (1024 * 1024)
+        )));
+    }
+    if extension == "png" {
+        let image = image::load_from_memory_with_format(content, ImageFormat::Png)?;
+        check_pixels(image.width() as usize, image.height() as usize)?;
+        let webp = to_webp(&image)?;
+        if webp.len() < content.len() {
+            return attachments::import(vault, note_id, "pasted.webp", &webp);
+        }
+    }
+    attachments::import(vault, note_id, &format!("pasted.{extension}"), content)
+}
+
+/// Stores the decoded pixels clipboards hold images as.
+fn save_pixels(vault: &Vault, note_id: &str, image: ImageData) -> Result<Attachment> {
+    check_pixels(image.width, image.height)?;
+    let pixels = RgbaImage::from_raw(
+        image.width as u32,
+        image.height as u32,
+        image.bytes.into_owned(),
+    )
+    .ok_or_else(|| Error::InvalidInput("the clipboard image is damaged".into()))?;
+    let webp = to_webp(&DynamicImage::ImageRgba8(pixels))?;
+    attachments::import(vault, note_id, "pasted.webp", &webp)
+}
+
+fn check_pixels(width: usize, height: usize) -> Result<()> {
+    match width.saturating_mul(height) > MAX_PIXELS {
+        true => Err(Error::InvalidInput(format!(
+            "the image is too large to paste ({width} × {height})"
+        ))),
+        false => Ok(()),
+    }
+}
+
+fn to_webp(image: &DynamicImage) -> Result<Vec<u8>> {
+    // The WebP encoder only takes 8-bit colour.
+    let image = DynamicImage::ImageRgba8(image.to_rgba8());
+    let mut out = Cursor::new(Vec::new());
+    image.write_to(&mut out, ImageFormat::WebP)?;
+    Ok(out.into_inner())
+}
+
+fn attr<'a>(element: &Element<'a>, name: &str) -> Option<&'a str> {
+    element
+        .attrs
+        .iter()
+        .find(|attr| &*attr.name.local == name)
+        .map(|attr| &*attr.value)
+        .filter(|value| !value.trim().is_empty())
+}
@@ -0,0 +1,16 @@
+use super::{ClipOptions, Clipping};
+use crate::error::Result;
+use crate::vaults::Current;
+
+/// Saves the article at `url` as a new note, with its images and, unless
+/// turned off, a copy of the page.
+#[tauri::command]
+pub async fn clip_url(
+    vault: Current,
+    url: String,
+    options: Option<ClipOptions>,
+) -> Result<Clipping> {
+    let options = options.unwrap_or_default();
+    let page = super::fetch(&url, &options).await?;
+    tauri::async_runtime::spawn_blocking(move || super::save(&vault, page, &options)).await?
+}
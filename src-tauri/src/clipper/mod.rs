@@ -0,0 +1,524 @@
+pub mod commands;
+
+use std::net::{IpAddr, SocketAddr};
+use std::path::Path;
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::Duration;
+
+use chrono::Local;
+use dom_smoothie::Readability;
+use htmd::element_handler::Handlers;
+use htmd::{Element, HtmlToMarkdown};
+use regex::Regex;
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use reqwest::header::CONTENT_TYPE;
+use reqwest::redirect::Policy;
+use reqwest::{Client, Response, Url};
+use serde::{Deserialize, Serialize};
+use serde_yaml_ng::Value;
+
+use crate::attachments;
+use crate::error::{Error, Result};
+use crate::files::NoteFiles;
+use crate::metadata::FrontMatter;
+use crate::storage::NotePatch;
+use crate::vault::Vault;
+
+const TIMEOUT: Duration = Duration::from_secs(30);
+/// Some sites turn away clients that do not look like a browser.
+const USER_AGENT: &str = "Mozilla/5.0 (compatible; notesdesktop clipper)";
+const MAX_PAGE_BYTES: usize = 10 * 1024 * 1024;
+const MAX_IMAGE_BYTES: usize = 20 * 1024 * 1024;
+/// Images past this many stay linked to the web rather than downloaded.
+const MAX_IMAGES: usize = 100;
+/// Files an archived page may load from its saved copies.
+const MAX_ASSETS: usize = 200;
+/// Stands in for an image's address in the Markdown until it is known
+/// whether the image was downloaded.
+const IMAGE_PLACEHOLDER: &str = "clip-image:";
+/// The front matter key listing what an archived page loads, so the files
+/// stay referenced wherever the note syncs to.
+pub const ASSETS_KEY: &str = "archive-assets";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ClipOptions {
+    pub folder: String,
+    /// Used instead of the page's title.
+    pub title: Option<String>,
+    pub tags: Vec<String>,
+    /// Download images into attachments rather than linking to the web.
+    pub images: bool,
+    /// Keep the page as fetched as an HTML attachment, for reading offline
+    /// or once it is gone.
+    pub archive: bool,
+    /// Save the images, stylesheets and icons the archived page loads too,
+    /// so it looks the same offline. What stylesheets load themselves, such
+    /// as fonts, stays on the web.
+    pub assets: bool,
+}
+
+impl Default for ClipOptions {
+    fn default() -> Self {
+        Self {
+            folder: String::new(),
+            title: None,
+            tags: Vec::new(),
+            images: true,
+            archive: true,
+            assets: false,
+        }
+    }
+}
+
+/// A page fetched and reduced to its article, ready to save.
+pub struct Page {
+    url: Url,
+    html: String,
+    title: String,
+    byline: Option<String>,
+    site_name: Option<String>,
+    published: Option<String>,
+    /// Images are [`IMAGE_PLACEHOLDER`] links numbered by `images`.
+    markdown: String,
+    images: Vec<Image>,
+    /// What the archive loads, for [`ClipOptions::assets`].
+    assets: Vec<Asset>,
+}
+
+struct Asset {
+    /// The address as the page writes it.
+    reference: String,
+    content: Vec<u8>,
+    extension: Option<String>,
+}
+
+struct Image {
+    url: Url,
+    /// `None` if not downloaded.
+    content: Option<Vec<u8>>,
+    extension: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Clipping {
+    pub note_id: String,
+    pub title: String,
+    /// Images saved as attachments.
+    pub images: usize,
+    /// Images that could not be downloaded and stay linked to the web.
+    pub missing_images: usize,
+    /// Link to the archived page, if one was kept.
+    pub archive: Option<String>,
+}
+
+/// Fetches `url` and pulls out its article, the way reader modes do, with
+/// images downloaded if `options.images` is set and the page's own files
+/// if `options.assets` is. Those only come from the public internet: the
+/// page picks them, and could otherwise have this device fetch from
+/// itself or the network it is on.
+pub async fn fetch(url: &str, options: &ClipOptions) -> Result<Page> {
+    let url = Url::parse(url.trim())
+        .ok()
+        .filter(|url| matches!(url.scheme(), "http" | "https"))
+        .ok_or_else(|| Error::InvalidInput(format!("not a web address: {url}")))?;
+    let client = Client::builder()
+        .timeout(TIMEOUT)
+        .user_agent(USER_AGENT)
+        .build()?;
+    let files = Client::builder()
+        .timeout(TIMEOUT)
+        .user_agent(USER_AGENT)
+        .dns_resolver(PublicOnly)
+        .redirect(Policy::custom(|attempt| {
+            match attempt.previous().len() < 10 && is_public_url(attempt.url()) {
+                true => attempt.follow(),
+                false => attempt.stop(),
+            }
+        }))
+        .build()?;
+    let response = client.get(url.clone()).send().await?.error_for_status()?;
+    // Redirects leave the page somewhere else, which relative links go by.
+    let url = response.url().clone();
+    let html = String::from_utf8_lossy(&read_limited(response, MAX_PAGE_BYTES).await?).into_owned();
+    let mut page = extract(url, html)?;
+    if options.images {
+        for image in page.images.iter_mut().take(MAX_IMAGES) {
+            // A missing image is not worth losing the clipping over.
+            if let Ok((content, extension)) = download(&files, &image.url).await {
+                image.content = Some(content);
+                image.extension = extension;
+            }
+        }
+    }
+    if options.archive && options.assets {
+        for reference in asset_references(&page.html).into_iter().take(MAX_ASSETS) {
+            let Ok(url) = page.url.join(&reference.replace("&amp;", "&")) else {
+                continue;
+            };
+            // As with images, the copy is still worth keeping without it.
+            if let Ok((content, extension)) = download(&files, &url).await {
+                page.assets.push(Asset {
+                    reference,
+                    content,
+                    extension,
+                });
+            }
+        }
+    }
+    Ok(page)
+}
+
+/// Saves a fetched page as a new note, with its source in the front
+/// matter.
+pub fn save(vault: &Vault, mut page: Page, options: &ClipOptions) -> Result<Clipping> {
+    let title = options
+        .title
+        .as_deref()
+        .map(str::trim)
+        .filter(|title| !title.is_empty())
+        .unwrap_or(&page.title);
+    let folder = options.folder.trim_matches('/');
+    let note = vault.create_note(title, "", folder)?;
+
+    let mut markdown = std::mem::take(&mut page.markdown);
+    let (mut saved, mut missing) = (0, 0);
+    for (n, image) in page.images.iter().enumerate().rev() {
+        let link = match &image.content {
+            Some(content) => {
+                let name = match &image.extension {
+                    Some(extension) => format!("image.{extension}"),
+                    None => "image".to_owned(),
+                };
+                saved += 1;
+                attachments::import(vault, &note.id, &name, content)?.link
+            }
+            None => {
+                missing += usize::from(options.images);
+                image.url.to_string()
+            }
+        };
+        // Backwards, so `clip-image:1` is not taken for part of `clip-image:12`.
+        markdown = markdown.replace(&format!("({IMAGE_PLACEHOLDER}{n})"), &format!("(<{link}>)"));
+    }
+
+    let (archive, assets) = match options.archive {
+        true => {
+            let (link, assets) = archive(vault, &note.id, &page)?;
+            (Some(link), assets)
+        }
+        false => (None, Vec::new()),
+    };
+
+    let mut front_matter = FrontMatter::default();
+    let mut set = |key: &str, value: Option<&str>| {
+        if let Some(value) = value.map(str::trim).filter(|value| !value.is_empty()) {
+            front_matter
+                .fields
+                .insert(Value::String(key.into()), Value::String(value.into()));
+        }
+    };
+    set("source", Some(page.url.as_str()));
+    set(
+        "clipped",
+        Some(&Local::now().format("%Y-%m-%d %H:%M").to_string()),
+    );
+    set("author", page.byline.as_deref());
+    set("site", page.site_name.as_deref());
+    set("published", page.published.as_deref());
+    set("archive", archive.as_deref());
+    if !assets.is_empty() {
+        let list = assets.into_iter().map(Value::String).collect();
+        front_matter
+            .fields
+            .insert(Value::String(ASSETS_KEY.into()), Value::Sequence(list));
+    }
+    front_matter.set_tags(&options.tags);
+
+    let body = format!("{}{}\n", front_matter.render(), markdown.trim());
+    let note = vault.update_note(
+        &note.id,
+        NotePatch {
+            body: Some(body),
+            ..Default::default()
+        },
+    )?;
+    Ok(Clipping {
+        note_id: note.id,
+        title: note.title,
+        images: saved,
+        missing_images: missing,
+        archive,
+    })
+}
+
+static ASSET_TAGS: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?is)<(img|link)\b[^>]*>").unwrap());
+static ATTRIBUTES: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"(?is)\s([a-z-]+)\s*=\s*(?:"([^"]*)"|'([^']*)')"#).unwrap());
+
+/// The images, stylesheets and icons `html` loads, as it writes them.
+fn asset_references(html: &str) -> Vec<String> {
+    let mut references = Vec::new();
+    for tag in ASSET_TAGS.captures_iter(html) {
+        let attributes: Vec<(String, &str)> = ATTRIBUTES
+            .captures_iter(&tag[0])
+            .filter_map(|found| {
+                let value = found.get(2).or_else(|| found.get(3))?.as_str();
+                Some((found[1].to_lowercase(), value))
+            })
+            .collect();
+        let value_of = |name: &str| {
+            attributes
+                .iter()
+                .find(|(attribute, _)| attribute == name)
+                .map(|(_, value)| value.trim())
+        };
+        let reference = match tag[1].to_lowercase().as_str() {
+            "img" => value_of("src"),
+            _ => value_of("rel")
+                .filter(|rel| {
+                    rel.split_whitespace().any(|rel| {
+                        rel.eq_ignore_ascii_case("stylesheet") || rel.eq_ignore_ascii_case("icon")
+                    })
+                })
+                .and_then(|_| value_of("href")),
+        };
+        if let Some(reference) =
+            reference.filter(|reference| !reference.is_empty() && !reference.starts_with("data:"))
+        {
+            references.push(reference.to_owned());
+        }
+    }
+    references.sort();
+    references.dedup();
+    references
+}
+
+/// Saves the page as fetched, what it loads pointed at the saved copies
+/// and then cleaned of anything that could run, and returns the link to it
+/// with the links to those copies, for the note to keep them by.
+fn archive(vault: &Vault, note_id: &str, page: &Page) -> Result<(String, Vec<String>)> {
+    let note = vault.storage.get_note(note_id)?;
+    let mut html = page.html.clone();
+    let mut saved = Vec::new();
+    for asset in &page.assets {
+        let name = match &asset.extension {
+            Some(extension) => format!("asset.{extension}"),
+            None => "asset".to_owned(),
+        };
+        let path = attachments::import(vault, note_id, &name, &asset.content)?.path;
+        // The archive is in the attachment folder too, so the file name is
+        // the relative address.
+        let file = path.rsplit('/').next().unwrap_or(&path);
+        for quote in ['"', '\''] {
+            html = html.replace(
+                &format!("={quote}{}{quote}", asset.reference),
+                &format!("={quote}{file}{quote}"),
+            );
+        }
+        saved.push(NoteFiles::link_from(&note.folder, &path));
+    }
+    let html = clean_archive(&html, page.assets.is_empty());
+    let archive = attachments::import(vault, note_id, "page.html", html.as_bytes())?;
+    saved.sort();
+    saved.dedup();
+    Ok((archive.link, saved))
+}
+
+/// `html` with scripts, event handlers, frames, forms and `<base>` taken
+/// out, keeping what it needs to look as it did. Without saved copies of
+/// its images, `srcset` stays so the browser can still pick one from the
+/// web; with them, it would pick one and find nothing.
+fn clean_archive(html: &str, keep_srcset: bool) -> String {
+    let mut builder = ammonia::Builder::default();
+    builder
+        .add_tags([
+            "link", "style", "title", "main", "picture", "source", "section",
+        ])
+        .rm_clean_content_tags(["style"])
+        .add_generic_attributes(["class", "id", "style", "dir", "role", "width", "height"])
+        .add_tag_attributes("link", ["rel", "href", "type", "media", "sizes"])
+        .add_tag_attributes("source", ["src", "type", "media"])
+        .add_url_schemes(["data"]);
+    if keep_srcset {
+        builder
+            .add_tag_attributes("img", ["srcset", "sizes"])
+            .add_tag_attributes("source", ["srcset", "sizes"]);
+    }
+    format!(
+        "<!DOCTYPE html>\n<meta charset=\"utf-8\">\n{}",
+        builder.clean(html)
+    )
+}
+
+/// Resolves names only to public addresses, so files a page asks for
+/// cannot come from this device or the network it is on.
+struct PublicOnly;
+
+impl Resolve for PublicOnly {
+    fn resolve(&self, name: Name) -> Resolving {
+        let host = name.as_str().to_owned();
+        Box::pin(async move {
+            let addresses: Vec<SocketAddr> = tokio::net::lookup_host((host.as_str(), 0))
+                .await?
+                .filter(|address| is_public(address.ip()))
+                .collect();
+            if addresses.is_empty() {
+                return Err(format!("{host} is not on the public internet").into());
+            }
+            Ok(Box::new(addresses.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// Whether `url` may be fetched for a page: names are checked as they are
+/// resolved, addresses here.
+fn is_public_url(url: &Url) -> bool {
+    match url.host() {
+        Some(url::Host::Domain(_)) => true,
+        Some(url::Host::Ipv4(ip)) => is_public(IpAddr::V4(ip)),
+        Some(url::Host::Ipv6(ip)) => is_public(IpAddr::V6(ip)),
+        None => false,
+    }
+}
+
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_private()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_documentation()
+                || ip.is_multicast()
+                || a == 0
+                || (a == 100 && (64..128).contains(&b)))
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public(IpAddr::V4(ip)),
+            None => {
+                let first = ip.segments()[0];
+                !(ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_multicast()
+                    || (first & 0xfe00) == 0xfc00
+                    || (first & 0xffc0) == 0xfe80)
+            }
+        },
+    }
+}
+
+/// The readable part of a page as Markdown.
+fn extract(url: Url, html: String) -> Result<Page> {
+    let mut readability = Readability::new(html.as_str(), Some(url.as_str()), None)
+        .map_err(|err| Error::InvalidInput(format!("could not read the page: {err}")))?;
+    let article = readability
+        .parse()
+        .map_err(|err| Error::InvalidInput(format!("no article found on the page: {err}")))?;
+
+    let sources = Arc::new(Mutex::new(Vec::new()));
+    let found = Arc::clone(&sources);
+    let base = url.clone();
+    let converter = HtmlToMarkdown::builder()
+        .skip_tags(vec!["head", "style", "script", "noscript"])
+        .add_handler(vec!["img"], move |_: &dyn Handlers, element: Element| {
+            let src = attr(&element, "src").or_else(|| attr(&element, "data-src"))?;
+            let src = base.join(src).ok()?;
+            let alt: String = attr(&element, "alt")
+                .unwrap_or_default()
+                .chars()
+                .filter(|c| !matches!(c, '[' | ']'))
+                .collect();
+            let mut sources = found.lock().expect("image list poisoned");
+            sources.push(src);
+            Some(format!("![{alt}]({IMAGE_PLACEHOLDER}{})", sources.len() - 1).into())
+        })
+        .build();
+    let markdown = converter.convert(&article.content)?;
+    drop(converter);
+    let images = std::mem::take(&mut *sources.lock().expect("image list poisoned"))
+        .into_iter()
+        .map(|url| Image {
+            url,
+            content: None,
+            extension: None,
+        })
+        .collect();
+
+    let title = match article.title.trim() {
+        "" => url.host_str().unwrap_or("Clipping").to_owned(),
+        title => title.to_owned(),
+    };
+    Ok(Page {
+        url,
+        html,
+        title,
+        byline: article.byline,
+        site_name: article.site_name,
+        published: article.published_time,
+        markdown,
+        images,
+        assets: Vec::new(),
+    })
+}
+
+/// An image, or another file a page loads, and the extension it should be
+/// saved under.
+async fn download(client: &Client, url: &Url) -> Result<(Vec<u8>, Option<String>)> {
+    if !is_public_url(url) {
+        return Err(Error::InvalidInput(format!(
+            "{url} is not on the public internet"
+        )));
+    }
+    let response = client.get(url.clone()).send().await?.error_for_status()?;
+    let from_type = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(';').next())
+        .and_then(|mime| match mime.trim() {
+            "text/css" => Some("css"),
+            mime => mime.strip_prefix("image/"),
+        })
+        .map(|subtype| match subtype {
+            "jpeg" | "pjpeg" => "jpg".to_owned(),
+            "svg+xml" => "svg".to_owned(),
+            "x-icon" | "vnd.microsoft.icon" => "ico".to_owned(),
+            other => other.to_owned(),
+        });
+    let extension = from_type.or_else(|| {
+        Path::new(url.path())
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(str::to_lowercase)
+    });
+    Ok((read_limited(response, MAX_IMAGE_BYTES).await?, extension))
+}
+
+async fn read_limited(mut response: Response, limit: usize) -> Result<Vec<u8>> {
+    let mut content = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        if content.len() + chunk.len() > limit {
+            return Err(Error::InvalidInput(format!(
+                "{} is larger than {} MB",
+                response.url(),
+                limit / (1024 * 1024)
+            )));
+        }
+        content.extend_from_slice(&chunk);
+    }
+    Ok(content)
+}
+
+fn attr<'a>(element: &Element<'a>, name: &str) -> Option<&'a str> {
+    element
+        .attrs
+        .iter()
+        .find(|attr| &*attr.name.local == name)
+        .map(|attr| &*attr.value)
+        .filter(|value| !value.trim().is_empty())
+}
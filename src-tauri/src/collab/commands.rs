@@ -0,0 +1,41 @@
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+
+use crate::error::{Error, Result};
+use crate::storage::Note;
+use crate::vaults::Current;
+
+// Yjs state vectors and updates cross the bridge as base64.
+
+/// Merges a Yjs update for note `noteId`, from a peer or a collaborating
+/// editor, and returns the note with the merged body.
+#[tauri::command]
+pub async fn collab_apply_update(vault: Current, note_id: String, update: String) -> Result<Note> {
+    let update = decode(&update)?;
+    tauri::async_runtime::spawn_blocking(move || super::apply_update(&vault, &note_id, &update))
+        .await?
+}
+
+#[tauri::command]
+pub async fn collab_state_vector(vault: Current, note_id: String) -> Result<String> {
+    Ok(BASE64.encode(super::state_vector(&vault, &note_id)?))
+}
+
+/// The update a peer at `stateVector` is missing; the whole document when
+/// it has none.
+#[tauri::command]
+pub async fn collab_encode_update(
+    vault: Current,
+    note_id: String,
+    state_vector: Option<String>,
+) -> Result<String> {
+    let since = state_vector.as_deref().map(decode).transpose()?;
+    let update = super::encode_update(&vault, &note_id, since.as_deref())?;
+    Ok(BASE64.encode(update))
+}
+
+fn decode(encoded: &str) -> Result<Vec<u8>> {
+    BASE64
+        .decode(encoded)
+        .map_err(|err| Error::InvalidInput(format!("not base64: {err}")))
+}
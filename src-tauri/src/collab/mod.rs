@@ -0,0 +1,433 @@
+pub mod commands;
+
+use std::fs;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use rusqlite::{params, OptionalExtension};
+use sha2::{Digest, Sha256};
+use similar::{DiffTag, TextDiff};
+use yrs::updates::decoder::Decode;
+use yrs::updates::encoder::Encode;
+use yrs::{Doc, GetString, ReadTxn, StateVector, Text, Transact, Update};
+
+use crate::atomic;
+use crate::error::{Error, Result};
+use crate::protected;
+use crate::storage::{Note, NotePatch, Storage};
+use crate::trash;
+use crate::vault::Vault;
+
+/// Mirror directory holding each device's copy of each note's document, as
+/// `<note id>/<client id>.yrs`. Every device only ever writes its own file,
+/// so syncing the directory never conflicts.
+pub const DIR: &str = ".collab";
+const EXTENSION: &str = "yrs";
+const CLIENT_KEY: &str = "collab.client";
+const TEXT: &str = "body";
+/// Yjs client ids are 53 bits so they survive a trip through JavaScript.
+const CLIENT_BITS: u64 = (1 << 53) - 1;
+/// Large rewrites fall back to a coarser diff rather than stall a save.
+const DIFF_TIMEOUT: Duration = Duration::from_millis(200);
+
+/// The CRDT history of a note body.
+///
+/// Every save is replayed onto the note's Yjs document as a minimal edit,
+/// so edits from other devices, or from a collaborating editor, can be
+/// merged in without either side losing text. The plain body in the store
+/// stays the source of what is shown; the document only decides how
+/// concurrent edits combine.
+struct NoteDoc {
+    doc: Doc,
+}
+
+impl NoteDoc {
+    /// The stored document of `note`, or a fresh one seeded with its body.
+    fn load(storage: &Storage, note: &Note) -> Result<Self> {
+        let doc = Doc::with_client_id(client_id(storage)?);
+        let stored: Option<String> = storage
+            .conn()
+            .query_row(
+                "SELECT state FROM collab_docs WHERE note_id = ?1",
+                [&note.id],
+                |row| row.get(0),
+            )
+            .optional()?;
+        let state = match stored {
+            Some(sealed) => decode(storage, sealed)?,
+            None => seed(&note.body),
+        };
+        let this = Self { doc };
+        this.apply(&state)?;
+        Ok(this)
+    }
+
+    fn text(&self) -> String {
+        let text = self.doc.get_or_insert_text(TEXT);
+        text.get_string(&self.doc.transact())
+    }
+
+    fn apply(&self, update: &[u8]) -> Result<()> {
+        let update = Update::decode_v1(update).map_err(|err| Error::Collab(err.to_string()))?;
+        self.doc
+            .transact_mut()
+            .apply_update(update)
+            .map_err(|err| Error::Collab(err.to_string()))
+    }
+
+    /// Turns the text into `body` with as small an edit as the diff finds.
+    fn edit_to(&self, body: &str) {
+        let text = self.doc.get_or_insert_text(TEXT);
+        let mut txn = self.doc.transact_mut();
+        let old = text.get_string(&txn);
+        let diff = TextDiff::configure()
+            .timeout(DIFF_TIMEOUT)
+            .diff_chars(old.as_str(), body);
+        // Byte offset of each char of `old`, and of its end.
+        let mut offsets: Vec<u32> = old.char_indices().map(|(i, _)| i as u32).collect();
+        offsets.push(old.len() as u32);
+        let new_offsets: Vec<usize> = body
+            .char_indices()
+            .map(|(i, _)| i)
+            .chain([body.len()])
+            .collect();
+        // Back to front, so earlier offsets stay valid.
+        for op in diff.ops().iter().rev() {
+            let (tag, old_range, new_range) = op.as_tag_tuple();
+            if tag == DiffTag::Equal {
+                continue;
+            }
+            let start = offsets[old_range.start];
+            let removed = offsets[old_range.end] - start;
+            if removed > 0 {
+                text.remove_range(&mut txn, start, removed);
+            }
+            let inserted = &body[new_offsets[new_range.start]..new_offsets[new_range.end]];
+            if !inserted.is_empty() {
+                text.insert(&mut txn, start, inserted);
+            }
+        }
+    }
+
+    fn state(&self) -> Vec<u8> {
+        self.doc
+            .transact()
+            .encode_state_as_update_v1(&StateVector::default())
+    }
+
+    fn save(&self, vault: &Vault, note_id: &str) -> Result<()> {
+        let sealed = vault.storage.seal_body(&BASE64.encode(self.state()))?;
+        vault.storage.conn().execute(
+            "INSERT OR REPLACE INTO collab_docs (note_id, state) VALUES (?1, ?2)",
+            params![note_id, sealed],
+        )?;
+        let path = own_file(vault, note_id)?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        atomic::write(&path, sealed.as_bytes())?;
+        Ok(())
+    }
+}
+
+/// Whether `rel` is a document file under the collab directory.
+pub fn is_state_path(rel: &str) -> bool {
+    let Some(rest) = rel
+        .strip_prefix(DIR)
+        .and_then(|rest| rest.strip_prefix('/'))
+    else {
+        return false;
+    };
+    let mut parts = rest.split('/');
+    matches!(
+        (parts.next(), parts.next(), parts.next()),
+        (Some(note), Some(file), None)
+            if !note.is_empty() && file.ends_with(&format!(".{EXTENSION}"))
+    )
+}
+
+/// Relative paths of every document file in the mirror.
+pub fn state_files(vault: &Vault) -> Result<Vec<String>> {
+    let root = vault.files.absolute(DIR);
+    let mut paths = Vec::new();
+    let notes = match fs::read_dir(&root) {
+        Ok(notes) => notes,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(paths),
+        Err(err) => return Err(err.into()),
+    };
+    for note in notes {
+        let note = note?;
+        if !note.file_type()?.is_dir() {
+            continue;
+        }
+        for file in fs::read_dir(note.path())? {
+            let rel = format!(
+                "{DIR}/{}/{}",
+                note.file_name().to_string_lossy(),
+                file?.file_name().to_string_lossy()
+            );
+            if is_state_path(&rel) {
+                paths.push(rel);
+            }
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+/// Brings the document of `note` up to date with a save. Notes with a
+/// passphrase of their own have none, and lose any they had: sealed
+/// bodies cannot merge, so sync takes whichever was saved last.
+pub fn record_local(vault: &Vault, note: &Note) -> Result<()> {
+    if protected::is_protected(&note.body) {
+        return forget(vault, &note.id);
+    }
+    let doc = NoteDoc::load(&vault.storage, note)?;
+    if doc.text() == note.body {
+        return Ok(());
+    }
+    doc.edit_to(&note.body);
+    doc.save(vault, &note.id)
+}
+
+/// Writes this device's document files again from the store, after
+/// [`Storage::set_password`] resealed it.
+pub fn rewrite_own_files(vault: &Vault) -> Result<()> {
+    let docs: Vec<(String, String)> = {
+        let conn = vault.storage.conn();
+        let mut stmt = conn.prepare("SELECT note_id, state FROM collab_docs")?;
+        let rows = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<rusqlite::Result<_>>()?;
+        rows
+    };
+    for (note_id, sealed) in docs {
+        let path = own_file(vault, &note_id)?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        atomic::write(&path, sealed.as_bytes())?;
+    }
+    Ok(())
+}
+
+/// Merges a Yjs update from elsewhere into note `note_id` and saves the
+/// merged body.
+pub fn apply_update(vault: &Vault, note_id: &str, update: &[u8]) -> Result<Note> {
+    let note = vault.storage.get_note(note_id)?;
+    if protected::is_protected(&note.body) {
+        return Err(Error::NoteLocked(note_id.to_owned()));
+    }
+    let doc = NoteDoc::load(&vault.storage, &note)?;
+    doc.apply(update)?;
+    doc.save(vault, note_id)?;
+    let merged = doc.text();
+    if merged == note.body {
+        return Ok(note);
+    }
+    vault.update_note(
+        note_id,
+        NotePatch {
+            body: Some(merged),
+            ..Default::default()
+        },
+    )
+}
+
+/// Adds `note`, which a peer has and this device does not, with the peer's
+/// whole document `update` as its history. Its body is whatever that
+/// document holds. Taking the document over rather than seeding a new one
+/// means later updates from the peer line up with it.
+pub fn adopt(vault: &Vault, mut note: Note, update: &[u8]) -> Result<Note> {
+    let doc = NoteDoc {
+        doc: Doc::with_client_id(client_id(&vault.storage)?),
+    };
+    doc.apply(update)?;
+    note.body = doc.text();
+    vault.import_note(&note)?;
+    doc.save(vault, &note.id)?;
+    Ok(note)
+}
+
+/// The encoded state vector of note `note_id`, for a peer to work out
+/// which updates it is missing.
+pub fn state_vector(vault: &Vault, note_id: &str) -> Result<Vec<u8>> {
+    let note = vault.storage.get_note(note_id)?;
+    let doc = NoteDoc::load(&vault.storage, &note)?;
+    let vector = doc.doc.transact().state_vector().encode_v1();
+    Ok(vector)
+}
+
+/// Whether a peer at state vector `vector` has every insertion this device
+/// has into note `note_id`. Deletions do not show in a state vector, so a
+/// peer can be covered and still lack some.
+pub fn covers(vault: &Vault, note_id: &str, vector: &[u8]) -> Result<bool> {
+    let theirs = StateVector::decode_v1(vector).map_err(|err| Error::Collab(err.to_string()))?;
+    let note = vault.storage.get_note(note_id)?;
+    let doc = NoteDoc::load(&vault.storage, &note)?;
+    let ours = doc.doc.transact().state_vector();
+    Ok(ours
+        .iter()
+        .all(|(client, clock)| theirs.get(client) >= *clock))
+}
+
+/// The update holding whatever a peer at `since` lacks, or the whole
+/// document without it.
+pub fn encode_update(vault: &Vault, note_id: &str, since: Option<&[u8]>) -> Result<Vec<u8>> {
+    let note = vault.storage.get_note(note_id)?;
+    let doc = NoteDoc::load(&vault.storage, &note)?;
+    let since = match since {
+        Some(vector) => {
+            StateVector::decode_v1(vector).map_err(|err| Error::Collab(err.to_string()))?
+        }
+        None => StateVector::default(),
+    };
+    let update = doc.doc.transact().encode_state_as_update_v1(&since);
+    Ok(update)
+}
+
+/// Merges in the documents other devices left in the mirror, after a sync
+/// brought them in. Returns how many notes changed.
+pub fn merge_peers(vault: &Vault) -> Result<usize> {
+    if vault.storage.is_locked() {
+        return Ok(0);
+    }
+    let own = format!("{}.{EXTENSION}", client_id(&vault.storage)?);
+    let mut merged = 0;
+    let mut notes: Vec<(String, Vec<String>)> = Vec::new();
+    for rel in state_files(vault)? {
+        let (dir, file) = rel.rsplit_once('/').expect("state paths have a file name");
+        if file == own {
+            continue;
+        }
+        match notes.last_mut() {
+            Some((last, files)) if last == dir => files.push(rel),
+            _ => notes.push((dir.to_owned(), vec![rel])),
+        }
+    }
+    for (_, files) in notes {
+        let Some(note_id) = note_of(&files[0]) else {
+            continue;
+        };
+        if !vault.storage.has_note(&note_id)? || trash::is_trashed(&vault.storage, &note_id)? {
+            continue;
+        }
+        let note = vault.storage.get_note(&note_id)?;
+        // Documents left from before the note got a passphrase.
+        if protected::is_protected(&note.body) {
+            continue;
+        }
+        let doc = NoteDoc::load(&vault.storage, &note)?;
+        let before = doc.state();
+        for rel in &files {
+            let sealed = fs::read_to_string(vault.files.absolute(rel))?;
+            // A peer's file sealed under a password this device has not
+            // caught up with yet is tried again after the next sync.
+            let Ok(state) = decode(&vault.storage, sealed) else {
+                continue;
+            };
+            doc.apply(&state)?;
+        }
+        if doc.state() == before {
+            continue;
+        }
+        doc.save(vault, &note_id)?;
+        let text = doc.text();
+        if text != note.body {
+            vault.update_note(
+                &note_id,
+                NotePatch {
+                    body: Some(text),
+                    ..Default::default()
+                },
+            )?;
+            merged += 1;
+        }
+    }
+    Ok(merged)
+}
+
+/// Drops this device's document, row and file, for a note deleted for
+/// good or given a passphrase of its own.
+pub fn forget(vault: &Vault, note_id: &str) -> Result<()> {
+    vault
+        .storage
+        .conn()
+        .execute("DELETE FROM collab_docs WHERE note_id = ?1", [note_id])?;
+    let path = own_file(vault, note_id)?;
+    match fs::remove_file(&path) {
+        Err(err) if err.kind() != ErrorKind::NotFound => return Err(err.into()),
+        _ => {}
+    }
+    if let Some(dir) = path.parent() {
+        // Other devices' files may still be in there.
+        let _ = fs::remove_dir(dir);
+    }
+    Ok(())
+}
+
+/// This device's id in every document of the vault, picked on first use.
+fn client_id(storage: &Storage) -> Result<u64> {
+    if let Some(id) = storage.meta(CLIENT_KEY)?.and_then(|id| id.parse().ok()) {
+        return Ok(id);
+    }
+    let id = (rand::random::<u64>() & CLIENT_BITS).max(1);
+    storage.set_meta(CLIENT_KEY, &id.to_string())?;
+    Ok(id)
+}
+
+/// The starting document for `body`. Devices that start tracking the same
+/// body independently end up with identical seeds that merge as one, as the
+/// seed's client id comes from the body. Different bodies get different
+/// ids, which Yjs needs to tell their items apart.
+fn seed(body: &str) -> Vec<u8> {
+    let digest = Sha256::digest(body.as_bytes());
+    let client = u64::from_le_bytes(digest[..8].try_into().expect("digest is 32 bytes"));
+    let doc = Doc::with_client_id(client & CLIENT_BITS);
+    let text = doc.get_or_insert_text(TEXT);
+    text.insert(&mut doc.transact_mut(), 0, body);
+    let txn = doc.transact();
+    txn.encode_state_as_update_v1(&StateVector::default())
+}
+
+fn decode(storage: &Storage, sealed: String) -> Result<Vec<u8>> {
+    let encoded = storage.open_body(sealed)?;
+    BASE64
+        .decode(encoded.trim())
+        .map_err(|err| Error::Collab(err.to_string()))
+}
+
+fn own_file(vault: &Vault, note_id: &str) -> Result<PathBuf> {
+    let client = client_id(&vault.storage)?;
+    Ok(vault
+        .files
+        .absolute(&format!("{DIR}/{}/{client}.{EXTENSION}", dir_name(note_id))))
+}
+
+/// Note ids are UUIDs, but imported ones may not be; anything that is not
+/// safe as a directory name is hex-encoded.
+fn dir_name(note_id: &str) -> String {
+    if note_id
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-')
+    {
+        note_id.to_owned()
+    } else {
+        format!("_{}", hex::encode(note_id))
+    }
+}
+
+fn note_of(rel: &str) -> Option<String> {
+    let name = rel
+        .strip_prefix(DIR)?
+        .strip_prefix('/')?
+        .split('/')
+        .next()?;
+    match name.strip_prefix('_') {
+        Some(encoded) => String::from_utf8(hex::decode(encoded).ok()?).ok(),
+        None => Some(name.to_owned()),
+    }
+}
@@ -0,0 +1,89 @@
+//! The order note titles and folders are listed in, the same in every
+//! listing: the locale's alphabetical order, with runs of digits by their
+//! value when natural sorting is on, so "note 2" comes before "note 10".
+//! It follows the [`Sorting`] settings, set once at startup and again
+//! whenever they change.
+
+use std::cmp::Ordering;
+use std::sync::{LazyLock, RwLock};
+
+use icu_collator::options::CollatorOptions;
+use icu_collator::preferences::CollationNumericOrdering;
+use icu_collator::{Collator, CollatorBorrowed, CollatorPreferences};
+use icu_locale_core::Locale;
+
+use crate::error::{Error, Result};
+use crate::settings::{Settings, Sorting};
+
+/// The SQLite collation doing the same, registered on every connection.
+pub const SQL_NAME: &str = "NOTES";
+
+struct Collation {
+    collator: CollatorBorrowed<'static>,
+    folders_first: bool,
+}
+
+static CURRENT: LazyLock<RwLock<Collation>> = LazyLock::new(|| {
+    let sorting = Sorting::default();
+    RwLock::new(Collation {
+        collator: collator(None, sorting.natural),
+        folders_first: sorting.folders_first,
+    })
+});
+
+/// Switches to the order `settings` ask for. The locale is the sorting
+/// one, then the UI language, then the system's.
+pub fn configure(settings: &Settings) {
+    let sorting = &settings.sorting;
+    let locale = sorting
+        .locale
+        .as_deref()
+        .or(settings.general.language.as_deref())
+        .and_then(|tag| parse(tag).ok())
+        .or_else(|| sys_locale::get_locale().and_then(|tag| parse(&tag).ok()));
+    let collation = Collation {
+        collator: collator(locale.as_ref(), sorting.natural),
+        folders_first: sorting.folders_first,
+    };
+    *CURRENT.write().expect("collation poisoned") = collation;
+}
+
+/// Checks a locale given in the settings.
+pub fn validate(tag: &str) -> Result<()> {
+    parse(tag).map(drop)
+}
+
+fn parse(tag: &str) -> Result<Locale> {
+    // POSIX names like de_DE.UTF-8, as some systems report them.
+    let tag = tag
+        .split(['.', '@'])
+        .next()
+        .unwrap_or_default()
+        .replace('_', "-");
+    Locale::try_from_str(&tag).map_err(|_| Error::InvalidInput(format!("not a locale: {tag:?}")))
+}
+
+fn collator(locale: Option<&Locale>, natural: bool) -> CollatorBorrowed<'static> {
+    let mut prefs = locale.map(CollatorPreferences::from).unwrap_or_default();
+    prefs.numeric_ordering = Some(match natural {
+        true => CollationNumericOrdering::True,
+        false => CollationNumericOrdering::False,
+    });
+    Collator::try_new(prefs, CollatorOptions::default())
+        // Every locale falls back to the root order, which is always there.
+        .or_else(|_| Collator::try_new(CollatorPreferences::default(), CollatorOptions::default()))
+        .expect("root collation data is compiled in")
+}
+
+pub fn compare(a: &str, b: &str) -> Ordering {
+    CURRENT
+        .read()
+        .expect("collation poisoned")
+        .collator
+        .compare(a, b)
+}
+
+/// Whether folders go before notes in the folder tree.
+pub fn folders_first() -> bool {
+    CURRENT.read().expect("collation poisoned").folders_first
+}
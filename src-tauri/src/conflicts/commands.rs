@@ -0,0 +1,30 @@
+use super::{Choice, Conflict, Resolution};
+use crate::error::{Error, Result};
+use crate::storage::Note;
+use crate::vaults::Current;
+
+#[tauri::command]
+pub async fn get_conflicts(vault: Current) -> Result<Vec<Conflict>> {
+    super::list(&vault)
+}
+
+/// Settles a conflict either by `choice`, keeping one side as it is, or
+/// with `mergedContent` edited together in the UI.
+#[tauri::command]
+pub async fn resolve_conflict(
+    vault: Current,
+    note_id: String,
+    choice: Option<Choice>,
+    merged_content: Option<String>,
+) -> Result<Note> {
+    let resolution = match (choice, merged_content) {
+        (Some(choice), None) => Resolution::Keep(choice),
+        (None, Some(content)) => Resolution::Merged(content),
+        _ => {
+            return Err(Error::InvalidInput(
+                "give either a choice or merged content".into(),
+            ))
+        }
+    };
+    super::resolve(&vault, &note_id, resolution)
+}
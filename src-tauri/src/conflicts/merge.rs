@@ -0,0 +1,121 @@
+use serde::Serialize;
+use similar::{DiffTag, TextDiff};
+
+const MINE_MARKER: &str = "<<<<<<< mine\n";
+const SPLIT_MARKER: &str = "=======\n";
+const THEIRS_MARKER: &str = ">>>>>>> theirs\n";
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Merge {
+    pub content: String,
+    /// No region was changed differently on both sides. Otherwise those
+    /// regions carry git-style conflict markers.
+    pub clean: bool,
+}
+
+/// Lines of `base[start..end]` replaced by `lines` on one side.
+struct Chunk<'a> {
+    start: usize,
+    end: usize,
+    lines: Vec<&'a str>,
+}
+
+/// Line-based three-way merge of `mine` and `theirs`, both edited from
+/// `base`. Edits to separate parts are combined; edits that touch the same
+/// lines merge cleanly only if they agree.
+pub fn merge3(base: &str, mine: &str, theirs: &str) -> Merge {
+    let base: Vec<&str> = base.split_inclusive('\n').collect();
+    let mine = chunks(&base, mine);
+    let theirs = chunks(&base, theirs);
+
+    let mut out = String::new();
+    let mut clean = true;
+    let (mut i, mut j, mut pos) = (0, 0, 0);
+    loop {
+        let start = match (mine.get(i), theirs.get(j)) {
+            (None, None) => break,
+            (Some(a), None) => a.start,
+            (None, Some(b)) => b.start,
+            (Some(a), Some(b)) => a.start.min(b.start),
+        };
+        out.extend(base[pos..start].iter().copied());
+
+        // Grows the region until no chunk on either side reaches into it;
+        // chunks that only touch it count, as their order is ambiguous.
+        let (first_mine, first_theirs) = (i, j);
+        let mut end = start;
+        loop {
+            let grown = (i, j);
+            while let Some(chunk) = mine.get(i).filter(|chunk| chunk.start <= end) {
+                end = end.max(chunk.end);
+                i += 1;
+            }
+            while let Some(chunk) = theirs.get(j).filter(|chunk| chunk.start <= end) {
+                end = end.max(chunk.end);
+                j += 1;
+            }
+            if (i, j) == grown {
+                break;
+            }
+        }
+
+        let ours = apply(&base, start, end, &mine[first_mine..i]);
+        let other = apply(&base, start, end, &theirs[first_theirs..j]);
+        if first_mine == i {
+            out.push_str(&other);
+        } else if first_theirs == j || ours == other {
+            out.push_str(&ours);
+        } else {
+            clean = false;
+            out.push_str(MINE_MARKER);
+            push_block(&mut out, &ours);
+            out.push_str(SPLIT_MARKER);
+            push_block(&mut out, &other);
+            out.push_str(THEIRS_MARKER);
+        }
+        pos = end;
+    }
+    out.extend(base[pos..].iter().copied());
+    Merge {
+        content: out,
+        clean,
+    }
+}
+
+fn chunks<'a>(base: &[&str], side: &'a str) -> Vec<Chunk<'a>> {
+    let lines: Vec<&str> = side.split_inclusive('\n').collect();
+    TextDiff::from_slices(base, &lines)
+        .ops()
+        .iter()
+        .filter_map(|op| {
+            let (tag, old, new) = op.as_tag_tuple();
+            (tag != DiffTag::Equal).then(|| Chunk {
+                start: old.start,
+                end: old.end,
+                lines: lines[new].to_vec(),
+            })
+        })
+        .collect()
+}
+
+/// `base[start..end]` with `chunks` applied.
+fn apply(base: &[&str], start: usize, end: usize, chunks: &[Chunk<'_>]) -> String {
+    let mut text = String::new();
+    let mut pos = start;
+    for chunk in chunks {
+        text.extend(base[pos..chunk.start].iter().copied());
+        text.extend(chunk.lines.iter().copied());
+        pos = chunk.end;
+    }
+    text.extend(base[pos..end].iter().copied());
+    text
+}
+
+/// Appends a side of a conflict so the marker after it starts a line.
+fn push_block(out: &mut String, block: &str) {
+    out.push_str(block);
+    if !block.is_empty() && !block.ends_with('\n') {
+        out.push('\n');
+    }
+}
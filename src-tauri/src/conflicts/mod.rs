@@ -0,0 +1,169 @@
+pub mod commands;
+mod merge;
+
+pub use merge::Merge;
+
+use chrono::Local;
+use rusqlite::{params, OptionalExtension};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+use crate::history;
+use crate::storage::{now_millis, Note, NotePatch};
+use crate::trash;
+use crate::vault::Vault;
+use merge::merge3;
+
+/// An editor save that raced a change from elsewhere. The note kept the
+/// other change; the editor's version lives on in a conflict copy until
+/// the two are reconciled.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Conflict {
+    pub note_id: String,
+    pub title: String,
+    pub copy_id: String,
+    pub detected_at: i64,
+    /// The body both sides started from, if history still has it.
+    pub base: Option<String>,
+    /// The editor's version, from the copy.
+    pub mine: String,
+    /// The note as it is now.
+    pub theirs: String,
+    /// A three-way merge of the two, as a starting point.
+    pub merged: Merge,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Choice {
+    Mine,
+    Theirs,
+}
+
+pub enum Resolution {
+    Keep(Choice),
+    Merged(String),
+}
+
+/// Whether saving `body` over note `current` would drop a change made
+/// since the editor loaded the note at `base_at`. A timestamp that moved
+/// without the content changing does not count.
+pub fn conflicts_with(vault: &Vault, current: &Note, base_at: i64, body: &str) -> Result<bool> {
+    if current.updated_at <= base_at || current.body == body {
+        return Ok(false);
+    }
+    let base = history::body_at(&vault.storage, &current.id, base_at)?;
+    Ok(base.as_deref() != Some(current.body.as_str()))
+}
+
+/// Keeps the editor's version of `current` as a conflict copy in the same
+/// folder. A note can only have one open conflict; a later one updates the
+/// copy.
+pub fn record(vault: &Vault, current: &Note, base_at: i64, title: &str, body: &str) -> Result<()> {
+    if let Some(copy_id) = copy_of(vault, &current.id)? {
+        if vault.storage.has_note(&copy_id)? && !trash::is_trashed(&vault.storage, &copy_id)? {
+            let patch = NotePatch {
+                body: Some(body.to_owned()),
+                ..Default::default()
+            };
+            vault.update_note(&copy_id, patch)?;
+            return Ok(());
+        }
+    }
+    let stamp = Local::now().format("%Y-%m-%d %H:%M");
+    let copy = vault.create_note(
+        &format!("{title} (conflict {stamp})"),
+        body,
+        &current.folder,
+    )?;
+    vault.storage.conn().execute(
+        "INSERT OR REPLACE INTO conflicts (note_id, copy_id, base_at, detected_at)
+         VALUES (?1, ?2, ?3, ?4)",
+        params![current.id, copy.id, base_at, now_millis()],
+    )?;
+    Ok(())
+}
+
+/// Open conflicts, oldest first, skipping notes in the trash.
+pub fn list(vault: &Vault) -> Result<Vec<Conflict>> {
+    rows(vault)?
+        .into_iter()
+        .map(|(note_id, copy_id, base_at, detected_at)| {
+            let note = vault.storage.get_note(&note_id)?;
+            let mine = vault.storage.get_note(&copy_id)?.body;
+            let base = history::body_at(&vault.storage, &note_id, base_at)?;
+            let merged = merge3(base.as_deref().unwrap_or_default(), &mine, &note.body);
+            Ok(Conflict {
+                note_id,
+                title: note.title,
+                copy_id,
+                detected_at,
+                base,
+                mine,
+                theirs: note.body,
+                merged,
+            })
+        })
+        .collect()
+}
+
+/// Settles the conflict on `note_id`, writing the chosen body to the note
+/// and moving the copy to the trash.
+pub fn resolve(vault: &Vault, note_id: &str, resolution: Resolution) -> Result<Note> {
+    let copy_id = copy_of(vault, note_id)?
+        .ok_or_else(|| Error::InvalidInput(format!("note {note_id} has no conflict")))?;
+    let body = match resolution {
+        Resolution::Keep(Choice::Theirs) => None,
+        Resolution::Keep(Choice::Mine) => Some(vault.storage.get_note(&copy_id)?.body),
+        Resolution::Merged(content) => Some(content),
+    };
+    let note = match body {
+        Some(body) => vault.update_note(
+            note_id,
+            NotePatch {
+                body: Some(body),
+                ..Default::default()
+            },
+        )?,
+        None => vault.storage.get_note(note_id)?,
+    };
+    vault
+        .storage
+        .conn()
+        .execute("DELETE FROM conflicts WHERE note_id = ?1", [note_id])?;
+    if vault.storage.has_note(&copy_id)? && !trash::is_trashed(&vault.storage, &copy_id)? {
+        vault.trash_note(&copy_id)?;
+    }
+    Ok(note)
+}
+
+fn copy_of(vault: &Vault, note_id: &str) -> Result<Option<String>> {
+    let copy_id = vault
+        .storage
+        .conn()
+        .query_row(
+            "SELECT copy_id FROM conflicts WHERE note_id = ?1",
+            [note_id],
+            |row| row.get(0),
+        )
+        .optional()?;
+    Ok(copy_id)
+}
+
+/// `(note_id, copy_id, base_at, detected_at)` of each open conflict.
+fn rows(vault: &Vault) -> Result<Vec<(String, String, i64, i64)>> {
+    let conn = vault.storage.conn();
+    let mut stmt = conn.prepare(
+        "SELECT note_id, copy_id, base_at, detected_at FROM conflicts
+         WHERE note_id NOT IN (SELECT note_id FROM trash)
+           AND copy_id NOT IN (SELECT note_id FROM trash)
+         ORDER BY detected_at",
+    )?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+        })?
+        .collect::<rusqlite::Result<_>>()?;
+    Ok(rows)
+}
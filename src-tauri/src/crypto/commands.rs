@@ -0,0 +1,52 @@
+use std::fs;
+
+use serde::Serialize;
+
+use crate::error::Result;
+use crate::vaults::Current;
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VaultStatus {
+    pub encrypted: bool,
+    pub locked: bool,
+    /// Attachments a vault password leaves readable, for the settings to
+    /// warn about: they stay files the preview, other apps and sync read as
+    /// they are, and so do their thumbnails. Zero without a password.
+    pub unsealed_attachments: usize,
+}
+
+#[tauri::command]
+pub async fn set_vault_password(vault: Current, password: String) -> Result<()> {
+    vault.set_password(&password)?;
+    crate::security::password_changed(&vault)
+}
+
+#[tauri::command]
+pub async fn unlock_vault(vault: Current, password: String) -> Result<()> {
+    vault.unlock(&password)
+}
+
+#[tauri::command]
+pub async fn lock_vault(vault: Current) -> Result<()> {
+    vault.lock()
+}
+
+#[tauri::command]
+pub async fn vault_status(vault: Current) -> Result<VaultStatus> {
+    let encrypted = vault.storage.is_encrypted();
+    let unsealed_attachments = match encrypted {
+        true => fs::read_dir(vault.files.attachments_dir())
+            .into_iter()
+            .flatten()
+            .flatten()
+            .filter(|entry| entry.file_type().is_ok_and(|kind| kind.is_file()))
+            .count(),
+        false => 0,
+    };
+    Ok(VaultStatus {
+        encrypted,
+        locked: vault.storage.is_locked(),
+        unsealed_attachments,
+    })
+}
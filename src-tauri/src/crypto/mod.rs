@@ -0,0 +1,135 @@
+pub mod commands;
+
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
+
+use crate::error::{Error, Result};
+
+/// Marks a stored value as sealed, followed by base64(nonce || ciphertext).
+const SEALED_PREFIX: &str = "enc:v1:";
+const NONCE_LEN: usize = 24;
+const SALT_LEN: usize = 16;
+
+/// Argon2id parameters and salt for deriving the vault key from a passphrase.
+///
+/// Stored next to the data so the key can be re-derived on unlock; none of
+/// it is secret.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeyParams {
+    salt: String,
+    m_cost: u32,
+    t_cost: u32,
+    p_cost: u32,
+}
+
+impl KeyParams {
+    pub fn generate() -> Self {
+        Self {
+            salt: BASE64.encode(rand::random::<[u8; SALT_LEN]>()),
+            m_cost: Params::DEFAULT_M_COST,
+            t_cost: Params::DEFAULT_T_COST,
+            p_cost: Params::DEFAULT_P_COST,
+        }
+    }
+
+    pub fn derive_key(&self, password: &str) -> Result<VaultKey> {
+        let salt = BASE64.decode(&self.salt).map_err(crypto_error)?;
+        let params =
+            Params::new(self.m_cost, self.t_cost, self.p_cost, Some(32)).map_err(crypto_error)?;
+        let mut key = VaultKey([0; 32]);
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(password.as_bytes(), &salt, &mut key.0)
+            .map_err(crypto_error)?;
+        Ok(key)
+    }
+}
+
+/// A 256-bit XChaCha20-Poly1305 key, wiped from memory when dropped.
+#[derive(Zeroize, ZeroizeOnDrop)]
+pub struct VaultKey([u8; 32]);
+
+impl VaultKey {
+    /// A key kept outside the vault, as written by [`VaultKey::to_bytes`].
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let bytes: [u8; 32] = bytes
+            .try_into()
+            .map_err(|_| Error::Crypto("stored key has the wrong length".into()))?;
+        Ok(Self(bytes))
+    }
+
+    /// The raw key, for handing to the OS keychain. Never store it anywhere
+    /// less protected.
+    pub fn to_bytes(&self) -> Zeroizing<[u8; 32]> {
+        Zeroizing::new(self.0)
+    }
+
+    pub fn seal(&self, plaintext: &str) -> Result<String> {
+        let payload = self.seal_bytes(plaintext.as_bytes())?;
+        Ok(format!("{SEALED_PREFIX}{}", BASE64.encode(payload)))
+    }
+
+    /// Decrypts a value produced by [`VaultKey::seal`]. A wrong key and a
+    /// tampered value are indistinguishable and both yield
+    /// [`Error::WrongPassword`].
+    pub fn open(&self, sealed: &str) -> Result<String> {
+        let encoded = sealed
+            .strip_prefix(SEALED_PREFIX)
+            .ok_or_else(|| Error::Crypto("value is not sealed".into()))?;
+        let payload = BASE64.decode(encoded).map_err(crypto_error)?;
+        String::from_utf8(self.open_bytes(&payload)?).map_err(crypto_error)
+    }
+
+    /// Binary form of [`VaultKey::seal`]: nonce followed by ciphertext,
+    /// without prefix or encoding.
+    pub fn seal_bytes(&self, plaintext: &[u8]) -> Result<Vec<u8>> {
+        let nonce = rand::random::<[u8; NONCE_LEN]>();
+        let ciphertext = self
+            .cipher()
+            .encrypt(&XNonce::from(nonce), plaintext)
+            .map_err(crypto_error)?;
+
+        let mut payload = Vec::with_capacity(NONCE_LEN + ciphertext.len());
+        payload.extend_from_slice(&nonce);
+        payload.extend_from_slice(&ciphertext);
+        Ok(payload)
+    }
+
+    pub fn open_bytes(&self, payload: &[u8]) -> Result<Vec<u8>> {
+        if payload.len() < NONCE_LEN {
+            return Err(Error::Crypto("sealed value is truncated".into()));
+        }
+        let (nonce, ciphertext) = payload.split_at(NONCE_LEN);
+        let nonce = XNonce::try_from(nonce).map_err(crypto_error)?;
+        self.cipher()
+            .decrypt(&nonce, ciphertext)
+            .map_err(|_| Error::WrongPassword)
+    }
+
+    /// HMAC-SHA256 of `data` under this key, hex-encoded: a name for
+    /// content that gives nothing away to whoever lacks the key.
+    pub fn digest(&self, data: &[u8]) -> String {
+        let mut mac = <Hmac<Sha256> as hmac::KeyInit>::new_from_slice(&self.0)
+            .expect("HMAC takes keys of any length");
+        mac.update(data);
+        hex::encode(mac.finalize().into_bytes())
+    }
+
+    fn cipher(&self) -> XChaCha20Poly1305 {
+        XChaCha20Poly1305::new(&Key::from(self.0))
+    }
+}
+
+pub fn is_sealed(value: &str) -> bool {
+    value.starts_with(SEALED_PREFIX)
+}
+
+fn crypto_error(err: impl std::fmt::Display) -> Error {
+    Error::Crypto(err.to_string())
+}
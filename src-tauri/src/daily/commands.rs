@@ -0,0 +1,51 @@
+use chrono::Local;
+
+use super::{DailyConfig, DayActivity};
+use crate::error::Result;
+use crate::storage::Note;
+use crate::vaults::Current;
+
+/// The daily note for `date` (`YYYY-MM-DD`, today if omitted), created
+/// from the template the first time.
+#[tauri::command]
+pub async fn get_or_create_daily_note(vault: Current, date: Option<String>) -> Result<Note> {
+    let date = match date {
+        Some(date) => super::parse_date(&date)?,
+        None => Local::now().date_naive(),
+    };
+    tauri::async_runtime::spawn_blocking(move || super::get_or_create(&vault, date)).await?
+}
+
+/// Per-day note activity from `start` to `end`, inclusive, for the calendar.
+#[tauri::command]
+pub async fn notes_for_date_range(
+    vault: Current,
+    start: String,
+    end: String,
+) -> Result<Vec<DayActivity>> {
+    let (start, end) = (super::parse_date(&start)?, super::parse_date(&end)?);
+    super::activity(&vault.storage, start, end)
+}
+
+/// Changes where daily notes go, how they are titled and what they start
+/// with. Omitted fields keep their current values.
+#[tauri::command]
+pub async fn configure_daily_notes(
+    vault: Current,
+    folder: Option<String>,
+    title: Option<String>,
+    template: Option<String>,
+) -> Result<DailyConfig> {
+    let mut config = super::config_of(&vault.storage)?;
+    if let Some(folder) = folder {
+        config.folder = folder;
+    }
+    if let Some(title) = title {
+        config.title = title;
+    }
+    if let Some(template) = template {
+        config.template = template;
+    }
+    super::set_config(&vault.storage, &config)?;
+    Ok(config)
+}
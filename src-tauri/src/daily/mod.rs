@@ -0,0 +1,239 @@
+pub mod commands;
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use chrono::{DateTime, Local, NaiveDate};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+use crate::links;
+use crate::metadata::FrontMatter;
+use crate::storage::{Note, NotePatch, Storage};
+use crate::trash;
+use crate::vault::Vault;
+
+const CONFIG_KEY: &str = "daily.config";
+/// Date of each daily note to its id, so notes can be renamed or moved.
+const INDEX_KEY: &str = "daily.notes";
+const DATE_FORMAT: &str = "%Y-%m-%d";
+/// Longest range [`activity`] answers for, about three years.
+const MAX_RANGE_DAYS: i64 = 3 * 366;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct DailyConfig {
+    /// Folder of a day's note, as a `strftime` pattern such as `Daily/%Y/%m`.
+    pub folder: String,
+    /// Title of a day's note, as a `strftime` pattern.
+    pub title: String,
+    /// Body of a new daily note. `{{date}}`, `{{title}}` and `{{weekday}}`
+    /// are filled in.
+    pub template: String,
+}
+
+impl Default for DailyConfig {
+    fn default() -> Self {
+        Self {
+            folder: "Daily/%Y".into(),
+            title: DATE_FORMAT.into(),
+            template: String::new(),
+        }
+    }
+}
+
+/// One day of the calendar.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DayActivity {
+    pub date: String,
+    pub daily_note_id: Option<String>,
+    /// Notes created that day.
+    pub created: usize,
+    /// Notes last edited that day.
+    pub updated: usize,
+}
+
+pub fn config_of(storage: &Storage) -> Result<DailyConfig> {
+    match storage.meta(CONFIG_KEY)? {
+        Some(json) => Ok(serde_json::from_str(&json)?),
+        None => Ok(DailyConfig::default()),
+    }
+}
+
+pub fn set_config(storage: &Storage, config: &DailyConfig) -> Result<()> {
+    for pattern in [&config.folder, &config.title] {
+        // chrono only reports a pattern it cannot fill in, such as one
+        // asking a date for its time, as a formatting error, which
+        // `to_string` turns into a panic. Try it on a day first.
+        let mut out = String::new();
+        if write!(out, "{}", Local::now().date_naive().format(pattern)).is_err() {
+            return Err(Error::InvalidInput(format!(
+                "not a valid date pattern: {pattern}"
+            )));
+        }
+    }
+    storage.set_meta(CONFIG_KEY, &serde_json::to_string(config)?)
+}
+
+pub fn parse_date(date: &str) -> Result<NaiveDate> {
+    NaiveDate::parse_from_str(date.trim(), DATE_FORMAT)
+        .map_err(|_| Error::InvalidInput(format!("not a date: {date}")))
+}
+
+/// The note for `date`, created from the template if there is none yet.
+/// A new note links to the daily notes before and after it, and they are
+/// updated to link back.
+pub fn get_or_create(vault: &Vault, date: NaiveDate) -> Result<Note> {
+    let mut index = live_index(&vault.storage)?;
+    let key = date.format(DATE_FORMAT).to_string();
+    if let Some(id) = index.get(&key) {
+        return vault.storage.get_note(id);
+    }
+
+    let config = config_of(&vault.storage)?;
+    let title = date.format(&config.title).to_string();
+    let folder = date
+        .format(&config.folder)
+        .to_string()
+        .trim_matches('/')
+        .to_owned();
+    let body = config
+        .template
+        .replace("{{date}}", &key)
+        .replace("{{title}}", &title)
+        .replace("{{weekday}}", &date.format("%A").to_string());
+    let note = vault.create_note(&title, &body, &folder)?;
+    index.insert(key.clone(), note.id.clone());
+    save_index(&vault.storage, &index)?;
+
+    let previous = index.range(..key.clone()).next_back();
+    let next = index.range(key.clone()..).nth(1);
+    let mut touched = vec![note.id.clone()];
+    touched.extend(previous.map(|(_, id)| id.clone()));
+    touched.extend(next.map(|(_, id)| id.clone()));
+    let mut result = note;
+    for id in touched {
+        let linked = relink(vault, &index, &id)?;
+        if linked.id == result.id {
+            result = linked;
+        }
+    }
+    Ok(result)
+}
+
+/// Notes created and edited on each day from `start` to `end`, inclusive,
+/// with the day's daily note if there is one.
+pub fn activity(storage: &Storage, start: NaiveDate, end: NaiveDate) -> Result<Vec<DayActivity>> {
+    if end < start {
+        return Err(Error::InvalidInput(
+            "the range ends before it starts".into(),
+        ));
+    }
+    if (end - start).num_days() > MAX_RANGE_DAYS {
+        return Err(Error::InvalidInput("the range is too long".into()));
+    }
+    let index = live_index(storage)?;
+    let mut days: BTreeMap<NaiveDate, DayActivity> = start
+        .iter_days()
+        .take_while(|day| *day <= end)
+        .map(|day| {
+            let date = day.format(DATE_FORMAT).to_string();
+            let daily_note_id = index.get(&date).cloned();
+            let activity = DayActivity {
+                date,
+                daily_note_id,
+                created: 0,
+                updated: 0,
+            };
+            (day, activity)
+        })
+        .collect();
+    for note in storage.list_notes(None)? {
+        if let Some(day) = local_day(note.created_at).and_then(|day| days.get_mut(&day)) {
+            day.created += 1;
+        }
+        if let Some(day) = local_day(note.updated_at).and_then(|day| days.get_mut(&day)) {
+            day.updated += 1;
+        }
+    }
+    Ok(days.into_values().collect())
+}
+
+/// The index without notes that were deleted or trashed.
+fn live_index(storage: &Storage) -> Result<BTreeMap<String, String>> {
+    let index: BTreeMap<String, String> = match storage.meta(INDEX_KEY)? {
+        Some(json) => serde_json::from_str(&json)?,
+        None => BTreeMap::new(),
+    };
+    let before = index.len();
+    let mut live = BTreeMap::new();
+    for (date, id) in index {
+        if storage.has_note(&id)? && !trash::is_trashed(storage, &id)? {
+            live.insert(date, id);
+        }
+    }
+    if live.len() != before {
+        save_index(storage, &live)?;
+    }
+    Ok(live)
+}
+
+fn save_index(storage: &Storage, index: &BTreeMap<String, String>) -> Result<()> {
+    storage.set_meta(INDEX_KEY, &serde_json::to_string(index)?)
+}
+
+/// Rewrites the navigation line of daily note `id` to point at its
+/// neighbours in `index`.
+fn relink(vault: &Vault, index: &BTreeMap<String, String>, id: &str) -> Result<Note> {
+    let note = vault.storage.get_note(id)?;
+    let Some(date) = index
+        .iter()
+        .find(|(_, entry)| *entry == id)
+        .map(|(date, _)| date.clone())
+    else {
+        return Ok(note);
+    };
+    let previous = index.range(..date.clone()).next_back();
+    let next = index.range(date.clone()..).nth(1);
+    let nav = [
+        previous.map(|(date, id)| format!("[← {date}]({})", links::note_url(id, None))),
+        next.map(|(date, id)| format!("[{date} →]({})", links::note_url(id, None))),
+    ]
+    .into_iter()
+    .flatten()
+    .collect::<Vec<_>>()
+    .join(" · ");
+
+    let (front_matter, markdown) = FrontMatter::split(&note.body);
+    let rest = match markdown.split_once('\n') {
+        Some((first, rest)) if is_nav(first) => rest.trim_start_matches('\n'),
+        None if is_nav(markdown) => "",
+        _ => markdown,
+    };
+    let block = front_matter.map(|fm| fm.render()).unwrap_or_default();
+    let body = match (nav.is_empty(), rest.is_empty()) {
+        (true, _) => format!("{block}{rest}"),
+        (false, true) => format!("{block}{nav}\n"),
+        (false, false) => format!("{block}{nav}\n\n{rest}"),
+    };
+    if body == note.body {
+        return Ok(note);
+    }
+    let patch = NotePatch {
+        body: Some(body),
+        ..Default::default()
+    };
+    vault.update_note(id, patch)
+}
+
+/// Whether `line` is a navigation line written by [`relink`].
+fn is_nav(line: &str) -> bool {
+    let line = line.trim();
+    line.starts_with("[← ")
+        || (line.starts_with('[') && line.ends_with(')') && line.contains(" →]("))
+}
+
+fn local_day(millis: i64) -> Option<NaiveDate> {
+    DateTime::from_timestamp_millis(millis).map(|time| time.with_timezone(&Local).date_naive())
+}
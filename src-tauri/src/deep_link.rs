@@ -0,0 +1,216 @@
+use std::sync::Mutex;
+
+use tauri::webview::{PageLoadEvent, PageLoadPayload};
+use tauri::{AppHandle, Emitter, Manager, Webview};
+use tauri_plugin_deep_link::DeepLinkExt;
+use tauri_plugin_dialog::{DialogExt, MessageDialogButtons};
+use tokio::sync::oneshot;
+use url::Url;
+
+use crate::error::{Error, Result};
+use crate::vaults::{self, Vaults};
+use crate::{beam, navigation};
+
+/// Event carrying the message of a link that could not be followed.
+pub const ERROR_EVENT: &str = "deep-link-error";
+
+const SCHEME: &str = "notes";
+
+/// What a `notes://` link asks for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Link {
+    /// `notes://open/<id>`, with `?vault=<id>` for a note outside the
+    /// main window's vault.
+    Open { id: String, vault: Option<String> },
+    /// `notes://new?title=...&body=...&folder=...`
+    New {
+        title: String,
+        body: String,
+        folder: String,
+    },
+    /// `notes://search?q=...`
+    Search(String),
+    /// `notes://beam/<code>`, a note another device is beaming.
+    Beam(String),
+}
+
+impl Link {
+    pub fn parse(url: &Url) -> Result<Self> {
+        if url.scheme() != SCHEME {
+            return Err(Error::InvalidInput(format!(
+                "not a {SCHEME}:// link: {url}"
+            )));
+        }
+        let param = |key: &str| {
+            url.query_pairs()
+                .find(|(name, _)| name == key)
+                .map(|(_, value)| value.into_owned())
+        };
+        match url.host_str() {
+            Some("open") => {
+                let id = url.path().trim_matches('/');
+                if id.is_empty() {
+                    return Err(Error::InvalidInput(format!("no note id in {url}")));
+                }
+                Ok(Link::Open {
+                    id: id.to_owned(),
+                    vault: param("vault"),
+                })
+            }
+            Some("new") => Ok(Link::New {
+                title: param("title").unwrap_or_else(|| "Untitled".into()),
+                body: param("body").unwrap_or_default(),
+                folder: param("folder").unwrap_or_default(),
+            }),
+            Some("search") => Ok(Link::Search(param("q").unwrap_or_default())),
+            Some("beam") => Ok(Link::Beam(url.path().trim_matches('/').to_owned())),
+            _ => Err(Error::InvalidInput(format!("unknown link: {url}"))),
+        }
+    }
+}
+
+/// Links the app was launched with, held until the main window has loaded
+/// and can receive the navigation events.
+struct LaunchLinks(Mutex<Option<Vec<Url>>>);
+
+/// Registers the `notes://` scheme where that happens at run time and starts
+/// following links. Links from a second launch arrive here through the
+/// single-instance plugin.
+pub fn start(app: &AppHandle) -> Result<()> {
+    // Installed bundles register the scheme themselves; AppImages and dev
+    // builds have to do it on launch.
+    #[cfg(any(target_os = "linux", all(debug_assertions, windows)))]
+    app.deep_link()
+        .register_all()
+        .map_err(|err| Error::InvalidInput(format!("cannot register {SCHEME}://: {err}")))?;
+
+    let launch = app
+        .deep_link()
+        .get_current()
+        .map_err(|err| Error::InvalidInput(err.to_string()))?;
+    app.manage(LaunchLinks(Mutex::new(launch)));
+
+    let handle = app.clone();
+    app.deep_link().on_open_url(move |event| {
+        for url in event.urls() {
+            follow_reporting(&handle, &url);
+        }
+    });
+    Ok(())
+}
+
+/// Follows the launch links once the main window's page has loaded.
+pub fn page_loaded(webview: &Webview, payload: &PageLoadPayload<'_>) {
+    if payload.event() != PageLoadEvent::Finished || webview.label() != navigation::MAIN_WINDOW {
+        return;
+    }
+    let app = webview.app_handle();
+    let Some(links) = app.try_state::<LaunchLinks>() else {
+        return;
+    };
+    let urls = links.0.lock().expect("launch links poisoned").take();
+    for url in urls.into_iter().flatten() {
+        follow_reporting(app, &url);
+    }
+}
+
+pub fn follow(app: &AppHandle, url: &Url) -> Result<()> {
+    match Link::parse(url)? {
+        Link::Open { id, vault } => {
+            if let Some(vault) = vault {
+                app.state::<Vaults>().show_in_primary(app, &vault)?;
+            }
+            // Fail here rather than leave the window to show nothing.
+            vaults::primary(app)?.storage.get_note(&id)?;
+            navigation::open_note(app, &id)
+        }
+        Link::New {
+            title,
+            body,
+            folder,
+        } => {
+            let app = app.clone();
+            tauri::async_runtime::spawn(async move {
+                let created = create_note(&app, &title, &body, &folder).await;
+                follow_error(&app, created);
+            });
+            Ok(())
+        }
+        Link::Search(query) => navigation::open_search(app, &query),
+        Link::Beam(code) => {
+            let app = app.clone();
+            tauri::async_runtime::spawn(async move {
+                let opened = receive_beam(&app, &code).await;
+                follow_error(&app, opened);
+            });
+            Ok(())
+        }
+    }
+}
+
+/// Makes the note a `new` link asks for once the user has agreed to it,
+/// since any page can open a link and fill the vault with whatever it
+/// likes.
+async fn create_note(app: &AppHandle, title: &str, body: &str, folder: &str) -> Result<()> {
+    let vault = vaults::primary(app)?;
+    let place = match folder.trim_matches('/') {
+        "" => String::new(),
+        folder => format!(" in {folder}"),
+    };
+    let message = format!(
+        "A link wants to make a note \"{title}\"{place} ({} characters). Only go ahead if \
+         you opened the link yourself.",
+        body.chars().count()
+    );
+    if !confirm(app, "Create a note", message, "Create").await {
+        return Ok(());
+    }
+    let note = vault.create_note(title, body, folder)?;
+    navigation::open_note(app, &note.id)
+}
+
+/// Takes in the beam at `code` once the user has seen what it holds and
+/// which device is sending it: any page can open a link.
+async fn receive_beam(app: &AppHandle, code: &str) -> Result<()> {
+    let vault = vaults::primary(app)?;
+    let offer = beam::offer(code).await?;
+    let message = format!(
+        "\"{}\" ({:.1} MB) is being beamed from {} at {}. Only take it in if you \
+         started this beam yourself.",
+        offer.title,
+        offer.size as f64 / (1024.0 * 1024.0),
+        offer.sender,
+        offer.address
+    );
+    if !confirm(app, "Receive a beamed note", message, "Receive").await {
+        return Ok(());
+    }
+    let note = beam::receive(&vault, code).await?;
+    navigation::open_note(app, &note.id)
+}
+
+/// Asks the user to go ahead with what a link wants to do.
+async fn confirm(app: &AppHandle, title: &str, message: String, accept: &str) -> bool {
+    let (sender, receiver) = oneshot::channel();
+    app.dialog()
+        .message(message)
+        .title(title)
+        .buttons(MessageDialogButtons::OkCancelCustom(
+            accept.into(),
+            "Cancel".into(),
+        ))
+        .show(move |accepted| {
+            let _ = sender.send(accepted);
+        });
+    receiver.await.unwrap_or(false)
+}
+
+fn follow_reporting(app: &AppHandle, url: &Url) {
+    follow_error(app, follow(app, url));
+}
+
+fn follow_error(app: &AppHandle, result: Result<()>) {
+    if let Err(err) = result {
+        let _ = app.emit(ERROR_EVENT, err.to_string());
+    }
+}
@@ -0,0 +1,17 @@
+use tauri::AppHandle;
+
+use super::{Diagram, DiagramKind};
+use crate::error::Result;
+use crate::vaults::Current;
+
+/// Renders a Mermaid, PlantUML or Graphviz diagram to SVG, or returns the
+/// one rendered before from the same source.
+#[tauri::command]
+pub async fn render_diagram(
+    app: AppHandle,
+    vault: Current,
+    kind: DiagramKind,
+    source: String,
+) -> Result<Diagram> {
+    super::render(&app, &vault, kind, &source).await
+}
@@ -0,0 +1,245 @@
+//! Diagram code blocks (Mermaid, PlantUML and Graphviz) rendered to SVG by
+//! the tools themselves, found like pandoc is. Each result goes into the
+//! attachment store, and is looked up by a hash of its source before any
+//! tool runs again. What the tools write is cleaned before it is kept or
+//! shown; see [`svg`].
+
+pub mod commands;
+mod svg;
+
+use std::fs;
+use std::time::Duration;
+
+use rusqlite::{params, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tauri::AppHandle;
+use tauri_plugin_shell::process::CommandEvent;
+use tauri_plugin_shell::ShellExt;
+
+use crate::error::{Error, Result};
+use crate::files::Staging;
+use crate::storage::now_millis;
+use crate::vault::Vault;
+
+/// Longest diagram source taken.
+const MAX_SOURCE: usize = 256 * 1024;
+/// Mermaid starts a headless browser, which takes a while the first time.
+const TIMEOUT: Duration = Duration::from_secs(60);
+const MERMAID_CONFIG: &str = r#"{"htmlLabels": false, "flowchart": {"htmlLabels": false}}"#;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiagramKind {
+    Mermaid,
+    #[serde(alias = "puml")]
+    Plantuml,
+    #[serde(alias = "dot")]
+    Graphviz,
+}
+
+impl DiagramKind {
+    /// The kind a fenced code block's language names, if any.
+    pub fn from_language(language: &str) -> Option<Self> {
+        match language.to_ascii_lowercase().as_str() {
+            "mermaid" => Some(Self::Mermaid),
+            "plantuml" | "puml" => Some(Self::Plantuml),
+            "graphviz" | "dot" => Some(Self::Graphviz),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Mermaid => "mermaid",
+            Self::Plantuml => "plantuml",
+            Self::Graphviz => "graphviz",
+        }
+    }
+
+    /// Where the tool is looked for: `PATH`, then the Homebrew prefixes
+    /// apps started from the Dock do not have on theirs.
+    fn programs(self) -> &'static [&'static str] {
+        match self {
+            Self::Mermaid => &["mmdc", "/opt/homebrew/bin/mmdc", "/usr/local/bin/mmdc"],
+            Self::Plantuml => &[
+                "plantuml",
+                "/opt/homebrew/bin/plantuml",
+                "/usr/local/bin/plantuml",
+            ],
+            Self::Graphviz => &["dot", "/opt/homebrew/bin/dot", "/usr/local/bin/dot"],
+        }
+    }
+
+    fn install_hint(self) -> &'static str {
+        match self {
+            Self::Mermaid => "install mermaid-cli (npm install -g @mermaid-js/mermaid-cli)",
+            Self::Plantuml => "install PlantUML",
+            Self::Graphviz => "install Graphviz",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Diagram {
+    /// The SVG in the attachment store, relative to the vault's files.
+    pub path: String,
+    pub svg: String,
+    /// Taken from the cache rather than rendered now.
+    pub cached: bool,
+}
+
+pub async fn render(
+    app: &AppHandle,
+    vault: &Vault,
+    kind: DiagramKind,
+    source: &str,
+) -> Result<Diagram> {
+    if source.trim().is_empty() {
+        return Err(Error::InvalidInput("the diagram is empty".into()));
+    }
+    if source.len() > MAX_SOURCE {
+        return Err(Error::InvalidInput(format!(
+            "diagrams are limited to {} KB of source",
+            MAX_SOURCE / 1024
+        )));
+    }
+    let hash = source_hash(kind, source);
+    if let Some(path) = cached(vault, &hash)? {
+        // Collected with the unused attachments perhaps, then it is rendered again.
+        if let Ok(svg) = fs::read_to_string(vault.files.attachments_dir().join(file_name(&path))) {
+            return Ok(Diagram {
+                path,
+                // Kept before SVGs were cleaned, perhaps.
+                svg: svg::sanitize(&svg)?,
+                cached: true,
+            });
+        }
+    }
+    let svg = svg::sanitize(&run(app, kind, source).await?)?;
+    let path = vault.files.save_attachment("diagram.svg", svg.as_bytes())?;
+    vault.storage.conn().execute(
+        "INSERT OR REPLACE INTO diagrams (source_hash, path, rendered_at) VALUES (?1, ?2, ?3)",
+        params![hash, path, now_millis()],
+    )?;
+    Ok(Diagram {
+        path,
+        svg,
+        cached: false,
+    })
+}
+
+fn source_hash(kind: DiagramKind, source: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(kind.name());
+    hasher.update([0]);
+    hasher.update(source);
+    hex::encode(hasher.finalize())
+}
+
+fn cached(vault: &Vault, hash: &str) -> Result<Option<String>> {
+    Ok(vault
+        .storage
+        .conn()
+        .query_row(
+            "SELECT path FROM diagrams WHERE source_hash = ?1",
+            [hash],
+            |row| row.get(0),
+        )
+        .optional()?)
+}
+
+fn file_name(path: &str) -> &str {
+    path.rsplit('/').next().unwrap_or(path)
+}
+
+/// Runs the first of the tool's programs that starts, in a staging
+/// directory holding the source.
+async fn run(app: &AppHandle, kind: DiagramKind, source: &str) -> Result<String> {
+    let staging = Staging::new("diagram")?;
+    let input = staging.0.join("diagram.txt");
+    let output = staging.0.join("diagram.svg");
+    // Labels as SVG text rather than HTML, which the cleaning would drop.
+    let config = staging.0.join("mermaid.json");
+    fs::write(&input, source)?;
+    fs::write(&config, MERMAID_CONFIG)?;
+    let args: Vec<&std::ffi::OsStr> = match kind {
+        DiagramKind::Mermaid => vec![
+            "--input".as_ref(),
+            input.as_os_str(),
+            "--output".as_ref(),
+            output.as_os_str(),
+            "--configFile".as_ref(),
+            config.as_os_str(),
+            "--backgroundColor".as_ref(),
+            "transparent".as_ref(),
+        ],
+        // PlantUML names its output after the input, next to it.
+        DiagramKind::Plantuml => vec![
+            "-tsvg".as_ref(),
+            "-charset".as_ref(),
+            "UTF-8".as_ref(),
+            input.as_os_str(),
+        ],
+        DiagramKind::Graphviz => vec![
+            "-Tsvg".as_ref(),
+            "-o".as_ref(),
+            output.as_os_str(),
+            input.as_os_str(),
+        ],
+    };
+    let shell = app.shell();
+    for program in kind.programs() {
+        let command = shell
+            .command(program)
+            .args(&args)
+            .current_dir(&staging.0)
+            // Keeps !include and the like from reading other files.
+            .env("PLANTUML_SECURITY_PROFILE", "SANDBOX");
+        let Ok((mut events, child)) = command.spawn() else {
+            continue;
+        };
+        let finished = async {
+            let (mut code, mut stdout, mut stderr) = (None, Vec::new(), Vec::new());
+            while let Some(event) = events.recv().await {
+                match event {
+                    CommandEvent::Stdout(line) => stdout.extend(line.into_iter().chain([b'\n'])),
+                    CommandEvent::Stderr(line) => stderr.extend(line.into_iter().chain([b'\n'])),
+                    CommandEvent::Terminated(payload) => code = payload.code,
+                    _ => {}
+                }
+            }
+            (code, stdout, stderr)
+        };
+        let (code, stdout, stderr) = match tokio::time::timeout(TIMEOUT, finished).await {
+            Ok(output) => output,
+            Err(_) => {
+                // The shell plugin waits on the process, so it is reaped
+                // once it has died.
+                let _ = child.kill();
+                return Err(Error::Diagram(format!(
+                    "{} took longer than {} seconds",
+                    kind.name(),
+                    TIMEOUT.as_secs()
+                )));
+            }
+        };
+        if code != Some(0) {
+            let stderr = String::from_utf8_lossy(&stderr);
+            let stdout = String::from_utf8_lossy(&stdout);
+            let message = match stderr.trim() {
+                "" => stdout.trim(),
+                stderr => stderr,
+            };
+            return Err(Error::Diagram(message.to_owned()));
+        }
+        return fs::read_to_string(&output)
+            .map_err(|_| Error::Diagram(format!("{} did not produce an SVG", kind.name())));
+    }
+    Err(Error::Diagram(format!(
+        "{} was not found; {}",
+        kind.name(),
+        kind.install_hint()
+    )))
+}
@@ -0,0 +1,107 @@
+//! What the diagram tools write is shown inline in the preview, and the
+//! source comes from the note, so the SVG is cleaned first: no scripts,
+//! no embedded HTML, no event handlers and no links that run code.
+
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::{Reader, Writer, XmlVersion};
+
+use crate::error::{Error, Result};
+use crate::xml;
+
+/// Elements dropped along with everything in them.
+const DROPPED: &[&str] = &[
+    "script",
+    "foreignobject",
+    "iframe",
+    "object",
+    "embed",
+    "handler",
+    "listener",
+    // Can set an attribute, such as a link, after the cleaning.
+    "set",
+    "animate",
+];
+
+/// `svg` without what could run code in the preview.
+pub fn sanitize(svg: &str) -> Result<String> {
+    let mut reader = Reader::from_str(svg);
+    let mut writer = Writer::new(Vec::new());
+    // Depth inside a dropped element, if in one.
+    let mut dropped = 0usize;
+    loop {
+        let event = reader.read_event().map_err(svg_error)?;
+        match event {
+            Event::Eof => break,
+            Event::Start(start) => {
+                if dropped > 0 || is_dropped(&start) {
+                    dropped += 1;
+                    continue;
+                }
+                writer
+                    .write_event(Event::Start(clean(&start)))
+                    .map_err(svg_error)?;
+            }
+            Event::Empty(start) if dropped == 0 && !is_dropped(&start) => writer
+                .write_event(Event::Empty(clean(&start)))
+                .map_err(svg_error)?,
+            Event::Empty(_) => {}
+            Event::End(end) => match dropped {
+                0 => writer.write_event(Event::End(end)).map_err(svg_error)?,
+                _ => dropped -= 1,
+            },
+            // Entities a doctype declares, and processing instructions,
+            // have no place in a diagram.
+            Event::DocType(_) | Event::PI(_) => {}
+            Event::GeneralRef(reference) if xml::resolve_entity(&reference).is_none() => {}
+            event if dropped == 0 => writer.write_event(event).map_err(svg_error)?,
+            _ => {}
+        }
+    }
+    String::from_utf8(writer.into_inner()).map_err(|err| svg_error(err.to_string()))
+}
+
+fn is_dropped(start: &BytesStart) -> bool {
+    let name = start.local_name().as_ref().to_ascii_lowercase();
+    DROPPED.contains(&name.as_str())
+}
+
+/// `start` without event handlers and links that are not to the diagram
+/// itself, the web or an embedded image.
+fn clean(start: &BytesStart) -> BytesStart<'static> {
+    let mut cleaned = BytesStart::new(start.name().as_ref().to_owned());
+    for attribute in start.attributes().flatten() {
+        let key = attribute.key.local_name().as_ref().to_ascii_lowercase();
+        if key.starts_with("on") {
+            continue;
+        }
+        if key == "href" || key == "src" {
+            let Ok(value) = attribute.normalized_value(XmlVersion::Implicit1_0) else {
+                continue;
+            };
+            if !safe_link(&value) {
+                continue;
+            }
+        }
+        cleaned.push_attribute(attribute);
+    }
+    cleaned.into_owned()
+}
+
+fn safe_link(link: &str) -> bool {
+    let link: String = link
+        .chars()
+        .filter(|c| !c.is_whitespace() && !c.is_control())
+        .collect::<String>()
+        .to_ascii_lowercase();
+    link.starts_with('#')
+        || link.starts_with("https:")
+        || link.starts_with("http:")
+        || (link.starts_with("data:image/") && !link.starts_with("data:image/svg"))
+}
+
+fn svg_error(err: impl ToString) -> Error {
+    Error::Diagram(format!(
+        "the diagram tool wrote a broken SVG: {}",
+        err.to_string()
+    ))
+}
@@ -0,0 +1,23 @@
+use super::RecoverableDraft;
+use crate::error::Result;
+use crate::vaults::Current;
+
+/// Hands the backend the editor's unsaved buffer for `noteId`. Cheap enough
+/// to call on every change; it reaches the disk within a few seconds.
+#[tauri::command]
+pub async fn stage_draft(vault: Current, note_id: String, content: String) -> Result<()> {
+    vault.drafts.stage(&note_id, content);
+    Ok(())
+}
+
+/// Drafts a previous session left unsaved, for the UI to offer after a
+/// crash.
+#[tauri::command]
+pub async fn list_recoverable_drafts(vault: Current) -> Result<Vec<RecoverableDraft>> {
+    vault.drafts.recoverable(&vault.storage)
+}
+
+#[tauri::command]
+pub async fn discard_draft(vault: Current, note_id: String) -> Result<()> {
+    vault.drafts.discard(&note_id)
+}
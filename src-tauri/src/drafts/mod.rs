@@ -0,0 +1,242 @@
+pub mod commands;
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::atomic;
+use crate::error::{Error, Result};
+use crate::storage::{now_millis, Note, Storage};
+use crate::trash;
+use crate::vault::Vault;
+
+/// How often staged buffers are written to the recovery area.
+const FLUSH_INTERVAL: Duration = Duration::from_secs(3);
+const EXTENSION: &str = "json";
+
+/// An unsaved editor buffer as kept on disk. The content is sealed like a
+/// note body.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Draft {
+    note_id: String,
+    content: String,
+    staged_at: i64,
+}
+
+/// A draft left behind by a session that did not get to save it.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecoverableDraft {
+    pub note_id: String,
+    pub title: String,
+    pub content: String,
+    pub staged_at: i64,
+    /// When the note was last saved; later than `staged_at` if it changed
+    /// since, say through sync.
+    pub note_updated_at: i64,
+}
+
+/// Unsaved editor buffers, one per note, under `<vault>/drafts`.
+///
+/// Staging only keeps the buffer in memory; the periodic flush writes what
+/// changed, so typing costs no disk writes. A draft goes away once the
+/// note is saved with content at least as new.
+pub struct Drafts {
+    dir: PathBuf,
+    /// Staged since the last flush, in plaintext.
+    pending: Mutex<HashMap<String, Draft>>,
+}
+
+impl Drafts {
+    pub fn open(dir: &Path) -> Result<Self> {
+        fs::create_dir_all(dir)?;
+        Ok(Self {
+            dir: dir.to_owned(),
+            pending: Mutex::new(HashMap::new()),
+        })
+    }
+
+    pub fn stage(&self, note_id: &str, content: String) {
+        let draft = Draft {
+            note_id: note_id.to_owned(),
+            content,
+            staged_at: now_millis(),
+        };
+        self.lock().insert(note_id.to_owned(), draft);
+    }
+
+    /// Writes out everything staged since the last flush. While the vault
+    /// is locked the drafts wait in memory, as they cannot be sealed.
+    pub fn flush(&self, storage: &Storage) -> Result<()> {
+        if storage.is_locked() {
+            return Ok(());
+        }
+        // Held throughout so a save cannot slip in between and be undone
+        // by an older draft landing after it.
+        let mut pending = self.lock();
+        for draft in pending.values() {
+            let sealed = Draft {
+                content: storage.seal_body(&draft.content)?,
+                ..draft.clone()
+            };
+            atomic::write(
+                &self.path(&draft.note_id),
+                serde_json::to_string(&sealed)?.as_bytes(),
+            )?;
+        }
+        pending.clear();
+        Ok(())
+    }
+
+    /// Reads the drafts on disk back into memory, as [`Storage::set_password`]
+    /// is about to change the key they are sealed with, so the next flush
+    /// writes them again under the new one. Buffers staged since they were
+    /// written stay as they are.
+    pub fn reopen(&self, storage: &Storage) -> Result<()> {
+        let mut pending = self.lock();
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some(EXTENSION) {
+                continue;
+            }
+            let Some(draft) = fs::read_to_string(&path)
+                .ok()
+                .and_then(|json| serde_json::from_str::<Draft>(&json).ok())
+            else {
+                continue;
+            };
+            if pending.contains_key(&draft.note_id) {
+                continue;
+            }
+            // Left for `recoverable` to drop.
+            let Ok(content) = storage.open_body(draft.content) else {
+                continue;
+            };
+            pending.insert(draft.note_id.clone(), Draft { content, ..draft });
+        }
+        Ok(())
+    }
+
+    /// Drops the draft of `note` if the save covers it.
+    pub fn saved(&self, note: &Note) -> Result<()> {
+        let mut pending = self.lock();
+        if pending
+            .get(&note.id)
+            .is_some_and(|draft| draft.staged_at <= note.updated_at)
+        {
+            pending.remove(&note.id);
+        }
+        match self.read(&note.id)? {
+            Some(draft) if draft.staged_at <= note.updated_at => self.remove_file(&note.id),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn discard(&self, note_id: &str) -> Result<()> {
+        self.lock().remove(note_id);
+        self.remove_file(note_id)
+    }
+
+    /// The drafts on disk that differ from their saved note, newest first.
+    /// Drafts of notes that are gone or trashed are dropped, and so are
+    /// drafts that will not open with the vault's key.
+    pub fn recoverable(&self, storage: &Storage) -> Result<Vec<RecoverableDraft>> {
+        let mut drafts = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some(EXTENSION) {
+                continue;
+            }
+            let Some(draft) = fs::read_to_string(&path)
+                .ok()
+                .and_then(|json| serde_json::from_str::<Draft>(&json).ok())
+            else {
+                continue;
+            };
+            if !storage.has_note(&draft.note_id)? || trash::is_trashed(storage, &draft.note_id)? {
+                self.remove_file(&draft.note_id)?;
+                continue;
+            }
+            let note = storage.get_note(&draft.note_id)?;
+            let content = match storage.open_body(draft.content) {
+                Ok(content) => content,
+                Err(Error::VaultLocked) => return Err(Error::VaultLocked),
+                Err(err) => {
+                    tracing::warn!("dropping the draft of {}: {err}", note.id);
+                    self.remove_file(&note.id)?;
+                    continue;
+                }
+            };
+            if content == note.body {
+                self.remove_file(&note.id)?;
+                continue;
+            }
+            drafts.push(RecoverableDraft {
+                note_id: note.id,
+                title: note.title,
+                content,
+                staged_at: draft.staged_at,
+                note_updated_at: note.updated_at,
+            });
+        }
+        drafts.sort_by_key(|draft| std::cmp::Reverse(draft.staged_at));
+        Ok(drafts)
+    }
+
+    fn read(&self, note_id: &str) -> Result<Option<Draft>> {
+        match fs::read_to_string(self.path(note_id)) {
+            Ok(json) => Ok(serde_json::from_str(&json).ok()),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn remove_file(&self, note_id: &str) -> Result<()> {
+        match fs::remove_file(self.path(note_id)) {
+            Err(err) if err.kind() != ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Note ids are UUIDs, but imported ones may not be; anything that is
+    /// not safe in a file name is hex-encoded.
+    fn path(&self, note_id: &str) -> PathBuf {
+        let safe = note_id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-');
+        let name = if safe {
+            note_id.to_owned()
+        } else {
+            hex::encode(note_id)
+        };
+        self.dir.join(format!("{name}.{EXTENSION}"))
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<String, Draft>> {
+        self.pending.lock().expect("drafts poisoned")
+    }
+}
+
+/// Flushes staged drafts every few seconds while the vault is open, and
+/// once more when it closes.
+pub fn spawn_periodic(vault: Arc<Vault>) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            tokio::time::sleep(FLUSH_INTERVAL).await;
+            let closed = vault.is_closed();
+            let handle = Arc::clone(&vault);
+            let _ =
+                tauri::async_runtime::spawn_blocking(move || handle.drafts.flush(&handle.storage))
+                    .await;
+            if closed {
+                break;
+            }
+        }
+    });
+}
@@ -0,0 +1,98 @@
+use serde::{Serialize, Serializer};
+
+/// Error type shared by every backend subsystem and returned from Tauri commands.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Database(#[from] rusqlite::Error),
+    #[error(transparent)]
+    Search(#[from] tantivy::TantivyError),
+    #[error(transparent)]
+    Git(#[from] git2::Error),
+    #[error(transparent)]
+    Http(#[from] reqwest::Error),
+    #[error(transparent)]
+    Archive(#[from] zip::result::ZipError),
+    #[error(transparent)]
+    Watch(#[from] notify_debouncer_full::notify::Error),
+    #[error(transparent)]
+    Image(#[from] image::ImageError),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    #[error(transparent)]
+    Tauri(#[from] tauri::Error),
+    #[error("note not found: {0}")]
+    NoteNotFound(String),
+    #[error("note {0} was changed elsewhere; the unsaved version was kept as a conflict copy")]
+    Conflict(String),
+    #[error("vault not found: {0}")]
+    VaultNotFound(String),
+    #[error("note is locked: {0}")]
+    NoteLocked(String),
+    #[error("note is read-only: {0}")]
+    ReadOnly(String),
+    #[error("vault is locked")]
+    VaultLocked,
+    #[error("the vault is open in another process; close it there first")]
+    VaultInUse,
+    #[error("incorrect password")]
+    WrongPassword,
+    #[error("encryption error: {0}")]
+    Crypto(String),
+    #[error("WebDAV error: {0}")]
+    WebDav(String),
+    #[error("S3 error: {0}")]
+    S3(String),
+    #[error("LAN sync: {0}")]
+    Lan(String),
+    #[error("text recognition failed: {0}")]
+    Ocr(String),
+    #[error("semantic search: {0}")]
+    Semantic(String),
+    #[error("collaboration: {0}")]
+    Collab(String),
+    #[error("document conversion failed: {0}")]
+    Pandoc(String),
+    #[error("diagram: {0}")]
+    Diagram(String),
+    #[error("export failed: {0}")]
+    Export(String),
+    #[error("cancelled")]
+    Cancelled,
+    #[error("keychain: {0}")]
+    Keychain(String),
+    #[error("email: {0}")]
+    Mail(String),
+    #[error("recording: {0}")]
+    Recorder(String),
+    #[error("transcription: {0}")]
+    Transcription(String),
+    #[error("assistant: {0}")]
+    Assistant(String),
+    #[error("automation: {0}")]
+    Automation(String),
+    #[error("plugin: {0}")]
+    Plugin(String),
+    #[error("update: {0}")]
+    Update(String),
+    #[error("moving the vault: {0}")]
+    Relocation(String),
+    #[error("beam: {0}")]
+    Beam(String),
+    #[cfg(windows)]
+    #[error("system search: {0}")]
+    OsIndex(String),
+    #[error("{0}")]
+    InvalidInput(String),
+}
+
+// Commands hand errors to the webview, which only needs the message.
+impl Serialize for Error {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
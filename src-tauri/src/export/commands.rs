@@ -0,0 +1,162 @@
+use std::path::{Path, PathBuf};
+
+use tauri::{AppHandle, Emitter, Manager};
+
+use super::epub::{self, EpubMetadata};
+use super::opml;
+use super::pdf::{self, PdfOptions};
+use super::print::{self, PrintJob};
+use super::site::{self, SiteOptions, SiteReport};
+use super::PROGRESS_EVENT;
+use crate::error::{Error, Result};
+use crate::files;
+use crate::jobs::{log, JobContext, JobKind, Jobs, Priority};
+use crate::vault::Vault;
+use crate::vaults::Current;
+
+/// Exports a note to PDF and returns the path of the written file.
+#[tauri::command]
+pub async fn export_note_pdf(
+    app: AppHandle,
+    vault: Current,
+    note_id: String,
+    options: Option<PdfOptions>,
+) -> Result<String> {
+    export_notes_pdf(app, vault, vec![note_id], options).await
+}
+
+/// Exports several notes into one PDF, in the order given, as a job.
+#[tauri::command]
+pub async fn export_notes_pdf(
+    app: AppHandle,
+    vault: Current,
+    note_ids: Vec<String>,
+    options: Option<PdfOptions>,
+) -> Result<String> {
+    let options = options.unwrap_or_default();
+    let dest = match &options.path {
+        Some(path) => PathBuf::from(path),
+        None => {
+            let title = match note_ids.as_slice() {
+                [id] => vault.storage.get_note(id)?.title,
+                _ => "Notes".to_owned(),
+            };
+            free_path(app.path().download_dir()?, &files::sanitize(&title), "pdf")
+        }
+    };
+    run_logged(&app, vault, "pdf", dest, move |vault, dest, _| {
+        pdf::export(vault, &note_ids, &options, dest)?;
+        Ok(dest.to_string_lossy().into_owned())
+    })
+    .await
+}
+
+/// Lays note `note_id` out for paper, with the page setup of `options`,
+/// and hands it to the system to print; see [`print::print`].
+#[tauri::command]
+pub async fn print_note(
+    app: AppHandle,
+    vault: Current,
+    note_id: String,
+    options: Option<PdfOptions>,
+) -> Result<PrintJob> {
+    tauri::async_runtime::spawn_blocking(move || {
+        print::print(&app, &vault, &[note_id], &options.unwrap_or_default())
+    })
+    .await?
+}
+
+/// Compiles the notes, in the order given, into an EPUB book at `dest`, as
+/// a job.
+#[tauri::command]
+pub async fn export_epub(
+    app: AppHandle,
+    vault: Current,
+    note_ids: Vec<String>,
+    metadata: Option<EpubMetadata>,
+    dest: String,
+) -> Result<()> {
+    run_logged(&app, vault, "epub", dest.into(), move |vault, dest, _| {
+        epub::export(vault, &note_ids, &metadata.unwrap_or_default(), dest)
+    })
+    .await
+}
+
+/// Writes the folder tree, or the part of it under `folder`, to `dest` as
+/// an OPML outline.
+#[tauri::command]
+pub async fn export_opml(vault: Current, folder: Option<String>, dest: String) -> Result<()> {
+    tauri::async_runtime::spawn_blocking(move || {
+        opml::export(&vault, folder.as_deref(), Path::new(&dest))
+    })
+    .await?
+}
+
+/// Writes the notebook to `dest_dir` as a static HTML site, as a job that
+/// can be cancelled between pages, emitting `export-progress` events as
+/// pages are written.
+#[tauri::command]
+pub async fn export_site(
+    app: AppHandle,
+    vault: Current,
+    dest_dir: String,
+    options: Option<SiteOptions>,
+) -> Result<SiteReport> {
+    let emitter = app.clone();
+    run_logged(
+        &app,
+        vault,
+        "site",
+        dest_dir.into(),
+        move |vault, dest, context| {
+            let options = options.unwrap_or_default();
+            site::export(vault, dest, &options, &mut |progress| {
+                context.progress(progress.current as usize, progress.total as usize);
+                let _ = emitter.emit(PROGRESS_EVENT, progress);
+                context.check()
+            })
+        },
+    )
+    .await
+}
+
+/// Runs an export to `dest` as a job, with an entry in the job log.
+async fn run_logged<T, F>(
+    app: &AppHandle,
+    vault: Current,
+    format: &'static str,
+    dest: PathBuf,
+    f: F,
+) -> Result<T>
+where
+    T: Send + 'static,
+    F: FnOnce(&Vault, &Path, &JobContext) -> Result<T> + Send + 'static,
+{
+    app.state::<Jobs>()
+        .run(JobKind::Export, Priority::User, move |context| {
+            if vault.storage.is_locked() {
+                return Err(Error::VaultLocked);
+            }
+            let id = log::start(
+                &vault.storage,
+                JobKind::Export,
+                format,
+                &dest.to_string_lossy(),
+            )?;
+            let result = f(&vault, &dest, context);
+            log::finish(&vault.storage, &id, &result)?;
+            result
+        })
+        .await
+}
+
+/// `<dir>/<stem>.<ext>`, or `<stem> (n).<ext>` if that already exists.
+pub(crate) fn free_path(dir: PathBuf, stem: &str, ext: &str) -> PathBuf {
+    (1..)
+        .map(|n| match n {
+            1 => dir.join(format!("{stem}.{ext}")),
+            n => dir.join(format!("{stem} ({n}).{ext}")),
+        })
+        .find(|path| !path.exists())
+        .expect("unbounded range")
+}
@@ -0,0 +1,237 @@
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::Path;
+
+use chrono::Utc;
+use serde::Deserialize;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use super::{escape, render_markdown, slug, Href, STYLE};
+use crate::error::{Error, Result};
+use crate::files::NoteFiles;
+use crate::links;
+use crate::protected;
+use crate::storage::Note;
+use crate::vault::Vault;
+
+const EPUB_STYLE: &str = r#"
+body { margin: 0 1em; }
+nav ol { list-style: none; padding-left: 0; }
+"#;
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct EpubMetadata {
+    /// Book title. Defaults to the first note's.
+    pub title: Option<String>,
+    pub author: Option<String>,
+    /// BCP 47 tag of the text, `en` unless given.
+    pub language: Option<String>,
+}
+
+/// An image carried into the book.
+struct Image {
+    /// Path inside the book, relative to the package document.
+    href: String,
+    media_type: &'static str,
+}
+
+/// Compiles the notes, in the order given, into an EPUB 3 book at `dest`:
+/// one chapter per note, a table of contents, links between the chosen
+/// notes kept within the book and images embedded.
+pub fn export(
+    vault: &Vault,
+    note_ids: &[String],
+    metadata: &EpubMetadata,
+    dest: &Path,
+) -> Result<()> {
+    if vault.storage.is_locked() {
+        return Err(Error::VaultLocked);
+    }
+    let notes = note_ids
+        .iter()
+        .map(|id| protected::readable(vault, id))
+        .collect::<Result<Vec<_>>>()?;
+    let Some(first) = notes.first() else {
+        return Err(Error::InvalidInput("no notes selected".into()));
+    };
+    let title = metadata.title.as_deref().unwrap_or(&first.title);
+    let language = metadata.language.as_deref().unwrap_or("en");
+    let chapters: HashMap<&str, String> = notes
+        .iter()
+        .enumerate()
+        .map(|(i, note)| (note.id.as_str(), format!("chapter-{}.xhtml", i + 1)))
+        .collect();
+
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut zip = ZipWriter::new(File::create(dest)?);
+    // Readers recognize the format by this entry, which has to come first
+    // and stay uncompressed.
+    zip.start_file(
+        "mimetype",
+        SimpleFileOptions::default().compression_method(CompressionMethod::Stored),
+    )?;
+    zip.write_all(b"application/epub+zip")?;
+    let options = SimpleFileOptions::default();
+    zip.start_file("META-INF/container.xml", options)?;
+    zip.write_all(CONTAINER.as_bytes())?;
+
+    let mut images: HashMap<String, Image> = HashMap::new();
+    for note in &notes {
+        let mut pending: Vec<(String, String, &str)> = Vec::new();
+        let body = render_markdown(&note.body, |dest| {
+            if let Some((target, heading)) = links::parse_note_url(dest) {
+                let id = vault.links.resolve(target).unwrap_or_default();
+                return match chapters.get(id.as_str()) {
+                    Some(chapter) => Href::Replace(match heading {
+                        Some(heading) => format!("{chapter}#{}", slug(&heading)),
+                        None => chapter.clone(),
+                    }),
+                    None => Href::Drop,
+                };
+            }
+            let Some(rel) = NoteFiles::resolve_from(&note.folder, dest) else {
+                return Href::Keep;
+            };
+            let known = images.get(&rel).map(|image| &image.href).or_else(|| {
+                pending
+                    .iter()
+                    .find(|(pending, ..)| *pending == rel)
+                    .map(|(_, href, _)| href)
+            });
+            if let Some(href) = known {
+                return Href::Replace(href.clone());
+            }
+            let media_type = media_type(&rel);
+            if media_type.is_none() || !vault.files.absolute(&rel).is_file() {
+                // Files other than images cannot be opened from a book.
+                return Href::Drop;
+            }
+            let ext = rel.rsplit_once('.').map_or("", |(_, ext)| ext);
+            let href = format!("images/{}.{ext}", images.len() + pending.len() + 1);
+            pending.push((rel, href.clone(), media_type.unwrap_or_default()));
+            Href::Replace(href)
+        });
+        for (rel, href, media_type) in pending {
+            zip.start_file(format!("OEBPS/{href}"), options)?;
+            zip.write_all(&fs::read(vault.files.absolute(&rel))?)?;
+            images.insert(rel, Image { href, media_type });
+        }
+        zip.start_file(format!("OEBPS/{}", chapters[note.id.as_str()]), options)?;
+        zip.write_all(
+            xhtml(
+                &note.title,
+                language,
+                &format!("<h1>{}</h1>\n{body}", escape(&note.title)),
+            )
+            .as_bytes(),
+        )?;
+    }
+
+    zip.start_file("OEBPS/style.css", options)?;
+    zip.write_all(format!("{STYLE}{EPUB_STYLE}").as_bytes())?;
+    zip.start_file("OEBPS/nav.xhtml", options)?;
+    zip.write_all(nav(title, language, &notes, &chapters).as_bytes())?;
+    zip.start_file("OEBPS/content.opf", options)?;
+    let mut images: Vec<Image> = images.into_values().collect();
+    images.sort_by(|a, b| a.href.cmp(&b.href));
+    zip.write_all(package(title, language, metadata, &notes, &chapters, &images).as_bytes())?;
+    zip.finish()?;
+    Ok(())
+}
+
+const CONTAINER: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles>
+    <rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/>
+  </rootfiles>
+</container>
+"#;
+
+fn xhtml(title: &str, language: &str, body: &str) -> String {
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<!DOCTYPE html>\n<html xmlns=\"http://www.w3.org/1999/xhtml\" xmlns:epub=\"http://www.idpf.org/2007/ops\" xml:lang=\"{lang}\" lang=\"{lang}\">\n<head>\n<meta charset=\"utf-8\"/>\n<title>{}</title>\n<link rel=\"stylesheet\" type=\"text/css\" href=\"style.css\"/>\n</head>\n<body>\n{body}</body>\n</html>\n",
+        escape(title),
+        lang = escape(language),
+    )
+}
+
+fn nav(title: &str, language: &str, notes: &[Note], chapters: &HashMap<&str, String>) -> String {
+    let mut items = String::new();
+    for note in notes {
+        items.push_str(&format!(
+            "<li><a href=\"{}\">{}</a></li>\n",
+            chapters[note.id.as_str()],
+            escape(&note.title)
+        ));
+    }
+    xhtml(
+        title,
+        language,
+        &format!(
+            "<nav epub:type=\"toc\" id=\"toc\">\n<h1>{}</h1>\n<ol>\n{items}</ol>\n</nav>\n",
+            escape(title)
+        ),
+    )
+}
+
+fn package(
+    title: &str,
+    language: &str,
+    metadata: &EpubMetadata,
+    notes: &[Note],
+    chapters: &HashMap<&str, String>,
+    images: &[Image],
+) -> String {
+    let mut manifest = String::from(concat!(
+        "<item id=\"nav\" href=\"nav.xhtml\" media-type=\"application/xhtml+xml\" properties=\"nav\"/>\n",
+        "<item id=\"style\" href=\"style.css\" media-type=\"text/css\"/>\n",
+    ));
+    let mut spine = String::new();
+    for (i, note) in notes.iter().enumerate() {
+        manifest.push_str(&format!(
+            "<item id=\"chapter-{n}\" href=\"{}\" media-type=\"application/xhtml+xml\"/>\n",
+            chapters[note.id.as_str()],
+            n = i + 1,
+        ));
+        spine.push_str(&format!("<itemref idref=\"chapter-{}\"/>\n", i + 1));
+    }
+    for (i, image) in images.iter().enumerate() {
+        manifest.push_str(&format!(
+            "<item id=\"image-{}\" href=\"{}\" media-type=\"{}\"/>\n",
+            i + 1,
+            image.href,
+            image.media_type
+        ));
+    }
+    let creator = metadata
+        .author
+        .as_deref()
+        .map(|author| format!("<dc:creator>{}</dc:creator>\n", escape(author)))
+        .unwrap_or_default();
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<package xmlns=\"http://www.idpf.org/2007/opf\" version=\"3.0\" unique-identifier=\"book-id\">\n<metadata xmlns:dc=\"http://purl.org/dc/elements/1.1/\">\n<dc:identifier id=\"book-id\">urn:uuid:{}</dc:identifier>\n<dc:title>{}</dc:title>\n<dc:language>{}</dc:language>\n{creator}<meta property=\"dcterms:modified\">{}</meta>\n</metadata>\n<manifest>\n{manifest}</manifest>\n<spine>\n{spine}</spine>\n</package>\n",
+        uuid::Uuid::new_v4(),
+        escape(title),
+        escape(language),
+        Utc::now().format("%Y-%m-%dT%H:%M:%SZ"),
+    )
+}
+
+/// The media type of an image EPUB readers have to support, or `None` for
+/// anything else.
+pub(crate) fn media_type(rel: &str) -> Option<&'static str> {
+    let ext = rel.rsplit_once('.')?.1.to_lowercase();
+    Some(match ext.as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "svg" => "image/svg+xml",
+        "webp" => "image/webp",
+        _ => return None,
+    })
+}
@@ -0,0 +1,174 @@
+use std::collections::HashMap;
+
+use pulldown_cmark::{html, Event, Options, Parser, Tag, TagEnd};
+use serde::Serialize;
+
+use crate::metadata::FrontMatter;
+use crate::render::highlight::{self, Styling};
+
+pub mod commands;
+pub mod epub;
+pub mod opml;
+pub mod pdf;
+pub mod print;
+pub mod site;
+
+/// Event carrying [`ExportProgress`] payloads while an export runs.
+pub const PROGRESS_EVENT: &str = "export-progress";
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportProgress {
+    pub current: u64,
+    pub total: u64,
+    /// Title of the note just written.
+    pub title: Option<String>,
+}
+
+/// Stylesheet shared by every HTML-based export.
+pub(crate) const STYLE: &str = r#"
+body { font-family: -apple-system, "Segoe UI", Helvetica, Arial, sans-serif; font-size: 11pt; line-height: 1.5; color: #1f2328; }
+.note + .note { break-before: page; }
+h1, h2, h3, h4, h5, h6 { line-height: 1.25; break-after: avoid; }
+img { max-width: 100%; }
+pre, blockquote, table, img { break-inside: avoid; }
+pre { background: #f6f8fa; padding: 8pt; border-radius: 4pt; white-space: pre-wrap; }
+code { font-family: ui-monospace, Menlo, Consolas, monospace; font-size: 0.9em; }
+blockquote { margin-left: 0; padding-left: 12pt; border-left: 3pt solid #d0d7de; color: #59636e; }
+table { border-collapse: collapse; }
+th, td { border: 1px solid #d0d7de; padding: 4pt 8pt; }
+li:has(> input[type=checkbox]) { list-style: none; }
+"#;
+
+/// What to do with a link or image destination while rendering.
+pub enum Href {
+    Keep,
+    Replace(String),
+    /// Render a link as plain text. Images are kept.
+    Drop,
+}
+
+/// Renders a note body to an HTML fragment, without its front matter.
+/// Every link and image destination is passed through `rewrite`, and
+/// headings get ids from [`slug`] so `#heading` fragments can target them.
+/// Fenced code is coloured by its language.
+pub fn render_markdown(body: &str, mut rewrite: impl FnMut(&str) -> Href) -> String {
+    let (_, markdown) = FrontMatter::split(body);
+    let options = Options::ENABLE_TABLES
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_TASKLISTS
+        | Options::ENABLE_FOOTNOTES;
+    // Links cannot nest, so one flag is enough to pair up a dropped start.
+    let mut dropped = false;
+    let events = Parser::new_ext(markdown, options).filter_map(|event| match event {
+        Event::Start(Tag::Link {
+            link_type,
+            dest_url,
+            title,
+            id,
+        }) => {
+            let dest_url = match rewrite(&dest_url) {
+                Href::Keep => dest_url,
+                Href::Replace(dest) => dest.into(),
+                Href::Drop => {
+                    dropped = true;
+                    return None;
+                }
+            };
+            Some(Event::Start(Tag::Link {
+                link_type,
+                dest_url,
+                title,
+                id,
+            }))
+        }
+        Event::End(TagEnd::Link) if dropped => {
+            dropped = false;
+            None
+        }
+        Event::Start(Tag::Image {
+            link_type,
+            dest_url,
+            title,
+            id,
+        }) => {
+            let dest_url = match rewrite(&dest_url) {
+                Href::Replace(dest) => dest.into(),
+                Href::Keep | Href::Drop => dest_url,
+            };
+            Some(Event::Start(Tag::Image {
+                link_type,
+                dest_url,
+                title,
+                id,
+            }))
+        }
+        event => Some(event),
+    });
+    let mut events: Vec<Event> = events.collect();
+    heading_ids(&mut events);
+    let mut out = String::new();
+    html::push_html(
+        &mut out,
+        highlight::code_blocks(events, Styling::Inline).into_iter(),
+    );
+    out
+}
+
+/// Gives each heading without an explicit id one from [`slug`], with a
+/// count appended to repeats.
+pub(crate) fn heading_ids(events: &mut [Event<'_>]) {
+    let mut seen: HashMap<String, u32> = HashMap::new();
+    for i in 0..events.len() {
+        if !matches!(events[i], Event::Start(Tag::Heading { id: None, .. })) {
+            continue;
+        }
+        let text: String = events[i + 1..]
+            .iter()
+            .take_while(|event| !matches!(event, Event::End(TagEnd::Heading(_))))
+            .filter_map(|event| match event {
+                Event::Text(text) | Event::Code(text) => Some(&**text),
+                _ => None,
+            })
+            .collect();
+        let mut anchor = slug(&text);
+        let count = seen.entry(anchor.clone()).or_default();
+        if *count > 0 {
+            anchor = format!("{anchor}-{count}");
+        }
+        *count += 1;
+        if let Event::Start(Tag::Heading { id, .. }) = &mut events[i] {
+            *id = Some(anchor.into());
+        }
+    }
+}
+
+/// The id a heading with `text` gets: lowercase words joined by `-`, the
+/// way most Markdown renderers do it.
+pub fn slug(text: &str) -> String {
+    text.trim()
+        .to_lowercase()
+        .chars()
+        .filter_map(|c| match c {
+            c if c.is_alphanumeric() || c == '-' || c == '_' => Some(c),
+            c if c.is_whitespace() => Some('-'),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Escapes text for use in HTML content or a quoted attribute.
+pub fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
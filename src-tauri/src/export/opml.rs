@@ -0,0 +1,177 @@
+//! The folder tree as an OPML outline, for outliners and mind-mapping
+//! tools. Folders and notes are outlines nested as they are in the vault,
+//! and each note's headings and list items are nested beneath it by level.
+
+use std::fmt::Write as _;
+use std::path::Path;
+
+use chrono::{DateTime, Utc};
+use pulldown_cmark::{Event, Options, Parser, Tag, TagEnd};
+
+use super::escape;
+use crate::error::{Error, Result};
+use crate::metadata::FrontMatter;
+use crate::storage::{FolderNode, TreeEntry};
+use crate::vault::Vault;
+use crate::{atomic, folders, protected};
+
+/// A note's headings and list items as a tree.
+#[derive(Default)]
+struct Item {
+    text: String,
+    children: Vec<Item>,
+}
+
+/// Writes the notes of `folder`, the whole vault when `None`, to `dest`.
+/// Protected notes are listed without their outline.
+pub fn export(vault: &Vault, folder: Option<&str>, dest: &Path) -> Result<()> {
+    if vault.storage.is_locked() {
+        return Err(Error::VaultLocked);
+    }
+    let tree = vault.storage.folder_tree(false)?;
+    let root = match folder.map(folders::normalize).transpose()? {
+        Some(path) if !path.is_empty() => {
+            find(&tree, &path).ok_or_else(|| Error::InvalidInput(format!("no folder {path}")))?
+        }
+        _ => &tree,
+    };
+    let title = match root.name.as_str() {
+        "" => "Notes",
+        name => name,
+    };
+    let mut out =
+        String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<opml version=\"2.0\">\n");
+    let _ = write!(
+        out,
+        "  <head>\n    <title>{}</title>\n    <dateCreated>{}</dateCreated>\n  </head>\n  <body>\n",
+        escape(title),
+        Utc::now().to_rfc2822()
+    );
+    for entry in &root.children {
+        write_entry(vault, &mut out, entry, 2)?;
+    }
+    out.push_str("  </body>\n</opml>\n");
+    atomic::write(dest, out.as_bytes())?;
+    Ok(())
+}
+
+fn find<'a>(folder: &'a FolderNode, path: &str) -> Option<&'a FolderNode> {
+    if folder.path == path {
+        return Some(folder);
+    }
+    folder.children.iter().find_map(|child| match child {
+        TreeEntry::Folder(child)
+            if path == child.path || path.starts_with(&format!("{}/", child.path)) =>
+        {
+            find(child, path)
+        }
+        _ => None,
+    })
+}
+
+fn write_entry(vault: &Vault, out: &mut String, entry: &TreeEntry, depth: usize) -> Result<()> {
+    let indent = "  ".repeat(depth);
+    match entry {
+        TreeEntry::Folder(folder) => {
+            let _ = writeln!(out, "{indent}<outline text=\"{}\">", escape(&folder.name));
+            for child in &folder.children {
+                write_entry(vault, out, child, depth + 1)?;
+            }
+            let _ = writeln!(out, "{indent}</outline>");
+        }
+        TreeEntry::Note(note) => {
+            let body = vault.storage.get_note(&note.id)?.body;
+            let items = match protected::is_protected(&body) {
+                true => Vec::new(),
+                false => outline(&body),
+            };
+            let created = DateTime::<Utc>::from_timestamp_millis(note.created_at)
+                .unwrap_or_default()
+                .to_rfc2822();
+            let _ = write!(
+                out,
+                "{indent}<outline text=\"{}\" type=\"note\" created=\"{created}\"",
+                escape(&note.title)
+            );
+            write_items(out, &items, depth);
+        }
+    }
+    Ok(())
+}
+
+/// Closes the outline opened just before, with `items` inside it.
+fn write_items(out: &mut String, items: &[Item], depth: usize) {
+    if items.is_empty() {
+        out.push_str("/>\n");
+        return;
+    }
+    out.push_str(">\n");
+    let indent = "  ".repeat(depth + 1);
+    for item in items {
+        let _ = write!(out, "{indent}<outline text=\"{}\"", escape(&item.text));
+        write_items(out, &item.children, depth + 1);
+    }
+    let _ = writeln!(out, "{}</outline>", "  ".repeat(depth));
+}
+
+/// The headings and list items of `body`, each nested under the nearest
+/// heading of a higher level or the item whose list it is in.
+fn outline(body: &str) -> Vec<Item> {
+    let (_, markdown) = FrontMatter::split(body);
+    // Ranks of the open items: heading levels 1 to 6, list depths above.
+    let mut open: Vec<(usize, Item)> = Vec::new();
+    let mut roots = Vec::new();
+    let mut depth = 0;
+    // Set while the text of a heading or item is being collected.
+    let mut collecting = false;
+
+    let close_to = |open: &mut Vec<(usize, Item)>, roots: &mut Vec<Item>, rank: usize| {
+        while open.last().is_some_and(|(top, _)| *top >= rank) {
+            let (_, item) = open.pop().expect("checked above");
+            match open.last_mut() {
+                Some((_, parent)) => parent.children.push(item),
+                None => roots.push(item),
+            }
+        }
+    };
+    for event in Parser::new_ext(markdown, Options::ENABLE_TASKLISTS) {
+        match event {
+            Event::Start(Tag::Heading { level, .. }) => {
+                close_to(&mut open, &mut roots, level as usize);
+                open.push((level as usize, Item::default()));
+                collecting = true;
+            }
+            Event::End(TagEnd::Heading(_)) => collecting = false,
+            Event::Start(Tag::List(_)) => {
+                depth += 1;
+                collecting = false;
+            }
+            Event::End(TagEnd::List(_)) => depth -= 1,
+            Event::Start(Tag::Item) => {
+                close_to(&mut open, &mut roots, 6 + depth);
+                open.push((6 + depth, Item::default()));
+                collecting = true;
+            }
+            Event::End(TagEnd::Item) => collecting = false,
+            Event::Start(Tag::CodeBlock(_)) => collecting = false,
+            Event::Text(text) | Event::Code(text) if collecting => {
+                if let Some((_, item)) = open.last_mut() {
+                    item.text.push_str(&text);
+                }
+            }
+            Event::SoftBreak | Event::HardBreak if collecting => {
+                if let Some((_, item)) = open.last_mut() {
+                    item.text.push(' ');
+                }
+            }
+            Event::TaskListMarker(done) if collecting => {
+                if let Some((_, item)) = open.last_mut() {
+                    item.text.push_str(if done { "[x] " } else { "[ ] " });
+                }
+            }
+            _ => {}
+        }
+    }
+    close_to(&mut open, &mut roots, 0);
+    roots
+}
@@ -0,0 +1,185 @@
+use std::collections::HashSet;
+use std::fmt::Display;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use headless_chrome::types::PrintToPdfOptions;
+use headless_chrome::{Browser, LaunchOptions};
+use serde::Deserialize;
+use url::Url;
+
+use super::{escape, render_markdown, Href, STYLE};
+use crate::error::{Error, Result};
+use crate::files::NoteFiles;
+use crate::links;
+use crate::protected;
+use crate::storage::Note;
+use crate::vault::Vault;
+
+const MM_PER_INCH: f64 = 25.4;
+const DEFAULT_MARGIN_MM: f64 = 15.0;
+const DEFAULT_HEADER: &str = "{title}";
+const DEFAULT_FOOTER: &str = "{page} / {pages}";
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PaperSize {
+    #[default]
+    A4,
+    Letter,
+    Legal,
+}
+
+impl PaperSize {
+    /// Width and height in inches, as the print protocol wants them.
+    fn inches(self) -> (f64, f64) {
+        match self {
+            Self::A4 => (8.27, 11.69),
+            Self::Letter => (8.5, 11.0),
+            Self::Legal => (8.5, 14.0),
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PdfOptions {
+    /// Output file. Defaults to the downloads folder.
+    pub path: Option<String>,
+    pub paper: PaperSize,
+    pub landscape: bool,
+    /// Margin on every side, in millimetres.
+    pub margin: Option<f64>,
+    /// Text at the top of each page. `{title}`, `{date}`, `{page}` and
+    /// `{pages}` are filled in; an empty string leaves it blank.
+    pub header: Option<String>,
+    /// Like `header`, at the bottom. Defaults to page numbers.
+    pub footer: Option<String>,
+}
+
+/// Renders the notes into one PDF at `dest`, each starting on a new page.
+/// Links between the selected notes jump within the document and images
+/// are embedded from the attachments folder.
+pub fn export(vault: &Vault, note_ids: &[String], options: &PdfOptions, dest: &Path) -> Result<()> {
+    let notes = note_ids
+        .iter()
+        .map(|id| protected::readable(vault, id))
+        .collect::<Result<Vec<_>>>()?;
+    let Some(first) = notes.first() else {
+        return Err(Error::InvalidInput("no notes selected".into()));
+    };
+    let title = match notes.len() {
+        1 => first.title.clone(),
+        n => format!("{} and {} more", first.title, n - 1),
+    };
+
+    let page = Page(
+        std::env::temp_dir().join(format!("notesdesktop-export-{}.html", uuid::Uuid::new_v4())),
+    );
+    fs::write(&page.0, document(vault, &title, &notes))?;
+    let pdf = print(&page.0, options)?;
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(dest, pdf)?;
+    Ok(())
+}
+
+/// The rendered HTML, removed once printed.
+struct Page(PathBuf);
+
+impl Drop for Page {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+fn document(vault: &Vault, title: &str, notes: &[Note]) -> String {
+    let selected: HashSet<&str> = notes.iter().map(|note| note.id.as_str()).collect();
+    let mut sections = String::new();
+    for note in notes {
+        let body = render_markdown(&note.body, |dest| {
+            if let Some((target, _)) = links::parse_note_url(dest) {
+                let id = vault.links.resolve(target).unwrap_or_default();
+                return match selected.contains(id.as_str()) {
+                    true => Href::Replace(format!("#note-{id}")),
+                    false => Href::Drop,
+                };
+            }
+            NoteFiles::resolve_from(&note.folder, dest)
+                .map(|rel| vault.files.absolute(&rel))
+                .filter(|path| path.exists())
+                .and_then(|path| Url::from_file_path(path).ok())
+                .map_or(Href::Keep, |url| Href::Replace(url.into()))
+        });
+        sections.push_str(&format!(
+            "<section class=\"note\" id=\"note-{}\">\n<h1>{}</h1>\n{body}</section>\n",
+            escape(&note.id),
+            escape(&note.title),
+        ));
+    }
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>{STYLE}</style>\n</head>\n<body>\n{sections}</body>\n</html>\n",
+        escape(title),
+    )
+}
+
+fn print(page: &Path, options: &PdfOptions) -> Result<Vec<u8>> {
+    let executable = headless_chrome::browser::default_executable().map_err(|_| {
+        Error::Export("PDF export needs Google Chrome, Chromium or Microsoft Edge".into())
+    })?;
+    let launch = LaunchOptions::default_builder()
+        .path(Some(executable))
+        .build()
+        .map_err(chrome)?;
+    let browser = Browser::new(launch).map_err(chrome)?;
+    let tab = browser.new_tab().map_err(chrome)?;
+    let url = Url::from_file_path(page)
+        .map_err(|_| Error::Export(format!("bad path: {}", page.display())))?;
+    // Navigation completes on the load event, after images have loaded.
+    tab.navigate_to(url.as_str())
+        .and_then(|tab| tab.wait_until_navigated())
+        .map_err(chrome)?;
+
+    let (width, height) = options.paper.inches();
+    let margin = options.margin.unwrap_or(DEFAULT_MARGIN_MM).max(0.0) / MM_PER_INCH;
+    let header = options.header.as_deref().unwrap_or(DEFAULT_HEADER);
+    let footer = options.footer.as_deref().unwrap_or(DEFAULT_FOOTER);
+    tab.print_to_pdf(Some(PrintToPdfOptions {
+        landscape: Some(options.landscape),
+        display_header_footer: Some(!header.is_empty() || !footer.is_empty()),
+        print_background: Some(true),
+        paper_width: Some(width),
+        paper_height: Some(height),
+        margin_top: Some(margin),
+        margin_bottom: Some(margin),
+        margin_left: Some(margin),
+        margin_right: Some(margin),
+        header_template: Some(template(header)),
+        footer_template: Some(template(footer)),
+        generate_document_outline: Some(true),
+        ..Default::default()
+    }))
+    .map_err(chrome)
+}
+
+/// A header or footer in Chrome's template markup, which fills in elements
+/// by class and needs its own styling since page styles do not apply.
+fn template(text: &str) -> String {
+    let mut html = escape(text);
+    for (placeholder, class) in [
+        ("{title}", "title"),
+        ("{date}", "date"),
+        ("{page}", "pageNumber"),
+        ("{pages}", "totalPages"),
+    ] {
+        html = html.replace(placeholder, &format!("<span class=\"{class}\"></span>"));
+    }
+    format!(
+        "<div style=\"width: 100%; font-size: 8pt; color: #6e7781; text-align: center; margin: 0 10mm;\">{html}</div>"
+    )
+}
+
+fn chrome(err: impl Display) -> Error {
+    Error::Export(err.to_string())
+}
@@ -0,0 +1,159 @@
+//! Printing through the system rather than the webview: notes are laid out
+//! as pages by the PDF renderer, then the document goes to the OS to print.
+
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+
+use super::pdf::{self, PdfOptions};
+use crate::error::{Error, Result};
+use crate::files;
+use crate::vault::Vault;
+
+/// Documents from earlier prints older than this are removed; a print
+/// queue may still be reading the newer ones.
+const KEEP: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Where the document went.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Delivery {
+    /// A print dialog came up for it.
+    #[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+    Dialog,
+    /// Straight to the default printer, the only way Windows prints a
+    /// file for another app.
+    #[cfg_attr(not(windows), allow(dead_code))]
+    Printer,
+    /// The default viewer, to print from there, where the system has no
+    /// dialog to offer.
+    #[cfg_attr(any(target_os = "macos", windows), allow(dead_code))]
+    Viewer,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PrintJob {
+    /// The laid-out document. It is removed once it has printed where
+    /// that can be told, and otherwise by a later print after a day.
+    pub path: String,
+    pub delivery: Delivery,
+}
+
+/// Renders the notes as [`pdf::export`] does and hands them to the system
+/// to print, as [`Delivery`] tells. `options.path` is ignored.
+pub fn print(
+    app: &AppHandle,
+    vault: &Vault,
+    note_ids: &[String],
+    options: &PdfOptions,
+) -> Result<PrintJob> {
+    let first = note_ids
+        .first()
+        .ok_or_else(|| Error::InvalidInput("no notes selected".into()))?;
+    let dir = app.path().app_cache_dir()?.join("print");
+    remove_old(&dir);
+    // The file name is what print queues show as the job's name.
+    let title = files::sanitize(&vault.storage.get_note(first)?.title);
+    let dest = dir
+        .join(uuid::Uuid::new_v4().to_string())
+        .join(format!("{title}.pdf"));
+    pdf::export(vault, note_ids, options, &dest)?;
+    let delivery = send(app, &dest)?;
+    Ok(PrintJob {
+        path: dest.to_string_lossy().into_owned(),
+        delivery,
+    })
+}
+
+fn remove_old(dir: &Path) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    let now = SystemTime::now();
+    for entry in entries.flatten() {
+        let old = entry
+            .metadata()
+            .and_then(|meta| meta.modified())
+            .is_ok_and(|modified| now.duration_since(modified).unwrap_or_default() > KEEP);
+        if old {
+            let _ = fs::remove_dir_all(entry.path());
+        }
+    }
+}
+
+/// Removes the directory of the print job whose document is `path`.
+#[cfg(any(target_os = "macos", windows))]
+fn remove_job(path: &Path) {
+    if let Some(job) = path.parent() {
+        let _ = fs::remove_dir_all(job);
+    }
+}
+
+/// Preview's print sheet, which stays up until the user prints or cancels.
+/// By then the document is spooled or not wanted.
+#[cfg(target_os = "macos")]
+fn send(_app: &AppHandle, path: &Path) -> Result<Delivery> {
+    const SCRIPT: &str = r#"on run argv
+    tell application "Preview"
+        activate
+        print (POSIX file (item 1 of argv)) with print dialog
+    end tell
+end run"#;
+    let output = std::process::Command::new("osascript")
+        .args(["-e", SCRIPT])
+        .arg(path)
+        .output()?;
+    remove_job(path);
+    // Cancelling the dialog is not a failure.
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if !output.status.success() && !stderr.contains("-128") {
+        return Err(Error::Export(format!("could not print: {}", stderr.trim())));
+    }
+    Ok(Delivery::Dialog)
+}
+
+/// The print verb of the app registered for PDFs, which prints to the
+/// default printer without asking. The document goes once that app is
+/// done with it, which may be when it is closed.
+#[cfg(windows)]
+fn send(_app: &AppHandle, path: &Path) -> Result<Delivery> {
+    use std::sync::mpsc;
+    use std::thread;
+
+    /// Without an app for PDFs, PowerShell gives up within this.
+    const FAILURE_WAIT: Duration = Duration::from_secs(3);
+
+    let quoted = path.to_string_lossy().replace('\'', "''");
+    let mut child = std::process::Command::new("powershell")
+        .args(["-NoProfile", "-NonInteractive", "-Command"])
+        .arg(format!(
+            "Start-Process -FilePath '{quoted}' -Verb Print -Wait"
+        ))
+        .spawn()?;
+    let path = path.to_owned();
+    let (failed, failure) = mpsc::channel();
+    thread::spawn(move || match child.wait() {
+        Ok(status) if status.success() => remove_job(&path),
+        _ => {
+            let _ = failed.send(());
+        }
+    });
+    match failure.recv_timeout(FAILURE_WAIT) {
+        Ok(()) => Err(Error::Export("no app is set up to print PDF files".into())),
+        Err(_) => Ok(Delivery::Printer),
+    }
+}
+
+#[cfg(not(any(target_os = "macos", windows)))]
+fn send(app: &AppHandle, path: &Path) -> Result<Delivery> {
+    use tauri_plugin_opener::OpenerExt;
+
+    app.opener()
+        .open_path(path.to_string_lossy(), None::<&str>)
+        .map_err(|err| Error::Export(format!("could not open the document: {err}")))?;
+    Ok(Delivery::Viewer)
+}
@@ -0,0 +1,334 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+
+use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS};
+use pulldown_cmark::{Event, Parser};
+use serde::{Deserialize, Serialize};
+
+use super::{escape, render_markdown, slug, ExportProgress, Href, STYLE};
+use crate::error::{Error, Result};
+use crate::files::NoteFiles;
+use crate::links;
+use crate::metadata::FrontMatter;
+use crate::protected;
+use crate::storage::Note;
+use crate::vault::Vault;
+
+/// Pages live below this folder so no note can collide with the index.
+const NOTES_DIR: &str = "notes";
+const DEFAULT_TITLE: &str = "Notes";
+/// How much of each note goes into the search index.
+const SEARCH_TEXT_CHARS: usize = 10_000;
+
+/// Characters escaped in each segment of a generated `href`.
+const PATH_SEGMENT: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'?')
+    .add(b'<')
+    .add(b'>')
+    .add(b'`')
+    .add(b'\\');
+
+const SITE_STYLE: &str = r#"
+body { max-width: 46rem; margin: 2rem auto; padding: 0 1rem; }
+nav { font-size: 0.9em; color: #59636e; margin-bottom: 1.5rem; }
+nav a { color: inherit; }
+.folder { color: #59636e; }
+#search { width: 100%; padding: 6pt 8pt; font: inherit; border: 1px solid #d0d7de; border-radius: 4pt; }
+"#;
+
+const SEARCH_SCRIPT: &str = r#"(function () {
+  var input = document.getElementById("search");
+  var results = document.getElementById("results");
+  var listing = document.getElementById("listing");
+  var notes = window.SEARCH_INDEX.map(function (note) {
+    return { note: note, text: (note.title + "\n" + note.text).toLowerCase() };
+  });
+  input.addEventListener("input", function () {
+    var terms = input.value.toLowerCase().split(/\s+/).filter(Boolean);
+    results.textContent = "";
+    results.hidden = terms.length === 0;
+    listing.hidden = terms.length > 0;
+    notes.forEach(function (entry) {
+      var hit = terms.every(function (term) { return entry.text.indexOf(term) !== -1; });
+      if (!hit) return;
+      var item = document.createElement("li");
+      var link = document.createElement("a");
+      link.href = entry.note.path;
+      link.textContent = entry.note.title;
+      item.appendChild(link);
+      if (entry.note.folder) {
+        var folder = document.createElement("span");
+        folder.className = "folder";
+        folder.textContent = " — " + entry.note.folder;
+        item.appendChild(folder);
+      }
+      results.appendChild(item);
+    });
+  });
+})();
+"#;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SiteOptions {
+    /// Heading of the index page.
+    pub title: Option<String>,
+    /// Only export this folder and its subfolders.
+    pub folder: Option<String>,
+    /// Add a search box backed by an index bundled with the site.
+    pub search: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SiteReport {
+    pub pages: usize,
+    pub attachments: usize,
+    /// Path of the generated `index.html`.
+    pub index: String,
+}
+
+#[derive(Serialize)]
+struct SearchEntry<'a> {
+    title: &'a str,
+    folder: &'a str,
+    path: String,
+    text: String,
+}
+
+/// Writes the notes to `dest` as static HTML that works straight from disk:
+/// an index, a page per note at its mirror path under `notes/`, links
+/// between notes made relative, and referenced attachments copied along.
+/// An error from `progress` stops it after the page just written.
+pub fn export(
+    vault: &Vault,
+    dest: &Path,
+    options: &SiteOptions,
+    progress: &mut dyn FnMut(ExportProgress) -> Result<()>,
+) -> Result<SiteReport> {
+    if vault.storage.is_locked() {
+        return Err(Error::VaultLocked);
+    }
+    let mut notes = vault.storage.all_notes()?;
+    if let Some(folder) = options
+        .folder
+        .as_deref()
+        .map(|folder| folder.trim_matches('/'))
+        .filter(|folder| !folder.is_empty())
+    {
+        let prefix = format!("{folder}/");
+        notes.retain(|note| note.folder == folder || note.folder.starts_with(&prefix));
+    }
+    let mut notes = notes
+        .into_iter()
+        .map(|note| match protected::is_protected(&note.body) {
+            true => protected::readable(vault, &note.id),
+            false => Ok(note),
+        })
+        .collect::<Result<Vec<_>>>()?;
+    notes.sort_by_cached_key(|note| (note.folder.clone(), note.title.to_lowercase()));
+
+    let mut pages = HashMap::new();
+    for note in &notes {
+        let rel = vault.files.path_of(&vault.storage, &note.id)?;
+        let stem = rel
+            .as_deref()
+            .and_then(|rel| rel.strip_suffix(".md"))
+            .unwrap_or(&note.id);
+        pages.insert(note.id.as_str(), format!("{NOTES_DIR}/{stem}.html"));
+    }
+
+    let site_title = options.title.as_deref().unwrap_or(DEFAULT_TITLE);
+    let total = notes.len() as u64;
+    let mut attachments = Vec::new();
+    let mut search = Vec::new();
+    for (n, note) in notes.iter().enumerate() {
+        let page = &pages[note.id.as_str()];
+        let from = page.rsplit_once('/').map_or("", |(dir, _)| dir);
+        let body = render_markdown(&note.body, |link| {
+            if let Some((target, heading)) = links::parse_note_url(link) {
+                let id = vault.links.resolve(target).unwrap_or_default();
+                return match pages.get(id.as_str()) {
+                    Some(target) => Href::Replace(page_href(from, target, heading.as_deref())),
+                    None => Href::Drop,
+                };
+            }
+            let Some(rel) = NoteFiles::resolve_from(&note.folder, link) else {
+                return Href::Keep;
+            };
+            if NoteFiles::is_note_path(&rel) {
+                let target = vault.files.note_at(&vault.storage, &rel).ok().flatten();
+                return match target.as_deref().and_then(|id| pages.get(id)) {
+                    Some(target) => Href::Replace(page_href(from, target, None)),
+                    None => Href::Drop,
+                };
+            }
+            if !vault.files.absolute(&rel).is_file() {
+                return Href::Keep;
+            }
+            let href = href(&NoteFiles::link_from(from, &rel));
+            attachments.push(rel);
+            Href::Replace(href)
+        });
+        write(dest, page, &note_page(site_title, from, note, &body))?;
+        if options.search {
+            search.push(SearchEntry {
+                title: &note.title,
+                folder: &note.folder,
+                path: href(page),
+                text: plain_text(&note.body),
+            });
+        }
+        progress(ExportProgress {
+            current: n as u64 + 1,
+            total,
+            title: Some(note.title.clone()),
+        })?;
+    }
+
+    attachments.sort();
+    attachments.dedup();
+    for rel in &attachments {
+        let target = dest.join(rel);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::copy(vault.files.absolute(rel), target)?;
+    }
+
+    write(dest, "style.css", &format!("{STYLE}{SITE_STYLE}"))?;
+    if options.search {
+        let index = serde_json::to_string(&search)?;
+        write(
+            dest,
+            "search-index.js",
+            &format!("window.SEARCH_INDEX = {index};\n"),
+        )?;
+        write(dest, "search.js", SEARCH_SCRIPT)?;
+    }
+    write(
+        dest,
+        "index.html",
+        &index_page(site_title, &notes, &pages, options.search),
+    )?;
+
+    Ok(SiteReport {
+        pages: notes.len(),
+        attachments: attachments.len(),
+        index: dest.join("index.html").to_string_lossy().into_owned(),
+    })
+}
+
+fn note_page(site_title: &str, from: &str, note: &Note, body: &str) -> String {
+    let root = "../".repeat(from.split('/').count());
+    let mut nav = format!("<a href=\"{root}index.html\">{}</a>", escape(site_title));
+    if !note.folder.is_empty() {
+        nav.push_str(&format!(
+            " / <span class=\"folder\">{}</span>",
+            escape(&note.folder)
+        ));
+    }
+    document(
+        &format!("{} - {}", note.title, site_title),
+        &root,
+        &format!(
+            "<nav>{nav}</nav>\n<main class=\"note\">\n<h1>{}</h1>\n{body}</main>\n",
+            escape(&note.title)
+        ),
+    )
+}
+
+fn index_page(
+    site_title: &str,
+    notes: &[Note],
+    pages: &HashMap<&str, String>,
+    search: bool,
+) -> String {
+    let mut folders: BTreeMap<&str, Vec<&Note>> = BTreeMap::new();
+    for note in notes {
+        folders.entry(&note.folder).or_default().push(note);
+    }
+    let mut listing = String::new();
+    for (folder, notes) in folders {
+        if !folder.is_empty() {
+            listing.push_str(&format!("<h2>{}</h2>\n", escape(folder)));
+        }
+        listing.push_str("<ul>\n");
+        for note in notes {
+            listing.push_str(&format!(
+                "<li><a href=\"{}\">{}</a></li>\n",
+                href(&pages[note.id.as_str()]),
+                escape(&note.title)
+            ));
+        }
+        listing.push_str("</ul>\n");
+    }
+
+    let mut main = format!("<h1>{}</h1>\n", escape(site_title));
+    if search {
+        main.push_str(concat!(
+            "<input id=\"search\" type=\"search\" placeholder=\"Search\" autocomplete=\"off\">\n",
+            "<ul id=\"results\" hidden></ul>\n",
+        ));
+    }
+    main.push_str(&format!("<div id=\"listing\">\n{listing}</div>\n"));
+    if search {
+        main.push_str(concat!(
+            "<script src=\"search-index.js\"></script>\n",
+            "<script src=\"search.js\"></script>\n",
+        ));
+    }
+    document(site_title, "", &format!("<main>\n{main}</main>\n"))
+}
+
+fn document(title: &str, root: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n<title>{}</title>\n<link rel=\"stylesheet\" href=\"{root}style.css\">\n</head>\n<body>\n{body}</body>\n</html>\n",
+        escape(title),
+    )
+}
+
+/// Link from a page in the `from` folder of the site to the page `target`.
+fn page_href(from: &str, target: &str, heading: Option<&str>) -> String {
+    let href = href(&NoteFiles::link_from(from, target));
+    match heading {
+        Some(heading) => format!("{href}#{}", slug(heading)),
+        None => href,
+    }
+}
+
+fn href(path: &str) -> String {
+    path.split('/')
+        .map(|segment| utf8_percent_encode(segment, PATH_SEGMENT).to_string())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// The text of a note without markup, for the search index.
+fn plain_text(body: &str) -> String {
+    let (_, markdown) = FrontMatter::split(body);
+    let mut text = String::new();
+    for event in Parser::new(markdown) {
+        match event {
+            Event::Text(t) | Event::Code(t) => text.push_str(&t),
+            Event::SoftBreak | Event::HardBreak | Event::End(_) => text.push(' '),
+            _ => {}
+        }
+    }
+    let words: Vec<&str> = text.split_whitespace().collect();
+    words.join(" ").chars().take(SEARCH_TEXT_CHARS).collect()
+}
+
+fn write(dest: &Path, rel: &str, content: &str) -> Result<()> {
+    let path = dest.join(rel);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, content)?;
+    Ok(())
+}
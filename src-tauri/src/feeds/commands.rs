@@ -0,0 +1,50 @@
+use super::{Feed, FeedsConfig, RefreshReport};
+use crate::error::{Error, Result};
+use crate::vaults::Current;
+
+/// Subscribes to an RSS or Atom feed. Its items go to `folder`, or to the
+/// folder set with `configure_feeds`.
+#[tauri::command]
+pub async fn add_feed(vault: Current, url: String, folder: Option<String>) -> Result<Feed> {
+    super::add(&vault, &url, folder.as_deref()).await
+}
+
+#[tauri::command]
+pub async fn list_feeds(vault: Current) -> Result<Vec<Feed>> {
+    super::list(&vault.storage)
+}
+
+/// Fetches every feed now, or only feed `id`.
+#[tauri::command]
+pub async fn refresh_feeds(vault: Current, id: Option<String>) -> Result<RefreshReport> {
+    super::refresh(&vault, id.as_deref()).await
+}
+
+#[tauri::command]
+pub async fn remove_feed(vault: Current, id: String) -> Result<()> {
+    super::remove(&vault.storage, &id)
+}
+
+/// Changes where feed items go and how often feeds are checked. Without
+/// arguments, returns the current settings.
+#[tauri::command]
+pub async fn configure_feeds(
+    vault: Current,
+    folder: Option<String>,
+    interval_minutes: Option<u64>,
+) -> Result<FeedsConfig> {
+    let mut config = super::config_of(&vault.storage)?;
+    if let Some(folder) = folder {
+        config.folder = folder.trim_matches('/').to_owned();
+    }
+    if let Some(minutes) = interval_minutes {
+        if minutes > 0 && minutes < 5 {
+            return Err(Error::InvalidInput(
+                "feeds are checked at most every 5 minutes".into(),
+            ));
+        }
+        config.interval_minutes = minutes;
+    }
+    super::set_config(&vault.storage, &config)?;
+    Ok(config)
+}
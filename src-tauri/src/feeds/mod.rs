@@ -0,0 +1,459 @@
+pub mod commands;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Local, Utc};
+use feed_rs::model::{Entry, Text};
+use htmd::HtmlToMarkdown;
+use reqwest::header::{ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use reqwest::{Client, StatusCode, Url};
+use rusqlite::{params, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use serde_yaml_ng::Value;
+use tauri::{AppHandle, Emitter};
+
+use crate::error::{Error, Result};
+use crate::metadata::FrontMatter;
+use crate::storage::{now_millis, Storage};
+use crate::vault::Vault;
+
+/// Event carrying the message of a background refresh that failed.
+pub const ERROR_EVENT: &str = "feed-error";
+
+const CONFIG_KEY: &str = "feeds.config";
+const TIMEOUT: Duration = Duration::from_secs(30);
+const IDLE_POLL: Duration = Duration::from_secs(60);
+const MAX_FEED_BYTES: usize = 10 * 1024 * 1024;
+/// Items turned into notes per feed and refresh. Older ones past this are
+/// marked as seen, so subscribing to a long feed does not flood the vault.
+const MAX_NEW_ITEMS: usize = 20;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct FeedsConfig {
+    /// Where items go unless a feed has a folder of its own.
+    pub folder: String,
+    /// 0 turns off refreshing in the background.
+    pub interval_minutes: u64,
+}
+
+impl Default for FeedsConfig {
+    fn default() -> Self {
+        Self {
+            folder: "Feeds".into(),
+            interval_minutes: 60,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Feed {
+    pub id: String,
+    pub url: String,
+    pub title: String,
+    /// Overrides [`FeedsConfig::folder`].
+    pub folder: Option<String>,
+    pub added_at: i64,
+    pub fetched_at: Option<i64>,
+    /// Why the last refresh failed, if it did.
+    pub error: Option<String>,
+    /// Notes made from the feed so far.
+    pub notes: usize,
+    #[serde(skip)]
+    etag: Option<String>,
+    #[serde(skip)]
+    last_modified: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RefreshReport {
+    pub feeds: usize,
+    pub new_notes: usize,
+    /// Feeds that could not be fetched; their `error` says why.
+    pub failed: usize,
+}
+
+pub fn config_of(storage: &Storage) -> Result<FeedsConfig> {
+    match storage.meta(CONFIG_KEY)? {
+        Some(json) => Ok(serde_json::from_str(&json)?),
+        None => Ok(FeedsConfig::default()),
+    }
+}
+
+pub fn set_config(storage: &Storage, config: &FeedsConfig) -> Result<()> {
+    storage.set_meta(CONFIG_KEY, &serde_json::to_string(config)?)
+}
+
+const COLUMNS: &str = "id, url, title, folder, added_at, fetched_at, error, etag, last_modified,
+    (SELECT COUNT(note_id) FROM feed_items WHERE feed_id = feeds.id)";
+
+fn feed_from_row(row: &Row) -> rusqlite::Result<Feed> {
+    Ok(Feed {
+        id: row.get(0)?,
+        url: row.get(1)?,
+        title: row.get(2)?,
+        folder: row.get(3)?,
+        added_at: row.get(4)?,
+        fetched_at: row.get(5)?,
+        error: row.get(6)?,
+        etag: row.get(7)?,
+        last_modified: row.get(8)?,
+        notes: row.get::<_, i64>(9)? as usize,
+    })
+}
+
+/// Every subscription by title.
+pub fn list(storage: &Storage) -> Result<Vec<Feed>> {
+    let conn = storage.conn();
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {COLUMNS} FROM feeds ORDER BY title COLLATE NOCASE, id"
+    ))?;
+    let feeds = stmt
+        .query_map([], feed_from_row)?
+        .collect::<rusqlite::Result<_>>()?;
+    Ok(feeds)
+}
+
+pub fn get(storage: &Storage, id: &str) -> Result<Feed> {
+    storage
+        .conn()
+        .query_row(
+            &format!("SELECT {COLUMNS} FROM feeds WHERE id = ?1"),
+            [id],
+            feed_from_row,
+        )
+        .optional()?
+        .ok_or_else(|| Error::InvalidInput(format!("no feed {id}")))
+}
+
+/// Unsubscribes. Notes made from the feed stay.
+pub fn remove(storage: &Storage, id: &str) -> Result<()> {
+    storage
+        .conn()
+        .execute("DELETE FROM feeds WHERE id = ?1", [id])?;
+    Ok(())
+}
+
+/// Subscribes to the RSS or Atom feed at `url` and brings in its newest
+/// items.
+pub async fn add(vault: &Vault, url: &str, folder: Option<&str>) -> Result<Feed> {
+    let url = Url::parse(url.trim())
+        .ok()
+        .filter(|url| matches!(url.scheme(), "http" | "https"))
+        .ok_or_else(|| Error::InvalidInput(format!("not a web address: {url}")))?;
+    let taken: bool = vault.storage.conn().query_row(
+        "SELECT EXISTS(SELECT 1 FROM feeds WHERE url = ?1)",
+        [url.as_str()],
+        |row| row.get(0),
+    )?;
+    if taken {
+        return Err(Error::InvalidInput(
+            "already subscribed to this feed".into(),
+        ));
+    }
+    let client = client()?;
+    let Fetched::Changed {
+        feed,
+        etag,
+        last_modified,
+    } = fetch(&client, url.as_str(), None, None).await?
+    else {
+        return Err(Error::InvalidInput("the feed sent nothing".into()));
+    };
+    let title = feed
+        .title
+        .as_ref()
+        .map(plain)
+        .filter(|title| !title.is_empty())
+        .or_else(|| url.host_str().map(str::to_owned))
+        .unwrap_or_else(|| url.to_string());
+    let id = uuid::Uuid::new_v4().to_string();
+    let folder = folder
+        .map(|folder| folder.trim_matches('/'))
+        .filter(|folder| !folder.is_empty());
+    vault.storage.conn().execute(
+        "INSERT INTO feeds (id, url, title, folder, added_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![id, url.as_str(), title, folder, now_millis()],
+    )?;
+    let subscribed = get(&vault.storage, &id)?;
+    store(vault, &subscribed, feed.entries, etag, last_modified)?;
+    get(&vault.storage, &id)
+}
+
+/// Fetches every feed, or only feed `only`, and makes notes of items not
+/// seen before. A feed that fails is recorded as such and does not stop
+/// the others.
+pub async fn refresh(vault: &Vault, only: Option<&str>) -> Result<RefreshReport> {
+    if vault.storage.is_locked() {
+        return Err(Error::VaultLocked);
+    }
+    let feeds = match only {
+        Some(id) => vec![get(&vault.storage, id)?],
+        None => list(&vault.storage)?,
+    };
+    let client = client()?;
+    let mut report = RefreshReport::default();
+    for feed in feeds {
+        report.feeds += 1;
+        let fetched = fetch(
+            &client,
+            &feed.url,
+            feed.etag.as_deref(),
+            feed.last_modified.as_deref(),
+        )
+        .await;
+        let result = match fetched {
+            Ok(Fetched::Unchanged) => mark_fetched(&vault.storage, &feed.id, None).map(|()| 0),
+            Ok(Fetched::Changed {
+                feed: parsed,
+                etag,
+                last_modified,
+            }) => store(vault, &feed, parsed.entries, etag, last_modified),
+            Err(err) => Err(err),
+        };
+        match result {
+            Ok(notes) => report.new_notes += notes,
+            Err(err) => {
+                report.failed += 1;
+                mark_fetched(&vault.storage, &feed.id, Some(&err.to_string()))?;
+            }
+        }
+    }
+    Ok(report)
+}
+
+/// Refreshes every configured interval while the vault is open. Failures
+/// are reported through [`ERROR_EVENT`]; a locked vault is skipped.
+pub fn spawn_periodic(app: AppHandle, vault: Arc<Vault>) {
+    tauri::async_runtime::spawn(async move {
+        while !vault.is_closed() {
+            let interval = config_of(&vault.storage)
+                .ok()
+                .map(|config| config.interval_minutes)
+                .filter(|minutes| *minutes > 0);
+            let Some(minutes) = interval else {
+                tokio::time::sleep(IDLE_POLL).await;
+                continue;
+            };
+            tokio::time::sleep(Duration::from_secs(minutes * 60)).await;
+
+            if vault.is_closed() || vault.storage.is_locked() {
+                continue;
+            }
+            match refresh(&vault, None).await {
+                Ok(report) if report.failed > 0 => {
+                    let message = format!(
+                        "{} of {} feeds could not be fetched",
+                        report.failed, report.feeds
+                    );
+                    let _ = app.emit(ERROR_EVENT, message);
+                }
+                Ok(_) => {}
+                Err(err) => {
+                    let _ = app.emit(ERROR_EVENT, err.to_string());
+                }
+            }
+        }
+    });
+}
+
+enum Fetched {
+    /// The server said nothing changed since the last fetch.
+    Unchanged,
+    Changed {
+        feed: Box<feed_rs::model::Feed>,
+        etag: Option<String>,
+        last_modified: Option<String>,
+    },
+}
+
+fn client() -> Result<Client> {
+    Ok(Client::builder().timeout(TIMEOUT).build()?)
+}
+
+async fn fetch(
+    client: &Client,
+    url: &str,
+    etag: Option<&str>,
+    last_modified: Option<&str>,
+) -> Result<Fetched> {
+    let mut request = client.get(url);
+    if let Some(etag) = etag {
+        request = request.header(IF_NONE_MATCH, etag);
+    }
+    if let Some(last_modified) = last_modified {
+        request = request.header(IF_MODIFIED_SINCE, last_modified);
+    }
+    let mut response = request.send().await?.error_for_status()?;
+    if response.status() == StatusCode::NOT_MODIFIED {
+        return Ok(Fetched::Unchanged);
+    }
+    let header = |name| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_owned)
+    };
+    let (etag, last_modified) = (header(ETAG), header(LAST_MODIFIED));
+    let mut content = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        if content.len() + chunk.len() > MAX_FEED_BYTES {
+            return Err(Error::InvalidInput(format!(
+                "the feed at {url} is too large"
+            )));
+        }
+        content.extend_from_slice(&chunk);
+    }
+    let feed = feed_rs::parser::parse(content.as_slice())
+        .map_err(|err| Error::InvalidInput(format!("not an RSS or Atom feed: {err}")))?;
+    Ok(Fetched::Changed {
+        feed: Box::new(feed),
+        etag,
+        last_modified,
+    })
+}
+
+/// Makes notes of the entries not seen before, newest [`MAX_NEW_ITEMS`]
+/// only, and returns how many.
+fn store(
+    vault: &Vault,
+    feed: &Feed,
+    mut entries: Vec<Entry>,
+    etag: Option<String>,
+    last_modified: Option<String>,
+) -> Result<usize> {
+    let config = config_of(&vault.storage)?;
+    let folder = feed.folder.as_deref().unwrap_or(&config.folder);
+    entries.sort_by_key(|entry| std::cmp::Reverse(entry.published.or(entry.updated)));
+    let mut made = Vec::new();
+    for entry in entries {
+        let seen: bool = vault.storage.conn().query_row(
+            "SELECT EXISTS(SELECT 1 FROM feed_items WHERE feed_id = ?1 AND guid = ?2)",
+            params![feed.id, entry.id],
+            |row| row.get(0),
+        )?;
+        if !seen {
+            made.push(entry);
+        }
+    }
+    let now = now_millis();
+    let mut count = 0;
+    // Oldest first, so the newest item is also the newest note.
+    for (n, entry) in made.into_iter().enumerate().rev() {
+        let note_id = match n < MAX_NEW_ITEMS {
+            true => {
+                let (title, body) = note_of(feed, &entry)?;
+                count += 1;
+                Some(vault.create_note(&title, &body, folder)?.id)
+            }
+            false => None,
+        };
+        vault.storage.conn().execute(
+            "INSERT INTO feed_items (feed_id, guid, note_id, fetched_at) VALUES (?1, ?2, ?3, ?4)",
+            params![feed.id, entry.id, note_id, now],
+        )?;
+    }
+    vault.storage.conn().execute(
+        "UPDATE feeds SET fetched_at = ?2, error = NULL, etag = ?3, last_modified = ?4
+         WHERE id = ?1",
+        params![feed.id, now, etag, last_modified],
+    )?;
+    Ok(count)
+}
+
+fn mark_fetched(storage: &Storage, id: &str, error: Option<&str>) -> Result<()> {
+    storage.conn().execute(
+        "UPDATE feeds SET fetched_at = ?2, error = ?3 WHERE id = ?1",
+        params![id, now_millis(), error],
+    )?;
+    Ok(())
+}
+
+/// The title and body of the note an entry becomes, with where it came
+/// from in the front matter.
+fn note_of(feed: &Feed, entry: &Entry) -> Result<(String, String)> {
+    let link = entry
+        .links
+        .iter()
+        .find(|link| matches!(link.rel.as_deref(), None | Some("alternate")))
+        .or(entry.links.first())
+        .map(|link| link.href.clone());
+    let title = entry
+        .title
+        .as_ref()
+        .map(plain)
+        .filter(|title| !title.is_empty())
+        .or_else(|| link.clone())
+        .unwrap_or_else(|| "Untitled item".into());
+
+    let content = match (&entry.content, &entry.summary) {
+        (Some(content), _) if content.body.is_some() => {
+            let body = content.body.as_deref().unwrap_or_default();
+            match content.content_type.to_string().contains("html") {
+                true => to_markdown(body)?,
+                false => body.trim().to_owned(),
+            }
+        }
+        (_, Some(summary)) => match summary.content_type.to_string().contains("html") {
+            true => to_markdown(&summary.content)?,
+            false => summary.content.trim().to_owned(),
+        },
+        _ => String::new(),
+    };
+
+    let mut front_matter = FrontMatter::default();
+    let mut set = |key: &str, value: Option<String>| {
+        if let Some(value) = value.filter(|value| !value.trim().is_empty()) {
+            front_matter
+                .fields
+                .insert(Value::String(key.into()), Value::String(value));
+        }
+    };
+    set("source", link);
+    set("feed", Some(feed.title.clone()));
+    set(
+        "author",
+        Some(
+            entry
+                .authors
+                .iter()
+                .map(|author| author.name.as_str())
+                .collect::<Vec<_>>()
+                .join(", "),
+        ),
+    );
+    set(
+        "published",
+        entry.published.or(entry.updated).map(local_time),
+    );
+    Ok((
+        title,
+        format!("{}{}\n", front_matter.render(), content.trim()),
+    ))
+}
+
+fn to_markdown(html: &str) -> Result<String> {
+    Ok(HtmlToMarkdown::builder()
+        .skip_tags(vec!["head", "style", "script", "noscript"])
+        .build()
+        .convert(html)?)
+}
+
+/// Titles may come as HTML; notes want them as text.
+fn plain(text: &Text) -> String {
+    let title = match text.content_type.to_string().contains("html") {
+        true => to_markdown(&text.content).unwrap_or_else(|_| text.content.clone()),
+        false => text.content.clone(),
+    };
+    title.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn local_time(time: DateTime<Utc>) -> String {
+    time.with_timezone(&Local)
+        .format("%Y-%m-%d %H:%M")
+        .to_string()
+}
@@ -0,0 +1,10 @@
+use tauri::{AppHandle, Manager, Window};
+
+use super::DropTargets;
+
+/// Tells the backend which note the calling window shows, so files dropped
+/// on it are attached there. `null` when it shows none.
+#[tauri::command]
+pub async fn set_drop_target(app: AppHandle, window: Window, note_id: Option<String>) {
+    app.state::<DropTargets>().set(window.label(), note_id);
+}
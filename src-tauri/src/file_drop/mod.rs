@@ -0,0 +1,199 @@
+//! Files dropped onto a window. Markdown, text and Word or OpenDocument
+//! files become notes of their own; anything else is attached to the note
+//! the window shows. The window then gets a [`FilesDropped`] with links to
+//! all of it, for the editor to insert.
+
+pub mod commands;
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use serde::Serialize;
+use tauri::{AppHandle, DragDropEvent, Emitter, Manager, Window, WindowEvent};
+
+use crate::attachments::{self, Attachment};
+use crate::error::{Error, Result};
+use crate::files::Staging;
+use crate::import::{markdown, pandoc};
+use crate::links;
+use crate::storage::Note;
+use crate::vault::Vault;
+use crate::vaults::Vaults;
+
+/// Event with the [`FilesDropped`] of a drop, sent to the window it was on.
+pub const DROPPED_EVENT: &str = "files-dropped";
+
+const TEXT_EXTENSIONS: &[&str] = &["md", "markdown", "txt"];
+const DOCUMENT_EXTENSIONS: &[&str] = &["docx", "odt"];
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FilesDropped {
+    /// The note attachments went to, if the window showed one.
+    pub note_id: Option<String>,
+    /// Links to the new notes and attachments, one paragraph each, in the
+    /// order the files were dropped.
+    pub markdown: String,
+    pub notes: Vec<Note>,
+    pub attachments: Vec<Attachment>,
+    /// Files that were skipped, each with the reason.
+    pub failed: Vec<String>,
+}
+
+/// The note each window shows, as its editor reports it, managed as app
+/// state.
+#[derive(Default)]
+pub struct DropTargets(Mutex<HashMap<String, String>>);
+
+impl DropTargets {
+    pub fn set(&self, window: &str, note_id: Option<String>) {
+        let mut targets = self.lock();
+        match note_id {
+            Some(id) => targets.insert(window.to_owned(), id),
+            None => targets.remove(window),
+        };
+    }
+
+    fn get(&self, window: &str) -> Option<String> {
+        self.lock().get(window).cloned()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<String, String>> {
+        self.0.lock().expect("drop targets poisoned")
+    }
+}
+
+/// What became of one dropped file.
+enum Ingested {
+    Note(Note),
+    Attachment(Attachment),
+}
+
+/// Registered for every window: takes in drops and forgets the target of
+/// a window once it is gone.
+pub fn window_event(window: &Window, event: &WindowEvent) {
+    let app = window.app_handle();
+    match event {
+        WindowEvent::DragDrop(DragDropEvent::Drop { paths, .. }) => {
+            let app = app.clone();
+            let label = window.label().to_owned();
+            let paths = paths.clone();
+            tauri::async_runtime::spawn(async move {
+                let dropped = ingest(&app, &label, paths).await;
+                if let Some(window) = app.get_webview_window(&label) {
+                    let _ = window.emit(DROPPED_EVENT, dropped);
+                }
+            });
+        }
+        WindowEvent::Destroyed => app.state::<DropTargets>().set(window.label(), None),
+        _ => {}
+    }
+}
+
+async fn ingest(app: &AppHandle, label: &str, paths: Vec<PathBuf>) -> FilesDropped {
+    let mut dropped = FilesDropped::default();
+    let vault = match app.state::<Vaults>().of_window(app, label) {
+        Ok(vault) => vault,
+        Err(err) => {
+            dropped.failed.push(err.to_string());
+            return dropped;
+        }
+    };
+    // A target deleted since it was set is as good as none.
+    let target = app
+        .state::<DropTargets>()
+        .get(label)
+        .and_then(|id| vault.storage.get_note(&id).ok());
+    let folder = target
+        .as_ref()
+        .map(|note| note.folder.clone())
+        .unwrap_or_default();
+    dropped.note_id = target.map(|note| note.id);
+
+    let mut snippets = Vec::new();
+    for path in paths {
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| path.display().to_string());
+        match ingest_one(app, &vault, dropped.note_id.as_deref(), &folder, &path).await {
+            Ok(Ingested::Note(note)) => {
+                snippets.push(format!(
+                    "[{}]({})",
+                    link_text(&note.title),
+                    links::note_url(&note.id, None)
+                ));
+                dropped.notes.push(note);
+            }
+            Ok(Ingested::Attachment(attachment)) => {
+                let embed = if attachment.thumbnail.is_some() {
+                    "!"
+                } else {
+                    ""
+                };
+                snippets.push(format!(
+                    "{embed}[{}]({})",
+                    link_text(&name),
+                    attachment.link
+                ));
+                dropped.attachments.push(attachment);
+            }
+            Err(err) => dropped.failed.push(format!("{name}: {err}")),
+        }
+    }
+    dropped.markdown = snippets.join("\n\n");
+    dropped
+}
+
+async fn ingest_one(
+    app: &AppHandle,
+    vault: &Arc<Vault>,
+    note_id: Option<&str>,
+    folder: &str,
+    path: &Path,
+) -> Result<Ingested> {
+    if path.is_dir() {
+        return Err(Error::InvalidInput("folders cannot be dropped".into()));
+    }
+    let extension = path
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let (vault, path, folder) = (Arc::clone(vault), path.to_owned(), folder.to_owned());
+    if TEXT_EXTENSIONS.contains(&extension.as_str()) {
+        let note =
+            tauri::async_runtime::spawn_blocking(move || markdown::import(&vault, &path, &folder))
+                .await??;
+        return Ok(Ingested::Note(note));
+    }
+    if DOCUMENT_EXTENSIONS.contains(&extension.as_str()) {
+        let staging = Staging::new("drop")?;
+        let converted = pandoc::convert(app, &path, &staging.0).await?;
+        let note = tauri::async_runtime::spawn_blocking(move || {
+            pandoc::import(&vault, &path, &converted, &staging.0, &folder)
+        })
+        .await??;
+        return Ok(Ingested::Note(note));
+    }
+    let Some(note_id) = note_id.map(str::to_owned) else {
+        return Err(Error::InvalidInput(
+            "open a note to attach files to it".into(),
+        ));
+    };
+    let attachment = tauri::async_runtime::spawn_blocking(move || {
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let content = std::fs::read(&path)?;
+        attachments::import(&vault, &note_id, &name, &content)
+    })
+    .await??;
+    Ok(Ingested::Attachment(attachment))
+}
+
+/// `text` with the brackets that would end a link's text taken out.
+fn link_text(text: &str) -> String {
+    text.chars().filter(|c| !matches!(c, '[' | ']')).collect()
+}
@@ -0,0 +1,68 @@
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use tauri::webview::{PageLoadEvent, PageLoadPayload};
+use tauri::{AppHandle, Emitter, Manager, Webview};
+
+use crate::error::Result;
+use crate::navigation;
+use crate::vaults;
+
+/// Event carrying the message of a file that could not be opened.
+pub const ERROR_EVENT: &str = "file-open-error";
+
+/// Files the OS asked the app to open before the main window could show
+/// them. `None` once the window has loaded.
+struct Pending(Mutex<Option<Vec<PathBuf>>>);
+
+/// Keeps `files`, from the launch arguments, for when the main window is up.
+pub fn start(app: &AppHandle, files: Vec<PathBuf>) {
+    app.manage(Pending(Mutex::new(Some(files))));
+}
+
+/// Opens `path` now, or once the main window has loaded.
+pub fn open_when_ready(app: &AppHandle, path: PathBuf) {
+    if let Some(pending) = app.try_state::<Pending>() {
+        if let Some(files) = pending.0.lock().expect("pending files poisoned").as_mut() {
+            files.push(path);
+            return;
+        }
+    }
+    open_reporting(app, &path);
+}
+
+pub fn page_loaded(webview: &Webview, payload: &PageLoadPayload<'_>) {
+    if payload.event() != PageLoadEvent::Finished || webview.label() != navigation::MAIN_WINDOW {
+        return;
+    }
+    let app = webview.app_handle();
+    let Some(pending) = app.try_state::<Pending>() else {
+        return;
+    };
+    let files = pending.0.lock().expect("pending files poisoned").take();
+    for path in files.into_iter().flatten() {
+        open_reporting(app, &path);
+    }
+}
+
+/// Shows the note a Markdown file belongs to. A file in the vault's mirror
+/// opens its note, picking up any outside edits first; anything else goes to
+/// the main window as an [`navigation::OPEN_FILE_EVENT`] so it can offer
+/// [`crate::import::markdown::import`].
+pub fn open(app: &AppHandle, path: &Path) -> Result<()> {
+    let vault = vaults::primary(app)?;
+    let Some(rel) = vault.files.relative(path) else {
+        return navigation::open_file(app, path);
+    };
+    vault.import_file(&rel)?;
+    match vault.files.note_at(&vault.storage, &rel)? {
+        Some(id) => navigation::open_note(app, &id),
+        None => navigation::open_file(app, path),
+    }
+}
+
+fn open_reporting(app: &AppHandle, path: &Path) {
+    if let Err(err) = open(app, path) {
+        let _ = app.emit(ERROR_EVENT, err.to_string());
+    }
+}
@@ -0,0 +1,375 @@
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use percent_encoding::percent_decode_str;
+use rusqlite::{params, OptionalExtension};
+use sha2::{Digest, Sha256};
+
+use crate::atomic;
+use crate::error::Result;
+use crate::storage::{Note, Storage};
+
+const EXTENSION: &str = "md";
+pub(crate) const ATTACHMENTS_DIR: &str = "attachments";
+const MAX_NAME_CHARS: usize = 120;
+
+/// Markdown mirror of the note store under `<vault>/notes`, one file per
+/// note at `<folder>/<title>.md`, for sync backends and external editors.
+///
+/// The database stays authoritative. Files hold the body exactly as stored,
+/// so an encrypted vault mirrors sealed text rather than plaintext. Paths are
+/// relative to the mirror root and always use `/`.
+pub struct NoteFiles {
+    dir: PathBuf,
+}
+
+/// A scratch directory under the system temp dir, removed when dropped.
+pub(crate) struct Staging(pub PathBuf);
+
+impl Staging {
+    pub fn new(purpose: &str) -> Result<Self> {
+        let dir =
+            std::env::temp_dir().join(format!("notesdesktop-{purpose}-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir)?;
+        Ok(Self(dir))
+    }
+}
+
+impl Drop for Staging {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// A mirrored file read back from disk.
+pub struct FileNote {
+    pub title: String,
+    pub folder: String,
+    pub body: String,
+}
+
+impl NoteFiles {
+    pub fn open(dir: &Path) -> Result<Self> {
+        fs::create_dir_all(dir)?;
+        Ok(Self {
+            dir: dir.to_owned(),
+        })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn absolute(&self, rel: &str) -> PathBuf {
+        self.dir.join(rel)
+    }
+
+    /// The reverse of [`Self::absolute`] for a note file somewhere on disk:
+    /// its path relative to the mirror, or `None` if it lies outside it.
+    pub fn relative(&self, path: &Path) -> Option<String> {
+        let dir = self.dir.canonicalize().ok()?;
+        let path = path.canonicalize().ok()?;
+        let rel = path
+            .strip_prefix(&dir)
+            .ok()?
+            .components()
+            .map(|part| part.as_os_str().to_str())
+            .collect::<Option<Vec<_>>>()?
+            .join("/");
+        Self::is_note_path(&rel).then_some(rel)
+    }
+
+    pub fn path_of(&self, storage: &Storage, note_id: &str) -> Result<Option<String>> {
+        let path = storage
+            .conn()
+            .query_row(
+                "SELECT path FROM note_files WHERE note_id = ?1",
+                [note_id],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(path)
+    }
+
+    pub fn note_at(&self, storage: &Storage, rel: &str) -> Result<Option<String>> {
+        let id = storage
+            .conn()
+            .query_row(
+                "SELECT note_id FROM note_files WHERE path = ?1",
+                [rel],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(id)
+    }
+
+    /// Writes `note` to its file, moving the file if the title or folder
+    /// changed, and returns its relative path. Unchanged files are not touched.
+    pub fn write(&self, storage: &Storage, note: &Note) -> Result<String> {
+        let base = base_path(note);
+        let current = self.path_of(storage, &note.id)?;
+        let rel = match current.as_deref() {
+            Some(path) if has_base(path, &base) => path.to_owned(),
+            _ => self.free_path(storage, &base, &note.id)?,
+        };
+
+        let content = storage.seal_body(&note.body)?;
+        let path = self.absolute(&rel);
+        let unchanged = fs::read_to_string(&path).is_ok_and(|existing| existing == content);
+        if !unchanged {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            atomic::write(&path, content.as_bytes())?;
+        }
+
+        if let Some(old) = current.filter(|old| *old != rel) {
+            self.remove_file(&old)?;
+        }
+        self.link(storage, &note.id, &rel)?;
+        Ok(rel)
+    }
+
+    /// Records that `note_id` already lives at `rel`, for files that were
+    /// created outside the app and imported.
+    pub fn link(&self, storage: &Storage, note_id: &str, rel: &str) -> Result<()> {
+        storage.conn().execute(
+            "INSERT OR REPLACE INTO note_files (note_id, path) VALUES (?1, ?2)",
+            params![note_id, rel],
+        )?;
+        Ok(())
+    }
+
+    /// Deletes a note's file, returning the path it had.
+    pub fn remove(&self, storage: &Storage, note_id: &str) -> Result<Option<String>> {
+        let Some(rel) = self.path_of(storage, note_id)? else {
+            return Ok(None);
+        };
+        self.remove_file(&rel)?;
+        storage
+            .conn()
+            .execute("DELETE FROM note_files WHERE note_id = ?1", [note_id])?;
+        Ok(Some(rel))
+    }
+
+    pub fn read(&self, storage: &Storage, rel: &str) -> Result<FileNote> {
+        let content = fs::read_to_string(self.absolute(rel))?;
+        let (folder, file) = rel.rsplit_once('/').unwrap_or(("", rel));
+        let title = file
+            .strip_suffix(&format!(".{EXTENSION}"))
+            .unwrap_or(file)
+            .to_owned();
+        Ok(FileNote {
+            title,
+            folder: folder.to_owned(),
+            body: storage.open_body(content)?,
+        })
+    }
+
+    /// Whether `rel` looks like a mirrored note rather than some other file
+    /// that happens to live in the directory.
+    pub fn is_note_path(rel: &str) -> bool {
+        rel.ends_with(&format!(".{EXTENSION}")) && !rel.split('/').any(|part| part.starts_with('.'))
+    }
+
+    /// Whether `rel` names a file in the attachment store.
+    pub fn is_attachment_path(rel: &str) -> bool {
+        rel.strip_prefix(ATTACHMENTS_DIR)
+            .and_then(|rest| rest.strip_prefix('/'))
+            .is_some_and(|name| !name.is_empty() && !name.contains('/'))
+    }
+
+    /// Stores `content` under `attachments/`, named by its SHA-256 so each
+    /// distinct file is kept once, and returns its relative path. `name` only
+    /// contributes the extension.
+    ///
+    /// A vault password does not seal attachments: the preview, other apps
+    /// and sync read them as they are. The vault status counts them so the
+    /// settings can say so.
+    pub fn save_attachment(&self, name: &str, content: &[u8]) -> Result<String> {
+        let hash = hex::encode(Sha256::digest(content));
+        let rel = match Path::new(name).extension().and_then(|ext| ext.to_str()) {
+            Some(ext) => format!("{ATTACHMENTS_DIR}/{hash}.{}", sanitize(ext).to_lowercase()),
+            None => format!("{ATTACHMENTS_DIR}/{hash}"),
+        };
+        let path = self.absolute(&rel);
+        if !path.exists() {
+            fs::create_dir_all(self.absolute(ATTACHMENTS_DIR))?;
+            atomic::write(&path, content)?;
+        }
+        Ok(rel)
+    }
+
+    pub fn attachments_dir(&self) -> PathBuf {
+        self.absolute(ATTACHMENTS_DIR)
+    }
+
+    /// How a note in `folder` refers to the mirror path `rel` in Markdown:
+    /// relative to the note's own file, so other editors resolve it too.
+    pub fn link_from(folder: &str, rel: &str) -> String {
+        let depth = folder.split('/').filter(|part| !part.is_empty()).count();
+        format!("{}{rel}", "../".repeat(depth))
+    }
+
+    /// The mirror path a Markdown link in a note in `folder` points to, the
+    /// reverse of [`Self::link_from`]. URLs and links escaping the mirror
+    /// give `None`.
+    pub fn resolve_from(folder: &str, link: &str) -> Option<String> {
+        let link = link.split(['#', '?']).next().unwrap_or_default();
+        if link.is_empty() || link.starts_with('/') || link.contains(':') {
+            return None;
+        }
+        let link = percent_decode_str(link).decode_utf8().ok()?;
+        let mut parts: Vec<&str> = folder.split('/').filter(|p| !p.is_empty()).collect();
+        for part in link.split('/') {
+            match part {
+                "" | "." => {}
+                ".." => {
+                    parts.pop()?;
+                }
+                part => parts.push(part),
+            }
+        }
+        Some(parts.join("/"))
+    }
+
+    /// Relative paths of every note file in the mirror, sorted.
+    pub fn list(&self) -> Result<Vec<String>> {
+        let mut paths = Vec::new();
+        let mut pending = vec![self.dir.clone()];
+        while let Some(dir) = pending.pop() {
+            for entry in fs::read_dir(&dir)? {
+                let entry = entry?;
+                if entry.file_name().to_string_lossy().starts_with('.') {
+                    continue;
+                }
+                let path = entry.path();
+                if entry.file_type()?.is_dir() {
+                    pending.push(path);
+                    continue;
+                }
+                let Ok(rel) = path.strip_prefix(&self.dir) else {
+                    continue;
+                };
+                let rel = rel.to_string_lossy().replace('\\', "/");
+                if Self::is_note_path(&rel) {
+                    paths.push(rel);
+                }
+            }
+        }
+        paths.sort();
+        Ok(paths)
+    }
+
+    /// Deletes every note file and forgets where notes were mirrored, so the
+    /// next [`Self::export_missing`] writes the mirror afresh.
+    pub fn clear(&self, storage: &Storage) -> Result<()> {
+        for rel in self.list()? {
+            self.remove_file(&rel)?;
+        }
+        storage.conn().execute("DELETE FROM note_files", [])?;
+        Ok(())
+    }
+
+    /// Writes every note that has no file yet, e.g. after upgrading an
+    /// existing vault. Returns how many were written.
+    pub fn export_missing(&self, storage: &Storage) -> Result<usize> {
+        let missing: Vec<String> = {
+            let conn = storage.conn();
+            let mut stmt = conn.prepare(
+                "SELECT id FROM notes WHERE id NOT IN (SELECT note_id FROM note_files)
+                     AND id NOT IN (SELECT note_id FROM trash)",
+            )?;
+            let ids = stmt
+                .query_map([], |row| row.get(0))?
+                .collect::<rusqlite::Result<_>>()?;
+            ids
+        };
+        for id in &missing {
+            self.write(storage, &storage.get_note(id)?)?;
+        }
+        Ok(missing.len())
+    }
+
+    /// Picks `<base>.md`, or `<base> (n).md` if that is taken by another note
+    /// or an unrelated file.
+    fn free_path(&self, storage: &Storage, base: &str, note_id: &str) -> Result<String> {
+        for n in 1.. {
+            let rel = if n == 1 {
+                format!("{base}.{EXTENSION}")
+            } else {
+                format!("{base} ({n}).{EXTENSION}")
+            };
+            let owner = self.note_at(storage, &rel)?;
+            let free = match owner {
+                Some(owner) => owner == note_id,
+                None => !self.absolute(&rel).exists(),
+            };
+            if free {
+                return Ok(rel);
+            }
+        }
+        unreachable!("unbounded range")
+    }
+
+    fn remove_file(&self, rel: &str) -> Result<()> {
+        let path = self.absolute(rel);
+        match fs::remove_file(&path) {
+            Ok(()) => {}
+            Err(err) if err.kind() == ErrorKind::NotFound => {}
+            Err(err) => return Err(err.into()),
+        }
+        // Drop folders left empty by the move, but never the mirror root.
+        let mut dir = path.parent();
+        while let Some(parent) = dir.filter(|d| *d != self.dir && d.starts_with(&self.dir)) {
+            if fs::remove_dir(parent).is_err() {
+                break;
+            }
+            dir = parent.parent();
+        }
+        Ok(())
+    }
+}
+
+/// `<folder>/<title>` with every component made safe for all platforms.
+fn base_path(note: &Note) -> String {
+    note.folder
+        .split('/')
+        .filter(|part| !part.is_empty())
+        .map(sanitize)
+        .chain(std::iter::once(sanitize(&note.title)))
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn has_base(path: &str, base: &str) -> bool {
+    let Some(stem) = path.strip_suffix(&format!(".{EXTENSION}")) else {
+        return false;
+    };
+    stem == base
+        || stem
+            .strip_prefix(base)
+            .and_then(|rest| rest.strip_prefix(" ("))
+            .and_then(|rest| rest.strip_suffix(')'))
+            .is_some_and(|n| n.parse::<u32>().is_ok())
+}
+
+/// `name` made safe to use as a file or folder name on every platform.
+pub(crate) fn sanitize(name: &str) -> String {
+    let cleaned: String = name
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '-',
+            c if c.is_control() => '-',
+            c => c,
+        })
+        .take(MAX_NAME_CHARS)
+        .collect();
+    let trimmed = cleaned.trim().trim_matches('.').trim();
+    if trimmed.is_empty() {
+        "Untitled".to_owned()
+    } else {
+        trimmed.to_owned()
+    }
+}
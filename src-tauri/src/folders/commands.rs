@@ -0,0 +1,61 @@
+use std::sync::Arc;
+
+use tauri::{AppHandle, Manager, Window};
+
+use super::Sibling;
+use crate::error::Result;
+use crate::storage::Note;
+use crate::vault::Vault;
+use crate::vaults::Vaults;
+
+/// Makes a folder, e.g. `Projects/2024`, and returns its path as stored.
+#[tauri::command]
+pub async fn create_folder(app: AppHandle, window: Window, path: String) -> Result<String> {
+    let (vault_id, vault) = vault_of(&app, &window)?;
+    let path = super::create(&vault.storage, &path)?;
+    super::changed(&app, &vault_id);
+    Ok(path)
+}
+
+#[tauri::command]
+pub async fn move_note(app: AppHandle, window: Window, id: String, folder: String) -> Result<Note> {
+    let (vault_id, vault) = vault_of(&app, &window)?;
+    let note = tauri::async_runtime::spawn_blocking(move || super::move_note(&vault, &id, &folder))
+        .await??;
+    super::changed(&app, &vault_id);
+    Ok(note)
+}
+
+/// Moves a folder and everything in it into `parent`, `""` for the top
+/// level, and returns its new path.
+#[tauri::command]
+pub async fn move_folder(
+    app: AppHandle,
+    window: Window,
+    path: String,
+    parent: String,
+) -> Result<String> {
+    let (vault_id, vault) = vault_of(&app, &window)?;
+    let path =
+        tauri::async_runtime::spawn_blocking(move || super::move_folder(&vault, &path, &parent))
+            .await??;
+    super::changed(&app, &vault_id);
+    Ok(path)
+}
+
+/// Sets the manual order of one folder's entries, given as
+/// `{ "kind": "folder", "path": ... }` or `{ "kind": "note", "id": ... }`.
+#[tauri::command]
+pub async fn reorder(app: AppHandle, window: Window, siblings: Vec<Sibling>) -> Result<()> {
+    let (vault_id, vault) = vault_of(&app, &window)?;
+    super::reorder(&vault.storage, &siblings)?;
+    super::changed(&app, &vault_id);
+    Ok(())
+}
+
+fn vault_of(app: &AppHandle, window: &Window) -> Result<(String, Arc<Vault>)> {
+    let vaults = app.state::<Vaults>();
+    let vault_id = vaults.id_of(window.label());
+    let vault = vaults.get(app, &vault_id)?;
+    Ok((vault_id, vault))
+}
@@ -0,0 +1,213 @@
+//! Folder operations for the sidebar. A folder is mostly just the path its
+//! notes carry, so moving one moves every note below it; the `folders`
+//! table only keeps empty ones and the order folders were put in by hand.
+
+pub mod commands;
+
+use rusqlite::{params, Transaction};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter};
+
+use crate::error::{Error, Result};
+use crate::storage::{Note, NotePatch, Storage};
+use crate::vault::Vault;
+use crate::{links, protected};
+
+/// Event broadcast with a [`FolderTreeChange`] when folders are made, moved
+/// or reordered, so every window showing the vault redraws its tree.
+pub const CHANGED_EVENT: &str = "folder-tree-changed";
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FolderTreeChange {
+    pub vault_id: String,
+}
+
+/// One entry of a folder, as given to [`reorder`].
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "kind")]
+pub enum Sibling {
+    Folder { path: String },
+    Note { id: String },
+}
+
+/// `path` with its slashes tidied, checked to be one a folder can have.
+pub fn normalize(path: &str) -> Result<String> {
+    let segments: Vec<&str> = path
+        .split('/')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .collect();
+    if segments.iter().any(|s| matches!(*s, "." | "..")) {
+        return Err(Error::InvalidInput(format!("not a folder path: {path:?}")));
+    }
+    Ok(segments.join("/"))
+}
+
+fn name_of(path: &str) -> &str {
+    path.rsplit('/').next().unwrap_or(path)
+}
+
+/// Whether `folder` is `path` or one of its subfolders.
+pub(crate) fn is_within(folder: &str, path: &str) -> bool {
+    folder == path
+        || folder
+            .strip_prefix(path)
+            .is_some_and(|rest| rest.starts_with('/'))
+}
+
+/// Makes a folder, which stays in the tree while it is empty. Making one
+/// that exists does nothing.
+pub fn create(storage: &Storage, path: &str) -> Result<String> {
+    let path = normalize(path)?;
+    if path.is_empty() {
+        return Err(Error::InvalidInput("a folder needs a name".into()));
+    }
+    storage
+        .conn()
+        .execute("INSERT OR IGNORE INTO folders (path) VALUES (?1)", [&path])?;
+    Ok(path)
+}
+
+/// Moves a note to `folder`, keeping its relative links working. It goes
+/// to the end of its new folder's manual order.
+pub fn move_note(vault: &Vault, id: &str, folder: &str) -> Result<Note> {
+    let folder = normalize(folder)?;
+    let note = vault.storage.get_note(id)?;
+    if note.folder == folder {
+        return Ok(note);
+    }
+    let mut moved = vault.rename_note(id, &note.title, Some(&folder), false, false)?;
+    vault
+        .storage
+        .conn()
+        .execute("DELETE FROM note_positions WHERE note_id = ?1", [id])?;
+    Ok(moved.swap_remove(0))
+}
+
+/// Moves the folder at `path`, with everything below it, into `parent`
+/// (the root when empty). Returns its new path. A folder of the same name
+/// already there is not merged into. The notes and the folder's own rows
+/// move in one transaction, and locked folders unlocked in this session
+/// stay unlocked at their new place.
+pub fn move_folder(vault: &Vault, path: &str, parent: &str) -> Result<String> {
+    if vault.storage.is_locked() {
+        return Err(Error::VaultLocked);
+    }
+    let path = normalize(path)?;
+    let parent = normalize(parent)?;
+    if path.is_empty() {
+        return Err(Error::InvalidInput(
+            "the root folder cannot be moved".into(),
+        ));
+    }
+    if is_within(&parent, &path) {
+        return Err(Error::InvalidInput(
+            "a folder cannot be moved into itself".into(),
+        ));
+    }
+    let to = match parent.is_empty() {
+        true => name_of(&path).to_owned(),
+        false => format!("{parent}/{}", name_of(&path)),
+    };
+    if to == path {
+        return Ok(to);
+    }
+    let notes = vault.storage.list_notes(None)?;
+    if notes.iter().any(|note| is_within(&note.folder, &to)) || explicit(&vault.storage, &to)? {
+        return Err(Error::InvalidInput(format!(
+            "there is a folder {to} already"
+        )));
+    }
+
+    let renamed = |folder: &str| format!("{to}{}", &folder[path.len()..]);
+    let mut patches = Vec::new();
+    for summary in notes.iter().filter(|note| is_within(&note.folder, &path)) {
+        let note = vault.storage.get_note(&summary.id)?;
+        let folder = renamed(&note.folder);
+        let patch = NotePatch {
+            body: links::rebase_links(&note.body, &note.folder, &folder),
+            folder: Some(folder),
+            ..Default::default()
+        };
+        patches.push((note.id, patch));
+    }
+    let prefix = format!("{path}/");
+    let move_rows = |tx: &Transaction<'_>| -> Result<()> {
+        // Trashed notes are moved along so they are restored to the
+        // folder's new place, but not reindexed.
+        let renames = [
+            ("notes", "folder", "id IN (SELECT note_id FROM trash) AND"),
+            ("folders", "path", ""),
+            ("folder_locks", "folder", ""),
+            ("note_locks", "folder", ""),
+        ];
+        for (table, column, only) in renames {
+            tx.execute(
+                &format!(
+                    "UPDATE {table} SET {column} = ?3 || substr({column}, length(?1) + 1)
+                     WHERE {only} ({column} = ?1 OR substr({column}, 1, length(?2)) = ?2)"
+                ),
+                params![path, prefix, to],
+            )?;
+        }
+        tx.execute("UPDATE folders SET position = NULL WHERE path = ?1", [&to])?;
+        Ok(())
+    };
+    vault.update_notes_with(patches, &format!("Move folder {path} to {to}"), move_rows)?;
+    protected::folder_moved(vault, &path, &to);
+    Ok(to)
+}
+
+/// Puts the entries of one folder in the order given. Entries left out
+/// keep their place after those given.
+pub fn reorder(storage: &Storage, siblings: &[Sibling]) -> Result<()> {
+    let mut conn = storage.conn();
+    let tx = conn.transaction()?;
+    for (position, sibling) in siblings.iter().enumerate() {
+        let position = position as i64;
+        match sibling {
+            Sibling::Folder { path } => {
+                tx.execute(
+                    "INSERT INTO folders (path, position) VALUES (?1, ?2)
+                     ON CONFLICT (path) DO UPDATE SET position = excluded.position",
+                    params![normalize(path)?, position],
+                )?;
+            }
+            Sibling::Note { id } => {
+                let exists: bool = tx.query_row(
+                    "SELECT EXISTS (SELECT 1 FROM notes WHERE id = ?1)",
+                    [id],
+                    |row| row.get(0),
+                )?;
+                if !exists {
+                    return Err(Error::NoteNotFound(id.clone()));
+                }
+                tx.execute(
+                    "INSERT INTO note_positions (note_id, position) VALUES (?1, ?2)
+                     ON CONFLICT (note_id) DO UPDATE SET position = excluded.position",
+                    params![id, position],
+                )?;
+            }
+        }
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+fn explicit(storage: &Storage, path: &str) -> Result<bool> {
+    let exists = storage.conn().query_row(
+        "SELECT EXISTS (SELECT 1 FROM folders WHERE path = ?1 OR substr(path, 1, length(?1) + 1) = ?1 || '/')",
+        [path],
+        |row| row.get(0),
+    )?;
+    Ok(exists)
+}
+
+/// Tells every window that the folder tree of vault `vault_id` changed.
+pub fn changed(app: &AppHandle, vault_id: &str) {
+    let change = FolderTreeChange {
+        vault_id: vault_id.to_owned(),
+    };
+    let _ = app.emit(CHANGED_EVENT, change);
+}
@@ -0,0 +1,42 @@
+//! The storage layer without the app: opens the same vaults the app does,
+//! for scripts and the command line. A vault can only be open in one
+//! process at a time, so these fail while the app has it.
+
+use std::path::PathBuf;
+
+use crate::error::{Error, Result};
+use crate::vault::Vault;
+use crate::vaults::{self, Vaults};
+
+/// Environment variable holding the password of an encrypted vault.
+pub const PASSWORD_ENV: &str = "NOTES_PASSWORD";
+
+/// Where the app keeps its vaults, as Tauri's `app_data_dir` resolves it.
+pub fn app_data_dir() -> Result<PathBuf> {
+    let config: serde_json::Value = serde_json::from_str(include_str!("../tauri.conf.json"))?;
+    let identifier = config["identifier"]
+        .as_str()
+        .ok_or_else(|| Error::InvalidInput("the app has no identifier".into()))?;
+    let data = dirs::data_dir()
+        .ok_or_else(|| Error::InvalidInput("no data directory for this user".into()))?;
+    Ok(data.join(identifier))
+}
+
+/// Opens vault `id`, or the default one, unlocking it from
+/// [`PASSWORD_ENV`] if it is encrypted.
+pub fn open_vault(id: Option<&str>) -> Result<Vault> {
+    let vaults = Vaults::load(&app_data_dir()?)?;
+    let root = vaults.path_of(id.unwrap_or(vaults::DEFAULT_ID))?;
+    let vault = Vault::open(&root)?;
+    if vault.storage.is_locked() {
+        match std::env::var(PASSWORD_ENV) {
+            Ok(password) => vault.unlock(&password)?,
+            Err(_) => {
+                return Err(Error::InvalidInput(format!(
+                    "the vault is encrypted; set {PASSWORD_ENV} to its password"
+                )))
+            }
+        }
+    }
+    Ok(vault)
+}
@@ -0,0 +1,46 @@
+use super::{DiffLine, Revision, RevisionInfo};
+use crate::error::Result;
+use crate::storage::{Note, NotePatch};
+use crate::vaults::Current;
+
+#[tauri::command]
+pub async fn list_revisions(vault: Current, note_id: String) -> Result<Vec<RevisionInfo>> {
+    super::list(&vault.storage, &note_id)
+}
+
+#[tauri::command]
+pub async fn get_revision(vault: Current, note_id: String, rev: i64) -> Result<Revision> {
+    super::get(&vault.storage, &note_id, rev)
+}
+
+/// Makes an old revision current again. The restore is itself saved as a
+/// new revision, so nothing is lost.
+#[tauri::command]
+pub async fn restore_revision(vault: Current, note_id: String, rev: i64) -> Result<Note> {
+    let revision = super::get(&vault.storage, &note_id, rev)?;
+    vault.update_note(
+        &note_id,
+        NotePatch {
+            title: Some(revision.title),
+            body: Some(revision.body),
+            ..Default::default()
+        },
+    )
+}
+
+/// Line diff from revision `from` to revision `to`, or to the current body
+/// when `to` is omitted.
+#[tauri::command]
+pub async fn diff_revisions(
+    vault: Current,
+    note_id: String,
+    from: i64,
+    to: Option<i64>,
+) -> Result<Vec<DiffLine>> {
+    let old = super::get(&vault.storage, &note_id, from)?.body;
+    let new = match to {
+        Some(rev) => super::get(&vault.storage, &note_id, rev)?.body,
+        None => vault.storage.get_note(&note_id)?.body,
+    };
+    Ok(super::diff_lines(&old, &new))
+}
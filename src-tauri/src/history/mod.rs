@@ -0,0 +1,287 @@
+pub mod commands;
+
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+use similar::{ChangeTag, TextDiff};
+
+use crate::blocks;
+use crate::error::{Error, Result};
+use crate::storage::{Note, Storage};
+
+/// One saved revision without its content.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RevisionInfo {
+    pub rev: i64,
+    pub title: String,
+    /// Body length in bytes.
+    pub size: i64,
+    pub created_at: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Revision {
+    pub note_id: String,
+    pub rev: i64,
+    pub title: String,
+    pub body: String,
+    pub created_at: i64,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LineChange {
+    Equal,
+    Insert,
+    Delete,
+}
+
+/// One line of a line-level diff. Line numbers are 1-based and absent on
+/// the side the line does not exist in.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffLine {
+    pub change: LineChange,
+    pub old_line: Option<usize>,
+    pub new_line: Option<usize>,
+    pub text: String,
+}
+
+/// Snapshots `note` as a new revision unless its content is unchanged since
+/// the latest one. Bodies are stored once per distinct
+/// [`Storage::content_hash`], in blocks shared with the other revisions.
+pub fn record(storage: &Storage, note: &Note) -> Result<()> {
+    let hash = storage.content_hash(note.body.as_bytes())?;
+    let mut conn = storage.conn();
+    let tx = conn.transaction()?;
+
+    let latest: Option<(i64, String, String)> = tx
+        .query_row(
+            "SELECT rev, title, hash FROM revisions WHERE note_id = ?1
+             ORDER BY rev DESC LIMIT 1",
+            [&note.id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .optional()?;
+    if let Some((_, title, latest_hash)) = &latest {
+        if *latest_hash == hash && *title == note.title {
+            return Ok(());
+        }
+    }
+
+    let known: bool = tx.query_row(
+        "SELECT EXISTS(SELECT 1 FROM blobs WHERE hash = ?1)",
+        [&hash],
+        |row| row.get(0),
+    )?;
+    if !known {
+        tx.execute(
+            "INSERT INTO blobs (hash, content, blocked) VALUES (?1, '', 1)",
+            [&hash],
+        )?;
+        add_blocks(&tx, storage, &hash, &note.body)?;
+    }
+
+    let rev = latest.map_or(1, |(rev, _, _)| rev + 1);
+    tx.execute(
+        "INSERT INTO revisions (note_id, rev, title, hash, size, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![
+            note.id,
+            rev,
+            note.title,
+            hash,
+            note.body.len() as i64,
+            note.updated_at
+        ],
+    )?;
+    tx.commit()?;
+    Ok(())
+}
+
+/// Lists a note's revisions, newest first.
+pub fn list(storage: &Storage, note_id: &str) -> Result<Vec<RevisionInfo>> {
+    let conn = storage.conn();
+    let mut stmt = conn.prepare(
+        "SELECT rev, title, size, created_at FROM revisions WHERE note_id = ?1
+         ORDER BY rev DESC",
+    )?;
+    let revisions = stmt
+        .query_map([note_id], |row| {
+            Ok(RevisionInfo {
+                rev: row.get(0)?,
+                title: row.get(1)?,
+                size: row.get(2)?,
+                created_at: row.get(3)?,
+            })
+        })?
+        .collect::<rusqlite::Result<_>>()?;
+    Ok(revisions)
+}
+
+pub fn get(storage: &Storage, note_id: &str, rev: i64) -> Result<Revision> {
+    let conn = storage.conn();
+    let (title, hash, created_at): (String, String, i64) = conn
+        .query_row(
+            "SELECT title, hash, created_at FROM revisions
+             WHERE note_id = ?1 AND rev = ?2",
+            params![note_id, rev],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .optional()?
+        .ok_or_else(|| Error::InvalidInput(format!("note {note_id} has no revision {rev}")))?;
+    Ok(Revision {
+        note_id: note_id.to_owned(),
+        rev,
+        title,
+        body: body_of(&conn, storage, &hash)?,
+        created_at,
+    })
+}
+
+/// The body note `note_id` had as of `time`: that of its latest revision
+/// saved no later. `None` if history does not reach back that far.
+pub fn body_at(storage: &Storage, note_id: &str, time: i64) -> Result<Option<String>> {
+    let conn = storage.conn();
+    let hash: Option<String> = conn
+        .query_row(
+            "SELECT hash FROM revisions WHERE note_id = ?1 AND created_at <= ?2
+             ORDER BY rev DESC LIMIT 1",
+            params![note_id, time],
+            |row| row.get(0),
+        )
+        .optional()?;
+    hash.map(|hash| body_of(&conn, storage, &hash)).transpose()
+}
+
+/// The body stored as blob `hash`, whole or in blocks.
+fn body_of(conn: &Connection, storage: &Storage, hash: &str) -> Result<String> {
+    let (content, blocked): (String, bool) = conn.query_row(
+        "SELECT content, blocked FROM blobs WHERE hash = ?1",
+        [hash],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+    if !blocked {
+        return storage.open_body(content);
+    }
+    let mut stmt =
+        conn.prepare_cached("SELECT block FROM blob_blocks WHERE blob = ?1 ORDER BY seq")?;
+    let hashes: Vec<String> = stmt
+        .query_map([hash], |row| row.get(0))?
+        .collect::<rusqlite::Result<_>>()?;
+    String::from_utf8(blocks::read(conn, storage, &hashes)?)
+        .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err).into())
+}
+
+fn add_blocks(conn: &Connection, storage: &Storage, hash: &str, body: &str) -> Result<()> {
+    let mut insert =
+        conn.prepare_cached("INSERT INTO blob_blocks (blob, seq, block) VALUES (?1, ?2, ?3)")?;
+    for (seq, block) in blocks::put(conn, storage, body.as_bytes())?
+        .iter()
+        .enumerate()
+    {
+        insert.execute(params![hash, seq as i64, block])?;
+    }
+    Ok(())
+}
+
+/// Moves bodies saved whole, before the block store, into blocks. Returns
+/// how many were moved.
+pub fn repack(storage: &Storage) -> Result<usize> {
+    if storage.is_locked() {
+        return Err(Error::VaultLocked);
+    }
+    let mut conn = storage.conn();
+    let tx = conn.transaction()?;
+    let whole: Vec<(String, String)> = {
+        let mut stmt = tx.prepare("SELECT hash, content FROM blobs WHERE NOT blocked")?;
+        let rows = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<rusqlite::Result<_>>()?;
+        rows
+    };
+    for (hash, content) in &whole {
+        add_blocks(&tx, storage, hash, &storage.open_body(content.clone())?)?;
+        tx.execute(
+            "UPDATE blobs SET content = '', blocked = 1 WHERE hash = ?1",
+            [hash],
+        )?;
+    }
+    tx.commit()?;
+    Ok(whole.len())
+}
+
+/// Files every body under the name `digest` gives it, and the blocks they
+/// are made of likewise, for a vault whose key changes. Run before the
+/// stored content is sealed with the new key, within the transaction that
+/// does.
+pub(crate) fn rehash(
+    conn: &Connection,
+    storage: &Storage,
+    digest: &dyn Fn(&[u8]) -> String,
+) -> Result<()> {
+    blocks::rehash(conn, storage, digest)?;
+    let blobs: Vec<String> = {
+        let mut stmt = conn.prepare("SELECT hash FROM blobs")?;
+        let hashes = stmt
+            .query_map([], |row| row.get(0))?
+            .collect::<rusqlite::Result<_>>()?;
+        hashes
+    };
+    for old in blobs {
+        let new = digest(body_of(conn, storage, &old)?.as_bytes());
+        if new == old {
+            continue;
+        }
+        // Revisions refer to the blob, so the new one is in place before
+        // they move over and the old one goes.
+        conn.execute(
+            "INSERT OR IGNORE INTO blobs (hash, content, blocked)
+             SELECT ?2, content, blocked FROM blobs WHERE hash = ?1",
+            params![old, new],
+        )?;
+        conn.execute(
+            "INSERT OR IGNORE INTO blob_blocks (blob, seq, block)
+             SELECT ?2, seq, block FROM blob_blocks WHERE blob = ?1",
+            params![old, new],
+        )?;
+        conn.execute(
+            "UPDATE revisions SET hash = ?2 WHERE hash = ?1",
+            params![old, new],
+        )?;
+        conn.execute("DELETE FROM blobs WHERE hash = ?1", [old])?;
+    }
+    Ok(())
+}
+
+/// Drops blobs no revision refers to any more, and the blocks only they
+/// were made of.
+pub fn prune_blobs(storage: &Storage) -> Result<usize> {
+    let conn = storage.conn();
+    let removed = conn.execute(
+        "DELETE FROM blobs WHERE hash NOT IN (SELECT hash FROM revisions)",
+        [],
+    )?;
+    blocks::prune(&conn)?;
+    Ok(removed)
+}
+
+pub fn diff_lines(old: &str, new: &str) -> Vec<DiffLine> {
+    TextDiff::from_lines(old, new)
+        .iter_all_changes()
+        .map(|change| DiffLine {
+            change: match change.tag() {
+                ChangeTag::Equal => LineChange::Equal,
+                ChangeTag::Insert => LineChange::Insert,
+                ChangeTag::Delete => LineChange::Delete,
+            },
+            old_line: change.old_index().map(|i| i + 1),
+            new_line: change.new_index().map(|i| i + 1),
+            text: change
+                .to_string_lossy()
+                .trim_end_matches(['\n', '\r'])
+                .to_owned(),
+        })
+        .collect()
+}
@@ -0,0 +1,26 @@
+//! What the servers the app runs have in common: the localhost API, the
+//! MCP server and beams all check bearer tokens and read bounded bodies.
+
+use std::io::{self, Read};
+
+use tiny_http::Request;
+
+/// Whether `a` equals `b`, taking as long whichever byte differs, so a
+/// token cannot be guessed one byte at a time.
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// The body of `request`, or `None` if it is longer than `limit` bytes,
+/// which the server answers with 413 rather than reading on.
+pub fn read_body(request: &mut Request, limit: u64) -> io::Result<Option<Vec<u8>>> {
+    if request
+        .body_length()
+        .is_some_and(|length| length as u64 > limit)
+    {
+        return Ok(None);
+    }
+    let mut body = Vec::new();
+    request.as_reader().take(limit + 1).read_to_end(&mut body)?;
+    Ok((body.len() as u64 <= limit).then_some(body))
+}
@@ -0,0 +1,249 @@
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use htmd::HtmlToMarkdown;
+use serde::Deserialize;
+
+use super::{rfc3339_millis, ImportProgress, ImportReport};
+use crate::error::{Error, Result};
+use crate::files::{NoteFiles, Staging};
+use crate::storage::{now_millis, Note};
+use crate::vault::Vault;
+
+const SOURCE: &str = "apple-notes";
+/// Folder the whole library goes into.
+const TOP_FOLDER: &str = "Apple Notes";
+/// AppleScript's "not authorized to send Apple events" error.
+const NOT_AUTHORIZED: &str = "-1743";
+
+/// Walks every account's folders and prints the notes as JSON. Attachments
+/// that Notes can save go to the directory passed as the first argument;
+/// inline images already sit in the body as data URLs.
+const SCRIPT: &str = r#"
+function run(argv) {
+  const Notes = Application("Notes");
+  const out = [];
+  let saved = 0;
+  const walk = (folder, path) => {
+    for (const note of folder.notes()) {
+      if (note.passwordProtected()) {
+        out.push({ name: note.name(), folder: path, locked: true });
+        continue;
+      }
+      const attachments = [];
+      for (const attachment of note.attachments()) {
+        const file = argv[0] + "/" + saved++ + "-" + attachment.name();
+        try {
+          Notes.save(attachment, { in: Path(file) });
+          attachments.push(file);
+        } catch (e) {}
+      }
+      out.push({
+        name: note.name(),
+        folder: path,
+        body: note.body(),
+        created: note.creationDate().toISOString(),
+        modified: note.modificationDate().toISOString(),
+        attachments: attachments,
+      });
+    }
+    for (const child of folder.folders()) {
+      walk(child, path.concat([child.name()]));
+    }
+  };
+  for (const account of Notes.accounts()) {
+    for (const folder of account.folders()) {
+      if (folder.container().id() === account.id() && folder.name() !== "Recently Deleted") {
+        walk(folder, [account.name(), folder.name()]);
+      }
+    }
+  }
+  return JSON.stringify(out);
+}
+"#;
+
+#[derive(Deserialize)]
+struct AppleNote {
+    name: String,
+    /// Account, then folders from the top.
+    folder: Vec<String>,
+    #[serde(default)]
+    locked: bool,
+    #[serde(default)]
+    body: String,
+    #[serde(default)]
+    created: String,
+    #[serde(default)]
+    modified: String,
+    /// Files the script saved the note's attachments to.
+    #[serde(default)]
+    attachments: Vec<String>,
+}
+
+/// Imports every note in Apple Notes through automation, keeping accounts
+/// and folders as folders below [`TOP_FOLDER`]. Bodies are converted from
+/// Notes' HTML to Markdown and attached images and files are stored as
+/// attachments. Password-protected notes are reported, not imported.
+///
+/// The first run makes macOS ask whether the app may control Notes; a
+/// refusal comes back as an error saying where to allow it.
+pub fn import(vault: &Vault, progress: &mut dyn FnMut(ImportProgress)) -> Result<ImportReport> {
+    if vault.storage.is_locked() {
+        return Err(Error::VaultLocked);
+    }
+    let staging = Staging::new("apple-notes")?;
+    let output = Command::new("osascript")
+        .args(["-l", "JavaScript", "-e", SCRIPT])
+        .arg(&staging.0)
+        .output()?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if stderr.contains(NOT_AUTHORIZED) {
+            return Err(Error::InvalidInput(
+                "access to Notes was denied; allow it under System Settings > Privacy & Security > Automation".into(),
+            ));
+        }
+        return Err(Error::InvalidInput(format!(
+            "could not read Apple Notes: {}",
+            stderr.trim()
+        )));
+    }
+    let notes: Vec<AppleNote> = serde_json::from_slice(&output.stdout)?;
+
+    let mut report = ImportReport::default();
+    let total = notes.len() as u64;
+    for (i, note) in notes.into_iter().enumerate() {
+        let title = match note.name.trim() {
+            "" => "Untitled".to_owned(),
+            title => title.to_owned(),
+        };
+        if note.locked {
+            report
+                .failed
+                .push(format!("{title}: locked with a password in Notes"));
+            continue;
+        }
+        match import_note(vault, &title, note, &mut report) {
+            Ok(()) => progress(ImportProgress {
+                source: SOURCE,
+                current: i as u64 + 1,
+                total,
+                title: Some(title),
+            }),
+            Err(err) => report.failed.push(format!("{title}: {err}")),
+        }
+    }
+
+    if report.notes > 0 {
+        vault.autocommit("Import from Apple Notes")?;
+    }
+    Ok(report)
+}
+
+fn import_note(
+    vault: &Vault,
+    title: &str,
+    note: AppleNote,
+    report: &mut ImportReport,
+) -> Result<()> {
+    let folder = std::iter::once(TOP_FOLDER.to_owned())
+        .chain(note.folder.iter().map(|part| part.replace('/', "-")))
+        .collect::<Vec<_>>()
+        .join("/");
+
+    let html = inline_images(vault, &folder, &note.body, report)?;
+    let markdown = HtmlToMarkdown::builder()
+        .skip_tags(vec!["head", "style", "script"])
+        .build()
+        .convert(&html)?;
+    // Notes makes the first line the title and keeps it in the body.
+    let mut body = drop_first_line(&markdown, title).to_owned();
+    for file in &note.attachments {
+        let path = Path::new(file);
+        let Ok(content) = fs::read(path) else {
+            continue;
+        };
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        // The script numbers files to keep them apart.
+        let name = name.split_once('-').map_or(&*name, |(_, name)| name);
+        let rel = vault.files.save_attachment(name, &content)?;
+        report.attachments += 1;
+        let label = name.replace(['[', ']'], "");
+        body.push_str(&format!(
+            "\n\n[{label}]({})",
+            NoteFiles::link_from(&folder, &rel)
+        ));
+    }
+
+    let created_at = rfc3339_millis(&note.created).unwrap_or_else(now_millis);
+    vault.import_note(&Note {
+        id: uuid::Uuid::new_v4().to_string(),
+        title: title.to_owned(),
+        body,
+        folder,
+        created_at,
+        updated_at: rfc3339_millis(&note.modified).unwrap_or(created_at),
+    })?;
+    report.notes += 1;
+    Ok(())
+}
+
+/// Stores the `data:` images in `html` as attachments and points their
+/// `src` at the stored files instead.
+fn inline_images(
+    vault: &Vault,
+    folder: &str,
+    html: &str,
+    report: &mut ImportReport,
+) -> Result<String> {
+    const PREFIX: &str = "src=\"data:";
+    let mut out = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some(start) = rest.find(PREFIX) {
+        let after = &rest[start + PREFIX.len()..];
+        let Some(end) = after.find('"') else {
+            break;
+        };
+        out.push_str(&rest[..start]);
+        rest = &after[end + 1..];
+        let Some((mime, data)) = after[..end].split_once(";base64,") else {
+            out.push_str(&format!("{PREFIX}{}\"", &after[..end]));
+            continue;
+        };
+        let bytes = BASE64
+            .decode(data)
+            .map_err(|err| Error::InvalidInput(format!("bad image data: {err}")))?;
+        let ext = mime.strip_prefix("image/").unwrap_or("bin");
+        let ext = match ext {
+            "jpeg" => "jpg",
+            "svg+xml" => "svg",
+            ext => ext,
+        };
+        let rel = vault
+            .files
+            .save_attachment(&format!("image.{ext}"), &bytes)?;
+        report.attachments += 1;
+        out.push_str(&format!("src=\"{}\"", NoteFiles::link_from(folder, &rel)));
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+fn drop_first_line<'a>(markdown: &'a str, title: &str) -> &'a str {
+    let trimmed = markdown.trim_start();
+    let (first, rest) = trimmed.split_once('\n').unwrap_or((trimmed, ""));
+    if first
+        .trim()
+        .trim_start_matches('#')
+        .trim_matches('*')
+        .trim()
+        == title
+    {
+        rest.trim_start_matches(['\r', '\n'])
+    } else {
+        markdown
+    }
+}
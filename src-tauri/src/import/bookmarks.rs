@@ -0,0 +1,347 @@
+//! Bookmarks exported from a browser, in the Netscape bookmark format
+//! Chrome, Firefox, Safari and Edge all write. Each bookmark folder becomes
+//! a note listing its links, placed in vault folders that mirror the
+//! bookmark folders above it.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use quick_xml::escape::{resolve_predefined_entity, unescape_with};
+use reqwest::Client;
+use serde::Deserialize;
+use tokio::sync::Semaphore;
+
+use super::{ImportProgress, ImportReport};
+use crate::error::{Error, Result};
+use crate::vault::Vault;
+
+const SOURCE: &str = "bookmarks";
+const TIMEOUT: Duration = Duration::from_secs(15);
+const USER_AGENT: &str = "Mozilla/5.0 (compatible; notesdesktop bookmarks)";
+/// Only the start of a page is read; titles and descriptions are in its
+/// head.
+const MAX_HEAD_BYTES: usize = 256 * 1024;
+/// Pages fetched at once.
+const CONCURRENCY: usize = 8;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct BookmarkOptions {
+    /// Vault folder the bookmark folders go under.
+    pub folder: String,
+    /// Fetch each page for a title where the bookmark has none, and for a
+    /// description.
+    pub fetch_details: bool,
+}
+
+impl Default for BookmarkOptions {
+    fn default() -> Self {
+        Self {
+            folder: "Bookmarks".into(),
+            fetch_details: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Bookmark {
+    pub url: String,
+    pub title: String,
+    pub description: Option<String>,
+}
+
+/// The bookmarks directly in one folder, by the names of the folders
+/// leading to it.
+#[derive(Debug, Clone)]
+pub struct BookmarkFolder {
+    pub path: Vec<String>,
+    pub bookmarks: Vec<Bookmark>,
+}
+
+/// Reads an export into its folders, in the order they appear. Folders
+/// without bookmarks of their own are left out.
+pub fn parse(html: &str) -> Result<Vec<BookmarkFolder>> {
+    if !html
+        .get(..html.len().min(1024))
+        .unwrap_or_default()
+        .to_ascii_uppercase()
+        .contains("NETSCAPE-BOOKMARK-FILE")
+    {
+        return Err(Error::InvalidInput(
+            "not a bookmarks export; export them from the browser as HTML".into(),
+        ));
+    }
+    let mut folders: Vec<BookmarkFolder> = Vec::new();
+    let mut index: HashMap<Vec<String>, usize> = HashMap::new();
+    let mut path: Vec<String> = Vec::new();
+    // Whether each open list is a named folder, and so adds to `path`.
+    let mut lists: Vec<bool> = Vec::new();
+    let mut heading: Option<String> = None;
+    let mut last: Option<(usize, usize)> = None;
+
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start..];
+        let Some(end) = rest.find('>') else {
+            break;
+        };
+        let tag = &rest[1..end];
+        rest = &rest[end + 1..];
+        let (name, attrs) = tag.split_once(char::is_whitespace).unwrap_or((tag, ""));
+        match name.to_ascii_lowercase().as_str() {
+            "h3" => {
+                heading = Some(text_until(&mut rest, "</h3"));
+                last = None;
+            }
+            "dl" => {
+                let named = heading.take().filter(|_| !lists.is_empty());
+                lists.push(named.is_some());
+                path.extend(named.map(|name| name.replace('/', "-")));
+            }
+            "/dl" => {
+                if lists.pop() == Some(true) {
+                    path.pop();
+                }
+                last = None;
+            }
+            "a" => {
+                let title = text_until(&mut rest, "</a");
+                let Some(url) = attr(attrs, "href").filter(|url| is_web(url)) else {
+                    last = None;
+                    continue;
+                };
+                let folder = *index.entry(path.clone()).or_insert_with(|| {
+                    folders.push(BookmarkFolder {
+                        path: path.clone(),
+                        bookmarks: Vec::new(),
+                    });
+                    folders.len() - 1
+                });
+                folders[folder].bookmarks.push(Bookmark {
+                    url,
+                    title,
+                    description: None,
+                });
+                last = Some((folder, folders[folder].bookmarks.len() - 1));
+            }
+            "dd" => {
+                let end = rest.find('<').unwrap_or(rest.len());
+                let description = decode(rest[..end].trim());
+                if let Some((folder, i)) = last.take() {
+                    if !description.is_empty() {
+                        folders[folder].bookmarks[i].description = Some(description);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(folders)
+}
+
+/// Fills in missing titles and descriptions from the pages themselves. A
+/// page that cannot be fetched keeps what the bookmark had.
+pub async fn fetch_details(
+    folders: &mut [BookmarkFolder],
+    mut progress: impl FnMut(ImportProgress) + Send,
+) -> Result<()> {
+    let client = Client::builder()
+        .timeout(TIMEOUT)
+        .user_agent(USER_AGENT)
+        .build()?;
+    let permits = Arc::new(Semaphore::new(CONCURRENCY));
+    let mut pending = Vec::new();
+    for (f, folder) in folders.iter().enumerate() {
+        for (b, bookmark) in folder.bookmarks.iter().enumerate() {
+            let (client, permits, url) =
+                (client.clone(), Arc::clone(&permits), bookmark.url.clone());
+            let task = tauri::async_runtime::spawn(async move {
+                let _permit = permits.acquire_owned().await.ok()?;
+                head_of(&client, &url).await.ok()
+            });
+            pending.push((f, b, task));
+        }
+    }
+    let total = pending.len() as u64;
+    for (done, (f, b, task)) in pending.into_iter().enumerate() {
+        let bookmark = &mut folders[f].bookmarks[b];
+        if let Ok(Some((title, description))) = task.await {
+            if bookmark.title.trim().is_empty() || bookmark.title == bookmark.url {
+                bookmark.title = title.unwrap_or_default();
+            }
+            bookmark.description = bookmark.description.take().or(description);
+        }
+        progress(ImportProgress {
+            source: SOURCE,
+            current: done as u64 + 1,
+            total,
+            title: Some(bookmark.url.clone()),
+        });
+    }
+    Ok(())
+}
+
+/// The title and description in the head of the page at `url`.
+async fn head_of(client: &Client, url: &str) -> Result<(Option<String>, Option<String>)> {
+    let mut response = client.get(url).send().await?.error_for_status()?;
+    let mut content = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        content.extend_from_slice(&chunk);
+        if content.len() >= MAX_HEAD_BYTES {
+            break;
+        }
+    }
+    let html = String::from_utf8_lossy(&content);
+    let head = match html.to_ascii_lowercase().find("</head") {
+        Some(end) => &html[..end],
+        None => &html[..],
+    };
+    let mut title = None;
+    let mut description = None;
+    let mut rest = head;
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start..];
+        let Some(end) = rest.find('>') else {
+            break;
+        };
+        let tag = &rest[1..end];
+        rest = &rest[end + 1..];
+        let (name, attrs) = tag.split_once(char::is_whitespace).unwrap_or((tag, ""));
+        match name.to_ascii_lowercase().as_str() {
+            "title" if title.is_none() => {
+                title = Some(text_until(&mut rest, "</title")).filter(|t| !t.is_empty());
+            }
+            "meta" if description.is_none() => {
+                let key = attr(attrs, "name").or_else(|| attr(attrs, "property"));
+                if matches!(key.as_deref(), Some("description" | "og:description")) {
+                    description = attr(attrs, "content").filter(|d| !d.is_empty());
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok((title, description))
+}
+
+/// Makes one note per folder. Returns how it went; a folder that fails is
+/// reported and the rest go on.
+pub fn import(
+    vault: &Vault,
+    folders: &[BookmarkFolder],
+    options: &BookmarkOptions,
+    progress: &mut dyn FnMut(ImportProgress),
+) -> Result<ImportReport> {
+    if vault.storage.is_locked() {
+        return Err(Error::VaultLocked);
+    }
+    let base = options.folder.trim().trim_matches('/');
+    let mut report = ImportReport::default();
+    for (i, folder) in folders.iter().enumerate() {
+        let (title, parents) = match folder.path.split_last() {
+            Some((name, parents)) => (name.clone(), parents),
+            None => ("Bookmarks".to_owned(), &[][..]),
+        };
+        let target = std::iter::once(base)
+            .chain(parents.iter().map(String::as_str))
+            .filter(|segment| !segment.is_empty())
+            .collect::<Vec<_>>()
+            .join("/");
+        match vault.create_note(&title, &body_of(folder), &target) {
+            Ok(_) => report.notes += 1,
+            Err(err) => report.failed.push(format!("{title}: {err}")),
+        }
+        progress(ImportProgress {
+            source: SOURCE,
+            current: i as u64 + 1,
+            total: folders.len() as u64,
+            title: Some(title),
+        });
+    }
+    Ok(report)
+}
+
+fn body_of(folder: &BookmarkFolder) -> String {
+    let mut body = String::new();
+    for bookmark in &folder.bookmarks {
+        let title: String = match bookmark.title.trim() {
+            "" => bookmark.url.clone(),
+            title => title.to_owned(),
+        }
+        .chars()
+        .filter(|c| !matches!(c, '[' | ']'))
+        .collect();
+        // Angle brackets keep spaces and parentheses in the address intact.
+        let url = match bookmark.url.contains([' ', '(', ')']) {
+            true => format!("<{}>", bookmark.url),
+            false => bookmark.url.clone(),
+        };
+        body.push_str(&format!("- [{title}]({url})"));
+        if let Some(description) = &bookmark.description {
+            let description = description.split_whitespace().collect::<Vec<_>>().join(" ");
+            body.push_str(&format!(" — {description}"));
+        }
+        body.push('\n');
+    }
+    body
+}
+
+/// The text up to the closing tag `close`, which `rest` is moved past.
+fn text_until(rest: &mut &str, close: &str) -> String {
+    let lower = rest.to_ascii_lowercase();
+    let end = lower.find(close).unwrap_or(rest.len());
+    let text = decode(strip_tags(&rest[..end]).trim());
+    *rest = &rest[end..];
+    text
+}
+
+fn strip_tags(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut in_tag = false;
+    for c in text.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' => in_tag = false,
+            c if !in_tag => out.push(c),
+            _ => {}
+        }
+    }
+    out
+}
+
+/// The value of attribute `name` in the attribute text of a tag, decoded.
+fn attr(attrs: &str, name: &str) -> Option<String> {
+    let lower = attrs.to_ascii_lowercase();
+    let mut from = 0;
+    while let Some(at) = lower[from..].find(name).map(|at| from + at) {
+        from = at + name.len();
+        let standalone = at == 0 || lower.as_bytes()[at - 1].is_ascii_whitespace();
+        let rest = attrs[from..].trim_start();
+        let (true, Some(value)) = (standalone, rest.strip_prefix('=')) else {
+            continue;
+        };
+        let value = value.trim_start();
+        let raw = match value.chars().next() {
+            Some(quote @ ('"' | '\'')) => value[1..].split(quote).next().unwrap_or_default(),
+            _ => value.split(char::is_whitespace).next().unwrap_or_default(),
+        };
+        return Some(decode(raw.trim()));
+    }
+    None
+}
+
+fn decode(text: &str) -> String {
+    unescape_with(text, |entity| {
+        resolve_predefined_entity(entity).or(match entity {
+            "nbsp" => Some(" "),
+            _ => None,
+        })
+    })
+    .map(|text| text.into_owned())
+    .unwrap_or_else(|_| text.to_owned())
+}
+
+fn is_web(url: &str) -> bool {
+    let lower = url.to_ascii_lowercase();
+    lower.starts_with("http://") || lower.starts_with("https://")
+}
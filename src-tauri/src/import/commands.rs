@@ -0,0 +1,211 @@
+use std::path::Path;
+
+use tauri::{AppHandle, Emitter, Manager};
+
+use super::bookmarks::{self, BookmarkOptions};
+use super::obsidian::{self, ObsidianReport};
+use super::opml::{self, OpmlMode};
+use super::{enex, joplin, markdown, notion, pandoc, ImportReport, Resumable, PROGRESS_EVENT};
+use crate::error::{Error, Result};
+use crate::files::Staging;
+use crate::jobs::{log, JobKind, Jobs, Priority};
+use crate::storage::Note;
+use crate::vaults::Current;
+
+/// Imports an Evernote `.enex` export as a job, emitting `import-progress`
+/// events as notes are added.
+#[tauri::command]
+pub async fn import_enex(app: AppHandle, vault: Current, path: String) -> Result<ImportReport> {
+    run_resumable(app, vault, enex::SOURCE, path, None).await
+}
+
+/// Imports an Obsidian vault directory. With `dryRun`, nothing is written and
+/// the report lists what would be imported.
+#[tauri::command]
+pub async fn import_obsidian(
+    app: AppHandle,
+    vault: Current,
+    path: String,
+    dry_run: Option<bool>,
+) -> Result<ObsidianReport> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let dry_run = dry_run.unwrap_or(false);
+        obsidian::import(&vault, Path::new(&path), dry_run, &mut |progress| {
+            let _ = app.emit(PROGRESS_EVENT, progress);
+        })
+    })
+    .await?
+}
+
+/// Imports a Notion export zip as a job, emitting an `import-progress`
+/// event per page.
+#[tauri::command]
+pub async fn import_notion_zip(
+    app: AppHandle,
+    vault: Current,
+    path: String,
+) -> Result<ImportReport> {
+    run_resumable(app, vault, notion::SOURCE, path, None).await
+}
+
+/// Carries on with the import the job log keeps as `id`, which was
+/// interrupted, cancelled or failed, from its last checkpoint.
+#[tauri::command]
+pub async fn resume_import(app: AppHandle, vault: Current, id: String) -> Result<ImportReport> {
+    let entry = log::get(&vault.storage, &id)?;
+    run_resumable(app, vault, &entry.source, entry.path, Some(id)).await
+}
+
+async fn run_resumable(
+    app: AppHandle,
+    vault: Current,
+    source: &str,
+    path: String,
+    resume: Option<String>,
+) -> Result<ImportReport> {
+    let import = match source {
+        enex::SOURCE => enex::import,
+        notion::SOURCE => notion::import,
+        source => {
+            return Err(Error::InvalidInput(format!(
+                "{source} imports cannot be resumed"
+            )))
+        }
+    };
+    let source = source.to_owned();
+    let emitter = app.clone();
+    app.state::<Jobs>()
+        .run(JobKind::Import, Priority::User, move |context| {
+            let path = Path::new(&path);
+            let mut resume =
+                Resumable::open(&vault.storage, context, &source, path, resume.as_deref())?;
+            let result = import(&vault, path, &mut resume, &mut |progress| {
+                let _ = emitter.emit(PROGRESS_EVENT, progress);
+            });
+            resume.finish(&result)?;
+            result
+        })
+        .await
+}
+
+/// Imports a Joplin `.jex` archive or raw export directory, emitting an
+/// `import-progress` event per note.
+#[tauri::command]
+pub async fn import_joplin(app: AppHandle, vault: Current, path: String) -> Result<ImportReport> {
+    tauri::async_runtime::spawn_blocking(move || {
+        joplin::import(&vault, Path::new(&path), &mut |progress| {
+            let _ = app.emit(PROGRESS_EVENT, progress);
+        })
+    })
+    .await?
+}
+
+/// Imports a browser's bookmarks export, one note per bookmark folder. With
+/// `fetchDetails`, each page is fetched first for a title and description,
+/// with an `import-progress` event per page.
+#[tauri::command]
+pub async fn import_bookmarks(
+    app: AppHandle,
+    vault: Current,
+    path: String,
+    options: Option<BookmarkOptions>,
+) -> Result<ImportReport> {
+    let options = options.unwrap_or_default();
+    let html = String::from_utf8_lossy(&std::fs::read(&path)?).into_owned();
+    let mut folders = bookmarks::parse(&html)?;
+    if options.fetch_details {
+        bookmarks::fetch_details(&mut folders, |progress| {
+            let _ = app.emit(PROGRESS_EVENT, progress);
+        })
+        .await?;
+    }
+    tauri::async_runtime::spawn_blocking(move || {
+        bookmarks::import(&vault, &folders, &options, &mut |progress| {
+            let _ = app.emit(PROGRESS_EVENT, progress);
+        })
+    })
+    .await?
+}
+
+/// Imports an OPML outline into `folder`, as one note or as folders and
+/// notes; see [`OpmlMode`].
+#[tauri::command]
+pub async fn import_opml(
+    app: AppHandle,
+    vault: Current,
+    path: String,
+    folder: Option<String>,
+    mode: Option<OpmlMode>,
+) -> Result<ImportReport> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let folder = folder.unwrap_or_default();
+        opml::import(&vault, Path::new(&path), &folder, mode, &mut |progress| {
+            let _ = app.emit(PROGRESS_EVENT, progress);
+        })
+    })
+    .await?
+}
+
+/// Imports everything in Apple Notes once the user agrees to it. macOS then
+/// asks separately whether the app may control Notes.
+#[cfg(target_os = "macos")]
+#[tauri::command]
+pub async fn import_apple_notes(app: AppHandle, vault: Current) -> Result<ImportReport> {
+    use tauri_plugin_dialog::{DialogExt, MessageDialogButtons};
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let allowed = app
+            .dialog()
+            .message(
+                "Saentis Notes will read every note in Apple Notes, except locked ones, \
+                 and copy them into this vault.",
+            )
+            .title("Import from Apple Notes")
+            .buttons(MessageDialogButtons::OkCancelCustom(
+                "Import".into(),
+                "Cancel".into(),
+            ))
+            .blocking_show();
+        if !allowed {
+            return Err(crate::error::Error::InvalidInput(
+                "the import was cancelled".into(),
+            ));
+        }
+        super::apple_notes::import(&vault, &mut |progress| {
+            let _ = app.emit(PROGRESS_EVENT, progress);
+        })
+    })
+    .await?
+}
+
+/// Copies a Markdown file opened from outside the vault, such as through
+/// the OS "Open With" menu, into a new note.
+#[tauri::command]
+pub async fn import_markdown_file(
+    vault: Current,
+    path: String,
+    folder: Option<String>,
+) -> Result<Note> {
+    tauri::async_runtime::spawn_blocking(move || {
+        markdown::import(&vault, Path::new(&path), &folder.unwrap_or_default())
+    })
+    .await?
+}
+
+/// Converts a Word, OpenDocument or HTML file into a new note with pandoc,
+/// telling the formats apart by extension or content.
+#[tauri::command]
+pub async fn import_document(
+    app: AppHandle,
+    vault: Current,
+    path: String,
+    folder: Option<String>,
+) -> Result<Note> {
+    let staging = Staging::new("pandoc")?;
+    let markdown = pandoc::convert(&app, Path::new(&path), &staging.0).await?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let folder = folder.unwrap_or_default();
+        pandoc::import(&vault, Path::new(&path), &markdown, &staging.0, &folder)
+    })
+    .await?
+}
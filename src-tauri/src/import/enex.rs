@@ -0,0 +1,293 @@
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::Path;
+
+use base64::Engine;
+use htmd::element_handler::Handlers;
+use htmd::{Element, HtmlToMarkdown};
+use md5::{Digest, Md5};
+use quick_xml::events::Event;
+use quick_xml::Reader;
+
+use super::{utc_millis, ImportProgress, ImportReport, Resumable};
+use crate::error::{Error, Result};
+use crate::files::NoteFiles;
+use crate::metadata::FrontMatter;
+use crate::storage::{now_millis, Note};
+use crate::vault::Vault;
+use crate::xml;
+
+pub const SOURCE: &str = "enex";
+/// Evernote timestamps look like `20240131T235959Z`.
+const DATE_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+
+/// A `<note>` as read from the export, before conversion.
+#[derive(Default)]
+struct EnexNote {
+    title: String,
+    /// ENML, an XHTML dialect.
+    content: String,
+    created: String,
+    updated: String,
+    tags: Vec<String>,
+    resources: Vec<Resource>,
+}
+
+#[derive(Default)]
+struct Resource {
+    /// Base64, possibly wrapped across lines.
+    data: String,
+    mime: String,
+    file_name: String,
+}
+
+impl EnexNote {
+    /// Appends character data found at `path`, the stack of open elements.
+    fn push_text(&mut self, path: &[String], text: &str) {
+        let [.., parent, element] = path else {
+            return;
+        };
+        let target = match (parent.as_str(), element.as_str()) {
+            ("note", "title") => &mut self.title,
+            ("note", "content") => &mut self.content,
+            ("note", "created") => &mut self.created,
+            ("note", "updated") => &mut self.updated,
+            ("note", "tag") => match self.tags.last_mut() {
+                Some(tag) => tag,
+                None => return,
+            },
+            ("resource", field @ ("data" | "mime"))
+            | ("resource-attributes", field @ "file-name") => {
+                let Some(resource) = self.resources.last_mut() else {
+                    return;
+                };
+                match field {
+                    "data" => &mut resource.data,
+                    "mime" => &mut resource.mime,
+                    _ => &mut resource.file_name,
+                }
+            }
+            _ => return,
+        };
+        target.push_str(text);
+    }
+}
+
+/// Imports every note in the Evernote export at `path` into a folder named
+/// after the file, with tags written as front matter and resources saved as
+/// attachments. The export is streamed, so its size does not matter, and
+/// `resume` keeps its place note by note.
+pub fn import(
+    vault: &Vault,
+    path: &Path,
+    resume: &mut Resumable,
+    progress: &mut dyn FnMut(ImportProgress),
+) -> Result<ImportReport> {
+    if vault.storage.is_locked() {
+        return Err(Error::VaultLocked);
+    }
+    let total = fs::metadata(path)?.len();
+    let folder = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    let mut reader = Reader::from_reader(BufReader::new(File::open(path)?));
+    let mut buf = Vec::new();
+    let mut open: Vec<String> = Vec::new();
+    let mut note: Option<EnexNote> = None;
+    let mut index = 0;
+    let mut report = resume.take_report();
+    loop {
+        buf.clear();
+        let event = reader
+            .read_event_into(&mut buf)
+            .map_err(|err| Error::InvalidInput(format!("not a valid ENEX file: {err}")))?;
+        let text = match event {
+            Event::Start(tag) => {
+                let name = tag.local_name().as_ref().to_owned();
+                if let Some(note) = note.as_mut() {
+                    match name.as_str() {
+                        "tag" => note.tags.push(String::new()),
+                        "resource" => note.resources.push(Resource::default()),
+                        _ => {}
+                    }
+                } else if name == "note" {
+                    note = Some(EnexNote::default());
+                }
+                open.push(name);
+                continue;
+            }
+            Event::End(_) => {
+                if open.pop().as_deref() == Some("note") {
+                    if let Some(note) = note.take() {
+                        let id = resume.note_id(&index.to_string());
+                        index += 1;
+                        if resume.is_done(index - 1, &id, &mut report)? {
+                            continue;
+                        }
+                        let title = title_of(&note);
+                        if let Err(err) = import_note(vault, &folder, id, note, &mut report) {
+                            report.failed.push(format!("{title}: {err}"));
+                        }
+                        let current = reader.buffer_position();
+                        progress(ImportProgress {
+                            source: SOURCE,
+                            current,
+                            total,
+                            title: Some(title),
+                        });
+                        resume.checkpoint(index, current, total, &report)?;
+                    }
+                }
+                continue;
+            }
+            Event::Text(text) => text.into_inner().into_owned(),
+            Event::CData(data) => data.into_inner().into_owned(),
+            Event::GeneralRef(reference) => match xml::resolve_entity(&reference) {
+                Some(c) => c.to_string(),
+                None => continue,
+            },
+            Event::Eof => break,
+            _ => continue,
+        };
+        if let Some(note) = note.as_mut() {
+            note.push_text(&open, &text);
+        }
+    }
+
+    if report.notes > 0 {
+        vault.autocommit(&format!("Import \"{folder}\" from Evernote"))?;
+    }
+    progress(ImportProgress {
+        source: SOURCE,
+        current: total,
+        total,
+        title: None,
+    });
+    Ok(report)
+}
+
+fn import_note(
+    vault: &Vault,
+    folder: &str,
+    id: String,
+    note: EnexNote,
+    report: &mut ImportReport,
+) -> Result<()> {
+    // ENML refers to resources by the MD5 of their data.
+    let mut media = HashMap::new();
+    for resource in &note.resources {
+        let data: String = resource
+            .data
+            .chars()
+            .filter(|c| !c.is_ascii_whitespace())
+            .collect();
+        let bytes = base64::engine::general_purpose::STANDARD
+            .decode(data)
+            .map_err(|err| Error::InvalidInput(format!("bad resource data: {err}")))?;
+        let name = attachment_name(resource);
+        let rel = vault.files.save_attachment(&name, &bytes)?;
+        report.attachments += 1;
+
+        let label = name.replace(['[', ']'], "");
+        let target = NoteFiles::link_from(folder, &rel);
+        let link = if resource.mime.trim().starts_with("image/") {
+            format!("![{label}]({target})")
+        } else {
+            format!("[{label}]({target})")
+        };
+        media.insert(hex::encode(Md5::digest(&bytes)), link);
+    }
+
+    let mut front_matter = FrontMatter::default();
+    front_matter.set_tags(&note.tags);
+    let body = enml_to_markdown(&note.content, media)?;
+    let created_at = utc_millis(&note.created, DATE_FORMAT).unwrap_or_else(now_millis);
+    vault.import_note(&Note {
+        id,
+        title: title_of(&note),
+        body: format!("{}{body}", front_matter.render()),
+        folder: folder.to_owned(),
+        created_at,
+        updated_at: utc_millis(&note.updated, DATE_FORMAT).unwrap_or(created_at),
+    })?;
+    report.notes += 1;
+    Ok(())
+}
+
+fn enml_to_markdown(enml: &str, media: HashMap<String, String>) -> Result<String> {
+    // ENML's empty elements are not void in HTML, so whatever follows them
+    // is parsed as their children and has to be carried through.
+    let converter = HtmlToMarkdown::builder()
+        .add_handler(
+            vec!["en-media"],
+            move |handlers: &dyn Handlers, element: Element| {
+                let link = attr(&element, "hash")
+                    .and_then(|hash| media.get(hash))
+                    .cloned()
+                    .unwrap_or_default();
+                let rest = handlers.walk_children(element.node).content;
+                Some(format!("{link}{rest}").into())
+            },
+        )
+        .add_handler(
+            vec!["en-todo"],
+            |handlers: &dyn Handlers, element: Element| {
+                let checked = attr(&element, "checked") == Some("true");
+                let rest = handlers.walk_children(element.node).content;
+                let mark = if checked { "- [x]" } else { "- [ ]" };
+                Some(format!("{mark} {rest}").into())
+            },
+        )
+        .add_handler(vec!["en-crypt"], |_: &dyn Handlers, _: Element| {
+            Some("*[encrypted content]*".into())
+        })
+        .build();
+    Ok(converter.convert(enml)?)
+}
+
+fn attr<'a>(element: &Element<'a>, name: &str) -> Option<&'a str> {
+    element
+        .attrs
+        .iter()
+        .find(|attr| &*attr.name.local == name)
+        .map(|attr| &*attr.value)
+}
+
+fn title_of(note: &EnexNote) -> String {
+    match note.title.trim() {
+        "" => "Untitled".to_owned(),
+        title => title.to_owned(),
+    }
+}
+
+fn attachment_name(resource: &Resource) -> String {
+    let name = match resource.file_name.trim() {
+        "" => "attachment",
+        name => name,
+    };
+    if Path::new(name).extension().is_some() {
+        return name.to_owned();
+    }
+    match mime_extension(resource.mime.trim()) {
+        Some(ext) => format!("{name}.{ext}"),
+        None => name.to_owned(),
+    }
+}
+
+fn mime_extension(mime: &str) -> Option<&'static str> {
+    Some(match mime {
+        "image/png" => "png",
+        "image/jpeg" => "jpg",
+        "image/gif" => "gif",
+        "image/webp" => "webp",
+        "image/svg+xml" => "svg",
+        "application/pdf" => "pdf",
+        "audio/mpeg" => "mp3",
+        "audio/wav" | "audio/x-wav" => "wav",
+        "text/plain" => "txt",
+        _ => return None,
+    })
+}
@@ -0,0 +1,317 @@
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+
+use htmd::HtmlToMarkdown;
+use serde_yaml_ng::Value;
+
+use super::{rfc3339_millis, ImportProgress, ImportReport};
+use crate::error::{Error, Result};
+use crate::files::{NoteFiles, Staging};
+use crate::links;
+use crate::markdown::rewrite_prose;
+use crate::metadata::FrontMatter;
+use crate::storage::{now_millis, Note};
+use crate::vault::Vault;
+
+const SOURCE: &str = "joplin";
+/// Joplin ids are 32 lowercase hex digits; links to them read `:/<id>`.
+const ID_LEN: usize = 32;
+
+/// Item types as Joplin numbers them in `type_`.
+const TYPE_NOTE: &str = "1";
+const TYPE_FOLDER: &str = "2";
+const TYPE_RESOURCE: &str = "4";
+const TYPE_TAG: &str = "5";
+const TYPE_NOTE_TAG: &str = "6";
+
+/// One serialized item: a title line, an optional body, and the `key: value`
+/// properties Joplin appends after a blank line.
+struct Item {
+    title: String,
+    body: String,
+    props: HashMap<String, String>,
+}
+
+impl Item {
+    fn parse(text: &str) -> Self {
+        let lines: Vec<&str> = text.trim_end().lines().collect();
+        let mut start = lines.len();
+        while start > 0 && property(lines[start - 1]).is_some() {
+            start -= 1;
+        }
+        let props = lines[start..]
+            .iter()
+            .filter_map(|line| property(line))
+            .map(|(key, value)| (key.to_owned(), value.to_owned()))
+            .collect();
+        let content = &lines[..start];
+        let title = content.first().map_or("", |line| line.trim()).to_owned();
+        let body = content
+            .get(1..)
+            .unwrap_or_default()
+            .join("\n")
+            .trim_matches('\n')
+            .to_owned();
+        Self { title, body, props }
+    }
+
+    fn prop(&self, key: &str) -> &str {
+        self.props.get(key).map_or("", String::as_str)
+    }
+}
+
+struct Importer<'a> {
+    vault: &'a Vault,
+    root: PathBuf,
+    /// Joplin id of each note to the id its note gets here.
+    notes: HashMap<String, String>,
+    resources: HashMap<String, Item>,
+    /// Joplin id of each resource to where it was stored.
+    attachments: HashMap<String, String>,
+    report: ImportReport,
+}
+
+/// Imports a Joplin export, either a `.jex` archive or a directory in the
+/// raw format. Notebooks become folders, tags and geolocation go into front
+/// matter, and `:/<id>` links to resources and other notes are rewritten to
+/// attachments and note links.
+pub fn import(
+    vault: &Vault,
+    path: &Path,
+    progress: &mut dyn FnMut(ImportProgress),
+) -> Result<ImportReport> {
+    if vault.storage.is_locked() {
+        return Err(Error::VaultLocked);
+    }
+    // A JEX file is the raw format in a tar archive.
+    let staging;
+    let root = if path.is_dir() {
+        path.to_owned()
+    } else {
+        staging = Staging::new("joplin")?;
+        tar::Archive::new(File::open(path)?)
+            .unpack(&staging.0)
+            .map_err(|err| Error::InvalidInput(format!("not a JEX archive: {err}")))?;
+        staging.0.clone()
+    };
+
+    let mut notes = Vec::new();
+    let mut folders = HashMap::new();
+    let mut tags = HashMap::new();
+    let mut note_tags: Vec<(String, String)> = Vec::new();
+    let mut resources = HashMap::new();
+    let mut failed = Vec::new();
+    for entry in fs::read_dir(&root)? {
+        let path = entry?.path();
+        if path.extension().is_none_or(|ext| ext != "md") {
+            continue;
+        }
+        let item = Item::parse(&fs::read_to_string(&path)?);
+        if item.prop("encryption_applied") == "1" {
+            failed.push(format!(
+                "{}: encrypted with Joplin's end-to-end encryption",
+                path.file_name().unwrap_or_default().to_string_lossy()
+            ));
+            continue;
+        }
+        let id = item.prop("id").to_owned();
+        match item.prop("type_") {
+            TYPE_NOTE => notes.push(item),
+            TYPE_FOLDER => {
+                folders.insert(id, item);
+            }
+            TYPE_RESOURCE => {
+                resources.insert(id, item);
+            }
+            TYPE_TAG => {
+                tags.insert(id, item.title);
+            }
+            TYPE_NOTE_TAG => note_tags.push((
+                item.prop("note_id").to_owned(),
+                item.prop("tag_id").to_owned(),
+            )),
+            _ => {}
+        }
+    }
+    notes.sort_by(|a, b| a.title.cmp(&b.title));
+
+    let mut tags_of: HashMap<&str, Vec<String>> = HashMap::new();
+    for (note_id, tag_id) in &note_tags {
+        if let Some(tag) = tags.get(tag_id) {
+            tags_of.entry(note_id).or_default().push(tag.clone());
+        }
+    }
+
+    let mut importer = Importer {
+        vault,
+        root,
+        notes: notes
+            .iter()
+            .map(|note| (note.prop("id").to_owned(), uuid::Uuid::new_v4().to_string()))
+            .collect(),
+        resources,
+        attachments: HashMap::new(),
+        report: ImportReport {
+            failed,
+            ..Default::default()
+        },
+    };
+
+    let total = notes.len() as u64;
+    for (i, note) in notes.iter().enumerate() {
+        let folder = folder_of(&folders, note.prop("parent_id"));
+        let tags = tags_of.get(note.prop("id")).cloned().unwrap_or_default();
+        let title = match note.title.as_str() {
+            "" => "Untitled".to_owned(),
+            title => title.to_owned(),
+        };
+        match importer.import_note(note, &title, folder, &tags) {
+            Ok(()) => progress(ImportProgress {
+                source: SOURCE,
+                current: i as u64 + 1,
+                total,
+                title: Some(title),
+            }),
+            Err(err) => importer.report.failed.push(format!("{title}: {err}")),
+        }
+    }
+
+    if importer.report.notes > 0 {
+        vault.autocommit("Import from Joplin")?;
+    }
+    Ok(importer.report)
+}
+
+impl Importer<'_> {
+    fn import_note(
+        &mut self,
+        note: &Item,
+        title: &str,
+        folder: String,
+        tags: &[String],
+    ) -> Result<()> {
+        // Markup language 2 is a note clipped or written as HTML.
+        let markdown = if note.prop("markup_language") == "2" {
+            HtmlToMarkdown::builder()
+                .skip_tags(vec!["head", "style", "script"])
+                .build()
+                .convert(&note.body)?
+        } else {
+            note.body.clone()
+        };
+        let body = rewrite_prose(&markdown, |text| {
+            rewrite_ids(text, |id| self.resolve(&folder, id))
+        });
+
+        let mut front_matter = FrontMatter::default();
+        front_matter.set_tags(tags);
+        for key in ["latitude", "longitude", "altitude"] {
+            // Joplin writes zeros when the location is unknown.
+            let Some(value) = note.prop(key).parse::<f64>().ok().filter(|v| *v != 0.0) else {
+                continue;
+            };
+            front_matter
+                .fields
+                .insert(Value::String(key.into()), Value::Number(value.into()));
+        }
+
+        let created_at = rfc3339_millis(note.prop("user_created_time"))
+            .or_else(|| rfc3339_millis(note.prop("created_time")))
+            .unwrap_or_else(now_millis);
+        let updated_at = rfc3339_millis(note.prop("user_updated_time"))
+            .or_else(|| rfc3339_millis(note.prop("updated_time")))
+            .unwrap_or(created_at);
+        self.vault.import_note(&Note {
+            id: self.notes[note.prop("id")].clone(),
+            title: title.to_owned(),
+            body: format!("{}{body}", front_matter.render()),
+            folder,
+            created_at,
+            updated_at,
+        })?;
+        self.report.notes += 1;
+        Ok(())
+    }
+
+    /// The new target for a `:/<id>` link from a note in `folder`: a note
+    /// link, or the resource stored as an attachment.
+    fn resolve(&mut self, folder: &str, id: &str) -> Option<String> {
+        if let Some(note) = self.notes.get(id) {
+            return Some(links::note_url(note, None));
+        }
+        if let Some(stored) = self.attachments.get(id) {
+            return Some(NoteFiles::link_from(folder, stored));
+        }
+        let resource = self.resources.get(id)?;
+        let ext = resource.prop("file_extension");
+        let file = if ext.is_empty() {
+            id.to_owned()
+        } else {
+            format!("{id}.{ext}")
+        };
+        let content = fs::read(self.root.join("resources").join(file)).ok()?;
+        let name = match (resource.prop("filename"), resource.title.as_str()) {
+            ("", "") => format!("{id}.{ext}"),
+            ("", title) => title.to_owned(),
+            (name, _) => name.to_owned(),
+        };
+        let name = if Path::new(&name).extension().is_none() && !ext.is_empty() {
+            format!("{name}.{ext}")
+        } else {
+            name
+        };
+        let stored = self.vault.files.save_attachment(&name, &content).ok()?;
+        self.report.attachments += 1;
+        self.attachments.insert(id.to_owned(), stored.clone());
+        Some(NoteFiles::link_from(folder, &stored))
+    }
+}
+
+/// Replaces every `:/<id>` in `text` for which `resolve` has a target,
+/// whether in a Markdown link or an HTML attribute.
+fn rewrite_ids(text: &str, mut resolve: impl FnMut(&str) -> Option<String>) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find(":/") {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let id = after.get(..ID_LEN).filter(|id| {
+            id.bytes().all(|b| b.is_ascii_hexdigit())
+                && !after[ID_LEN..].starts_with(|c: char| c.is_ascii_alphanumeric())
+        });
+        match id.and_then(&mut resolve) {
+            Some(target) => {
+                out.push_str(&target);
+                rest = &after[ID_LEN..];
+            }
+            None => {
+                out.push_str(":/");
+                rest = after;
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Path of notebook `id`, its parents' titles first. Cycles and dangling
+/// parents end the walk.
+fn folder_of<'a>(folders: &'a HashMap<String, Item>, mut id: &'a str) -> String {
+    let mut parts = Vec::new();
+    while let Some(folder) = folders.get(id) {
+        if parts.len() > folders.len() {
+            break;
+        }
+        parts.push(folder.title.replace('/', "-"));
+        id = folder.prop("parent_id");
+    }
+    parts.reverse();
+    parts.join("/")
+}
+
+fn property(line: &str) -> Option<(&str, &str)> {
+    let (key, value) = line.split_once(':')?;
+    let valid = !key.is_empty() && key.bytes().all(|b| b.is_ascii_lowercase() || b == b'_');
+    valid.then(|| (key, value.strip_prefix(' ').unwrap_or(value)))
+}
@@ -0,0 +1,24 @@
+use std::fs;
+use std::path::Path;
+
+use crate::error::{Error, Result};
+use crate::storage::Note;
+use crate::vault::Vault;
+
+/// Copies a single Markdown file from outside the vault into a new note in
+/// `folder`, titled after the file name. Files already mirrored in the
+/// vault are refused; they have a note.
+pub fn import(vault: &Vault, path: &Path, folder: &str) -> Result<Note> {
+    if vault.files.relative(path).is_some() {
+        return Err(Error::InvalidInput(format!(
+            "{} is already in the vault",
+            path.display()
+        )));
+    }
+    let body = fs::read_to_string(path)?;
+    let title = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "Untitled".into());
+    vault.create_note(&title, &body, folder)
+}
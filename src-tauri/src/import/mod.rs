@@ -0,0 +1,75 @@
+#[cfg(target_os = "macos")]
+pub mod apple_notes;
+pub mod bookmarks;
+pub mod commands;
+pub mod enex;
+pub mod joplin;
+pub mod markdown;
+pub mod notion;
+pub mod obsidian;
+pub mod opml;
+pub mod pandoc;
+mod resume;
+
+pub use resume::Resumable;
+
+use chrono::{DateTime, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+
+/// Event carrying [`ImportProgress`] payloads while an import runs.
+pub const PROGRESS_EVENT: &str = "import-progress";
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportProgress {
+    pub source: &'static str,
+    /// How far the import got, in a unit that depends on the source (bytes
+    /// of an export file, or items).
+    pub current: u64,
+    pub total: u64,
+    /// Title of the note just imported.
+    pub title: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportReport {
+    pub notes: usize,
+    pub attachments: usize,
+    /// Items that were skipped, each with the reason.
+    pub failed: Vec<String>,
+}
+
+/// `value` read as an RFC 3339 timestamp, in milliseconds.
+fn rfc3339_millis(value: &str) -> Option<i64> {
+    DateTime::parse_from_rfc3339(value.trim())
+        .ok()
+        .map(|date| date.timestamp_millis())
+}
+
+/// `value` read with `format` as a time in UTC, in milliseconds.
+fn utc_millis(value: &str, format: &str) -> Option<i64> {
+    NaiveDateTime::parse_from_str(value.trim(), format)
+        .ok()
+        .map(|date| date.and_utc().timestamp_millis())
+}
+
+/// Rewrites the targets of the Markdown links in `text` for which
+/// `resolve` has a replacement.
+fn rewrite_links(text: &str, mut resolve: impl FnMut(&str) -> Option<String>) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("](") {
+        let after = &rest[start + 2..];
+        let Some(end) = after.find(')') else {
+            break;
+        };
+        let target = &after[..end];
+        out.push_str(&rest[..start + 2]);
+        out.push_str(&resolve(target).unwrap_or_else(|| target.to_owned()));
+        out.push(')');
+        rest = &after[end + 1..];
+    }
+    out.push_str(rest);
+    out
+}
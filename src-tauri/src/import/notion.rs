@@ -0,0 +1,436 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+
+use htmd::HtmlToMarkdown;
+use percent_encoding::percent_decode_str;
+use serde_yaml_ng::Value;
+use zip::ZipArchive;
+
+use super::{rewrite_links, utc_millis, ImportProgress, ImportReport, Resumable};
+use crate::error::{Error, Result};
+use crate::files::{NoteFiles, Staging};
+use crate::links;
+use crate::markdown::rewrite_prose;
+use crate::metadata::FrontMatter;
+use crate::storage::{now_millis, Note};
+use crate::vault::Vault;
+
+pub const SOURCE: &str = "notion";
+/// Folder the whole export goes into.
+const TOP_FOLDER: &str = "Notion";
+/// How Notion formats created and edited times in page properties.
+const DATE_FORMAT: &str = "%B %d, %Y %I:%M %p";
+
+struct Importer<'a> {
+    vault: &'a Vault,
+    root: PathBuf,
+    /// Export path of each page to the id its note gets.
+    pages: HashMap<String, String>,
+    /// Database properties of row pages, keyed by export path.
+    properties: HashMap<String, Vec<(String, String)>>,
+    /// Export path of each attachment to where it was stored.
+    attachments: HashMap<String, String>,
+    report: ImportReport,
+}
+
+/// Imports a Notion export zip, in HTML or Markdown & CSV format. Pages keep
+/// their hierarchy as folders, databases become a note with a table plus
+/// front matter on each row page, and links between pages and to attached
+/// files are rewritten. `resume` keeps its place page by page.
+pub fn import(
+    vault: &Vault,
+    path: &Path,
+    resume: &mut Resumable,
+    progress: &mut dyn FnMut(ImportProgress),
+) -> Result<ImportReport> {
+    if vault.storage.is_locked() {
+        return Err(Error::VaultLocked);
+    }
+    let staging = Staging::new("notion")?;
+    extract(path, &staging.0)?;
+
+    let mut pages = Vec::new();
+    let mut databases = Vec::new();
+    for rel in walk(&staging.0)? {
+        let lower = rel.to_lowercase();
+        if lower.ends_with(".md") || lower.ends_with(".html") {
+            pages.push(rel);
+        } else if lower.ends_with(".csv") {
+            databases.push(rel);
+        }
+    }
+    pages.sort();
+    // Notion writes both the current view and `_all` rows; prefer the latter.
+    let all_rows: HashSet<String> = databases
+        .iter()
+        .filter(|rel| rel.ends_with("_all.csv"))
+        .cloned()
+        .collect();
+    databases.retain(|rel| {
+        let all = format!("{}_all.csv", rel.trim_end_matches(".csv"));
+        rel.ends_with("_all.csv") || !all_rows.contains(&all)
+    });
+
+    let mut importer = Importer {
+        vault,
+        root: staging.0.clone(),
+        pages: pages
+            .iter()
+            .map(|rel| (rel.clone(), resume.note_id(rel)))
+            .collect(),
+        properties: HashMap::new(),
+        attachments: HashMap::new(),
+        report: resume.take_report(),
+    };
+
+    let mut tables = Vec::new();
+    for rel in &databases {
+        match importer.read_database(rel) {
+            Ok(table) => tables.push(table),
+            Err(err) => importer.report.failed.push(format!("{rel}: {err}")),
+        }
+    }
+
+    let total = (pages.len() + tables.len()) as u64;
+    for (i, rel) in pages.iter().enumerate() {
+        let current = i as u64 + 1;
+        if resume.is_done(current - 1, &importer.pages[rel], &mut importer.report)? {
+            continue;
+        }
+        match importer.import_page(rel) {
+            Ok(title) => progress(ImportProgress {
+                source: SOURCE,
+                current,
+                total,
+                title: Some(title),
+            }),
+            Err(err) => importer.report.failed.push(format!("{rel}: {err}")),
+        }
+        resume.checkpoint(current, current, total, &importer.report)?;
+    }
+    for (i, table) in tables.into_iter().enumerate() {
+        let current = (pages.len() + i) as u64 + 1;
+        let id = resume.note_id(&table.rel);
+        if resume.is_done(current - 1, &id, &mut importer.report)? {
+            continue;
+        }
+        let title = table.title.clone();
+        match importer.import_table(id, table) {
+            Ok(()) => progress(ImportProgress {
+                source: SOURCE,
+                current,
+                total,
+                title: Some(title),
+            }),
+            Err(err) => importer.report.failed.push(format!("{title}: {err}")),
+        }
+        resume.checkpoint(current, current, total, &importer.report)?;
+    }
+
+    // A resumed import read the databases again and failed on the same ones.
+    let mut seen = HashSet::new();
+    importer
+        .report
+        .failed
+        .retain(|failed| seen.insert(failed.clone()));
+    if importer.report.notes > 0 {
+        vault.autocommit("Import from Notion")?;
+    }
+    Ok(importer.report)
+}
+
+/// A database export: its column names and rows.
+struct Table {
+    /// Export path of the CSV.
+    rel: String,
+    title: String,
+    headers: Vec<String>,
+    rows: Vec<Vec<String>>,
+}
+
+impl Importer<'_> {
+    fn read_database(&mut self, rel: &str) -> Result<Table> {
+        let csv_error =
+            |err: csv::Error| Error::InvalidInput(format!("bad database export: {err}"));
+        let mut reader = csv::ReaderBuilder::new()
+            .flexible(true)
+            .from_path(self.root.join(rel))
+            .map_err(csv_error)?;
+        let headers: Vec<String> = reader
+            .headers()
+            .map_err(csv_error)?
+            .iter()
+            // Excel-friendly exports start with a byte order mark.
+            .map(|header| header.trim_start_matches('\u{feff}').to_owned())
+            .collect();
+        let rows = reader
+            .records()
+            .map(|record| {
+                Ok(record
+                    .map_err(csv_error)?
+                    .iter()
+                    .map(str::to_owned)
+                    .collect())
+            })
+            .collect::<Result<Vec<Vec<String>>>>()?;
+
+        // Row pages live in a folder named like the CSV.
+        let (dir, file) = split_path(rel);
+        let stem = file.trim_end_matches(".csv").trim_end_matches("_all");
+        let rows_dir = join(dir, stem);
+        for row in &rows {
+            let Some(name) = row.first() else {
+                continue;
+            };
+            if let Some(page) = self.row_page(&rows_dir, name) {
+                let props = headers
+                    .iter()
+                    .zip(row)
+                    .skip(1)
+                    .filter(|(_, value)| !value.is_empty())
+                    .map(|(header, value)| (header.clone(), value.clone()))
+                    .collect();
+                self.properties.insert(page, props);
+            }
+        }
+        Ok(Table {
+            rel: rel.to_owned(),
+            title: strip_id(stem).to_owned(),
+            headers,
+            rows,
+        })
+    }
+
+    /// The page for the database row titled `name` in `dir`.
+    fn row_page(&self, dir: &str, name: &str) -> Option<String> {
+        let prefix = format!("{dir}/");
+        self.pages
+            .keys()
+            .filter(|rel| {
+                rel.strip_prefix(&prefix)
+                    .is_some_and(|file| !file.contains('/'))
+            })
+            .find(|rel| {
+                let (_, file) = split_path(rel);
+                let stem = file.rsplit_once('.').map_or(file, |(stem, _)| stem);
+                strip_id(stem) == name.trim()
+            })
+            .cloned()
+    }
+
+    fn import_page(&mut self, rel: &str) -> Result<String> {
+        let (dir, file) = split_path(rel);
+        let (stem, ext) = file.rsplit_once('.').unwrap_or((file, ""));
+        let title = match strip_id(stem).trim() {
+            "" => "Untitled".to_owned(),
+            title => title.to_owned(),
+        };
+        let folder = folder_of(dir);
+
+        let content = fs::read_to_string(self.root.join(rel))?;
+        let markdown = if ext.eq_ignore_ascii_case("html") {
+            HtmlToMarkdown::builder()
+                .skip_tags(vec!["head", "style", "script"])
+                .build()
+                .convert(&content)?
+        } else {
+            content
+        };
+        let markdown = drop_title_heading(&markdown, &title);
+        let body = rewrite_prose(markdown, |text| {
+            rewrite_links(text, |target| self.resolve(dir, &folder, target))
+        });
+
+        let mut front_matter = FrontMatter::default();
+        let mut created_at = None;
+        let mut updated_at = None;
+        for (name, value) in self.properties.get(rel).into_iter().flatten() {
+            match name.to_lowercase().as_str() {
+                "tags" => {
+                    let tags: Vec<String> = value.split(',').map(|t| t.trim().to_owned()).collect();
+                    front_matter.set_tags(&tags);
+                }
+                "created" | "created time" => created_at = utc_millis(value, DATE_FORMAT),
+                "last edited time" | "updated" => updated_at = utc_millis(value, DATE_FORMAT),
+                _ => {
+                    front_matter
+                        .fields
+                        .insert(Value::String(name.clone()), Value::String(value.clone()));
+                }
+            }
+        }
+        let created_at = created_at.unwrap_or_else(now_millis);
+
+        self.vault.import_note(&Note {
+            id: self.pages[rel].clone(),
+            title: title.clone(),
+            body: format!("{}{body}", front_matter.render()),
+            folder,
+            created_at,
+            updated_at: updated_at.unwrap_or(created_at),
+        })?;
+        self.report.notes += 1;
+        Ok(title)
+    }
+
+    /// Writes a database as a note holding a Markdown table, its first
+    /// column linking to the row pages.
+    fn import_table(&mut self, id: String, table: Table) -> Result<()> {
+        let (dir, file) = split_path(&table.rel);
+        let rows_dir = join(dir, file.trim_end_matches(".csv").trim_end_matches("_all"));
+        let cell = |value: &str| value.replace('|', "\\|").replace('\n', "<br>");
+
+        let mut body = String::new();
+        let columns = table.headers.len().max(1);
+        body.push_str(&format!(
+            "| {} |\n",
+            table
+                .headers
+                .iter()
+                .map(|h| cell(h))
+                .collect::<Vec<_>>()
+                .join(" | ")
+        ));
+        body.push_str(&format!("|{}\n", " --- |".repeat(columns)));
+        for row in &table.rows {
+            let cells: Vec<String> = (0..columns)
+                .map(|i| {
+                    let value = row.get(i).map(String::as_str).unwrap_or_default();
+                    let page = (i == 0).then(|| self.row_page(&rows_dir, value)).flatten();
+                    match page {
+                        Some(page) => format!(
+                            "[{}]({})",
+                            cell(value),
+                            links::note_url(&self.pages[&page], None)
+                        ),
+                        None => cell(value),
+                    }
+                })
+                .collect();
+            body.push_str(&format!("| {} |\n", cells.join(" | ")));
+        }
+
+        let now = now_millis();
+        self.vault.import_note(&Note {
+            id,
+            title: table.title,
+            body,
+            folder: folder_of(dir),
+            created_at: now,
+            updated_at: now,
+        })?;
+        self.report.notes += 1;
+        Ok(())
+    }
+
+    /// The new target for a relative link found in a page in `dir`: a note
+    /// link for pages, or a stored attachment for other files.
+    fn resolve(&mut self, dir: &str, folder: &str, target: &str) -> Option<String> {
+        if target.contains("://") || target.starts_with(['#', '/']) || target.starts_with("mailto:")
+        {
+            return None;
+        }
+        let path = target.split(['#', '?']).next().unwrap_or(target);
+        let decoded = percent_decode_str(path).decode_utf8().ok()?;
+        let rel = normalize(&join(dir, &decoded))?;
+        if let Some(id) = self.pages.get(&rel) {
+            return Some(links::note_url(id, None));
+        }
+        if let Some(stored) = self.attachments.get(&rel) {
+            return Some(NoteFiles::link_from(folder, stored));
+        }
+        let content = fs::read(self.root.join(&rel)).ok()?;
+        let stored = self.vault.files.save_attachment(&rel, &content).ok()?;
+        self.report.attachments += 1;
+        self.attachments.insert(rel, stored.clone());
+        Some(NoteFiles::link_from(folder, &stored))
+    }
+}
+
+/// Unpacks the export into `dest`. Large workspaces are exported as a zip of
+/// zipped parts, which are unpacked in place.
+fn extract(path: &Path, dest: &Path) -> Result<()> {
+    ZipArchive::new(File::open(path)?)?.extract(dest)?;
+    for rel in walk(dest)? {
+        if rel.to_lowercase().ends_with(".zip") {
+            let part = dest.join(&rel);
+            ZipArchive::new(File::open(&part)?)?.extract(dest)?;
+            fs::remove_file(part)?;
+        }
+    }
+    Ok(())
+}
+
+fn walk(root: &Path) -> Result<Vec<String>> {
+    let mut files = Vec::new();
+    let mut pending = vec![root.to_owned()];
+    while let Some(dir) = pending.pop() {
+        for entry in fs::read_dir(&dir)? {
+            let entry = entry?;
+            let path = entry.path();
+            if entry.file_type()?.is_dir() {
+                pending.push(path);
+            } else if let Ok(rel) = path.strip_prefix(root) {
+                files.push(rel.to_string_lossy().replace('\\', "/"));
+            }
+        }
+    }
+    Ok(files)
+}
+
+/// Notion repeats the page title as the first heading.
+fn drop_title_heading<'a>(markdown: &'a str, title: &str) -> &'a str {
+    let trimmed = markdown.trim_start();
+    match trimmed.split_once('\n') {
+        Some((first, rest)) if first.trim() == format!("# {title}") => {
+            rest.trim_start_matches(['\r', '\n'])
+        }
+        None if trimmed.trim() == format!("# {title}") => "",
+        _ => markdown,
+    }
+}
+
+/// Folder for a page in export directory `dir`: each parent page's title
+/// below [`TOP_FOLDER`].
+fn folder_of(dir: &str) -> String {
+    std::iter::once(TOP_FOLDER)
+        .chain(dir.split('/').filter(|part| !part.is_empty()).map(strip_id))
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Drops the 32 hex digit id Notion appends to page and folder names.
+fn strip_id(name: &str) -> &str {
+    match name.rsplit_once(' ') {
+        Some((title, id)) if id.len() == 32 && id.chars().all(|c| c.is_ascii_hexdigit()) => title,
+        _ => name,
+    }
+}
+
+fn split_path(rel: &str) -> (&str, &str) {
+    rel.rsplit_once('/').unwrap_or(("", rel))
+}
+
+fn join(dir: &str, name: &str) -> String {
+    if dir.is_empty() {
+        name.to_owned()
+    } else {
+        format!("{dir}/{name}")
+    }
+}
+
+/// Resolves `.` and `..` components, or `None` if the path leaves the root.
+fn normalize(path: &str) -> Option<String> {
+    let mut parts = Vec::new();
+    for part in path.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop()?;
+            }
+            part => parts.push(part),
+        }
+    }
+    Some(parts.join("/"))
+}
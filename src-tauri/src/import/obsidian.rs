@@ -0,0 +1,384 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+use serde::Serialize;
+
+use super::ImportProgress;
+use crate::error::{Error, Result};
+use crate::files::NoteFiles;
+use crate::links;
+use crate::markdown::rewrite_prose;
+use crate::metadata::FrontMatter;
+use crate::storage::{now_millis, Note};
+use crate::vault::Vault;
+
+const SOURCE: &str = "obsidian";
+const IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "gif", "webp", "svg", "bmp", "avif"];
+
+/// A note an Obsidian import creates, or would create in a dry run.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlannedNote {
+    /// Path of the file inside the Obsidian vault.
+    pub source: String,
+    pub title: String,
+    pub folder: String,
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ObsidianReport {
+    /// False for a dry run, which only reports what an import would do.
+    pub applied: bool,
+    pub notes: Vec<PlannedNote>,
+    /// Distinct files embedded in or linked from notes.
+    pub attachments: usize,
+    /// Wiki links whose target is not in the vault, as written.
+    pub unresolved: Vec<String>,
+    /// Files that were skipped, each with the reason.
+    pub failed: Vec<String>,
+}
+
+/// Every file of the Obsidian vault, indexed the ways wiki links name them.
+struct SourceIndex {
+    root: PathBuf,
+    /// Markdown files by vault path, each with the id its note will get.
+    notes: Vec<(String, String)>,
+    /// Lowercased vault path without `.md` to an index into `notes`.
+    note_paths: HashMap<String, usize>,
+    /// Lowercased file stem to indexes into `notes`.
+    note_names: HashMap<String, Vec<usize>>,
+    /// Lowercased vault path and file name of every other file to its path.
+    file_paths: HashMap<String, String>,
+    file_names: HashMap<String, String>,
+}
+
+impl SourceIndex {
+    fn scan(root: &Path) -> Result<Self> {
+        let mut index = Self {
+            root: root.to_owned(),
+            notes: Vec::new(),
+            note_paths: HashMap::new(),
+            note_names: HashMap::new(),
+            file_paths: HashMap::new(),
+            file_names: HashMap::new(),
+        };
+        let mut pending = vec![root.to_owned()];
+        while let Some(dir) = pending.pop() {
+            for entry in fs::read_dir(&dir)? {
+                let entry = entry?;
+                // Skips .obsidian, .trash and the like.
+                if entry.file_name().to_string_lossy().starts_with('.') {
+                    continue;
+                }
+                let path = entry.path();
+                if entry.file_type()?.is_dir() {
+                    pending.push(path);
+                    continue;
+                }
+                let Ok(rel) = path.strip_prefix(root) else {
+                    continue;
+                };
+                let rel = rel.to_string_lossy().replace('\\', "/");
+                index.add(rel);
+            }
+        }
+        index.notes.sort();
+        index.reindex();
+        Ok(index)
+    }
+
+    fn add(&mut self, rel: String) {
+        if rel.to_lowercase().ends_with(".md") {
+            self.notes.push((rel, uuid::Uuid::new_v4().to_string()));
+            return;
+        }
+        let name = rel.rsplit('/').next().unwrap_or(&rel).to_lowercase();
+        self.file_names.entry(name).or_insert_with(|| rel.clone());
+        self.file_paths.insert(rel.to_lowercase(), rel);
+    }
+
+    fn reindex(&mut self) {
+        for (i, (rel, _)) in self.notes.iter().enumerate() {
+            let key = rel[..rel.len() - ".md".len()].to_lowercase();
+            let name = key.rsplit('/').next().unwrap_or(&key).to_owned();
+            self.note_paths.insert(key, i);
+            self.note_names.entry(name).or_default().push(i);
+        }
+    }
+
+    /// The note a link target names, the way Obsidian resolves it: by full
+    /// path, else by file name, preferring a match in `folder`.
+    fn note(&self, target: &str, folder: &str) -> Option<&(String, String)> {
+        let key = target.trim().trim_start_matches("./").to_lowercase();
+        let key = key.strip_suffix(".md").unwrap_or(&key);
+        if let Some(&i) = self.note_paths.get(key) {
+            return Some(&self.notes[i]);
+        }
+        let name = key.rsplit('/').next().unwrap_or(key);
+        let candidates = self.note_names.get(name)?;
+        let folder = folder.to_lowercase();
+        let local = candidates.iter().find(|&&i| {
+            let rel = self.notes[i].0.to_lowercase();
+            rel.rsplit_once('/').map_or("", |(dir, _)| dir) == folder
+        });
+        local.or(candidates.first()).map(|&i| &self.notes[i])
+    }
+
+    fn file(&self, target: &str) -> Option<&String> {
+        let key = target.trim().trim_start_matches("./").to_lowercase();
+        self.file_paths.get(&key).or_else(|| {
+            let name = key.rsplit('/').next().unwrap_or(&key);
+            self.file_names.get(name)
+        })
+    }
+}
+
+struct Importer<'a> {
+    vault: &'a Vault,
+    index: SourceIndex,
+    /// Folder the whole vault goes into, named after its directory.
+    top: String,
+    dry_run: bool,
+    /// Source path of each attachment to where it was stored.
+    attachments: HashMap<String, String>,
+    report: ObsidianReport,
+}
+
+/// Imports the Obsidian vault at `root` into a folder named after it,
+/// keeping its folder structure and front matter and rewriting wiki links
+/// and embeds into note links and attachment links. With `dry_run`, nothing
+/// is written and the report describes what would be imported.
+pub fn import(
+    vault: &Vault,
+    root: &Path,
+    dry_run: bool,
+    progress: &mut dyn FnMut(ImportProgress),
+) -> Result<ObsidianReport> {
+    if !root.is_dir() {
+        return Err(Error::InvalidInput(format!(
+            "{} is not a directory",
+            root.display()
+        )));
+    }
+    if !dry_run && vault.storage.is_locked() {
+        return Err(Error::VaultLocked);
+    }
+    let top = root
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "Obsidian".to_owned());
+    let mut importer = Importer {
+        vault,
+        index: SourceIndex::scan(root)?,
+        top,
+        dry_run,
+        attachments: HashMap::new(),
+        report: ObsidianReport {
+            applied: !dry_run,
+            ..Default::default()
+        },
+    };
+
+    let notes = importer.index.notes.clone();
+    let total = notes.len() as u64;
+    for (i, (rel, id)) in notes.iter().enumerate() {
+        match importer.import_note(rel, id) {
+            Ok(title) if !dry_run => progress(ImportProgress {
+                source: SOURCE,
+                current: i as u64 + 1,
+                total,
+                title: Some(title),
+            }),
+            Ok(_) => {}
+            Err(err) => importer.report.failed.push(format!("{rel}: {err}")),
+        }
+    }
+
+    if !dry_run && !importer.report.notes.is_empty() {
+        vault.autocommit(&format!("Import \"{}\" from Obsidian", importer.top))?;
+    }
+    Ok(importer.report)
+}
+
+impl Importer<'_> {
+    fn import_note(&mut self, rel: &str, id: &str) -> Result<String> {
+        let path = self.index.root.join(rel);
+        let content = fs::read_to_string(&path)?;
+        let (front_matter, markdown) = FrontMatter::split(&content);
+        let mut front_matter = front_matter.unwrap_or_default();
+
+        let (dir, file) = rel.rsplit_once('/').unwrap_or(("", rel));
+        let stem = &file[..file.len() - ".md".len()];
+        let title = front_matter.str("title").unwrap_or(stem).trim().to_owned();
+        let folder = match dir {
+            "" => self.top.clone(),
+            dir => format!("{}/{dir}", self.top),
+        };
+        let tags = front_matter.tags();
+        front_matter.set_tags(&tags);
+
+        // Wiki links in code are meant literally.
+        let body = rewrite_prose(markdown, |text| {
+            self.rewrite_segment(text, dir, &folder, id)
+        });
+        let modified = fs::metadata(&path)?;
+        let created_at = ["created", "date"]
+            .iter()
+            .find_map(|key| front_matter.str(key).and_then(parse_date))
+            .or_else(|| modified.created().ok().map(millis))
+            .unwrap_or_else(now_millis);
+        let updated_at = ["updated", "modified"]
+            .iter()
+            .find_map(|key| front_matter.str(key).and_then(parse_date))
+            .or_else(|| modified.modified().ok().map(millis))
+            .unwrap_or(created_at);
+
+        if !self.dry_run {
+            self.vault.import_note(&Note {
+                id: id.to_owned(),
+                title: title.clone(),
+                body: format!("{}{body}", front_matter.render()),
+                folder: folder.clone(),
+                created_at,
+                updated_at,
+            })?;
+        }
+        self.report.notes.push(PlannedNote {
+            source: rel.to_owned(),
+            title: title.clone(),
+            folder,
+            tags,
+        });
+        Ok(title)
+    }
+
+    fn rewrite_segment(&mut self, text: &str, dir: &str, folder: &str, id: &str) -> String {
+        let mut out = String::with_capacity(text.len());
+        let mut rest = text;
+        while let Some(start) = rest.find("[[") {
+            let Some(len) = rest[start + 2..].find("]]") else {
+                break;
+            };
+            let inner = &rest[start + 2..start + 2 + len];
+            let embed = rest[..start].ends_with('!');
+            let prefix = &rest[..start - usize::from(embed)];
+            out.push_str(prefix);
+            match self.link(inner, embed, dir, folder, id) {
+                Some(link) => out.push_str(&link),
+                None => {
+                    self.report.unresolved.push(format!("[[{inner}]]"));
+                    out.push_str(&rest[prefix.len()..start + 2 + len + 2]);
+                }
+            }
+            rest = &rest[start + 2 + len + 2..];
+        }
+        out.push_str(rest);
+        out
+    }
+
+    /// The Markdown for one `[[target#heading|alias]]`, or `None` if the
+    /// target does not exist.
+    fn link(
+        &mut self,
+        inner: &str,
+        embed: bool,
+        dir: &str,
+        folder: &str,
+        id: &str,
+    ) -> Option<String> {
+        let (target, alias) = match inner.split_once('|') {
+            Some((target, alias)) => (target, Some(alias.trim())),
+            None => (inner, None),
+        };
+        let (name, heading) = match target.split_once('#') {
+            Some((name, heading)) => (name.trim(), Some(heading.trim())),
+            None => (target.trim(), None),
+        };
+        let bang = if embed { "!" } else { "" };
+
+        let is_note = Path::new(name)
+            .extension()
+            .is_none_or(|ext| ext.eq_ignore_ascii_case("md"));
+        if is_note {
+            let target_id = match name {
+                "" => id,
+                name => &self.index.note(name, dir)?.1,
+            };
+            let label = alias.unwrap_or(if name.is_empty() {
+                heading.unwrap_or_default()
+            } else {
+                target.trim()
+            });
+            let url = links::note_url(target_id, heading);
+            return Some(format!("{bang}[{}]({url})", escape_label(label)));
+        }
+
+        let source = self.index.file(name)?.clone();
+        let stored = self.attachment(&source)?;
+        let file_name = source.rsplit('/').next().unwrap_or(&source);
+        let label = escape_label(alias.unwrap_or(file_name));
+        let url = NoteFiles::link_from(folder, &stored);
+        let image = Path::new(file_name)
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| IMAGE_EXTENSIONS.contains(&ext.to_lowercase().as_str()));
+        Some(if embed && image {
+            format!("![{label}]({url})")
+        } else {
+            format!("[{label}]({url})")
+        })
+    }
+
+    /// Copies a source file into the attachment store once, returning its
+    /// stored path. Dry runs only count it.
+    fn attachment(&mut self, source: &str) -> Option<String> {
+        if let Some(stored) = self.attachments.get(source) {
+            return Some(stored.clone());
+        }
+        let stored = if self.dry_run {
+            format!("attachments/{source}")
+        } else {
+            let content = fs::read(self.index.root.join(source)).ok()?;
+            self.vault.files.save_attachment(source, &content).ok()?
+        };
+        self.report.attachments += 1;
+        self.attachments.insert(source.to_owned(), stored.clone());
+        Some(stored)
+    }
+}
+
+fn escape_label(label: &str) -> String {
+    label.replace('[', "\\[").replace(']', "\\]")
+}
+
+/// Reads the date formats Obsidian users commonly put in front matter.
+fn parse_date(value: &str) -> Option<i64> {
+    let value = value.trim();
+    if let Ok(date) = DateTime::parse_from_rfc3339(value) {
+        return Some(date.timestamp_millis());
+    }
+    [
+        "%Y-%m-%dT%H:%M:%S",
+        "%Y-%m-%d %H:%M:%S",
+        "%Y-%m-%dT%H:%M",
+        "%Y-%m-%d %H:%M",
+    ]
+    .iter()
+    .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+    .or_else(|| {
+        NaiveDate::parse_from_str(value, "%Y-%m-%d")
+            .ok()
+            .and_then(|date| date.and_hms_opt(0, 0, 0))
+    })
+    .map(|date| date.and_utc().timestamp_millis())
+}
+
+fn millis(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_millis() as i64)
+}
@@ -0,0 +1,242 @@
+//! OPML outlines from outliners and mind-mapping tools. An outline either
+//! becomes one note of nested lists, or, as written by our own OPML export,
+//! a tree of folders and notes.
+
+use std::fs;
+use std::path::Path;
+
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::{Reader, XmlVersion};
+use serde::Deserialize;
+
+use super::{ImportProgress, ImportReport};
+use crate::error::{Error, Result};
+use crate::vault::Vault;
+use crate::xml;
+
+const SOURCE: &str = "opml";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum OpmlMode {
+    /// The whole outline as one note, its levels as nested lists.
+    Outline,
+    /// Outlines with children as folders and the rest as notes, with
+    /// outlines marked `type="note"` as notes whatever is below them.
+    Folders,
+}
+
+#[derive(Default)]
+struct Outline {
+    text: String,
+    /// The `_note` some outliners keep beside an outline's text.
+    note: Option<String>,
+    url: Option<String>,
+    is_note: bool,
+    children: Vec<Outline>,
+}
+
+/// Imports the OPML file at `path` into `folder`. Without a `mode`, files
+/// with notes marked as such are read as folders, and others as one
+/// outline.
+pub fn import(
+    vault: &Vault,
+    path: &Path,
+    folder: &str,
+    mode: Option<OpmlMode>,
+    progress: &mut dyn FnMut(ImportProgress),
+) -> Result<ImportReport> {
+    if vault.storage.is_locked() {
+        return Err(Error::VaultLocked);
+    }
+    let (title, outlines) = parse(&fs::read_to_string(path)?)?;
+    let title = title.unwrap_or_else(|| {
+        path.file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "Outline".into())
+    });
+    let folder = folder.trim_matches('/');
+    let mode = mode.unwrap_or(match outlines.iter().any(has_notes) {
+        true => OpmlMode::Folders,
+        false => OpmlMode::Outline,
+    });
+
+    let mut report = ImportReport::default();
+    match mode {
+        OpmlMode::Outline => {
+            let mut body = String::new();
+            list(&mut body, &outlines, 0);
+            vault.create_note(&title, &body, folder)?;
+            report.notes += 1;
+            progress(ImportProgress {
+                source: SOURCE,
+                current: 1,
+                total: 1,
+                title: Some(title),
+            });
+        }
+        OpmlMode::Folders => {
+            let total = outlines.iter().map(count_notes).sum();
+            let mut done = 0;
+            for outline in &outlines {
+                import_tree(vault, outline, folder, &mut report, &mut |title| {
+                    done += 1;
+                    progress(ImportProgress {
+                        source: SOURCE,
+                        current: done,
+                        total,
+                        title: Some(title),
+                    });
+                });
+            }
+        }
+    }
+    Ok(report)
+}
+
+fn import_tree(
+    vault: &Vault,
+    outline: &Outline,
+    folder: &str,
+    report: &mut ImportReport,
+    done: &mut dyn FnMut(String),
+) {
+    let name = match outline.text.trim() {
+        "" => "Untitled",
+        text => text,
+    };
+    if outline.is_note || outline.children.is_empty() {
+        let mut body = String::new();
+        if let Some(note) = &outline.note {
+            body.push_str(note.trim());
+            body.push_str("\n\n");
+        }
+        list(&mut body, &outline.children, 0);
+        match vault.create_note(name, &body, folder) {
+            Ok(_) => report.notes += 1,
+            Err(err) => report.failed.push(format!("{name}: {err}")),
+        }
+        done(name.to_owned());
+        return;
+    }
+    let name = name.replace('/', "-");
+    let sub = match folder.is_empty() {
+        true => name,
+        false => format!("{folder}/{name}"),
+    };
+    for child in &outline.children {
+        import_tree(vault, child, &sub, report, done);
+    }
+}
+
+/// Appends `outlines` as a Markdown list nested `depth` levels deep.
+fn list(out: &mut String, outlines: &[Outline], depth: usize) {
+    let indent = "  ".repeat(depth);
+    for outline in outlines {
+        let text = outline
+            .text
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ");
+        match &outline.url {
+            Some(url) => {
+                let text: String = text.chars().filter(|c| !matches!(c, '[' | ']')).collect();
+                out.push_str(&format!("{indent}- [{text}](<{url}>)\n"));
+            }
+            None => out.push_str(&format!("{indent}- {text}\n")),
+        }
+        if let Some(note) = outline
+            .note
+            .as_deref()
+            .map(str::trim)
+            .filter(|n| !n.is_empty())
+        {
+            for line in note.lines() {
+                out.push_str(&format!("{indent}  {line}\n"));
+            }
+        }
+        list(out, &outline.children, depth + 1);
+    }
+}
+
+fn has_notes(outline: &Outline) -> bool {
+    outline.is_note || outline.children.iter().any(has_notes)
+}
+
+fn count_notes(outline: &Outline) -> u64 {
+    match outline.is_note || outline.children.is_empty() {
+        true => 1,
+        false => outline.children.iter().map(count_notes).sum(),
+    }
+}
+
+/// The head's title and the body's outlines.
+fn parse(text: &str) -> Result<(Option<String>, Vec<Outline>)> {
+    let invalid =
+        |err: &dyn std::fmt::Display| Error::InvalidInput(format!("not a valid OPML file: {err}"));
+    let mut reader = Reader::from_str(text);
+    let mut title: Option<String> = None;
+    let mut in_title = false;
+    let mut open: Vec<Outline> = Vec::new();
+    let mut roots = Vec::new();
+    let mut seen_opml = false;
+    loop {
+        match reader.read_event().map_err(|err| invalid(&err))? {
+            Event::Start(tag) if tag.local_name().as_ref() == "outline" => {
+                open.push(outline_of(&tag).map_err(|err| invalid(&err))?);
+            }
+            Event::Empty(tag) if tag.local_name().as_ref() == "outline" => {
+                let outline = outline_of(&tag).map_err(|err| invalid(&err))?;
+                match open.last_mut() {
+                    Some(parent) => parent.children.push(outline),
+                    None => roots.push(outline),
+                }
+            }
+            Event::End(tag) if tag.local_name().as_ref() == "outline" => {
+                if let Some(outline) = open.pop() {
+                    match open.last_mut() {
+                        Some(parent) => parent.children.push(outline),
+                        None => roots.push(outline),
+                    }
+                }
+            }
+            Event::Start(tag) => match tag.local_name().as_ref() {
+                "opml" => seen_opml = true,
+                "title" if open.is_empty() => in_title = true,
+                _ => {}
+            },
+            Event::End(tag) if tag.local_name().as_ref() == "title" => in_title = false,
+            Event::Text(text) if in_title => {
+                title.get_or_insert_default().push_str(&text.into_inner());
+            }
+            Event::GeneralRef(reference) if in_title => {
+                if let Some(c) = xml::resolve_entity(&reference) {
+                    title.get_or_insert_default().push(c);
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    if !seen_opml {
+        return Err(Error::InvalidInput("not an OPML file".into()));
+    }
+    let title = title.map(|t| t.trim().to_owned()).filter(|t| !t.is_empty());
+    Ok((title, roots))
+}
+
+fn outline_of(tag: &BytesStart) -> quick_xml::Result<Outline> {
+    let mut outline = Outline::default();
+    for attr in tag.attributes().flatten() {
+        let value = attr.normalized_value(XmlVersion::Implicit1_0)?.into_owned();
+        match attr.key.local_name().as_ref() {
+            "text" => outline.text = value,
+            "title" if outline.text.is_empty() => outline.text = value,
+            "_note" => outline.note = Some(value),
+            "url" | "htmlUrl" | "xmlUrl" if outline.url.is_none() => outline.url = Some(value),
+            "type" => outline.is_note = value == "note",
+            _ => {}
+        }
+    }
+    Ok(outline)
+}
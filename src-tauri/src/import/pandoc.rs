@@ -0,0 +1,155 @@
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::Path;
+
+use tauri::AppHandle;
+use tauri_plugin_shell::ShellExt;
+use zip::ZipArchive;
+
+use super::rewrite_links;
+use crate::error::{Error, Result};
+use crate::files::NoteFiles;
+use crate::storage::Note;
+use crate::vault::Vault;
+
+/// Where pandoc is looked for after the bundled sidecar: `PATH`, then the
+/// usual Homebrew prefixes, which apps started from the Dock do not have
+/// on their `PATH`.
+const CANDIDATES: &[&str] = &[
+    "pandoc",
+    "/opt/homebrew/bin/pandoc",
+    "/usr/local/bin/pandoc",
+];
+/// Name of the converted file inside the staging directory.
+const OUTPUT: &str = "converted.md";
+
+/// Works out whether `path` is a Word, OpenDocument or HTML file, from its
+/// extension or failing that its contents, as the pandoc reader name.
+pub fn detect(path: &Path) -> Result<&'static str> {
+    let ext = path
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase());
+    match ext.as_deref() {
+        Some("docx") => return Ok("docx"),
+        Some("odt") => return Ok("odt"),
+        Some("html" | "htm" | "xhtml") => return Ok("html"),
+        _ => {}
+    }
+    let mut head = [0; 512];
+    let len = File::open(path)?.read(&mut head)?;
+    let head = &head[..len];
+    if head.starts_with(b"PK") {
+        let archive = ZipArchive::new(File::open(path)?)?;
+        let has = |name: &str| archive.index_for_name(name).is_some();
+        if has("word/document.xml") {
+            return Ok("docx");
+        }
+        if has("content.xml") && has("mimetype") {
+            return Ok("odt");
+        }
+    }
+    let text = String::from_utf8_lossy(head).to_lowercase();
+    if text
+        .trim_start_matches('\u{feff}')
+        .trim_start()
+        .starts_with('<')
+        && (text.contains("<html") || text.contains("<!doctype html"))
+    {
+        return Ok("html");
+    }
+    Err(Error::InvalidInput(format!(
+        "{} is not a Word, OpenDocument or HTML file",
+        path.display()
+    )))
+}
+
+/// Converts the document at `path` to Markdown with pandoc, extracting its
+/// images and other media below `staging`.
+pub async fn convert(app: &AppHandle, path: &Path, staging: &Path) -> Result<String> {
+    let format = detect(path)?;
+    let args = [
+        "--from".as_ref(),
+        format.as_ref(),
+        "--to".as_ref(),
+        "gfm".as_ref(),
+        "--wrap=none".as_ref(),
+        "--extract-media=.".as_ref(),
+        "--output".as_ref(),
+        OUTPUT.as_ref(),
+        path.as_os_str(),
+    ];
+    let shell = app.shell();
+    let mut commands = Vec::new();
+    // A sidecar only exists in builds that bundle pandoc.
+    if let Ok(sidecar) = shell.sidecar("pandoc") {
+        commands.push(sidecar);
+    }
+    commands.extend(CANDIDATES.iter().map(|program| shell.command(program)));
+
+    for command in commands {
+        let Ok(output) = command.args(args).current_dir(staging).output().await else {
+            continue;
+        };
+        if output.status.success() {
+            return Ok(fs::read_to_string(staging.join(OUTPUT))?);
+        }
+        return Err(Error::Pandoc(
+            String::from_utf8_lossy(&output.stderr).trim().to_owned(),
+        ));
+    }
+    Err(Error::Pandoc(
+        "pandoc was not found; install it to import documents".into(),
+    ))
+}
+
+/// Creates a note in `folder` from pandoc's `markdown` for the document at
+/// `path`, storing the media it extracted to `staging` as attachments.
+pub fn import(
+    vault: &Vault,
+    path: &Path,
+    markdown: &str,
+    staging: &Path,
+    folder: &str,
+) -> Result<Note> {
+    let mut store = |target: &str| -> Option<String> {
+        let rel = target.trim_start_matches("./");
+        if target.contains("://") || !rel.starts_with("media/") || rel.contains("..") {
+            return None;
+        }
+        let content = fs::read(staging.join(rel)).ok()?;
+        let name = rel.rsplit('/').next().unwrap_or(rel);
+        let stored = vault.files.save_attachment(name, &content).ok()?;
+        Some(NoteFiles::link_from(folder, &stored))
+    };
+    // Images pandoc could not express in Markdown, such as sized ones, come
+    // out as HTML.
+    let body = rewrite_links(markdown, &mut store);
+    let body = rewrite_sources(&body, &mut store);
+
+    let title = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "Untitled".into());
+    vault.create_note(&title, &body, folder)
+}
+
+/// Rewrites the `src` attributes in `html` for which `resolve` has a
+/// replacement.
+fn rewrite_sources(html: &str, mut resolve: impl FnMut(&str) -> Option<String>) -> String {
+    const PREFIX: &str = "src=\"";
+    let mut out = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some(start) = rest.find(PREFIX) {
+        let after = &rest[start + PREFIX.len()..];
+        let Some(end) = after.find('"') else {
+            break;
+        };
+        let target = &after[..end];
+        out.push_str(&rest[..start + PREFIX.len()]);
+        out.push_str(&resolve(target).unwrap_or_else(|| target.to_owned()));
+        out.push('"');
+        rest = &after[end + 1..];
+    }
+    out.push_str(rest);
+    out
+}
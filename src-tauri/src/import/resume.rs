@@ -0,0 +1,169 @@
+//! Imports that keep their place in the job log, so one cut short by a
+//! crash, a quit or the user carries on where it stopped. Importers go
+//! through their export in the same order every time and name their notes
+//! after the log entry and the item, so a second run skips what the first
+//! finished, including what it imported after its last checkpoint.
+
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, Instant, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::ImportReport;
+use crate::error::{Error, Result};
+use crate::jobs::{log, JobContext, JobKind, JobState};
+use crate::storage::Storage;
+
+/// Least time between two checkpoints written to the database.
+const CHECKPOINT_EVERY: Duration = Duration::from_secs(1);
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Checkpoint<R> {
+    report: R,
+    /// Items finished, in the order the importer goes through them.
+    items: u64,
+    /// Size and modification time of the export, which has to be the same
+    /// file to carry on.
+    size: u64,
+    modified: i64,
+}
+
+pub struct Resumable<'a> {
+    storage: &'a Storage,
+    context: &'a JobContext,
+    /// The log entry.
+    id: String,
+    /// Whether an earlier run got anywhere.
+    resumed: bool,
+    /// Items the earlier runs finished.
+    skip: u64,
+    report: ImportReport,
+    size: u64,
+    modified: i64,
+    saved_at: Option<Instant>,
+}
+
+impl<'a> Resumable<'a> {
+    /// Logs a new import of `path` by `source`, or picks up entry `resume`.
+    pub fn open(
+        storage: &'a Storage,
+        context: &'a JobContext,
+        source: &str,
+        path: &Path,
+        resume: Option<&str>,
+    ) -> Result<Self> {
+        if storage.is_locked() {
+            return Err(Error::VaultLocked);
+        }
+        let meta = fs::metadata(path)?;
+        let size = meta.len();
+        let modified = meta
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_millis() as i64);
+        let mut resumable = Self {
+            storage,
+            context,
+            id: String::new(),
+            resumed: false,
+            skip: 0,
+            report: ImportReport::default(),
+            size,
+            modified,
+            saved_at: None,
+        };
+        let Some(id) = resume else {
+            let path = path.to_string_lossy();
+            resumable.id = log::start(storage, JobKind::Import, source, &path)?;
+            return Ok(resumable);
+        };
+        let entry = log::get(storage, id)?;
+        if entry.kind != JobKind::Import || entry.source != source {
+            return Err(Error::InvalidInput(format!("not a {source} import: {id}")));
+        }
+        match entry.state {
+            JobState::Interrupted | JobState::Cancelled | JobState::Failed => {}
+            JobState::Done => {
+                return Err(Error::InvalidInput("the import already finished".into()))
+            }
+            _ => return Err(Error::InvalidInput("the import is still running".into())),
+        }
+        if let Some(checkpoint) = entry.checkpoint_as::<Checkpoint<ImportReport>>()? {
+            if checkpoint.size != size || checkpoint.modified != modified {
+                return Err(Error::InvalidInput(
+                    "the export changed since the import started; import it again instead".into(),
+                ));
+            }
+            resumable.skip = checkpoint.items;
+            resumable.report = checkpoint.report;
+        }
+        log::restart(storage, id)?;
+        resumable.resumed = true;
+        resumable.id = entry.id;
+        Ok(resumable)
+    }
+
+    /// What the earlier runs imported, to add to.
+    pub fn take_report(&mut self) -> ImportReport {
+        std::mem::take(&mut self.report)
+    }
+
+    /// The id of the note made from the item `key` names, the same every
+    /// time this import runs.
+    pub fn note_id(&self, key: &str) -> String {
+        let digest = Sha256::digest(format!("{}\0{key}", self.id));
+        let mut bytes = [0; 16];
+        bytes.copy_from_slice(&digest[..16]);
+        uuid::Builder::from_random_bytes(bytes)
+            .into_uuid()
+            .to_string()
+    }
+
+    /// Whether item `index`, which becomes note `id`, was imported by an
+    /// earlier run. One imported after the last checkpoint is counted now.
+    pub fn is_done(&self, index: u64, id: &str, report: &mut ImportReport) -> Result<bool> {
+        if index < self.skip {
+            return Ok(true);
+        }
+        if self.resumed && self.storage.has_note(id)? {
+            report.notes += 1;
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    /// Records that the first `items` items are finished, `current` of
+    /// `total` in the importer's own unit, and stops there if the job has
+    /// been cancelled.
+    pub fn checkpoint(
+        &mut self,
+        items: u64,
+        current: u64,
+        total: u64,
+        report: &ImportReport,
+    ) -> Result<()> {
+        self.context.progress(current as usize, total as usize);
+        let due = self
+            .saved_at
+            .is_none_or(|saved| saved.elapsed() >= CHECKPOINT_EVERY);
+        if due || current >= total {
+            let checkpoint = Checkpoint {
+                report,
+                items,
+                size: self.size,
+                modified: self.modified,
+            };
+            log::checkpoint(self.storage, &self.id, current, total, &checkpoint)?;
+            self.saved_at = Some(Instant::now());
+        }
+        self.context.check()
+    }
+
+    /// Records how the import ended.
+    pub fn finish<T>(self, result: &Result<T>) -> Result<()> {
+        log::finish(self.storage, &self.id, result)
+    }
+}
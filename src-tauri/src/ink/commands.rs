@@ -0,0 +1,56 @@
+use std::fs;
+use std::path::PathBuf;
+
+use serde::Deserialize;
+use tauri::{AppHandle, Manager};
+
+use super::{Ink, SavedInk, Stroke};
+use crate::error::Result;
+use crate::export::commands::free_path;
+use crate::vaults::Current;
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InkFormat {
+    Svg,
+    Png,
+}
+
+/// Stores a drawing made in note `note_id`; embed the returned link.
+#[tauri::command]
+pub async fn save_ink(vault: Current, note_id: String, strokes: Vec<Stroke>) -> Result<SavedInk> {
+    tauri::async_runtime::spawn_blocking(move || super::save(&vault, &note_id, &strokes)).await?
+}
+
+/// The strokes of drawing `id`, or of the one previewed at `id`, to edit.
+#[tauri::command]
+pub async fn load_ink(vault: Current, id: String) -> Result<Ink> {
+    super::load(&vault, &id)
+}
+
+/// Writes drawing `id` as an image to `path`, or the downloads folder.
+/// A PNG has `scale` pixels per unit, 2 unless given. Returns the path.
+#[tauri::command]
+pub async fn export_ink(
+    app: AppHandle,
+    vault: Current,
+    id: String,
+    format: InkFormat,
+    scale: Option<f32>,
+    path: Option<String>,
+) -> Result<String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let ink = super::load(&vault, &id)?;
+        let (data, ext) = match format {
+            InkFormat::Svg => (super::svg(&ink.strokes)?.into_bytes(), "svg"),
+            InkFormat::Png => (super::png(&ink.strokes, scale.unwrap_or(2.0))?, "png"),
+        };
+        let dest = match path {
+            Some(path) => PathBuf::from(path),
+            None => free_path(app.path().download_dir()?, "drawing", ext),
+        };
+        fs::write(&dest, data)?;
+        Ok(dest.to_string_lossy().into_owned())
+    })
+    .await?
+}
@@ -0,0 +1,256 @@
+//! Stylus drawings. Each is kept in the attachment store twice: its strokes
+//! in a small binary format, `.ink`, to edit it again, and an SVG preview
+//! made from them, which is what notes embed so other Markdown apps show
+//! it too. The `ink` table leads from a preview back to its strokes.
+//!
+//! The format, little-endian throughout: the magic `NINK`, a version
+//! byte, a `u32` stroke count, then per stroke its tool as a byte, RGBA
+//! colour, `f32` size and a `u32` point count followed by `f32` x, y and
+//! pressure for each point.
+
+pub mod commands;
+mod render;
+
+pub use render::{png, svg};
+
+use std::fs;
+use std::time::SystemTime;
+
+use rusqlite::{params, OptionalExtension};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+use crate::files::NoteFiles;
+use crate::vault::Vault;
+
+const MAGIC: &[u8; 4] = b"NINK";
+const VERSION: u8 = 1;
+/// Points taken in one drawing, across its strokes.
+const MAX_POINTS: usize = 1_000_000;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Tool {
+    #[default]
+    Pen,
+    /// Translucent and of even width.
+    Highlighter,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Point {
+    pub x: f32,
+    pub y: f32,
+    /// 0 to 1; styli that cannot tell send 1.
+    #[serde(default = "full_pressure")]
+    pub pressure: f32,
+}
+
+fn full_pressure() -> f32 {
+    1.0
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Stroke {
+    #[serde(default)]
+    pub tool: Tool,
+    /// `#rgb`, `#rrggbb` or `#rrggbbaa`.
+    pub color: String,
+    /// Width at full pressure.
+    pub size: f32,
+    pub points: Vec<Point>,
+}
+
+impl Stroke {
+    fn rgba(&self) -> Result<[u8; 4]> {
+        let hex = self.color.trim().trim_start_matches('#');
+        let digits = match hex.len() {
+            3 => hex.chars().flat_map(|c| [c, c]).chain(['f', 'f']).collect(),
+            6 => format!("{hex}ff"),
+            _ => hex.to_owned(),
+        };
+        let rgba = (digits.len() == 8 && digits.chars().all(|c| c.is_ascii_hexdigit()))
+            .then(|| u32::from_str_radix(&digits, 16).ok())
+            .flatten()
+            .ok_or_else(|| Error::InvalidInput(format!("not a colour: {}", self.color)))?;
+        Ok(rgba.to_be_bytes())
+    }
+}
+
+fn color_of([r, g, b, a]: [u8; 4]) -> String {
+    match a {
+        255 => format!("#{r:02x}{g:02x}{b:02x}"),
+        a => format!("#{r:02x}{g:02x}{b:02x}{a:02x}"),
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SavedInk {
+    /// The strokes in the attachment store, what [`load`] takes.
+    pub id: String,
+    /// The SVG made from them.
+    pub preview: String,
+    /// What to put in the note's Markdown to embed the preview.
+    pub link: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Ink {
+    pub id: String,
+    pub strokes: Vec<Stroke>,
+}
+
+pub fn encode(strokes: &[Stroke]) -> Result<Vec<u8>> {
+    let points: usize = strokes.iter().map(|stroke| stroke.points.len()).sum();
+    if points == 0 {
+        return Err(Error::InvalidInput("the drawing is empty".into()));
+    }
+    if points > MAX_POINTS {
+        return Err(Error::InvalidInput(format!(
+            "drawings are limited to {MAX_POINTS} points"
+        )));
+    }
+    let mut data = Vec::with_capacity(9 + strokes.len() * 13 + points * 12);
+    data.extend_from_slice(MAGIC);
+    data.push(VERSION);
+    data.extend_from_slice(&(strokes.len() as u32).to_le_bytes());
+    for stroke in strokes {
+        data.push(match stroke.tool {
+            Tool::Pen => 0,
+            Tool::Highlighter => 1,
+        });
+        data.extend_from_slice(&stroke.rgba()?);
+        data.extend_from_slice(&stroke.size.to_le_bytes());
+        data.extend_from_slice(&(stroke.points.len() as u32).to_le_bytes());
+        for point in &stroke.points {
+            for value in [point.x, point.y, point.pressure] {
+                data.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+    }
+    Ok(data)
+}
+
+pub fn decode(data: &[u8]) -> Result<Vec<Stroke>> {
+    let bad = || Error::InvalidInput("not an ink drawing".into());
+    let mut reader = Reader(data);
+    if reader.take(4).ok_or_else(bad)? != MAGIC {
+        return Err(bad());
+    }
+    let version = reader.byte().ok_or_else(bad)?;
+    if version != VERSION {
+        return Err(Error::InvalidInput(format!(
+            "ink format version {version} is newer than this build supports"
+        )));
+    }
+    let count = reader.u32().ok_or_else(bad)?;
+    let mut strokes = Vec::new();
+    for _ in 0..count {
+        let tool = match reader.byte().ok_or_else(bad)? {
+            0 => Tool::Pen,
+            1 => Tool::Highlighter,
+            _ => return Err(bad()),
+        };
+        let rgba: [u8; 4] = reader
+            .take(4)
+            .ok_or_else(bad)?
+            .try_into()
+            .map_err(|_| bad())?;
+        let size = reader.f32().ok_or_else(bad)?;
+        let count = reader.u32().ok_or_else(bad)? as usize;
+        // Checked before allocating, against a count that lies.
+        if count.checked_mul(12).is_none_or(|len| reader.0.len() < len) {
+            return Err(bad());
+        }
+        let mut points = Vec::with_capacity(count);
+        for _ in 0..count {
+            let (x, y, pressure) = (reader.f32(), reader.f32(), reader.f32());
+            points.push(Point {
+                x: x.ok_or_else(bad)?,
+                y: y.ok_or_else(bad)?,
+                pressure: pressure.ok_or_else(bad)?,
+            });
+        }
+        strokes.push(Stroke {
+            tool,
+            color: color_of(rgba),
+            size,
+            points,
+        });
+    }
+    Ok(strokes)
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        if self.0.len() < len {
+            return None;
+        }
+        let (taken, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Some(taken)
+    }
+
+    fn byte(&mut self) -> Option<u8> {
+        self.take(1).map(|bytes| bytes[0])
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_le_bytes(self.take(4)?.try_into().ok()?))
+    }
+
+    fn f32(&mut self) -> Option<f32> {
+        Some(f32::from_le_bytes(self.take(4)?.try_into().ok()?))
+    }
+}
+
+/// Stores a drawing for note `note_id` with its preview. Identical
+/// drawings are stored once.
+pub fn save(vault: &Vault, note_id: &str, strokes: &[Stroke]) -> Result<SavedInk> {
+    let note = vault.storage.get_note(note_id)?;
+    let id = vault
+        .files
+        .save_attachment("drawing.ink", &encode(strokes)?)?;
+    let preview = vault
+        .files
+        .save_attachment("drawing.svg", svg(strokes)?.as_bytes())?;
+    // Deduplicated files keep their old timestamps; see attachments::import.
+    for rel in [&id, &preview] {
+        fs::File::options()
+            .write(true)
+            .open(vault.files.absolute(rel))?
+            .set_modified(SystemTime::now())?;
+    }
+    vault.storage.conn().execute(
+        "INSERT OR REPLACE INTO ink (preview, source) VALUES (?1, ?2)",
+        params![preview, id],
+    )?;
+    Ok(SavedInk {
+        link: NoteFiles::link_from(&note.folder, &preview),
+        id,
+        preview,
+    })
+}
+
+/// The drawing `id`, or the one whose preview `id` is.
+pub fn load(vault: &Vault, id: &str) -> Result<Ink> {
+    let source: Option<String> = vault
+        .storage
+        .conn()
+        .query_row("SELECT source FROM ink WHERE preview = ?1", [id], |row| {
+            row.get(0)
+        })
+        .optional()?;
+    let id = source.unwrap_or_else(|| id.to_owned());
+    if !NoteFiles::is_attachment_path(&id) {
+        return Err(Error::InvalidInput(format!("not an attachment: {id}")));
+    }
+    let strokes = decode(&fs::read(vault.files.absolute(&id))?)?;
+    Ok(Ink { id, strokes })
+}
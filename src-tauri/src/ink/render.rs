@@ -0,0 +1,190 @@
+//! Strokes drawn as SVG, for previews, and as PNG for exporting to apps
+//! that take no SVG. Both come from the same runs, so they look alike.
+
+use std::fmt::Write;
+
+use tiny_skia::{
+    FillRule, LineCap, LineJoin, Paint, PathBuilder, Pixmap, Stroke as Pen, Transform,
+};
+
+use super::{Stroke, Tool};
+use crate::error::{Error, Result};
+
+/// Room around the strokes, in drawing units.
+const MARGIN: f32 = 4.0;
+/// Widths are rounded to this, so a stroke breaks into few runs.
+const WIDTH_STEP: f32 = 0.25;
+const HIGHLIGHTER_ALPHA: f32 = 0.4;
+/// Longest side of a PNG, in pixels.
+const MAX_PIXELS: f32 = 8192.0;
+
+/// A stretch of a stroke drawn at one width.
+struct Run {
+    points: Vec<(f32, f32)>,
+    width: f32,
+    rgba: [u8; 4],
+}
+
+fn runs(stroke: &Stroke) -> Result<Vec<Run>> {
+    let mut rgba = stroke.rgba()?;
+    let pressure = |p: f32| match stroke.tool {
+        Tool::Pen if p > 0.0 => p.min(1.0),
+        _ => 1.0,
+    };
+    if stroke.tool == Tool::Highlighter {
+        rgba[3] = (f32::from(rgba[3]) * HIGHLIGHTER_ALPHA) as u8;
+    }
+    let width_at =
+        |p: f32| ((stroke.size * pressure(p)) / WIDTH_STEP).round().max(1.0) * WIDTH_STEP;
+    let points = &stroke.points;
+    let Some(first) = points.first() else {
+        return Ok(Vec::new());
+    };
+    if points.len() == 1 {
+        return Ok(vec![Run {
+            points: vec![(first.x, first.y)],
+            width: width_at(first.pressure),
+            rgba,
+        }]);
+    }
+    let mut runs: Vec<Run> = Vec::new();
+    for pair in points.windows(2) {
+        let width = width_at((pair[0].pressure + pair[1].pressure) / 2.0);
+        match runs.last_mut() {
+            Some(run) if run.width == width => run.points.push((pair[1].x, pair[1].y)),
+            _ => runs.push(Run {
+                points: vec![(pair[0].x, pair[0].y), (pair[1].x, pair[1].y)],
+                width,
+                rgba,
+            }),
+        }
+    }
+    Ok(runs)
+}
+
+/// The area the runs cover: left, top, width and height.
+fn bounds(runs: &[Run]) -> (f32, f32, f32, f32) {
+    let (mut left, mut top) = (f32::MAX, f32::MAX);
+    let (mut right, mut bottom) = (f32::MIN, f32::MIN);
+    for run in runs {
+        let reach = run.width / 2.0 + MARGIN;
+        for &(x, y) in &run.points {
+            left = left.min(x - reach);
+            top = top.min(y - reach);
+            right = right.max(x + reach);
+            bottom = bottom.max(y + reach);
+        }
+    }
+    (left, top, right - left, bottom - top)
+}
+
+fn all_runs(strokes: &[Stroke]) -> Result<Vec<Run>> {
+    let mut all = Vec::new();
+    for stroke in strokes {
+        all.extend(runs(stroke)?);
+    }
+    if all.is_empty() {
+        return Err(Error::InvalidInput("the drawing is empty".into()));
+    }
+    Ok(all)
+}
+
+/// Two decimals, without trailing zeros.
+fn num(value: f32) -> String {
+    let text = format!("{value:.2}");
+    text.trim_end_matches('0').trim_end_matches('.').to_owned()
+}
+
+pub fn svg(strokes: &[Stroke]) -> Result<String> {
+    let runs = all_runs(strokes)?;
+    let (left, top, width, height) = bounds(&runs);
+    let (width, height) = (num(width), num(height));
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"{} {} {width} {height}\" \
+         width=\"{width}\" height=\"{height}\">\n",
+        num(left),
+        num(top),
+    );
+    for run in &runs {
+        let [r, g, b, a] = run.rgba;
+        let color = format!("#{r:02x}{g:02x}{b:02x}");
+        let opacity = match a {
+            255 => String::new(),
+            a => format!(" opacity=\"{}\"", num(f32::from(a) / 255.0)),
+        };
+        if let [(x, y)] = run.points[..] {
+            let _ = writeln!(
+                svg,
+                "<circle cx=\"{}\" cy=\"{}\" r=\"{}\" fill=\"{color}\"{opacity}/>",
+                num(x),
+                num(y),
+                num(run.width / 2.0)
+            );
+            continue;
+        }
+        let mut d = String::new();
+        for (i, &(x, y)) in run.points.iter().enumerate() {
+            let _ = write!(
+                d,
+                "{}{} {}",
+                if i == 0 { "M" } else { " L" },
+                num(x),
+                num(y)
+            );
+        }
+        let _ = writeln!(
+            svg,
+            "<path d=\"{d}\" fill=\"none\" stroke=\"{color}\" stroke-width=\"{}\" \
+             stroke-linecap=\"round\" stroke-linejoin=\"round\"{opacity}/>",
+            num(run.width)
+        );
+    }
+    svg.push_str("</svg>\n");
+    Ok(svg)
+}
+
+/// The drawing at `scale` pixels per unit, on a transparent background.
+pub fn png(strokes: &[Stroke], scale: f32) -> Result<Vec<u8>> {
+    let runs = all_runs(strokes)?;
+    let (left, top, width, height) = bounds(&runs);
+    let scale = scale.clamp(0.1, MAX_PIXELS / width.max(height));
+    let mut pixmap = Pixmap::new(
+        (width * scale).ceil() as u32,
+        (height * scale).ceil() as u32,
+    )
+    .ok_or_else(|| Error::InvalidInput("the drawing is too large".into()))?;
+    let transform = Transform::from_row(scale, 0.0, 0.0, scale, -left * scale, -top * scale);
+    for run in &runs {
+        let [r, g, b, a] = run.rgba;
+        let mut paint = Paint::default();
+        paint.set_color_rgba8(r, g, b, a);
+        paint.anti_alias = true;
+        if let [(x, y)] = run.points[..] {
+            if let Some(dot) = PathBuilder::from_circle(x, y, run.width / 2.0) {
+                pixmap.fill_path(&dot, &paint, FillRule::Winding, transform, None);
+            }
+            continue;
+        }
+        let mut builder = PathBuilder::new();
+        for (i, &(x, y)) in run.points.iter().enumerate() {
+            if i == 0 {
+                builder.move_to(x, y);
+            } else {
+                builder.line_to(x, y);
+            }
+        }
+        let Some(path) = builder.finish() else {
+            continue;
+        };
+        let pen = Pen {
+            width: run.width,
+            line_cap: LineCap::Round,
+            line_join: LineJoin::Round,
+            ..Default::default()
+        };
+        pixmap.stroke_path(&path, &paint, &pen, transform, None);
+    }
+    pixmap
+        .encode_png()
+        .map_err(|err| Error::Export(err.to_string()))
+}
@@ -0,0 +1,25 @@
+use tauri::State;
+
+use super::log::{self, LoggedJob};
+use super::{JobProgress, Jobs};
+use crate::error::Result;
+use crate::vaults::Current;
+
+/// Jobs waiting or running, for the indexing status bar.
+#[tauri::command]
+pub async fn list_jobs(jobs: State<'_, Jobs>) -> Result<Vec<JobProgress>> {
+    Ok(jobs.list())
+}
+
+/// Stops job `id`, returning whether it was still queued or running.
+#[tauri::command]
+pub async fn cancel_job(jobs: State<'_, Jobs>, id: u64) -> Result<bool> {
+    Ok(jobs.cancel(id))
+}
+
+/// The imports and exports this vault has run, newest first. Interrupted,
+/// cancelled and failed imports can be carried on with `resume_import`.
+#[tauri::command]
+pub async fn list_job_log(vault: Current) -> Result<Vec<LoggedJob>> {
+    tauri::async_runtime::spawn_blocking(move || log::list(&vault.storage)).await?
+}
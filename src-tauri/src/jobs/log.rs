@@ -0,0 +1,175 @@
+//! Imports and exports as a vault remembers them, in its database, so the
+//! app can say how the last ones went after a restart and an import cut
+//! short can carry on from its checkpoint; see [`crate::import::Resumable`].
+//! Entries still running when the vault opens are marked
+//! [`JobState::Interrupted`].
+
+use rusqlite::{params, OptionalExtension, Row};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use super::{JobKind, JobState};
+use crate::error::{Error, Result};
+use crate::storage::{now_millis, Storage};
+
+/// Finished entries kept, newest first.
+const KEEP: usize = 100;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LoggedJob {
+    pub id: String,
+    pub kind: JobKind,
+    /// The format, as the importer or exporter names it.
+    pub source: String,
+    /// The file imported, or where the export went.
+    pub path: String,
+    pub state: JobState,
+    pub done: u64,
+    pub total: u64,
+    /// What the job saved at its last checkpoint, as it wrote it.
+    pub checkpoint: Option<serde_json::Value>,
+    pub error: Option<String>,
+    pub started_at: i64,
+    pub updated_at: i64,
+}
+
+impl LoggedJob {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        let checkpoint: Option<String> = row.get(7)?;
+        Ok(Self {
+            id: row.get(0)?,
+            kind: parse(&row.get::<_, String>(1)?).unwrap_or(JobKind::Import),
+            source: row.get(2)?,
+            path: row.get(3)?,
+            state: parse(&row.get::<_, String>(4)?).unwrap_or(JobState::Interrupted),
+            done: row.get::<_, i64>(5)? as u64,
+            total: row.get::<_, i64>(6)? as u64,
+            checkpoint: checkpoint.and_then(|json| serde_json::from_str(&json).ok()),
+            error: row.get(8)?,
+            started_at: row.get(9)?,
+            updated_at: row.get(10)?,
+        })
+    }
+
+    /// The checkpoint read back as `T`, if there is one.
+    pub fn checkpoint_as<T: DeserializeOwned>(&self) -> Result<Option<T>> {
+        match &self.checkpoint {
+            Some(value) => Ok(Some(T::deserialize(value)?)),
+            None => Ok(None),
+        }
+    }
+}
+
+/// A unit variant's serde name, as the table stores it.
+fn name(value: impl Serialize) -> String {
+    match serde_json::to_value(value) {
+        Ok(serde_json::Value::String(name)) => name,
+        _ => String::new(),
+    }
+}
+
+fn parse<T: for<'de> Deserialize<'de>>(name: &str) -> Option<T> {
+    serde_json::from_value(serde_json::Value::String(name.to_owned())).ok()
+}
+
+const COLUMNS: &str = "id, kind, source, path, state, done, total, checkpoint, error, \
+                       started_at, updated_at";
+
+/// Records a job starting on `path` and returns its entry's id.
+pub fn start(storage: &Storage, kind: JobKind, source: &str, path: &str) -> Result<String> {
+    let id = uuid::Uuid::new_v4().to_string();
+    let now = now_millis();
+    let conn = storage.conn();
+    conn.execute(
+        "INSERT INTO job_log (id, kind, source, path, state, started_at, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?6)",
+        params![id, name(kind), source, path, name(JobState::Running), now],
+    )?;
+    conn.execute(
+        "DELETE FROM job_log WHERE state != ?1 AND id NOT IN
+             (SELECT id FROM job_log ORDER BY started_at DESC LIMIT ?2)",
+        params![name(JobState::Running), KEEP as i64],
+    )?;
+    Ok(id)
+}
+
+/// Marks entry `id` as running again, for a job picked up where it stopped.
+pub fn restart(storage: &Storage, id: &str) -> Result<()> {
+    storage.conn().execute(
+        "UPDATE job_log SET state = ?2, error = NULL, updated_at = ?3 WHERE id = ?1",
+        params![id, name(JobState::Running), now_millis()],
+    )?;
+    Ok(())
+}
+
+/// Saves how far entry `id` got. `checkpoint` is whatever the job needs to
+/// carry on from here.
+pub fn checkpoint(
+    storage: &Storage,
+    id: &str,
+    done: u64,
+    total: u64,
+    checkpoint: &impl Serialize,
+) -> Result<()> {
+    storage.conn().execute(
+        "UPDATE job_log SET done = ?2, total = ?3, checkpoint = ?4, updated_at = ?5
+         WHERE id = ?1",
+        params![
+            id,
+            done as i64,
+            total as i64,
+            serde_json::to_string(checkpoint)?,
+            now_millis()
+        ],
+    )?;
+    Ok(())
+}
+
+/// Records how entry `id` ended.
+pub fn finish<T>(storage: &Storage, id: &str, result: &Result<T>) -> Result<()> {
+    let (state, error) = match result {
+        Ok(_) => (JobState::Done, None),
+        Err(Error::Cancelled) => (JobState::Cancelled, None),
+        Err(err) => (JobState::Failed, Some(err.to_string())),
+    };
+    storage.conn().execute(
+        "UPDATE job_log SET state = ?2, error = ?3, updated_at = ?4 WHERE id = ?1",
+        params![id, name(state), error, now_millis()],
+    )?;
+    Ok(())
+}
+
+pub fn get(storage: &Storage, id: &str) -> Result<LoggedJob> {
+    storage
+        .conn()
+        .query_row(
+            &format!("SELECT {COLUMNS} FROM job_log WHERE id = ?1"),
+            [id],
+            LoggedJob::from_row,
+        )
+        .optional()?
+        .ok_or_else(|| Error::InvalidInput(format!("no such job: {id}")))
+}
+
+/// Every entry, newest first.
+pub fn list(storage: &Storage) -> Result<Vec<LoggedJob>> {
+    let conn = storage.conn();
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {COLUMNS} FROM job_log ORDER BY started_at DESC"
+    ))?;
+    let jobs = stmt
+        .query_map([], LoggedJob::from_row)?
+        .collect::<rusqlite::Result<_>>()?;
+    Ok(jobs)
+}
+
+/// Marks the entries still running as interrupted; on opening a vault,
+/// nothing can be running on it yet.
+pub fn interrupted(storage: &Storage) -> Result<usize> {
+    let count = storage.conn().execute(
+        "UPDATE job_log SET state = ?2 WHERE state = ?1",
+        params![name(JobState::Running), name(JobState::Interrupted)],
+    )?;
+    Ok(count)
+}
@@ -0,0 +1,302 @@
+pub mod commands;
+pub mod log;
+
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BinaryHeap};
+use std::sync::atomic::{self, AtomicBool, AtomicU64};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter};
+use tokio::sync::{oneshot, Notify};
+
+use crate::error::{Error, Result};
+
+/// Event carrying a [`JobProgress`] whenever a job is queued, starts,
+/// advances or ends.
+pub const PROGRESS_EVENT: &str = "job-progress";
+
+/// Most jobs that run at once; each holds a blocking thread.
+const MAX_WORKERS: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Priority {
+    /// Catching up on work nobody is waiting for.
+    Background,
+    Normal,
+    /// Something the user asked for and is watching.
+    User,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum JobKind {
+    /// Search index, links, tags and the other in-memory indexes.
+    Reindex,
+    Embeddings,
+    Ocr,
+    PdfText,
+    Transcription,
+    /// A local model writing an answer.
+    Assistant,
+    /// A vault being copied to another directory.
+    Relocation,
+    /// Notes brought in from another app; see [`log`].
+    Import,
+    Export,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum JobState {
+    Queued,
+    Running,
+    Done,
+    Failed,
+    Cancelled,
+    /// Stopped by a crash or by quitting; only found in the [`log`].
+    Interrupted,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JobProgress {
+    pub id: u64,
+    pub kind: JobKind,
+    pub priority: Priority,
+    pub state: JobState,
+    pub done: usize,
+    /// Unknown until the job has counted its work.
+    pub total: Option<usize>,
+    pub error: Option<String>,
+}
+
+struct Job {
+    progress: Mutex<JobProgress>,
+    cancel: AtomicBool,
+}
+
+/// A job waiting for a worker. Higher priorities go first, then older jobs.
+struct Queued {
+    priority: Priority,
+    id: u64,
+    run: Box<dyn FnOnce(&JobContext) -> JobState + Send>,
+}
+
+impl PartialEq for Queued {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Queued {}
+
+impl PartialOrd for Queued {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Queued {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.priority
+            .cmp(&other.priority)
+            .then_with(|| other.id.cmp(&self.id))
+    }
+}
+
+/// What a running job sees: a way to report how far it got, and whether
+/// it has been asked to stop.
+pub struct JobContext {
+    app: AppHandle,
+    job: Arc<Job>,
+}
+
+impl JobContext {
+    pub fn is_cancelled(&self) -> bool {
+        self.job.cancel.load(atomic::Ordering::Relaxed)
+    }
+
+    /// [`Error::Cancelled`] once the job has been cancelled, for `?` between
+    /// steps.
+    pub fn check(&self) -> Result<()> {
+        match self.is_cancelled() {
+            true => Err(Error::Cancelled),
+            false => Ok(()),
+        }
+    }
+
+    pub fn progress(&self, done: usize, total: usize) {
+        self.update(|progress| {
+            progress.done = done;
+            progress.total = Some(total);
+        });
+    }
+
+    fn update(&self, change: impl FnOnce(&mut JobProgress)) {
+        let progress = {
+            let mut progress = self.job.progress.lock().expect("job poisoned");
+            change(&mut progress);
+            progress.clone()
+        };
+        let _ = self.app.emit(PROGRESS_EVENT, progress);
+    }
+}
+
+#[derive(Default)]
+struct Shared {
+    queue: Mutex<BinaryHeap<Queued>>,
+    /// Queued and running jobs; finished ones are dropped.
+    jobs: Mutex<BTreeMap<u64, Arc<Job>>>,
+    ready: Notify,
+    next_id: AtomicU64,
+}
+
+impl Shared {
+    fn queue(&self) -> MutexGuard<'_, BinaryHeap<Queued>> {
+        self.queue.lock().expect("job queue poisoned")
+    }
+
+    fn jobs(&self) -> MutexGuard<'_, BTreeMap<u64, Arc<Job>>> {
+        self.jobs.lock().expect("job list poisoned")
+    }
+}
+
+/// A few workers shared by every open vault for indexing that is too slow
+/// to run inline, so one large vault catching up cannot starve the rest of
+/// the app of threads.
+pub struct Jobs {
+    app: AppHandle,
+    shared: Arc<Shared>,
+}
+
+impl Jobs {
+    pub fn start(app: &AppHandle) -> Self {
+        let shared = Arc::new(Shared::default());
+        let workers = thread::available_parallelism()
+            .map_or(1, |n| n.get() / 2)
+            .clamp(1, MAX_WORKERS);
+        for _ in 0..workers {
+            tauri::async_runtime::spawn(work(app.clone(), Arc::clone(&shared)));
+        }
+        Self {
+            app: app.clone(),
+            shared,
+        }
+    }
+
+    /// Queues `f` and waits for it to finish. A job cancelled before it
+    /// starts fails with [`Error::Cancelled`].
+    pub async fn run<T, F>(&self, kind: JobKind, priority: Priority, f: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&JobContext) -> Result<T> + Send + 'static,
+    {
+        let id = self.shared.next_id.fetch_add(1, atomic::Ordering::Relaxed) + 1;
+        let job = Arc::new(Job {
+            progress: Mutex::new(JobProgress {
+                id,
+                kind,
+                priority,
+                state: JobState::Queued,
+                done: 0,
+                total: None,
+                error: None,
+            }),
+            cancel: AtomicBool::new(false),
+        });
+        let (sender, receiver) = oneshot::channel();
+        let run = Box::new(move |context: &JobContext| {
+            let result = f(context);
+            let state = match &result {
+                Ok(_) => JobState::Done,
+                Err(Error::Cancelled) => JobState::Cancelled,
+                Err(err) => {
+                    let message = err.to_string();
+                    tracing::warn!(job = id, ?kind, error = %message, "job failed");
+                    context.update(|progress| progress.error = Some(message));
+                    JobState::Failed
+                }
+            };
+            let _ = sender.send(result);
+            state
+        });
+        self.shared.jobs().insert(id, Arc::clone(&job));
+        self.shared.queue().push(Queued { priority, id, run });
+        self.shared.ready.notify_one();
+        let _ = self.app.emit(
+            PROGRESS_EVENT,
+            job.progress.lock().expect("job poisoned").clone(),
+        );
+        receiver.await.unwrap_or(Err(Error::Cancelled))
+    }
+
+    /// Queued and running jobs, oldest first.
+    pub fn list(&self) -> Vec<JobProgress> {
+        self.shared
+            .jobs()
+            .values()
+            .map(|job| job.progress.lock().expect("job poisoned").clone())
+            .collect()
+    }
+
+    /// Asks job `id` to stop. A queued job never starts; a running one stops
+    /// at its next [`JobContext::check`]. Returns whether the job was found.
+    pub fn cancel(&self, id: u64) -> bool {
+        let Some(job) = self.shared.jobs().get(&id).cloned() else {
+            return false;
+        };
+        job.cancel.store(true, atomic::Ordering::Relaxed);
+        let mut queue = self.shared.queue();
+        let before = queue.len();
+        queue.retain(|queued| queued.id != id);
+        if queue.len() < before {
+            drop(queue);
+            self.shared.jobs().remove(&id);
+            let context = JobContext {
+                app: self.app.clone(),
+                job,
+            };
+            context.update(|progress| progress.state = JobState::Cancelled);
+        }
+        true
+    }
+}
+
+async fn work(app: AppHandle, shared: Arc<Shared>) {
+    loop {
+        let next = shared.queue().pop();
+        let Some(queued) = next else {
+            shared.ready.notified().await;
+            continue;
+        };
+        let Some(job) = shared.jobs().get(&queued.id).cloned() else {
+            continue;
+        };
+        let id = queued.id;
+        let context = JobContext {
+            app: app.clone(),
+            job: Arc::clone(&job),
+        };
+        if context.is_cancelled() {
+            // Cancelled between leaving the queue and getting here.
+            drop(queued);
+            shared.jobs().remove(&id);
+            context.update(|progress| progress.state = JobState::Cancelled);
+            continue;
+        }
+        context.update(|progress| progress.state = JobState::Running);
+        // A job that panicked counts as failed.
+        let state = tauri::async_runtime::spawn_blocking(move || (queued.run)(&context))
+            .await
+            .unwrap_or(JobState::Failed);
+        shared.jobs().remove(&id);
+        let context = JobContext {
+            app: app.clone(),
+            job,
+        };
+        context.update(|progress| progress.state = state);
+    }
+}
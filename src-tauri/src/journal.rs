@@ -0,0 +1,189 @@
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::Result;
+use crate::storage::{Note, Storage};
+
+const JOURNAL_FILE: &str = "journal.log";
+const PREVIOUS_FILE: &str = "journal.old.log";
+/// Size past which the journal is rolled over at the next save.
+const MAX_BYTES: u64 = 4 * 1024 * 1024;
+
+/// One line of the journal. Bodies are sealed like in the database, so an
+/// encrypted vault journals no plaintext.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+enum Entry {
+    #[serde(rename_all = "camelCase")]
+    Save {
+        id: String,
+        title: String,
+        body: String,
+        folder: String,
+        created_at: i64,
+        updated_at: i64,
+    },
+    #[serde(rename_all = "camelCase")]
+    Delete { id: String },
+}
+
+/// What [`Journal::recover`] found missing from the database.
+#[derive(Debug, Default)]
+pub struct Lost {
+    /// Notes to write back as they are, in the order they were saved.
+    pub saved: Vec<Note>,
+    /// Notes that were deleted but are still there.
+    pub deleted: Vec<String>,
+}
+
+/// Append-only log of every note write, synced to disk before the write is
+/// reported done.
+///
+/// The database runs with `synchronous = NORMAL`, so a power cut can lose
+/// its last few commits. Those are still in the journal and put back by
+/// [`Self::recover`] at the next open. Rolling over first checkpoints the
+/// database, after which the old entries are no longer needed; the last
+/// generation is kept next to it anyway.
+pub struct Journal {
+    dir: PathBuf,
+    file: Mutex<Option<File>>,
+}
+
+impl Journal {
+    pub fn open(dir: &Path) -> Self {
+        Self {
+            dir: dir.to_owned(),
+            file: Mutex::new(None),
+        }
+    }
+
+    /// Records that `note` was just saved as it is.
+    pub fn saved(&self, storage: &Storage, note: &Note) -> Result<()> {
+        let entry = Entry::Save {
+            id: note.id.clone(),
+            title: note.title.clone(),
+            body: storage.seal_body(&note.body)?,
+            folder: note.folder.clone(),
+            created_at: note.created_at,
+            updated_at: note.updated_at,
+        };
+        self.append(storage, &entry)
+    }
+
+    /// Records that note `id` was deleted for good, so it is not brought
+    /// back from an earlier entry.
+    pub fn deleted(&self, storage: &Storage, id: &str) -> Result<()> {
+        self.append(storage, &Entry::Delete { id: id.to_owned() })
+    }
+
+    /// The journaled writes that never reached the database. A torn last
+    /// line, from a crash in the middle of appending, is skipped.
+    pub fn recover(&self, storage: &Storage) -> Result<Lost> {
+        let file = match File::open(self.path()) {
+            Ok(file) => file,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Lost::default()),
+            Err(err) => return Err(err.into()),
+        };
+        let mut last: HashMap<String, (usize, Option<Note>)> = HashMap::new();
+        for (n, line) in BufReader::new(file).lines().enumerate() {
+            let Ok(entry) = serde_json::from_str::<Entry>(&line?) else {
+                continue;
+            };
+            match entry {
+                Entry::Save {
+                    id,
+                    title,
+                    body,
+                    folder,
+                    created_at,
+                    updated_at,
+                } => {
+                    let note = Note {
+                        id: id.clone(),
+                        title,
+                        body,
+                        folder,
+                        created_at,
+                        updated_at,
+                    };
+                    last.insert(id, (n, Some(note)));
+                }
+                Entry::Delete { id } => {
+                    last.insert(id, (n, None));
+                }
+            }
+        }
+
+        let mut saved: Vec<(usize, Note)> = Vec::new();
+        let mut lost = Lost::default();
+        for (id, (n, note)) in last {
+            let stored = storage.updated_at(&id)?;
+            match note {
+                Some(note) if stored.is_some_and(|stored| stored >= note.updated_at) => {}
+                Some(mut note) => {
+                    note.body = storage.open_body(note.body)?;
+                    saved.push((n, note));
+                }
+                None if stored.is_some() => lost.deleted.push(id),
+                None => {}
+            }
+        }
+        saved.sort_by_key(|(n, _)| *n);
+        lost.saved = saved.into_iter().map(|(_, note)| note).collect();
+        Ok(lost)
+    }
+
+    /// Makes the database durable and starts a fresh journal. Needed before
+    /// the entries stop matching the database, such as when it is replaced
+    /// or resealed under a new password.
+    pub fn roll(&self, storage: &Storage) -> Result<()> {
+        self.roll_locked(storage, &mut self.lock())
+    }
+
+    fn roll_locked(&self, storage: &Storage, file: &mut Option<File>) -> Result<()> {
+        storage.checkpoint()?;
+        *file = None;
+        match fs::rename(self.path(), self.dir.join(PREVIOUS_FILE)) {
+            Err(err) if err.kind() != ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
+
+    fn append(&self, storage: &Storage, entry: &Entry) -> Result<()> {
+        let mut line = serde_json::to_string(entry)?;
+        line.push('\n');
+        let mut file = self.lock();
+        if file.is_none() {
+            *file = Some(self.open_file()?);
+        }
+        if file.as_ref().expect("journal is open").metadata()?.len() >= MAX_BYTES {
+            self.roll_locked(storage, &mut file)?;
+            *file = Some(self.open_file()?);
+        }
+        let open = file.as_mut().expect("journal is open");
+        open.write_all(line.as_bytes())?;
+        open.sync_data()?;
+        Ok(())
+    }
+
+    fn open_file(&self) -> Result<File> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.path())?;
+        Ok(file)
+    }
+
+    fn path(&self) -> PathBuf {
+        self.dir.join(JOURNAL_FILE)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Option<File>> {
+        self.file.lock().expect("journal poisoned")
+    }
+}
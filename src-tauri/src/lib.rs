@@ -1,22 +1,533 @@
-use tauri::App;
+#[cfg(desktop)]
+use std::path::Path;
+
+use tauri::{App, Manager, RunEvent};
+
+mod activity;
+mod api;
+mod appearance;
+mod archive;
+mod assistant;
+mod atomic;
+mod attachments;
+mod automation;
+mod backup;
+mod beam;
+mod blocks;
+mod boards;
+mod calendar;
+#[cfg(desktop)]
+mod cli;
+#[cfg(desktop)]
+mod clipboard;
+mod clipper;
+mod collab;
+mod collation;
+mod conflicts;
+mod crypto;
+mod daily;
+#[cfg(desktop)]
+mod deep_link;
+mod diagrams;
+mod drafts;
+mod error;
+mod export;
+mod feeds;
+#[cfg(desktop)]
+mod file_drop;
+#[cfg(desktop)]
+mod file_open;
+mod files;
+mod folders;
+pub mod headless;
+mod history;
+mod http;
+mod import;
+mod ink;
+mod jobs;
+mod journal;
+mod links;
+mod logging;
+mod mailin;
+mod maintenance;
+mod markdown;
+mod mcp;
+mod metadata;
+#[cfg(desktop)]
+mod navigation;
+#[cfg(desktop)]
+mod note_windows;
+mod ocr;
+mod os_index;
+mod pdf_text;
+mod places;
+mod plugins;
+mod protected;
+mod quick_capture;
+mod quickswitch;
+mod readlater;
+mod recents;
+mod recorder;
+mod reminders;
+mod render;
+mod search;
+mod security;
+mod settings;
+mod share;
+mod shortcuts;
+mod spell;
+mod srs;
+mod startup;
+mod stats;
+mod storage;
+mod sync;
+mod tags;
+mod tasks;
+mod templates;
+mod transcribe;
+mod trash;
+#[cfg(desktop)]
+mod tray;
+#[cfg(desktop)]
+mod updater;
+mod vault;
+mod vaults;
+mod watcher;
+#[cfg(desktop)]
+mod window_state;
+mod xml;
 
 #[cfg(mobile)]
 mod mobile;
 #[cfg(mobile)]
 pub use mobile::*;
 
+pub use error::{Error, Result};
+pub use search::SearchHit;
+pub use storage::{Note, NotePatch, NoteSummary};
+pub use vault::Vault;
+use vaults::Vaults;
+
 /// Shared app setup logic used by both desktop and mobile entry points.
 pub fn run() {
-    tauri::Builder::default()
+    startup::launched();
+    // Started by an AI tool as its stdio server, or from a terminal with a
+    // command: do that and never open a window.
+    #[cfg(desktop)]
+    if let Some(code) = mcp::bridge::run_from_args().or_else(cli::run_from_args) {
+        std::process::exit(code);
+    }
+    let builder = tauri::Builder::default();
+    // Registered first so a second launch hands over its links and files
+    // and exits before anything else starts.
+    #[cfg(desktop)]
+    let builder = builder
+        .plugin(tauri_plugin_single_instance::init(|app, args, cwd| {
+            let files = navigation::markdown_args(&args, Path::new(&cwd));
+            if files.is_empty() {
+                let _ = navigation::show_main(app);
+            }
+            for file in files {
+                file_open::open_when_ready(app, file);
+            }
+        }))
+        .plugin(tauri_plugin_deep_link::init())
+        .plugin(window_state::plugin())
+        .plugin(tauri_plugin_updater::Builder::new().build())
+        .manage(file_drop::DropTargets::default())
+        .on_window_event(|window, event| {
+            file_drop::window_event(window, event);
+            appearance::window_event(window, event);
+        })
+        .on_page_load(|webview, payload| {
+            deep_link::page_loaded(webview, payload);
+            file_open::page_loaded(webview, payload);
+            startup::page_loaded(webview, payload);
+        });
+    #[cfg(mobile)]
+    let builder = builder
+        .plugin(tauri_plugin_biometric::init())
+        .plugin(tauri_plugin_geolocation::init());
+    builder
         .plugin(tauri_plugin_shell::init())
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_notification::init())
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_fs::init())
-        .setup(|_app: &mut App| {
-            // Shared setup logic goes here
+        .setup(|app: &mut App| {
+            // Before any vault opens, since their indexing runs on it.
+            app.manage(jobs::Jobs::start(app.handle()));
+            let config_dir = app.path().app_config_dir()?;
+            let settings = startup::time("load settings", || {
+                settings::SettingsStore::load(&config_dir)
+            })?;
+            let log_level = settings.get().general.log_level;
+            app.manage(settings);
+            app.manage(shortcuts::Shortcuts::load(&config_dir)?);
+            app.manage(automation::Automations::load(&config_dir)?);
+            let app_dir = app.path().app_data_dir()?;
+            logging::init(&app_dir, log_level);
+            tracing::info!(version = %app.package_info().version, "starting");
+            app.manage(startup::time("load vault list", || Vaults::load(&app_dir))?);
+            let plugins = startup::time("load plugins", || {
+                let plugins = plugins::Plugins::new(&app_dir)?;
+                // A broken plugins folder leaves plugins off, not the app.
+                let _ = plugins.reload(app.handle());
+                Ok::<_, Error>(plugins)
+            })?;
+            app.manage(plugins);
+            app.manage(spell::Dictionaries::new(&app_dir));
+            app.manage(appearance::Themes::new(&app_dir));
+            app.manage(security::autolock::Activity::default());
+            app.manage(recorder::Recorder::default());
+            security::autolock::start(app.handle());
+            // Opened up front so its sync and reminders run from launch;
+            // other vaults open when a window asks for them.
+            let primary = startup::time("open primary vault", || vaults::primary(app.handle()))?;
+            let adopted = quick_capture::adopt_legacy_shortcut(
+                &app.state::<shortcuts::Shortcuts>(),
+                &primary.storage,
+            );
+            if let Err(err) = adopted {
+                tracing::warn!(error = %err, "could not move the capture shortcut to the keymap");
+            }
+            app.manage(api::Api::default());
+            // A port taken by another program leaves the API off until it
+            // is enabled again on a free one.
+            let _ = api::start_if_enabled(app.handle());
+            app.manage(mcp::Mcp::default());
+            let _ = mcp::start_if_enabled(app.handle());
+            app.manage(sync::lan::Lan::default());
+            // Without a usable network interface LAN sync stays off until
+            // enabled again.
+            let _ = sync::lan::start_if_enabled(app.handle());
+            app.manage(beam::Beams::default());
+            #[cfg(mobile)]
+            mobile::attach(app.handle());
+            #[cfg(desktop)]
+            {
+                app.handle().plugin(shortcuts::plugin())?;
+                // Another program may hold a shortcut; it can still be
+                // rebound from the settings.
+                let _ = shortcuts::register_all(app.handle());
+                if let Some(main) = app.get_webview_window(navigation::MAIN_WINDOW) {
+                    window_state::fit(&main)?;
+                    appearance::start(&main);
+                }
+                tray::start(app.handle())?;
+                app.manage(updater::Installer::load(&app_dir)?);
+                updater::spawn(app.handle().clone());
+                deep_link::start(app.handle())?;
+                let args: Vec<String> = std::env::args().collect();
+                let cwd = std::env::current_dir()?;
+                file_open::start(app.handle(), navigation::markdown_args(&args, &cwd));
+                app.manage(note_windows::NoteWindows::new(&app_dir));
+                note_windows::restore(app.handle())?;
+            }
             Ok(())
         })
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .invoke_handler(tauri::generate_handler![
+            storage::commands::create_note,
+            storage::commands::get_note,
+            storage::commands::read_note_range,
+            storage::commands::save_note,
+            storage::commands::update_note,
+            drafts::commands::stage_draft,
+            drafts::commands::list_recoverable_drafts,
+            drafts::commands::discard_draft,
+            conflicts::commands::get_conflicts,
+            conflicts::commands::resolve_conflict,
+            collab::commands::collab_apply_update,
+            collab::commands::collab_state_vector,
+            collab::commands::collab_encode_update,
+            storage::commands::list_notes,
+            storage::commands::folder_tree,
+            storage::commands::archive_note,
+            storage::commands::set_read_only,
+            folders::commands::create_folder,
+            folders::commands::move_note,
+            folders::commands::move_folder,
+            folders::commands::reorder,
+            recents::commands::pin_note,
+            recents::commands::list_pinned,
+            recents::commands::recent_notes,
+            activity::commands::note_activity,
+            activity::commands::activity_feed,
+            storage::commands::query_notes,
+            storage::commands::rename_note,
+            storage::commands::delete_note,
+            search::commands::search_notes,
+            search::commands::reindex_all,
+            jobs::commands::list_jobs,
+            jobs::commands::cancel_job,
+            jobs::commands::list_job_log,
+            security::commands::keychain_status,
+            security::commands::remember_vault_key,
+            security::commands::forget_vault_key,
+            security::commands::unlock_with_keychain,
+            security::commands::report_activity,
+            security::commands::get_autolock_config,
+            security::commands::set_autolock_config,
+            protected::commands::note_lock_status,
+            protected::commands::lock_note,
+            protected::commands::unlock_note,
+            protected::commands::relock_note,
+            protected::commands::remove_note_lock,
+            protected::commands::lock_folder,
+            protected::commands::unlock_folder,
+            protected::commands::relock_folder,
+            search::commands::semantic_search,
+            search::commands::related_notes,
+            search::commands::semantic_status,
+            search::commands::list_saved_searches,
+            search::commands::save_search,
+            search::commands::delete_saved_search,
+            search::commands::run_saved_search,
+            search::commands::search_replace,
+            crypto::commands::set_vault_password,
+            crypto::commands::unlock_vault,
+            crypto::commands::lock_vault,
+            crypto::commands::vault_status,
+            history::commands::list_revisions,
+            history::commands::get_revision,
+            history::commands::restore_revision,
+            history::commands::diff_revisions,
+            blocks::commands::store_stats,
+            sync::commands::configure_remote,
+            sync::commands::sync_now,
+            sync::commands::sync_status,
+            sync::commands::webdav_configure,
+            sync::commands::webdav_sync,
+            sync::commands::s3_configure,
+            sync::commands::s3_sync,
+            sync::commands::lan_enable,
+            sync::commands::lan_disable,
+            sync::commands::lan_status,
+            sync::commands::lan_pair,
+            sync::commands::lan_confirm_pairing,
+            sync::commands::lan_unpair,
+            sync::commands::lan_sync,
+            sync::commands::get_sync_rules,
+            sync::commands::set_sync_rules,
+            import::commands::import_enex,
+            import::commands::import_obsidian,
+            import::commands::import_notion_zip,
+            import::commands::resume_import,
+            import::commands::import_joplin,
+            import::commands::import_bookmarks,
+            import::commands::import_opml,
+            #[cfg(target_os = "macos")]
+            import::commands::import_apple_notes,
+            import::commands::import_markdown_file,
+            import::commands::import_document,
+            export::commands::export_note_pdf,
+            export::commands::print_note,
+            export::commands::export_notes_pdf,
+            export::commands::export_epub,
+            archive::commands::export_archive,
+            archive::commands::import_archive,
+            export::commands::export_site,
+            export::commands::export_opml,
+            backup::commands::create_backup_now,
+            backup::commands::list_backups,
+            backup::commands::restore_backup,
+            backup::commands::configure_backups,
+            trash::commands::trash_note,
+            trash::commands::restore_note,
+            trash::commands::list_trash,
+            trash::commands::empty_trash,
+            trash::commands::configure_trash,
+            tags::commands::list_tags,
+            metadata::commands::get_metadata,
+            metadata::commands::set_metadata,
+            metadata::commands::list_metadata_fields,
+            metadata::commands::query_metadata,
+            render::commands::render_markdown,
+            render::commands::highlight_code,
+            render::commands::highlight_stylesheet,
+            diagrams::commands::render_diagram,
+            spell::commands::check_text,
+            spell::commands::list_dictionaries,
+            spell::commands::add_word,
+            spell::commands::remove_word,
+            spell::commands::list_words,
+            tasks::commands::list_tasks,
+            tasks::commands::toggle_task,
+            tasks::commands::task_counts,
+            srs::commands::due_cards,
+            srs::commands::review_card,
+            srs::commands::deck_stats,
+            srs::commands::configure_srs,
+            boards::commands::create_board,
+            boards::commands::list_boards,
+            boards::commands::get_board,
+            boards::commands::delete_board,
+            boards::commands::add_board_column,
+            boards::commands::add_board_card,
+            boards::commands::move_card,
+            boards::commands::remove_board_card,
+            clipper::commands::clip_url,
+            feeds::commands::add_feed,
+            feeds::commands::list_feeds,
+            feeds::commands::refresh_feeds,
+            feeds::commands::remove_feed,
+            feeds::commands::configure_feeds,
+            readlater::commands::save_for_later,
+            readlater::commands::list_read_later,
+            readlater::commands::mark_read,
+            readlater::commands::set_reading_progress,
+            readlater::commands::remove_from_read_later,
+            mailin::commands::add_mail_account,
+            mailin::commands::update_mail_account,
+            mailin::commands::list_mail_accounts,
+            mailin::commands::remove_mail_account,
+            mailin::commands::check_mail_now,
+            mailin::commands::configure_mailin,
+            recorder::commands::start_recording,
+            recorder::commands::stop_recording,
+            recorder::commands::recording_status,
+            transcribe::commands::transcription_status,
+            transcribe::commands::configure_transcription,
+            transcribe::commands::list_transcription_models,
+            transcribe::commands::download_transcription_model,
+            transcribe::commands::delete_transcription_model,
+            assistant::commands::summarize_note,
+            assistant::commands::ask_vault,
+            assistant::commands::configure_assistant,
+            daily::commands::get_or_create_daily_note,
+            daily::commands::notes_for_date_range,
+            daily::commands::configure_daily_notes,
+            templates::commands::list_templates,
+            templates::commands::save_template,
+            templates::commands::delete_template,
+            templates::commands::render_template,
+            templates::commands::create_note_from_template,
+            quickswitch::commands::fuzzy_find,
+            stats::commands::note_stats,
+            stats::commands::vault_stats,
+            maintenance::commands::links_report,
+            maintenance::commands::find_duplicates,
+            maintenance::commands::merge_notes,
+            maintenance::commands::vault_doctor,
+            tags::commands::rename_tag,
+            tags::commands::merge_tags,
+            tags::commands::notes_with_tag,
+            links::commands::get_backlinks,
+            links::commands::resolve_link,
+            links::commands::get_outgoing_links,
+            links::commands::get_graph,
+            links::commands::unlinked_mentions,
+            links::commands::link_mention,
+            places::commands::notes_near,
+            ink::commands::save_ink,
+            ink::commands::load_ink,
+            ink::commands::export_ink,
+            quick_capture::commands::append_to_inbox,
+            quick_capture::commands::configure_quick_capture,
+            shortcuts::commands::list_shortcuts,
+            shortcuts::commands::shortcut_conflicts,
+            shortcuts::commands::set_shortcut,
+            shortcuts::commands::reset_shortcuts,
+            shortcuts::commands::export_keymap,
+            shortcuts::commands::import_keymap,
+            os_index::commands::os_index_status,
+            os_index::commands::set_os_index_enabled,
+            share::commands::receive_share,
+            share::commands::configure_share,
+            share::commands::share_note,
+            share::commands::share_qr,
+            beam::commands::beam_note,
+            beam::commands::cancel_beam,
+            beam::commands::beam_offer,
+            beam::commands::receive_beam,
+            reminders::commands::set_reminder,
+            reminders::commands::list_reminders,
+            reminders::commands::snooze_reminder,
+            reminders::commands::cancel_reminder,
+            calendar::commands::export_calendar,
+            calendar::commands::get_calendar_config,
+            calendar::commands::set_calendar_config,
+            calendar::commands::calendar_url,
+            calendar::commands::rotate_calendar_url,
+            automation::commands::list_rules,
+            automation::commands::add_rule,
+            automation::commands::remove_rule,
+            automation::commands::run_rule,
+            plugins::commands::list_plugins,
+            plugins::commands::enable_plugin,
+            plugins::commands::reload_plugins,
+            plugins::commands::run_plugin_command,
+            appearance::commands::get_theme,
+            appearance::commands::list_themes,
+            appearance::commands::import_theme,
+            appearance::commands::remove_theme,
+            appearance::commands::apply_theme,
+            appearance::commands::set_vault_theme,
+            attachments::commands::import_attachment,
+            attachments::commands::get_thumbnail,
+            attachments::commands::gc_orphaned_attachments,
+            attachments::commands::optimize_attachments,
+            attachments::commands::configure_attachment_optimization,
+            #[cfg(desktop)]
+            clipboard::commands::paste_rich_content,
+            #[cfg(desktop)]
+            file_drop::commands::set_drop_target,
+            ocr::commands::ocr_status,
+            ocr::commands::configure_ocr,
+            pdf_text::commands::extract_pdf_text,
+            api::commands::api_enable,
+            api::commands::api_disable,
+            api::commands::api_rotate_token,
+            api::commands::api_status,
+            mcp::commands::mcp_enable,
+            mcp::commands::mcp_disable,
+            mcp::commands::mcp_rotate_token,
+            mcp::commands::mcp_status,
+            vaults::commands::list_vaults,
+            vaults::commands::create_vault,
+            vaults::commands::open_vault,
+            vaults::commands::remove_vault,
+            vaults::commands::relocate_vault,
+            settings::commands::get_settings,
+            settings::commands::update_settings,
+            startup::commands::startup_report,
+            logging::commands::get_recent_logs,
+            logging::commands::set_log_level,
+            logging::commands::export_diagnostics_bundle,
+            #[cfg(desktop)]
+            note_windows::commands::open_note_window,
+            #[cfg(desktop)]
+            note_windows::commands::list_note_windows,
+            #[cfg(desktop)]
+            updater::commands::check_for_update,
+            #[cfg(desktop)]
+            updater::commands::ready_update,
+            #[cfg(desktop)]
+            updater::commands::install_update_and_restart,
+        ])
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app, event| match event {
+            // Keep running with every window closed so the tray and capture
+            // shortcut still work, unless turned off in the settings; an
+            // explicit exit carries a code and goes through.
+            RunEvent::ExitRequested {
+                code: None, api, ..
+            } if app
+                .state::<settings::SettingsStore>()
+                .get()
+                .general
+                .close_to_tray =>
+            {
+                api.prevent_exit()
+            }
+            // Finder hands over files this way instead of as arguments.
+            #[cfg(target_os = "macos")]
+            RunEvent::Opened { urls } => {
+                for path in urls.iter().filter_map(|url| url.to_file_path().ok()) {
+                    file_open::open_when_ready(app, path);
+                }
+            }
+            _ => {}
+        });
 }
@@ -0,0 +1,88 @@
+use std::collections::{HashMap, HashSet};
+
+use super::mentions::{self, MentionRange, UnlinkedMention};
+use super::{self as links, GraphEdge, GraphNode, GraphView, OutgoingLink};
+use crate::error::Result;
+use crate::storage::{Note, NoteSummary};
+use crate::vaults::Current;
+
+/// Notes linking to `note_id`, most recently edited first.
+#[tauri::command]
+pub async fn get_backlinks(vault: Current, note_id: String) -> Result<Vec<NoteSummary>> {
+    let sources: HashSet<String> = vault.links.backlinks(&note_id).into_iter().collect();
+    let mut notes = vault.storage.list_notes(None)?;
+    notes.retain(|note| sources.contains(&note.id));
+    Ok(notes)
+}
+
+/// The id of the note a link target stands for, resolving aliases and
+/// titles, or `null` if none does.
+#[tauri::command]
+pub async fn resolve_link(vault: Current, target: String) -> Result<Option<String>> {
+    let target = links::parse_note_url(&target).map_or(target.as_str(), |(target, _)| target);
+    Ok(vault.links.resolve(target))
+}
+
+/// Links in `note_id` in the order they appear, including ones whose
+/// target is gone.
+#[tauri::command]
+pub async fn get_outgoing_links(vault: Current, note_id: String) -> Result<Vec<OutgoingLink>> {
+    let titles: HashMap<String, String> = vault
+        .storage
+        .list_notes(None)?
+        .into_iter()
+        .map(|note| (note.id, note.title))
+        .collect();
+    let links = vault.links.outgoing(&note_id);
+    Ok(links
+        .into_iter()
+        .map(|link| OutgoingLink {
+            title: titles.get(&link.target).cloned(),
+            target: link.target,
+            heading: link.heading,
+        })
+        .collect())
+}
+
+/// Every note and the links between them, for the graph view. Links to
+/// missing notes are left out.
+#[tauri::command]
+pub async fn get_graph(vault: Current) -> Result<GraphView> {
+    let nodes: Vec<GraphNode> = vault
+        .storage
+        .list_notes(None)?
+        .into_iter()
+        .map(|note| GraphNode {
+            id: note.id,
+            title: note.title,
+            folder: note.folder,
+        })
+        .collect();
+    let ids: HashSet<&str> = nodes.iter().map(|node| node.id.as_str()).collect();
+    let edges = vault
+        .links
+        .edges()
+        .into_iter()
+        .filter(|(source, target)| ids.contains(source.as_str()) && ids.contains(target.as_str()))
+        .map(|(source, target)| GraphEdge { source, target })
+        .collect();
+    Ok(GraphView { nodes, edges })
+}
+
+/// Titles and aliases of other notes written in `note_id` without a link.
+#[tauri::command]
+pub async fn unlinked_mentions(vault: Current, note_id: String) -> Result<Vec<UnlinkedMention>> {
+    tauri::async_runtime::spawn_blocking(move || mentions::find(&vault, &note_id)).await?
+}
+
+/// Links the mention of `target` at `range`, as [`unlinked_mentions`] gave
+/// it, or all of them in the note when `range` is omitted.
+#[tauri::command]
+pub async fn link_mention(
+    vault: Current,
+    note_id: String,
+    range: Option<MentionRange>,
+    target: String,
+) -> Result<Note> {
+    mentions::link(&vault, &note_id, range, &target)
+}
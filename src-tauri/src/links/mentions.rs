@@ -0,0 +1,220 @@
+//! Unlinked mentions: another note's title or alias written out in plain
+//! text where a link could be. Names are matched whole-word and ignoring
+//! ASCII case, longest first, outside links, code and front matter.
+
+use std::collections::HashMap;
+use std::ops::Range;
+
+use aho_corasick::{AhoCorasickBuilder, MatchKind};
+use pulldown_cmark::{Event, Parser, Tag, TagEnd};
+use serde::{Deserialize, Serialize};
+
+use super::note_url;
+use crate::error::{Error, Result};
+use crate::metadata::FrontMatter;
+use crate::protected;
+use crate::render;
+use crate::storage::{Note, NotePatch};
+use crate::vault::Vault;
+
+/// Names shorter than this, in characters, are too common to suggest.
+const MIN_NAME: usize = 3;
+/// Characters of the line shown on each side of a mention.
+const CONTEXT: usize = 60;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UnlinkedMention {
+    /// The note it names.
+    pub target: String,
+    pub title: String,
+    /// The mention as written.
+    pub text: String,
+    /// Offsets into the body in UTF-16 code units, as JavaScript counts.
+    pub start: usize,
+    pub end: usize,
+    /// The text around it on its line.
+    pub context: String,
+}
+
+/// A mention's place, as given by [`find`].
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct MentionRange {
+    pub start: usize,
+    pub end: usize,
+}
+
+/// The unlinked mentions of other notes in note `note_id`, in order.
+pub fn find(vault: &Vault, note_id: &str) -> Result<Vec<UnlinkedMention>> {
+    let note = protected::read(vault, note_id)?;
+    let titles: HashMap<String, String> = vault
+        .storage
+        .list_notes(None)?
+        .into_iter()
+        .map(|note| (note.id, note.title))
+        .collect();
+    let mut mentions = Vec::new();
+    for (range, target) in mentioned(vault, &note)? {
+        let Some(title) = titles.get(&target) else {
+            continue;
+        };
+        mentions.push(UnlinkedMention {
+            title: title.clone(),
+            text: note.body[range.clone()].to_owned(),
+            start: utf16_at(&note.body, range.start),
+            end: utf16_at(&note.body, range.end),
+            context: context(&note.body, range),
+            target,
+        });
+    }
+    Ok(mentions)
+}
+
+/// Turns the mention of `target` at `range` in note `note_id` into a link,
+/// or every unlinked mention of `target` there when `range` is `None`.
+pub fn link(
+    vault: &Vault,
+    note_id: &str,
+    range: Option<MentionRange>,
+    target: &str,
+) -> Result<Note> {
+    let target = vault
+        .links
+        .resolve(target)
+        .ok_or_else(|| Error::NoteNotFound(target.to_owned()))?;
+    let note = protected::read(vault, note_id)?;
+    let edits: Vec<Range<usize>> = mentioned(vault, &note)?
+        .into_iter()
+        .filter(|(found, id)| {
+            *id == target
+                && range.is_none_or(|range| {
+                    utf16_at(&note.body, found.start) == range.start
+                        && utf16_at(&note.body, found.end) == range.end
+                })
+        })
+        .map(|(found, _)| found)
+        .collect();
+    if edits.is_empty() {
+        return Err(Error::InvalidInput(format!(
+            "no unlinked mention of {target} there"
+        )));
+    }
+    let url = note_url(&target, None);
+    let mut body = note.body;
+    for range in edits.into_iter().rev() {
+        let text = body[range.clone()].replace('[', "\\[").replace(']', "\\]");
+        body.replace_range(range, &format!("[{text}]({url})"));
+    }
+    vault.update_note(
+        note_id,
+        NotePatch {
+            body: Some(body),
+            ..Default::default()
+        },
+    )
+}
+
+/// Byte ranges in `note`'s body naming another note, with that note's id.
+fn mentioned(vault: &Vault, note: &Note) -> Result<Vec<(Range<usize>, String)>> {
+    let names: Vec<(String, String)> = vault
+        .links
+        .names()
+        .into_iter()
+        .filter(|(name, id)| *id != note.id && name.chars().count() >= MIN_NAME)
+        .collect();
+    if names.is_empty() {
+        return Ok(Vec::new());
+    }
+    let matcher = AhoCorasickBuilder::new()
+        .match_kind(MatchKind::LeftmostLongest)
+        .build(names.iter().map(|(name, _)| folded(name).0))
+        .map_err(|err| Error::InvalidInput(err.to_string()))?;
+    let body = &note.body;
+    let mut found = Vec::new();
+    for region in prose(body) {
+        let text = &body[region.clone()];
+        let (lower, origin) = folded(text);
+        for hit in matcher.find_iter(&lower) {
+            let (start, end) = (origin[hit.start()], origin[hit.end()]);
+            // A match has to cover whole characters of the text, not part
+            // of what one of them lowercases to.
+            let whole_chars = (hit.start() == 0 || origin[hit.start() - 1] != start)
+                && (hit.end() == lower.len() || origin[hit.end() - 1] != end);
+            let whole_word = !text[..start].chars().next_back().is_some_and(word)
+                && !text[end..].chars().next().is_some_and(word);
+            if whole_chars && whole_word {
+                let range = region.start + start..region.start + end;
+                found.push((range, names[hit.pattern().as_usize()].1.clone()));
+            }
+        }
+    }
+    Ok(found)
+}
+
+/// `text` lowercased as link names are, with final sigmas as plain ones,
+/// and for each of its bytes and its end, where in `text` that came from.
+fn folded(text: &str) -> (String, Vec<usize>) {
+    let mut lower = String::with_capacity(text.len());
+    let mut origin = Vec::with_capacity(text.len() + 1);
+    for (at, c) in text.char_indices() {
+        for c in c.to_lowercase() {
+            let c = if c == 'ς' { 'σ' } else { c };
+            lower.push(c);
+            origin.resize(lower.len(), at);
+        }
+    }
+    origin.push(text.len());
+    (lower, origin)
+}
+
+fn word(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Byte ranges of the plain text in `body`: not in front matter, code,
+/// HTML, links, wiki links or images. Text split up by the parser is joined again.
+fn prose(body: &str) -> Vec<Range<usize>> {
+    let (_, markdown) = FrontMatter::split(body);
+    let base = body.len() - markdown.len();
+    let mut regions: Vec<Range<usize>> = Vec::new();
+    let mut skipping = 0usize;
+    // Parsed as the preview renders it, so `[[wiki links]]` count as
+    // links rather than text.
+    for (event, range) in Parser::new_ext(markdown, render::options()).into_offset_iter() {
+        match event {
+            Event::Start(Tag::CodeBlock(_) | Tag::Link { .. } | Tag::Image { .. }) => skipping += 1,
+            Event::End(TagEnd::CodeBlock | TagEnd::Link | TagEnd::Image) => {
+                skipping = skipping.saturating_sub(1)
+            }
+            Event::Text(_) if skipping == 0 => {
+                let range = base + range.start..base + range.end;
+                match regions.last_mut() {
+                    Some(last) if last.end == range.start => last.end = range.end,
+                    _ => regions.push(range),
+                }
+            }
+            _ => {}
+        }
+    }
+    regions
+}
+
+fn utf16_at(body: &str, byte: usize) -> usize {
+    body[..byte].encode_utf16().count()
+}
+
+/// The line around `range`, cut to [`CONTEXT`] characters on each side.
+fn context(body: &str, range: Range<usize>) -> String {
+    let line_start = body[..range.start].rfind('\n').map_or(0, |at| at + 1);
+    let line_end = body[range.end..]
+        .find('\n')
+        .map_or(body.len(), |at| range.end + at);
+    let before: String = {
+        let chars: Vec<char> = body[line_start..range.start].chars().collect();
+        chars[chars.len().saturating_sub(CONTEXT)..]
+            .iter()
+            .collect()
+    };
+    let after: String = body[range.end..line_end].chars().take(CONTEXT).collect();
+    format!("{before}{}{after}", &body[range]).trim().to_owned()
+}
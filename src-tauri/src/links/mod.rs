@@ -0,0 +1,449 @@
+pub mod commands;
+pub mod mentions;
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::ops::Range;
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, CONTROLS};
+use pulldown_cmark::{Event, Parser, Tag, TagEnd};
+use serde::Serialize;
+
+use crate::files::NoteFiles;
+use crate::metadata::FrontMatter;
+use crate::storage::Note;
+
+/// Notes link to each other as `[label](note://<id>)`, optionally with a
+/// `#heading` fragment. Ids survive renames and moves; titles and paths
+/// do not. In place of the id, a link may name a note by an alias or its
+/// title, percent-encoded; see [`LinkGraph`].
+pub const SCHEME: &str = "note://";
+
+/// Characters escaped in a heading fragment so the link stays one token.
+const FRAGMENT: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'<')
+    .add(b'>')
+    .add(b'`')
+    .add(b'(')
+    .add(b')')
+    .add(b'#');
+
+pub fn note_url(id: &str, heading: Option<&str>) -> String {
+    match heading.map(str::trim).filter(|h| !h.is_empty()) {
+        Some(heading) => format!("{SCHEME}{id}#{}", utf8_percent_encode(heading, FRAGMENT)),
+        None => format!("{SCHEME}{id}"),
+    }
+}
+
+/// The note id and decoded heading of `url`, if it is a `note://` link.
+pub fn parse_note_url(url: &str) -> Option<(&str, Option<String>)> {
+    let rest = url.strip_prefix(SCHEME)?;
+    let (id, heading) = match rest.split_once('#') {
+        Some((id, fragment)) => {
+            let heading = percent_decode_str(fragment).decode_utf8_lossy();
+            (id, Some(heading.into_owned()).filter(|h| !h.is_empty()))
+        }
+        None => (rest, None),
+    };
+    (!id.is_empty()).then_some((id, heading))
+}
+
+/// One `note://` link as written in a note.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NoteLink {
+    pub target: String,
+    pub heading: Option<String>,
+}
+
+/// The `note://` links in a note body, in order, skipping any in code.
+pub fn parse_links(body: &str) -> Vec<NoteLink> {
+    let (_, markdown) = FrontMatter::split(body);
+    Parser::new(markdown)
+        .filter_map(|event| match event {
+            Event::Start(Tag::Link { dest_url, .. }) => {
+                parse_note_url(&dest_url).map(|(target, heading)| NoteLink {
+                    target: target.to_owned(),
+                    heading,
+                })
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// `body` with the text of links to `id` that read `old_title` changed to
+/// `new_title`, or `None` if there are none. Links with other text, or
+/// formatting, are the writer's wording and stay as they are.
+pub fn retitle_links(body: &str, id: &str, old_title: &str, new_title: &str) -> Option<String> {
+    let (_, markdown) = FrontMatter::split(body);
+    let base = body.len() - markdown.len();
+    let mut edits = Vec::new();
+    // Inside a link to `id`: the events of its text so far.
+    let mut open: Option<Vec<(Event<'_>, Range<usize>)>> = None;
+    for (event, range) in Parser::new(markdown).into_offset_iter() {
+        match event {
+            Event::Start(Tag::Link { dest_url, .. }) => {
+                let to_id = parse_note_url(&dest_url).is_some_and(|(target, _)| target == id);
+                open = to_id.then(Vec::new);
+            }
+            Event::End(TagEnd::Link) => {
+                if let Some([(Event::Text(text), range)]) = open.take().as_deref() {
+                    if **text == *old_title && markdown[range.clone()] == *old_title {
+                        edits.push(base + range.start..base + range.end);
+                    }
+                }
+            }
+            event => {
+                if let Some(parts) = &mut open {
+                    parts.push((event, range));
+                }
+            }
+        }
+    }
+    if edits.is_empty() {
+        return None;
+    }
+    let escaped = new_title.replace('[', "\\[").replace(']', "\\]");
+    let mut body = body.to_owned();
+    for range in edits.into_iter().rev() {
+        body.replace_range(range, &escaped);
+    }
+    Some(body)
+}
+
+/// `body` with `alias` added to the aliases in its front matter and
+/// `title` taken out of them, since the title names the note already, or
+/// `None` if that changes nothing. Renames use it to keep links that name
+/// the note by its old title working.
+pub fn add_alias(body: &str, alias: &str, title: &str) -> Option<String> {
+    let (front_matter, markdown) = FrontMatter::split(body);
+    // A block that does not parse is the writer's to fix, not to bury.
+    if front_matter.is_none() && body.starts_with("---") {
+        return None;
+    }
+    let mut front_matter = front_matter.unwrap_or_default();
+    let before = front_matter.aliases();
+    let (alias_key, title_key) = (name_key(alias), name_key(title));
+    let mut aliases: Vec<String> = before
+        .iter()
+        .filter(|existing| name_key(existing) != title_key)
+        .cloned()
+        .collect();
+    if alias_key != title_key
+        && !aliases
+            .iter()
+            .any(|existing| name_key(existing) == alias_key)
+    {
+        aliases.push(alias.trim().to_owned());
+    }
+    if aliases == before {
+        return None;
+    }
+    front_matter.set_aliases(&aliases);
+    Some(format!("{}{markdown}", front_matter.render()))
+}
+
+/// `body` of a note moved from folder `from` to `to`, with its relative
+/// links and images changed to point at the same files as before, or `None`
+/// if none needed changing.
+pub fn rebase_links(body: &str, from: &str, to: &str) -> Option<String> {
+    let (_, markdown) = FrontMatter::split(body);
+    let base = body.len() - markdown.len();
+    let mut edits = Vec::new();
+    for (event, range) in Parser::new(markdown).into_offset_iter() {
+        let (Event::Start(Tag::Link { dest_url, .. }) | Event::Start(Tag::Image { dest_url, .. })) =
+            event
+        else {
+            continue;
+        };
+        let Some(rel) = NoteFiles::resolve_from(from, &dest_url) else {
+            continue;
+        };
+        let rebased = NoteFiles::link_from(to, &rel);
+        if rebased == *dest_url {
+            continue;
+        }
+        // The destination as written; links with escapes in it are skipped.
+        let raw = &markdown[range.clone()];
+        if let Some(at) = raw.rfind(&format!("({dest_url}")) {
+            let start = base + range.start + at + 1;
+            edits.push((start..start + dest_url.len(), rebased));
+        }
+    }
+    if edits.is_empty() {
+        return None;
+    }
+    let mut body = body.to_owned();
+    for (range, rebased) in edits.into_iter().rev() {
+        body.replace_range(range, &rebased);
+    }
+    Some(body)
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OutgoingLink {
+    pub target: String,
+    pub heading: Option<String>,
+    /// Title of the target, or `None` if it no longer exists.
+    pub title: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GraphNode {
+    pub id: String,
+    pub title: String,
+    pub folder: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GraphEdge {
+    pub source: String,
+    pub target: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GraphView {
+    pub nodes: Vec<GraphNode>,
+    pub edges: Vec<GraphEdge>,
+}
+
+#[derive(Default)]
+struct Graph {
+    /// Links out of each note, as parsed.
+    outgoing: HashMap<String, Vec<NoteLink>>,
+    /// Notes linking to each target by its [`name_key`]; the target need
+    /// not exist.
+    incoming: HashMap<String, HashSet<String>>,
+    /// Ids of the notes with each title, by key.
+    titles: HashMap<String, BTreeSet<String>>,
+    /// Ids of the notes with each alias, by key.
+    aliases: HashMap<String, BTreeSet<String>>,
+    /// The title and alias keys of each note.
+    names: HashMap<String, Vec<String>>,
+}
+
+impl Graph {
+    fn set(&mut self, source: &str, links: Vec<NoteLink>) {
+        self.unset(source);
+        for link in &links {
+            self.incoming
+                .entry(name_key(&link.target))
+                .or_default()
+                .insert(source.to_owned());
+        }
+        if !links.is_empty() {
+            self.outgoing.insert(source.to_owned(), links);
+        }
+    }
+
+    fn unset(&mut self, source: &str) {
+        let Some(old) = self.outgoing.remove(source) else {
+            return;
+        };
+        for link in old {
+            let key = name_key(&link.target);
+            if let Some(sources) = self.incoming.get_mut(&key) {
+                sources.remove(source);
+                if sources.is_empty() {
+                    self.incoming.remove(&key);
+                }
+            }
+        }
+    }
+
+    fn name(&mut self, note: &Note) {
+        self.unname(&note.id);
+        let title = name_key(&note.title);
+        let aliases: Vec<String> = FrontMatter::split(&note.body)
+            .0
+            .map(|front_matter| front_matter.aliases())
+            .unwrap_or_default()
+            .iter()
+            .map(|alias| name_key(alias))
+            .collect();
+        let id = &note.id;
+        self.titles
+            .entry(title.clone())
+            .or_default()
+            .insert(id.clone());
+        for alias in &aliases {
+            self.aliases
+                .entry(alias.clone())
+                .or_default()
+                .insert(id.clone());
+        }
+        let mut names = vec![title];
+        names.extend(aliases);
+        self.names.insert(id.clone(), names);
+    }
+
+    fn unname(&mut self, id: &str) {
+        let Some(names) = self.names.remove(id) else {
+            return;
+        };
+        for map in [&mut self.titles, &mut self.aliases] {
+            for name in &names {
+                if let Some(ids) = map.get_mut(name) {
+                    ids.remove(id);
+                    if ids.is_empty() {
+                        map.remove(name);
+                    }
+                }
+            }
+        }
+    }
+
+    /// The note `target` stands for: the note with that id, else the one
+    /// with it as an alias, else as its title. Where names are shared the
+    /// lowest id wins, so the choice is at least stable.
+    fn resolve(&self, target: &str) -> Option<String> {
+        if self.names.contains_key(target) {
+            return Some(target.to_owned());
+        }
+        self.resolve_name(&name_key(target))
+    }
+
+    fn resolve_name(&self, key: &str) -> Option<String> {
+        self.aliases
+            .get(key)
+            .or_else(|| self.titles.get(key))
+            .and_then(|ids| ids.first())
+            .cloned()
+    }
+}
+
+/// How a link target is matched against ids, titles and aliases: decoded,
+/// trimmed and lowercase.
+fn name_key(name: &str) -> String {
+    percent_decode_str(name)
+        .decode_utf8_lossy()
+        .trim()
+        .to_lowercase()
+}
+
+/// Links between notes in both directions, updated note by note as they are
+/// saved. In memory only, since links come from note bodies; see
+/// [`crate::tags::TagIndex`].
+///
+/// A link names its target by id, or by an alias from the target's front
+/// matter or its title, which is how links written by hand or elsewhere
+/// look. Names are resolved when asked, so renaming a note or changing its
+/// aliases changes where such links go without reindexing their sources.
+#[derive(Default)]
+pub struct LinkGraph {
+    graph: RwLock<Graph>,
+}
+
+impl LinkGraph {
+    pub fn index_note(&self, note: &Note) {
+        let links = parse_links(&note.body);
+        let mut graph = self.write();
+        graph.set(&note.id, links);
+        graph.name(note);
+    }
+
+    pub fn remove_note(&self, id: &str) {
+        let mut graph = self.write();
+        graph.unset(id);
+        graph.unname(id);
+    }
+
+    pub fn rebuild(&self, notes: &[Note]) {
+        let mut graph = Graph::default();
+        for note in notes {
+            graph.set(&note.id, parse_links(&note.body));
+            graph.name(note);
+        }
+        *self.write() = graph;
+    }
+
+    pub fn clear(&self) {
+        *self.write() = Graph::default();
+    }
+
+    /// The id of the note a link `target` goes to, if there is one.
+    pub fn resolve(&self, target: &str) -> Option<String> {
+        self.read().resolve(target)
+    }
+
+    /// Links out of `id` in order, with targets resolved to ids where they
+    /// can be and left as written where not.
+    pub fn outgoing(&self, id: &str) -> Vec<NoteLink> {
+        let graph = self.read();
+        let links = graph.outgoing.get(id).cloned().unwrap_or_default();
+        links
+            .into_iter()
+            .map(|link| NoteLink {
+                target: graph.resolve(&link.target).unwrap_or(link.target),
+                heading: link.heading,
+            })
+            .collect()
+    }
+
+    /// Ids of the notes linking to `id`, by id or by a name that resolves
+    /// to it, sorted.
+    pub fn backlinks(&self, id: &str) -> Vec<String> {
+        let graph = self.read();
+        let mut sources: BTreeSet<String> = BTreeSet::new();
+        let by_id = graph.incoming.get(&name_key(id));
+        let by_name = graph
+            .names
+            .get(id)
+            .into_iter()
+            .flatten()
+            .filter(|name| graph.resolve_name(name).as_deref() == Some(id))
+            .filter_map(|name| graph.incoming.get(name));
+        for found in by_id.into_iter().chain(by_name) {
+            sources.extend(found.iter().cloned());
+        }
+        sources.into_iter().collect()
+    }
+
+    /// Every title and alias key, as links are matched against them, with
+    /// the note it resolves to.
+    pub fn names(&self) -> HashMap<String, String> {
+        let graph = self.read();
+        graph
+            .titles
+            .keys()
+            .chain(graph.aliases.keys())
+            .filter_map(|key| Some((key.clone(), graph.resolve_name(key)?)))
+            .collect()
+    }
+
+    /// Every `(source, target)` pair that has at least one link, with
+    /// targets resolved as in [`Self::outgoing`].
+    pub fn edges(&self) -> Vec<(String, String)> {
+        let graph = self.read();
+        let edges: BTreeSet<(String, String)> = graph
+            .outgoing
+            .iter()
+            .flat_map(|(source, links)| {
+                let graph = &graph;
+                links.iter().map(move |link| {
+                    let target = graph.resolve(&link.target);
+                    (
+                        source.clone(),
+                        target.unwrap_or_else(|| link.target.clone()),
+                    )
+                })
+            })
+            .collect();
+        edges.into_iter().collect()
+    }
+
+    fn read(&self) -> RwLockReadGuard<'_, Graph> {
+        self.graph.read().expect("link graph poisoned")
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, Graph> {
+        self.graph.write().expect("link graph poisoned")
+    }
+}
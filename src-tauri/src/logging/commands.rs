@@ -0,0 +1,47 @@
+use std::path::PathBuf;
+
+use chrono::Local;
+use serde_json::json;
+use tauri::{AppHandle, Emitter, Manager};
+
+use super::{DiagnosticsReport, LogEntry, LogFilter};
+use crate::error::Result;
+use crate::export::commands::free_path;
+use crate::settings::{self, LogLevel, SettingsStore};
+
+/// The latest log entries, oldest first, for the log viewer.
+#[tauri::command]
+pub async fn get_recent_logs(filter: Option<LogFilter>) -> Result<Vec<LogEntry>> {
+    Ok(super::recent(&filter.unwrap_or_default()))
+}
+
+/// Changes how much is logged, from now on and after a restart.
+#[tauri::command]
+pub async fn set_log_level(app: AppHandle, level: LogLevel) -> Result<()> {
+    let settings = app
+        .state::<SettingsStore>()
+        .update(&json!({ "general": { "logLevel": level } }))?;
+    super::set_level(level)?;
+    app.emit(settings::CHANGED_EVENT, &settings)?;
+    Ok(())
+}
+
+/// Zips the logs with counts and sizes for each vault, to attach to a bug
+/// report. Goes to `path`, or the downloads folder.
+#[tauri::command]
+pub async fn export_diagnostics_bundle(
+    app: AppHandle,
+    path: Option<String>,
+) -> Result<DiagnosticsReport> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let dest = match path {
+            Some(path) => PathBuf::from(path),
+            None => {
+                let stem = format!("diagnostics {}", Local::now().format("%Y-%m-%d"));
+                free_path(app.path().download_dir()?, &stem, "zip")
+            }
+        };
+        super::export_bundle(&app, &dest)
+    })
+    .await?
+}
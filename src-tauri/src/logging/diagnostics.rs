@@ -0,0 +1,153 @@
+//! A zip of the logs and some numbers about each vault, to attach to a
+//! bug report. Nothing in it names or quotes a note.
+
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::Path;
+
+use serde::Serialize;
+use serde_json::json;
+use tauri::{AppHandle, Manager};
+use zip::write::SimpleFileOptions;
+use zip::ZipWriter;
+
+use super::LOGS_DIR;
+use crate::error::Result;
+use crate::settings::SettingsStore;
+use crate::startup;
+use crate::vault::Vault;
+use crate::vaults::Vaults;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct VaultDiagnostics {
+    id: String,
+    open: bool,
+    encrypted: Option<bool>,
+    locked: Option<bool>,
+    warm: Option<bool>,
+    notes: Option<usize>,
+    words: Option<usize>,
+    tags: Option<usize>,
+    indexed_notes: Option<usize>,
+    attachments: Option<usize>,
+    attachment_bytes: Option<u64>,
+    database_bytes: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiagnosticsReport {
+    pub path: String,
+    pub log_files: usize,
+}
+
+/// Writes the bundle to `dest`.
+pub fn export_bundle(app: &AppHandle, dest: &Path) -> Result<DiagnosticsReport> {
+    let vaults = app.state::<Vaults>();
+    let open = vaults.open_vaults();
+    let mut settings = serde_json::to_value(app.state::<SettingsStore>().get())?;
+    // A folder path says something about what is in the notes.
+    if let Some(editor) = settings.get_mut("editor") {
+        editor["defaultFolderId"] = serde_json::Value::Null;
+    }
+    let package = app.package_info();
+    let system = json!({
+        "app": package.name,
+        "version": package.version.to_string(),
+        "os": std::env::consts::OS,
+        "arch": std::env::consts::ARCH,
+        "settings": settings,
+    });
+    let startup = json!({
+        "readyMs": startup::ready_ms(),
+        "spans": startup::spans(),
+    });
+
+    let mut zip = ZipWriter::new(File::create(dest)?);
+    let options = SimpleFileOptions::default();
+    zip.start_file("system.json", options)?;
+    zip.write_all(&serde_json::to_vec_pretty(&system)?)?;
+    zip.start_file("startup.json", options)?;
+    zip.write_all(&serde_json::to_vec_pretty(&startup)?)?;
+
+    let mut log_files = add_logs(&mut zip, &app.path().app_data_dir()?.join(LOGS_DIR), "app")?;
+    let mut diagnostics = Vec::new();
+    for info in vaults.list() {
+        let root = vaults.path_of(&info.id)?;
+        let vault = open
+            .iter()
+            .find(|(id, _)| *id == info.id)
+            .map(|(_, vault)| vault);
+        diagnostics.push(diagnose(&info.id, &root, vault.map(|vault| &**vault)));
+        log_files += add_logs(&mut zip, &root.join(LOGS_DIR), &info.id)?;
+    }
+    zip.start_file("vaults.json", options)?;
+    zip.write_all(&serde_json::to_vec_pretty(&diagnostics)?)?;
+    zip.finish()?.sync_all()?;
+    Ok(DiagnosticsReport {
+        path: dest.to_string_lossy().into_owned(),
+        log_files,
+    })
+}
+
+fn diagnose(id: &str, root: &Path, vault: Option<&Vault>) -> VaultDiagnostics {
+    let size = |path: &Path| fs::metadata(path).map_or(0, |meta| meta.len());
+    let db = root.join("notes.db");
+    let database_bytes = size(&db) + size(&db.with_extension("db-wal"));
+    let mut diagnostics = VaultDiagnostics {
+        id: id.to_owned(),
+        open: vault.is_some(),
+        encrypted: None,
+        locked: None,
+        warm: None,
+        notes: None,
+        words: None,
+        tags: None,
+        indexed_notes: None,
+        attachments: None,
+        attachment_bytes: None,
+        database_bytes,
+    };
+    let Some(vault) = vault else {
+        return diagnostics;
+    };
+    let stats = vault.stats.vault(vault.tags.counts());
+    let attachments: Vec<u64> = fs::read_dir(vault.files.attachments_dir())
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|entry| entry.metadata().ok())
+        .filter(|meta| meta.is_file())
+        .map(|meta| meta.len())
+        .collect();
+    diagnostics.encrypted = Some(vault.storage.is_encrypted());
+    diagnostics.locked = Some(vault.storage.is_locked());
+    diagnostics.warm = Some(vault.is_warm());
+    diagnostics.notes = Some(stats.notes);
+    diagnostics.words = Some(stats.words);
+    diagnostics.tags = Some(stats.tags.len());
+    diagnostics.indexed_notes = Some(vault.search.len());
+    diagnostics.attachments = Some(attachments.len());
+    diagnostics.attachment_bytes = Some(attachments.iter().sum());
+    diagnostics
+}
+
+/// Adds the log files in `dir` under `logs/<name>/`.
+fn add_logs(zip: &mut ZipWriter<File>, dir: &Path, name: &str) -> Result<usize> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Ok(0);
+    };
+    let mut added = 0;
+    for entry in entries {
+        let entry = entry?;
+        if !entry.file_type()?.is_file() {
+            continue;
+        }
+        let file = entry.file_name().to_string_lossy().into_owned();
+        zip.start_file(format!("logs/{name}/{file}"), SimpleFileOptions::default())?;
+        zip.write_all(&fs::read(entry.path())?)?;
+        added += 1;
+    }
+    Ok(added)
+}
@@ -0,0 +1,292 @@
+//! Structured logs. Events carry fields rather than formatted text and
+//! are written one JSON object a line to daily files that rotate out
+//! after a week: in `logs/` of the vault named by an event's `vault`
+//! field, else in `logs/` of the app's data folder. The latest are also
+//! kept in memory for the in-app log viewer.
+//!
+//! Log ids, counts and errors, never note titles or text: the files go
+//! into the diagnostics bundles attached to bug reports.
+
+pub mod commands;
+mod diagnostics;
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard, OnceLock};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use tracing::field::{Field, Visit};
+use tracing::{Event, Level, Subscriber};
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::layer::{Context, SubscriberExt};
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{reload, Layer, Registry};
+
+use crate::error::{Error, Result};
+use crate::settings::LogLevel;
+use crate::storage::now_millis;
+
+pub use diagnostics::{export_bundle, DiagnosticsReport};
+
+pub const LOGS_DIR: &str = "logs";
+const FILE_PREFIX: &str = "notes";
+const FILE_SUFFIX: &str = "log";
+/// Days of files kept in each folder.
+const KEEP_FILES: usize = 7;
+/// Entries kept in memory for [`recent`].
+const RECENT: usize = 2000;
+/// File key of events that name no vault.
+const APP: &str = "";
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LogEntry {
+    /// Milliseconds since the epoch.
+    pub time: i64,
+    pub level: LogLevel,
+    /// The module it came from.
+    pub target: String,
+    pub message: String,
+    pub vault_id: Option<String>,
+    pub fields: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct LogFilter {
+    /// Least severe level to include.
+    pub level: Option<LogLevel>,
+    pub vault_id: Option<String>,
+    /// Only events from modules starting with this.
+    pub target: Option<String>,
+    /// Only events whose message contains this, ignoring case.
+    pub contains: Option<String>,
+    /// Newest entries to return; all that are kept when unset.
+    pub limit: Option<usize>,
+}
+
+impl LogFilter {
+    fn matches(&self, entry: &LogEntry, contains: Option<&str>) -> bool {
+        self.level.is_none_or(|level| entry.level <= level)
+            && self
+                .vault_id
+                .as_ref()
+                .is_none_or(|id| entry.vault_id.as_ref() == Some(id))
+            && self
+                .target
+                .as_ref()
+                .is_none_or(|target| entry.target.starts_with(target.as_str()))
+            && contains.is_none_or(|text| entry.message.to_lowercase().contains(text))
+    }
+}
+
+struct Logs {
+    app_dir: PathBuf,
+    recent: Mutex<VecDeque<LogEntry>>,
+    /// Open files by vault id.
+    files: Mutex<HashMap<String, RollingFileAppender>>,
+    /// Where the logs of each open vault go.
+    vault_dirs: Mutex<HashMap<String, PathBuf>>,
+}
+
+impl Logs {
+    /// Appends `entry` to its vault's file, or the app's if that vault is
+    /// not open.
+    fn write(&self, entry: &LogEntry) {
+        let Ok(mut line) = serde_json::to_vec(entry) else {
+            return;
+        };
+        line.push(b'\n');
+        let (key, dir) = match entry.vault_id.as_deref() {
+            Some(id) => match lock(&self.vault_dirs).get(id) {
+                Some(dir) => (id, dir.clone()),
+                None => (APP, self.app_dir.join(LOGS_DIR)),
+            },
+            None => (APP, self.app_dir.join(LOGS_DIR)),
+        };
+        let mut files = lock(&self.files);
+        if !files.contains_key(key) {
+            let appender = RollingFileAppender::builder()
+                .rotation(Rotation::DAILY)
+                .filename_prefix(FILE_PREFIX)
+                .filename_suffix(FILE_SUFFIX)
+                .max_log_files(KEEP_FILES)
+                .build(dir);
+            match appender {
+                Ok(appender) => files.insert(key.to_owned(), appender),
+                Err(_) => return,
+            };
+        }
+        if let Some(file) = files.get_mut(key) {
+            let _ = file.write_all(&line);
+        }
+    }
+}
+
+static LOGS: OnceLock<Logs> = OnceLock::new();
+static FILTER: OnceLock<reload::Handle<LevelFilter, Registry>> = OnceLock::new();
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().expect("logs poisoned")
+}
+
+fn level_filter(level: LogLevel) -> LevelFilter {
+    match level {
+        LogLevel::Error => LevelFilter::ERROR,
+        LogLevel::Warn => LevelFilter::WARN,
+        LogLevel::Info => LevelFilter::INFO,
+        LogLevel::Debug => LevelFilter::DEBUG,
+        LogLevel::Trace => LevelFilter::TRACE,
+    }
+}
+
+fn level_of(level: &Level) -> LogLevel {
+    match *level {
+        Level::ERROR => LogLevel::Error,
+        Level::WARN => LogLevel::Warn,
+        Level::INFO => LogLevel::Info,
+        Level::DEBUG => LogLevel::Debug,
+        Level::TRACE => LogLevel::Trace,
+    }
+}
+
+/// Starts logging to `logs/` in `app_dir` at `level`. Only the first call
+/// does anything.
+pub fn init(app_dir: &Path, level: LogLevel) {
+    if LOGS.get().is_some() {
+        return;
+    }
+    let _ = LOGS.set(Logs {
+        app_dir: app_dir.to_owned(),
+        recent: Mutex::new(VecDeque::with_capacity(RECENT)),
+        files: Mutex::new(HashMap::new()),
+        vault_dirs: Mutex::new(HashMap::new()),
+    });
+    let (filter, handle) = reload::Layer::new(level_filter(level));
+    let _ = FILTER.set(handle);
+    // Fails only if something set up a subscriber first, which then wins.
+    let _ = tracing_subscriber::registry()
+        .with(filter)
+        .with(Sink)
+        .try_init();
+}
+
+/// Changes how much is logged from now on.
+pub fn set_level(level: LogLevel) -> Result<()> {
+    match FILTER.get() {
+        Some(handle) => handle
+            .reload(level_filter(level))
+            .map_err(|err| Error::InvalidInput(format!("logging: {err}"))),
+        None => Ok(()),
+    }
+}
+
+/// Sends events about vault `id` to `logs/` in `root` from now on.
+pub fn attach_vault(id: &str, root: &Path) {
+    if let Some(logs) = LOGS.get() {
+        lock(&logs.vault_dirs).insert(id.to_owned(), root.join(LOGS_DIR));
+    }
+}
+
+/// Closes the log file of vault `id`, once it is no longer open.
+pub fn detach_vault(id: &str) {
+    if let Some(logs) = LOGS.get() {
+        lock(&logs.vault_dirs).remove(id);
+        lock(&logs.files).remove(id);
+    }
+}
+
+/// The newest entries `filter` lets through, oldest first.
+pub fn recent(filter: &LogFilter) -> Vec<LogEntry> {
+    let Some(logs) = LOGS.get() else {
+        return Vec::new();
+    };
+    let contains = filter.contains.as_deref().map(str::to_lowercase);
+    let recent = lock(&logs.recent);
+    let mut entries: Vec<LogEntry> = recent
+        .iter()
+        .rev()
+        .filter(|entry| filter.matches(entry, contains.as_deref()))
+        .take(filter.limit.unwrap_or(RECENT))
+        .cloned()
+        .collect();
+    entries.reverse();
+    entries
+}
+
+/// The layer turning each event into a [`LogEntry`].
+struct Sink;
+
+impl<S: Subscriber> Layer<S> for Sink {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let Some(logs) = LOGS.get() else {
+            return;
+        };
+        let metadata = event.metadata();
+        let mut fields = Fields::default();
+        event.record(&mut fields);
+        let entry = LogEntry {
+            time: now_millis(),
+            level: level_of(metadata.level()),
+            target: metadata.target().to_owned(),
+            message: fields.message,
+            vault_id: fields.vault_id,
+            fields: fields.rest,
+        };
+        logs.write(&entry);
+        let mut recent = lock(&logs.recent);
+        if recent.len() == RECENT {
+            recent.pop_front();
+        }
+        recent.push_back(entry);
+    }
+}
+
+#[derive(Default)]
+struct Fields {
+    message: String,
+    vault_id: Option<String>,
+    rest: Map<String, Value>,
+}
+
+impl Fields {
+    fn put(&mut self, field: &Field, value: Value) {
+        match (field.name(), value) {
+            ("message", Value::String(message)) => self.message = message,
+            ("vault", Value::String(id)) => self.vault_id = Some(id),
+            (name, value) => {
+                self.rest.insert(name.to_owned(), value);
+            }
+        }
+    }
+}
+
+impl Visit for Fields {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.put(field, Value::String(format!("{value:?}")));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.put(field, Value::String(value.to_owned()));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.put(field, value.into());
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.put(field, value.into());
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.put(field, value.into());
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.put(field, value.into());
+    }
+}
@@ -0,0 +1,92 @@
+use serde::Serialize;
+
+use super::{FolderMapping, MailAccount, MailReport, MIN_INTERVAL_MINUTES};
+use crate::error::{Error, Result};
+use crate::vaults::Current;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MailSettings {
+    /// Whether this build can fetch mail.
+    pub available: bool,
+    pub interval_minutes: u64,
+}
+
+/// Adds an IMAP account. The password goes to the OS keychain.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn add_mail_account(
+    vault: Current,
+    name: String,
+    host: String,
+    port: Option<u16>,
+    username: String,
+    password: String,
+    mappings: Option<Vec<FolderMapping>>,
+) -> Result<MailAccount> {
+    let account = MailAccount {
+        id: String::new(),
+        name: match name.trim() {
+            "" => username.trim().to_owned(),
+            name => name.to_owned(),
+        },
+        host,
+        port: port.unwrap_or(993),
+        username,
+        mappings: mappings.unwrap_or_default(),
+        enabled: true,
+        checked_at: None,
+        error: None,
+    };
+    super::add_account(&vault.storage, account, &password)
+}
+
+#[tauri::command]
+pub async fn update_mail_account(
+    vault: Current,
+    id: String,
+    mappings: Option<Vec<FolderMapping>>,
+    enabled: Option<bool>,
+    password: Option<String>,
+) -> Result<MailAccount> {
+    super::update_account(&vault.storage, &id, mappings, enabled, password.as_deref())
+}
+
+#[tauri::command]
+pub async fn list_mail_accounts(vault: Current) -> Result<Vec<MailAccount>> {
+    Ok(super::config_of(&vault.storage)?.accounts)
+}
+
+#[tauri::command]
+pub async fn remove_mail_account(vault: Current, id: String) -> Result<()> {
+    super::remove_account(&vault.storage, &id)
+}
+
+/// Checks every enabled account, or only account `id`, right away.
+#[tauri::command]
+pub async fn check_mail_now(vault: Current, id: Option<String>) -> Result<MailReport> {
+    tauri::async_runtime::spawn_blocking(move || super::check(&vault, id.as_deref())).await?
+}
+
+/// Changes how often mail is checked. Without arguments, returns the
+/// current settings.
+#[tauri::command]
+pub async fn configure_mailin(
+    vault: Current,
+    interval_minutes: Option<u64>,
+) -> Result<MailSettings> {
+    let mut config = super::config_of(&vault.storage)?;
+    if let Some(minutes) = interval_minutes {
+        if minutes > 0 && minutes < MIN_INTERVAL_MINUTES {
+            return Err(Error::InvalidInput(format!(
+                "mail is checked at most every {MIN_INTERVAL_MINUTES} minutes"
+            )));
+        }
+        config.interval_minutes = minutes;
+        super::set_config(&vault.storage, &config)?;
+    }
+    Ok(MailSettings {
+        available: super::AVAILABLE,
+        interval_minutes: config.interval_minutes,
+    })
+}
@@ -0,0 +1,176 @@
+//! Talking IMAP and reading the messages, in builds with the `mailin`
+//! feature.
+
+use chrono::{DateTime, Local};
+use htmd::HtmlToMarkdown;
+use imap::{ClientBuilder, Session};
+use mail_parser::{MessageParser, MimeHeaders};
+use serde_yaml_ng::Value;
+
+use super::{FolderMapping, MailAccount};
+use crate::attachments;
+use crate::error::{Error, Result};
+use crate::metadata::FrontMatter;
+use crate::storage::NotePatch;
+use crate::vault::Vault;
+
+/// Messages taken per mailbox and check; the rest wait for the next one.
+const MAX_MESSAGES: usize = 50;
+const NO_SUBJECT: &str = "(no subject)";
+/// Prefixes a forwarded message's subject starts with.
+const FORWARD_PREFIXES: [&str; 4] = ["fwd:", "fw:", "wg:", "tr:"];
+
+/// Makes notes of the unread mail in every mapped mailbox and marks it
+/// read. Returns how many notes were made.
+pub fn check(vault: &Vault, account: &MailAccount, password: &str) -> Result<usize> {
+    let client = ClientBuilder::new(account.host.trim(), account.port)
+        .connect()
+        .map_err(mail_error)?;
+    let mut session = client
+        .login(account.username.trim(), password)
+        .map_err(|(err, _)| mail_error(err))?;
+    let mut made = 0;
+    let result = account.mappings.iter().try_for_each(|mapping| {
+        made += check_mailbox(vault, account, &mut session, mapping)?;
+        Ok(())
+    });
+    let _ = session.logout();
+    result.map(|()| made)
+}
+
+fn check_mailbox<T: std::io::Read + std::io::Write>(
+    vault: &Vault,
+    account: &MailAccount,
+    session: &mut Session<T>,
+    mapping: &FolderMapping,
+) -> Result<usize> {
+    let mailbox = session.select(&mapping.mailbox).map_err(mail_error)?;
+    let validity = mailbox.uid_validity.unwrap_or_default();
+    // A new UID validity means the server renumbered the mailbox, so the
+    // old cursor says nothing about it any more.
+    let last = match super::cursor(&vault.storage, &account.id, &mapping.mailbox)? {
+        Some((seen_validity, last)) if seen_validity == validity => last,
+        _ => 0,
+    };
+    let mut uids: Vec<u32> = session
+        .uid_search(format!("UNSEEN UID {}:*", last + 1))
+        .map_err(mail_error)?
+        .into_iter()
+        // `n:*` always matches the newest message, even below `n`.
+        .filter(|uid| *uid > last)
+        .collect();
+    uids.sort_unstable();
+    uids.truncate(MAX_MESSAGES);
+
+    let folder = mapping.folder.trim_matches('/');
+    let mut made = 0;
+    for uid in uids {
+        let fetched = session
+            .uid_fetch(uid.to_string(), "BODY.PEEK[]")
+            .map_err(mail_error)?;
+        if let Some(raw) = fetched.iter().find_map(|message| message.body()) {
+            save(vault, folder, raw)?;
+            made += 1;
+        }
+        session
+            .uid_store(uid.to_string(), "+FLAGS (\\Seen)")
+            .map_err(mail_error)?;
+        super::set_cursor(&vault.storage, &account.id, &mapping.mailbox, validity, uid)?;
+    }
+    Ok(made)
+}
+
+/// A message as a note in `folder`: its text, who sent it in the front
+/// matter, and its attachments linked at the end.
+fn save(vault: &Vault, folder: &str, raw: &[u8]) -> Result<()> {
+    let message = MessageParser::default()
+        .parse(raw)
+        .ok_or_else(|| Error::Mail("could not read a message".into()))?;
+    let title = title_of(message.subject().unwrap_or_default());
+    let note = vault.create_note(&title, "", folder)?;
+
+    let text = match (message.body_text(0), message.body_html(0)) {
+        (Some(text), _) if !text.trim().is_empty() => text.trim().to_owned(),
+        (_, Some(html)) => HtmlToMarkdown::builder()
+            .skip_tags(vec!["head", "style", "script", "noscript"])
+            .build()
+            .convert(&html)?,
+        _ => String::new(),
+    };
+
+    let mut links = Vec::new();
+    for part in message.attachments() {
+        let name = part.attachment_name().unwrap_or("attachment");
+        let attachment = attachments::import(vault, &note.id, name, part.contents())?;
+        let image = part
+            .content_type()
+            .is_some_and(|kind| kind.ctype().eq_ignore_ascii_case("image"));
+        links.push(match image {
+            true => format!("![{name}](<{}>)", attachment.link),
+            false => format!("- [{name}](<{}>)", attachment.link),
+        });
+    }
+
+    let mut front_matter = FrontMatter::default();
+    let mut set = |key: &str, value: Option<String>| {
+        if let Some(value) = value.filter(|value| !value.trim().is_empty()) {
+            front_matter
+                .fields
+                .insert(Value::String(key.into()), Value::String(value));
+        }
+    };
+    let from = message.from().and_then(|from| from.first()).map(|from| {
+        match (from.name.as_deref(), from.address.as_deref()) {
+            (Some(name), Some(address)) => format!("{name} <{address}>"),
+            (name, address) => name.or(address).unwrap_or_default().to_owned(),
+        }
+    });
+    set("from", from);
+    set(
+        "date",
+        message
+            .date()
+            .and_then(|date| DateTime::from_timestamp(date.to_timestamp(), 0))
+            .map(|date| {
+                date.with_timezone(&Local)
+                    .format("%Y-%m-%d %H:%M")
+                    .to_string()
+            }),
+    );
+    set("source", Some("email".into()));
+    set("message_id", message.message_id().map(str::to_owned));
+
+    let mut body = format!("{}{}\n", front_matter.render(), text.trim());
+    if !links.is_empty() {
+        body.push('\n');
+        body.push_str(&links.join("\n"));
+        body.push('\n');
+    }
+    vault.update_note(
+        &note.id,
+        NotePatch {
+            body: Some(body),
+            ..Default::default()
+        },
+    )?;
+    Ok(())
+}
+
+/// The subject without the prefixes forwarding adds.
+fn title_of(subject: &str) -> String {
+    let mut title = subject.trim();
+    while let Some(prefix) = FORWARD_PREFIXES
+        .iter()
+        .find(|prefix| title.to_lowercase().starts_with(*prefix))
+    {
+        title = title[prefix.len()..].trim_start();
+    }
+    match title {
+        "" => NO_SUBJECT.to_owned(),
+        title => title.to_owned(),
+    }
+}
+
+fn mail_error(err: imap::Error) -> Error {
+    Error::Mail(err.to_string())
+}
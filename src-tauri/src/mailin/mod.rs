@@ -0,0 +1,310 @@
+pub mod commands;
+#[cfg(feature = "mailin")]
+mod fetch;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use rusqlite::{params, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter};
+
+use crate::error::{Error, Result};
+use crate::security;
+use crate::storage::{now_millis, Storage};
+use crate::vault::Vault;
+
+/// Event carrying the message of a background mail check that failed.
+pub const ERROR_EVENT: &str = "mail-error";
+/// Whether this build can fetch mail at all.
+pub const AVAILABLE: bool = cfg!(feature = "mailin");
+
+const CONFIG_KEY: &str = "mailin.config";
+const IDLE_POLL: Duration = Duration::from_secs(60);
+/// Shortest interval between background checks.
+pub const MIN_INTERVAL_MINUTES: u64 = 5;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct MailConfig {
+    pub accounts: Vec<MailAccount>,
+    /// 0 checks only when asked to.
+    pub interval_minutes: u64,
+}
+
+impl Default for MailConfig {
+    fn default() -> Self {
+        Self {
+            accounts: Vec::new(),
+            interval_minutes: 15,
+        }
+    }
+}
+
+/// An IMAP account whose mail becomes notes. Its password is in the OS
+/// keychain, never in the vault.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MailAccount {
+    pub id: String,
+    pub name: String,
+    pub host: String,
+    /// 993 for TLS from the start; any other port upgrades with STARTTLS.
+    pub port: u16,
+    pub username: String,
+    pub mappings: Vec<FolderMapping>,
+    pub enabled: bool,
+    #[serde(default)]
+    pub checked_at: Option<i64>,
+    /// Why the last check failed, if it did.
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+/// Unread mail in `mailbox` goes to the notes folder `folder`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FolderMapping {
+    pub mailbox: String,
+    pub folder: String,
+}
+
+impl Default for FolderMapping {
+    fn default() -> Self {
+        Self {
+            mailbox: "INBOX".into(),
+            folder: "Mail".into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MailReport {
+    pub accounts: usize,
+    pub notes: usize,
+    /// Accounts that could not be checked; their `error` says why.
+    pub failed: usize,
+}
+
+pub fn config_of(storage: &Storage) -> Result<MailConfig> {
+    match storage.meta(CONFIG_KEY)? {
+        Some(json) => Ok(serde_json::from_str(&json)?),
+        None => Ok(MailConfig::default()),
+    }
+}
+
+pub fn set_config(storage: &Storage, config: &MailConfig) -> Result<()> {
+    storage.set_meta(CONFIG_KEY, &serde_json::to_string(config)?)
+}
+
+/// Adds an account, keeping `password` in the keychain. Without mappings,
+/// the inbox goes to a "Mail" folder.
+pub fn add_account(
+    storage: &Storage,
+    mut account: MailAccount,
+    password: &str,
+) -> Result<MailAccount> {
+    account.id = uuid::Uuid::new_v4().to_string();
+    account.checked_at = None;
+    account.error = None;
+    if account.mappings.is_empty() {
+        account.mappings.push(FolderMapping::default());
+    }
+    validate(&account)?;
+    security::store_secret(&keychain_account(&account.id), password)?;
+    let mut config = config_of(storage)?;
+    config.accounts.push(account.clone());
+    set_config(storage, &config)?;
+    Ok(account)
+}
+
+/// Changes the folder mappings, whether the account is checked, or its
+/// password.
+pub fn update_account(
+    storage: &Storage,
+    id: &str,
+    mappings: Option<Vec<FolderMapping>>,
+    enabled: Option<bool>,
+    password: Option<&str>,
+) -> Result<MailAccount> {
+    let mut config = config_of(storage)?;
+    let account = config
+        .accounts
+        .iter_mut()
+        .find(|account| account.id == id)
+        .ok_or_else(|| no_account(id))?;
+    if let Some(mappings) = mappings {
+        account.mappings = mappings;
+    }
+    if let Some(enabled) = enabled {
+        account.enabled = enabled;
+    }
+    validate(account)?;
+    if let Some(password) = password {
+        security::store_secret(&keychain_account(id), password)?;
+        account.error = None;
+    }
+    let account = account.clone();
+    set_config(storage, &config)?;
+    Ok(account)
+}
+
+/// Removes an account and its password. Notes made from its mail stay.
+pub fn remove_account(storage: &Storage, id: &str) -> Result<()> {
+    let mut config = config_of(storage)?;
+    let before = config.accounts.len();
+    config.accounts.retain(|account| account.id != id);
+    if config.accounts.len() == before {
+        return Err(no_account(id));
+    }
+    security::delete_secret(&keychain_account(id))?;
+    storage
+        .conn()
+        .execute("DELETE FROM mail_cursors WHERE account_id = ?1", [id])?;
+    set_config(storage, &config)
+}
+
+/// Turns unread mail of every enabled account, or only account `only`,
+/// into notes. An account that fails is recorded as such and does not stop
+/// the others.
+pub fn check(vault: &Vault, only: Option<&str>) -> Result<MailReport> {
+    if vault.storage.is_locked() {
+        return Err(Error::VaultLocked);
+    }
+    let accounts: Vec<MailAccount> = config_of(&vault.storage)?
+        .accounts
+        .into_iter()
+        .filter(|account| match only {
+            Some(id) => account.id == id,
+            None => account.enabled,
+        })
+        .collect();
+    if let Some(id) = only.filter(|_| accounts.is_empty()) {
+        return Err(no_account(id));
+    }
+    let mut report = MailReport::default();
+    for account in accounts {
+        report.accounts += 1;
+        let result = check_account(vault, &account);
+        if let Ok(notes) = result {
+            report.notes += notes;
+        } else {
+            report.failed += 1;
+        }
+        let mut config = config_of(&vault.storage)?;
+        if let Some(saved) = config.accounts.iter_mut().find(|a| a.id == account.id) {
+            saved.checked_at = Some(now_millis());
+            saved.error = result.err().map(|err| err.to_string());
+        }
+        set_config(&vault.storage, &config)?;
+    }
+    Ok(report)
+}
+
+/// Checks mail every configured interval while the vault is open. Failures
+/// are reported through [`ERROR_EVENT`]; a locked vault is skipped.
+pub fn spawn_periodic(app: AppHandle, vault: Arc<Vault>) {
+    if !AVAILABLE {
+        return;
+    }
+    tauri::async_runtime::spawn(async move {
+        while !vault.is_closed() {
+            let interval = config_of(&vault.storage)
+                .ok()
+                .filter(|config| config.accounts.iter().any(|account| account.enabled))
+                .map(|config| config.interval_minutes)
+                .filter(|minutes| *minutes > 0);
+            let Some(minutes) = interval else {
+                tokio::time::sleep(IDLE_POLL).await;
+                continue;
+            };
+            tokio::time::sleep(Duration::from_secs(minutes * 60)).await;
+
+            if vault.is_closed() || vault.storage.is_locked() {
+                continue;
+            }
+            let checked = Arc::clone(&vault);
+            let message =
+                match tauri::async_runtime::spawn_blocking(move || check(&checked, None)).await {
+                    Ok(Ok(report)) if report.failed > 0 => format!(
+                        "{} of {} mail accounts could not be checked",
+                        report.failed, report.accounts
+                    ),
+                    Ok(Ok(_)) => continue,
+                    Ok(Err(err)) => err.to_string(),
+                    Err(err) => err.to_string(),
+                };
+            let _ = app.emit(ERROR_EVENT, message);
+        }
+    });
+}
+
+#[cfg(feature = "mailin")]
+fn check_account(vault: &Vault, account: &MailAccount) -> Result<usize> {
+    let password = security::load_secret(&keychain_account(&account.id))?
+        .ok_or_else(|| Error::Mail(format!("no password for {} in the keychain", account.name)))?;
+    fetch::check(vault, account, &password)
+}
+
+#[cfg(not(feature = "mailin"))]
+fn check_account(_vault: &Vault, _account: &MailAccount) -> Result<usize> {
+    Err(Error::Mail(
+        "this build does not include email import".into(),
+    ))
+}
+
+/// The UID validity and last UID taken from a mailbox, if any were.
+#[cfg_attr(not(feature = "mailin"), allow(dead_code))]
+fn cursor(storage: &Storage, account_id: &str, mailbox: &str) -> Result<Option<(u32, u32)>> {
+    let cursor = storage
+        .conn()
+        .query_row(
+            "SELECT uid_validity, last_uid FROM mail_cursors
+             WHERE account_id = ?1 AND mailbox = ?2",
+            params![account_id, mailbox],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()?;
+    Ok(cursor)
+}
+
+#[cfg_attr(not(feature = "mailin"), allow(dead_code))]
+fn set_cursor(
+    storage: &Storage,
+    account_id: &str,
+    mailbox: &str,
+    uid_validity: u32,
+    last_uid: u32,
+) -> Result<()> {
+    storage.conn().execute(
+        "INSERT OR REPLACE INTO mail_cursors (account_id, mailbox, uid_validity, last_uid)
+         VALUES (?1, ?2, ?3, ?4)",
+        params![account_id, mailbox, uid_validity, last_uid],
+    )?;
+    Ok(())
+}
+
+fn validate(account: &MailAccount) -> Result<()> {
+    if account.host.trim().is_empty() || account.username.trim().is_empty() {
+        return Err(Error::InvalidInput(
+            "a mail account needs a server and a user name".into(),
+        ));
+    }
+    if account
+        .mappings
+        .iter()
+        .any(|mapping| mapping.mailbox.trim().is_empty())
+    {
+        return Err(Error::InvalidInput("a mapping needs a mailbox".into()));
+    }
+    Ok(())
+}
+
+fn keychain_account(id: &str) -> String {
+    format!("mailin/{id}")
+}
+
+fn no_account(id: &str) -> Error {
+    Error::InvalidInput(format!("no mail account {id}"))
+}
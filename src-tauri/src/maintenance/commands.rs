@@ -0,0 +1,52 @@
+use super::doctor::DoctorReport;
+use super::duplicates::{DuplicateGroup, MergeMode};
+use super::LinksReport;
+use crate::error::Result;
+use crate::storage::Note;
+use crate::vaults::Current;
+
+/// Broken links, notes nothing links to and unused attachments. With
+/// `fix`, broken note links that clearly belong to another note are
+/// repointed first.
+#[tauri::command]
+pub async fn links_report(vault: Current, fix: Option<bool>) -> Result<LinksReport> {
+    tauri::async_runtime::spawn_blocking(move || {
+        super::links_report(&vault, fix.unwrap_or_default())
+    })
+    .await?
+}
+
+/// Notes with the same text and, with `near`, ones mostly alike: at least
+/// `threshold` (0 to 1) of their word sequences shared.
+#[tauri::command]
+pub async fn find_duplicates(
+    vault: Current,
+    near: Option<bool>,
+    threshold: Option<f64>,
+) -> Result<Vec<DuplicateGroup>> {
+    tauri::async_runtime::spawn_blocking(move || {
+        super::duplicates::find(&vault, near.unwrap_or(true), threshold)
+    })
+    .await?
+}
+
+/// Keeps `primary` and folds `duplicates` into it, moving them to the trash.
+#[tauri::command]
+pub async fn merge_notes(
+    vault: Current,
+    primary: String,
+    duplicates: Vec<String>,
+    mode: Option<MergeMode>,
+) -> Result<Note> {
+    tauri::async_runtime::spawn_blocking(move || {
+        super::duplicates::merge(&vault, &primary, &duplicates, mode.unwrap_or_default())
+    })
+    .await?
+}
+
+/// Checks the vault for inconsistencies, fixes what is safe to fix,
+/// rebuilds the indexes and compacts the database.
+#[tauri::command]
+pub async fn vault_doctor(vault: Current) -> Result<DoctorReport> {
+    tauri::async_runtime::spawn_blocking(move || super::doctor::run(&vault)).await?
+}
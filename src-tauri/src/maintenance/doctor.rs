@@ -0,0 +1,118 @@
+use std::collections::HashSet;
+
+use serde::Serialize;
+
+use crate::error::{Error, Result};
+use crate::files::NoteFiles;
+use crate::vault::Vault;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ProblemKind {
+    /// Reported by SQLite itself; restoring a backup is the way out.
+    Database,
+    /// A note whose Markdown file is gone or was never written.
+    MissingFile,
+    /// A Markdown file in the vault that no note knows about.
+    UntrackedFile,
+    /// A note whose file says something else than the database.
+    FileDiffers,
+    /// A note links an attachment that is not there.
+    MissingAttachment,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Problem {
+    pub kind: ProblemKind,
+    pub detail: String,
+    pub fixed: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DoctorReport {
+    pub problems: Vec<Problem>,
+    /// Notes put back into the search index.
+    pub reindexed: usize,
+    /// Old revision bodies moved into the block store.
+    pub repacked: usize,
+    /// Bytes the database shrank by.
+    pub reclaimed: u64,
+}
+
+/// Checks the database, the Markdown mirror and the attachment store
+/// against each other and fixes what can be fixed without guessing:
+/// missing files are written from the database and stray files imported,
+/// as the watcher would have. Files that disagree with their note are only
+/// reported, since either side may be the one to keep. Ends by rebuilding
+/// the indexes, repacking old revisions and compacting the database.
+pub fn run(vault: &Vault) -> Result<DoctorReport> {
+    if vault.storage.is_locked() {
+        return Err(Error::VaultLocked);
+    }
+    let mut report = DoctorReport::default();
+    let problem = |kind, detail: String, fixed| Problem {
+        kind,
+        detail,
+        fixed,
+    };
+    for detail in vault.storage.integrity_check()? {
+        report
+            .problems
+            .push(problem(ProblemKind::Database, detail, false));
+    }
+
+    let notes = vault.storage.all_notes()?;
+    let mut tracked = HashSet::new();
+    for note in &notes {
+        let path = vault.files.path_of(&vault.storage, &note.id)?;
+        let on_disk = path
+            .as_deref()
+            .filter(|rel| vault.files.absolute(rel).is_file());
+        match on_disk {
+            Some(rel) => {
+                tracked.insert(rel.to_owned());
+                if vault.files.read(&vault.storage, rel)?.body != note.body {
+                    let detail = format!("{rel} differs from \"{}\"", note.title);
+                    report
+                        .problems
+                        .push(problem(ProblemKind::FileDiffers, detail, false));
+                }
+            }
+            None => {
+                let rel = vault.files.write(&vault.storage, note)?;
+                tracked.insert(rel.clone());
+                let detail = format!("\"{}\" had no file; wrote {rel}", note.title);
+                report
+                    .problems
+                    .push(problem(ProblemKind::MissingFile, detail, true));
+            }
+        }
+    }
+    for rel in vault.files.list()? {
+        if tracked.contains(&rel) || vault.files.note_at(&vault.storage, &rel)?.is_some() {
+            continue;
+        }
+        let fixed = vault.import_file(&rel)?.is_some();
+        report
+            .problems
+            .push(problem(ProblemKind::UntrackedFile, rel, fixed));
+    }
+
+    for note in &notes {
+        for rel in crate::attachments::references(note) {
+            if !vault.files.absolute(&rel).is_file() && NoteFiles::is_attachment_path(&rel) {
+                let detail = format!("\"{}\" links {rel}", note.title);
+                report
+                    .problems
+                    .push(problem(ProblemKind::MissingAttachment, detail, false));
+            }
+        }
+    }
+
+    report.repacked = crate::history::repack(&vault.storage)?;
+    report.reindexed = vault.reindex_all()?;
+    report.reclaimed = vault.storage.vacuum()?;
+    Ok(report)
+}
@@ -0,0 +1,311 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::error::{Error, Result};
+use crate::metadata::FrontMatter;
+use crate::protected;
+use crate::storage::{Note, NotePatch, NoteSummary};
+use crate::tags;
+use crate::vault::Vault;
+
+/// Words per shingle when comparing texts.
+const SHINGLE: usize = 3;
+/// MinHash signature length, split into bands of `ROWS` for bucketing.
+const HASHES: usize = 64;
+const ROWS: usize = 4;
+/// Similarity at or above which two notes count as near duplicates.
+const DEFAULT_THRESHOLD: f64 = 0.8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DuplicateKind {
+    /// The same text once case, spacing and front matter are ignored.
+    Exact,
+    Near,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DuplicateGroup {
+    pub kind: DuplicateKind,
+    /// Lowest similarity between two notes of the group, 1 for exact ones.
+    pub similarity: f64,
+    /// Oldest first, the usual choice to keep.
+    pub notes: Vec<NoteSummary>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum MergeMode {
+    /// Append each duplicate's text to the primary note.
+    #[default]
+    Concatenate,
+    /// Keep the primary as it is and drop the duplicates.
+    Discard,
+}
+
+/// Groups of notes with the same text and, with `near`, of notes with
+/// mostly the same text. Near duplicates are found by MinHash over word
+/// shingles, so only notes sharing a band of the signature are compared.
+pub fn find(vault: &Vault, near: bool, threshold: Option<f64>) -> Result<Vec<DuplicateGroup>> {
+    let threshold = threshold.unwrap_or(DEFAULT_THRESHOLD).clamp(0.0, 1.0);
+    let notes = vault.storage.all_notes()?;
+    let summaries: HashMap<String, NoteSummary> = vault
+        .storage
+        .list_notes(None)?
+        .into_iter()
+        .map(|note| (note.id.clone(), note))
+        .collect();
+    let texts: Vec<(&Note, String)> = notes
+        .iter()
+        .map(|note| (note, normalize(&note.body)))
+        .filter(|(_, text)| !text.is_empty())
+        .collect();
+
+    let mut by_hash: HashMap<Vec<u8>, Vec<usize>> = HashMap::new();
+    for (i, (_, text)) in texts.iter().enumerate() {
+        by_hash
+            .entry(Sha256::digest(text.as_bytes()).to_vec())
+            .or_default()
+            .push(i);
+    }
+    let mut groups = Vec::new();
+    // The first note of each exact group stands for the others below.
+    let mut copies: HashMap<usize, Vec<usize>> = HashMap::new();
+    for members in by_hash.into_values().filter(|members| members.len() > 1) {
+        copies.insert(members[0], members.clone());
+        groups.push((DuplicateKind::Exact, 1.0, members));
+    }
+
+    if near {
+        let duplicated: HashSet<usize> = copies.values().flatten().copied().collect();
+        let candidates: Vec<usize> = (0..texts.len())
+            .filter(|i| !duplicated.contains(i) || copies.contains_key(i))
+            .collect();
+        let shingles: HashMap<usize, HashSet<u64>> = candidates
+            .iter()
+            .map(|&i| (i, shingles(&texts[i].1)))
+            .filter(|(_, set)| !set.is_empty())
+            .collect();
+        let mut buckets: HashMap<(usize, Vec<u64>), Vec<usize>> = HashMap::new();
+        for (&i, set) in &shingles {
+            let signature = minhash(set);
+            for (band, rows) in signature.chunks(ROWS).enumerate() {
+                buckets.entry((band, rows.to_vec())).or_default().push(i);
+            }
+        }
+        let mut compared = HashSet::new();
+        let mut parent: HashMap<usize, usize> = HashMap::new();
+        let mut lowest: HashMap<usize, f64> = HashMap::new();
+        for members in buckets.values().filter(|members| members.len() > 1) {
+            for (n, &a) in members.iter().enumerate() {
+                for &b in &members[n + 1..] {
+                    let pair = (a.min(b), a.max(b));
+                    if !compared.insert(pair) {
+                        continue;
+                    }
+                    let similarity = jaccard(&shingles[&a], &shingles[&b]);
+                    if similarity < threshold {
+                        continue;
+                    }
+                    let (ra, rb) = (root(&mut parent, a), root(&mut parent, b));
+                    let low = similarity
+                        .min(lowest.get(&ra).copied().unwrap_or(1.0))
+                        .min(lowest.get(&rb).copied().unwrap_or(1.0));
+                    parent.insert(rb, ra);
+                    lowest.insert(ra, low);
+                }
+            }
+        }
+        let mut near_groups: HashMap<usize, Vec<usize>> = HashMap::new();
+        for i in parent.keys().copied().collect::<Vec<_>>() {
+            let r = root(&mut parent, i);
+            near_groups.entry(r).or_default().push(i);
+        }
+        for (r, mut members) in near_groups {
+            if !members.contains(&r) {
+                members.push(r);
+            }
+            let members: Vec<usize> = members
+                .into_iter()
+                .flat_map(|i| copies.get(&i).cloned().unwrap_or_else(|| vec![i]))
+                .collect();
+            let similarity = lowest.get(&r).copied().unwrap_or(threshold);
+            groups.push((DuplicateKind::Near, similarity, members));
+        }
+    }
+
+    let mut result: Vec<DuplicateGroup> = groups
+        .into_iter()
+        .map(|(kind, similarity, members)| {
+            let mut notes: Vec<NoteSummary> = members
+                .into_iter()
+                .filter_map(|i| summaries.get(&texts[i].0.id).cloned())
+                .collect();
+            notes.sort_by_key(|note| note.created_at);
+            DuplicateGroup {
+                kind,
+                similarity,
+                notes,
+            }
+        })
+        .filter(|group| group.notes.len() > 1)
+        .collect();
+    result.sort_by(|a, b| {
+        b.similarity
+            .total_cmp(&a.similarity)
+            .then_with(|| b.notes.len().cmp(&a.notes.len()))
+    });
+    Ok(result)
+}
+
+/// Folds `duplicates` into `primary`: their text is appended or dropped,
+/// their tags kept, links to them point at `primary` and they go to the
+/// trash. Returns the merged note.
+pub fn merge(vault: &Vault, primary: &str, duplicates: &[String], mode: MergeMode) -> Result<Note> {
+    if vault.storage.is_locked() {
+        return Err(Error::VaultLocked);
+    }
+    if duplicates.is_empty() || duplicates.iter().any(|id| id == primary) {
+        return Err(Error::InvalidInput(
+            "pick the duplicates to merge, apart from the note kept".into(),
+        ));
+    }
+    let target = vault.storage.get_note(primary)?;
+    let others = duplicates
+        .iter()
+        .map(|id| vault.storage.get_note(id))
+        .collect::<Result<Vec<_>>>()?;
+    if std::iter::once(&target)
+        .chain(&others)
+        .any(|note| protected::is_protected(&note.body))
+    {
+        return Err(Error::InvalidInput(
+            "protected notes cannot be merged; take their passphrase off first".into(),
+        ));
+    }
+
+    let (front_matter, markdown) = FrontMatter::split(&target.body);
+    let mut front_matter = front_matter.unwrap_or_default();
+    let mut tag_list = front_matter.tags();
+    let mut text = markdown.trim_end().to_owned();
+    for other in &others {
+        let (other_matter, other_text) = FrontMatter::split(&other.body);
+        for tag in other_matter.map(|fm| fm.tags()).unwrap_or_default() {
+            if !tag_list.contains(&tag) {
+                tag_list.push(tag);
+            }
+        }
+        if mode == MergeMode::Concatenate && !other_text.trim().is_empty() {
+            text.push_str(&format!("\n\n## {}\n\n{}", other.title, other_text.trim()));
+        }
+    }
+    front_matter.set_tags(&tag_list);
+    let body = format!("{}{}\n", front_matter.render(), text.trim_start());
+
+    vault.check_warm()?;
+    let retargets: HashMap<String, String> = duplicates
+        .iter()
+        .map(|id| (id.clone(), primary.to_owned()))
+        .collect();
+    let mut sources: HashSet<String> = duplicates
+        .iter()
+        .flat_map(|id| vault.links.backlinks(id))
+        .collect();
+    sources.remove(primary);
+    for id in duplicates {
+        sources.remove(id);
+    }
+    let mut patches = vec![(
+        primary.to_owned(),
+        NotePatch {
+            body: super::retarget(&body, &retargets).or(Some(body)),
+            ..Default::default()
+        },
+    )];
+    for source in sources {
+        let body = vault.storage.get_note(&source)?.body;
+        if protected::is_protected(&body) {
+            continue;
+        }
+        if let Some(body) = super::retarget(&body, &retargets) {
+            let patch = NotePatch {
+                body: Some(body),
+                ..Default::default()
+            };
+            patches.push((source, patch));
+        }
+    }
+    let message = format!(
+        "Merge {} duplicates into \"{}\"",
+        others.len(),
+        target.title
+    );
+    let merged = vault.update_notes(patches, &message)?.remove(0);
+    for id in duplicates {
+        vault.trash_note(id)?;
+    }
+    Ok(merged)
+}
+
+/// Lowercased words of the text without front matter or tags, one space
+/// apart.
+fn normalize(body: &str) -> String {
+    let (_, markdown) = FrontMatter::split(body);
+    let words: Vec<String> = markdown
+        .split_whitespace()
+        .filter(|word| !(word.starts_with('#') && tags::normalize(word).is_ok()))
+        .map(str::to_lowercase)
+        .collect();
+    words.join(" ")
+}
+
+fn shingles(text: &str) -> HashSet<u64> {
+    let words: Vec<&str> = text.split(' ').collect();
+    words
+        .windows(SHINGLE.min(words.len()))
+        .map(|window| {
+            let mut hasher = DefaultHasher::new();
+            window.hash(&mut hasher);
+            hasher.finish()
+        })
+        .collect()
+}
+
+fn minhash(shingles: &HashSet<u64>) -> Vec<u64> {
+    (0..HASHES as u64)
+        .map(|seed| {
+            shingles
+                .iter()
+                .map(|shingle| {
+                    let mut hasher = DefaultHasher::new();
+                    (seed, shingle).hash(&mut hasher);
+                    hasher.finish()
+                })
+                .min()
+                .unwrap_or_default()
+        })
+        .collect()
+}
+
+fn jaccard(a: &HashSet<u64>, b: &HashSet<u64>) -> f64 {
+    let shared = a.intersection(b).count();
+    shared as f64 / (a.len() + b.len() - shared) as f64
+}
+
+fn root(parent: &mut HashMap<usize, usize>, i: usize) -> usize {
+    let mut r = i;
+    while let Some(&p) = parent.get(&r) {
+        if p == r {
+            break;
+        }
+        r = p;
+    }
+    parent.entry(i).or_insert(i);
+    parent.entry(r).or_insert(r);
+    r
+}
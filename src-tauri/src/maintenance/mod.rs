@@ -0,0 +1,264 @@
+pub mod commands;
+pub mod doctor;
+pub mod duplicates;
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::ErrorKind;
+
+use pulldown_cmark::{Event, Parser, Tag, TagEnd};
+use serde::Serialize;
+
+use crate::error::{Error, Result};
+use crate::files::{NoteFiles, ATTACHMENTS_DIR};
+use crate::links;
+use crate::metadata::FrontMatter;
+use crate::storage::{Note, NotePatch, NoteSummary};
+use crate::trash;
+use crate::vault::Vault;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum LinkKind {
+    Note,
+    Attachment,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Broken {
+    /// Nothing with that id or path exists.
+    Missing,
+    /// The target is in the trash and could still be restored.
+    Trashed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BrokenLink {
+    pub note_id: String,
+    pub note_title: String,
+    pub kind: LinkKind,
+    /// Note id or attachment path the link points at.
+    pub target: String,
+    /// The link's text, or an image's alt text.
+    pub label: String,
+    pub reason: Broken,
+    /// The note the link was pointed at instead, when fixing.
+    pub fixed_to: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LinksReport {
+    pub broken: Vec<BrokenLink>,
+    /// Notes no other note links to.
+    pub orphaned_notes: Vec<NoteSummary>,
+    /// Attachments no note links to, trashed notes included.
+    pub orphaned_attachments: Vec<String>,
+    /// Notes rewritten to fix their links.
+    pub fixed_notes: usize,
+}
+
+/// Checks every link in the vault. With `fix`, a link to a note that no
+/// longer exists is pointed at the one live note whose title matches the
+/// link's text, which is what remains of a note deleted and written again
+/// or imported twice.
+pub fn links_report(vault: &Vault, fix: bool) -> Result<LinksReport> {
+    if vault.storage.is_locked() {
+        return Err(Error::VaultLocked);
+    }
+    let summaries = vault.storage.list_notes(None)?;
+    let trashed: HashSet<String> = trash::ids(&vault.storage, None)?.into_iter().collect();
+    let mut by_title: HashMap<String, Vec<&str>> = HashMap::new();
+    for note in &summaries {
+        by_title
+            .entry(note.title.trim().to_lowercase())
+            .or_default()
+            .push(&note.id);
+    }
+
+    let mut report = LinksReport::default();
+    let mut patches = Vec::new();
+    for note in vault.storage.all_notes()? {
+        let mut retargets: HashMap<String, Option<String>> = HashMap::new();
+        for link in labelled_links(&note) {
+            let (kind, target, reason) = match link.dest {
+                Dest::Note(target) => {
+                    let id = vault.links.resolve(&target).unwrap_or(target);
+                    let reason = if trashed.contains(&id) {
+                        Broken::Trashed
+                    } else if vault.storage.has_note(&id)? {
+                        continue;
+                    } else {
+                        Broken::Missing
+                    };
+                    (LinkKind::Note, id, reason)
+                }
+                Dest::Attachment(rel) => {
+                    if vault.files.absolute(&rel).is_file() {
+                        continue;
+                    }
+                    (LinkKind::Attachment, rel, Broken::Missing)
+                }
+            };
+            let fixed_to = match (fix, kind, reason) {
+                (true, LinkKind::Note, Broken::Missing) => {
+                    let candidate = match by_title.get(&link.label.trim().to_lowercase()) {
+                        Some(ids) if ids.len() == 1 && ids[0] != note.id => Some(ids[0].to_owned()),
+                        _ => None,
+                    };
+                    // Links to one old id that would go to different notes
+                    // are left for the user.
+                    let retarget = retargets.entry(target.clone()).or_insert(candidate.clone());
+                    if *retarget != candidate {
+                        *retarget = None;
+                    }
+                    candidate
+                }
+                _ => None,
+            };
+            report.broken.push(BrokenLink {
+                note_id: note.id.clone(),
+                note_title: note.title.clone(),
+                kind,
+                target,
+                label: link.label,
+                reason,
+                fixed_to,
+            });
+        }
+
+        let retargets: HashMap<String, String> = retargets
+            .into_iter()
+            .filter_map(|(from, to)| Some((from, to?)))
+            .collect();
+        for broken in report.broken.iter_mut().filter(|b| b.note_id == note.id) {
+            if broken.fixed_to.is_some() && !retargets.contains_key(&broken.target) {
+                broken.fixed_to = None;
+            }
+        }
+        if let Some(body) = retarget(&note.body, &retargets) {
+            let patch = NotePatch {
+                body: Some(body),
+                ..Default::default()
+            };
+            patches.push((note.id, patch));
+        }
+    }
+    if !patches.is_empty() {
+        report.fixed_notes = patches.len();
+        vault.update_notes(patches, "Fix broken links")?;
+    }
+
+    report.orphaned_notes = summaries
+        .into_iter()
+        .filter(|note| {
+            vault
+                .links
+                .backlinks(&note.id)
+                .iter()
+                .all(|source| *source == note.id)
+        })
+        .collect();
+    report.orphaned_attachments = orphaned_attachments(vault)?;
+    Ok(report)
+}
+
+enum Dest {
+    Note(String),
+    Attachment(String),
+}
+
+struct LabelledLink {
+    dest: Dest,
+    label: String,
+}
+
+/// Note and attachment links in `note`, with their text.
+fn labelled_links(note: &Note) -> Vec<LabelledLink> {
+    let (_, markdown) = FrontMatter::split(&note.body);
+    let mut found = Vec::new();
+    let mut open: Option<(Dest, String)> = None;
+    for event in Parser::new(markdown) {
+        match event {
+            Event::Start(Tag::Link { dest_url, .. } | Tag::Image { dest_url, .. }) => {
+                open = if let Some((id, _)) = links::parse_note_url(&dest_url) {
+                    Some((Dest::Note(id.to_owned()), String::new()))
+                } else {
+                    NoteFiles::resolve_from(&note.folder, &dest_url)
+                        .filter(|rel| NoteFiles::is_attachment_path(rel))
+                        .map(|rel| (Dest::Attachment(rel), String::new()))
+                };
+            }
+            Event::Text(text) | Event::Code(text) => {
+                if let Some((_, label)) = &mut open {
+                    label.push_str(&text);
+                }
+            }
+            Event::End(TagEnd::Link | TagEnd::Image) => {
+                if let Some((dest, label)) = open.take() {
+                    found.push(LabelledLink { dest, label });
+                }
+            }
+            _ => {}
+        }
+    }
+    found
+}
+
+/// `body` with `note://` links to each old id pointed at its new id, or
+/// `None` if none of them occur.
+fn retarget(body: &str, retargets: &HashMap<String, String>) -> Option<String> {
+    let mut body = body.to_owned();
+    let mut changed = false;
+    for (from, to) in retargets {
+        let needle = links::note_url(from, None);
+        let mut out = String::with_capacity(body.len());
+        let mut rest = body.as_str();
+        while let Some(at) = rest.find(&needle) {
+            let after = &rest[at + needle.len()..];
+            out.push_str(&rest[..at]);
+            // Only whole ids, not a longer id starting with this one.
+            if after.starts_with(|c: char| c.is_alphanumeric() || c == '-' || c == '_') {
+                out.push_str(&needle);
+            } else {
+                out.push_str(&links::note_url(to, None));
+                changed = true;
+            }
+            rest = after;
+        }
+        out.push_str(rest);
+        body = out;
+    }
+    changed.then_some(body)
+}
+
+fn orphaned_attachments(vault: &Vault) -> Result<Vec<String>> {
+    let used: HashSet<String> = {
+        let conn = vault.storage.conn();
+        let mut stmt = conn.prepare("SELECT DISTINCT path FROM attachment_refs")?;
+        let paths = stmt
+            .query_map([], |row| row.get(0))?
+            .collect::<rusqlite::Result<_>>()?;
+        paths
+    };
+    let entries = match fs::read_dir(vault.files.attachments_dir()) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err.into()),
+    };
+    let mut orphaned = Vec::new();
+    for entry in entries {
+        let entry = entry?;
+        if !entry.file_type()?.is_file() {
+            continue;
+        }
+        let rel = format!("{ATTACHMENTS_DIR}/{}", entry.file_name().to_string_lossy());
+        if !used.contains(&rel) {
+            orphaned.push(rel);
+        }
+    }
+    orphaned.sort();
+    Ok(orphaned)
+}
@@ -0,0 +1,33 @@
+/// Applies `rewrite` to the parts of `markdown` that are not code: fenced
+/// blocks and inline code spans are copied unchanged.
+pub fn rewrite_prose(markdown: &str, mut rewrite: impl FnMut(&str) -> String) -> String {
+    let mut out = String::with_capacity(markdown.len());
+    let mut fence: Option<&str> = None;
+    for line in markdown.split_inclusive('\n') {
+        let trimmed = line.trim_start();
+        let marker = ["```", "~~~"]
+            .into_iter()
+            .find(|marker| trimmed.starts_with(marker));
+        match (fence, marker) {
+            (None, Some(marker)) => fence = Some(marker),
+            (Some(open), Some(marker)) if open == marker => fence = None,
+            _ => {}
+        }
+        if fence.is_some() || marker.is_some() {
+            out.push_str(line);
+            continue;
+        }
+        // Odd segments between backticks are inline code.
+        for (i, segment) in line.split('`').enumerate() {
+            if i > 0 {
+                out.push('`');
+            }
+            if i % 2 == 1 {
+                out.push_str(segment);
+            } else {
+                out.push_str(&rewrite(segment));
+            }
+        }
+    }
+    out
+}
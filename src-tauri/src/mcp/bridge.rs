@@ -0,0 +1,102 @@
+//! `notesdesktop --mcp --port <port>`: the stdio transport. Clients that
+//! launch their servers as subprocesses talk to this process, which hands
+//! each message to the MCP server of the running app.
+
+use std::io::{self, BufRead, Write};
+
+use reqwest::header::{ACCEPT, CONTENT_TYPE};
+use reqwest::{Client, StatusCode};
+use serde_json::{json, Value};
+
+use super::{PATH, TOKEN_ENV};
+
+/// Runs the bridge if the process was started as one, returning its exit
+/// code once stdin closes. `None` for a normal launch.
+pub fn run_from_args() -> Option<i32> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if !args.iter().any(|arg| arg == "--mcp") {
+        return None;
+    }
+    let port = args
+        .iter()
+        .position(|arg| arg == "--port")
+        .and_then(|i| args.get(i + 1))
+        .and_then(|port| port.parse::<u16>().ok())
+        .unwrap_or(super::McpConfig::default().port);
+    let Ok(token) = std::env::var(TOKEN_ENV) else {
+        eprintln!("{TOKEN_ENV} must hold the access token shown in the app's MCP settings");
+        return Some(2);
+    };
+    Some(
+        match tauri::async_runtime::block_on(forward(port, &token)) {
+            Ok(()) => 0,
+            Err(err) => {
+                eprintln!("{err}");
+                1
+            }
+        },
+    )
+}
+
+/// Relays newline-delimited messages from stdin and writes each reply to
+/// stdout on a line of its own.
+async fn forward(port: u16, token: &str) -> io::Result<()> {
+    let url = format!("http://127.0.0.1:{port}{PATH}");
+    let client = Client::new();
+    let mut stdout = io::stdout().lock();
+    for line in io::stdin().lock().lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let id = serde_json::from_str::<Value>(&line)
+            .ok()
+            .and_then(|request| request.get("id").cloned());
+        let reply = client
+            .post(&url)
+            .bearer_auth(token)
+            .header(CONTENT_TYPE, "application/json")
+            .header(ACCEPT, "application/json")
+            .body(line.clone())
+            .send()
+            .await;
+        let reply = match reply {
+            Ok(response) if response.status() == StatusCode::ACCEPTED => continue,
+            Ok(response) if response.status().is_success() => match response.text().await {
+                Ok(text) => Some(text),
+                Err(err) => failure(id, &err.to_string()),
+            },
+            Ok(response) => {
+                let status = response.status();
+                let detail = response.text().await.unwrap_or_default();
+                let message = serde_json::from_str::<Value>(&detail)
+                    .ok()
+                    .and_then(|detail| detail["error"].as_str().map(str::to_owned))
+                    .unwrap_or_else(|| status.to_string());
+                failure(id, &message)
+            }
+            Err(_) => failure(
+                id,
+                "the notes app is not running, or its MCP server is turned off",
+            ),
+        };
+        let Some(reply) = reply else {
+            continue;
+        };
+        // Replies are JSON objects without newlines of their own.
+        writeln!(stdout, "{}", reply.trim())?;
+        stdout.flush()?;
+    }
+    Ok(())
+}
+
+/// A JSON-RPC error for the request `id`. Notifications, without one, get
+/// none.
+fn failure(id: Option<Value>, message: &str) -> Option<String> {
+    let error = json!({
+        "jsonrpc": "2.0",
+        "id": id?,
+        "error": { "code": -32000, "message": message },
+    });
+    Some(error.to_string())
+}
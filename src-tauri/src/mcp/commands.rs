@@ -0,0 +1,51 @@
+use tauri::{AppHandle, Manager};
+
+use super::{Mcp, McpStatus};
+use crate::error::Result;
+use crate::vaults;
+
+/// Starts the MCP server on `port`, or the last one used, and keeps it on
+/// across restarts. `readOnly` hides the tools that change notes.
+#[tauri::command]
+pub async fn mcp_enable(
+    app: AppHandle,
+    port: Option<u16>,
+    read_only: Option<bool>,
+) -> Result<McpStatus> {
+    let vault = vaults::primary(&app)?;
+    let storage = &vault.storage;
+    let mut config = super::config_of(storage)?;
+    if let Some(port) = port {
+        config.port = port;
+    }
+    if let Some(read_only) = read_only {
+        config.read_only = read_only;
+    }
+    app.state::<Mcp>().start(&app, config.port)?;
+    config.enabled = true;
+    super::set_config(storage, &config)?;
+    super::status(&app)
+}
+
+#[tauri::command]
+pub async fn mcp_disable(app: AppHandle) -> Result<McpStatus> {
+    let vault = vaults::primary(&app)?;
+    let storage = &vault.storage;
+    let mut config = super::config_of(storage)?;
+    config.enabled = false;
+    super::set_config(storage, &config)?;
+    app.state::<Mcp>().stop();
+    super::status(&app)
+}
+
+/// Issues a new access token; clients set up with the old one are refused.
+#[tauri::command]
+pub async fn mcp_rotate_token(app: AppHandle) -> Result<McpStatus> {
+    super::rotate_token(&vaults::primary(&app)?.storage)?;
+    super::status(&app)
+}
+
+#[tauri::command]
+pub async fn mcp_status(app: AppHandle) -> Result<McpStatus> {
+    super::status(&app)
+}
@@ -0,0 +1,225 @@
+#[cfg(desktop)]
+pub mod bridge;
+pub mod commands;
+mod protocol;
+
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tauri::{AppHandle, Manager};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use crate::error::{Error, Result};
+use crate::storage::Storage;
+use crate::{http, vaults};
+
+const CONFIG_KEY: &str = "mcp.config";
+const TOKEN_KEY: &str = "mcp.token";
+const MAX_BODY_BYTES: u64 = 4 * 1024 * 1024;
+/// The path clients post to, as in the streamable HTTP transport.
+pub const PATH: &str = "/mcp";
+/// Environment variable the stdio bridge reads the token from.
+pub const TOKEN_ENV: &str = "NOTES_MCP_TOKEN";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct McpConfig {
+    pub enabled: bool,
+    pub port: u16,
+    /// Leaves out the tools that change notes.
+    pub read_only: bool,
+}
+
+impl Default for McpConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: 27_184,
+            read_only: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct McpStatus {
+    pub running: bool,
+    pub port: u16,
+    pub read_only: bool,
+    pub token: String,
+    /// An `mcpServers` entry for clients that launch servers over stdio,
+    /// such as Claude Desktop, running this app as the bridge.
+    pub client_config: serde_json::Value,
+}
+
+/// The Model Context Protocol server for AI tools, managed as app state.
+/// It serves the primary vault on localhost only, and only while enabled.
+#[derive(Default)]
+pub struct Mcp {
+    running: Mutex<Option<Running>>,
+}
+
+struct Running {
+    server: Arc<Server>,
+    port: u16,
+    thread: JoinHandle<()>,
+}
+
+impl Mcp {
+    /// Starts serving on `127.0.0.1:port`, replacing any running server.
+    pub fn start(&self, app: &AppHandle, port: u16) -> Result<()> {
+        let mut running = self.lock();
+        if let Some(old) = running.take() {
+            old.stop();
+        }
+        let server = Server::http(("127.0.0.1", port))
+            .map_err(|err| Error::InvalidInput(format!("cannot listen on port {port}: {err}")))?;
+        let server = Arc::new(server);
+        let requests = Arc::clone(&server);
+        let app = app.clone();
+        let thread = thread::spawn(move || {
+            for request in requests.incoming_requests() {
+                handle(&app, request);
+            }
+        });
+        *running = Some(Running {
+            server,
+            port,
+            thread,
+        });
+        Ok(())
+    }
+
+    pub fn stop(&self) {
+        if let Some(running) = self.lock().take() {
+            running.stop();
+        }
+    }
+
+    pub fn port(&self) -> Option<u16> {
+        self.lock().as_ref().map(|running| running.port)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Option<Running>> {
+        self.running.lock().expect("mcp state poisoned")
+    }
+}
+
+impl Running {
+    fn stop(self) {
+        self.server.unblock();
+        let _ = self.thread.join();
+    }
+}
+
+pub fn config_of(storage: &Storage) -> Result<McpConfig> {
+    match storage.meta(CONFIG_KEY)? {
+        Some(json) => Ok(serde_json::from_str(&json)?),
+        None => Ok(McpConfig::default()),
+    }
+}
+
+pub fn set_config(storage: &Storage, config: &McpConfig) -> Result<()> {
+    storage.set_meta(CONFIG_KEY, &serde_json::to_string(config)?)
+}
+
+/// The access token, separate from the REST API's so each can be revoked
+/// on its own.
+pub fn token(storage: &Storage) -> Result<String> {
+    match storage.meta(TOKEN_KEY)? {
+        Some(token) => Ok(token),
+        None => rotate_token(storage),
+    }
+}
+
+pub fn rotate_token(storage: &Storage) -> Result<String> {
+    let token = hex::encode(rand::random::<[u8; 32]>());
+    storage.set_meta(TOKEN_KEY, &token)?;
+    Ok(token)
+}
+
+pub fn status(app: &AppHandle) -> Result<McpStatus> {
+    let vault = vaults::primary(app)?;
+    let config = config_of(&vault.storage)?;
+    let running = app.state::<Mcp>().port();
+    let port = running.unwrap_or(config.port);
+    let token = token(&vault.storage)?;
+    let exe = std::env::current_exe()?;
+    Ok(McpStatus {
+        running: running.is_some(),
+        port,
+        read_only: config.read_only,
+        client_config: json!({
+            "notes": {
+                "command": exe.to_string_lossy(),
+                "args": ["--mcp", "--port", port.to_string()],
+                "env": { (TOKEN_ENV): token },
+            }
+        }),
+        token,
+    })
+}
+
+/// Starts the server at launch if it was left enabled.
+pub fn start_if_enabled(app: &AppHandle) -> Result<()> {
+    let config = config_of(&vaults::primary(app)?.storage)?;
+    if config.enabled {
+        app.state::<Mcp>().start(app, config.port)?;
+    }
+    Ok(())
+}
+
+fn handle(app: &AppHandle, mut request: Request) {
+    let (status, body) = match respond(app, &mut request) {
+        Ok(Some(reply)) => (200, reply.to_string()),
+        // Notifications are acknowledged without a body.
+        Ok(None) => (202, String::new()),
+        Err((status, message)) => (status, json!({ "error": message }).to_string()),
+    };
+    let mut response = Response::from_string(body).with_status_code(status);
+    if let Ok(header) = Header::from_bytes("Content-Type", "application/json") {
+        response.add_header(header);
+    }
+    let _ = request.respond(response);
+}
+
+fn respond(
+    app: &AppHandle,
+    request: &mut Request,
+) -> std::result::Result<Option<serde_json::Value>, (u16, String)> {
+    let header = |name: &'static str| {
+        request
+            .headers()
+            .iter()
+            .find(|header| header.field.equiv(name))
+            .map(|header| header.value.as_str().to_owned())
+    };
+    // Web pages could reach a port on localhost through DNS rebinding;
+    // they always send an origin, and MCP clients never do.
+    if header("Origin").is_some() {
+        return Err((403, "requests from web pages are not allowed".into()));
+    }
+    if request.url().split('?').next() != Some(PATH) {
+        return Err((404, "no such endpoint".into()));
+    }
+    if *request.method() != Method::Post {
+        return Err((405, "only POST is supported".into()));
+    }
+    let vault = vaults::primary(app).map_err(|err| (500, err.to_string()))?;
+    let expected = token(&vault.storage).map_err(|err| (500, err.to_string()))?;
+    let given = header("Authorization").unwrap_or_default();
+    let given = given.strip_prefix("Bearer ").unwrap_or_default();
+    if !http::constant_time_eq(given.as_bytes(), expected.as_bytes()) {
+        return Err((401, "missing or wrong access token".into()));
+    }
+    let config = config_of(&vault.storage).map_err(|err| (500, err.to_string()))?;
+    let body = http::read_body(request, MAX_BODY_BYTES)
+        .map_err(|err| (400, err.to_string()))?
+        .ok_or_else(|| {
+            let limit = MAX_BODY_BYTES / (1024 * 1024);
+            (413, format!("requests are limited to {limit} MB"))
+        })?;
+    Ok(protocol::handle(&vault, &config, &body))
+}
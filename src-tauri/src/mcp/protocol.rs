@@ -0,0 +1,281 @@
+//! JSON-RPC messages of the Model Context Protocol and the tools behind
+//! them. Only tools are offered; resources and prompts are not.
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use super::McpConfig;
+use crate::error::{Error, Result};
+use crate::storage::NotePatch;
+use crate::vault::Vault;
+use crate::{protected, trash};
+
+/// Protocol revisions this server speaks, newest first.
+const VERSIONS: &[&str] = &["2025-06-18", "2025-03-26", "2024-11-05"];
+const DEFAULT_SEARCH_LIMIT: usize = 10;
+const MAX_SEARCH_LIMIT: usize = 50;
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+#[derive(Deserialize)]
+struct Request {
+    /// Absent for notifications, which get no reply.
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Deserialize)]
+struct ToolCall {
+    name: String,
+    #[serde(default)]
+    arguments: Value,
+}
+
+#[derive(Deserialize)]
+struct SearchArgs {
+    query: String,
+    limit: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct ReadArgs {
+    id: String,
+}
+
+#[derive(Deserialize)]
+struct CreateArgs {
+    title: String,
+    #[serde(default)]
+    body: String,
+    #[serde(default)]
+    folder: String,
+}
+
+#[derive(Deserialize)]
+struct AppendArgs {
+    id: String,
+    text: String,
+}
+
+/// The reply to one message from a client, or `None` for a notification.
+pub fn handle(vault: &Vault, config: &McpConfig, message: &[u8]) -> Option<Value> {
+    let request: Request = match serde_json::from_slice::<Value>(message) {
+        Err(err) => return Some(error(Value::Null, PARSE_ERROR, &err.to_string())),
+        Ok(Value::Array(_)) => {
+            return Some(error(
+                Value::Null,
+                INVALID_REQUEST,
+                "batches are not supported",
+            ))
+        }
+        Ok(value) => match serde_json::from_value(value) {
+            Ok(request) => request,
+            Err(err) => return Some(error(Value::Null, INVALID_REQUEST, &err.to_string())),
+        },
+    };
+    let id = request.id?;
+    let result = match request.method.as_str() {
+        "initialize" => Ok(initialize(&request.params)),
+        "ping" => Ok(json!({})),
+        "tools/list" => Ok(json!({ "tools": tools(config) })),
+        "tools/call" => match serde_json::from_value::<ToolCall>(request.params) {
+            Ok(call) => Ok(call_tool(vault, config, call)),
+            Err(err) => Err((INVALID_PARAMS, err.to_string())),
+        },
+        method => Err((METHOD_NOT_FOUND, format!("unknown method {method}"))),
+    };
+    Some(match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err((code, message)) => error(id, code, &message),
+    })
+}
+
+fn error(id: Value, code: i64, message: &str) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": code, "message": message },
+    })
+}
+
+fn initialize(params: &Value) -> Value {
+    let asked = params["protocolVersion"].as_str().unwrap_or_default();
+    let version = VERSIONS
+        .iter()
+        .find(|&&version| version == asked)
+        .unwrap_or(&VERSIONS[0]);
+    json!({
+        "protocolVersion": version,
+        "capabilities": { "tools": { "listChanged": false } },
+        "serverInfo": { "name": "notesdesktop", "version": env!("CARGO_PKG_VERSION") },
+        "instructions": "Tools for the user's notes. Notes are Markdown; ids come from \
+            search_notes. Protected and trashed notes cannot be read or changed.",
+    })
+}
+
+fn tools(config: &McpConfig) -> Vec<Value> {
+    let mut tools = vec![
+        json!({
+            "name": "search_notes",
+            "description": "Full-text search over the notes. Returns the id, title and a \
+                matching passage of each hit, best first.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "query": { "type": "string" },
+                    "limit": { "type": "integer", "minimum": 1, "maximum": MAX_SEARCH_LIMIT },
+                },
+                "required": ["query"],
+            },
+            "annotations": { "readOnlyHint": true },
+        }),
+        json!({
+            "name": "read_note",
+            "description": "The Markdown of a note, by id.",
+            "inputSchema": {
+                "type": "object",
+                "properties": { "id": { "type": "string" } },
+                "required": ["id"],
+            },
+            "annotations": { "readOnlyHint": true },
+        }),
+    ];
+    if !config.read_only {
+        tools.push(json!({
+            "name": "create_note",
+            "description": "Creates a note and returns its id. `folder` is a path like \
+                `Projects/Ideas`; empty for the top level.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "title": { "type": "string" },
+                    "body": { "type": "string", "description": "Markdown" },
+                    "folder": { "type": "string" },
+                },
+                "required": ["title"],
+            },
+            "annotations": { "destructiveHint": false },
+        }));
+        tools.push(json!({
+            "name": "append_to_note",
+            "description": "Adds Markdown to the end of a note, as a paragraph of its own.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "id": { "type": "string" },
+                    "text": { "type": "string", "description": "Markdown" },
+                },
+                "required": ["id", "text"],
+            },
+            "annotations": { "destructiveHint": false },
+        }));
+    }
+    tools
+}
+
+/// Failures of a tool go back as its result, flagged, so the model can read
+/// them; protocol errors are for malformed calls.
+fn call_tool(vault: &Vault, config: &McpConfig, call: ToolCall) -> Value {
+    let writes = matches!(call.name.as_str(), "create_note" | "append_to_note");
+    let result = if writes && config.read_only {
+        Err(Error::InvalidInput("the vault is shared read-only".into()))
+    } else {
+        run_tool(vault, &call)
+    };
+    let (text, is_error) = match result {
+        Ok(text) => (text, false),
+        Err(err) => (err.to_string(), true),
+    };
+    json!({
+        "content": [{ "type": "text", "text": text }],
+        "isError": is_error,
+    })
+}
+
+fn run_tool(vault: &Vault, call: &ToolCall) -> Result<String> {
+    match call.name.as_str() {
+        "search_notes" => {
+            let args: SearchArgs = arguments(call)?;
+            let limit = args
+                .limit
+                .unwrap_or(DEFAULT_SEARCH_LIMIT)
+                .clamp(1, MAX_SEARCH_LIMIT);
+            let mut lines = Vec::new();
+            for hit in vault.search_notes(&args.query, limit, false)? {
+                if trash::is_trashed(&vault.storage, &hit.id)? {
+                    continue;
+                }
+                let snippet = hit.snippet.replace("<b>", "").replace("</b>", "");
+                lines.push(format!(
+                    "- {} (id: {})\n  {}",
+                    hit.title,
+                    hit.id,
+                    snippet.trim()
+                ));
+            }
+            Ok(match lines.is_empty() {
+                true => "No notes match.".into(),
+                false => lines.join("\n"),
+            })
+        }
+        "read_note" => {
+            let args: ReadArgs = arguments(call)?;
+            let note = readable(vault, &args.id)?;
+            Ok(format!(
+                "# {}\n\nFolder: {}\n\n{}",
+                note.title,
+                if note.folder.is_empty() {
+                    "/"
+                } else {
+                    &note.folder
+                },
+                note.body
+            ))
+        }
+        "create_note" => {
+            let args: CreateArgs = arguments(call)?;
+            let folder = args.folder.trim_matches('/');
+            let note = vault.create_note(&args.title, &args.body, folder)?;
+            Ok(format!("Created \"{}\" with id {}.", note.title, note.id))
+        }
+        "append_to_note" => {
+            let args: AppendArgs = arguments(call)?;
+            let note = readable(vault, &args.id)?;
+            let body = format!("{}\n\n{}\n", note.body.trim_end(), args.text.trim());
+            vault.edit_note(
+                &note.id,
+                NotePatch {
+                    body: Some(body),
+                    ..Default::default()
+                },
+            )?;
+            Ok(format!("Appended to \"{}\".", note.title))
+        }
+        name => Err(Error::InvalidInput(format!("unknown tool {name}"))),
+    }
+}
+
+/// Note `id` if a client may see it: not in the trash and without a
+/// passphrase of its own, even one unlocked in the app right now.
+fn readable(vault: &Vault, id: &str) -> Result<crate::storage::Note> {
+    if !vault.storage.has_note(id)? || trash::is_trashed(&vault.storage, id)? {
+        return Err(Error::NoteNotFound(id.to_owned()));
+    }
+    let note = vault.storage.get_note(id)?;
+    if protected::is_protected(&note.body) {
+        return Err(Error::InvalidInput(
+            "this note is protected and cannot be shared".into(),
+        ));
+    }
+    Ok(note)
+}
+
+fn arguments<T: serde::de::DeserializeOwned>(call: &ToolCall) -> Result<T> {
+    serde_json::from_value(call.arguments.clone())
+        .map_err(|err| Error::InvalidInput(format!("arguments of {}: {err}", call.name)))
+}
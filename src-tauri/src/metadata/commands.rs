@@ -0,0 +1,35 @@
+use super::index::FieldCount;
+use super::query::QueryResult;
+use super::{MetadataPatch, NoteMetadata};
+use crate::error::Result;
+use crate::vaults::Current;
+
+#[tauri::command]
+pub async fn get_metadata(vault: Current, id: String) -> Result<NoteMetadata> {
+    super::get_metadata(&vault, &id)
+}
+
+/// Changes a note's title, tags, aliases or front matter fields. Fields
+/// not in the patch are left exactly as they are written.
+#[tauri::command]
+pub async fn set_metadata(
+    vault: Current,
+    id: String,
+    patch: MetadataPatch,
+) -> Result<NoteMetadata> {
+    super::set_metadata(&vault, &id, patch)
+}
+
+/// Custom front matter fields in use, for offering them as filters.
+#[tauri::command]
+pub async fn list_metadata_fields(vault: Current) -> Result<Vec<FieldCount>> {
+    Ok(vault.fields.keys())
+}
+
+/// Runs a metadata query, such as
+/// `TABLE rating WHERE tag = 'book' AND rating >= 4 SORT BY modified DESC`.
+/// See [`super::query`] for the language.
+#[tauri::command]
+pub async fn query_metadata(vault: Current, query: String) -> Result<QueryResult> {
+    super::query::run(&vault, &query)
+}
@@ -0,0 +1,124 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use super::{custom_fields, FrontMatter};
+use crate::storage::{FieldFilter, Note};
+
+type Fields = Map<String, Value>;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FieldCount {
+    pub key: String,
+    pub count: usize,
+}
+
+/// Custom front matter fields of every note, so listings can filter by
+/// them. In memory only, like [`crate::tags::TagIndex`].
+#[derive(Default)]
+pub struct FieldIndex {
+    notes: RwLock<HashMap<String, Fields>>,
+}
+
+impl FieldIndex {
+    pub fn index_note(&self, note: &Note) {
+        let fields = extract(&note.body);
+        let mut notes = self.write();
+        if fields.is_empty() {
+            notes.remove(&note.id);
+        } else {
+            notes.insert(note.id.clone(), fields);
+        }
+    }
+
+    pub fn remove_note(&self, id: &str) {
+        self.write().remove(id);
+    }
+
+    pub fn rebuild(&self, notes: &[Note]) {
+        let index = notes
+            .iter()
+            .map(|note| (note.id.clone(), extract(&note.body)))
+            .filter(|(_, fields)| !fields.is_empty())
+            .collect();
+        *self.write() = index;
+    }
+
+    pub fn clear(&self) {
+        self.write().clear();
+    }
+
+    /// The custom fields of note `id`.
+    pub fn of(&self, id: &str) -> Fields {
+        self.read().get(id).cloned().unwrap_or_default()
+    }
+
+    /// Every field key with the number of notes that have it, by name.
+    pub fn keys(&self) -> Vec<FieldCount> {
+        let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+        for key in self.read().values().flat_map(Map::keys) {
+            *counts.entry(key.clone()).or_default() += 1;
+        }
+        counts
+            .into_iter()
+            .map(|(key, count)| FieldCount { key, count })
+            .collect()
+    }
+
+    /// Ids of notes whose fields pass `filter`.
+    pub fn matching(&self, filter: &FieldFilter) -> HashSet<String> {
+        self.read()
+            .iter()
+            .filter(|(_, fields)| {
+                fields.get(&filter.key).is_some_and(|value| {
+                    filter
+                        .value
+                        .as_ref()
+                        .is_none_or(|wanted| matches(value, wanted))
+                })
+            })
+            .map(|(id, _)| id.clone())
+            .collect()
+    }
+
+    fn read(&self) -> RwLockReadGuard<'_, HashMap<String, Fields>> {
+        self.notes.read().expect("field index poisoned")
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, HashMap<String, Fields>> {
+        self.notes.write().expect("field index poisoned")
+    }
+}
+
+fn extract(body: &str) -> Fields {
+    match FrontMatter::split(body) {
+        (Some(front), _) => custom_fields(&front),
+        (None, _) => Fields::new(),
+    }
+}
+
+/// Scalars compare as text, ignoring case, so `2024` finds `"2024"`; a
+/// list matches if any of its items does.
+fn matches(value: &Value, wanted: &Value) -> bool {
+    match (value, wanted) {
+        (Value::Array(items), wanted) if !wanted.is_array() => {
+            items.iter().any(|item| matches(item, wanted))
+        }
+        (value, wanted) => match (text(value), text(wanted)) {
+            (Some(value), Some(wanted)) => value.eq_ignore_ascii_case(&wanted),
+            _ => value == wanted,
+        },
+    }
+}
+
+fn text(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.trim().to_owned()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
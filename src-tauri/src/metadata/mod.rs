@@ -0,0 +1,374 @@
+//! Front matter: the YAML block notes keep their metadata in, read as typed
+//! fields and edited in place.
+
+pub mod commands;
+mod index;
+pub mod query;
+
+pub use index::FieldIndex;
+
+use std::ops::Range;
+
+use serde::{Deserialize, Serialize};
+use serde_yaml_ng::{Mapping, Value};
+
+use crate::error::{Error, Result};
+use crate::storage::{Note, NotePatch};
+use crate::vault::Vault;
+use crate::{protected, tags};
+
+const FENCE: &str = "---";
+/// Keys with a typed place in [`NoteMetadata`], so not among its `fields`.
+const TYPED_KEYS: &[&str] = &["tags", "tag", "aliases", "alias", "created"];
+
+/// The YAML block between `---` lines at the very top of a note body.
+///
+/// Notes keep their metadata there rather than in extra columns, so it
+/// travels with the Markdown through the file mirror and sync.
+#[derive(Debug, Clone, Default)]
+pub struct FrontMatter {
+    pub fields: Mapping,
+}
+
+impl FrontMatter {
+    /// Splits `body` into its front matter and the Markdown after it. A block
+    /// that is unterminated or not a YAML mapping is left in the body.
+    pub fn split(body: &str) -> (Option<Self>, &str) {
+        let Some((yaml, end)) = locate(body) else {
+            return (None, body);
+        };
+        let after = body[end..].trim_start_matches(['\r', '\n']);
+        match serde_yaml_ng::from_str::<Option<Mapping>>(&body[yaml]) {
+            Ok(fields) => (
+                Some(Self {
+                    fields: fields.unwrap_or_default(),
+                }),
+                after,
+            ),
+            Err(_) => (None, body),
+        }
+    }
+
+    pub fn str(&self, key: &str) -> Option<&str> {
+        self.fields.get(key).and_then(Value::as_str)
+    }
+
+    /// Tags listed under `tags` (or `tag`), as a YAML list or a comma or
+    /// space separated string, without any leading `#`.
+    pub fn tags(&self) -> Vec<String> {
+        let Some(value) = self.fields.get("tags").or(self.fields.get("tag")) else {
+            return Vec::new();
+        };
+        let raw: Vec<String> = match value {
+            Value::Sequence(items) => items.iter().filter_map(scalar).collect(),
+            Value::String(s) => s.split([',', ' ']).map(str::to_owned).collect(),
+            other => scalar(other).into_iter().collect(),
+        };
+        raw.iter()
+            .map(|tag| tag.trim().trim_start_matches('#'))
+            .filter(|tag| !tag.is_empty())
+            .map(str::to_owned)
+            .collect()
+    }
+
+    /// Other names of the note under `aliases` (or `alias`), as a YAML list
+    /// or a comma separated string.
+    pub fn aliases(&self) -> Vec<String> {
+        let Some(value) = self.fields.get("aliases").or(self.fields.get("alias")) else {
+            return Vec::new();
+        };
+        let raw: Vec<String> = match value {
+            Value::Sequence(items) => items.iter().filter_map(scalar).collect(),
+            Value::String(s) => s.split(',').map(str::to_owned).collect(),
+            other => scalar(other).into_iter().collect(),
+        };
+        raw.iter()
+            .map(|alias| alias.trim())
+            .filter(|alias| !alias.is_empty())
+            .map(str::to_owned)
+            .collect()
+    }
+
+    /// Replaces the tag list, dropping the key entirely when `tags` is empty.
+    pub fn set_tags(&mut self, tags: &[String]) {
+        self.fields.remove("tag");
+        if tags.is_empty() {
+            self.fields.remove("tags");
+        } else {
+            let list = tags.iter().cloned().map(Value::String).collect();
+            self.fields
+                .insert(Value::String("tags".into()), Value::Sequence(list));
+        }
+    }
+
+    /// Replaces the alias list, dropping the key entirely when `aliases` is
+    /// empty.
+    pub fn set_aliases(&mut self, aliases: &[String]) {
+        self.fields.remove("alias");
+        if aliases.is_empty() {
+            self.fields.remove("aliases");
+        } else {
+            let list = aliases.iter().cloned().map(Value::String).collect();
+            self.fields
+                .insert(Value::String("aliases".into()), Value::Sequence(list));
+        }
+    }
+
+    /// The block with its fences and a blank line after it, or nothing if
+    /// there are no fields, ready to prepend to the rest of a body.
+    pub fn render(&self) -> String {
+        if self.fields.is_empty() {
+            return String::new();
+        }
+        let yaml = serde_yaml_ng::to_string(&self.fields).expect("mappings always serialize");
+        format!("{FENCE}\n{yaml}{FENCE}\n\n")
+    }
+}
+
+/// The YAML between the fences of `body` and where the rest starts, if it
+/// opens with a terminated block.
+fn locate(body: &str) -> Option<(Range<usize>, usize)> {
+    let open = body.strip_prefix(FENCE)?;
+    let rest = open.strip_prefix('\n').or(open.strip_prefix("\r\n"))?;
+    let start = body.len() - rest.len();
+    let mut offset = start;
+    for line in rest.split_inclusive('\n') {
+        if matches!(line.trim_end(), FENCE | "...") {
+            return Some((start..offset, offset + line.len()));
+        }
+        offset += line.len();
+    }
+    None
+}
+
+/// `body` with front matter field `key` set to `value`, or taken out for
+/// `None`. Only the lines of that field change, so the others keep their
+/// order, quoting and comments, which going through [`FrontMatter::render`]
+/// would lose. A block left without fields goes away.
+pub fn set_field(body: &str, key: &str, value: Option<&Value>) -> Result<String> {
+    let Some((yaml, _)) = locate(body) else {
+        return Ok(match value {
+            Some(value) => format!("{FENCE}\n{}{FENCE}\n\n{body}", entry(key, value)),
+            None => body.to_owned(),
+        });
+    };
+    if serde_yaml_ng::from_str::<Option<Mapping>>(&body[yaml.clone()]).is_err() {
+        return Err(Error::InvalidInput(
+            "the front matter is not valid YAML".into(),
+        ));
+    }
+    let existing = entries(body, yaml.clone())
+        .into_iter()
+        .find(|(name, _)| name == key)
+        .map(|(_, span)| span);
+    let replacement = value.map(|value| entry(key, value)).unwrap_or_default();
+    let mut edited = String::with_capacity(body.len() + replacement.len());
+    match existing {
+        Some(span) => {
+            edited.push_str(&body[..span.start]);
+            edited.push_str(&replacement);
+            edited.push_str(&body[span.end..]);
+        }
+        None if value.is_none() => return Ok(body.to_owned()),
+        None => {
+            edited.push_str(&body[..yaml.end]);
+            if !body[..yaml.end].ends_with('\n') {
+                edited.push('\n');
+            }
+            edited.push_str(&replacement);
+            edited.push_str(&body[yaml.end..]);
+        }
+    }
+    // Dropping the block once nothing but blank lines is left of it.
+    if let Some((yaml, end)) = locate(&edited) {
+        if edited[yaml].trim().is_empty() {
+            return Ok(edited[end..].trim_start_matches(['\r', '\n']).to_owned());
+        }
+    }
+    Ok(edited)
+}
+
+/// Each top-level field in the `yaml` range of `body`, by key, with the
+/// lines it takes up: its key line and the indented or list lines under
+/// it, without trailing blank lines.
+fn entries(body: &str, yaml: Range<usize>) -> Vec<(String, Range<usize>)> {
+    let mut found: Vec<(String, Range<usize>)> = Vec::new();
+    let mut offset = yaml.start;
+    for line in body[yaml].split_inclusive('\n') {
+        let range = offset..offset + line.len();
+        offset = range.end;
+        let text = line.trim_end();
+        let continues = text.is_empty()
+            || line.starts_with([' ', '\t'])
+            || text == "-"
+            || text.starts_with("- ");
+        match found.last_mut() {
+            Some((_, span)) if continues => {
+                if !text.is_empty() {
+                    span.end = range.end;
+                }
+            }
+            _ if text.is_empty() || text.starts_with('#') || continues => {}
+            _ => found.push((key_of(text), range)),
+        }
+    }
+    found
+}
+
+/// The key of a field's first line, unquoted.
+fn key_of(line: &str) -> String {
+    if let Ok(Some(mapping)) = serde_yaml_ng::from_str::<Option<Mapping>>(line) {
+        if let Some(key) = mapping.keys().next().and_then(scalar) {
+            return key;
+        }
+    }
+    let key = line.split_once(':').map_or(line, |(key, _)| key).trim();
+    key.trim_matches(['"', '\'']).to_owned()
+}
+
+fn entry(key: &str, value: &Value) -> String {
+    let mut mapping = Mapping::new();
+    mapping.insert(Value::String(key.to_owned()), value.clone());
+    serde_yaml_ng::to_string(&mapping).expect("mappings always serialize")
+}
+
+/// A note's metadata: the columns every note has and its front matter,
+/// with the fields the app knows apart from the rest.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NoteMetadata {
+    pub id: String,
+    pub title: String,
+    pub created_at: i64,
+    pub updated_at: i64,
+    /// Front matter tags; inline `#tags` are not among them.
+    pub tags: Vec<String>,
+    pub aliases: Vec<String>,
+    /// `created` from the front matter, which imported notes often carry.
+    pub created: Option<String>,
+    /// Every other front matter field, as JSON.
+    pub fields: serde_json::Map<String, serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct MetadataPatch {
+    /// Renames the note.
+    pub title: Option<String>,
+    /// Replaces the front matter tags; an empty list removes them.
+    pub tags: Option<Vec<String>>,
+    pub aliases: Option<Vec<String>>,
+    /// Fields to set, `created` included; `null` removes one. Tags and
+    /// aliases go through their own entries.
+    pub fields: serde_json::Map<String, serde_json::Value>,
+}
+
+impl NoteMetadata {
+    pub fn of(note: &Note) -> Self {
+        let (front, _) = FrontMatter::split(&note.body);
+        let front = front.unwrap_or_default();
+        Self {
+            id: note.id.clone(),
+            title: note.title.clone(),
+            created_at: note.created_at,
+            updated_at: note.updated_at,
+            tags: front.tags(),
+            aliases: front.aliases(),
+            created: front.fields.get("created").and_then(scalar),
+            fields: custom_fields(&front),
+        }
+    }
+}
+
+/// The fields of `front` without a typed place, as JSON. Keys that are not
+/// strings, and values JSON cannot hold, are left out.
+pub fn custom_fields(front: &FrontMatter) -> serde_json::Map<String, serde_json::Value> {
+    front
+        .fields
+        .iter()
+        .filter_map(|(key, value)| Some((key.as_str()?, value)))
+        .filter(|(key, _)| !TYPED_KEYS.contains(key))
+        .filter_map(|(key, value)| Some((key.to_owned(), serde_json::to_value(value).ok()?)))
+        .collect()
+}
+
+pub fn get_metadata(vault: &Vault, id: &str) -> Result<NoteMetadata> {
+    Ok(NoteMetadata::of(&protected::read(vault, id)?))
+}
+
+/// Applies `patch` to note `id`, editing its front matter in place.
+pub fn set_metadata(vault: &Vault, id: &str, patch: MetadataPatch) -> Result<NoteMetadata> {
+    let note = protected::read(vault, id)?;
+    let mut body = note.body.clone();
+    if let Some(list) = patch.tags {
+        let list: Vec<String> = list
+            .iter()
+            .map(|tag| tags::normalize(tag))
+            .collect::<Result<_>>()?;
+        body = set_field(&body, "tag", None)?;
+        body = set_field(&body, "tags", sequence(list).as_ref())?;
+    }
+    if let Some(list) = patch.aliases {
+        let list: Vec<String> = list
+            .iter()
+            .map(|alias| alias.trim().to_owned())
+            .filter(|alias| !alias.is_empty())
+            .collect();
+        body = set_field(&body, "alias", None)?;
+        body = set_field(&body, "aliases", sequence(list).as_ref())?;
+    }
+    for (key, value) in patch.fields {
+        let key = key.trim();
+        if key.is_empty() {
+            return Err(Error::InvalidInput("a field needs a name".into()));
+        }
+        if TYPED_KEYS.contains(&key) && key != "created" {
+            return Err(Error::InvalidInput(format!(
+                "set {key} through its own entry, not as a field"
+            )));
+        }
+        let value = match value {
+            serde_json::Value::Null => None,
+            value => Some(
+                serde_yaml_ng::to_value(value)
+                    .map_err(|err| Error::InvalidInput(err.to_string()))?,
+            ),
+        };
+        body = set_field(&body, key, value.as_ref())?;
+    }
+    if body == note.body
+        && patch
+            .title
+            .as_ref()
+            .is_none_or(|title| *title == note.title)
+    {
+        return Ok(NoteMetadata::of(&note));
+    }
+    let note = vault.update_note(
+        id,
+        NotePatch {
+            title: patch.title,
+            body: (body != note.body).then_some(body),
+            ..Default::default()
+        },
+    )?;
+    Ok(NoteMetadata::of(&note))
+}
+
+fn sequence(items: Vec<String>) -> Option<Value> {
+    match items.is_empty() {
+        true => None,
+        false => Some(Value::Sequence(
+            items.into_iter().map(Value::String).collect(),
+        )),
+    }
+}
+
+fn scalar(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
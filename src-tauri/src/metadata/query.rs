@@ -0,0 +1,528 @@
+//! A small query language over note metadata, in the spirit of Dataview:
+//!
+//! ```text
+//! TABLE author, rating WHERE tag = 'book' AND rating >= 4 SORT BY modified DESC LIMIT 20
+//! ```
+//!
+//! Every part is optional, `WHERE` included. Conditions compare a field
+//! with a quoted string, a number or `true`/`false` using `=`, `!=`, `<`,
+//! `<=`, `>`, `>=` or `CONTAINS`, and combine with `AND`, `OR`, `NOT` and
+//! parentheses; a field on its own holds where a note has it. `title`,
+//! `folder`, `created`, `modified` and `tag` are the note's own, with dates
+//! given as `'YYYY-MM-DD'`; any other name is a front matter field, in
+//! backquotes if it has spaces.
+
+use std::cmp::Ordering;
+
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::error::{Error, Result};
+use crate::search::saved::day_start;
+use crate::storage::NoteSummary;
+use crate::vault::Vault;
+
+const MAX_ROWS: usize = 1_000;
+const KEYWORDS: &[&str] = &[
+    "TABLE", "LIST", "WHERE", "AND", "OR", "NOT", "CONTAINS", "SORT", "BY", "ASC", "DESC", "LIMIT",
+];
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueryResult {
+    /// The `TABLE` columns, none for a list.
+    pub columns: Vec<String>,
+    pub rows: Vec<QueryRow>,
+    /// Notes that matched, before `LIMIT`.
+    pub total: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueryRow {
+    pub id: String,
+    pub title: String,
+    pub folder: String,
+    pub created_at: i64,
+    pub updated_at: i64,
+    /// One per column, `null` where the note has no such field.
+    pub values: Vec<Value>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    /// A backquoted field name, never a keyword.
+    Field(String),
+    Text(String),
+    Number(f64),
+    Op(&'static str),
+    Open,
+    Close,
+    Comma,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Contains,
+}
+
+#[derive(Debug)]
+enum Expr {
+    Has(String),
+    Compare(String, Op, Value),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+}
+
+#[derive(Debug)]
+struct Query {
+    columns: Vec<String>,
+    filter: Option<Expr>,
+    /// Fields with whether they sort descending.
+    sort: Vec<(String, bool)>,
+    limit: Option<usize>,
+}
+
+/// A note as queries see it.
+struct Record {
+    note: NoteSummary,
+    tags: Vec<String>,
+    fields: Map<String, Value>,
+}
+
+pub fn run(vault: &Vault, source: &str) -> Result<QueryResult> {
+    if vault.storage.is_locked() {
+        return Err(Error::VaultLocked);
+    }
+    let query = parse(source)?;
+    let mut records: Vec<Record> = vault
+        .storage
+        .list_notes(None)?
+        .into_iter()
+        .map(|note| Record {
+            tags: vault.tags.of(&note.id),
+            fields: vault.fields.of(&note.id),
+            note,
+        })
+        .filter(|record| query.filter.as_ref().is_none_or(|e| record.eval(e)))
+        .collect();
+    // Notes come most recently edited first, which stands when nothing else is asked.
+    records.sort_by(|a, b| {
+        query
+            .sort
+            .iter()
+            .map(|(field, descending)| sort_order(&a.get(field), &b.get(field), *descending))
+            .find(|order| order.is_ne())
+            .unwrap_or(Ordering::Equal)
+    });
+    let total = records.len();
+    let rows = records
+        .into_iter()
+        .take(query.limit.unwrap_or(MAX_ROWS).min(MAX_ROWS))
+        .map(|record| QueryRow {
+            values: query.columns.iter().map(|c| record.get(c)).collect(),
+            id: record.note.id,
+            title: record.note.title,
+            folder: record.note.folder,
+            created_at: record.note.created_at,
+            updated_at: record.note.updated_at,
+        })
+        .collect();
+    Ok(QueryResult {
+        columns: query.columns,
+        rows,
+        total,
+    })
+}
+
+impl Record {
+    fn get(&self, name: &str) -> Value {
+        match name.to_ascii_lowercase().as_str() {
+            "title" => Value::from(self.note.title.as_str()),
+            "folder" => Value::from(self.note.folder.as_str()),
+            "created" => Value::from(self.note.created_at),
+            "modified" | "updated" => Value::from(self.note.updated_at),
+            "tag" | "tags" => Value::from(self.tags.clone()),
+            _ => self
+                .fields
+                .get(name)
+                .or_else(|| {
+                    self.fields
+                        .iter()
+                        .find(|(key, _)| key.eq_ignore_ascii_case(name))
+                        .map(|(_, value)| value)
+                })
+                .cloned()
+                .unwrap_or(Value::Null),
+        }
+    }
+
+    fn eval(&self, expr: &Expr) -> bool {
+        match expr {
+            Expr::Has(field) => match self.get(field) {
+                Value::Null => false,
+                Value::Array(items) => !items.is_empty(),
+                _ => true,
+            },
+            Expr::Compare(field, op, literal) => {
+                compare(&self.get(field), *op, &coerce(field, literal))
+            }
+            Expr::Not(inner) => !self.eval(inner),
+            Expr::And(a, b) => self.eval(a) && self.eval(b),
+            Expr::Or(a, b) => self.eval(a) || self.eval(b),
+        }
+    }
+}
+
+/// `literal` in the terms of `field`: dates become timestamps for the
+/// note's times, and tags lose a leading `#`.
+fn coerce(field: &str, literal: &Value) -> Value {
+    let Some(text) = literal.as_str() else {
+        return literal.clone();
+    };
+    match field.to_ascii_lowercase().as_str() {
+        "created" | "modified" | "updated" => match day_start(Some(text), 0) {
+            Ok(Some(millis)) => Value::from(millis),
+            _ => literal.clone(),
+        },
+        "tag" | "tags" => Value::from(text.trim_start_matches('#')),
+        _ => literal.clone(),
+    }
+}
+
+/// Missing fields only pass `!=`; a list passes if one of its items does,
+/// or for `!=` if none equals the literal.
+fn compare(value: &Value, op: Op, literal: &Value) -> bool {
+    match value {
+        Value::Null => op == Op::Ne,
+        Value::Array(items) => match op {
+            Op::Ne => !items.iter().any(|item| compare(item, Op::Eq, literal)),
+            Op::Contains => items.iter().any(|item| compare(item, Op::Eq, literal)),
+            op => items.iter().any(|item| compare(item, op, literal)),
+        },
+        value => match op {
+            Op::Contains => match (text(value), text(literal)) {
+                (Some(value), Some(part)) => value.to_lowercase().contains(&part.to_lowercase()),
+                _ => false,
+            },
+            op => {
+                let order = order(value, literal);
+                match op {
+                    Op::Eq => order == Some(Ordering::Equal),
+                    Op::Ne => order != Some(Ordering::Equal),
+                    Op::Lt => order == Some(Ordering::Less),
+                    Op::Le => matches!(order, Some(Ordering::Less | Ordering::Equal)),
+                    Op::Gt => order == Some(Ordering::Greater),
+                    Op::Ge => matches!(order, Some(Ordering::Greater | Ordering::Equal)),
+                    Op::Contains => unreachable!(),
+                }
+            }
+        },
+    }
+}
+
+/// Numbers, and strings that are numbers, compare by value; other scalars
+/// as text, ignoring case.
+fn order(a: &Value, b: &Value) -> Option<Ordering> {
+    if let (Some(a), Some(b)) = (number(a), number(b)) {
+        return a.partial_cmp(&b);
+    }
+    match (text(a), text(b)) {
+        (Some(a), Some(b)) => Some(a.to_lowercase().cmp(&b.to_lowercase())),
+        _ => None,
+    }
+}
+
+/// Notes without the field go last either way; lists sort by their first
+/// item.
+fn sort_order(a: &Value, b: &Value, descending: bool) -> Ordering {
+    let first = |value: &Value| match value {
+        Value::Array(items) => items.first().cloned().unwrap_or(Value::Null),
+        value => value.clone(),
+    };
+    match (first(a), first(b)) {
+        (Value::Null, Value::Null) => Ordering::Equal,
+        (Value::Null, _) => Ordering::Greater,
+        (_, Value::Null) => Ordering::Less,
+        (a, b) => {
+            let order = order(&a, &b).unwrap_or(Ordering::Equal);
+            match descending {
+                true => order.reverse(),
+                false => order,
+            }
+        }
+    }
+}
+
+fn number(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => s.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+fn text(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.trim().to_owned()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+fn parse(source: &str) -> Result<Query> {
+    let mut parser = Parser {
+        tokens: tokenize(source)?,
+        at: 0,
+    };
+    parser.query()
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = source.char_indices().peekable();
+    while let Some(&(start, c)) = chars.peek() {
+        chars.next();
+        let token = match c {
+            c if c.is_whitespace() => continue,
+            '(' => Token::Open,
+            ')' => Token::Close,
+            ',' => Token::Comma,
+            '\'' | '"' | '`' => {
+                let mut text = String::new();
+                loop {
+                    match chars.next() {
+                        // A doubled quote stands for itself.
+                        Some((_, ch)) if ch == c => match chars.next_if(|&(_, n)| n == c) {
+                            Some(_) => text.push(c),
+                            None => break,
+                        },
+                        Some((_, ch)) => text.push(ch),
+                        None => return Err(invalid(format!("unterminated quote at {start}"))),
+                    }
+                }
+                match c {
+                    '`' => Token::Field(text),
+                    _ => Token::Text(text),
+                }
+            }
+            '=' | '!' | '<' | '>' => {
+                let equals = chars.next_if(|&(_, n)| n == '=').is_some();
+                Token::Op(match (c, equals) {
+                    ('=', _) => "=",
+                    ('!', true) => "!=",
+                    ('<', false) => "<",
+                    ('<', true) => "<=",
+                    ('>', false) => ">",
+                    ('>', true) => ">=",
+                    _ => return Err(invalid(format!("expected != at {start}"))),
+                })
+            }
+            c if c.is_ascii_digit() || c == '-' => {
+                let mut number = String::from(c);
+                while let Some((_, d)) = chars.next_if(|&(_, d)| d.is_ascii_digit() || d == '.') {
+                    number.push(d);
+                }
+                Token::Number(
+                    number
+                        .parse()
+                        .map_err(|_| invalid(format!("not a number: {number}")))?,
+                )
+            }
+            c if c.is_alphanumeric() || c == '_' => {
+                let mut word = String::from(c);
+                while let Some((_, d)) = chars
+                    .next_if(|&(_, d)| d.is_alphanumeric() || matches!(d, '_' | '-' | '.' | '/'))
+                {
+                    word.push(d);
+                }
+                Token::Word(word)
+            }
+            other => return Err(invalid(format!("unexpected {other:?} at {start}"))),
+        };
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    at: usize,
+}
+
+impl Parser {
+    fn query(&mut self) -> Result<Query> {
+        let mut columns = Vec::new();
+        if self.keyword("TABLE") {
+            loop {
+                columns.push(self.field()?);
+                if !self.eat(&Token::Comma) {
+                    break;
+                }
+            }
+        } else {
+            self.keyword("LIST");
+        }
+        self.keyword("WHERE");
+        let filter = match self.peek() {
+            None => None,
+            Some(Token::Word(word)) if is(word, "SORT") || is(word, "LIMIT") => None,
+            Some(_) => Some(self.or()?),
+        };
+        let mut sort = Vec::new();
+        if self.keyword("SORT") {
+            if !self.keyword("BY") {
+                return Err(self.unexpected("BY"));
+            }
+            loop {
+                let field = self.field()?;
+                let descending = self.keyword("DESC");
+                if !descending {
+                    self.keyword("ASC");
+                }
+                sort.push((field, descending));
+                if !self.eat(&Token::Comma) {
+                    break;
+                }
+            }
+        }
+        let mut limit = None;
+        if self.keyword("LIMIT") {
+            match self.peek() {
+                Some(Token::Number(n)) if *n >= 0.0 && n.fract() == 0.0 => {
+                    limit = Some(*n as usize);
+                    self.at += 1;
+                }
+                _ => return Err(self.unexpected("a count")),
+            }
+        }
+        match self.peek() {
+            None => Ok(Query {
+                columns,
+                filter,
+                sort,
+                limit,
+            }),
+            Some(_) => Err(self.unexpected("the end of the query")),
+        }
+    }
+
+    fn or(&mut self) -> Result<Expr> {
+        let mut expr = self.and()?;
+        while self.keyword("OR") {
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr> {
+        let mut expr = self.not()?;
+        while self.keyword("AND") {
+            expr = Expr::And(Box::new(expr), Box::new(self.not()?));
+        }
+        Ok(expr)
+    }
+
+    fn not(&mut self) -> Result<Expr> {
+        match self.keyword("NOT") {
+            true => Ok(Expr::Not(Box::new(self.not()?))),
+            false => self.primary(),
+        }
+    }
+
+    fn primary(&mut self) -> Result<Expr> {
+        if self.eat(&Token::Open) {
+            let expr = self.or()?;
+            if !self.eat(&Token::Close) {
+                return Err(self.unexpected(")"));
+            }
+            return Ok(expr);
+        }
+        let field = self.field()?;
+        let op = match self.peek() {
+            Some(Token::Op(op)) => match *op {
+                "=" => Op::Eq,
+                "!=" => Op::Ne,
+                "<" => Op::Lt,
+                "<=" => Op::Le,
+                ">" => Op::Gt,
+                _ => Op::Ge,
+            },
+            Some(Token::Word(word)) if is(word, "CONTAINS") => Op::Contains,
+            _ => return Ok(Expr::Has(field)),
+        };
+        self.at += 1;
+        let literal = match self.peek() {
+            Some(Token::Text(text)) => Value::from(text.as_str()),
+            Some(Token::Number(n)) => Value::from(*n),
+            Some(Token::Word(word)) if is(word, "true") => Value::Bool(true),
+            Some(Token::Word(word)) if is(word, "false") => Value::Bool(false),
+            _ => return Err(self.unexpected("a quoted string, number or true/false")),
+        };
+        self.at += 1;
+        Ok(Expr::Compare(field, op, literal))
+    }
+
+    fn field(&mut self) -> Result<String> {
+        let field = match self.peek() {
+            Some(Token::Word(word)) if !KEYWORDS.iter().any(|k| is(word, k)) => word.clone(),
+            Some(Token::Field(field)) => field.clone(),
+            _ => return Err(self.unexpected("a field")),
+        };
+        self.at += 1;
+        Ok(field)
+    }
+
+    fn keyword(&mut self, keyword: &str) -> bool {
+        match self.peek() {
+            Some(Token::Word(word)) if is(word, keyword) => {
+                self.at += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        let found = self.peek() == Some(token);
+        if found {
+            self.at += 1;
+        }
+        found
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.at)
+    }
+
+    fn unexpected(&self, expected: &str) -> Error {
+        let found = match self.peek() {
+            None => "the end".to_owned(),
+            Some(Token::Word(word) | Token::Field(word)) => word.clone(),
+            Some(Token::Text(text)) => format!("'{text}'"),
+            Some(Token::Number(n)) => n.to_string(),
+            Some(Token::Op(op)) => (*op).to_owned(),
+            Some(Token::Open) => "(".into(),
+            Some(Token::Close) => ")".into(),
+            Some(Token::Comma) => ",".into(),
+        };
+        invalid(format!("expected {expected}, found {found}"))
+    }
+}
+
+fn is(word: &str, keyword: &str) -> bool {
+    word.eq_ignore_ascii_case(keyword)
+}
+
+fn invalid(message: String) -> Error {
+    Error::InvalidInput(format!("query: {message}"))
+}
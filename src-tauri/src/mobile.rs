@@ -1,7 +1,193 @@
+use std::sync::{Condvar, Mutex, OnceLock};
+use std::time::Duration;
+
 use notesdesktop_lib;
+use tauri::AppHandle;
+
+static APP: OnceLock<AppHandle> = OnceLock::new();
+/// Set and signalled once [`APP`] is, for a background task that woke the
+/// process before the app was up.
+static ATTACHED: (Mutex<bool>, Condvar) = (Mutex::new(false), Condvar::new());
+/// How long a background task waits for the app to come up.
+const APP_WAIT: Duration = Duration::from_secs(20);
 
 /// Mobile entry point — called by the Tauri runtime on iOS / Android.
 #[tauri::mobile_entry_point]
 pub fn mobile_main() {
     notesdesktop_lib::run();
 }
+
+/// Makes the app reachable from the platform code, once setup is done.
+pub(crate) fn attach(app: &AppHandle) {
+    let _ = APP.set(app.clone());
+    let (attached, signal) = &ATTACHED;
+    *attached.lock().expect("attached flag poisoned") = true;
+    signal.notify_all();
+    // Saves shares that arrived while the app was starting.
+    crate::share::start();
+}
+
+/// The running app, `None` while it starts up or when a background task
+/// woke the process without it.
+pub(crate) fn app() -> Option<&'static AppHandle> {
+    APP.get()
+}
+
+/// The device's position, asking for permission if it has not been given
+/// or refused yet. A position up to a minute old will do.
+pub(crate) fn current_location(app: &AppHandle) -> Option<crate::places::Location> {
+    use tauri::plugin::PermissionState;
+    use tauri_plugin_geolocation::{GeolocationExt, PermissionType, PositionOptions};
+
+    let geolocation = app.geolocation();
+    let mut status = geolocation.check_permissions().ok()?;
+    if matches!(
+        status.location,
+        PermissionState::Prompt | PermissionState::PromptWithRationale
+    ) {
+        status = geolocation
+            .request_permissions(Some(vec![PermissionType::Location]))
+            .ok()?;
+    }
+    if status.location != PermissionState::Granted {
+        return None;
+    }
+    let options = PositionOptions {
+        enable_high_accuracy: false,
+        timeout: 10_000,
+        maximum_age: 60_000,
+    };
+    match geolocation.get_current_position(Some(options)) {
+        Ok(position) => {
+            crate::places::Location::new(position.coords.latitude, position.coords.longitude)
+        }
+        Err(err) => {
+            tracing::debug!(error = %err, "no position");
+            None
+        }
+    }
+}
+
+/// The running app, waiting up to [`APP_WAIT`] for it to start.
+fn wait_for_app() -> Option<&'static AppHandle> {
+    let (attached, signal) = &ATTACHED;
+    let attached = attached.lock().ok()?;
+    drop(
+        signal
+            .wait_timeout_while(attached, APP_WAIT, |attached| !*attached)
+            .ok()?,
+    );
+    app()
+}
+
+/// Syncs in the background and says whether it went well; nothing to do
+/// counts as success. Blocks until done, so the platform keeps the
+/// process up for it. The platform launches the app for the task; if it
+/// does not come up in time, that is a failure, so the task is tried
+/// again rather than counted as a sync.
+fn run_background_sync() -> bool {
+    let Some(app) = wait_for_app() else {
+        tracing::warn!("background sync woke before the app came up");
+        return false;
+    };
+    tauri::async_runtime::block_on(crate::sync::background::run(app)).is_ok()
+}
+
+/// The background sync schedule as JSON, for the platform scheduler.
+fn background_sync_schedule() -> Option<String> {
+    let schedule = crate::sync::background::schedule(app()?).ok()?;
+    serde_json::to_string(&schedule).ok()
+}
+
+/// Hands a share's JSON from the platform code to [`crate::share`]. Bad
+/// payloads are dropped, since there is nobody to tell yet.
+fn receive_share(json: &str) {
+    if let Ok(payload) = serde_json::from_str(json) {
+        crate::share::submit(payload);
+    }
+}
+
+/// `MainActivity.receiveShare`, called for `ACTION_SEND` and
+/// `ACTION_SEND_MULTIPLE` intents.
+#[cfg(target_os = "android")]
+#[no_mangle]
+pub extern "system" fn Java_com_saentis_1notes_app_MainActivity_receiveShare(
+    mut env: jni::JNIEnv,
+    _class: jni::objects::JClass,
+    json: jni::objects::JString,
+) {
+    if let Ok(json) = env.get_string(&json) {
+        receive_share(&String::from(json));
+    }
+}
+
+/// `SyncWorker.runBackgroundSync`, called by WorkManager.
+#[cfg(target_os = "android")]
+#[no_mangle]
+pub extern "system" fn Java_com_saentis_1notes_app_SyncWorker_runBackgroundSync(
+    _env: jni::JNIEnv,
+    _class: jni::objects::JClass,
+) -> jni::sys::jboolean {
+    jni::sys::jboolean::from(run_background_sync())
+}
+
+/// `SyncWorker.backgroundSyncSchedule`: a [`crate::sync::background::Schedule`]
+/// as JSON, or null while the app is not up.
+#[cfg(target_os = "android")]
+#[no_mangle]
+pub extern "system" fn Java_com_saentis_1notes_app_SyncWorker_backgroundSyncSchedule(
+    env: jni::JNIEnv,
+    _class: jni::objects::JClass,
+) -> jni::sys::jstring {
+    background_sync_schedule()
+        .and_then(|json| env.new_string(json).ok())
+        .map_or(std::ptr::null_mut(), |json| json.into_raw())
+}
+
+/// Run by the `BGProcessingTask` registered as `com.saentis-notes.app.sync`.
+/// Blocks until the sync is done.
+#[cfg(target_os = "ios")]
+#[no_mangle]
+pub extern "C" fn notes_run_background_sync() -> bool {
+    run_background_sync()
+}
+
+/// The background sync schedule as JSON for the next `BGProcessingTaskRequest`,
+/// or null while the app is not up. Free it with [`notes_free_string`].
+#[cfg(target_os = "ios")]
+#[no_mangle]
+pub extern "C" fn notes_background_sync_schedule() -> *mut std::ffi::c_char {
+    background_sync_schedule()
+        .and_then(|json| std::ffi::CString::new(json).ok())
+        .map_or(std::ptr::null_mut(), std::ffi::CString::into_raw)
+}
+
+/// Frees a string returned by one of the functions above.
+///
+/// # Safety
+///
+/// `string` must be null or come from this library, and not be used again.
+#[cfg(target_os = "ios")]
+#[no_mangle]
+pub unsafe extern "C" fn notes_free_string(string: *mut std::ffi::c_char) {
+    if !string.is_null() {
+        drop(std::ffi::CString::from_raw(string));
+    }
+}
+
+/// Called by the share extension with a NUL-terminated UTF-8 payload.
+///
+/// # Safety
+///
+/// `json` must be null or point to a NUL-terminated string that stays
+/// valid for the call.
+#[cfg(target_os = "ios")]
+#[no_mangle]
+pub unsafe extern "C" fn notes_receive_share(json: *const std::ffi::c_char) {
+    if json.is_null() {
+        return;
+    }
+    if let Ok(json) = std::ffi::CStr::from_ptr(json).to_str() {
+        receive_share(json);
+    }
+}
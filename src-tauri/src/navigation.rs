@@ -0,0 +1,77 @@
+use std::path::{Path, PathBuf};
+
+use tauri::{AppHandle, Emitter, Manager, WebviewWindow, WebviewWindowBuilder};
+
+use crate::error::{Error, Result};
+
+/// Event sent to the main window with the id of a note to show.
+pub const OPEN_NOTE_EVENT: &str = "open-note";
+/// Event sent to the main window with the absolute path of a Markdown file
+/// the app was asked to open.
+pub const OPEN_FILE_EVENT: &str = "open-file";
+/// Event sent to the main window with a query to run in the search view.
+pub const SEARCH_EVENT: &str = "open-search";
+
+pub const MAIN_WINDOW: &str = "main";
+
+/// Brings up the main window, reopening it if it was closed, and asks it to
+/// show the note.
+pub fn open_note(app: &AppHandle, id: &str) -> Result<()> {
+    show_main(app)?.emit(OPEN_NOTE_EVENT, id)?;
+    Ok(())
+}
+
+/// Brings up the main window on the search view with `query` filled in.
+pub fn open_search(app: &AppHandle, query: &str) -> Result<()> {
+    show_main(app)?.emit(SEARCH_EVENT, query)?;
+    Ok(())
+}
+
+/// Brings up the main window and hands it a file to open.
+pub fn open_file(app: &AppHandle, path: &Path) -> Result<()> {
+    show_main(app)?.emit(OPEN_FILE_EVENT, path)?;
+    Ok(())
+}
+
+/// The Markdown files among command-line arguments, made absolute against
+/// `cwd`, the directory the launch happened in. The program path and any
+/// flags or links are skipped.
+pub fn markdown_args(args: &[String], cwd: &Path) -> Vec<PathBuf> {
+    args.iter()
+        .skip(1)
+        .filter(|arg| !arg.starts_with('-') && !arg.contains("://"))
+        .map(Path::new)
+        .filter(|path| {
+            path.extension()
+                .and_then(|ext| ext.to_str())
+                .is_some_and(|ext| {
+                    ext.eq_ignore_ascii_case("md") || ext.eq_ignore_ascii_case("markdown")
+                })
+        })
+        .map(|path| cwd.join(path))
+        .collect()
+}
+
+pub fn show_main(app: &AppHandle) -> Result<WebviewWindow> {
+    let window = main_window(app)?;
+    window.show()?;
+    window.unminimize()?;
+    window.set_focus()?;
+    Ok(window)
+}
+
+fn main_window(app: &AppHandle) -> Result<WebviewWindow> {
+    if let Some(window) = app.get_webview_window(MAIN_WINDOW) {
+        return Ok(window);
+    }
+    let config = app
+        .config()
+        .app
+        .windows
+        .iter()
+        .find(|window| window.label == MAIN_WINDOW)
+        .ok_or_else(|| Error::InvalidInput("no main window configured".into()))?;
+    let window = WebviewWindowBuilder::from_config(app, config)?.build()?;
+    crate::window_state::fit(&window)?;
+    Ok(window)
+}
@@ -0,0 +1,23 @@
+use tauri::{AppHandle, Manager, Window};
+
+use super::{NoteWindow, NoteWindows};
+use crate::error::Result;
+use crate::vaults::Vaults;
+
+/// Opens a note of the calling window's vault in a window of its own, or
+/// brings that window forward if there is one.
+#[tauri::command]
+pub async fn open_note_window(
+    app: AppHandle,
+    window: Window,
+    note_id: String,
+) -> Result<NoteWindow> {
+    let vault_id = app.state::<Vaults>().id_of(window.label());
+    super::open(&app, &vault_id, &note_id)
+}
+
+/// Every detached note window, with the vault and note it shows.
+#[tauri::command]
+pub async fn list_note_windows(app: AppHandle) -> Result<Vec<NoteWindow>> {
+    Ok(app.state::<NoteWindows>().list())
+}
@@ -0,0 +1,159 @@
+pub mod commands;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, WebviewUrl, WebviewWindowBuilder, WindowEvent};
+
+use crate::error::{Error, Result};
+use crate::trash;
+use crate::vaults::Vaults;
+use crate::window_state;
+
+const LABEL_PREFIX: &str = "note-";
+const STATE_FILE: &str = "windows.json";
+const WIDTH: f64 = 720.0;
+const HEIGHT: f64 = 640.0;
+
+/// A note shown in a window of its own.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NoteWindow {
+    pub label: String,
+    pub vault_id: String,
+    pub note_id: String,
+}
+
+/// The detached note windows that are open, managed as app state and saved
+/// to `windows.json` so the set comes back on the next launch.
+pub struct NoteWindows {
+    file: PathBuf,
+    open: Mutex<Vec<NoteWindow>>,
+}
+
+impl NoteWindows {
+    pub fn new(app_dir: &Path) -> Self {
+        Self {
+            file: app_dir.join(STATE_FILE),
+            open: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn list(&self) -> Vec<NoteWindow> {
+        self.lock().clone()
+    }
+
+    fn add(&self, window: NoteWindow) -> Result<()> {
+        let mut open = self.lock();
+        open.retain(|known| known.label != window.label);
+        open.push(window);
+        self.save(&open)
+    }
+
+    fn forget(&self, label: &str) -> Result<()> {
+        let mut open = self.lock();
+        open.retain(|window| window.label != label);
+        self.save(&open)
+    }
+
+    /// The windows that were open when the app last quit.
+    fn saved(&self) -> Result<Vec<NoteWindow>> {
+        match fs::read_to_string(&self.file) {
+            Ok(json) => Ok(serde_json::from_str(&json)?),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn save(&self, open: &[NoteWindow]) -> Result<()> {
+        fs::write(&self.file, serde_json::to_string_pretty(open)?)?;
+        Ok(())
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Vec<NoteWindow>> {
+        self.open.lock().expect("note windows poisoned")
+    }
+}
+
+/// Shows note `note_id` of vault `vault_id` in a window of its own,
+/// focusing the one it already has.
+pub fn open(app: &AppHandle, vault_id: &str, note_id: &str) -> Result<NoteWindow> {
+    let vault = app.state::<Vaults>().get(app, vault_id)?;
+    let note = vault.storage.get_note(note_id)?;
+    if trash::is_trashed(&vault.storage, note_id)? {
+        return Err(Error::InvalidInput("note is in the trash".into()));
+    }
+    let window = NoteWindow {
+        label: label_for(vault_id, note_id),
+        vault_id: vault_id.to_owned(),
+        note_id: note_id.to_owned(),
+    };
+    if let Some(existing) = app.get_webview_window(&window.label) {
+        existing.unminimize()?;
+        existing.set_focus()?;
+        return Ok(window);
+    }
+
+    app.state::<Vaults>().bind(app, &window.label, vault_id)?;
+    let query: String = url::form_urlencoded::Serializer::new(String::new())
+        .append_pair("id", note_id)
+        .finish();
+    let title = if note.title.trim().is_empty() {
+        "Untitled"
+    } else {
+        &note.title
+    };
+    let built = WebviewWindowBuilder::new(
+        app,
+        &window.label,
+        WebviewUrl::App(format!("note?{query}").into()),
+    )
+    .title(title)
+    .inner_size(WIDTH, HEIGHT)
+    .build()?;
+    window_state::fit(&built)?;
+
+    let handle = app.clone();
+    let label = window.label.clone();
+    // Closing at quit does not come through here, so the set survives it.
+    built.on_window_event(move |event| {
+        if let WindowEvent::CloseRequested { .. } = event {
+            let _ = window_state::save(&handle);
+            let _ = handle.state::<NoteWindows>().forget(&label);
+            let _ = handle.state::<Vaults>().unbind(&label);
+        }
+    });
+    app.state::<NoteWindows>().add(window.clone())?;
+    Ok(window)
+}
+
+/// Reopens the windows left open at the last quit, skipping notes that are
+/// gone since.
+pub fn restore(app: &AppHandle) -> Result<()> {
+    let windows = app.state::<NoteWindows>();
+    let saved = windows.saved()?;
+    windows.save(&[])?;
+    for window in saved {
+        let _ = open(app, &window.vault_id, &window.note_id);
+    }
+    Ok(())
+}
+
+/// Window labels only allow a few characters; anything else in an id is
+/// folded into `_`.
+fn label_for(vault_id: &str, note_id: &str) -> String {
+    let safe = |id: &str| -> String {
+        id.chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '-' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect()
+    };
+    format!("{LABEL_PREFIX}{}:{}", safe(vault_id), safe(note_id))
+}
@@ -0,0 +1,28 @@
+use super::{OcrConfig, OcrStatus};
+use crate::error::Result;
+use crate::vaults::Current;
+
+#[tauri::command]
+pub async fn ocr_status(vault: Current, note_id: String) -> Result<OcrStatus> {
+    super::status(&vault, &note_id)
+}
+
+/// Turns OCR on or off or changes its language. Without arguments, returns
+/// the current settings. Turning it on starts on any unread images.
+#[tauri::command]
+pub async fn configure_ocr(
+    vault: Current,
+    enabled: Option<bool>,
+    language: Option<String>,
+) -> Result<OcrConfig> {
+    let mut config = super::config_of(&vault.storage)?;
+    if let Some(enabled) = enabled {
+        config.enabled = enabled;
+    }
+    if let Some(language) = language {
+        config.language = language;
+    }
+    super::set_config(&vault.storage, &config)?;
+    vault.ocr.wake();
+    Ok(config)
+}
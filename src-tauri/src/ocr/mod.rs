@@ -0,0 +1,175 @@
+pub mod commands;
+
+use std::path::Path;
+use std::process::Command;
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::attachments::text::{self, AttachmentText};
+use crate::error::{Error, Result};
+use crate::jobs::{JobContext, JobKind, Jobs, Priority};
+use crate::storage::Storage;
+use crate::vault::Vault;
+
+/// Event carrying the [`AttachmentText`] of each image once it is read.
+pub const DONE_EVENT: &str = "ocr-done";
+/// Event carrying the message of a batch that could not run.
+pub const ERROR_EVENT: &str = "ocr-error";
+
+const CONFIG_KEY: &str = "ocr.config";
+const TESSERACT: &str = "tesseract";
+const IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "gif", "bmp", "webp", "tif", "tiff"];
+/// How long the worker sleeps when nothing wakes it, so images left over
+/// from a failed batch are retried eventually.
+const IDLE: Duration = Duration::from_secs(10 * 60);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct OcrConfig {
+    pub enabled: bool,
+    /// Tesseract language codes joined with `+`, e.g. `eng+deu`.
+    pub language: String,
+}
+
+impl Default for OcrConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            language: "eng".into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OcrStatus {
+    pub enabled: bool,
+    /// Each image the note uses and whether its text has been read.
+    pub images: Vec<AttachmentText>,
+}
+
+pub fn config_of(storage: &Storage) -> Result<OcrConfig> {
+    match storage.meta(CONFIG_KEY)? {
+        Some(json) => Ok(serde_json::from_str(&json)?),
+        None => Ok(OcrConfig::default()),
+    }
+}
+
+pub fn set_config(storage: &Storage, config: &OcrConfig) -> Result<()> {
+    storage.set_meta(CONFIG_KEY, &serde_json::to_string(config)?)
+}
+
+pub fn is_image(path: &str) -> bool {
+    Path::new(path)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| IMAGE_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()))
+}
+
+/// Where OCR stands for the images in a note.
+pub fn status(vault: &Vault, note_id: &str) -> Result<OcrStatus> {
+    let note = vault.storage.get_note(note_id)?;
+    let images = crate::attachments::references(&note)
+        .iter()
+        .filter(|path| is_image(path))
+        .map(|path| text::status(&vault.storage, path))
+        .collect::<Result<_>>()?;
+    Ok(OcrStatus {
+        enabled: config_of(&vault.storage)?.enabled,
+        images,
+    })
+}
+
+/// Reads every image some note uses that has no text yet, one at a time,
+/// and reindexes the notes using it. Stops early if OCR is turned off or
+/// the vault locks, since the text is sealed like note bodies, or if the
+/// job is cancelled.
+pub fn run_pending(app: &AppHandle, vault: &Vault, job: &JobContext) -> Result<usize> {
+    let runnable = |vault: &Vault| -> Result<Option<OcrConfig>> {
+        let config = config_of(&vault.storage)?;
+        Ok((config.enabled && !vault.storage.is_locked()).then_some(config))
+    };
+    if runnable(vault)?.is_none() {
+        return Ok(0);
+    }
+    let pending: Vec<String> = text::pending(&vault.storage)?
+        .into_iter()
+        .filter(|path| is_image(path))
+        .collect();
+    if pending.is_empty() {
+        return Ok(0);
+    }
+    check_engine()?;
+
+    let mut read = 0;
+    let total = pending.len();
+    for (i, path) in pending.into_iter().enumerate() {
+        job.check()?;
+        job.progress(i, total);
+        let Some(config) = runnable(vault)? else {
+            break;
+        };
+        let file = vault.files.absolute(&path);
+        if !file.is_file() {
+            continue;
+        }
+        match recognize(&file, &config.language) {
+            Ok(found) => text::set(&vault.storage, &path, Ok(found.trim()))?,
+            Err(err) => text::set(&vault.storage, &path, Err(&err))?,
+        }
+        vault.attachment_read(&path)?;
+        let _ = app.emit(DONE_EVENT, text::status(&vault.storage, &path)?);
+        read += 1;
+    }
+    Ok(read)
+}
+
+/// Runs [`run_pending`] whenever the queue is woken, and every [`IDLE`]
+/// otherwise. Failures are reported through [`ERROR_EVENT`].
+pub fn spawn_worker(app: AppHandle, vault: Arc<Vault>) {
+    tauri::async_runtime::spawn(async move {
+        while !vault.is_closed() {
+            let (handle, worker) = (app.clone(), Arc::clone(&vault));
+            let result = app
+                .state::<Jobs>()
+                .run(JobKind::Ocr, Priority::Background, move |job| {
+                    run_pending(&handle, &worker, job)
+                })
+                .await;
+            match result {
+                Ok(_) | Err(Error::Cancelled) => {}
+                Err(err) => {
+                    let _ = app.emit(ERROR_EVENT, err.to_string());
+                }
+            }
+            let _ = tokio::time::timeout(IDLE, vault.ocr.notified()).await;
+        }
+    });
+}
+
+fn check_engine() -> Result<()> {
+    match Command::new(TESSERACT).arg("--version").output() {
+        Ok(output) if output.status.success() => Ok(()),
+        _ => Err(Error::Ocr(
+            "tesseract was not found; install it to read text in images".into(),
+        )),
+    }
+}
+
+/// The text Tesseract finds in the image at `file`, or why it failed.
+fn recognize(file: &Path, language: &str) -> std::result::Result<String, String> {
+    let output = Command::new(TESSERACT)
+        .arg(file)
+        .arg("stdout")
+        .args(["-l", language])
+        .output()
+        .map_err(|err| err.to_string())?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(stderr.trim().to_owned());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
@@ -0,0 +1,29 @@
+use tauri::{AppHandle, Manager, Window};
+
+use super::OsIndexStatus;
+use crate::error::Result;
+use crate::vaults::{Current, Vaults};
+
+#[tauri::command]
+pub async fn os_index_status(vault: Current) -> Result<OsIndexStatus> {
+    super::status(&vault.storage)
+}
+
+/// Adds the calling window's vault to the system search or takes it out.
+/// Encrypted vaults always stay out.
+#[tauri::command]
+pub async fn set_os_index_enabled(
+    app: AppHandle,
+    window: Window,
+    enabled: bool,
+) -> Result<OsIndexStatus> {
+    let vaults = app.state::<Vaults>();
+    let vault_id = vaults.id_of(window.label());
+    let vault = vaults.get(&app, &vault_id)?;
+    let handle = app.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        super::set_enabled(&handle, &vault_id, &vault, enabled)?;
+        super::status(&vault.storage)
+    })
+    .await?
+}
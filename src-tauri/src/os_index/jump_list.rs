@@ -0,0 +1,129 @@
+//! The taskbar jump list, which Windows Search also looks through. It only
+//! has room for a handful of items, so it holds the most recently edited
+//! notes of every indexed vault.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use tauri::AppHandle;
+use windows::core::{Interface, HSTRING};
+use windows::Win32::Storage::EnhancedStorage::PKEY_Title;
+use windows::Win32::System::Com::StructuredStorage::PROPVARIANT;
+use windows::Win32::System::Com::{
+    CoCreateInstance, CoInitializeEx, CLSCTX_INPROC_SERVER, COINIT_APARTMENTTHREADED,
+};
+use windows::Win32::UI::Shell::Common::{IObjectArray, IObjectCollection};
+use windows::Win32::UI::Shell::PropertiesSystem::IPropertyStore;
+use windows::Win32::UI::Shell::{
+    DestinationList, EnumerableObjectCollection, ICustomDestinationList, IShellLinkW, ShellLink,
+};
+
+use super::Entry;
+use crate::error::{Error, Result};
+
+pub const AVAILABLE: bool = true;
+
+const CATEGORY: &str = "Recent notes";
+/// More than the list ever shows, so a removed note leaves no gap.
+const KEEP: usize = 20;
+
+/// The newest notes of each vault, newest first.
+static RECENT: Mutex<Option<HashMap<String, Vec<Entry>>>> = Mutex::new(None);
+
+pub fn replace_all(app: &AppHandle, vault_id: &str, mut entries: Vec<Entry>) -> Result<()> {
+    entries.sort_by_key(|entry| std::cmp::Reverse(entry.updated_at));
+    entries.truncate(KEEP);
+    recent(|recent| {
+        recent.insert(vault_id.to_owned(), entries);
+    });
+    rebuild(app)
+}
+
+pub fn update(
+    app: &AppHandle,
+    vault_id: &str,
+    saved: Vec<Entry>,
+    removed: Vec<String>,
+) -> Result<()> {
+    recent(|recent| {
+        let entries = recent.entry(vault_id.to_owned()).or_default();
+        entries.retain(|entry| {
+            !removed.contains(&entry.id) && !saved.iter().any(|saved| saved.id == entry.id)
+        });
+        entries.extend(saved);
+        entries.sort_by_key(|entry| std::cmp::Reverse(entry.updated_at));
+        entries.truncate(KEEP);
+    });
+    rebuild(app)
+}
+
+pub fn clear(app: &AppHandle, vault_id: &str) -> Result<()> {
+    recent(|recent| {
+        recent.remove(vault_id);
+    });
+    rebuild(app)
+}
+
+fn recent(change: impl FnOnce(&mut HashMap<String, Vec<Entry>>)) {
+    change(
+        RECENT
+            .lock()
+            .expect("jump list poisoned")
+            .get_or_insert_with(HashMap::new),
+    );
+}
+
+/// Writes the jump list afresh. Each item starts the app with the note's
+/// link, which the single-instance plugin hands to a running app.
+fn rebuild(app: &AppHandle) -> Result<()> {
+    let mut entries: Vec<Entry> = RECENT
+        .lock()
+        .expect("jump list poisoned")
+        .iter()
+        .flat_map(HashMap::values)
+        .flatten()
+        .cloned()
+        .collect();
+    entries.sort_by_key(|entry| std::cmp::Reverse(entry.updated_at));
+    let exe = HSTRING::from(std::env::current_exe()?.as_os_str());
+    let app_id = HSTRING::from(app.config().identifier.as_str());
+    unsafe {
+        // Already initialised on this thread is fine.
+        let _ = CoInitializeEx(None, COINIT_APARTMENTTHREADED);
+        let list: ICustomDestinationList =
+            CoCreateInstance(&DestinationList, None, CLSCTX_INPROC_SERVER)
+                .map_err(jump_list_error)?;
+        list.SetAppID(&app_id).map_err(jump_list_error)?;
+        let mut slots = 0u32;
+        let _removed: IObjectArray = list.BeginList(&mut slots).map_err(jump_list_error)?;
+        let items: IObjectCollection =
+            CoCreateInstance(&EnumerableObjectCollection, None, CLSCTX_INPROC_SERVER)
+                .map_err(jump_list_error)?;
+        for entry in entries.iter().take(slots as usize) {
+            let link: IShellLinkW = CoCreateInstance(&ShellLink, None, CLSCTX_INPROC_SERVER)
+                .map_err(jump_list_error)?;
+            link.SetPath(&exe).map_err(jump_list_error)?;
+            link.SetArguments(&HSTRING::from(entry.url.as_str()))
+                .map_err(jump_list_error)?;
+            link.SetDescription(&HSTRING::from(entry.snippet.as_str()))
+                .map_err(jump_list_error)?;
+            let properties: IPropertyStore = link.cast().map_err(jump_list_error)?;
+            properties
+                .SetValue(&PKEY_Title, &PROPVARIANT::from(entry.title.as_str()))
+                .map_err(jump_list_error)?;
+            properties.Commit().map_err(jump_list_error)?;
+            items.AddObject(&link).map_err(jump_list_error)?;
+        }
+        if !entries.is_empty() {
+            let items: IObjectArray = items.cast().map_err(jump_list_error)?;
+            list.AppendCategory(&HSTRING::from(CATEGORY), &items)
+                .map_err(jump_list_error)?;
+        }
+        list.CommitList().map_err(jump_list_error)?;
+    }
+    Ok(())
+}
+
+fn jump_list_error(err: windows::core::Error) -> Error {
+    Error::OsIndex(err.message())
+}
@@ -0,0 +1,221 @@
+pub mod commands;
+
+#[cfg_attr(windows, path = "jump_list.rs")]
+#[cfg_attr(any(target_os = "macos", target_os = "ios"), path = "spotlight.rs")]
+#[cfg_attr(
+    not(any(windows, target_os = "macos", target_os = "ios")),
+    path = "unsupported.rs"
+)]
+mod platform;
+
+use std::collections::BTreeSet;
+use std::sync::Arc;
+use std::time::Duration;
+
+use pulldown_cmark::{Event, Parser};
+use serde::Serialize;
+use tauri::{AppHandle, Emitter};
+use tokio::sync::broadcast::error::RecvError;
+use url::Url;
+
+use crate::error::Result;
+use crate::metadata::FrontMatter;
+use crate::storage::{Note, Storage};
+use crate::vault::Vault;
+use crate::{protected, trash};
+
+/// Event carrying the message of a failed update of the system search.
+pub const ERROR_EVENT: &str = "os-index-error";
+
+/// Set to `false` to keep a vault out of the system search.
+const ENABLED_KEY: &str = "os_index.enabled";
+const SNIPPET_CHARS: usize = 300;
+/// Changes are gathered this long, so typing does not update the index on
+/// every save.
+const SETTLE: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OsIndexStatus {
+    /// Whether this platform has a system search to add notes to.
+    pub available: bool,
+    pub enabled: bool,
+}
+
+/// A note as the system search shows it.
+#[cfg_attr(
+    not(any(windows, target_os = "macos", target_os = "ios")),
+    allow(dead_code)
+)]
+#[derive(Debug, Clone)]
+struct Entry {
+    id: String,
+    title: String,
+    snippet: String,
+    /// The `notes://open` link that brings the note up.
+    url: String,
+    updated_at: i64,
+}
+
+/// Whether the notes of `storage` go into the system search. On unless
+/// turned off, and never for an encrypted vault, whose titles would end
+/// up in the clear in the OS's index.
+pub fn is_enabled(storage: &Storage) -> Result<bool> {
+    if storage.is_encrypted() {
+        return Ok(false);
+    }
+    Ok(storage.meta(ENABLED_KEY)?.as_deref() != Some("false"))
+}
+
+pub fn status(storage: &Storage) -> Result<OsIndexStatus> {
+    Ok(OsIndexStatus {
+        available: platform::AVAILABLE,
+        enabled: platform::AVAILABLE && is_enabled(storage)?,
+    })
+}
+
+/// Adds the notes of vault `vault_id` to the system search or takes them
+/// out again.
+pub fn set_enabled(app: &AppHandle, vault_id: &str, vault: &Vault, enabled: bool) -> Result<()> {
+    vault
+        .storage
+        .set_meta(ENABLED_KEY, if enabled { "true" } else { "false" })?;
+    match is_enabled(&vault.storage)? {
+        true => reindex(app, vault_id, vault),
+        false => platform::clear(app, vault_id),
+    }
+}
+
+/// Puts the notes of vault `vault_id` in the system search and keeps them
+/// there as they change, until the vault is closed.
+pub fn spawn_indexer(app: AppHandle, vault_id: String, vault: Arc<Vault>) {
+    if !platform::AVAILABLE {
+        return;
+    }
+    let mut changes = vault.changes.subscribe();
+    tauri::async_runtime::spawn(async move {
+        let (id, indexed) = (vault_id.clone(), Arc::clone(&vault));
+        let first = app.clone();
+        let result =
+            tauri::async_runtime::spawn_blocking(move || match is_enabled(&indexed.storage)? {
+                true => reindex(&first, &id, &indexed),
+                false => platform::clear(&first, &id),
+            })
+            .await;
+        report(&app, result);
+
+        while !vault.is_closed() {
+            let mut changed = BTreeSet::new();
+            match changes.recv().await {
+                Ok(change) => changed.insert(change.note_id),
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => break,
+            };
+            let settled = tokio::time::Instant::now() + SETTLE;
+            while let Ok(change) = tokio::time::timeout_at(settled, changes.recv()).await {
+                match change {
+                    Ok(change) => {
+                        changed.insert(change.note_id);
+                    }
+                    Err(RecvError::Lagged(_)) => {}
+                    Err(RecvError::Closed) => break,
+                }
+            }
+            let (id, indexed) = (vault_id.clone(), Arc::clone(&vault));
+            let handle = app.clone();
+            let result = tauri::async_runtime::spawn_blocking(move || {
+                update(&handle, &id, &indexed, changed)
+            })
+            .await;
+            report(&app, result);
+        }
+    });
+}
+
+fn report(app: &AppHandle, result: std::result::Result<Result<()>, tauri::Error>) {
+    let message = match result {
+        Ok(Ok(())) => return,
+        Ok(Err(err)) => err.to_string(),
+        Err(err) => err.to_string(),
+    };
+    let _ = app.emit(ERROR_EVENT, message);
+}
+
+fn reindex(app: &AppHandle, vault_id: &str, vault: &Vault) -> Result<()> {
+    let mut entries = Vec::new();
+    for summary in vault.storage.list_notes(None)? {
+        if summary.locked {
+            continue;
+        }
+        let note = vault.storage.get_note(&summary.id)?;
+        entries.extend(entry_of(vault_id, &note));
+    }
+    platform::replace_all(app, vault_id, entries)
+}
+
+/// Brings the notes `ids` up to date: saved ones are indexed again, and
+/// removed, trashed or locked ones are taken out. Once indexing is off,
+/// which a vault password also turns it, every note comes out.
+fn update(app: &AppHandle, vault_id: &str, vault: &Vault, ids: BTreeSet<String>) -> Result<()> {
+    if !is_enabled(&vault.storage)? {
+        return platform::clear(app, vault_id);
+    }
+    let mut saved = Vec::new();
+    let mut removed = Vec::new();
+    for id in ids {
+        let entry = match vault.storage.has_note(&id)? && !trash::is_trashed(&vault.storage, &id)? {
+            true => entry_of(vault_id, &vault.storage.get_note(&id)?),
+            false => None,
+        };
+        match entry {
+            Some(entry) => saved.push(entry),
+            None => removed.push(id),
+        }
+    }
+    platform::update(app, vault_id, saved, removed)
+}
+
+/// `None` for a note with a passphrase of its own, which stays out of the
+/// index even while unlocked.
+fn entry_of(vault_id: &str, note: &Note) -> Option<Entry> {
+    if protected::is_protected(&note.body) {
+        return None;
+    }
+    Some(Entry {
+        id: note.id.clone(),
+        title: note.title.clone(),
+        snippet: snippet(&note.body),
+        url: open_url(vault_id, &note.id),
+        updated_at: note.updated_at,
+    })
+}
+
+/// The link that opens a note through the `notes://` handler, switching
+/// the main window to its vault first.
+fn open_url(vault_id: &str, note_id: &str) -> String {
+    let mut url = Url::parse("notes://open/").expect("a valid link");
+    url.path_segments_mut()
+        .expect("a link with a path")
+        .pop_if_empty()
+        .push(note_id);
+    url.query_pairs_mut().append_pair("vault", vault_id);
+    url.into()
+}
+
+/// The start of a note's text, without markup.
+fn snippet(body: &str) -> String {
+    let (_, markdown) = FrontMatter::split(body);
+    let mut text = String::new();
+    for event in Parser::new(markdown) {
+        match event {
+            Event::Text(t) | Event::Code(t) => text.push_str(&t),
+            Event::SoftBreak | Event::HardBreak | Event::End(_) => text.push(' '),
+            _ => {}
+        }
+        if text.len() > SNIPPET_CHARS * 4 {
+            break;
+        }
+    }
+    let words: Vec<&str> = text.split_whitespace().collect();
+    words.join(" ").chars().take(SNIPPET_CHARS).collect()
+}
@@ -0,0 +1,91 @@
+//! Core Spotlight. Each vault is a domain, so it can be dropped in one call.
+
+use objc2::rc::Retained;
+use objc2::{AllocAnyThread, Message};
+use objc2_core_spotlight::{CSSearchableIndex, CSSearchableItem, CSSearchableItemAttributeSet};
+use objc2_foundation::{ns_string, NSArray, NSDate, NSString, NSURL};
+use objc2_uniform_type_identifiers::{UTType, UTTypePlainText};
+use tauri::AppHandle;
+
+use super::Entry;
+use crate::error::Result;
+
+pub const AVAILABLE: bool = true;
+
+pub fn replace_all(app: &AppHandle, vault_id: &str, entries: Vec<Entry>) -> Result<()> {
+    clear(app, vault_id)?;
+    update(app, vault_id, entries, Vec::new())
+}
+
+pub fn update(
+    _app: &AppHandle,
+    vault_id: &str,
+    saved: Vec<Entry>,
+    removed: Vec<String>,
+) -> Result<()> {
+    // Spotlight indexes in the background and only reports failures to a
+    // completion handler; nothing here waits for it.
+    let index = unsafe { CSSearchableIndex::defaultSearchableIndex() };
+    if !removed.is_empty() {
+        let ids: Vec<Retained<NSString>> = removed
+            .iter()
+            .map(|id| NSString::from_str(&identifier(vault_id, id)))
+            .collect();
+        unsafe {
+            index.deleteSearchableItemsWithIdentifiers_completionHandler(
+                &NSArray::from_retained_slice(&ids),
+                None,
+            )
+        };
+    }
+    if !saved.is_empty() {
+        let items: Vec<Retained<CSSearchableItem>> =
+            saved.iter().map(|entry| item(vault_id, entry)).collect();
+        unsafe {
+            index
+                .indexSearchableItems_completionHandler(&NSArray::from_retained_slice(&items), None)
+        };
+    }
+    Ok(())
+}
+
+pub fn clear(_app: &AppHandle, vault_id: &str) -> Result<()> {
+    let domains = NSArray::from_retained_slice(&[NSString::from_str(vault_id)]);
+    unsafe {
+        CSSearchableIndex::defaultSearchableIndex()
+            .deleteSearchableItemsWithDomainIdentifiers_completionHandler(&domains, None)
+    };
+    Ok(())
+}
+
+/// Unique across vaults, since the same note can be in two of them after a
+/// copy.
+fn identifier(vault_id: &str, note_id: &str) -> String {
+    format!("{vault_id}/{note_id}")
+}
+
+fn item(vault_id: &str, entry: &Entry) -> Retained<CSSearchableItem> {
+    // Markdown where the system knows the type, as it does once an app
+    // that opens Markdown is installed.
+    let content_type = UTType::typeWithIdentifier(ns_string!("net.daringfireball.markdown"))
+        .unwrap_or_else(|| unsafe { UTTypePlainText }.retain());
+    unsafe {
+        let attributes = CSSearchableItemAttributeSet::initWithContentType(
+            CSSearchableItemAttributeSet::alloc(),
+            &content_type,
+        );
+        attributes.setTitle(Some(&NSString::from_str(&entry.title)));
+        attributes.setDisplayName(Some(&NSString::from_str(&entry.title)));
+        attributes.setContentDescription(Some(&NSString::from_str(&entry.snippet)));
+        attributes.setContentModificationDate(Some(&NSDate::dateWithTimeIntervalSince1970(
+            entry.updated_at as f64 / 1000.0,
+        )));
+        attributes.setContentURL(NSURL::URLWithString(&NSString::from_str(&entry.url)).as_deref());
+        CSSearchableItem::initWithUniqueIdentifier_domainIdentifier_attributeSet(
+            CSSearchableItem::alloc(),
+            Some(&NSString::from_str(&identifier(vault_id, &entry.id))),
+            Some(&NSString::from_str(vault_id)),
+            &attributes,
+        )
+    }
+}
@@ -0,0 +1,25 @@
+//! Platforms without a system search this app can add to.
+
+use tauri::AppHandle;
+
+use super::Entry;
+use crate::error::Result;
+
+pub const AVAILABLE: bool = false;
+
+pub fn replace_all(_app: &AppHandle, _vault_id: &str, _entries: Vec<Entry>) -> Result<()> {
+    Ok(())
+}
+
+pub fn update(
+    _app: &AppHandle,
+    _vault_id: &str,
+    _saved: Vec<Entry>,
+    _removed: Vec<String>,
+) -> Result<()> {
+    Ok(())
+}
+
+pub fn clear(_app: &AppHandle, _vault_id: &str) -> Result<()> {
+    Ok(())
+}
@@ -0,0 +1,11 @@
+use crate::attachments::text::AttachmentText;
+use crate::error::Result;
+use crate::vaults::Current;
+
+/// Reads the text of a PDF attachment now, identified by its
+/// `attachments/...` path, and indexes it with the notes using it. Also
+/// redoes a PDF that was read before.
+#[tauri::command]
+pub async fn extract_pdf_text(vault: Current, attachment_id: String) -> Result<AttachmentText> {
+    tauri::async_runtime::spawn_blocking(move || super::extract(&vault, &attachment_id)).await?
+}
@@ -0,0 +1,136 @@
+pub mod commands;
+
+use std::fs;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::attachments::text::{self, AttachmentText};
+use crate::error::{Error, Result};
+use crate::files::NoteFiles;
+use crate::jobs::{JobContext, JobKind, Jobs, Priority};
+use crate::vault::Vault;
+
+/// Event carrying the [`AttachmentText`] of each PDF once it is read.
+pub const DONE_EVENT: &str = "pdf-text-done";
+/// Event carrying the message of a PDF that could not be recorded.
+pub const ERROR_EVENT: &str = "pdf-text-error";
+
+/// Upper bound on worker threads, so a large import does not take every
+/// core away from the UI.
+const MAX_WORKERS: usize = 4;
+/// Larger PDFs are left unindexed rather than loaded into memory whole.
+const MAX_BYTES: u64 = 64 * 1024 * 1024;
+const IDLE: Duration = Duration::from_secs(10 * 60);
+
+pub fn is_pdf(path: &str) -> bool {
+    path.to_ascii_lowercase().ends_with(".pdf")
+}
+
+/// Reads the text of the PDF attachment `path`, records it and reindexes
+/// the notes using it.
+pub fn extract(vault: &Vault, path: &str) -> Result<AttachmentText> {
+    if !NoteFiles::is_attachment_path(path) || !is_pdf(path) {
+        return Err(Error::InvalidInput(format!("not a PDF attachment: {path}")));
+    }
+    if vault.storage.is_locked() {
+        return Err(Error::VaultLocked);
+    }
+    match read(vault, path)? {
+        Ok(found) => text::set(&vault.storage, path, Ok(found.trim()))?,
+        Err(err) => text::set(&vault.storage, path, Err(&err))?,
+    }
+    vault.attachment_read(path)?;
+    text::status(&vault.storage, path)
+}
+
+/// The text of the PDF, or why it has none. The outer error is for the file
+/// going missing, which is not recorded against it.
+fn read(vault: &Vault, path: &str) -> Result<std::result::Result<String, String>> {
+    let file = vault.files.absolute(path);
+    if fs::metadata(&file)?.len() > MAX_BYTES {
+        return Ok(Err("too large to index".into()));
+    }
+    let content = fs::read(&file)?;
+    // The parser panics on some malformed files rather than failing.
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        pdf_extract::extract_text_from_mem(&content)
+    }));
+    Ok(match result {
+        Ok(Ok(found)) => Ok(found),
+        Ok(Err(err)) => Err(err.to_string()),
+        Err(_) => Err("could not parse the PDF".into()),
+    })
+}
+
+/// Reads every PDF some note uses that has no text yet, on a few threads at
+/// once. Returns how many were read.
+pub fn run_pending(app: &AppHandle, vault: &Vault, job: &JobContext) -> Result<usize> {
+    if vault.storage.is_locked() {
+        return Ok(0);
+    }
+    let pending: Vec<String> = text::pending(&vault.storage)?
+        .into_iter()
+        .filter(|path| is_pdf(path) && vault.files.absolute(path).is_file())
+        .collect();
+    let workers = thread::available_parallelism()
+        .map_or(1, |n| n.get())
+        .clamp(1, MAX_WORKERS)
+        .min(pending.len());
+    let total = pending.len();
+    let queue = Mutex::new(pending);
+    let done = AtomicUsize::new(0);
+    thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| loop {
+                if job.is_cancelled() {
+                    break;
+                }
+                let Some(path) = queue.lock().expect("pdf queue poisoned").pop() else {
+                    break;
+                };
+                let result = extract(vault, &path);
+                job.progress(done.fetch_add(1, Ordering::Relaxed) + 1, total);
+                match result {
+                    Ok(status) => {
+                        let _ = app.emit(DONE_EVENT, status);
+                    }
+                    // Locked halfway; the rest waits for the next unlock.
+                    Err(Error::VaultLocked) => break,
+                    Err(err) => {
+                        let _ = app.emit(ERROR_EVENT, err.to_string());
+                    }
+                }
+            });
+        }
+    });
+    job.check()?;
+    Ok(total)
+}
+
+/// Runs [`run_pending`] whenever the queue is woken, and every [`IDLE`]
+/// otherwise.
+pub fn spawn_worker(app: AppHandle, vault: Arc<Vault>) {
+    tauri::async_runtime::spawn(async move {
+        while !vault.is_closed() {
+            let (handle, worker) = (app.clone(), Arc::clone(&vault));
+            let result = app
+                .state::<Jobs>()
+                .run(JobKind::PdfText, Priority::Background, move |job| {
+                    run_pending(&handle, &worker, job)
+                })
+                .await;
+            match result {
+                Ok(_) | Err(Error::Cancelled) => {}
+                Err(err) => {
+                    let _ = app.emit(ERROR_EVENT, err.to_string());
+                }
+            }
+            let _ = tokio::time::timeout(IDLE, vault.pdf_text.notified()).await;
+        }
+    });
+}
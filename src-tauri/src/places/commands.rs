@@ -0,0 +1,56 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use super::Location;
+use crate::error::{Error, Result};
+use crate::vaults::Current;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NearbyNote {
+    pub id: String,
+    pub title: String,
+    pub folder: String,
+    pub location: Location,
+    /// Metres from the place asked about.
+    pub distance: f64,
+}
+
+/// Notes written within `radius` metres of `lat`, `lon`, nearest first.
+#[tauri::command]
+pub async fn notes_near(
+    vault: Current,
+    lat: f64,
+    lon: f64,
+    radius: f64,
+) -> Result<Vec<NearbyNote>> {
+    let center = Location::new(lat, lon)
+        .ok_or_else(|| Error::InvalidInput(format!("no such place: {lat}, {lon}")))?;
+    if radius.is_nan() || radius < 0.0 {
+        return Err(Error::InvalidInput(format!("bad radius: {radius}")));
+    }
+    let found = vault.places.near(center, radius);
+    if found.is_empty() {
+        return Ok(Vec::new());
+    }
+    let mut notes: HashMap<String, _> = vault
+        .storage
+        .list_notes(None)?
+        .into_iter()
+        .map(|note| (note.id.clone(), note))
+        .collect();
+    Ok(found
+        .into_iter()
+        .filter_map(|(id, location, distance)| {
+            let note = notes.remove(&id)?;
+            Some(NearbyNote {
+                id,
+                title: note.title,
+                folder: note.folder,
+                location,
+                distance,
+            })
+        })
+        .collect())
+}
@@ -0,0 +1,180 @@
+//! Where notes were written. A note's place is `location: [lat, lon]` in
+//! its front matter, set when it is created on a phone if
+//! [`crate::settings::Editor::geotag`] is on, or by hand anywhere. Only
+//! phones have a position to offer; see [`here`].
+
+pub mod commands;
+
+use std::collections::{BTreeSet, HashMap};
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use serde::Serialize;
+use serde_yaml_ng::Value;
+use tauri::AppHandle;
+
+use crate::metadata::FrontMatter;
+use crate::storage::Note;
+
+const KEY: &str = "location";
+const EARTH_RADIUS_M: f64 = 6_371_008.8;
+/// Metres in a degree of latitude, near enough everywhere.
+const METRES_PER_DEGREE: f64 = 111_320.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Location {
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+impl Location {
+    pub fn new(latitude: f64, longitude: f64) -> Option<Self> {
+        let valid = (-90.0..=90.0).contains(&latitude) && (-180.0..=180.0).contains(&longitude);
+        valid.then_some(Self {
+            latitude,
+            longitude,
+        })
+    }
+
+    /// The place in `note`'s front matter, as `[lat, lon]` or `"lat, lon"`.
+    pub fn of(note: &Note) -> Option<Self> {
+        let (front_matter, _) = FrontMatter::split(&note.body);
+        let (latitude, longitude) = match front_matter?.fields.get(KEY)? {
+            Value::Sequence(items) => match items.as_slice() {
+                [latitude, longitude] => (latitude.as_f64()?, longitude.as_f64()?),
+                _ => return None,
+            },
+            Value::String(text) => {
+                let (latitude, longitude) = text.split_once(',')?;
+                (
+                    latitude.trim().parse().ok()?,
+                    longitude.trim().parse().ok()?,
+                )
+            }
+            _ => return None,
+        };
+        Self::new(latitude, longitude)
+    }
+
+    /// Great-circle distance to `other` in metres.
+    pub fn distance(&self, other: &Self) -> f64 {
+        let (lat1, lat2) = (self.latitude.to_radians(), other.latitude.to_radians());
+        let dlat = lat2 - lat1;
+        let dlon = (other.longitude - self.longitude).to_radians();
+        let a = (dlat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (dlon / 2.0).sin().powi(2);
+        2.0 * EARTH_RADIUS_M * a.sqrt().min(1.0).asin()
+    }
+}
+
+/// `body` with `location` set in its front matter. A block that does not
+/// parse is left alone, and so is the body.
+pub fn with_location(body: &str, location: Location) -> String {
+    let (front_matter, markdown) = FrontMatter::split(body);
+    if front_matter.is_none() && body.starts_with("---") {
+        return body.to_owned();
+    }
+    // Six decimals are about ten centimetres.
+    let round = |degrees: f64| Value::Number(((degrees * 1e6).round() / 1e6).into());
+    let mut front_matter = front_matter.unwrap_or_default();
+    front_matter.fields.insert(
+        Value::String(KEY.into()),
+        Value::Sequence(vec![round(location.latitude), round(location.longitude)]),
+    );
+    format!("{}{markdown}", front_matter.render())
+}
+
+/// Where the device is, if it can tell and may. Asks for permission the
+/// first time.
+#[cfg(mobile)]
+pub fn here(app: &AppHandle) -> Option<Location> {
+    crate::mobile::current_location(app)
+}
+
+/// Desktops have no position to offer.
+#[cfg(desktop)]
+pub fn here(_app: &AppHandle) -> Option<Location> {
+    None
+}
+
+#[derive(Default)]
+struct Places {
+    at: HashMap<String, Location>,
+    /// Note ids by latitude in microdegrees, so a search only looks at
+    /// the band of latitudes it can reach.
+    by_latitude: BTreeSet<(i64, String)>,
+}
+
+impl Places {
+    fn set(&mut self, id: &str, location: Option<Location>) {
+        if let Some(old) = self.at.remove(id) {
+            self.by_latitude
+                .remove(&(micro(old.latitude), id.to_owned()));
+        }
+        if let Some(location) = location {
+            self.by_latitude
+                .insert((micro(location.latitude), id.to_owned()));
+            self.at.insert(id.to_owned(), location);
+        }
+    }
+}
+
+fn micro(degrees: f64) -> i64 {
+    (degrees * 1e6).round() as i64
+}
+
+/// The places of every note, kept in memory like [`crate::tags::TagIndex`].
+#[derive(Default)]
+pub struct PlaceIndex {
+    places: RwLock<Places>,
+}
+
+impl PlaceIndex {
+    pub fn index_note(&self, note: &Note) {
+        self.write().set(&note.id, Location::of(note));
+    }
+
+    pub fn remove_note(&self, id: &str) {
+        self.write().set(id, None);
+    }
+
+    pub fn rebuild(&self, notes: &[Note]) {
+        let mut places = Places::default();
+        for note in notes {
+            places.set(&note.id, Location::of(note));
+        }
+        *self.write() = places;
+    }
+
+    pub fn clear(&self) {
+        *self.write() = Places::default();
+    }
+
+    /// Notes within `radius` metres of `center` with their distance,
+    /// nearest first.
+    pub fn near(&self, center: Location, radius: f64) -> Vec<(String, Location, f64)> {
+        let places = self.read();
+        let band = radius / METRES_PER_DEGREE;
+        let from = (micro(center.latitude - band), String::new());
+        let to = micro(center.latitude + band);
+        let mut found: Vec<(String, Location, f64)> = places
+            .by_latitude
+            .range(from..)
+            .take_while(|(latitude, _)| *latitude <= to)
+            .filter_map(|(_, id)| {
+                let location = places.at[id];
+                let distance = center.distance(&location);
+                (distance <= radius).then(|| (id.clone(), location, distance))
+            })
+            .collect();
+        found.sort_by(|a, b| a.2.total_cmp(&b.2));
+        found
+    }
+
+    fn read(&self) -> RwLockReadGuard<'_, Places> {
+        self.places.read().expect("place index poisoned")
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, Places> {
+        self.places.write().expect("place index poisoned")
+    }
+}
@@ -0,0 +1,51 @@
+use serde_json::Value;
+use tauri::{AppHandle, Manager, Window};
+
+use super::{PluginInfo, Plugins};
+use crate::error::Result;
+use crate::vaults::Vaults;
+
+#[tauri::command]
+pub async fn list_plugins(app: AppHandle) -> Vec<PluginInfo> {
+    app.state::<Plugins>().list()
+}
+
+/// Enables plugin `id`, granting the capabilities its manifest asks for,
+/// or disables it.
+#[tauri::command]
+pub async fn enable_plugin(app: AppHandle, id: String, enabled: bool) -> Result<Vec<PluginInfo>> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let plugins = app.state::<Plugins>();
+        plugins.set_enabled(&app, &id, enabled)?;
+        Ok(plugins.list())
+    })
+    .await?
+}
+
+/// Looks for plugins added, updated or removed on disk.
+#[tauri::command]
+pub async fn reload_plugins(app: AppHandle) -> Result<Vec<PluginInfo>> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let plugins = app.state::<Plugins>();
+        plugins.reload(&app)?;
+        Ok(plugins.list())
+    })
+    .await?
+}
+
+/// Runs a command a plugin registered on the calling window's vault.
+#[tauri::command]
+pub async fn run_plugin_command(
+    app: AppHandle,
+    window: Window,
+    plugin: String,
+    command: String,
+    input: Option<Value>,
+) -> Result<Value> {
+    let vault = app.state::<Vaults>().of_window(&app, window.label())?;
+    tauri::async_runtime::spawn_blocking(move || {
+        app.state::<Plugins>()
+            .run(vault, &plugin, &command, input.unwrap_or(Value::Null))
+    })
+    .await?
+}
@@ -0,0 +1,392 @@
+//! The sandbox one plugin runs in and the host API it gets.
+//!
+//! Plugins are core WebAssembly modules without WASI, so they reach
+//! nothing but what is imported here. Strings cross as JSON in the
+//! plugin's memory: the plugin exports `memory` and `alloc(len) -> ptr`,
+//! and results come back as `ptr << 32 | len`, 0 meaning nothing.
+//!
+//! Imports, from module `notes`:
+//! - `log(ptr, len)`: a message for the app's plugin log.
+//! - `call(ptr, len) -> i64`: a [`Request`], answered with
+//!   `{"ok": ...}` or `{"error": "..."}`.
+//!
+//! Optional exports:
+//! - `init()`: run once on load; commands and subscriptions are made here.
+//! - `command(ptr, len) -> i64`: `{"name", "input"}` of a registered
+//!   command, answered with its output.
+//! - `event(ptr, len)`: `{"event", "payload"}` of a subscribed event.
+
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tauri::{AppHandle, Emitter};
+use wasmtime::{Caller, Engine, Extern, Instance, Linker, Memory, Module, Store, StoreLimits};
+use wasmtime::{StoreLimitsBuilder, TypedFunc};
+
+use super::{Capability, Manifest, LOG_EVENT};
+use crate::error::{Error, Result};
+use crate::protected;
+use crate::storage::NotePatch;
+use crate::vault::Vault;
+
+/// Instructions one call may run before it is stopped.
+const FUEL: u64 = 5_000_000_000;
+const MAX_MEMORY_BYTES: usize = 256 * 1024 * 1024;
+/// Largest message either side may pass.
+const MAX_MESSAGE_BYTES: usize = 32 * 1024 * 1024;
+/// A save of the plugin's own is not handed back to it as an event within
+/// this long, so a processor does not feed on its own writes.
+const ECHO: Duration = Duration::from_secs(5);
+/// Events a plugin may subscribe to.
+pub const EVENTS: &[&str] = &["note-changed", "sync-complete"];
+
+/// A command a plugin registered, run with `run_plugin_command`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PluginCommand {
+    pub name: String,
+    pub title: String,
+}
+
+/// What a plugin can ask of the host through `call`.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "op")]
+enum Request {
+    GetNote {
+        id: String,
+    },
+    ListNotes {
+        folder: Option<String>,
+    },
+    Search {
+        query: String,
+        #[serde(default = "default_limit")]
+        limit: usize,
+    },
+    CreateNote {
+        title: String,
+        #[serde(default)]
+        body: String,
+        #[serde(default)]
+        folder: String,
+    },
+    UpdateNote {
+        id: String,
+        title: Option<String>,
+        body: Option<String>,
+    },
+    RegisterCommand(PluginCommand),
+    Subscribe {
+        event: String,
+    },
+}
+
+fn default_limit() -> usize {
+    20
+}
+
+struct Host {
+    app: AppHandle,
+    plugin: String,
+    granted: Vec<Capability>,
+    /// The vault of the call under way; none while `init` runs.
+    vault: Option<Arc<Vault>>,
+    commands: Vec<PluginCommand>,
+    events: BTreeSet<String>,
+    wrote: HashMap<String, Instant>,
+    limits: StoreLimits,
+}
+
+impl Host {
+    fn require(&self, capability: Capability) -> Result<()> {
+        match self.granted.contains(&capability) {
+            true => Ok(()),
+            false => Err(Error::Plugin(format!(
+                "{} was not granted {}",
+                self.plugin,
+                capability.as_str()
+            ))),
+        }
+    }
+
+    fn vault(&self) -> Result<&Arc<Vault>> {
+        self.vault
+            .as_ref()
+            .ok_or_else(|| Error::Plugin("notes cannot be reached during init".into()))
+    }
+
+    fn handle(&mut self, request: Request) -> Result<Value> {
+        match request {
+            Request::GetNote { id } => {
+                self.require(Capability::ReadNotes)?;
+                Ok(serde_json::to_value(protected::read(self.vault()?, &id)?)?)
+            }
+            Request::ListNotes { folder } => {
+                self.require(Capability::ReadNotes)?;
+                let notes = self.vault()?.storage.list_notes(folder.as_deref())?;
+                Ok(serde_json::to_value(notes)?)
+            }
+            Request::Search { query, limit } => {
+                self.require(Capability::ReadNotes)?;
+                let hits = self.vault()?.search_notes(&query, limit, false)?;
+                Ok(serde_json::to_value(hits)?)
+            }
+            Request::CreateNote {
+                title,
+                body,
+                folder,
+            } => {
+                self.require(Capability::WriteNotes)?;
+                let note = self.vault()?.create_note(&title, &body, &folder)?;
+                self.wrote.insert(note.id.clone(), Instant::now());
+                Ok(serde_json::to_value(note)?)
+            }
+            Request::UpdateNote { id, title, body } => {
+                self.require(Capability::WriteNotes)?;
+                let patch = NotePatch {
+                    title,
+                    body,
+                    ..Default::default()
+                };
+                let note = self.vault()?.edit_note(&id, patch)?;
+                self.wrote.insert(id, Instant::now());
+                Ok(serde_json::to_value(note)?)
+            }
+            Request::RegisterCommand(command) => {
+                self.require(Capability::Commands)?;
+                if command.name.trim().is_empty() {
+                    return Err(Error::Plugin("a command needs a name".into()));
+                }
+                self.commands
+                    .retain(|existing| existing.name != command.name);
+                self.commands.push(command);
+                Ok(Value::Null)
+            }
+            Request::Subscribe { event } => {
+                self.require(Capability::Events)?;
+                if !EVENTS.contains(&event.as_str()) {
+                    return Err(Error::Plugin(format!("no event {event}")));
+                }
+                self.events.insert(event);
+                Ok(Value::Null)
+            }
+        }
+    }
+}
+
+/// A loaded plugin, its instance kept between calls so it can hold state.
+pub struct Runtime {
+    store: Store<Host>,
+    instance: Instance,
+    memory: Memory,
+    alloc: TypedFunc<i32, i32>,
+}
+
+impl Runtime {
+    /// Instantiates `wasm` with the capabilities in `granted` and runs its
+    /// `init`.
+    pub fn start(
+        engine: &Engine,
+        app: &AppHandle,
+        manifest: &Manifest,
+        granted: Vec<Capability>,
+        wasm: &[u8],
+    ) -> Result<Self> {
+        let module = Module::from_binary(engine, wasm).map_err(plugin_error)?;
+        let mut linker = Linker::<Host>::new(engine);
+        linker
+            .func_wrap(
+                "notes",
+                "log",
+                |mut caller: Caller<'_, Host>, ptr: i32, len: i32| -> wasmtime::Result<()> {
+                    let message =
+                        String::from_utf8_lossy(&read(&mut caller, ptr, len)?).into_owned();
+                    let host = caller.data();
+                    let _ = host.app.emit(
+                        LOG_EVENT,
+                        json!({ "plugin": host.plugin, "message": message }),
+                    );
+                    Ok(())
+                },
+            )
+            .map_err(plugin_error)?;
+        linker
+            .func_wrap(
+                "notes",
+                "call",
+                |mut caller: Caller<'_, Host>, ptr: i32, len: i32| -> wasmtime::Result<i64> {
+                    let request = read(&mut caller, ptr, len)?;
+                    let reply = match serde_json::from_slice::<Request>(&request) {
+                        Ok(request) => match caller.data_mut().handle(request) {
+                            Ok(value) => json!({ "ok": value }),
+                            Err(err) => json!({ "error": err.to_string() }),
+                        },
+                        Err(err) => json!({ "error": format!("bad request: {err}") }),
+                    };
+                    write(&mut caller, &serde_json::to_vec(&reply)?)
+                },
+            )
+            .map_err(plugin_error)?;
+
+        let host = Host {
+            app: app.clone(),
+            plugin: manifest.id.clone(),
+            granted,
+            vault: None,
+            commands: Vec::new(),
+            events: BTreeSet::new(),
+            wrote: HashMap::new(),
+            limits: StoreLimitsBuilder::new()
+                .memory_size(MAX_MEMORY_BYTES)
+                .instances(1)
+                .build(),
+        };
+        let mut store = Store::new(engine, host);
+        store.limiter(|host| &mut host.limits);
+        store.set_fuel(FUEL).map_err(plugin_error)?;
+        let instance = linker
+            .instantiate(&mut store, &module)
+            .map_err(plugin_error)?;
+        let memory = instance
+            .get_memory(&mut store, "memory")
+            .ok_or_else(|| Error::Plugin("the plugin exports no memory".into()))?;
+        let alloc = instance
+            .get_typed_func::<i32, i32>(&mut store, "alloc")
+            .map_err(plugin_error)?;
+        let mut runtime = Self {
+            store,
+            instance,
+            memory,
+            alloc,
+        };
+        if let Ok(init) = runtime
+            .instance
+            .get_typed_func::<(), ()>(&mut runtime.store, "init")
+        {
+            init.call(&mut runtime.store, ()).map_err(plugin_error)?;
+        }
+        Ok(runtime)
+    }
+
+    pub fn commands(&self) -> &[PluginCommand] {
+        &self.store.data().commands
+    }
+
+    pub fn subscribes_to(&self, event: &str) -> bool {
+        self.store.data().events.contains(event)
+    }
+
+    /// Runs registered command `name` on `vault`.
+    pub fn command(&mut self, vault: Arc<Vault>, name: &str, input: Value) -> Result<Value> {
+        if !self.commands().iter().any(|command| command.name == name) {
+            return Err(Error::Plugin(format!("no command {name}")));
+        }
+        let output = self.call(vault, "command", &json!({ "name": name, "input": input }))?;
+        match output {
+            Some(output) => Ok(serde_json::from_slice(&output)?),
+            None => Ok(Value::Null),
+        }
+    }
+
+    /// Hands `event` to the plugin, unless it is about a note the plugin
+    /// just wrote itself.
+    pub fn event(&mut self, vault: Arc<Vault>, event: &str, payload: Value) -> Result<()> {
+        let host = self.store.data_mut();
+        host.wrote.retain(|_, at| at.elapsed() < ECHO);
+        let note = payload.get("noteId").and_then(Value::as_str);
+        if note.is_some_and(|id| host.wrote.contains_key(id)) {
+            return Ok(());
+        }
+        self.call(
+            vault,
+            "event",
+            &json!({ "event": event, "payload": payload }),
+        )
+        .map(drop)
+    }
+
+    fn call(&mut self, vault: Arc<Vault>, export: &str, input: &Value) -> Result<Option<Vec<u8>>> {
+        let func = self
+            .instance
+            .get_typed_func::<(i32, i32), i64>(&mut self.store, export)
+            .map_err(|_| Error::Plugin(format!("the plugin exports no {export}")))?;
+        self.store.set_fuel(FUEL).map_err(plugin_error)?;
+        self.store.data_mut().vault = Some(vault);
+        let result = (|| {
+            let input = serde_json::to_vec(input)?;
+            let len = i32::try_from(input.len())
+                .map_err(|_| Error::Plugin("the input is too large".into()))?;
+            let ptr = self
+                .alloc
+                .call(&mut self.store, len)
+                .map_err(plugin_error)?;
+            self.memory
+                .write(&mut self.store, ptr as u32 as usize, &input)
+                .map_err(|err| Error::Plugin(err.to_string()))?;
+            let packed = func
+                .call(&mut self.store, (ptr, len))
+                .map_err(plugin_error)?;
+            if packed == 0 {
+                return Ok(None);
+            }
+            let (ptr, len) = unpack(packed);
+            let data = self.memory.data(&self.store);
+            data.get(ptr..ptr.saturating_add(len))
+                .filter(|_| len <= MAX_MESSAGE_BYTES)
+                .map(|output| Some(output.to_vec()))
+                .ok_or_else(|| Error::Plugin("the plugin returned a bad pointer".into()))
+        })();
+        self.store.data_mut().vault = None;
+        result
+    }
+}
+
+fn read(caller: &mut Caller<'_, Host>, ptr: i32, len: i32) -> wasmtime::Result<Vec<u8>> {
+    let memory = memory_of(caller)?;
+    let (ptr, len) = (ptr as u32 as usize, len as u32 as usize);
+    if len > MAX_MESSAGE_BYTES {
+        wasmtime::bail!("message too large");
+    }
+    match memory.data(&caller).get(ptr..ptr + len) {
+        Some(bytes) => Ok(bytes.to_vec()),
+        None => wasmtime::bail!("pointer out of bounds"),
+    }
+}
+
+/// Copies `bytes` into memory the plugin allocates, returning where.
+fn write(caller: &mut Caller<'_, Host>, bytes: &[u8]) -> wasmtime::Result<i64> {
+    let alloc = caller
+        .get_export("alloc")
+        .and_then(Extern::into_func)
+        .ok_or_else(|| wasmtime::format_err!("the plugin exports no alloc"))?
+        .typed::<i32, i32>(&caller)?;
+    let len = i32::try_from(bytes.len())?;
+    let ptr = alloc.call(&mut *caller, len)?;
+    memory_of(caller)?.write(&mut *caller, ptr as u32 as usize, bytes)?;
+    Ok(pack(ptr, len))
+}
+
+fn memory_of(caller: &mut Caller<'_, Host>) -> wasmtime::Result<Memory> {
+    caller
+        .get_export("memory")
+        .and_then(Extern::into_memory)
+        .ok_or_else(|| wasmtime::format_err!("the plugin exports no memory"))
+}
+
+fn pack(ptr: i32, len: i32) -> i64 {
+    ((ptr as u32 as i64) << 32) | len as u32 as i64
+}
+
+fn unpack(packed: i64) -> (usize, usize) {
+    (
+        (packed as u64 >> 32) as usize,
+        (packed as u64 & 0xffff_ffff) as usize,
+    )
+}
+
+fn plugin_error(err: wasmtime::Error) -> Error {
+    Error::Plugin(err.to_string())
+}
@@ -0,0 +1,297 @@
+//! Community plugins: sandboxed WebAssembly modules that add importers,
+//! exporters and processors without a fork of the app. Each lives in a
+//! folder of its own under `plugins/` in the app's data folder, holding a
+//! `plugin.json` [`Manifest`] and the module as `plugin.wasm`.
+//!
+//! A plugin gets nothing but the [`Capability`]s its manifest asks for and
+//! the user granted by enabling it; one that asks for more after an update
+//! stays off until enabled again.
+
+pub mod commands;
+mod host;
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tauri::{AppHandle, Emitter, Manager};
+use tokio::sync::broadcast::error::RecvError;
+use wasmtime::{Config, Engine};
+
+pub use host::PluginCommand;
+use host::Runtime;
+
+use crate::atomic;
+use crate::error::{Error, Result};
+use crate::vault::Vault;
+
+/// Event carrying `{plugin, message}` for each line a plugin logs.
+pub const LOG_EVENT: &str = "plugin-log";
+/// Event carrying the message of a plugin that failed handling an event.
+pub const ERROR_EVENT: &str = "plugin-error";
+
+const PLUGINS_DIR: &str = "plugins";
+const MANIFEST_FILE: &str = "plugin.json";
+const MODULE_FILE: &str = "plugin.wasm";
+/// The capabilities the user granted each plugin, by id.
+const GRANTS_FILE: &str = "grants.json";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Capability {
+    ReadNotes,
+    WriteNotes,
+    /// Registering commands the app can run.
+    Commands,
+    /// Being told about note changes and finished syncs.
+    Events,
+}
+
+impl Capability {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::ReadNotes => "read-notes",
+            Self::WriteNotes => "write-notes",
+            Self::Commands => "commands",
+            Self::Events => "events",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Manifest {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub version: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub capabilities: Vec<Capability>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PluginInfo {
+    #[serde(flatten)]
+    pub manifest: Manifest,
+    pub enabled: bool,
+    /// Commands it registered, while it runs.
+    pub commands: Vec<PluginCommand>,
+    /// Why it is not running although enabled.
+    pub error: Option<String>,
+}
+
+struct Plugin {
+    manifest: Manifest,
+    dir: PathBuf,
+    runtime: Option<Arc<Mutex<Runtime>>>,
+    error: Option<String>,
+}
+
+/// Every plugin found on disk, managed as app state.
+pub struct Plugins {
+    dir: PathBuf,
+    engine: Engine,
+    plugins: Mutex<Vec<Plugin>>,
+}
+
+impl Plugins {
+    pub fn new(app_dir: &Path) -> Result<Self> {
+        let mut config = Config::new();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config).map_err(|err| Error::Plugin(err.to_string()))?;
+        Ok(Self {
+            dir: app_dir.join(PLUGINS_DIR),
+            engine,
+            plugins: Mutex::new(Vec::new()),
+        })
+    }
+
+    /// Finds the plugins on disk again and starts the enabled ones. One
+    /// that fails to start is listed with its error.
+    pub fn reload(&self, app: &AppHandle) -> Result<()> {
+        let grants = self.grants()?;
+        let mut plugins = Vec::new();
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == ErrorKind::NotFound => {
+                *self.lock() = plugins;
+                return Ok(());
+            }
+            Err(err) => return Err(err.into()),
+        };
+        for entry in entries {
+            let dir = entry?.path();
+            let Ok(json) = fs::read_to_string(dir.join(MANIFEST_FILE)) else {
+                continue;
+            };
+            let manifest: Manifest = match serde_json::from_str(&json) {
+                Ok(manifest) => manifest,
+                Err(_) => continue,
+            };
+            let mut plugin = Plugin {
+                manifest,
+                dir,
+                runtime: None,
+                error: None,
+            };
+            if let Some(granted) = grants.get(&plugin.manifest.id) {
+                match self.start(app, &plugin, granted) {
+                    Ok(runtime) => plugin.runtime = Some(Arc::new(Mutex::new(runtime))),
+                    Err(err) => plugin.error = Some(err.to_string()),
+                }
+            }
+            plugins.push(plugin);
+        }
+        plugins.sort_by(|a, b| a.manifest.name.cmp(&b.manifest.name));
+        *self.lock() = plugins;
+        Ok(())
+    }
+
+    fn start(&self, app: &AppHandle, plugin: &Plugin, granted: &[Capability]) -> Result<Runtime> {
+        let asked = &plugin.manifest.capabilities;
+        if asked.iter().any(|capability| !granted.contains(capability)) {
+            return Err(Error::Plugin(
+                "it asks for more than was granted; enable it again to allow that".into(),
+            ));
+        }
+        let wasm = fs::read(plugin.dir.join(MODULE_FILE))?;
+        Runtime::start(&self.engine, app, &plugin.manifest, asked.clone(), &wasm)
+    }
+
+    pub fn list(&self) -> Vec<PluginInfo> {
+        let grants = self.grants().unwrap_or_default();
+        self.lock()
+            .iter()
+            .map(|plugin| PluginInfo {
+                manifest: plugin.manifest.clone(),
+                enabled: grants.contains_key(&plugin.manifest.id),
+                commands: plugin
+                    .runtime
+                    .as_ref()
+                    .map(|runtime| lock(runtime).commands().to_vec())
+                    .unwrap_or_default(),
+                error: plugin.error.clone(),
+            })
+            .collect()
+    }
+
+    /// Enables plugin `id`, granting what its manifest asks for, or
+    /// disables it.
+    pub fn set_enabled(&self, app: &AppHandle, id: &str, enabled: bool) -> Result<()> {
+        let capabilities = self
+            .lock()
+            .iter()
+            .find(|plugin| plugin.manifest.id == id)
+            .map(|plugin| plugin.manifest.capabilities.clone())
+            .ok_or_else(|| Error::InvalidInput(format!("no plugin {id}")))?;
+        let mut grants = self.grants()?;
+        match enabled {
+            true => grants.insert(id.to_owned(), capabilities),
+            false => grants.remove(id),
+        };
+        fs::create_dir_all(&self.dir)?;
+        atomic::write(
+            &self.dir.join(GRANTS_FILE),
+            serde_json::to_string_pretty(&grants)?.as_bytes(),
+        )?;
+        self.reload(app)
+    }
+
+    /// Runs command `name` of plugin `id` on `vault`.
+    pub fn run(&self, vault: Arc<Vault>, id: &str, name: &str, input: Value) -> Result<Value> {
+        let runtime = self
+            .lock()
+            .iter()
+            .find(|plugin| plugin.manifest.id == id)
+            .ok_or_else(|| Error::InvalidInput(format!("no plugin {id}")))?
+            .runtime
+            .clone()
+            .ok_or_else(|| Error::Plugin(format!("{id} is not running")))?;
+        let mut runtime = lock(&runtime);
+        runtime.command(vault, name, input)
+    }
+
+    /// Hands `event` to every running plugin subscribed to it.
+    fn dispatch(&self, app: &AppHandle, vault: &Arc<Vault>, event: &str, payload: &Value) {
+        let runtimes: Vec<_> = self
+            .lock()
+            .iter()
+            .filter_map(|plugin| Some((plugin.manifest.name.clone(), plugin.runtime.clone()?)))
+            .collect();
+        for (name, runtime) in runtimes {
+            let mut runtime = lock(&runtime);
+            if !runtime.subscribes_to(event) {
+                continue;
+            }
+            if let Err(err) = runtime.event(Arc::clone(vault), event, payload.clone()) {
+                tracing::warn!(plugin = %name, event, error = %err, "plugin failed handling an event");
+                let _ = app.emit(ERROR_EVENT, format!("{name}: {err}"));
+            }
+        }
+    }
+
+    fn grants(&self) -> Result<HashMap<String, Vec<Capability>>> {
+        match fs::read_to_string(self.dir.join(GRANTS_FILE)) {
+            Ok(json) => Ok(serde_json::from_str(&json)?),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(HashMap::new()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Vec<Plugin>> {
+        self.plugins.lock().expect("plugins poisoned")
+    }
+}
+
+fn lock(runtime: &Mutex<Runtime>) -> MutexGuard<'_, Runtime> {
+    runtime.lock().expect("plugin poisoned")
+}
+
+/// Passes the note changes and finished syncs of `vault` on to the plugins
+/// subscribed to them, until the vault is closed.
+pub fn spawn_events(app: AppHandle, vault: Arc<Vault>) {
+    let mut changes = vault.changes.subscribe();
+    let (handle, watched) = (app.clone(), Arc::clone(&vault));
+    tauri::async_runtime::spawn(async move {
+        while !watched.is_closed() {
+            let change = match changes.recv().await {
+                Ok(change) => change,
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => break,
+            };
+            let Ok(payload) = serde_json::to_value(change) else {
+                continue;
+            };
+            deliver(&handle, &watched, "note-changed", payload).await;
+        }
+    });
+    let mut synced = vault.synced.subscribe();
+    tauri::async_runtime::spawn(async move {
+        while !vault.is_closed() {
+            let provider = match synced.recv().await {
+                Ok(provider) => provider,
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => break,
+            };
+            let payload = serde_json::json!({ "provider": provider });
+            deliver(&app, &vault, "sync-complete", payload).await;
+        }
+    });
+}
+
+async fn deliver(app: &AppHandle, vault: &Arc<Vault>, event: &'static str, payload: Value) {
+    let (app, vault) = (app.clone(), Arc::clone(vault));
+    let _ = tauri::async_runtime::spawn_blocking(move || {
+        if let Some(plugins) = app.try_state::<Plugins>() {
+            plugins.dispatch(&app, &vault, event, &payload);
+        }
+    })
+    .await;
+}
@@ -0,0 +1,52 @@
+use super::NoteLockStatus;
+use crate::error::Result;
+use crate::storage::Note;
+use crate::vaults::Current;
+
+#[tauri::command]
+pub async fn note_lock_status(vault: Current, id: String) -> Result<NoteLockStatus> {
+    super::status(&vault, &id)
+}
+
+/// Protects note `id` with a passphrase of its own, on top of the vault's.
+#[tauri::command]
+pub async fn lock_note(vault: Current, id: String, passphrase: String) -> Result<()> {
+    tauri::async_runtime::spawn_blocking(move || super::lock_note(&vault, &id, &passphrase)).await?
+}
+
+/// Opens a protected note for this session, returning it readable.
+#[tauri::command]
+pub async fn unlock_note(vault: Current, id: String, passphrase: String) -> Result<Note> {
+    tauri::async_runtime::spawn_blocking(move || super::unlock_note(&vault, &id, &passphrase))
+        .await?
+}
+
+#[tauri::command]
+pub async fn relock_note(vault: Current, id: String) -> Result<()> {
+    super::relock_note(&vault, &id);
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn remove_note_lock(vault: Current, id: String, passphrase: String) -> Result<Note> {
+    tauri::async_runtime::spawn_blocking(move || super::remove_note_lock(&vault, &id, &passphrase))
+        .await?
+}
+
+/// Protects every note in `folder`, returning how many were sealed.
+#[tauri::command]
+pub async fn lock_folder(vault: Current, folder: String, passphrase: String) -> Result<usize> {
+    tauri::async_runtime::spawn_blocking(move || super::lock_folder(&vault, &folder, &passphrase))
+        .await?
+}
+
+#[tauri::command]
+pub async fn unlock_folder(vault: Current, folder: String, passphrase: String) -> Result<()> {
+    tauri::async_runtime::spawn_blocking(move || super::unlock_folder(&vault, &folder, &passphrase))
+        .await?
+}
+
+#[tauri::command]
+pub async fn relock_folder(vault: Current, folder: String) -> Result<()> {
+    tauri::async_runtime::spawn_blocking(move || super::relock_folder(&vault, &folder)).await?
+}
@@ -0,0 +1,476 @@
+pub mod commands;
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use rusqlite::{params, OptionalExtension};
+use serde::Serialize;
+
+use crate::collab;
+use crate::crypto::{KeyParams, VaultKey};
+use crate::error::{Error, Result};
+use crate::folders;
+use crate::history;
+use crate::storage::{Note, NotePatch, Storage};
+use crate::trash;
+use crate::vault::Vault;
+
+/// Event carrying a [`Relocked`] when protected notes lock again on their own.
+pub const RELOCKED_EVENT: &str = "note-relocked";
+
+/// Marks a body sealed with a note's own passphrase, followed by
+/// base64(key parameters as JSON), `:` and base64(nonce || ciphertext).
+/// The parameters travel with the body so any device it syncs to can
+/// derive the key from the passphrase. Such a body also goes through the
+/// vault key like any other when the vault has a password.
+const PREFIX: &str = "locked:v2:";
+/// Bodies sealed before the parameters went along, which only this
+/// device's `note_locks` has.
+const PREFIX_V1: &str = "locked:v1:";
+/// Sealed with a folder's key so its passphrase can be checked before any
+/// of its notes is opened.
+const CHECK_PLAINTEXT: &str = "notesdesktop folder key check";
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NoteLockStatus {
+    pub protected: bool,
+    /// Whether its body can be read right now.
+    pub unlocked: bool,
+    /// The locked folder it was protected with, if any.
+    pub folder: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Relocked {
+    pub vault_id: String,
+    pub note_ids: Vec<String>,
+    pub folders: Vec<String>,
+}
+
+struct Session {
+    key: Arc<VaultKey>,
+    last_used: Instant,
+}
+
+/// Keys of the protected notes and folders unlocked in this session. They
+/// are only ever held here, and dropped when the vault locks.
+#[derive(Default)]
+pub struct Unlocked {
+    notes: Mutex<HashMap<String, Session>>,
+    folders: Mutex<HashMap<String, Session>>,
+}
+
+impl Unlocked {
+    pub fn clear(&self) {
+        self.notes().clear();
+        self.folders().clear();
+    }
+
+    fn notes(&self) -> MutexGuard<'_, HashMap<String, Session>> {
+        self.notes.lock().expect("note keys poisoned")
+    }
+
+    fn folders(&self) -> MutexGuard<'_, HashMap<String, Session>> {
+        self.folders.lock().expect("folder keys poisoned")
+    }
+}
+
+pub fn is_protected(body: &str) -> bool {
+    body.starts_with(PREFIX) || body.starts_with(PREFIX_V1)
+}
+
+/// Follows the lock of a body saved on another device: a sealed body
+/// brings its key parameters, so the note opens here with the same
+/// passphrase, and a plain one over a protected note means the lock came
+/// off there.
+pub(crate) fn track(vault: &Vault, note: &Note) -> Result<()> {
+    let lock = lock_of(&vault.storage, &note.id)?;
+    if !is_protected(&note.body) {
+        if lock.is_some() {
+            vault
+                .storage
+                .conn()
+                .execute("DELETE FROM note_locks WHERE note_id = ?1", [&note.id])?;
+            relock_note(vault, &note.id);
+        }
+        return Ok(());
+    }
+    let Some(params) = params_of(&note.body) else {
+        return Ok(());
+    };
+    let params = serde_json::to_string(&params)?;
+    if let Some((current, _)) = lock {
+        if serde_json::to_string(&current)? == params {
+            return Ok(());
+        }
+    }
+    // Under a passphrase of its own now, whatever folder it was locked with.
+    vault.storage.conn().execute(
+        "INSERT OR REPLACE INTO note_locks (note_id, params) VALUES (?1, ?2)",
+        params![note.id, params],
+    )?;
+    relock_note(vault, &note.id);
+    Ok(())
+}
+
+pub fn status(vault: &Vault, id: &str) -> Result<NoteLockStatus> {
+    let row = lock_of(&vault.storage, id)?;
+    Ok(NoteLockStatus {
+        protected: row.is_some(),
+        unlocked: row.is_some() && key_for(vault, id)?.is_some(),
+        folder: row.and_then(|(_, folder)| folder),
+    })
+}
+
+/// Seals note `id` with a passphrase of its own. Its plaintext revisions
+/// and other copies kept in the database go; commits already made by git
+/// sync keep what they had.
+pub fn lock_note(vault: &Vault, id: &str, passphrase: &str) -> Result<()> {
+    check_passphrase(passphrase)?;
+    if lock_of(&vault.storage, id)?.is_some() {
+        return Err(Error::InvalidInput("the note is already protected".into()));
+    }
+    let params = KeyParams::generate();
+    let key = params.derive_key(passphrase)?;
+    protect(vault, id, &key, &params, None)
+}
+
+/// Unlocks note `id` for this session and returns it readable. It locks
+/// again when the vault does, after the idle time in the auto-lock
+/// settings, or on [`relock_note`].
+pub fn unlock_note(vault: &Vault, id: &str, passphrase: &str) -> Result<Note> {
+    let Some((params, _)) = lock_of(&vault.storage, id)? else {
+        return vault.storage.get_note(id);
+    };
+    let key = Arc::new(params.derive_key(passphrase)?);
+    let mut note = vault.storage.get_note(id)?;
+    note.body = open(&key, &note.body).map_err(wrong_password)?;
+    vault.protected.notes().insert(
+        id.to_owned(),
+        Session {
+            key,
+            last_used: Instant::now(),
+        },
+    );
+    Ok(note)
+}
+
+/// Note `id` as the editor should see it: readable if it is not protected
+/// or unlocked, otherwise with an empty body standing in for the sealed one.
+pub fn read(vault: &Vault, id: &str) -> Result<Note> {
+    let mut note = vault.storage.get_note(id)?;
+    if is_protected(&note.body) {
+        note.body = match key_for(vault, id)? {
+            Some(key) => open(&key, &note.body)?,
+            None => String::new(),
+        };
+    }
+    Ok(note)
+}
+
+/// Note `id` as exports and the API hand it out: opened if it is unlocked,
+/// or [`Error::NoteLocked`] while it is locked. Notes in the trash are not
+/// found.
+pub fn readable(vault: &Vault, id: &str) -> Result<Note> {
+    if !vault.storage.has_note(id)? || trash::is_trashed(&vault.storage, id)? {
+        return Err(Error::NoteNotFound(id.to_owned()));
+    }
+    let mut note = vault.storage.get_note(id)?;
+    if is_protected(&note.body) {
+        let key = key_for(vault, id)?.ok_or_else(|| Error::NoteLocked(id.to_owned()))?;
+        note.body = open(&key, &note.body)?;
+    }
+    Ok(note)
+}
+
+pub fn relock_note(vault: &Vault, id: &str) {
+    vault.protected.notes().remove(id);
+}
+
+/// Takes the passphrase off note `id`, leaving it in plain text again.
+pub fn remove_note_lock(vault: &Vault, id: &str, passphrase: &str) -> Result<Note> {
+    let note = unlock_note(vault, id, passphrase)?;
+    let patch = NotePatch {
+        body: Some(note.body),
+        ..Default::default()
+    };
+    let note = vault.update_note_with(id, patch, |tx| {
+        tx.execute("DELETE FROM note_locks WHERE note_id = ?1", [id])?;
+        Ok(())
+    })?;
+    relock_note(vault, id);
+    Ok(note)
+}
+
+/// Protects every note in `folder` and its subfolders with one passphrase.
+/// Notes added to the folder later are sealed the next time it locks.
+pub fn lock_folder(vault: &Vault, folder: &str, passphrase: &str) -> Result<usize> {
+    check_passphrase(passphrase)?;
+    let folder = folder.trim_matches('/');
+    if folder.is_empty() {
+        return Err(Error::InvalidInput(
+            "use the vault password to protect every note".into(),
+        ));
+    }
+    if folder_lock(&vault.storage, folder)?.is_some() {
+        return Err(Error::InvalidInput(
+            "the folder is already protected".into(),
+        ));
+    }
+    let params = KeyParams::generate();
+    let key = params.derive_key(passphrase)?;
+    vault.storage.conn().execute(
+        "INSERT INTO folder_locks (folder, params, check_value) VALUES (?1, ?2, ?3)",
+        params![
+            folder,
+            serde_json::to_string(&params)?,
+            vault
+                .storage
+                .seal_body(&seal(&key, &params, CHECK_PLAINTEXT)?)?
+        ],
+    )?;
+    seal_strays(vault, folder, &key, &params)
+}
+
+pub fn unlock_folder(vault: &Vault, folder: &str, passphrase: &str) -> Result<()> {
+    let folder = folder.trim_matches('/');
+    let (params, check) = folder_lock(&vault.storage, folder)?
+        .ok_or_else(|| Error::InvalidInput("the folder is not protected".into()))?;
+    let key = params.derive_key(passphrase)?;
+    if open(&key, &check).map_err(wrong_password)? != CHECK_PLAINTEXT {
+        return Err(Error::WrongPassword);
+    }
+    vault.protected.folders().insert(
+        folder.to_owned(),
+        Session {
+            key: Arc::new(key),
+            last_used: Instant::now(),
+        },
+    );
+    Ok(())
+}
+
+/// Locks `folder` again, first sealing notes that arrived while it was open.
+pub fn relock_folder(vault: &Vault, folder: &str) -> Result<()> {
+    let folder = folder.trim_matches('/');
+    let Some(session) = vault.protected.folders().remove(folder) else {
+        return Ok(());
+    };
+    if let Some((params, _)) = folder_lock(&vault.storage, folder)? {
+        seal_strays(vault, folder, &session.key, &params)?;
+    }
+    Ok(())
+}
+
+/// Carries the sessions of locked folders at or below `from` over to where
+/// [`crate::folders::move_folder`] put them.
+pub fn folder_moved(vault: &Vault, from: &str, to: &str) {
+    let mut sessions = vault.protected.folders();
+    let moved: Vec<String> = sessions
+        .keys()
+        .filter(|folder| folders::is_within(folder, from))
+        .cloned()
+        .collect();
+    for folder in moved {
+        if let Some(session) = sessions.remove(&folder) {
+            sessions.insert(format!("{to}{}", &folder[from.len()..]), session);
+        }
+    }
+}
+
+/// Locks the notes and folders unused for `idle`, returning which.
+pub fn expire(vault: &Vault, idle: Duration) -> Result<(Vec<String>, Vec<String>)> {
+    let stale = |sessions: &HashMap<String, Session>| -> Vec<String> {
+        sessions
+            .iter()
+            .filter(|(_, session)| session.last_used.elapsed() >= idle)
+            .map(|(id, _)| id.clone())
+            .collect()
+    };
+    let notes = stale(&vault.protected.notes());
+    for id in &notes {
+        relock_note(vault, id);
+    }
+    let folders = stale(&vault.protected.folders());
+    for folder in &folders {
+        relock_folder(vault, folder)?;
+    }
+    Ok((notes, folders))
+}
+
+/// Seals the body of a patch to a protected note with its key, returning
+/// the patch and the plain body to hand back to the editor. Fails with
+/// [`Error::NoteLocked`] while the note is locked. Bodies that are already
+/// sealed pass through.
+pub(crate) fn seal_patch(
+    vault: &Vault,
+    id: &str,
+    mut patch: NotePatch,
+) -> Result<(NotePatch, Option<String>)> {
+    let Some(body) = patch.body.take() else {
+        return Ok((patch, None));
+    };
+    let lock = match is_protected(&body) {
+        true => None,
+        false => lock_of(&vault.storage, id)?,
+    };
+    let Some((params, _)) = lock else {
+        patch.body = Some(body);
+        return Ok((patch, None));
+    };
+    let key = key_for(vault, id)?.ok_or_else(|| Error::NoteLocked(id.to_owned()))?;
+    patch.body = Some(seal(&key, &params, &body)?);
+    Ok((patch, Some(body)))
+}
+
+fn protect(
+    vault: &Vault,
+    id: &str,
+    key: &VaultKey,
+    params: &KeyParams,
+    folder: Option<&str>,
+) -> Result<()> {
+    let note = vault.storage.get_note(id)?;
+    if is_protected(&note.body) {
+        return Ok(());
+    }
+    let patch = NotePatch {
+        body: Some(seal(key, params, &note.body)?),
+        ..Default::default()
+    };
+    let params = serde_json::to_string(params)?;
+    vault.update_note_with(id, patch, |tx| {
+        tx.execute(
+            "INSERT OR REPLACE INTO note_locks (note_id, params, folder) VALUES (?1, ?2, ?3)",
+            params![id, params, folder],
+        )?;
+        Ok(())
+    })?;
+    forget_plaintext(vault, id)
+}
+
+/// Seals the notes under `folder` that are not protected yet.
+fn seal_strays(vault: &Vault, folder: &str, key: &VaultKey, params: &KeyParams) -> Result<usize> {
+    let ids: Vec<String> = {
+        let conn = vault.storage.conn();
+        let mut stmt = conn.prepare(
+            "SELECT id FROM notes
+             WHERE (folder = ?1 OR folder LIKE ?1 || '/%')
+               AND id NOT IN (SELECT note_id FROM note_locks)
+               AND id NOT IN (SELECT note_id FROM trash)",
+        )?;
+        let ids = stmt
+            .query_map([folder], |row| row.get(0))?
+            .collect::<rusqlite::Result<_>>()?;
+        ids
+    };
+    for id in &ids {
+        protect(vault, id, key, params, Some(folder))?;
+    }
+    Ok(ids.len())
+}
+
+/// Drops the revisions, collaboration state and embedding that still hold
+/// the note's plain text.
+fn forget_plaintext(vault: &Vault, id: &str) -> Result<()> {
+    vault
+        .storage
+        .conn()
+        .execute("DELETE FROM revisions WHERE note_id = ?1", [id])?;
+    vault
+        .storage
+        .conn()
+        .execute("DELETE FROM embeddings WHERE note_id = ?1", [id])?;
+    collab::forget(vault, id)?;
+    vault.drafts.discard(id)?;
+    history::prune_blobs(&vault.storage).map(drop)
+}
+
+/// The key that opens note `id` in this session, marking it as used.
+fn key_for(vault: &Vault, id: &str) -> Result<Option<Arc<VaultKey>>> {
+    if let Some(session) = vault.protected.notes().get_mut(id) {
+        session.last_used = Instant::now();
+        return Ok(Some(Arc::clone(&session.key)));
+    }
+    let Some((_, Some(folder))) = lock_of(&vault.storage, id)? else {
+        return Ok(None);
+    };
+    Ok(vault.protected.folders().get_mut(&folder).map(|session| {
+        session.last_used = Instant::now();
+        Arc::clone(&session.key)
+    }))
+}
+
+fn lock_of(storage: &Storage, id: &str) -> Result<Option<(KeyParams, Option<String>)>> {
+    let row: Option<(String, Option<String>)> = storage
+        .conn()
+        .query_row(
+            "SELECT params, folder FROM note_locks WHERE note_id = ?1",
+            [id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()?;
+    row.map(|(params, folder)| Ok((serde_json::from_str(&params)?, folder)))
+        .transpose()
+}
+
+fn folder_lock(storage: &Storage, folder: &str) -> Result<Option<(KeyParams, String)>> {
+    let row: Option<(String, String)> = storage
+        .conn()
+        .query_row(
+            "SELECT params, check_value FROM folder_locks WHERE folder = ?1",
+            [folder],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()?;
+    row.map(|(params, check)| Ok((serde_json::from_str(&params)?, storage.open_body(check)?)))
+        .transpose()
+}
+
+fn check_passphrase(passphrase: &str) -> Result<()> {
+    match passphrase.is_empty() {
+        true => Err(Error::InvalidInput(
+            "the passphrase must not be empty".into(),
+        )),
+        false => Ok(()),
+    }
+}
+
+fn seal(key: &VaultKey, params: &KeyParams, body: &str) -> Result<String> {
+    Ok(format!(
+        "{PREFIX}{}:{}",
+        BASE64.encode(serde_json::to_string(params)?),
+        BASE64.encode(key.seal_bytes(body.as_bytes())?)
+    ))
+}
+
+/// The key parameters a sealed body carries.
+fn params_of(body: &str) -> Option<KeyParams> {
+    let (params, _) = body.strip_prefix(PREFIX)?.split_once(':')?;
+    serde_json::from_slice(&BASE64.decode(params).ok()?).ok()
+}
+
+/// What a value that will not open with a passphrase means to the user.
+fn wrong_password(err: Error) -> Error {
+    match err {
+        Error::Crypto(_) => Error::WrongPassword,
+        err => err,
+    }
+}
+
+fn open(key: &VaultKey, body: &str) -> Result<String> {
+    let encoded = match (body.strip_prefix(PREFIX), body.strip_prefix(PREFIX_V1)) {
+        (Some(rest), _) => rest.split_once(':').map_or(rest, |(_, payload)| payload),
+        (None, Some(payload)) => payload,
+        (None, None) => return Ok(body.to_owned()),
+    };
+    let payload = BASE64
+        .decode(encoded)
+        .map_err(|err| Error::Crypto(err.to_string()))?;
+    String::from_utf8(key.open_bytes(&payload)?).map_err(|err| Error::Crypto(err.to_string()))
+}
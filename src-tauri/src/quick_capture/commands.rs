@@ -0,0 +1,40 @@
+use tauri::AppHandle;
+
+use super::CaptureConfig;
+use crate::error::{Error, Result};
+use crate::storage::Note;
+use crate::{shortcuts, vaults};
+
+/// Appends a capture to the inbox note. Runs against the backend alone, so
+/// it works from the capture window with the main window closed.
+#[tauri::command]
+pub async fn append_to_inbox(app: AppHandle, text: String) -> Result<Note> {
+    if text.trim().is_empty() {
+        return Err(Error::InvalidInput("nothing to capture".into()));
+    }
+    let vault = vaults::primary(&app)?;
+    super::append(&vault, &text)
+}
+
+/// Changes the capture shortcut or inbox note. Without arguments, returns
+/// the current settings; the shortcut is listed with the others.
+#[tauri::command]
+pub async fn configure_quick_capture(
+    app: AppHandle,
+    shortcut: Option<String>,
+    inbox_id: Option<String>,
+) -> Result<CaptureConfig> {
+    let vault = vaults::primary(&app)?;
+    let mut config = super::config_of(&vault.storage)?;
+    if let Some(id) = inbox_id {
+        if !vault.storage.has_note(&id)? {
+            return Err(Error::NoteNotFound(id));
+        }
+        config.inbox_id = Some(id);
+    }
+    if let Some(shortcut) = shortcut {
+        shortcuts::rebind(&app, shortcuts::QUICK_CAPTURE, Some(&shortcut))?;
+    }
+    super::set_config(&vault.storage, &config)?;
+    Ok(config)
+}
@@ -0,0 +1,125 @@
+pub mod commands;
+
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+
+use crate::error::Result;
+use crate::shortcuts::{Shortcuts, QUICK_CAPTURE};
+use crate::storage::{Note, NotePatch, Storage};
+use crate::trash;
+use crate::vault::Vault;
+
+const CONFIG_KEY: &str = "capture.config";
+const INBOX_TITLE: &str = "Inbox";
+/// Label of the capture window, which the capabilities also grant.
+pub const WINDOW_LABEL: &str = "quick-capture";
+
+/// The shortcut that opens the capture window is in the keymap, under
+/// [`crate::shortcuts::QUICK_CAPTURE`].
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct CaptureConfig {
+    /// Note captures are appended to. Created on first capture if unset or
+    /// gone.
+    pub inbox_id: Option<String>,
+}
+
+pub fn config_of(storage: &Storage) -> Result<CaptureConfig> {
+    match storage.meta(CONFIG_KEY)? {
+        Some(json) => Ok(serde_json::from_str(&json)?),
+        None => Ok(CaptureConfig::default()),
+    }
+}
+
+pub fn set_config(storage: &Storage, config: &CaptureConfig) -> Result<()> {
+    storage.set_meta(CONFIG_KEY, &serde_json::to_string(config)?)
+}
+
+/// Moves the shortcut an earlier build kept in the vault's capture
+/// settings into the keymap, if none has been saved yet, and drops it from
+/// the vault.
+pub fn adopt_legacy_shortcut(shortcuts: &Shortcuts, storage: &Storage) -> Result<()> {
+    let Some(json) = storage.meta(CONFIG_KEY)? else {
+        return Ok(());
+    };
+    let mut legacy: serde_json::Map<String, serde_json::Value> = serde_json::from_str(&json)?;
+    let Some(shortcut) = legacy.remove("shortcut") else {
+        return Ok(());
+    };
+    let shortcut = shortcut.as_str().map(str::trim).unwrap_or_default();
+    if shortcuts.is_fresh() && !shortcut.is_empty() {
+        if let Err(err) = shortcuts.set(QUICK_CAPTURE, Some(shortcut)) {
+            tracing::warn!(shortcut, error = %err, "could not keep the old capture shortcut");
+        }
+    }
+    storage.set_meta(CONFIG_KEY, &serde_json::Value::Object(legacy).to_string())
+}
+
+/// Appends `text` to the inbox note under a timestamp heading.
+pub fn append(vault: &Vault, text: &str) -> Result<Note> {
+    let inbox = inbox(vault)?;
+    let stamp = Local::now().format("%Y-%m-%d %H:%M");
+    let mut body = inbox.body.trim_end().to_owned();
+    if !body.is_empty() {
+        body.push_str("\n\n");
+    }
+    body.push_str(&format!("## {stamp}\n\n{}\n", text.trim()));
+    vault.update_note(
+        &inbox.id,
+        NotePatch {
+            title: None,
+            body: Some(body),
+            folder: None,
+        },
+    )
+}
+
+/// The inbox note, made anew if it was never set up or is gone.
+pub fn inbox(vault: &Vault) -> Result<Note> {
+    let mut config = config_of(&vault.storage)?;
+    if let Some(id) = config.inbox_id.as_deref() {
+        if vault.storage.has_note(id)? && !trash::is_trashed(&vault.storage, id)? {
+            return vault.storage.get_note(id);
+        }
+    }
+    let note = vault.create_note(INBOX_TITLE, "", "")?;
+    config.inbox_id = Some(note.id.clone());
+    set_config(&vault.storage, &config)?;
+    Ok(note)
+}
+
+#[cfg(desktop)]
+mod window {
+    use tauri::{AppHandle, Manager, WebviewUrl, WebviewWindowBuilder};
+
+    use super::WINDOW_LABEL;
+    use crate::error::Result;
+
+    /// Shows the capture window, building it the first time, or hides it
+    /// if it is already up.
+    pub fn toggle(app: &AppHandle) -> Result<()> {
+        if let Some(window) = app.get_webview_window(WINDOW_LABEL) {
+            if window.is_visible()? && window.is_focused()? {
+                window.hide()?;
+            } else {
+                window.show()?;
+                window.set_focus()?;
+            }
+            return Ok(());
+        }
+        let window =
+            WebviewWindowBuilder::new(app, WINDOW_LABEL, WebviewUrl::App("capture".into()))
+                .title("Quick capture")
+                .inner_size(480.0, 160.0)
+                .resizable(false)
+                .always_on_top(true)
+                .skip_taskbar(true)
+                .center()
+                .build()?;
+        window.set_focus()?;
+        Ok(())
+    }
+}
+
+#[cfg(desktop)]
+pub use window::toggle;
@@ -0,0 +1,15 @@
+use super::FuzzyMatch;
+use crate::error::Result;
+use crate::vaults::Current;
+
+const DEFAULT_LIMIT: usize = 20;
+
+/// Notes whose title, alias or path fuzzily match `query`, best first.
+#[tauri::command]
+pub async fn fuzzy_find(
+    vault: Current,
+    query: String,
+    limit: Option<usize>,
+) -> Result<Vec<FuzzyMatch>> {
+    Ok(vault.switcher.find(&query, limit.unwrap_or(DEFAULT_LIMIT)))
+}
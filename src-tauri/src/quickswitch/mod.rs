@@ -0,0 +1,185 @@
+pub mod commands;
+
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::sync::{Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use nucleo_matcher::pattern::{CaseMatching, Normalization, Pattern};
+use nucleo_matcher::{Config, Matcher, Utf32String};
+use serde::Serialize;
+
+use crate::collation;
+use crate::metadata::FrontMatter;
+use crate::storage::Note;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum MatchedOn {
+    Title,
+    Alias,
+    Path,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FuzzyMatch {
+    pub id: String,
+    pub title: String,
+    pub folder: String,
+    pub matched_on: MatchedOn,
+    /// The title, alias or path that matched.
+    pub text: String,
+    /// Character positions in `text` to highlight.
+    pub indices: Vec<u32>,
+    pub score: u32,
+}
+
+struct Entry {
+    title: String,
+    folder: String,
+    updated_at: i64,
+    /// Title first, then aliases, then the path, already converted for the
+    /// matcher so a query does not convert 50k strings each keystroke.
+    haystacks: Vec<(MatchedOn, String, Utf32String)>,
+}
+
+/// Titles, aliases and paths of every note for the quick switcher, kept in
+/// memory like [`crate::tags::TagIndex`].
+pub struct SwitchIndex {
+    notes: RwLock<HashMap<String, Entry>>,
+    matcher: Mutex<Matcher>,
+}
+
+impl Default for SwitchIndex {
+    fn default() -> Self {
+        Self {
+            notes: RwLock::default(),
+            matcher: Mutex::new(Matcher::new(Config::DEFAULT.match_paths())),
+        }
+    }
+}
+
+impl SwitchIndex {
+    pub fn index_note(&self, note: &Note) {
+        self.write().insert(note.id.clone(), entry(note));
+    }
+
+    pub fn remove_note(&self, id: &str) {
+        self.write().remove(id);
+    }
+
+    pub fn rebuild(&self, notes: &[Note]) {
+        let index = notes
+            .iter()
+            .map(|note| (note.id.clone(), entry(note)))
+            .collect();
+        *self.write() = index;
+    }
+
+    pub fn clear(&self) {
+        self.write().clear();
+    }
+
+    /// The `limit` notes matching `query` best, each with whichever of its
+    /// title, aliases or path matched best. Titles win ties. An empty query
+    /// gives the most recently edited notes.
+    pub fn find(&self, query: &str, limit: usize) -> Vec<FuzzyMatch> {
+        let notes = self.read();
+        if query.trim().is_empty() {
+            let mut recent: Vec<(&String, &Entry)> = notes.iter().collect();
+            recent.sort_by_key(|(_, entry)| Reverse(entry.updated_at));
+            return recent
+                .into_iter()
+                .take(limit)
+                .map(|(id, entry)| FuzzyMatch {
+                    id: id.clone(),
+                    title: entry.title.clone(),
+                    folder: entry.folder.clone(),
+                    matched_on: MatchedOn::Title,
+                    text: entry.title.clone(),
+                    indices: Vec::new(),
+                    score: 0,
+                })
+                .collect();
+        }
+
+        let pattern = Pattern::parse(query, CaseMatching::Smart, Normalization::Smart);
+        let mut matcher = self.matcher.lock().expect("quick switcher poisoned");
+        let mut ranked: Vec<(u32, &String, &Entry, usize)> = Vec::new();
+        for (id, entry) in notes.iter() {
+            let best = entry
+                .haystacks
+                .iter()
+                .enumerate()
+                .filter_map(|(i, (_, _, haystack))| {
+                    Some((pattern.score(haystack.slice(..), &mut matcher)?, i))
+                })
+                .max_by_key(|(score, i)| (*score, Reverse(*i)));
+            if let Some((score, i)) = best {
+                ranked.push((score, id, entry, i));
+            }
+        }
+        ranked.sort_unstable_by(|a, b| {
+            (Reverse(a.0), a.2.title.len())
+                .cmp(&(Reverse(b.0), b.2.title.len()))
+                .then_with(|| collation::compare(&a.2.title, &b.2.title))
+        });
+        ranked.truncate(limit);
+
+        // Only the matches shown need their positions worked out.
+        ranked
+            .into_iter()
+            .map(|(score, id, entry, i)| {
+                let (matched_on, text, haystack) = &entry.haystacks[i];
+                let mut indices = Vec::new();
+                pattern.indices(haystack.slice(..), &mut matcher, &mut indices);
+                indices.sort_unstable();
+                indices.dedup();
+                FuzzyMatch {
+                    id: id.clone(),
+                    title: entry.title.clone(),
+                    folder: entry.folder.clone(),
+                    matched_on: *matched_on,
+                    text: text.clone(),
+                    indices,
+                    score,
+                }
+            })
+            .collect()
+    }
+
+    fn read(&self) -> RwLockReadGuard<'_, HashMap<String, Entry>> {
+        self.notes.read().expect("quick switcher poisoned")
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, HashMap<String, Entry>> {
+        self.notes.write().expect("quick switcher poisoned")
+    }
+}
+
+fn entry(note: &Note) -> Entry {
+    let mut haystacks = vec![(MatchedOn::Title, note.title.clone())];
+    if let (Some(front_matter), _) = FrontMatter::split(&note.body) {
+        haystacks.extend(
+            front_matter
+                .aliases()
+                .into_iter()
+                .map(|alias| (MatchedOn::Alias, alias)),
+        );
+    }
+    if !note.folder.is_empty() {
+        haystacks.push((MatchedOn::Path, format!("{}/{}", note.folder, note.title)));
+    }
+    Entry {
+        title: note.title.clone(),
+        folder: note.folder.clone(),
+        updated_at: note.updated_at,
+        haystacks: haystacks
+            .into_iter()
+            .map(|(on, text)| {
+                let haystack = Utf32String::from(text.as_str());
+                (on, text, haystack)
+            })
+            .collect(),
+    }
+}
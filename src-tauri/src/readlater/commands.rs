@@ -0,0 +1,43 @@
+use super::{ReadFilter, ReadLaterItem};
+use crate::error::Result;
+use crate::vaults::Current;
+
+/// Clips the article at `url` into `folder`, "Read later" by default, with
+/// an offline copy of the page, and adds it to the queue.
+#[tauri::command]
+pub async fn save_for_later(
+    vault: Current,
+    url: String,
+    folder: Option<String>,
+) -> Result<ReadLaterItem> {
+    super::save(&vault, &url, folder.as_deref()).await
+}
+
+/// The queue, newest first; unread articles unless `filter` says otherwise.
+#[tauri::command]
+pub async fn list_read_later(
+    vault: Current,
+    filter: Option<ReadFilter>,
+) -> Result<Vec<ReadLaterItem>> {
+    super::list(&vault.storage, filter.unwrap_or_default())
+}
+
+#[tauri::command]
+pub async fn mark_read(vault: Current, note_id: String, read: bool) -> Result<ReadLaterItem> {
+    super::set_read(&vault.storage, &note_id, read)
+}
+
+/// Records how far into the article the reader scrolled, from 0 to 1.
+#[tauri::command]
+pub async fn set_reading_progress(
+    vault: Current,
+    note_id: String,
+    progress: f64,
+) -> Result<ReadLaterItem> {
+    super::set_progress(&vault.storage, &note_id, progress)
+}
+
+#[tauri::command]
+pub async fn remove_from_read_later(vault: Current, note_id: String) -> Result<()> {
+    super::remove(&vault.storage, &note_id)
+}
@@ -0,0 +1,173 @@
+//! Articles saved to read later. Each is clipped into a note, with a copy of
+//! the page and the files it loads so it reads offline and outlives the
+//! site; the queue itself, what has been read and how far into the rest the
+//! reader got, is kept beside the notes.
+
+pub mod commands;
+
+use rusqlite::{params, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+
+use crate::clipper::{self, ClipOptions};
+use crate::error::{Error, Result};
+use crate::files::NoteFiles;
+use crate::storage::{now_millis, Storage};
+use crate::vault::Vault;
+
+/// Where articles go unless asked otherwise.
+const DEFAULT_FOLDER: &str = "Read later";
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReadLaterItem {
+    pub note_id: String,
+    pub title: String,
+    pub url: String,
+    /// Mirror path of the archived page, if it could be kept.
+    pub archive: Option<String>,
+    pub added_at: i64,
+    pub read_at: Option<i64>,
+    /// How far into the article the reader got, from 0 to 1.
+    pub progress: f64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ReadFilter {
+    #[default]
+    Unread,
+    Read,
+    All,
+}
+
+const COLUMNS: &str = "read_later.note_id, notes.title, read_later.url, read_later.archive,
+    read_later.added_at, read_later.read_at, read_later.progress";
+
+fn item_from_row(row: &Row) -> rusqlite::Result<ReadLaterItem> {
+    Ok(ReadLaterItem {
+        note_id: row.get(0)?,
+        title: row.get(1)?,
+        url: row.get(2)?,
+        archive: row.get(3)?,
+        added_at: row.get(4)?,
+        read_at: row.get(5)?,
+        progress: row.get(6)?,
+    })
+}
+
+/// The queue, newest first, leaving out notes in the trash.
+pub fn list(storage: &Storage, filter: ReadFilter) -> Result<Vec<ReadLaterItem>> {
+    let condition = match filter {
+        ReadFilter::Unread => "AND read_later.read_at IS NULL",
+        ReadFilter::Read => "AND read_later.read_at IS NOT NULL",
+        ReadFilter::All => "",
+    };
+    let conn = storage.conn();
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {COLUMNS} FROM read_later JOIN notes ON notes.id = read_later.note_id
+         WHERE read_later.note_id NOT IN (SELECT note_id FROM trash) {condition}
+         ORDER BY read_later.added_at DESC"
+    ))?;
+    let items = stmt
+        .query_map([], item_from_row)?
+        .collect::<rusqlite::Result<_>>()?;
+    Ok(items)
+}
+
+pub fn get(storage: &Storage, note_id: &str) -> Result<ReadLaterItem> {
+    find(storage, "read_later.note_id", note_id)?
+        .ok_or_else(|| Error::InvalidInput(format!("not in the read-later queue: {note_id}")))
+}
+
+fn find(storage: &Storage, column: &str, value: &str) -> Result<Option<ReadLaterItem>> {
+    let item = storage
+        .conn()
+        .query_row(
+            &format!(
+                "SELECT {COLUMNS} FROM read_later JOIN notes ON notes.id = read_later.note_id
+                 WHERE {column} = ?1"
+            ),
+            [value],
+            item_from_row,
+        )
+        .optional()?;
+    Ok(item)
+}
+
+/// Clips the article at `url` into `folder` and queues it. An address
+/// already queued gives back what was saved then.
+pub async fn save(vault: &Vault, url: &str, folder: Option<&str>) -> Result<ReadLaterItem> {
+    let url = url.trim();
+    if let Some(item) = find(&vault.storage, "read_later.url", url)? {
+        return Ok(item);
+    }
+    let options = ClipOptions {
+        folder: folder
+            .map(|folder| folder.trim_matches('/'))
+            .filter(|folder| !folder.is_empty())
+            .unwrap_or(DEFAULT_FOLDER)
+            .to_owned(),
+        images: true,
+        archive: true,
+        assets: true,
+        ..ClipOptions::default()
+    };
+    let page = clipper::fetch(url, &options).await?;
+    let clipping = clipper::save(vault, page, &options)?;
+    let archive = clipping
+        .archive
+        .as_deref()
+        .and_then(|link| NoteFiles::resolve_from(&options.folder, link));
+    vault.storage.conn().execute(
+        "INSERT INTO read_later (note_id, url, archive, added_at) VALUES (?1, ?2, ?3, ?4)",
+        params![clipping.note_id, url, archive, now_millis()],
+    )?;
+    get(&vault.storage, &clipping.note_id)
+}
+
+/// Marks an article read, or unread again.
+pub fn set_read(storage: &Storage, note_id: &str, read: bool) -> Result<ReadLaterItem> {
+    let read_at = read.then(now_millis);
+    let changed = storage.conn().execute(
+        "UPDATE read_later SET read_at = CASE WHEN ?2 IS NULL THEN NULL
+             ELSE COALESCE(read_at, ?2) END
+         WHERE note_id = ?1",
+        params![note_id, read_at],
+    )?;
+    if changed == 0 {
+        return Err(Error::InvalidInput(format!(
+            "not in the read-later queue: {note_id}"
+        )));
+    }
+    get(storage, note_id)
+}
+
+/// Records how far into an article the reader got. Reaching the end marks
+/// it read.
+pub fn set_progress(storage: &Storage, note_id: &str, progress: f64) -> Result<ReadLaterItem> {
+    if !progress.is_finite() {
+        return Err(Error::InvalidInput(format!("not a position: {progress}")));
+    }
+    let progress = progress.clamp(0.0, 1.0);
+    let changed = storage.conn().execute(
+        "UPDATE read_later SET progress = ?2,
+             read_at = CASE WHEN ?2 >= 1 THEN COALESCE(read_at, ?3) ELSE read_at END
+         WHERE note_id = ?1",
+        params![note_id, progress, now_millis()],
+    )?;
+    if changed == 0 {
+        return Err(Error::InvalidInput(format!(
+            "not in the read-later queue: {note_id}"
+        )));
+    }
+    get(storage, note_id)
+}
+
+/// Takes an article off the queue. Its note stays, but the archived page
+/// and its files are no longer kept from the attachment clean-up.
+pub fn remove(storage: &Storage, note_id: &str) -> Result<()> {
+    storage
+        .conn()
+        .execute("DELETE FROM read_later WHERE note_id = ?1", [note_id])?;
+    Ok(())
+}
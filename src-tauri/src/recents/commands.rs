@@ -0,0 +1,26 @@
+use tauri::{AppHandle, Manager, Window};
+
+use crate::error::Result;
+use crate::storage::NoteSummary;
+use crate::vaults::{Current, Vaults};
+
+#[tauri::command]
+pub async fn pin_note(app: AppHandle, window: Window, id: String, pinned: bool) -> Result<()> {
+    let vaults = app.state::<Vaults>();
+    let vault_id = vaults.id_of(window.label());
+    let vault = vaults.get(&app, &vault_id)?;
+    super::pin(&vault.storage, &id, pinned)?;
+    super::changed(&app, &vault_id, &id, pinned);
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn list_pinned(vault: Current) -> Result<Vec<NoteSummary>> {
+    super::pinned(&vault.storage)
+}
+
+/// The notes opened most often and most lately, best first.
+#[tauri::command]
+pub async fn recent_notes(vault: Current, limit: Option<usize>) -> Result<Vec<NoteSummary>> {
+    super::recent(&vault.storage, limit.unwrap_or(super::DEFAULT_RECENT))
+}
@@ -0,0 +1,134 @@
+//! Pinned notes and the "Recent" list. Opening a note counts as a visit,
+//! and recents are ranked by frecency: every visit adds one to a note's
+//! score, and scores halve each week they go unused, so a note opened
+//! daily last month can still outrank one opened once this morning.
+
+pub mod commands;
+
+use std::collections::HashMap;
+
+use rusqlite::{params, OptionalExtension};
+use serde::Serialize;
+use tauri::{AppHandle, Emitter};
+
+use crate::error::{Error, Result};
+use crate::storage::{now_millis, NoteSummary, Storage};
+
+/// Event broadcast with a [`PinnedChange`] when a note is pinned or
+/// unpinned, so every window's sidebar shows the same pins.
+pub const PINNED_EVENT: &str = "pinned-changed";
+
+const HALF_LIFE_MS: f64 = 7.0 * 24.0 * 60.0 * 60.0 * 1000.0;
+/// Openings this close together count as one visit, so windows reloading
+/// a note do not inflate it.
+const REVISIT_MS: i64 = 60 * 1000;
+pub const DEFAULT_RECENT: usize = 20;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PinnedChange {
+    pub vault_id: String,
+    pub note_id: String,
+    pub pinned: bool,
+}
+
+/// Counts a visit to note `id`.
+pub fn visited(storage: &Storage, id: &str) -> Result<()> {
+    let now = now_millis();
+    let conn = storage.conn();
+    let last: Option<(f64, i64)> = conn
+        .query_row(
+            "SELECT score, visited_at FROM note_visits WHERE note_id = ?1",
+            [id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()?;
+    let score = match last {
+        Some((score, at)) if now - at < REVISIT_MS => score,
+        Some((score, at)) => decayed(score, at, now) + 1.0,
+        None => 1.0,
+    };
+    conn.execute(
+        "INSERT INTO note_visits (note_id, score, visited_at) VALUES (?1, ?2, ?3)
+         ON CONFLICT (note_id) DO UPDATE SET score = excluded.score, visited_at = excluded.visited_at",
+        params![id, score, now],
+    )?;
+    Ok(())
+}
+
+fn decayed(score: f64, at: i64, now: i64) -> f64 {
+    score * 0.5f64.powf((now - at).max(0) as f64 / HALF_LIFE_MS)
+}
+
+/// The `limit` notes outside the trash and the archive with the highest
+/// frecency.
+pub fn recent(storage: &Storage, limit: usize) -> Result<Vec<NoteSummary>> {
+    let now = now_millis();
+    let scores: HashMap<String, f64> = {
+        let conn = storage.conn();
+        let mut stmt = conn.prepare("SELECT note_id, score, visited_at FROM note_visits")?;
+        let rows = stmt.query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get(1)?, row.get(2)?))
+        })?;
+        rows.map(|row| row.map(|(id, score, at)| (id, decayed(score, at, now))))
+            .collect::<rusqlite::Result<_>>()?
+    };
+    let mut notes: Vec<(f64, NoteSummary)> = storage
+        .list_notes(None)?
+        .into_iter()
+        .filter(|note| !note.archived)
+        .filter_map(|note| Some((*scores.get(&note.id)?, note)))
+        .collect();
+    notes.sort_by(|a, b| {
+        b.0.total_cmp(&a.0)
+            .then(b.1.updated_at.cmp(&a.1.updated_at))
+    });
+    Ok(notes
+        .into_iter()
+        .take(limit)
+        .map(|(_, note)| note)
+        .collect())
+}
+
+pub fn pin(storage: &Storage, id: &str, pinned: bool) -> Result<()> {
+    if !storage.has_note(id)? {
+        return Err(Error::NoteNotFound(id.to_owned()));
+    }
+    let conn = storage.conn();
+    match pinned {
+        true => conn.execute(
+            "INSERT OR IGNORE INTO pinned (note_id, pinned_at) VALUES (?1, ?2)",
+            params![id, now_millis()],
+        )?,
+        false => conn.execute("DELETE FROM pinned WHERE note_id = ?1", [id])?,
+    };
+    Ok(())
+}
+
+/// Pinned notes outside the trash, in the order they were pinned.
+pub fn pinned(storage: &Storage) -> Result<Vec<NoteSummary>> {
+    let order: HashMap<String, i64> = {
+        let conn = storage.conn();
+        let mut stmt = conn.prepare("SELECT note_id, pinned_at FROM pinned")?;
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        rows.collect::<rusqlite::Result<_>>()?
+    };
+    let mut notes: Vec<(i64, NoteSummary)> = storage
+        .list_notes(None)?
+        .into_iter()
+        .filter_map(|note| Some((*order.get(&note.id)?, note)))
+        .collect();
+    notes.sort_by_key(|(at, _)| *at);
+    Ok(notes.into_iter().map(|(_, note)| note).collect())
+}
+
+/// Tells every window that note `note_id` of vault `vault_id` was pinned
+/// or unpinned.
+pub fn changed(app: &AppHandle, vault_id: &str, note_id: &str, pinned: bool) {
+    let change = PinnedChange {
+        vault_id: vault_id.to_owned(),
+        note_id: note_id.to_owned(),
+        pinned,
+    };
+    let _ = app.emit(PINNED_EVENT, change);
+}
@@ -0,0 +1,324 @@
+//! Microphone capture with cpal, encoded to Opus in an Ogg stream as it
+//! comes in.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{FromSample, SampleFormat, SizedSample, Stream, StreamConfig};
+use ogg::{PacketWriteEndInfo, PacketWriter};
+use opus::{Application, Bitrate, Channels, Encoder};
+
+use super::{Encoded, Level};
+use crate::error::{Error, Result};
+
+pub const AVAILABLE: bool = true;
+
+/// Opus always works at 48 kHz internally, and granule positions count
+/// samples at that rate.
+const RATE: u32 = 48_000;
+/// 20 ms frames.
+const FRAME: usize = 960;
+/// Plenty for speech in mono.
+const BITRATE: i32 = 32_000;
+const MAX_PACKET: usize = 4000;
+/// Frames per level event, about 15 a second.
+const FRAMES_PER_LEVEL: usize = 3;
+
+pub struct Capture {
+    stop: Arc<AtomicBool>,
+    thread: JoinHandle<Result<Encoded>>,
+}
+
+impl Capture {
+    /// Ends the recording and returns it, once everything captured so far
+    /// is encoded.
+    pub fn stop(self) -> Result<Encoded> {
+        self.stop.store(true, Ordering::Relaxed);
+        self.thread
+            .join()
+            .map_err(|_| Error::Recorder("the recording thread panicked".into()))?
+    }
+}
+
+/// Starts recording from the default input device. The stream lives on a
+/// thread of its own, since cpal streams cannot move between threads.
+pub fn start(on_level: impl Fn(Level) + Send + 'static) -> Result<Capture> {
+    let stop = Arc::new(AtomicBool::new(false));
+    let (ready_tx, ready_rx) = mpsc::sync_channel(1);
+    let stopped = Arc::clone(&stop);
+    let thread = std::thread::spawn(move || {
+        let (samples_tx, samples_rx) = mpsc::channel();
+        let (stream, rate) = match open(samples_tx) {
+            Ok(opened) => {
+                let _ = ready_tx.send(Ok(()));
+                opened
+            }
+            Err(err) => {
+                let message = err.to_string();
+                let _ = ready_tx.send(Err(err));
+                return Err(Error::Recorder(message));
+            }
+        };
+        let result = record(&samples_rx, rate, &stopped, &on_level);
+        drop(stream);
+        result
+    });
+    match ready_rx.recv() {
+        Ok(Ok(())) => Ok(Capture { stop, thread }),
+        Ok(Err(err)) => Err(err),
+        Err(_) => Err(Error::Recorder("the recording thread stopped".into())),
+    }
+}
+
+/// Opens and starts the default microphone, sending mono chunks at its
+/// own sample rate to `samples`.
+fn open(samples: mpsc::Sender<Vec<f32>>) -> Result<(Stream, u32)> {
+    let device = cpal::default_host()
+        .default_input_device()
+        .ok_or_else(|| Error::Recorder("no microphone found".into()))?;
+    let supported = device.default_input_config().map_err(recorder_error)?;
+    let config: StreamConfig = supported.config();
+    let stream = match supported.sample_format() {
+        SampleFormat::F32 => build::<f32>(&device, &config, samples),
+        SampleFormat::I16 => build::<i16>(&device, &config, samples),
+        SampleFormat::U16 => build::<u16>(&device, &config, samples),
+        SampleFormat::I32 => build::<i32>(&device, &config, samples),
+        other => Err(Error::Recorder(format!(
+            "unsupported sample format {other}"
+        ))),
+    }?;
+    stream.play().map_err(recorder_error)?;
+    Ok((stream, config.sample_rate.0))
+}
+
+fn build<T>(
+    device: &cpal::Device,
+    config: &StreamConfig,
+    samples: mpsc::Sender<Vec<f32>>,
+) -> Result<Stream>
+where
+    T: SizedSample,
+    f32: FromSample<T>,
+{
+    let channels = usize::from(config.channels.max(1));
+    device
+        .build_input_stream(
+            config,
+            move |data: &[T], _| {
+                let mono = data
+                    .chunks(channels)
+                    .map(|frame| {
+                        frame.iter().map(|s| s.to_sample::<f32>()).sum::<f32>() / frame.len() as f32
+                    })
+                    .collect();
+                let _ = samples.send(mono);
+            },
+            // Dropouts are not worth ending the recording over.
+            |_| {},
+            None,
+        )
+        .map_err(recorder_error)
+}
+
+fn record(
+    samples: &mpsc::Receiver<Vec<f32>>,
+    input_rate: u32,
+    stop: &AtomicBool,
+    on_level: &dyn Fn(Level),
+) -> Result<Encoded> {
+    let mut encoder = Encoder::new(RATE, Channels::Mono, Application::Voip).map_err(opus_error)?;
+    encoder
+        .set_bitrate(Bitrate::Bits(BITRATE))
+        .map_err(opus_error)?;
+    let pre_skip = encoder.get_lookahead().map_err(opus_error)? as u16;
+
+    let serial = rand::random::<u32>();
+    let mut ogg = PacketWriter::new(Vec::new());
+    ogg.write_packet(
+        opus_head(pre_skip, input_rate),
+        serial,
+        PacketWriteEndInfo::EndPage,
+        0,
+    )?;
+    ogg.write_packet(opus_tags(), serial, PacketWriteEndInfo::EndPage, 0)?;
+
+    let started = Instant::now();
+    let mut resampler = Resampler::new(input_rate);
+    let mut pending: Vec<f32> = Vec::new();
+    let mut encoded_samples: u64 = 0;
+    let mut meter = Meter::default();
+    let mut packet = vec![0u8; MAX_PACKET];
+    let mut last: Option<Vec<u8>> = None;
+    loop {
+        let mut done = stop.load(Ordering::Relaxed);
+        match samples.recv_timeout(Duration::from_millis(50)) {
+            Ok(chunk) => resampler.push(&chunk, &mut pending),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => done = true,
+        }
+        // Once asked to stop, what is already queued is still taken.
+        if done {
+            while let Ok(chunk) = samples.try_recv() {
+                resampler.push(&chunk, &mut pending);
+            }
+        }
+        while pending.len() >= FRAME {
+            let frame: Vec<f32> = pending.drain(..FRAME).collect();
+            if let Some(level) = meter.add(&frame, started.elapsed()) {
+                on_level(level);
+            }
+            let len = encoder
+                .encode_float(&frame, &mut packet)
+                .map_err(opus_error)?;
+            // Each packet is written once the next is known, so the very
+            // last one can end the stream.
+            if let Some(previous) = last.replace(packet[..len].to_vec()) {
+                ogg.write_packet(
+                    previous,
+                    serial,
+                    PacketWriteEndInfo::NormalPacket,
+                    u64::from(pre_skip) + encoded_samples,
+                )?;
+            }
+            encoded_samples += FRAME as u64;
+        }
+        if done {
+            break;
+        }
+    }
+
+    // The tail, padded with silence to a whole frame.
+    let tail = pending.len();
+    if tail > 0 {
+        pending.resize(FRAME, 0.0);
+        let len = encoder
+            .encode_float(&pending, &mut packet)
+            .map_err(opus_error)?;
+        if let Some(previous) = last.replace(packet[..len].to_vec()) {
+            ogg.write_packet(
+                previous,
+                serial,
+                PacketWriteEndInfo::NormalPacket,
+                u64::from(pre_skip) + encoded_samples,
+            )?;
+        }
+        encoded_samples += tail as u64;
+    }
+    let Some(last) = last else {
+        return Err(Error::Recorder("nothing was recorded".into()));
+    };
+    ogg.write_packet(
+        last,
+        serial,
+        PacketWriteEndInfo::EndStream,
+        u64::from(pre_skip) + encoded_samples,
+    )?;
+    Ok(Encoded {
+        ogg: ogg.into_inner(),
+        duration_ms: encoded_samples * 1000 / u64::from(RATE),
+    })
+}
+
+/// The identification header of RFC 7845.
+fn opus_head(pre_skip: u16, input_rate: u32) -> Vec<u8> {
+    let mut head = b"OpusHead".to_vec();
+    head.push(1); // version
+    head.push(1); // channels
+    head.extend_from_slice(&pre_skip.to_le_bytes());
+    head.extend_from_slice(&input_rate.to_le_bytes());
+    head.extend_from_slice(&0i16.to_le_bytes()); // output gain
+    head.push(0); // mono/stereo mapping
+    head
+}
+
+/// The comment header, with only the vendor.
+fn opus_tags() -> Vec<u8> {
+    let vendor = b"notesdesktop";
+    let mut tags = b"OpusTags".to_vec();
+    tags.extend_from_slice(&(vendor.len() as u32).to_le_bytes());
+    tags.extend_from_slice(vendor);
+    tags.extend_from_slice(&0u32.to_le_bytes());
+    tags
+}
+
+/// Linear resampling to 48 kHz, carried across chunks. Good enough for
+/// speech; most microphones run at 48 kHz anyway.
+struct Resampler {
+    step: f64,
+    /// Position of the next output sample, in input samples after `prev`.
+    pos: f64,
+    prev: f32,
+}
+
+impl Resampler {
+    fn new(input_rate: u32) -> Self {
+        Self {
+            step: f64::from(input_rate) / f64::from(RATE),
+            pos: 0.0,
+            prev: 0.0,
+        }
+    }
+
+    fn push(&mut self, input: &[f32], out: &mut Vec<f32>) {
+        let Some(&last) = input.last() else {
+            return;
+        };
+        let end = (input.len() - 1) as f64;
+        while self.pos <= end {
+            let i = self.pos.floor();
+            let frac = (self.pos - i) as f32;
+            let (a, b) = match i < 0.0 {
+                true => (self.prev, input[0]),
+                false => {
+                    let i = i as usize;
+                    (input[i], input.get(i + 1).copied().unwrap_or(input[i]))
+                }
+            };
+            out.push(a + (b - a) * frac);
+            self.pos += self.step;
+        }
+        self.pos -= input.len() as f64;
+        self.prev = last;
+    }
+}
+
+#[derive(Default)]
+struct Meter {
+    frames: usize,
+    sum_squares: f32,
+    samples: usize,
+    peak: f32,
+}
+
+impl Meter {
+    fn add(&mut self, frame: &[f32], elapsed: Duration) -> Option<Level> {
+        for sample in frame {
+            self.sum_squares += sample * sample;
+            self.peak = self.peak.max(sample.abs());
+        }
+        self.samples += frame.len();
+        self.frames += 1;
+        if self.frames < FRAMES_PER_LEVEL {
+            return None;
+        }
+        let level = Level {
+            rms: (self.sum_squares / self.samples as f32).sqrt().min(1.0),
+            peak: self.peak.min(1.0),
+            elapsed_ms: elapsed.as_millis() as u64,
+        };
+        *self = Self::default();
+        Some(level)
+    }
+}
+
+fn recorder_error(err: impl std::fmt::Display) -> Error {
+    Error::Recorder(err.to_string())
+}
+
+fn opus_error(err: opus::Error) -> Error {
+    Error::Recorder(err.to_string())
+}
@@ -0,0 +1,29 @@
+use tauri::{AppHandle, Manager, State, Window};
+
+use super::{Recorder, Recording, RecordingStatus};
+use crate::error::Result;
+use crate::vaults::Vaults;
+
+/// Starts recording a voice memo for note `note_id` of the calling
+/// window's vault. Levels arrive as `recording-level` events.
+#[tauri::command]
+pub async fn start_recording(
+    app: AppHandle,
+    window: Window,
+    recorder: State<'_, Recorder>,
+    note_id: String,
+) -> Result<()> {
+    let vault_id = app.state::<Vaults>().id_of(window.label());
+    recorder.start(&app, &vault_id, &note_id)
+}
+
+/// Stops the recording and embeds it in its note.
+#[tauri::command]
+pub async fn stop_recording(app: AppHandle) -> Result<Recording> {
+    tauri::async_runtime::spawn_blocking(move || app.state::<Recorder>().stop(&app)).await?
+}
+
+#[tauri::command]
+pub async fn recording_status(recorder: State<'_, Recorder>) -> Result<RecordingStatus> {
+    Ok(recorder.status())
+}
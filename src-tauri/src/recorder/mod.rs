@@ -0,0 +1,146 @@
+pub mod commands;
+
+#[cfg_attr(feature = "recorder", path = "capture.rs")]
+#[cfg_attr(not(feature = "recorder"), path = "unsupported.rs")]
+mod capture;
+
+use std::sync::Mutex;
+
+use chrono::Local;
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::attachments::{self, Attachment};
+use crate::error::{Error, Result};
+use crate::protected;
+use crate::storage::{now_millis, NotePatch};
+use crate::vaults::Vaults;
+
+/// Event carrying a [`Level`] several times a second while recording.
+pub const LEVEL_EVENT: &str = "recording-level";
+/// Whether this build can record.
+pub const AVAILABLE: bool = capture::AVAILABLE;
+
+/// Loudness of the last few milliseconds of a recording, for a meter.
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Level {
+    /// Root mean square, from 0 to 1.
+    pub rms: f32,
+    pub peak: f32,
+    pub elapsed_ms: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordingStatus {
+    pub available: bool,
+    /// The note being recorded into, if a recording is running.
+    pub note_id: Option<String>,
+    pub started_at: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Recording {
+    pub note_id: String,
+    pub attachment: Attachment,
+    pub duration_ms: u64,
+}
+
+/// The one recording that can run at a time, across windows.
+#[derive(Default)]
+pub struct Recorder {
+    active: Mutex<Option<Active>>,
+}
+
+struct Active {
+    vault_id: String,
+    note_id: String,
+    started_at: i64,
+    capture: capture::Capture,
+}
+
+impl Recorder {
+    pub fn status(&self) -> RecordingStatus {
+        let active = self.active.lock().expect("recorder poisoned");
+        RecordingStatus {
+            available: AVAILABLE,
+            note_id: active.as_ref().map(|active| active.note_id.clone()),
+            started_at: active.as_ref().map(|active| active.started_at),
+        }
+    }
+
+    /// Starts recording from the default microphone into note `note_id` of
+    /// vault `vault_id`.
+    pub fn start(&self, app: &AppHandle, vault_id: &str, note_id: &str) -> Result<()> {
+        let mut active = self.active.lock().expect("recorder poisoned");
+        if active.is_some() {
+            return Err(Error::Recorder("already recording".into()));
+        }
+        let vault = app.state::<Vaults>().get(app, vault_id)?;
+        if protected::is_protected(&vault.storage.get_note(note_id)?.body) {
+            return Err(Error::NoteLocked(
+                "voice memos cannot go into protected notes".into(),
+            ));
+        }
+        let handle = app.clone();
+        let capture = capture::start(move |level| {
+            let _ = handle.emit(LEVEL_EVENT, level);
+        })?;
+        *active = Some(Active {
+            vault_id: vault_id.to_owned(),
+            note_id: note_id.to_owned(),
+            started_at: now_millis(),
+            capture,
+        });
+        Ok(())
+    }
+
+    /// Stops the running recording, saves it as an attachment and embeds it
+    /// at the end of its note. Blocks until the last audio is encoded.
+    pub fn stop(&self, app: &AppHandle) -> Result<Recording> {
+        let active = self
+            .active
+            .lock()
+            .expect("recorder poisoned")
+            .take()
+            .ok_or_else(|| Error::Recorder("not recording".into()))?;
+        let encoded = active.capture.stop()?;
+        let vault = app.state::<Vaults>().get(app, &active.vault_id)?;
+        let attachment =
+            attachments::import(&vault, &active.note_id, "recording.ogg", &encoded.ogg)?;
+        let note = vault.storage.get_note(&active.note_id)?;
+        // Protected while it was recording; its body is no longer text.
+        if protected::is_protected(&note.body) {
+            return Err(Error::NoteLocked(format!(
+                "the note was protected while recording; the memo is at {} but not embedded",
+                attachment.link
+            )));
+        }
+        let label = format!("Voice memo {}", Local::now().format("%Y-%m-%d %H:%M"));
+        let body = format!(
+            "{}\n\n![{label}](<{}>)\n",
+            note.body.trim_end(),
+            attachment.link
+        );
+        vault.update_note(
+            &note.id,
+            NotePatch {
+                body: Some(body),
+                ..Default::default()
+            },
+        )?;
+        Ok(Recording {
+            note_id: note.id,
+            attachment,
+            duration_ms: encoded.duration_ms,
+        })
+    }
+}
+
+/// A finished recording as Ogg Opus.
+struct Encoded {
+    ogg: Vec<u8>,
+    duration_ms: u64,
+}
@@ -0,0 +1,22 @@
+//! Builds without the `recorder` feature cannot record.
+
+use super::{Encoded, Level};
+use crate::error::{Error, Result};
+
+pub const AVAILABLE: bool = false;
+
+pub struct Capture;
+
+impl Capture {
+    pub fn stop(self) -> Result<Encoded> {
+        Err(unavailable())
+    }
+}
+
+pub fn start(_on_level: impl Fn(Level) + Send + 'static) -> Result<Capture> {
+    Err(unavailable())
+}
+
+fn unavailable() -> crate::error::Error {
+    Error::Recorder("this build does not include audio recording".into())
+}
@@ -0,0 +1,50 @@
+use super::Reminder;
+use crate::error::{Error, Result};
+use crate::vaults::Current;
+
+/// Schedules a reminder at `dueAt` (milliseconds since the epoch) on a note,
+/// or on the task `task` within it.
+#[tauri::command]
+pub async fn set_reminder(
+    vault: Current,
+    note_id: String,
+    due_at: i64,
+    message: Option<String>,
+    task: Option<String>,
+) -> Result<Reminder> {
+    super::set(
+        &vault.storage,
+        &note_id,
+        task.as_deref(),
+        message.as_deref().unwrap_or_default(),
+        due_at,
+    )
+}
+
+/// Pending reminders, soonest first. `includeFired` adds those already
+/// shown.
+#[tauri::command]
+pub async fn list_reminders(
+    vault: Current,
+    note_id: Option<String>,
+    include_fired: Option<bool>,
+) -> Result<Vec<Reminder>> {
+    super::list(
+        &vault.storage,
+        note_id.as_deref(),
+        include_fired.unwrap_or(false),
+    )
+}
+
+#[tauri::command]
+pub async fn snooze_reminder(vault: Current, id: String, minutes: u32) -> Result<Reminder> {
+    if minutes == 0 {
+        return Err(Error::InvalidInput("snooze needs at least a minute".into()));
+    }
+    super::snooze(&vault.storage, &id, minutes)
+}
+
+#[tauri::command]
+pub async fn cancel_reminder(vault: Current, id: String) -> Result<()> {
+    super::cancel(&vault.storage, &id)
+}
@@ -0,0 +1,250 @@
+pub mod commands;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Local};
+use rusqlite::{params, OptionalExtension, Row};
+use serde::Serialize;
+use tauri::{AppHandle, Emitter};
+use tauri_plugin_notification::NotificationExt;
+
+use crate::error::{Error, Result};
+use crate::storage::{now_millis, Storage};
+use crate::vault::Vault;
+
+/// Event carrying each [`Reminder`] as it fires, so an open window can
+/// show it in-app as well.
+pub const DUE_EVENT: &str = "reminder-due";
+/// Event carrying the message of a reminder that could not be shown.
+pub const ERROR_EVENT: &str = "reminder-error";
+
+/// How often the ticker looks for due reminders.
+const TICK: Duration = Duration::from_secs(15);
+/// Reminders firing later than this are marked as missed in the
+/// notification.
+const LATE_MS: i64 = 60 * 1000;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Reminder {
+    pub id: String,
+    pub note_id: String,
+    /// Text of the task within the note the reminder is for, if any.
+    pub task: Option<String>,
+    pub message: String,
+    pub due_at: i64,
+    /// When the notification was shown; pending reminders have none.
+    pub fired_at: Option<i64>,
+}
+
+const COLUMNS: &str = "id, note_id, task, message, due_at, fired_at";
+
+/// A row as stored, its message and task still sealed with a vault
+/// password; [`open`] them before handing it out.
+fn reminder_from_row(row: &Row) -> rusqlite::Result<Reminder> {
+    Ok(Reminder {
+        id: row.get(0)?,
+        note_id: row.get(1)?,
+        task: row.get(2)?,
+        message: row.get(3)?,
+        due_at: row.get(4)?,
+        fired_at: row.get(5)?,
+    })
+}
+
+fn open(storage: &Storage, mut reminder: Reminder) -> Result<Reminder> {
+    reminder.message = storage.open_body(reminder.message)?;
+    reminder.task = reminder
+        .task
+        .map(|task| storage.open_body(task))
+        .transpose()?;
+    Ok(reminder)
+}
+
+/// Schedules a reminder on a note, or on one of its tasks. With a vault
+/// password the message and task are sealed like bodies.
+pub fn set(
+    storage: &Storage,
+    note_id: &str,
+    task: Option<&str>,
+    message: &str,
+    due_at: i64,
+) -> Result<Reminder> {
+    if !storage.has_note(note_id)? {
+        return Err(Error::NoteNotFound(note_id.to_owned()));
+    }
+    let reminder = Reminder {
+        id: uuid::Uuid::new_v4().to_string(),
+        note_id: note_id.to_owned(),
+        task: task.map(str::to_owned),
+        message: message.to_owned(),
+        due_at,
+        fired_at: None,
+    };
+    storage.conn().execute(
+        "INSERT INTO reminders (id, note_id, task, message, due_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![
+            reminder.id,
+            reminder.note_id,
+            reminder
+                .task
+                .as_deref()
+                .map(|task| storage.seal_body(task))
+                .transpose()?,
+            storage.seal_body(&reminder.message)?,
+            reminder.due_at
+        ],
+    )?;
+    Ok(reminder)
+}
+
+pub fn get(storage: &Storage, id: &str) -> Result<Reminder> {
+    let reminder = storage
+        .conn()
+        .query_row(
+            &format!("SELECT {COLUMNS} FROM reminders WHERE id = ?1"),
+            [id],
+            reminder_from_row,
+        )
+        .optional()?
+        .ok_or_else(|| Error::InvalidInput(format!("no reminder {id}")))?;
+    open(storage, reminder)
+}
+
+/// Reminders soonest first, optionally only those of one note. Fired ones
+/// are included only with `include_fired`.
+pub fn list(
+    storage: &Storage,
+    note_id: Option<&str>,
+    include_fired: bool,
+) -> Result<Vec<Reminder>> {
+    let conn = storage.conn();
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {COLUMNS} FROM reminders
+         WHERE (?1 IS NULL OR note_id = ?1) AND (?2 OR fired_at IS NULL)
+         ORDER BY due_at"
+    ))?;
+    let reminders = stmt
+        .query_map(params![note_id, include_fired], reminder_from_row)?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    reminders
+        .into_iter()
+        .map(|reminder| open(storage, reminder))
+        .collect()
+}
+
+/// Pushes a reminder back to fire again `minutes` from now.
+pub fn snooze(storage: &Storage, id: &str, minutes: u32) -> Result<Reminder> {
+    let due_at = now_millis() + i64::from(minutes) * 60 * 1000;
+    let updated = storage.conn().execute(
+        "UPDATE reminders SET due_at = ?2, fired_at = NULL WHERE id = ?1",
+        params![id, due_at],
+    )?;
+    if updated == 0 {
+        return Err(Error::InvalidInput(format!("no reminder {id}")));
+    }
+    get(storage, id)
+}
+
+pub fn cancel(storage: &Storage, id: &str) -> Result<()> {
+    storage
+        .conn()
+        .execute("DELETE FROM reminders WHERE id = ?1", [id])?;
+    Ok(())
+}
+
+/// Pending reminders due by `now`, leaving out those of trashed notes.
+/// While the vault is locked their message and task are left empty.
+fn due(storage: &Storage, now: i64) -> Result<Vec<(Reminder, String)>> {
+    let conn = storage.conn();
+    let mut stmt = conn.prepare(
+        "SELECT r.id, r.note_id, r.task, r.message, r.due_at, r.fired_at, n.title
+         FROM reminders r JOIN notes n ON n.id = r.note_id
+         WHERE r.fired_at IS NULL AND r.due_at <= ?1
+           AND r.note_id NOT IN (SELECT note_id FROM trash)
+         ORDER BY r.due_at",
+    )?;
+    let due = stmt
+        .query_map([now], |row| Ok((reminder_from_row(row)?, row.get(6)?)))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    due.into_iter()
+        .map(|(reminder, title)| {
+            let reminder = match storage.is_locked() {
+                true => Reminder {
+                    task: None,
+                    message: String::new(),
+                    ..reminder
+                },
+                false => open(storage, reminder)?,
+            };
+            Ok((reminder, title))
+        })
+        .collect()
+}
+
+fn mark_fired(storage: &Storage, id: &str, at: i64) -> Result<()> {
+    storage.conn().execute(
+        "UPDATE reminders SET fired_at = ?2 WHERE id = ?1",
+        params![id, at],
+    )?;
+    Ok(())
+}
+
+/// Shows a notification for every reminder that is due. Anything that came
+/// due while the app was closed fires on the first tick after startup.
+/// While the vault is locked the notification only names the note.
+pub fn fire_due(app: &AppHandle, vault: &Vault) -> Result<usize> {
+    let now = now_millis();
+    let due = due(&vault.storage, now)?;
+    let count = due.len();
+    for (mut reminder, title) in due {
+        let mut body = match (&reminder.task, reminder.message.is_empty()) {
+            (Some(task), true) => task.clone(),
+            (_, true) => "Reminder".to_owned(),
+            (_, false) => reminder.message.clone(),
+        };
+        if now - reminder.due_at > LATE_MS {
+            body.push_str(&format!(
+                " (missed, was due {})",
+                local_time(reminder.due_at)
+            ));
+        }
+        // Marked first so a notification that keeps failing is not retried
+        // every tick; the event still reaches an open window.
+        mark_fired(&vault.storage, &reminder.id, now)?;
+        reminder.fired_at = Some(now);
+        let _ = app.emit(DUE_EVENT, reminder);
+        if let Err(err) = app.notification().builder().title(&title).body(body).show() {
+            let _ = app.emit(ERROR_EVENT, err.to_string());
+        }
+    }
+    Ok(count)
+}
+
+/// Fires due reminders at startup and then every [`TICK`]. Failures are
+/// reported through [`ERROR_EVENT`].
+pub fn spawn_periodic(app: AppHandle, vault: Arc<Vault>) {
+    tauri::async_runtime::spawn(async move {
+        while !vault.is_closed() {
+            let (handle, vault) = (app.clone(), Arc::clone(&vault));
+            let result = tauri::async_runtime::spawn_blocking(move || fire_due(&handle, &vault))
+                .await
+                .unwrap_or_else(|err| Err(err.into()));
+            if let Err(err) = result {
+                let _ = app.emit(ERROR_EVENT, err.to_string());
+            }
+            tokio::time::sleep(TICK).await;
+        }
+    });
+}
+
+fn local_time(millis: i64) -> String {
+    DateTime::from_timestamp_millis(millis)
+        .map(|time| {
+            time.with_timezone(&Local)
+                .format("%Y-%m-%d %H:%M")
+                .to_string()
+        })
+        .unwrap_or_default()
+}
@@ -0,0 +1,48 @@
+use tauri::State;
+
+use super::highlight::{self, Styling};
+use super::Rendered;
+use crate::error::{Error, Result};
+use crate::export::escape;
+use crate::protected;
+use crate::settings::SettingsStore;
+use crate::vaults::Current;
+
+/// Renders note `noteId`, or `text` as the editor has it, to HTML for the
+/// preview. `text` wins when both are given, so unsaved edits show.
+#[tauri::command]
+pub async fn render_markdown(
+    vault: Current,
+    note_id: Option<String>,
+    text: Option<String>,
+) -> Result<Rendered> {
+    let body = match (note_id, text) {
+        (_, Some(text)) => text,
+        (Some(id), None) => protected::read(&vault, &id)?.body,
+        (None, None) => {
+            return Err(Error::InvalidInput(
+                "give a note or the text to render".into(),
+            ))
+        }
+    };
+    Ok(tauri::async_runtime::spawn_blocking(move || super::render(&vault, &body)).await?)
+}
+
+/// `code` coloured as `language`, with the same classes as code blocks in
+/// [`render_markdown`]. An unknown language comes back uncoloured.
+#[tauri::command]
+pub async fn highlight_code(language: String, code: String) -> String {
+    tauri::async_runtime::spawn_blocking(move || {
+        highlight::highlight(&language, &code, Styling::Classes)
+            .unwrap_or_else(|| format!("<pre><code>{}</code></pre>\n", escape(&code)))
+    })
+    .await
+    .unwrap_or_default()
+}
+
+/// CSS colouring highlighted code for the theme in the settings. Fetch it
+/// again when they change.
+#[tauri::command]
+pub async fn highlight_stylesheet(store: State<'_, SettingsStore>) -> Result<String> {
+    Ok(highlight::stylesheet(store.get().appearance.theme))
+}
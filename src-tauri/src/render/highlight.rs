@@ -0,0 +1,136 @@
+//! Syntax colouring for fenced code blocks. Exports get inline styles, so
+//! printed pages and e-book readers need no stylesheet for it; the preview
+//! gets classes, styled by [`stylesheet`] for the app's theme.
+
+use std::sync::OnceLock;
+
+use pulldown_cmark::{CodeBlockKind, Event, Tag, TagEnd};
+use syntect::easy::HighlightLines;
+use syntect::highlighting::{Theme as Colours, ThemeSet};
+use syntect::html::{
+    css_for_theme_with_class_style, styled_line_to_highlighted_html, ClassStyle,
+    ClassedHTMLGenerator, IncludeBackground,
+};
+use syntect::parsing::SyntaxSet;
+use syntect::util::LinesWithEndings;
+
+use crate::export::escape;
+use crate::settings::Theme;
+
+/// A light theme, close to the colours of the export stylesheet.
+const LIGHT: &str = "InspiredGitHub";
+const DARK: &str = "base16-ocean.dark";
+const CLASSES: ClassStyle = ClassStyle::SpacedPrefixed { prefix: "hl-" };
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Styling {
+    /// Colours inline, in the light theme.
+    Inline,
+    /// `hl-` classes, for [`stylesheet`].
+    Classes,
+}
+
+struct Highlighter {
+    syntaxes: SyntaxSet,
+    light: Colours,
+    dark: Colours,
+}
+
+fn highlighter() -> &'static Highlighter {
+    static HIGHLIGHTER: OnceLock<Highlighter> = OnceLock::new();
+    HIGHLIGHTER.get_or_init(|| {
+        let mut themes = ThemeSet::load_defaults().themes;
+        Highlighter {
+            syntaxes: SyntaxSet::load_defaults_newlines(),
+            light: themes.remove(LIGHT).unwrap_or_default(),
+            dark: themes.remove(DARK).unwrap_or_default(),
+        }
+    })
+}
+
+/// Replaces each fenced code block in a language syntect knows with the
+/// coloured HTML of the whole block. Other blocks pass through as they are.
+pub fn code_blocks(events: Vec<Event<'_>>, styling: Styling) -> Vec<Event<'_>> {
+    let mut out = Vec::with_capacity(events.len());
+    let mut events = events.into_iter();
+    while let Some(event) = events.next() {
+        let Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(info))) = &event else {
+            out.push(event);
+            continue;
+        };
+        let language = info
+            .split_whitespace()
+            .next()
+            .unwrap_or_default()
+            .to_owned();
+        let mut block = vec![event];
+        let mut code = String::new();
+        for event in events.by_ref() {
+            if let Event::Text(text) = &event {
+                code.push_str(text);
+            }
+            let end = matches!(event, Event::End(TagEnd::CodeBlock));
+            block.push(event);
+            if end {
+                break;
+            }
+        }
+        match highlight(&language, &code, styling) {
+            Some(html) => out.push(Event::Html(html.into())),
+            None => out.extend(block),
+        }
+    }
+    out
+}
+
+/// `code` as a `<pre>` block coloured for `language`, or `None` if syntect
+/// does not know it.
+pub fn highlight(language: &str, code: &str, styling: Styling) -> Option<String> {
+    let Highlighter {
+        syntaxes, light, ..
+    } = highlighter();
+    let syntax = syntaxes.find_syntax_by_token(language)?;
+    let mut html = format!(
+        "<pre class=\"highlight\"><code class=\"language-{}\">",
+        escape(language)
+    );
+    match styling {
+        Styling::Inline => {
+            let mut lines = HighlightLines::new(syntax, light);
+            for line in LinesWithEndings::from(code) {
+                let ranges = lines.highlight_line(line, syntaxes).ok()?;
+                html.push_str(
+                    &styled_line_to_highlighted_html(&ranges, IncludeBackground::No).ok()?,
+                );
+            }
+        }
+        Styling::Classes => {
+            let mut generator =
+                ClassedHTMLGenerator::new_with_class_style(syntax, syntaxes, CLASSES);
+            for line in LinesWithEndings::from(code) {
+                generator
+                    .parse_html_for_line_which_includes_newline(line)
+                    .ok()?;
+            }
+            html.push_str(&generator.finalize());
+        }
+    }
+    html.push_str("</code></pre>\n");
+    Some(html)
+}
+
+/// CSS for [`Styling::Classes`] in `theme`. The system theme gets both,
+/// the dark one under `prefers-color-scheme`.
+pub fn stylesheet(theme: Theme) -> String {
+    let Highlighter { light, dark, .. } = highlighter();
+    let css = |colours| css_for_theme_with_class_style(colours, CLASSES).unwrap_or_default();
+    match theme {
+        Theme::Light => css(light),
+        Theme::Dark => css(dark),
+        Theme::System => format!(
+            "{}\n@media (prefers-color-scheme: dark) {{\n{}}}\n",
+            css(light),
+            css(dark)
+        ),
+    }
+}
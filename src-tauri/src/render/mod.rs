@@ -0,0 +1,255 @@
+//! Markdown to HTML for the editor's preview, rendered here rather than in
+//! the webview. Top-level blocks are cached by their source, so after a
+//! small edit to a long note only the blocks that changed are rendered
+//! again. The whole page is then [`sanitize`]d.
+
+pub mod commands;
+pub mod highlight;
+pub mod sanitize;
+
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::ops::Range;
+use std::sync::{Mutex, MutexGuard};
+
+use pulldown_cmark::{html, CodeBlockKind, Event, LinkType, Options, Parser, Tag, TagEnd};
+use serde::Serialize;
+
+use self::highlight::Styling;
+use crate::diagrams::DiagramKind;
+use crate::export;
+use crate::links;
+use crate::metadata::FrontMatter;
+use crate::vault::Vault;
+
+/// Blocks kept; past this the less recently used half is dropped.
+const MAX_BLOCKS: usize = 20_000;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Rendered {
+    pub html: String,
+    pub blocks: usize,
+    /// Blocks taken from the cache rather than rendered.
+    pub reused: usize,
+}
+
+/// HTML of the blocks rendered lately, by [`key`].
+#[derive(Default)]
+pub struct RenderCache(Mutex<Blocks>);
+
+#[derive(Default)]
+struct Blocks {
+    /// HTML with the render it was last used in.
+    html: HashMap<u64, (String, u64)>,
+    renders: u64,
+}
+
+impl RenderCache {
+    pub fn clear(&self) {
+        self.lock().html.clear();
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Blocks> {
+        self.0.lock().expect("render cache poisoned")
+    }
+}
+
+/// Tables, footnotes, task lists, strikethrough, `[[wiki links]]` and
+/// GitHub's `> [!NOTE]` callouts.
+pub(crate) fn options() -> Options {
+    Options::ENABLE_TABLES
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_TASKLISTS
+        | Options::ENABLE_FOOTNOTES
+        | Options::ENABLE_GFM
+        | Options::ENABLE_WIKILINKS
+}
+
+/// Renders a note body, without its front matter. Wiki links that name a
+/// note become `note://` links to it; the others keep the name as written,
+/// for the editor to offer creating the note.
+pub fn render(vault: &Vault, body: &str) -> Rendered {
+    let (_, markdown) = FrontMatter::split(body);
+    let (mut events, ranges): (Vec<Event>, Vec<Range<usize>>) =
+        Parser::new_ext(markdown, options())
+            .into_offset_iter()
+            .map(|(event, range)| (wiki_link(vault, event), range))
+            .unzip();
+    export::heading_ids(&mut events);
+
+    let spans = blocks(&events);
+    let renders = {
+        let mut cache = vault.rendered.lock();
+        cache.renders += 1;
+        cache.renders
+    };
+    let mut rendered = Rendered {
+        html: String::new(),
+        blocks: spans.len(),
+        reused: 0,
+    };
+    let mut stream = Vec::with_capacity(spans.len());
+    let mut events = events.into_iter();
+    for span in spans {
+        let block = diagrams(events.by_ref().take(span.len()).collect());
+        // Footnotes are numbered across the whole note, so they are left to
+        // the final pass.
+        if block.iter().any(|e| {
+            matches!(
+                e,
+                Event::FootnoteReference(_) | Event::Start(Tag::FootnoteDefinition(_))
+            )
+        }) {
+            stream.extend(highlight::code_blocks(block, Styling::Classes));
+            continue;
+        }
+        let key = key(&markdown[ranges[span.start].clone()], &block);
+        let cached = vault
+            .rendered
+            .lock()
+            .html
+            .get_mut(&key)
+            .map(|(html, used)| {
+                *used = renders;
+                html.clone()
+            });
+        let html = match cached {
+            Some(html) => {
+                rendered.reused += 1;
+                html
+            }
+            None => {
+                let mut html = String::new();
+                html::push_html(
+                    &mut html,
+                    highlight::code_blocks(block, Styling::Classes).into_iter(),
+                );
+                vault
+                    .rendered
+                    .lock()
+                    .html
+                    .insert(key, (html.clone(), renders));
+                html
+            }
+        };
+        stream.push(Event::Html(html.into()));
+    }
+    let mut html = String::new();
+    html::push_html(&mut html, stream.into_iter());
+    rendered.html = sanitize::html(&html);
+    evict(&mut vault.rendered.lock());
+    rendered
+}
+
+fn wiki_link<'a>(vault: &Vault, event: Event<'a>) -> Event<'a> {
+    let Event::Start(Tag::Link {
+        link_type: link_type @ LinkType::WikiLink { .. },
+        dest_url,
+        title,
+        id,
+    }) = event
+    else {
+        return event;
+    };
+    let (target, heading) = match dest_url.split_once('#') {
+        Some((target, heading)) => (target, Some(heading)),
+        None => (&*dest_url, None),
+    };
+    let resolved = vault
+        .links
+        .resolve(target.trim())
+        .map(|note| links::note_url(&note, heading));
+    Event::Start(Tag::Link {
+        link_type,
+        dest_url: resolved.map(Into::into).unwrap_or(dest_url),
+        title,
+        id,
+    })
+}
+
+/// Turns Mermaid, PlantUML and Graphviz code blocks into
+/// `<pre class="diagram">` placeholders with their source, for the editor
+/// to swap for what `render_diagram` makes of it.
+fn diagrams(events: Vec<Event<'_>>) -> Vec<Event<'_>> {
+    let mut out = Vec::with_capacity(events.len());
+    let mut events = events.into_iter();
+    while let Some(event) = events.next() {
+        let kind = match &event {
+            Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(info))) => info
+                .split_whitespace()
+                .next()
+                .and_then(DiagramKind::from_language),
+            _ => None,
+        };
+        let Some(kind) = kind else {
+            out.push(event);
+            continue;
+        };
+        let mut source = String::new();
+        for event in events.by_ref() {
+            match event {
+                Event::Text(text) => source.push_str(&text),
+                Event::End(TagEnd::CodeBlock) => break,
+                _ => {}
+            }
+        }
+        out.push(Event::Html(
+            format!(
+                "<pre class=\"diagram\" data-diagram=\"{}\"><code>{}</code></pre>\n",
+                kind.name(),
+                export::escape(&source)
+            )
+            .into(),
+        ));
+    }
+    out
+}
+
+/// The event ranges of the top-level blocks.
+fn blocks(events: &[Event]) -> Vec<Range<usize>> {
+    let mut spans = Vec::new();
+    let (mut depth, mut start) = (0usize, 0);
+    for (i, event) in events.iter().enumerate() {
+        match event {
+            Event::Start(_) => depth += 1,
+            Event::End(_) => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+        if depth == 0 {
+            spans.push(start..i + 1);
+            start = i + 1;
+        }
+    }
+    if start < events.len() {
+        spans.push(start..events.len());
+    }
+    spans
+}
+
+/// Identifies a block by its source and what it takes from the rest of the
+/// note and vault: resolved link targets, reference links included, and
+/// heading ids, which depend on the headings before.
+fn key(source: &str, block: &[Event]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    source.hash(&mut hasher);
+    for event in block {
+        match event {
+            Event::Start(Tag::Link { dest_url, .. })
+            | Event::Start(Tag::Image { dest_url, .. }) => dest_url.hash(&mut hasher),
+            Event::Start(Tag::Heading { id: Some(id), .. }) => id.hash(&mut hasher),
+            _ => {}
+        }
+    }
+    hasher.finish()
+}
+
+fn evict(blocks: &mut Blocks) {
+    if blocks.html.len() <= MAX_BLOCKS {
+        return;
+    }
+    let mut used: Vec<u64> = blocks.html.values().map(|(_, used)| *used).collect();
+    used.sort_unstable();
+    let cutoff = used[used.len() / 2];
+    blocks.html.retain(|_, (_, used)| *used > cutoff);
+}
@@ -0,0 +1,39 @@
+//! What the preview is allowed to show. Notes may hold raw HTML, and
+//! pages clipped or synced from elsewhere can carry anything, so the
+//! rendered HTML is cleaned before it reaches the webview: no scripts,
+//! event handlers, frames or forms, and links only to schemes that open
+//! something harmless.
+
+use std::collections::HashSet;
+use std::sync::LazyLock;
+
+use ammonia::Builder;
+
+/// Schemes links and images may use; relative URLs, which attachments and
+/// unresolved wiki links are, pass as they are.
+const SCHEMES: &[&str] = &["http", "https", "mailto", "tel", "note", "data"];
+
+static PREVIEW: LazyLock<Builder<'static>> = LazyLock::new(|| {
+    let mut builder = Builder::default();
+    builder
+        .url_schemes(SCHEMES.iter().copied().collect::<HashSet<_>>())
+        .add_tags(["input"])
+        .add_tag_attributes("input", ["type", "checked", "disabled"])
+        .add_tag_attributes("th", ["style"])
+        .add_tag_attributes("td", ["style"])
+        .add_generic_attributes(["class", "id"])
+        .generic_attribute_prefixes(["data-"].into_iter().collect::<HashSet<_>>())
+        .attribute_filter(|element, attribute, value| match (element, attribute) {
+            // Task list checkboxes only; anything else could post a form.
+            ("input", "type") if value != "checkbox" => None,
+            // `data:` is for inline images, not for pages to navigate to.
+            ("a", "href") if value.trim_start().to_ascii_lowercase().starts_with("data:") => None,
+            _ => Some(value.into()),
+        });
+    builder
+});
+
+/// `html` with everything the preview should not run taken out.
+pub fn html(html: &str) -> String {
+    PREVIEW.clean(html).to_string()
+}
@@ -0,0 +1,151 @@
+use tauri::State;
+
+use super::replace::{NoteReplacement, ReplaceOptions, ReplaceOutcome, Replacer};
+use super::saved::{SavedSearch, SavedSearchHit, SearchFilter};
+use super::semantic::{SemanticHit, SemanticStatus};
+use super::SearchHit;
+use crate::error::Result;
+use crate::jobs::{JobKind, Jobs, Priority};
+use crate::protected;
+use crate::vaults::Current;
+
+const DEFAULT_LIMIT: usize = 50;
+const DEFAULT_RELATED: usize = 10;
+
+#[tauri::command]
+pub async fn search_notes(
+    vault: Current,
+    query: String,
+    limit: Option<usize>,
+    include_archived: Option<bool>,
+) -> Result<Vec<SearchHit>> {
+    vault.search_notes(
+        &query,
+        limit.unwrap_or(DEFAULT_LIMIT),
+        include_archived.unwrap_or(false),
+    )
+}
+
+/// Rebuilds the search index from storage, returning the number of notes indexed.
+#[tauri::command]
+pub async fn reindex_all(vault: Current, jobs: State<'_, Jobs>) -> Result<usize> {
+    jobs.run(JobKind::Reindex, Priority::User, move |_| {
+        vault.reindex_all()
+    })
+    .await
+}
+
+/// Notes closest in meaning to `query`, using the local embedding model.
+#[tauri::command]
+pub async fn semantic_search(
+    vault: Current,
+    query: String,
+    k: Option<usize>,
+) -> Result<Vec<SemanticHit>> {
+    tauri::async_runtime::spawn_blocking(move || {
+        vault
+            .semantic
+            .search(&vault.storage, &query, k.unwrap_or(DEFAULT_RELATED))
+    })
+    .await?
+}
+
+/// Notes similar to `noteId`, for the "similar notes" panel.
+#[tauri::command]
+pub async fn related_notes(
+    vault: Current,
+    note_id: String,
+    k: Option<usize>,
+) -> Result<Vec<SemanticHit>> {
+    vault
+        .semantic
+        .related(&vault.storage, &note_id, k.unwrap_or(DEFAULT_RELATED))
+}
+
+/// Whether semantic search can run here and how far indexing has got.
+#[tauri::command]
+pub async fn semantic_status(vault: Current) -> Result<SemanticStatus> {
+    vault.semantic.status(&vault.storage)
+}
+
+#[tauri::command]
+pub async fn list_saved_searches(vault: Current) -> Result<Vec<SavedSearch>> {
+    super::saved::list(&vault.storage)
+}
+
+/// Saves `filter` under `name`, replacing the saved search `id` if given.
+#[tauri::command]
+pub async fn save_search(
+    vault: Current,
+    id: Option<String>,
+    name: String,
+    filter: SearchFilter,
+) -> Result<SavedSearch> {
+    let search = SavedSearch {
+        id: id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string()),
+        name: name.trim().to_owned(),
+        filter,
+    };
+    super::saved::save(&vault.storage, search)
+}
+
+#[tauri::command]
+pub async fn delete_saved_search(vault: Current, id: String) -> Result<()> {
+    super::saved::delete(&vault.storage, &id)
+}
+
+/// The notes saved search `id` matches as of now.
+#[tauri::command]
+pub async fn run_saved_search(
+    vault: Current,
+    id: String,
+    limit: Option<usize>,
+) -> Result<Vec<SavedSearchHit>> {
+    let search = super::saved::get(&vault.storage, &id)?;
+    super::saved::run(&vault, &search.filter, limit.unwrap_or(usize::MAX))
+}
+
+/// Finds `query` across the vault. As a preview, lists every match with
+/// its line and the text it would become; with `options.apply`, rewrites
+/// the notes and reports what changed in each.
+#[tauri::command]
+pub async fn search_replace(
+    vault: Current,
+    query: String,
+    replacement: String,
+    options: Option<ReplaceOptions>,
+) -> Result<ReplaceOutcome> {
+    let options = options.unwrap_or_default();
+    let replacer = Replacer::new(&query, &replacement, &options)?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let note_ids = options.note_ids.as_deref();
+        if options.apply {
+            return Ok(ReplaceOutcome {
+                notes: vault.replace_text(&replacer, note_ids)?,
+                applied: true,
+                ..Default::default()
+            });
+        }
+        let mut outcome = ReplaceOutcome::default();
+        let mut total = 0;
+        for note in vault.storage.all_notes()? {
+            if note_ids.is_some_and(|ids| !ids.contains(&note.id))
+                || protected::is_protected(&note.body)
+            {
+                continue;
+            }
+            let replacements = replacer.find(&note, &mut outcome.matches);
+            if replacements > 0 {
+                total += replacements;
+                outcome.notes.push(NoteReplacement {
+                    note_id: note.id,
+                    note_title: note.title,
+                    replacements,
+                });
+            }
+        }
+        outcome.truncated = total > outcome.matches.len();
+        Ok(outcome)
+    })
+    .await?
+}
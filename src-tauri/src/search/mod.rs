@@ -0,0 +1,286 @@
+pub mod commands;
+pub mod replace;
+pub mod saved;
+pub mod semantic;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard, RwLock, RwLockReadGuard};
+
+use serde::Serialize;
+use tantivy::collector::TopDocs;
+use tantivy::directory::MmapDirectory;
+use tantivy::query::QueryParser;
+use tantivy::schema::{Field, Schema, Value, STORED, STRING, TEXT};
+use tantivy::snippet::{Snippet, SnippetGenerator};
+use tantivy::{doc, Index, IndexReader, IndexWriter, ReloadPolicy, TantivyDocument, Term};
+
+use crate::error::Result;
+use crate::metadata::FrontMatter;
+use crate::storage::Note;
+
+const WRITER_HEAP_BYTES: usize = 50_000_000;
+/// Texts are searched for a snippet this much at a time, stopping at the
+/// first stretch with a hit, so a huge log is not tokenized whole for the
+/// sake of a line of it.
+const SNIPPET_WINDOW_BYTES: usize = 64 * 1024;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchHit {
+    pub id: String,
+    pub title: String,
+    pub score: f32,
+    /// HTML fragment of the best matching passage, with `<b>` around the hits.
+    pub snippet: String,
+}
+
+#[derive(Clone, Copy)]
+struct Fields {
+    id: Field,
+    title: Field,
+    body: Field,
+    attachments: Field,
+}
+
+/// Tantivy full-text index over note titles and bodies, plus the text read
+/// out of their attachments.
+///
+/// Only ids and titles are stored; bodies are indexed but read back from
+/// storage when building snippets. Encrypted vaults keep the index in memory
+/// only, since the inverted index would otherwise leak body text to disk.
+pub struct SearchIndex {
+    dir: PathBuf,
+    inner: RwLock<Inner>,
+    fields: Fields,
+}
+
+struct Inner {
+    index: Index,
+    reader: IndexReader,
+    writer: Mutex<IndexWriter>,
+}
+
+impl SearchIndex {
+    /// Opens the index in `dir`, or an in-memory index when `persistent` is false.
+    pub fn open(dir: &Path, persistent: bool) -> Result<Self> {
+        let mut builder = Schema::builder();
+        let fields = Fields {
+            id: builder.add_text_field("id", STRING | STORED),
+            title: builder.add_text_field("title", TEXT | STORED),
+            body: builder.add_text_field("body", TEXT),
+            attachments: builder.add_text_field("attachments", TEXT),
+        };
+        let schema = builder.build();
+        let inner = Inner::open(dir, persistent, schema)?;
+        Ok(Self {
+            dir: dir.to_owned(),
+            inner: RwLock::new(inner),
+            fields,
+        })
+    }
+
+    /// Moves the index between disk and memory. The new index starts empty
+    /// and must be rebuilt; switching to memory deletes the on-disk copy.
+    pub fn set_persistent(&self, persistent: bool) -> Result<()> {
+        let mut inner = self.inner.write().expect("search index poisoned");
+        let schema = inner.index.schema();
+        *inner = Inner::open(&self.dir, persistent, schema)?;
+        if !persistent && self.dir.exists() {
+            fs::remove_dir_all(&self.dir)?;
+        }
+        Ok(())
+    }
+
+    pub fn clear(&self) -> Result<()> {
+        self.rebuild(&[], |_| String::new()).map(drop)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// How many notes are indexed.
+    pub fn len(&self) -> usize {
+        self.inner().reader.searcher().num_docs() as usize
+    }
+
+    /// Adds or replaces a single note and commits immediately.
+    /// `attachments` is the text of the attachments it uses.
+    pub fn index_note(&self, note: &Note, attachments: &str) -> Result<()> {
+        let inner = self.inner();
+        let mut writer = inner.writer();
+        writer.delete_term(Term::from_field_text(self.fields.id, &note.id));
+        writer.add_document(self.document(note, attachments))?;
+        inner.commit(&mut writer)
+    }
+
+    pub fn remove_note(&self, id: &str) -> Result<()> {
+        let inner = self.inner();
+        let mut writer = inner.writer();
+        writer.delete_term(Term::from_field_text(self.fields.id, id));
+        inner.commit(&mut writer)
+    }
+
+    /// Replaces the whole index with `notes`, returning how many were indexed.
+    pub fn rebuild(
+        &self,
+        notes: &[Note],
+        attachments_of: impl Fn(&Note) -> String,
+    ) -> Result<usize> {
+        let inner = self.inner();
+        let mut writer = inner.writer();
+        writer.delete_all_documents()?;
+        for note in notes {
+            writer.add_document(self.document(note, &attachments_of(note)))?;
+        }
+        inner.commit(&mut writer)?;
+        Ok(notes.len())
+    }
+
+    /// Runs `query` and returns hits, best first. `texts_of` supplies the
+    /// current body and attachment text for a note id so a snippet can be
+    /// built.
+    pub fn search(
+        &self,
+        query: &str,
+        limit: usize,
+        texts_of: impl Fn(&str) -> Option<(String, String)>,
+    ) -> Result<Vec<SearchHit>> {
+        let inner = self.inner();
+        let mut parser = QueryParser::for_index(
+            &inner.index,
+            vec![self.fields.title, self.fields.body, self.fields.attachments],
+        );
+        parser.set_field_boost(self.fields.title, 2.0);
+        parser.set_field_boost(self.fields.attachments, 0.5);
+        // User input is free text; treat syntax mistakes as plain terms rather than errors.
+        let (query, _) = parser.parse_query_lenient(query);
+
+        let searcher = inner.reader.searcher();
+        let snippets = SnippetGenerator::create(&searcher, &*query, self.fields.body)?;
+        let attachment_snippets =
+            SnippetGenerator::create(&searcher, &*query, self.fields.attachments)?;
+        let top = searcher.search(&query, &TopDocs::with_limit(limit).order_by_score())?;
+
+        let mut hits = Vec::with_capacity(top.len());
+        for (score, address) in top {
+            let doc: TantivyDocument = searcher.doc(address)?;
+            let text = |field| {
+                doc.get_first(field)
+                    .and_then(|value| value.as_str())
+                    .unwrap_or_default()
+                    .to_owned()
+            };
+            let id = text(self.fields.id);
+            // Matches only inside an attachment show that passage instead.
+            let snippet = texts_of(&id)
+                .map(|(body, attachments)| {
+                    let snippet = snippet_of(&snippets, &body);
+                    if snippet.is_empty() && !attachments.is_empty() {
+                        snippet_of(&attachment_snippets, &attachments).to_html()
+                    } else {
+                        snippet.to_html()
+                    }
+                })
+                .unwrap_or_default();
+            hits.push(SearchHit {
+                title: text(self.fields.title),
+                id,
+                score,
+                snippet,
+            });
+        }
+        Ok(hits)
+    }
+
+    /// Aliases go in as further titles, after the real one so hits still
+    /// show that.
+    fn document(&self, note: &Note, attachments: &str) -> TantivyDocument {
+        let mut document = doc!(
+            self.fields.id => note.id.as_str(),
+            self.fields.title => note.title.as_str(),
+            self.fields.body => note.body.as_str(),
+            self.fields.attachments => attachments,
+        );
+        if let (Some(front_matter), _) = FrontMatter::split(&note.body) {
+            for alias in front_matter.aliases() {
+                document.add_text(self.fields.title, alias);
+            }
+        }
+        document
+    }
+
+    fn inner(&self) -> RwLockReadGuard<'_, Inner> {
+        self.inner.read().expect("search index poisoned")
+    }
+}
+
+impl Inner {
+    fn open(dir: &Path, persistent: bool, schema: Schema) -> Result<Self> {
+        let index = if persistent {
+            Self::open_dir(dir, schema)?
+        } else {
+            Index::create_in_ram(schema)
+        };
+        let reader = index
+            .reader_builder()
+            .reload_policy(ReloadPolicy::Manual)
+            .try_into()?;
+        let writer = index.writer(WRITER_HEAP_BYTES)?;
+        Ok(Self {
+            index,
+            reader,
+            writer: Mutex::new(writer),
+        })
+    }
+
+    fn open_dir(dir: &Path, schema: Schema) -> Result<Index> {
+        fs::create_dir_all(dir)?;
+        let directory = MmapDirectory::open(dir).map_err(tantivy::TantivyError::from)?;
+        match Index::open_or_create(directory, schema.clone()) {
+            Ok(index) => Ok(index),
+            // The on-disk schema comes from an older build; the index is only a
+            // cache, so start over and let the caller repopulate it.
+            Err(tantivy::TantivyError::SchemaError(_)) => {
+                fs::remove_dir_all(dir)?;
+                fs::create_dir_all(dir)?;
+                Ok(Index::create_in_dir(dir, schema)?)
+            }
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn writer(&self) -> MutexGuard<'_, IndexWriter> {
+        self.writer.lock().expect("search writer poisoned")
+    }
+
+    fn commit(&self, writer: &mut IndexWriter) -> Result<()> {
+        writer.commit()?;
+        self.reader.reload()?;
+        Ok(())
+    }
+}
+
+/// The snippet of the first window of `text` with a hit in it. Windows end
+/// at whitespace where they can, so no word is cut in two.
+fn snippet_of(generator: &SnippetGenerator, text: &str) -> Snippet {
+    let mut rest = text;
+    while !rest.is_empty() {
+        let mut end = rest.len().min(SNIPPET_WINDOW_BYTES);
+        while !rest.is_char_boundary(end) {
+            end -= 1;
+        }
+        if end < rest.len() {
+            if let Some(space) = rest[..end].rfind(char::is_whitespace).filter(|&i| i > 0) {
+                end = space;
+            }
+        }
+        let snippet = generator.snippet(&rest[..end]);
+        if !snippet.is_empty() {
+            return snippet;
+        }
+        rest = &rest[end..];
+    }
+    Snippet::empty()
+}
@@ -0,0 +1,177 @@
+use regex::{Captures, Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+use crate::storage::Note;
+
+/// Most matches a preview returns; the rest are only counted.
+const MAX_MATCHES: usize = 5_000;
+/// Characters of context kept on each side of a match.
+const CONTEXT_CHARS: usize = 60;
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ReplaceOptions {
+    /// Treat the query as a regular expression, whose groups the
+    /// replacement can use as `$1` or `${name}`.
+    pub regex: bool,
+    pub case_sensitive: bool,
+    pub whole_word: bool,
+    /// Rewrite the notes instead of only listing the matches.
+    pub apply: bool,
+    /// Only these notes, usually the ones kept after a preview.
+    pub note_ids: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReplaceMatch {
+    pub note_id: String,
+    pub note_title: String,
+    /// 1-based line of the match in the note body.
+    pub line: usize,
+    /// The line around the match as it is.
+    pub context: String,
+    /// The same span with this match replaced.
+    pub replaced: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NoteReplacement {
+    pub note_id: String,
+    pub note_title: String,
+    pub replacements: usize,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReplaceOutcome {
+    /// Every match, for a preview. Empty once applied.
+    pub matches: Vec<ReplaceMatch>,
+    /// Whether there were more matches than could be listed.
+    pub truncated: bool,
+    /// Matches per note, or replacements made per note once applied.
+    pub notes: Vec<NoteReplacement>,
+    pub applied: bool,
+}
+
+/// A compiled find-and-replace.
+pub struct Replacer {
+    regex: Regex,
+    replacement: String,
+    /// Whether `$` in the replacement refers to groups.
+    expand: bool,
+}
+
+impl Replacer {
+    pub fn new(query: &str, replacement: &str, options: &ReplaceOptions) -> Result<Self> {
+        if query.is_empty() {
+            return Err(Error::InvalidInput("nothing to search for".into()));
+        }
+        let mut pattern = if options.regex {
+            query.to_owned()
+        } else {
+            regex::escape(query)
+        };
+        if options.whole_word {
+            pattern = format!(r"\b(?:{pattern})\b");
+        }
+        let regex = RegexBuilder::new(&pattern)
+            .case_insensitive(!options.case_sensitive)
+            .multi_line(true)
+            .build()
+            .map_err(|err| Error::InvalidInput(format!("invalid pattern: {err}")))?;
+        Ok(Self {
+            regex,
+            replacement: replacement.to_owned(),
+            expand: options.regex,
+        })
+    }
+
+    /// Adds the matches in `note` to `out`, up to [`MAX_MATCHES`] in all,
+    /// and returns how many there are.
+    pub fn find(&self, note: &Note, out: &mut Vec<ReplaceMatch>) -> usize {
+        let body = &note.body;
+        let mut count = 0;
+        let mut line = 1;
+        let mut counted_to = 0;
+        for caps in self.captures(body) {
+            count += 1;
+            if out.len() >= MAX_MATCHES {
+                continue;
+            }
+            let found = caps.get(0).expect("group 0 always matches");
+            line += body[counted_to..found.start()].matches('\n').count();
+            counted_to = found.start();
+            let line_start = body[..found.start()].rfind('\n').map_or(0, |i| i + 1);
+            let line_end = body[found.start()..]
+                .find('\n')
+                .map_or(body.len(), |i| found.start() + i);
+            let end = found.end().min(line_end);
+            let before = tail(&body[line_start..found.start()]);
+            let after = head(&body[end..line_end]);
+            let mut replaced = String::from(before);
+            self.push_replacement(&caps, &mut replaced);
+            replaced.push_str(after);
+            out.push(ReplaceMatch {
+                note_id: note.id.clone(),
+                note_title: note.title.clone(),
+                line,
+                context: format!("{before}{}{after}", &body[found.start()..end]),
+                replaced,
+            });
+        }
+        count
+    }
+
+    /// `body` with every match replaced and the number of replacements, or
+    /// `None` if nothing matched.
+    pub fn apply(&self, body: &str) -> Option<(String, usize)> {
+        let mut out = String::with_capacity(body.len());
+        let mut last = 0;
+        let mut count = 0;
+        for caps in self.captures(body) {
+            let found = caps.get(0).expect("group 0 always matches");
+            out.push_str(&body[last..found.start()]);
+            self.push_replacement(&caps, &mut out);
+            last = found.end();
+            count += 1;
+        }
+        if count == 0 {
+            return None;
+        }
+        out.push_str(&body[last..]);
+        Some((out, count))
+    }
+
+    /// Matches in `body`, leaving out empty ones, which a pattern like `a*`
+    /// finds between every character.
+    fn captures<'a>(&'a self, body: &'a str) -> impl Iterator<Item = Captures<'a>> + 'a {
+        self.regex
+            .captures_iter(body)
+            .filter(|caps| caps.get(0).is_some_and(|found| !found.is_empty()))
+    }
+
+    fn push_replacement(&self, caps: &Captures<'_>, out: &mut String) {
+        if self.expand {
+            caps.expand(&self.replacement, out);
+        } else {
+            out.push_str(&self.replacement);
+        }
+    }
+}
+
+fn tail(text: &str) -> &str {
+    match text.char_indices().rev().nth(CONTEXT_CHARS) {
+        Some((i, c)) => &text[i + c.len_utf8()..],
+        None => text,
+    }
+}
+
+fn head(text: &str) -> &str {
+    match text.char_indices().nth(CONTEXT_CHARS) {
+        Some((i, _)) => &text[..i],
+        None => text,
+    }
+}
@@ -0,0 +1,221 @@
+use std::collections::{HashMap, HashSet};
+
+use chrono::{Local, NaiveDate, TimeZone};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+use crate::storage::Storage;
+use crate::tags;
+use crate::vault::Vault;
+
+const SAVED_KEY: &str = "search.saved";
+
+/// What a saved search matches. Every filter that is set has to hold.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SearchFilter {
+    /// Full-text query, in the syntax of [`super::SearchIndex::search`].
+    pub query: Option<String>,
+    /// Tags a note needs all of. Nested tags count for their parents.
+    pub tags: Vec<String>,
+    /// Only notes in this folder or below it.
+    pub folder: Option<String>,
+    /// Days as `YYYY-MM-DD`, inclusive, in local time.
+    pub created_after: Option<String>,
+    pub created_before: Option<String>,
+    pub updated_after: Option<String>,
+    pub updated_before: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SavedSearch {
+    pub id: String,
+    pub name: String,
+    pub filter: SearchFilter,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SavedSearchHit {
+    pub id: String,
+    pub title: String,
+    pub folder: String,
+    pub updated_at: i64,
+    /// The matching passage when the search has a query.
+    pub snippet: Option<String>,
+}
+
+pub fn list(storage: &Storage) -> Result<Vec<SavedSearch>> {
+    match storage.meta(SAVED_KEY)? {
+        Some(json) => Ok(serde_json::from_str(&json)?),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Adds `search`, or replaces the saved search with its id.
+pub fn save(storage: &Storage, mut search: SavedSearch) -> Result<SavedSearch> {
+    if search.name.trim().is_empty() {
+        return Err(Error::InvalidInput("a saved search needs a name".into()));
+    }
+    search.filter.tags = search
+        .filter
+        .tags
+        .iter()
+        .map(|tag| tags::normalize(tag))
+        .collect::<Result<_>>()?;
+    // Bad dates would otherwise only show up when the search runs.
+    Bounds::of(&search.filter)?;
+    let mut saved = list(storage)?;
+    match saved.iter_mut().find(|s| s.id == search.id) {
+        Some(existing) => *existing = search.clone(),
+        None => saved.push(search.clone()),
+    }
+    saved.sort_by_key(|s| s.name.to_lowercase());
+    storage.set_meta(SAVED_KEY, &serde_json::to_string(&saved)?)?;
+    Ok(search)
+}
+
+pub fn delete(storage: &Storage, id: &str) -> Result<()> {
+    let mut saved = list(storage)?;
+    let before = saved.len();
+    saved.retain(|search| search.id != id);
+    if saved.len() == before {
+        return Err(Error::InvalidInput(format!("no saved search {id}")));
+    }
+    storage.set_meta(SAVED_KEY, &serde_json::to_string(&saved)?)
+}
+
+pub fn get(storage: &Storage, id: &str) -> Result<SavedSearch> {
+    list(storage)?
+        .into_iter()
+        .find(|search| search.id == id)
+        .ok_or_else(|| Error::InvalidInput(format!("no saved search {id}")))
+}
+
+/// The notes matching `filter` right now: by relevance when there is a
+/// query, otherwise most recently edited first.
+///
+/// Tags narrow the candidates through the tag index before anything else,
+/// and dates and folders are checked against note summaries, so only the
+/// full-text part touches the search index.
+pub fn run(vault: &Vault, filter: &SearchFilter, limit: usize) -> Result<Vec<SavedSearchHit>> {
+    let bounds = Bounds::of(filter)?;
+    let mut allowed: Option<HashSet<String>> = None;
+    for tag in &filter.tags {
+        let tagged: HashSet<String> = vault
+            .tags
+            .notes_with(&tags::normalize(tag)?)
+            .into_iter()
+            .collect();
+        allowed = Some(match allowed {
+            Some(allowed) => &allowed & &tagged,
+            None => tagged,
+        });
+    }
+    let folder = filter
+        .folder
+        .as_deref()
+        .map(|folder| folder.trim_matches('/'))
+        .filter(|folder| !folder.is_empty());
+
+    let notes = vault.storage.list_notes(None)?;
+    let total = notes.len();
+    let candidates: HashMap<String, _> = notes
+        .into_iter()
+        .filter(|note| {
+            allowed
+                .as_ref()
+                .is_none_or(|allowed| allowed.contains(&note.id))
+        })
+        .filter(|note| {
+            folder.is_none_or(|folder| {
+                note.folder == folder
+                    || note
+                        .folder
+                        .strip_prefix(folder)
+                        .is_some_and(|rest| rest.starts_with('/'))
+            })
+        })
+        .filter(|note| bounds.contains(note.created_at, note.updated_at))
+        .map(|note| (note.id.clone(), note))
+        .collect();
+
+    let query = filter.query.as_deref().map(str::trim).unwrap_or_default();
+    if query.is_empty() {
+        let mut notes: Vec<_> = candidates.into_values().collect();
+        notes.sort_by_key(|note| std::cmp::Reverse(note.updated_at));
+        return Ok(notes
+            .into_iter()
+            .take(limit)
+            .map(|note| SavedSearchHit {
+                id: note.id,
+                title: note.title,
+                folder: note.folder,
+                updated_at: note.updated_at,
+                snippet: None,
+            })
+            .collect());
+    }
+    let mut hits = Vec::new();
+    for hit in vault.search_notes(query, total.max(1), false)? {
+        let Some(note) = candidates.get(&hit.id) else {
+            continue;
+        };
+        hits.push(SavedSearchHit {
+            id: hit.id,
+            title: note.title.clone(),
+            folder: note.folder.clone(),
+            updated_at: note.updated_at,
+            snippet: Some(hit.snippet),
+        });
+        if hits.len() == limit {
+            break;
+        }
+    }
+    Ok(hits)
+}
+
+/// The date filters as half-open millisecond ranges.
+struct Bounds {
+    created: (i64, i64),
+    updated: (i64, i64),
+}
+
+impl Bounds {
+    fn of(filter: &SearchFilter) -> Result<Self> {
+        Ok(Self {
+            created: (
+                day_start(filter.created_after.as_deref(), 0)?.unwrap_or(i64::MIN),
+                day_start(filter.created_before.as_deref(), 1)?.unwrap_or(i64::MAX),
+            ),
+            updated: (
+                day_start(filter.updated_after.as_deref(), 0)?.unwrap_or(i64::MIN),
+                day_start(filter.updated_before.as_deref(), 1)?.unwrap_or(i64::MAX),
+            ),
+        })
+    }
+
+    fn contains(&self, created_at: i64, updated_at: i64) -> bool {
+        (self.created.0..self.created.1).contains(&created_at)
+            && (self.updated.0..self.updated.1).contains(&updated_at)
+    }
+}
+
+/// Local midnight `days_later` days after `date`, in milliseconds.
+pub(crate) fn day_start(date: Option<&str>, days_later: u64) -> Result<Option<i64>> {
+    let Some(date) = date.map(str::trim).filter(|date| !date.is_empty()) else {
+        return Ok(None);
+    };
+    let day = NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .ok()
+        .and_then(|day| day.checked_add_days(chrono::Days::new(days_later)))
+        .ok_or_else(|| Error::InvalidInput(format!("not a date: {date}")))?;
+    let midnight = day.and_hms_opt(0, 0, 0).unwrap_or_default();
+    // A day starting in a DST gap still has a first instant after it.
+    let start = Local.from_local_datetime(&midnight).earliest().map_or_else(
+        || midnight.and_utc().timestamp_millis(),
+        |start| start.timestamp_millis(),
+    );
+    Ok(Some(start))
+}
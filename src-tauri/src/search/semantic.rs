@@ -0,0 +1,319 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+#[cfg(feature = "semantic")]
+use std::sync::Mutex;
+use std::time::Duration;
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use rusqlite::{params, OptionalExtension};
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::attachments::text::TextQueue;
+use crate::error::{Error, Result};
+use crate::jobs::{JobContext, JobKind, Jobs, Priority};
+use crate::metadata::FrontMatter;
+use crate::storage::{Note, Storage};
+use crate::vault::Vault;
+
+/// Event carrying the message of an embedding pass that failed.
+pub const ERROR_EVENT: &str = "semantic-error";
+
+/// Files of the embedding model, exported from a sentence-transformers model
+/// such as all-MiniLM-L6-v2.
+const MODEL_FILES: [&str; 5] = [
+    "model.onnx",
+    "tokenizer.json",
+    "config.json",
+    "special_tokens_map.json",
+    "tokenizer_config.json",
+];
+/// How much of a note is embedded; the model truncates long input anyway.
+const MAX_CHARS: usize = 4_000;
+const BATCH: i64 = 16;
+const IDLE: Duration = Duration::from_secs(10 * 60);
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SemanticHit {
+    pub id: String,
+    pub title: String,
+    /// Cosine similarity, higher is closer.
+    pub score: f32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SemanticStatus {
+    /// Whether this build includes the embedding runtime.
+    pub available: bool,
+    /// Whether the model files are in place.
+    pub model_ready: bool,
+    pub model_dir: String,
+    pub indexed: usize,
+    pub total: usize,
+}
+
+/// Sentence embeddings of every note, computed on this machine with a model
+/// kept under `<vault>/models/embedding`; nothing is downloaded.
+///
+/// Vectors are stored one per note and sealed like bodies. A note's vector
+/// is dropped whenever it changes and recomputed in the background.
+pub struct Semantic {
+    model_dir: PathBuf,
+    #[cfg(feature = "semantic")]
+    model: Mutex<Option<fastembed::TextEmbedding>>,
+    /// Wakes the embedding worker.
+    pub queue: TextQueue,
+}
+
+impl Semantic {
+    pub fn new(model_dir: &Path) -> Self {
+        Self {
+            model_dir: model_dir.to_owned(),
+            #[cfg(feature = "semantic")]
+            model: Mutex::new(None),
+            queue: TextQueue::default(),
+        }
+    }
+
+    pub fn model_ready(&self) -> bool {
+        MODEL_FILES
+            .iter()
+            .all(|name| self.model_dir.join(name).is_file())
+    }
+
+    /// Forgets the embedding of a note that changed, for the worker to redo.
+    pub fn note_changed(&self, storage: &Storage, id: &str) -> Result<()> {
+        storage
+            .conn()
+            .execute("DELETE FROM embeddings WHERE note_id = ?1", [id])?;
+        self.queue.wake();
+        Ok(())
+    }
+
+    pub fn status(&self, storage: &Storage) -> Result<SemanticStatus> {
+        let (indexed, total): (i64, i64) = storage.conn().query_row(
+            "SELECT (SELECT COUNT(*) FROM embeddings),
+                    (SELECT COUNT(*) FROM notes WHERE id NOT IN (SELECT note_id FROM trash))",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+        Ok(SemanticStatus {
+            available: cfg!(feature = "semantic"),
+            model_ready: self.model_ready(),
+            model_dir: self.model_dir.to_string_lossy().into_owned(),
+            indexed: indexed as usize,
+            total: total as usize,
+        })
+    }
+
+    /// The `k` notes closest in meaning to `query`.
+    pub fn search(&self, storage: &Storage, query: &str, k: usize) -> Result<Vec<SemanticHit>> {
+        let query = self.embed(&[query.to_owned()])?.pop().unwrap_or_default();
+        nearest(storage, &query, k, None)
+    }
+
+    /// The `k` notes closest in meaning to the note `id`, for a "similar
+    /// notes" panel. Empty until the note has been embedded.
+    pub fn related(&self, storage: &Storage, id: &str, k: usize) -> Result<Vec<SemanticHit>> {
+        let stored: Option<String> = storage
+            .conn()
+            .query_row(
+                "SELECT vector FROM embeddings WHERE note_id = ?1",
+                [id],
+                |row| row.get(0),
+            )
+            .optional()?;
+        let Some(stored) = stored else {
+            return Ok(Vec::new());
+        };
+        let vector = decode(&storage.open_body(stored)?)?;
+        nearest(storage, &vector, k, Some(id))
+    }
+
+    /// Embeds notes that have no vector yet, a batch at a time, returning
+    /// how many. Does nothing without a model or while the vault is locked.
+    pub fn run_pending(&self, storage: &Storage, job: &JobContext) -> Result<usize> {
+        if !cfg!(feature = "semantic") || !self.model_ready() {
+            return Ok(0);
+        }
+        let total: i64 = storage.conn().query_row(
+            "SELECT COUNT(*) FROM notes
+             WHERE id NOT IN (SELECT note_id FROM embeddings)
+               AND id NOT IN (SELECT note_id FROM trash)",
+            [],
+            |row| row.get(0),
+        )?;
+        let mut done = 0;
+        loop {
+            job.check()?;
+            job.progress(done, total as usize);
+            if storage.is_locked() {
+                return Ok(done);
+            }
+            let ids: Vec<String> = {
+                let conn = storage.conn();
+                let mut stmt = conn.prepare(
+                    "SELECT id FROM notes
+                     WHERE id NOT IN (SELECT note_id FROM embeddings)
+                       AND id NOT IN (SELECT note_id FROM trash)
+                     LIMIT ?1",
+                )?;
+                let ids = stmt
+                    .query_map([BATCH], |row| row.get(0))?
+                    .collect::<rusqlite::Result<_>>()?;
+                ids
+            };
+            if ids.is_empty() {
+                return Ok(done);
+            }
+            let notes = ids
+                .iter()
+                .map(|id| storage.get_note(id))
+                .collect::<Result<Vec<_>>>()?;
+            let texts: Vec<String> = notes.iter().map(embedding_text).collect();
+            let vectors = self.embed(&texts)?;
+            for (note, vector) in notes.iter().zip(vectors) {
+                storage.conn().execute(
+                    "INSERT OR REPLACE INTO embeddings (note_id, vector) VALUES (?1, ?2)",
+                    params![note.id, storage.seal_body(&encode(&vector))?],
+                )?;
+            }
+            done += notes.len();
+        }
+    }
+
+    #[cfg(feature = "semantic")]
+    fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        let mut model = self.model.lock().expect("embedding model poisoned");
+        if model.is_none() {
+            *model = Some(self.load()?);
+        }
+        let model = model.as_mut().expect("model was just loaded");
+        model
+            .embed(texts, None)
+            .map_err(|err| Error::Semantic(err.to_string()))
+    }
+
+    #[cfg(not(feature = "semantic"))]
+    fn embed(&self, _texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        Err(Error::Semantic(
+            "this build does not include semantic search".into(),
+        ))
+    }
+
+    #[cfg(feature = "semantic")]
+    fn load(&self) -> Result<fastembed::TextEmbedding> {
+        if !self.model_ready() {
+            return Err(Error::Semantic(format!(
+                "no embedding model in {}",
+                self.model_dir.display()
+            )));
+        }
+        let read = |name: &str| std::fs::read(self.model_dir.join(name));
+        let tokenizer = fastembed::TokenizerFiles {
+            tokenizer_file: read("tokenizer.json")?,
+            config_file: read("config.json")?,
+            special_tokens_map_file: read("special_tokens_map.json")?,
+            tokenizer_config_file: read("tokenizer_config.json")?,
+        };
+        let model = fastembed::UserDefinedEmbeddingModel::new(read("model.onnx")?, tokenizer)
+            .with_pooling(fastembed::Pooling::Mean);
+        fastembed::TextEmbedding::try_new_from_user_defined(model, Default::default())
+            .map_err(|err| Error::Semantic(err.to_string()))
+    }
+}
+
+/// Embeds changed notes whenever woken, and every [`IDLE`] otherwise.
+/// Failures are reported through [`ERROR_EVENT`].
+pub fn spawn_worker(app: AppHandle, vault: Arc<Vault>) {
+    tauri::async_runtime::spawn(async move {
+        while !vault.is_closed() {
+            let worker = Arc::clone(&vault);
+            let result = app
+                .state::<Jobs>()
+                .run(JobKind::Embeddings, Priority::Background, move |job| {
+                    worker.semantic.run_pending(&worker.storage, job)
+                })
+                .await;
+            match result {
+                Ok(_) | Err(Error::Cancelled) => {}
+                Err(err) => {
+                    let _ = app.emit(ERROR_EVENT, err.to_string());
+                }
+            }
+            let _ = tokio::time::timeout(IDLE, vault.semantic.queue.notified()).await;
+        }
+    });
+}
+
+fn nearest(
+    storage: &Storage,
+    target: &[f32],
+    k: usize,
+    skip: Option<&str>,
+) -> Result<Vec<SemanticHit>> {
+    let rows: Vec<(String, String, String)> = {
+        let conn = storage.conn();
+        let mut stmt = conn.prepare(
+            "SELECT e.note_id, n.title, e.vector FROM embeddings e
+             JOIN notes n ON n.id = e.note_id
+             WHERE e.note_id NOT IN (SELECT note_id FROM trash)",
+        )?;
+        let rows = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+            .collect::<rusqlite::Result<_>>()?;
+        rows
+    };
+    let mut hits = Vec::with_capacity(rows.len());
+    for (id, title, stored) in rows {
+        if skip == Some(id.as_str()) {
+            continue;
+        }
+        let vector = decode(&storage.open_body(stored)?)?;
+        hits.push(SemanticHit {
+            score: cosine(target, &vector),
+            id,
+            title,
+        });
+    }
+    hits.sort_by(|a, b| b.score.total_cmp(&a.score));
+    hits.truncate(k);
+    Ok(hits)
+}
+
+fn embedding_text(note: &Note) -> String {
+    let (_, markdown) = FrontMatter::split(&note.body);
+    let text = format!("{}\n\n{markdown}", note.title);
+    text.chars().take(MAX_CHARS).collect()
+}
+
+fn cosine(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm = |v: &[f32]| v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let denominator = norm(a) * norm(b);
+    if denominator == 0.0 {
+        0.0
+    } else {
+        dot / denominator
+    }
+}
+
+/// Vectors are stored as base64 of little-endian `f32`s, so they can be
+/// sealed like any other text.
+fn encode(vector: &[f32]) -> String {
+    let bytes: Vec<u8> = vector.iter().flat_map(|x| x.to_le_bytes()).collect();
+    BASE64.encode(bytes)
+}
+
+fn decode(stored: &str) -> Result<Vec<f32>> {
+    let bytes = BASE64
+        .decode(stored)
+        .map_err(|err| Error::Semantic(format!("corrupt embedding: {err}")))?;
+    Ok(bytes
+        .chunks_exact(4)
+        .map(|chunk| f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+        .collect())
+}
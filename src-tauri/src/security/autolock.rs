@@ -0,0 +1,201 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::error::Result;
+use crate::protected::{self, Relocked};
+use crate::storage::Storage;
+use crate::vault::Vault;
+use crate::vaults::Vaults;
+
+/// Event carrying a [`VaultLocked`] when a vault locks on its own.
+pub const LOCKED_EVENT: &str = "vault-locked";
+
+const CONFIG_KEY: &str = "security.autolock";
+const CHECK_INTERVAL: Duration = Duration::from_secs(15);
+/// How far wall time may run ahead of the monotonic clock between two
+/// checks before it counts as the machine having slept. The monotonic
+/// clock stands still during suspend on Linux and macOS.
+const SLEEP_GAP: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct AutoLockConfig {
+    /// Minutes without activity before an encrypted vault locks; 0 never.
+    pub idle_minutes: u32,
+    /// Lock when the computer sleeps.
+    pub on_sleep: bool,
+    /// Lock when the screen locks.
+    pub on_screen_lock: bool,
+    /// Minutes after which a note or folder unlocked with its own
+    /// passphrase locks again unless it is being worked on; 0 never.
+    pub protected_minutes: u32,
+}
+
+impl Default for AutoLockConfig {
+    fn default() -> Self {
+        Self {
+            idle_minutes: 15,
+            on_sleep: true,
+            on_screen_lock: true,
+            protected_minutes: 5,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum LockReason {
+    Idle,
+    Sleep,
+    ScreenLock,
+}
+
+/// What the OS announces, as the platform hooks pass it on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SystemEvent {
+    Sleep,
+    ScreenLock,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VaultLocked {
+    pub vault_id: String,
+    pub reason: LockReason,
+}
+
+/// When the user last did something in any window, managed as app state.
+pub struct Activity(Mutex<Instant>);
+
+impl Default for Activity {
+    fn default() -> Self {
+        Self(Mutex::new(Instant::now()))
+    }
+}
+
+impl Activity {
+    pub fn touch(&self) {
+        *self.0.lock().expect("activity poisoned") = Instant::now();
+    }
+
+    pub fn idle(&self) -> Duration {
+        self.0.lock().expect("activity poisoned").elapsed()
+    }
+}
+
+pub fn config_of(storage: &Storage) -> Result<AutoLockConfig> {
+    match storage.meta(CONFIG_KEY)? {
+        Some(json) => Ok(serde_json::from_str(&json)?),
+        None => Ok(AutoLockConfig::default()),
+    }
+}
+
+pub fn set_config(storage: &Storage, config: &AutoLockConfig) -> Result<()> {
+    storage.set_meta(CONFIG_KEY, &serde_json::to_string(config)?)
+}
+
+/// Checks every [`CHECK_INTERVAL`], and straight away when the OS says it
+/// is going to sleep or the screen locked, whether an open vault should
+/// lock: because the user has been away longer than its idle period, the
+/// machine slept since the last check or the screen locked. Locking drops
+/// the key, which wipes it from memory, and announces it through
+/// [`LOCKED_EVENT`].
+pub fn start(app: &AppHandle) {
+    let app = app.clone();
+    let (events, mut received) = tokio::sync::mpsc::unbounded_channel();
+    super::session::watch(move |event| {
+        let _ = events.send(event);
+    });
+    tauri::async_runtime::spawn(async move {
+        let mut last = (Instant::now(), SystemTime::now());
+        loop {
+            let event = match tokio::time::timeout(CHECK_INTERVAL, received.recv()).await {
+                Ok(Some(event)) => Some(event),
+                // Nothing on this platform sends any.
+                Ok(None) => {
+                    tokio::time::sleep(CHECK_INTERVAL).await;
+                    None
+                }
+                Err(_) => None,
+            };
+            let now = (Instant::now(), SystemTime::now());
+            let wall = now.1.duration_since(last.1).unwrap_or_default();
+            let slept = event == Some(SystemEvent::Sleep)
+                || wall.saturating_sub(now.0 - last.0) > SLEEP_GAP;
+            let screen_locked = event == Some(SystemEvent::ScreenLock);
+            last = now;
+            let idle = app.state::<Activity>().idle();
+
+            for (id, vault) in app.state::<Vaults>().open_vaults() {
+                if vault.storage.is_locked() {
+                    continue;
+                }
+                let Ok(config) = config_of(&vault.storage) else {
+                    continue;
+                };
+                let away = if slept && config.on_sleep {
+                    Some(LockReason::Sleep)
+                } else if screen_locked && config.on_screen_lock {
+                    Some(LockReason::ScreenLock)
+                } else {
+                    None
+                };
+                relock_protected(&app, &id, &vault, &config, away.is_some()).await;
+                if !vault.storage.is_encrypted() {
+                    continue;
+                }
+                let reason = match away {
+                    Some(reason) => reason,
+                    None if config.idle_minutes > 0
+                        && idle >= Duration::from_secs(u64::from(config.idle_minutes) * 60) =>
+                    {
+                        LockReason::Idle
+                    }
+                    None => continue,
+                };
+                let locked = tauri::async_runtime::spawn_blocking(move || vault.lock()).await;
+                if let Ok(Ok(())) = locked {
+                    let _ = app.emit(
+                        LOCKED_EVENT,
+                        VaultLocked {
+                            vault_id: id,
+                            reason,
+                        },
+                    );
+                }
+            }
+        }
+    });
+}
+
+/// Locks notes and folders with a passphrase of their own that have sat
+/// unused too long, or all of them once the user is `away`.
+async fn relock_protected(
+    app: &AppHandle,
+    id: &str,
+    vault: &Arc<Vault>,
+    config: &AutoLockConfig,
+    away: bool,
+) {
+    let idle = match (away, config.protected_minutes) {
+        (true, _) => Duration::ZERO,
+        (false, 0) => return,
+        (false, minutes) => Duration::from_secs(u64::from(minutes) * 60),
+    };
+    let vault = Arc::clone(vault);
+    let expired =
+        tauri::async_runtime::spawn_blocking(move || protected::expire(&vault, idle)).await;
+    if let Ok(Ok((note_ids, folders))) = expired {
+        if !note_ids.is_empty() || !folders.is_empty() {
+            let relocked = Relocked {
+                vault_id: id.to_owned(),
+                note_ids,
+                folders,
+            };
+            let _ = app.emit(protected::RELOCKED_EVENT, relocked);
+        }
+    }
+}
@@ -0,0 +1,48 @@
+use tauri::{AppHandle, State};
+
+use super::autolock::{self, Activity, AutoLockConfig};
+use super::KeychainStatus;
+use crate::error::Result;
+use crate::vaults::Current;
+
+#[tauri::command]
+pub async fn keychain_status(app: AppHandle, vault: Current) -> Result<KeychainStatus> {
+    tauri::async_runtime::spawn_blocking(move || super::status(&app, &vault)).await?
+}
+
+/// Keeps the unlocked vault's key in the OS keychain for
+/// [`unlock_with_keychain`].
+#[tauri::command]
+pub async fn remember_vault_key(vault: Current) -> Result<()> {
+    tauri::async_runtime::spawn_blocking(move || super::remember(&vault)).await?
+}
+
+#[tauri::command]
+pub async fn forget_vault_key(vault: Current) -> Result<()> {
+    tauri::async_runtime::spawn_blocking(move || super::forget(&vault)).await?
+}
+
+/// Unlocks without the password, using the key from the keychain. On
+/// phones this asks for a fingerprint or face first.
+#[tauri::command]
+pub async fn unlock_with_keychain(app: AppHandle, vault: Current) -> Result<()> {
+    tauri::async_runtime::spawn_blocking(move || super::unlock(&app, &vault)).await?
+}
+
+/// Called by the frontend on input, at most every few seconds, to hold off
+/// the idle lock.
+#[tauri::command]
+pub async fn report_activity(activity: State<'_, Activity>) -> Result<()> {
+    activity.touch();
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn get_autolock_config(vault: Current) -> Result<AutoLockConfig> {
+    autolock::config_of(&vault.storage)
+}
+
+#[tauri::command]
+pub async fn set_autolock_config(vault: Current, config: AutoLockConfig) -> Result<()> {
+    autolock::set_config(&vault.storage, &config)
+}
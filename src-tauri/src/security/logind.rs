@@ -0,0 +1,97 @@
+//! Sleep and screen lock from logind: the manager announces a suspend
+//! before it happens, and desktops that lock the screen mark this
+//! session as locked or ask logind to lock it.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::thread;
+
+use zbus::blocking::{Connection, MessageIterator};
+use zbus::message::Type;
+use zbus::zvariant::{OwnedObjectPath, OwnedValue};
+use zbus::{MatchRule, Message};
+
+use super::autolock::SystemEvent;
+
+const LOGIND: &str = "org.freedesktop.login1";
+const SESSION: &str = "org.freedesktop.login1.Session";
+
+pub fn watch(notify: impl Fn(SystemEvent) + Send + Sync + 'static) {
+    thread::spawn(move || {
+        if let Err(err) = listen(Arc::new(notify)) {
+            tracing::warn!("cannot follow sleep and screen lock: {err}");
+        }
+    });
+}
+
+fn listen(notify: Arc<dyn Fn(SystemEvent) + Send + Sync>) -> zbus::Result<()> {
+    let connection = Connection::system()?;
+    let sleep = MatchRule::builder()
+        .msg_type(Type::Signal)
+        .sender(LOGIND)?
+        .interface("org.freedesktop.login1.Manager")?
+        .member("PrepareForSleep")?
+        .build();
+    follow(&connection, sleep, Arc::clone(&notify), |message| {
+        // Sent again with `false` on waking.
+        message
+            .body()
+            .deserialize::<bool>()
+            .is_ok_and(|starting| starting)
+            .then_some(SystemEvent::Sleep)
+    })?;
+
+    let session: OwnedObjectPath = connection
+        .call_method(
+            Some(LOGIND),
+            "/org/freedesktop/login1",
+            Some("org.freedesktop.login1.Manager"),
+            "GetSessionByPID",
+            &(std::process::id()),
+        )?
+        .body()
+        .deserialize()?;
+    let lock = MatchRule::builder()
+        .msg_type(Type::Signal)
+        .sender(LOGIND)?
+        .path(session.clone())?
+        .interface(SESSION)?
+        .member("Lock")?
+        .build();
+    follow(&connection, lock, Arc::clone(&notify), |_| {
+        Some(SystemEvent::ScreenLock)
+    })?;
+    let hint = MatchRule::builder()
+        .msg_type(Type::Signal)
+        .sender(LOGIND)?
+        .path(session)?
+        .interface("org.freedesktop.DBus.Properties")?
+        .member("PropertiesChanged")?
+        .arg(0, SESSION)?
+        .build();
+    follow(&connection, hint, notify, |message| {
+        let (_, changed, _): (String, HashMap<String, OwnedValue>, Vec<String>) =
+            message.body().deserialize().ok()?;
+        let locked = bool::try_from(changed.get("LockedHint")?).ok()?;
+        locked.then_some(SystemEvent::ScreenLock)
+    })
+}
+
+/// Hands what `rule` matches, read by `event`, to `notify` on a thread of
+/// its own.
+fn follow(
+    connection: &Connection,
+    rule: MatchRule<'static>,
+    notify: Arc<dyn Fn(SystemEvent) + Send + Sync>,
+    event: impl Fn(&Message) -> Option<SystemEvent> + Send + 'static,
+) -> zbus::Result<()> {
+    let messages = MessageIterator::for_match_rule(rule, connection, None)?;
+    thread::spawn(move || {
+        for message in messages.flatten() {
+            if let Some(event) = event(&message) {
+                notify(event);
+            }
+        }
+    });
+    Ok(())
+}
@@ -0,0 +1,200 @@
+pub mod autolock;
+pub mod commands;
+
+#[cfg_attr(target_os = "linux", path = "logind.rs")]
+#[cfg_attr(windows, path = "wts.rs")]
+#[cfg_attr(target_os = "macos", path = "workspace.rs")]
+#[cfg_attr(
+    not(any(target_os = "linux", windows, target_os = "macos")),
+    path = "unsupported.rs"
+)]
+mod session;
+
+use std::sync::LazyLock;
+
+use serde::Serialize;
+use tauri::AppHandle;
+use zeroize::Zeroizing;
+
+use crate::crypto::VaultKey;
+use crate::error::{Error, Result};
+use crate::vault::Vault;
+
+/// Service name of every credential this app keeps in the OS keychain.
+const SERVICE: &str = "notesdesktop";
+/// Keychain account under which a vault's key is remembered. Random per
+/// vault, so moving or renaming the vault folder keeps it.
+const ACCOUNT_KEY: &str = "security.keychain_account";
+
+/// Whether the platform credential store could be opened, set up once.
+static STORE: LazyLock<std::result::Result<(), String>> = LazyLock::new(|| {
+    #[cfg(target_os = "macos")]
+    let store = apple_native_keyring_store::keychain::Store::new();
+    #[cfg(target_os = "ios")]
+    let store = apple_native_keyring_store::protected::Store::new();
+    #[cfg(target_os = "windows")]
+    let store = windows_native_keyring_store::Store::new();
+    #[cfg(target_os = "android")]
+    let store = android_native_keyring_store::Store::new();
+    #[cfg(all(
+        unix,
+        not(any(target_os = "macos", target_os = "ios", target_os = "android"))
+    ))]
+    let store = zbus_secret_service_keyring_store::Store::new();
+    keyring_core::set_default_store(store.map_err(|err| err.to_string())?);
+    Ok(())
+});
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KeychainStatus {
+    /// Whether the OS keychain can be used on this machine.
+    pub available: bool,
+    /// Whether this vault's key is in it.
+    pub remembered: bool,
+    /// Whether reading the key asks for a fingerprint or face first.
+    pub biometric: bool,
+    /// Why the keychain is unavailable.
+    pub error: Option<String>,
+}
+
+pub fn status(app: &AppHandle, vault: &Vault) -> Result<KeychainStatus> {
+    Ok(KeychainStatus {
+        available: STORE.is_ok(),
+        remembered: vault.storage.meta(ACCOUNT_KEY)?.is_some(),
+        biometric: biometric_available(app),
+        error: STORE.as_ref().err().cloned(),
+    })
+}
+
+/// Keeps the key of the unlocked vault in the OS keychain, so the next
+/// launch can unlock without the password. The key itself is stored, not
+/// the password, and only there.
+pub fn remember(vault: &Vault) -> Result<()> {
+    let key = vault.storage.key_bytes()?;
+    let account = match vault.storage.meta(ACCOUNT_KEY)? {
+        Some(account) => account,
+        None => uuid::Uuid::new_v4().to_string(),
+    };
+    entry(&account)?
+        .set_secret(key.as_slice())
+        .map_err(keychain_error)?;
+    vault.storage.set_meta(ACCOUNT_KEY, &account)
+}
+
+/// Removes the vault's key from the keychain.
+pub fn forget(vault: &Vault) -> Result<()> {
+    let Some(account) = vault.storage.meta(ACCOUNT_KEY)? else {
+        return Ok(());
+    };
+    match entry(&account)?.delete_credential() {
+        Ok(()) | Err(keyring_core::Error::NoEntry) => {}
+        Err(err) => return Err(keychain_error(err)),
+    }
+    vault.storage.delete_meta(ACCOUNT_KEY)
+}
+
+/// Called after the password changed, since that changes the key too.
+pub fn password_changed(vault: &Vault) -> Result<()> {
+    match vault.storage.meta(ACCOUNT_KEY)? {
+        Some(_) => remember(vault),
+        None => Ok(()),
+    }
+}
+
+/// Unlocks the vault with its remembered key, after a biometric check
+/// where the device has one. A key that no longer fits, because the
+/// password was changed on another device, is forgotten.
+pub fn unlock(app: &AppHandle, vault: &Vault) -> Result<()> {
+    let Some(account) = vault.storage.meta(ACCOUNT_KEY)? else {
+        return Err(Error::InvalidInput(
+            "this vault's key is not remembered on this device".into(),
+        ));
+    };
+    authenticate(app)?;
+    let secret = match entry(&account)?.get_secret() {
+        Ok(secret) => Zeroizing::new(secret),
+        Err(keyring_core::Error::NoEntry) => {
+            vault.storage.delete_meta(ACCOUNT_KEY)?;
+            return Err(Error::Keychain(
+                "the key is no longer in the keychain".into(),
+            ));
+        }
+        Err(err) => return Err(keychain_error(err)),
+    };
+    match vault.unlock_with_key(VaultKey::from_bytes(&secret)?) {
+        Err(Error::WrongPassword) => {
+            forget(vault)?;
+            Err(Error::WrongPassword)
+        }
+        result => result,
+    }
+}
+
+/// Keeps a password for another service, such as a mail account, in the
+/// OS keychain under `account`.
+pub fn store_secret(account: &str, secret: &str) -> Result<()> {
+    entry(account)?.set_password(secret).map_err(keychain_error)
+}
+
+pub fn load_secret(account: &str) -> Result<Option<Zeroizing<String>>> {
+    match entry(account)?.get_password() {
+        Ok(secret) => Ok(Some(Zeroizing::new(secret))),
+        Err(keyring_core::Error::NoEntry) => Ok(None),
+        Err(err) => Err(keychain_error(err)),
+    }
+}
+
+pub fn delete_secret(account: &str) -> Result<()> {
+    match entry(account)?.delete_credential() {
+        Ok(()) | Err(keyring_core::Error::NoEntry) => Ok(()),
+        Err(err) => Err(keychain_error(err)),
+    }
+}
+
+fn entry(account: &str) -> Result<keyring_core::Entry> {
+    STORE.as_ref().map_err(|err| Error::Keychain(err.clone()))?;
+    keyring_core::Entry::new(SERVICE, account).map_err(keychain_error)
+}
+
+fn keychain_error(err: keyring_core::Error) -> Error {
+    Error::Keychain(err.to_string())
+}
+
+#[cfg(mobile)]
+fn biometric_available(app: &AppHandle) -> bool {
+    use tauri_plugin_biometric::BiometricExt;
+
+    app.biometric()
+        .status()
+        .is_ok_and(|status| status.is_available)
+}
+
+#[cfg(desktop)]
+fn biometric_available(_app: &AppHandle) -> bool {
+    false
+}
+
+/// Asks for a fingerprint or face, or the device passcode as a fallback.
+/// Desktop keychains do their own prompting.
+#[cfg(mobile)]
+fn authenticate(app: &AppHandle) -> Result<()> {
+    use tauri_plugin_biometric::{AuthOptions, BiometricExt};
+
+    if !biometric_available(app) {
+        return Ok(());
+    }
+    let options = AuthOptions {
+        allow_device_credential: true,
+        title: Some("Unlock notes".into()),
+        ..Default::default()
+    };
+    app.biometric()
+        .authenticate("Unlock your encrypted notes".into(), options)
+        .map_err(|err| Error::Keychain(err.to_string()))
+}
+
+#[cfg(desktop)]
+fn authenticate(_app: &AppHandle) -> Result<()> {
+    Ok(())
+}
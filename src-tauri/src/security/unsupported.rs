@@ -0,0 +1,6 @@
+//! Platforms whose sleep and screen lock this app cannot follow; the
+//! clocks drifting apart still catch a sleep.
+
+use super::autolock::SystemEvent;
+
+pub fn watch(_notify: impl Fn(SystemEvent) + Send + Sync + 'static) {}
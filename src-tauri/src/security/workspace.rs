@@ -0,0 +1,48 @@
+//! Sleep from the workspace, which announces it before the Mac goes to
+//! sleep, and the screen lock from the notification the login window
+//! sends to every app.
+
+use std::ptr::NonNull;
+use std::sync::Arc;
+
+use block2::RcBlock;
+use objc2_app_kit::{NSWorkspace, NSWorkspaceWillSleepNotification};
+use objc2_foundation::{
+    ns_string, NSDistributedNotificationCenter, NSNotification, NSNotificationCenter,
+    NSNotificationName,
+};
+
+use super::autolock::SystemEvent;
+
+pub fn watch(notify: impl Fn(SystemEvent) + Send + Sync + 'static) {
+    let notify: Arc<dyn Fn(SystemEvent) + Send + Sync> = Arc::new(notify);
+    let workspace = NSWorkspace::sharedWorkspace().notificationCenter();
+    observe(
+        &workspace,
+        unsafe { NSWorkspaceWillSleepNotification },
+        Arc::clone(&notify),
+        SystemEvent::Sleep,
+    );
+    observe(
+        &NSDistributedNotificationCenter::defaultCenter(),
+        ns_string!("com.apple.screenIsLocked"),
+        notify,
+        SystemEvent::ScreenLock,
+    );
+}
+
+/// Hands `event` to `notify` whenever `center` posts `name`. The centre
+/// keeps the observer for the life of the app.
+fn observe(
+    center: &NSNotificationCenter,
+    name: &NSNotificationName,
+    notify: Arc<dyn Fn(SystemEvent) + Send + Sync>,
+    event: SystemEvent,
+) {
+    let block = RcBlock::new(move |_: NonNull<NSNotification>| notify(event));
+    // SAFETY: no object to filter by, no queue, so the block runs on the
+    // posting thread, and it only passes a copy of `event` along.
+    let _ = unsafe {
+        center.addObserverForName_object_queue_usingBlock(Some(name), None, None, &block)
+    };
+}
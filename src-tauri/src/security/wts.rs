@@ -0,0 +1,92 @@
+//! Sleep and screen lock as Windows broadcasts them, caught by a window
+//! that is never shown. The clocks cannot tell a sleep here, as the
+//! monotonic one keeps counting through it.
+
+use std::sync::OnceLock;
+use std::thread;
+
+use windows::core::{w, PCWSTR};
+use windows::Win32::Foundation::{HWND, LPARAM, LRESULT, WPARAM};
+use windows::Win32::System::LibraryLoader::GetModuleHandleW;
+use windows::Win32::System::RemoteDesktop::{
+    WTSRegisterSessionNotification, NOTIFY_FOR_THIS_SESSION,
+};
+use windows::Win32::UI::WindowsAndMessaging::{
+    CreateWindowExW, DefWindowProcW, DispatchMessageW, GetMessageW, RegisterClassW, MSG,
+    PBT_APMSUSPEND, WINDOW_EX_STYLE, WINDOW_STYLE, WM_POWERBROADCAST, WM_WTSSESSION_CHANGE,
+    WNDCLASSW, WTS_SESSION_LOCK,
+};
+
+use super::autolock::SystemEvent;
+
+const CLASS: PCWSTR = w!("NotesDesktopSessionEvents");
+
+static NOTIFY: OnceLock<Box<dyn Fn(SystemEvent) + Send + Sync>> = OnceLock::new();
+
+pub fn watch(notify: impl Fn(SystemEvent) + Send + Sync + 'static) {
+    if NOTIFY.set(Box::new(notify)).is_err() {
+        return;
+    }
+    thread::spawn(|| {
+        if let Err(err) = listen() {
+            tracing::warn!("cannot follow sleep and screen lock: {err}");
+        }
+    });
+}
+
+fn listen() -> windows::core::Result<()> {
+    // SAFETY: the class and window are made, and their messages pumped,
+    // on this thread, which runs until the app quits.
+    unsafe {
+        let instance = GetModuleHandleW(None)?.into();
+        let class = WNDCLASSW {
+            lpfnWndProc: Some(window_proc),
+            hInstance: instance,
+            lpszClassName: CLASS,
+            ..Default::default()
+        };
+        RegisterClassW(&class);
+        // Top-level, as message-only windows are left out of the power
+        // broadcasts.
+        let window = CreateWindowExW(
+            WINDOW_EX_STYLE::default(),
+            CLASS,
+            PCWSTR::null(),
+            WINDOW_STYLE::default(),
+            0,
+            0,
+            0,
+            0,
+            None,
+            None,
+            Some(instance),
+            None,
+        )?;
+        WTSRegisterSessionNotification(window, NOTIFY_FOR_THIS_SESSION)?;
+        let mut message = MSG::default();
+        while GetMessageW(&mut message, None, 0, 0).as_bool() {
+            DispatchMessageW(&message);
+        }
+    }
+    Ok(())
+}
+
+unsafe extern "system" fn window_proc(
+    window: HWND,
+    message: u32,
+    wparam: WPARAM,
+    lparam: LPARAM,
+) -> LRESULT {
+    let event = match message {
+        WM_POWERBROADCAST if wparam.0 == PBT_APMSUSPEND as usize => Some(SystemEvent::Sleep),
+        WM_WTSSESSION_CHANGE if wparam.0 == WTS_SESSION_LOCK as usize => {
+            Some(SystemEvent::ScreenLock)
+        }
+        _ => None,
+    };
+    if let (Some(event), Some(notify)) = (event, NOTIFY.get()) {
+        notify(event);
+    }
+    // SAFETY: the arguments are the ones Windows passed in.
+    unsafe { DefWindowProcW(window, message, wparam, lparam) }
+}
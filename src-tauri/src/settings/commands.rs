@@ -0,0 +1,25 @@
+use serde_json::Value;
+use tauri::{AppHandle, Emitter, State};
+
+use super::{Settings, SettingsStore, CHANGED_EVENT};
+use crate::error::Result;
+use crate::logging;
+
+#[tauri::command]
+pub async fn get_settings(store: State<'_, SettingsStore>) -> Result<Settings> {
+    Ok(store.get())
+}
+
+/// Applies a partial settings object, e.g. `{ "appearance": { "theme":
+/// "dark" } }`, and tells every window about the result.
+#[tauri::command]
+pub async fn update_settings(
+    app: AppHandle,
+    store: State<'_, SettingsStore>,
+    patch: Value,
+) -> Result<Settings> {
+    let settings = store.update(&patch)?;
+    logging::set_level(settings.general.log_level)?;
+    app.emit(CHANGED_EVENT, &settings)?;
+    Ok(settings)
+}
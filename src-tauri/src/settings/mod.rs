@@ -0,0 +1,361 @@
+pub mod commands;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::error::{Error, Result};
+use crate::{atomic, collation};
+
+/// Event broadcast to every window with the full [`Settings`] after they
+/// change.
+pub const CHANGED_EVENT: &str = "settings-changed";
+
+const SETTINGS_FILE: &str = "settings.json";
+/// Version of the file layout written by this build. Each step up has a
+/// matching entry in [`MIGRATIONS`].
+const VERSION: u32 = 1;
+/// Upgrades a file from version `i` to `i + 1`.
+const MIGRATIONS: [fn(&mut Map<String, Value>); VERSION as usize] = [nest_flat_keys];
+
+const FONT_SIZES: std::ops::RangeInclusive<u32> = 8..=48;
+/// Shortest background sync interval; neither iOS nor Android run
+/// background work more often.
+const MIN_BACKGROUND_MINUTES: u32 = 15;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Theme {
+    #[default]
+    System,
+    Light,
+    Dark,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Appearance {
+    pub theme: Theme,
+    /// Editor text size in points.
+    pub font_size: u32,
+    /// CSS font family for the editor; the app's own when unset.
+    pub font_family: Option<String>,
+    /// Id of the custom theme in `themes/`, over the built-in look.
+    pub custom_theme: Option<String>,
+}
+
+impl Default for Appearance {
+    fn default() -> Self {
+        Self {
+            theme: Theme::System,
+            font_size: 15,
+            font_family: None,
+            custom_theme: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Editor {
+    pub spell_check: bool,
+    pub line_numbers: bool,
+    pub wrap_lines: bool,
+    /// Folder new notes go into when none is picked.
+    pub default_folder_id: Option<String>,
+    /// Record where new notes are written, on devices that know.
+    pub geotag: bool,
+}
+
+impl Default for Editor {
+    fn default() -> Self {
+        Self {
+            spell_check: true,
+            line_numbers: false,
+            wrap_lines: true,
+            default_folder_id: None,
+            geotag: false,
+        }
+    }
+}
+
+/// How much goes into the logs, from least to most.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Error,
+    Warn,
+    #[default]
+    Info,
+    Debug,
+    Trace,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct General {
+    /// UI language as a BCP 47 tag; the system's when unset.
+    pub language: Option<String>,
+    /// Keep running in the tray once the last window is closed, rather
+    /// than quitting.
+    pub close_to_tray: bool,
+    pub log_level: LogLevel,
+}
+
+impl Default for General {
+    fn default() -> Self {
+        Self {
+            language: None,
+            close_to_tray: true,
+            log_level: LogLevel::Info,
+        }
+    }
+}
+
+/// How notes and folders are ordered wherever they are listed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Sorting {
+    /// Runs of digits by their value, so "note 2" comes before "note 10".
+    pub natural: bool,
+    /// BCP 47 locale whose alphabetical order to use; the UI language's,
+    /// then the system's, when unset.
+    pub locale: Option<String>,
+    /// Folders before notes in the folder tree, rather than mixed in by
+    /// name.
+    pub folders_first: bool,
+}
+
+impl Default for Sorting {
+    fn default() -> Self {
+        Self {
+            natural: true,
+            locale: None,
+            folders_first: true,
+        }
+    }
+}
+
+/// When the mobile apps sync in the background. The OS decides the exact
+/// time; these are the conditions it waits for.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct BackgroundSync {
+    pub enabled: bool,
+    /// Least time between two runs.
+    pub interval_minutes: u32,
+    /// Only while plugged in.
+    pub requires_charging: bool,
+    /// Not while the battery saver would be on.
+    pub requires_battery_not_low: bool,
+    /// Also over mobile data and other metered networks. Android only;
+    /// iOS does not tell background tasks apart by network.
+    pub allow_metered: bool,
+}
+
+impl Default for BackgroundSync {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            interval_minutes: 60,
+            requires_charging: false,
+            requires_battery_not_low: true,
+            allow_metered: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Channel {
+    #[default]
+    Stable,
+    /// Releases ahead of stable, for trying them out.
+    Beta,
+}
+
+/// How the desktop app keeps itself up to date.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Updates {
+    pub channel: Channel,
+    /// Check now and then and download what is found, leaving only the
+    /// restart to the user.
+    pub automatic: bool,
+}
+
+impl Default for Updates {
+    fn default() -> Self {
+        Self {
+            channel: Channel::Stable,
+            automatic: true,
+        }
+    }
+}
+
+/// App-wide preferences, shared by every window and vault.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Settings {
+    pub appearance: Appearance,
+    pub editor: Editor,
+    pub general: General,
+    pub background_sync: BackgroundSync,
+    pub sorting: Sorting,
+    pub updates: Updates,
+}
+
+impl Settings {
+    fn validate(&self) -> Result<()> {
+        if !FONT_SIZES.contains(&self.appearance.font_size) {
+            return Err(Error::InvalidInput(format!(
+                "font size must be between {} and {}",
+                FONT_SIZES.start(),
+                FONT_SIZES.end()
+            )));
+        }
+        if let Some(locale) = &self.sorting.locale {
+            collation::validate(locale)?;
+        }
+        if self.background_sync.interval_minutes < MIN_BACKGROUND_MINUTES {
+            return Err(Error::InvalidInput(format!(
+                "background sync cannot run more often than every {MIN_BACKGROUND_MINUTES} minutes"
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// The settings in `settings.json`, managed as app state. The file carries
+/// a `version`; older layouts are migrated on load and written back in the
+/// current one on the next change.
+pub struct SettingsStore {
+    file: PathBuf,
+    /// Set when the file comes from a newer build, whose fields this one
+    /// would drop if it wrote them back.
+    read_only: bool,
+    current: Mutex<Settings>,
+}
+
+impl SettingsStore {
+    pub fn load(config_dir: &Path) -> Result<Self> {
+        fs::create_dir_all(config_dir)?;
+        let file = config_dir.join(SETTINGS_FILE);
+        let (settings, read_only) = match fs::read_to_string(&file) {
+            Ok(json) => parse(&json)?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => (Settings::default(), false),
+            Err(err) => return Err(err.into()),
+        };
+        collation::configure(&settings);
+        Ok(Self {
+            file,
+            read_only,
+            current: Mutex::new(settings),
+        })
+    }
+
+    pub fn get(&self) -> Settings {
+        self.lock().clone()
+    }
+
+    /// Merges `patch` into the settings the way a JSON merge patch does:
+    /// objects merge key by key, `null` resets a key to its default, and
+    /// anything else replaces the value.
+    pub fn update(&self, patch: &Value) -> Result<Settings> {
+        if self.read_only {
+            return Err(Error::InvalidInput(
+                "settings were saved by a newer version of the app".into(),
+            ));
+        }
+        let mut current = self.lock();
+        let mut merged = serde_json::to_value(&*current)?;
+        merge(&mut merged, patch);
+        let updated: Settings = serde_json::from_value(merged)?;
+        updated.validate()?;
+        self.save(&updated)?;
+        collation::configure(&updated);
+        *current = updated.clone();
+        Ok(updated)
+    }
+
+    fn save(&self, settings: &Settings) -> Result<()> {
+        let mut json = serde_json::to_value(settings)?;
+        if let Value::Object(map) = &mut json {
+            map.insert("version".into(), VERSION.into());
+        }
+        atomic::write(&self.file, serde_json::to_string_pretty(&json)?.as_bytes())?;
+        Ok(())
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Settings> {
+        self.current.lock().expect("settings poisoned")
+    }
+}
+
+/// Reads a settings file of any version, saying whether it is newer than
+/// this build.
+fn parse(json: &str) -> Result<(Settings, bool)> {
+    let Value::Object(mut map) = serde_json::from_str(json)? else {
+        return Err(Error::InvalidInput("settings file is not an object".into()));
+    };
+    let version = map
+        .remove("version")
+        .and_then(|version| version.as_u64())
+        .unwrap_or(0);
+    for migrate in MIGRATIONS.iter().skip(version as usize) {
+        migrate(&mut map);
+    }
+    let settings = serde_json::from_value(Value::Object(map))?;
+    Ok((settings, version > u64::from(VERSION)))
+}
+
+fn merge(target: &mut Value, patch: &Value) {
+    let Value::Object(patch) = patch else {
+        *target = patch.clone();
+        return;
+    };
+    if !target.is_object() {
+        *target = Value::Object(Map::new());
+    }
+    let target = target.as_object_mut().expect("just made an object");
+    for (key, value) in patch {
+        if value.is_null() {
+            target.remove(key);
+        } else {
+            merge(target.entry(key.clone()).or_insert(Value::Null), value);
+        }
+    }
+}
+
+/// Version 0 was the flat object the webview kept in local storage and
+/// exported as is.
+fn nest_flat_keys(map: &mut Map<String, Value>) {
+    let moves = [
+        ("theme", "appearance", "theme"),
+        ("fontSize", "appearance", "fontSize"),
+        ("fontFamily", "appearance", "fontFamily"),
+        ("spellCheck", "editor", "spellCheck"),
+        ("lineNumbers", "editor", "lineNumbers"),
+        ("wordWrap", "editor", "wrapLines"),
+        ("defaultFolderId", "editor", "defaultFolderId"),
+        ("language", "general", "language"),
+        ("closeToTray", "general", "closeToTray"),
+    ];
+    if let Some(dark) = map.remove("darkMode").and_then(|dark| dark.as_bool()) {
+        map.entry("theme")
+            .or_insert_with(|| if dark { "dark" } else { "light" }.into());
+    }
+    for (old, section, key) in moves {
+        if let Some(value) = map.remove(old) {
+            let section = map
+                .entry(section)
+                .or_insert_with(|| Value::Object(Map::new()));
+            if let Value::Object(section) = section {
+                section.insert(key.into(), value);
+            }
+        }
+    }
+}
@@ -0,0 +1,129 @@
+//! A note as one self-contained HTML file, encrypted with a password. The
+//! page carries everything it needs: the ciphertext, its images inlined, and
+//! a script that decrypts it with the browser's WebCrypto, so it can be
+//! mailed or dropped anywhere and opened without the app.
+
+use std::fs;
+use std::path::Path;
+
+use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::{Aes256Gcm, Nonce};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use sha2::Sha256;
+
+use crate::error::{Error, Result};
+use crate::export::{epub, escape, render_markdown, Href, STYLE};
+use crate::files::NoteFiles;
+use crate::storage::Note;
+use crate::vault::Vault;
+use crate::{atomic, links, protected};
+
+/// PBKDF2-SHA256 rounds, as OWASP recommends. The page has to repeat them,
+/// so opening a bundle takes about as long in the browser.
+const ITERATIONS: u32 = 600_000;
+/// Images larger than this are left out rather than inlined.
+const MAX_IMAGE_BYTES: u64 = 10 * 1024 * 1024;
+
+/// Writes note `id` to `dest`, readable with `password`.
+pub fn export(vault: &Vault, id: &str, password: &str, dest: &Path) -> Result<()> {
+    if password.is_empty() {
+        return Err(Error::InvalidInput("a password is needed to share".into()));
+    }
+    let status = protected::status(vault, id)?;
+    if status.protected && !status.unlocked {
+        return Err(Error::NoteLocked(id.to_owned()));
+    }
+    let note = protected::read(vault, id)?;
+    let page = encrypt(password, content(vault, &note).as_bytes())?;
+    atomic::write(dest, wrapper(&note.title, &page).as_bytes())?;
+    Ok(())
+}
+
+/// The note as HTML, with its images as `data:` URLs. Other links into the
+/// vault lead nowhere outside it and are dropped.
+fn content(vault: &Vault, note: &Note) -> String {
+    let body = render_markdown(&note.body, |dest| {
+        if links::parse_note_url(dest).is_some() {
+            return Href::Drop;
+        }
+        match NoteFiles::resolve_from(&note.folder, dest) {
+            Some(rel) => inline(vault, &rel).map_or(Href::Drop, Href::Replace),
+            None => Href::Keep,
+        }
+    });
+    format!("<h1>{}</h1>\n{body}", escape(&note.title))
+}
+
+fn inline(vault: &Vault, rel: &str) -> Option<String> {
+    let media_type = epub::media_type(rel)?;
+    let path = vault.files.absolute(rel);
+    if fs::metadata(&path).ok()?.len() > MAX_IMAGE_BYTES {
+        return None;
+    }
+    let content = fs::read(path).ok()?;
+    Some(format!(
+        "data:{media_type};base64,{}",
+        BASE64.encode(content)
+    ))
+}
+
+/// What the page needs to decrypt: the salt, nonce and ciphertext.
+struct Sealed {
+    salt: [u8; 16],
+    nonce: [u8; 12],
+    ciphertext: Vec<u8>,
+}
+
+fn encrypt(password: &str, plaintext: &[u8]) -> Result<Sealed> {
+    let salt = rand::random::<[u8; 16]>();
+    let nonce = rand::random::<[u8; 12]>();
+    let mut key = [0u8; 32];
+    pbkdf2::pbkdf2_hmac::<Sha256>(password.as_bytes(), &salt, ITERATIONS, &mut key);
+    let cipher = Aes256Gcm::new_from_slice(&key).map_err(|err| Error::Crypto(err.to_string()))?;
+    let ciphertext = cipher
+        .encrypt(&Nonce::from(nonce), plaintext)
+        .map_err(|err| Error::Crypto(err.to_string()))?;
+    Ok(Sealed {
+        salt,
+        nonce,
+        ciphertext,
+    })
+}
+
+fn wrapper(title: &str, sealed: &Sealed) -> String {
+    // The title stays readable so the recipient knows what they got.
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n<title>{title}</title>\n<style>{STYLE}{PAGE_STYLE}</style>\n</head>\n<body>\n<form id=\"unlock\">\n<p>This note is encrypted. Enter the password to read it.</p>\n<input id=\"password\" type=\"password\" autocomplete=\"off\" autofocus>\n<button type=\"submit\">Open</button>\n<p id=\"error\" hidden>The password is wrong.</p>\n</form>\n<main id=\"note\"></main>\n<script>\nconst SEALED = {{ salt: \"{salt}\", nonce: \"{nonce}\", iterations: {ITERATIONS}, ciphertext: \"{ciphertext}\" }};\n{SCRIPT}</script>\n</body>\n</html>\n",
+        title = escape(title),
+        salt = BASE64.encode(sealed.salt),
+        nonce = BASE64.encode(sealed.nonce),
+        ciphertext = BASE64.encode(&sealed.ciphertext),
+    )
+}
+
+const PAGE_STYLE: &str = r#"
+body { max-width: 46em; margin: 2em auto; padding: 0 1em; }
+#unlock { display: flex; flex-wrap: wrap; gap: 0.5em; align-items: center; }
+#unlock p { flex-basis: 100%; margin: 0.25em 0; }
+#error { color: #cf222e; }
+"#;
+
+const SCRIPT: &str = r#"const bytes = (text) => Uint8Array.from(atob(text), (c) => c.charCodeAt(0));
+document.getElementById("unlock").addEventListener("submit", async (event) => {
+  event.preventDefault();
+  const password = new TextEncoder().encode(document.getElementById("password").value);
+  const base = await crypto.subtle.importKey("raw", password, "PBKDF2", false, ["deriveKey"]);
+  const key = await crypto.subtle.deriveKey(
+    { name: "PBKDF2", hash: "SHA-256", salt: bytes(SEALED.salt), iterations: SEALED.iterations },
+    base, { name: "AES-GCM", length: 256 }, false, ["decrypt"]);
+  try {
+    const plain = await crypto.subtle.decrypt(
+      { name: "AES-GCM", iv: bytes(SEALED.nonce) }, key, bytes(SEALED.ciphertext));
+    document.getElementById("note").innerHTML = new TextDecoder().decode(plain);
+    document.getElementById("unlock").remove();
+  } catch {
+    document.getElementById("error").hidden = false;
+  }
+});
+"#;
@@ -0,0 +1,80 @@
+use std::path::PathBuf;
+
+use tauri::{AppHandle, Manager, Window};
+
+use super::{bundle, qr, IncomingShare, ShareConfig, SharePayload, ShareTarget};
+use crate::attachments::Attachment;
+use crate::error::Result;
+use crate::export::commands::free_path;
+use crate::files;
+use crate::vaults::{self, Current, Vaults};
+
+/// Saves something shared into the app, the way the share sheet does. The
+/// mobile entry points go through the same path without the webview.
+#[tauri::command]
+pub async fn receive_share(app: AppHandle, payload: SharePayload) -> Result<IncomingShare> {
+    tauri::async_runtime::spawn_blocking(move || super::deliver(&app, &payload)).await?
+}
+
+/// Changes where shares go. Without arguments, returns the current
+/// settings.
+#[tauri::command]
+pub async fn configure_share(
+    app: AppHandle,
+    target: Option<ShareTarget>,
+    folder: Option<String>,
+) -> Result<ShareConfig> {
+    let vault = vaults::primary(&app)?;
+    let mut config = super::config_of(&vault.storage)?;
+    if let Some(target) = target {
+        config.target = target;
+    }
+    if let Some(folder) = folder {
+        config.folder = folder.trim_matches('/').to_owned();
+    }
+    super::set_config(&vault.storage, &config)?;
+    Ok(config)
+}
+
+/// Writes note `id` as an HTML file that opens in any browser given
+/// `password`, and returns its path. It goes to the downloads folder
+/// unless `path` is given.
+#[tauri::command]
+pub async fn share_note(
+    app: AppHandle,
+    vault: Current,
+    id: String,
+    password: String,
+    path: Option<String>,
+) -> Result<String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let dest = match path {
+            Some(path) => PathBuf::from(path),
+            None => {
+                let title = vault.storage.get_note(&id)?.title;
+                free_path(app.path().download_dir()?, &files::sanitize(&title), "html")
+            }
+        };
+        bundle::export(&vault, &id, &password, &dest)?;
+        Ok(dest.to_string_lossy().into_owned())
+    })
+    .await?
+}
+
+/// Attaches a QR code of a `notes://` link to note `id`. With `embed`, the
+/// link carries the note itself rather than opening it in the vault.
+#[tauri::command]
+pub async fn share_qr(
+    app: AppHandle,
+    window: Window,
+    id: String,
+    embed: Option<bool>,
+) -> Result<Attachment> {
+    let vaults = app.state::<Vaults>();
+    let vault_id = vaults.id_of(window.label());
+    let vault = vaults.get(&app, &vault_id)?;
+    tauri::async_runtime::spawn_blocking(move || {
+        qr::attach(&vault, &vault_id, &id, embed.unwrap_or(false))
+    })
+    .await?
+}
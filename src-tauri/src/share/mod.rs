@@ -0,0 +1,241 @@
+pub mod bundle;
+pub mod commands;
+pub mod qr;
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter};
+
+use crate::error::{Error, Result};
+use crate::storage::{NotePatch, Storage};
+use crate::vault::Vault;
+use crate::{attachments, quick_capture, vaults};
+
+/// Event carrying an [`IncomingShare`] once something shared from another
+/// app has been saved.
+pub const INCOMING_EVENT: &str = "incoming-share";
+/// Event carrying the message of a share from the system share sheet that
+/// could not be saved.
+#[cfg(mobile)]
+pub const ERROR_EVENT: &str = "share-error";
+
+const CONFIG_KEY: &str = "share.config";
+const SHARED_TITLE: &str = "Shared";
+/// Longest title taken from the first line of shared text.
+const MAX_TITLE_CHARS: usize = 80;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ShareTarget {
+    /// Appended to the quick capture inbox.
+    #[default]
+    Inbox,
+    /// A new note per share.
+    NewNote,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ShareConfig {
+    pub target: ShareTarget,
+    /// Folder of notes made from shares.
+    pub folder: String,
+}
+
+/// One thing handed over by the system share sheet.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "kind")]
+pub enum SharedItem {
+    Text {
+        text: String,
+    },
+    Url {
+        url: String,
+        title: Option<String>,
+    },
+    Image {
+        name: String,
+        /// Base64 of the file.
+        data: String,
+    },
+}
+
+/// What the native side sends for one share.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SharePayload {
+    /// Subject line some apps send along, used as a title.
+    pub subject: Option<String>,
+    pub items: Vec<SharedItem>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IncomingShare {
+    pub note_id: String,
+    pub title: String,
+    /// Whether the share started a note of its own.
+    pub created: bool,
+}
+
+pub fn config_of(storage: &Storage) -> Result<ShareConfig> {
+    match storage.meta(CONFIG_KEY)? {
+        Some(json) => Ok(serde_json::from_str(&json)?),
+        None => Ok(ShareConfig::default()),
+    }
+}
+
+pub fn set_config(storage: &Storage, config: &ShareConfig) -> Result<()> {
+    storage.set_meta(CONFIG_KEY, &serde_json::to_string(config)?)
+}
+
+/// Saves a share as configured: appended to the inbox or as a new note.
+/// Images are imported as attachments and embedded. A share that cannot
+/// be saved leaves no new note behind.
+pub fn receive(vault: &Vault, payload: &SharePayload) -> Result<IncomingShare> {
+    if payload.items.is_empty() {
+        return Err(Error::InvalidInput("nothing was shared".into()));
+    }
+    let images = images(payload)?;
+    let config = config_of(&vault.storage)?;
+    let (note, created) = match config.target {
+        ShareTarget::Inbox => (quick_capture::inbox(vault)?, false),
+        ShareTarget::NewNote => {
+            let folder = config.folder.trim_matches('/');
+            (vault.create_note(&title_of(payload), "", folder)?, true)
+        }
+    };
+    let saved = markdown(vault, &note.id, payload, images).and_then(|body| match created {
+        true => vault.update_note(
+            &note.id,
+            NotePatch {
+                body: Some(body),
+                ..Default::default()
+            },
+        ),
+        false => quick_capture::append(vault, &body),
+    });
+    let note = match saved {
+        Ok(note) => note,
+        Err(err) => {
+            if created {
+                let _ = vault.delete_note(&note.id);
+            }
+            return Err(err);
+        }
+    };
+    Ok(IncomingShare {
+        note_id: note.id,
+        title: note.title,
+        created,
+    })
+}
+
+/// Saves a share to the primary vault and tells the windows.
+pub fn deliver(app: &AppHandle, payload: &SharePayload) -> Result<IncomingShare> {
+    let vault = vaults::primary(app)?;
+    let incoming = receive(&vault, payload)?;
+    let _ = app.emit(INCOMING_EVENT, incoming.clone());
+    Ok(incoming)
+}
+
+/// The files of the shared images in order, decoded before anything is
+/// saved.
+fn images(payload: &SharePayload) -> Result<Vec<Vec<u8>>> {
+    payload
+        .items
+        .iter()
+        .filter_map(|item| match item {
+            SharedItem::Image { name, data } => Some(
+                BASE64
+                    .decode(data)
+                    .map_err(|err| Error::InvalidInput(format!("shared image {name}: {err}"))),
+            ),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Markdown for the items of a share going into note `note_id`, with the
+/// [`images`] of the share.
+fn markdown(
+    vault: &Vault,
+    note_id: &str,
+    payload: &SharePayload,
+    images: Vec<Vec<u8>>,
+) -> Result<String> {
+    let mut images = images.into_iter();
+    let mut parts = Vec::new();
+    for item in &payload.items {
+        match item {
+            SharedItem::Text { text } => parts.push(text.trim().to_owned()),
+            SharedItem::Url { url, title } => parts.push(match title {
+                Some(title) if !title.trim().is_empty() => format!("[{}]({url})", title.trim()),
+                _ => format!("<{url}>"),
+            }),
+            SharedItem::Image { name, .. } => {
+                let content = images.next().unwrap_or_default();
+                let attachment = attachments::import(vault, note_id, name, &content)?;
+                parts.push(format!("![{name}]({})", attachment.link));
+            }
+        }
+    }
+    parts.retain(|part| !part.is_empty());
+    Ok(parts.join("\n\n"))
+}
+
+/// The subject, else the first line of shared text or a link's title.
+fn title_of(payload: &SharePayload) -> String {
+    let first = payload.items.iter().find_map(|item| match item {
+        SharedItem::Text { text } => text.lines().find(|line| !line.trim().is_empty()),
+        SharedItem::Url { title, url } => Some(title.as_deref().unwrap_or(url)),
+        SharedItem::Image { .. } => None,
+    });
+    let title = payload
+        .subject
+        .as_deref()
+        .filter(|subject| !subject.trim().is_empty())
+        .or(first)
+        .unwrap_or(SHARED_TITLE);
+    let title = title.trim().trim_start_matches('#').trim();
+    title.chars().take(MAX_TITLE_CHARS).collect()
+}
+
+/// Shares that reach the native entry points before the app is set up
+/// wait here.
+#[cfg(mobile)]
+mod handoff {
+    use std::sync::Mutex;
+
+    use tauri::Emitter;
+
+    use super::SharePayload;
+
+    static PENDING: Mutex<Vec<SharePayload>> = Mutex::new(Vec::new());
+
+    /// Called from setup once the app handle is known; saves whatever
+    /// arrived while the app launched.
+    pub fn start() {
+        let pending = std::mem::take(&mut *PENDING.lock().expect("shares poisoned"));
+        for payload in pending {
+            submit(payload);
+        }
+    }
+
+    /// Takes a share from the platform code in `mobile.rs`.
+    pub fn submit(payload: SharePayload) {
+        let Some(app) = crate::mobile::app() else {
+            PENDING.lock().expect("shares poisoned").push(payload);
+            return;
+        };
+        tauri::async_runtime::spawn_blocking(move || {
+            if let Err(err) = super::deliver(app, &payload) {
+                let _ = app.emit(super::ERROR_EVENT, err.to_string());
+            }
+        });
+    }
+}
+
+#[cfg(mobile)]
+pub use handoff::{start, submit};
@@ -0,0 +1,68 @@
+//! QR codes of `notes://` links, for carrying a note over to a phone. The
+//! code either opens the note in a synced copy of the vault or, for short
+//! notes, carries the note itself as a `notes://new` link.
+
+use std::io::Cursor;
+
+use image::{DynamicImage, ImageFormat, Luma};
+use qrcode::{EcLevel, QrCode};
+use url::Url;
+
+use crate::attachments::{self, Attachment};
+use crate::error::{Error, Result};
+use crate::protected;
+use crate::vault::Vault;
+
+/// Longest link put in a code. Version 40 holds more, but phone cameras
+/// struggle with anything much denser.
+const MAX_LINK_BYTES: usize = 1200;
+/// Smallest side of the image, in pixels.
+const MIN_SIZE: u32 = 512;
+
+/// The link a code for note `id` carries. With `embed`, the note's title,
+/// body and folder go in the link when they fit, so the phone needs no
+/// copy of the vault.
+pub fn link(vault: &Vault, vault_id: &str, id: &str, embed: bool) -> Result<String> {
+    let note = vault.storage.get_note(id)?;
+    if embed {
+        if protected::is_protected(&note.body) {
+            return Err(Error::InvalidInput(
+                "protected notes cannot be put in a QR code".into(),
+            ));
+        }
+        let mut url = Url::parse("notes://new").expect("valid link");
+        url.query_pairs_mut()
+            .append_pair("title", &note.title)
+            .append_pair("body", &note.body)
+            .append_pair("folder", &note.folder);
+        if url.as_str().len() > MAX_LINK_BYTES {
+            return Err(Error::InvalidInput(
+                "the note is too long to fit in a QR code".into(),
+            ));
+        }
+        return Ok(url.into());
+    }
+    let mut url = Url::parse(&format!("notes://open/{id}"))
+        .map_err(|err| Error::InvalidInput(format!("note id {id}: {err}")))?;
+    url.query_pairs_mut().append_pair("vault", vault_id);
+    Ok(url.into())
+}
+
+/// A PNG of the QR code for `link`.
+pub fn render(link: &str) -> Result<Vec<u8>> {
+    let code = QrCode::with_error_correction_level(link, EcLevel::M)
+        .map_err(|err| Error::InvalidInput(format!("cannot make a QR code: {err}")))?;
+    let image = code
+        .render::<Luma<u8>>()
+        .min_dimensions(MIN_SIZE, MIN_SIZE)
+        .build();
+    let mut out = Cursor::new(Vec::new());
+    DynamicImage::ImageLuma8(image).write_to(&mut out, ImageFormat::Png)?;
+    Ok(out.into_inner())
+}
+
+/// Renders the code for note `id` and attaches it to the note.
+pub fn attach(vault: &Vault, vault_id: &str, id: &str, embed: bool) -> Result<Attachment> {
+    let png = render(&link(vault, vault_id, id, embed)?)?;
+    attachments::import(vault, id, "qr.png", &png)
+}
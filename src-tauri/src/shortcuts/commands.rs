@@ -0,0 +1,63 @@
+use std::path::PathBuf;
+
+use tauri::{AppHandle, Emitter, State};
+
+use super::{Binding, Conflict, Shortcuts, CHANGED_EVENT};
+use crate::error::Result;
+
+#[tauri::command]
+pub async fn list_shortcuts(shortcuts: State<'_, Shortcuts>) -> Result<Vec<Binding>> {
+    Ok(shortcuts.list())
+}
+
+#[tauri::command]
+pub async fn shortcut_conflicts(shortcuts: State<'_, Shortcuts>) -> Result<Vec<Conflict>> {
+    Ok(shortcuts.conflicts())
+}
+
+/// Binds `action` to `accelerator`, e.g. `CommandOrControl+Shift+K`, or
+/// unbinds it with `null`.
+#[tauri::command]
+pub async fn set_shortcut(
+    app: AppHandle,
+    shortcuts: State<'_, Shortcuts>,
+    action: String,
+    accelerator: Option<String>,
+) -> Result<Vec<Binding>> {
+    super::rebind(&app, &action, accelerator.as_deref())?;
+    Ok(shortcuts.list())
+}
+
+/// Puts every action back on this platform's default.
+#[tauri::command]
+pub async fn reset_shortcuts(
+    app: AppHandle,
+    shortcuts: State<'_, Shortcuts>,
+) -> Result<Vec<Binding>> {
+    shortcuts.reset()?;
+    changed(&app, &shortcuts)
+}
+
+#[tauri::command]
+pub async fn export_keymap(shortcuts: State<'_, Shortcuts>, path: String) -> Result<()> {
+    shortcuts.export(&PathBuf::from(path))
+}
+
+#[tauri::command]
+pub async fn import_keymap(
+    app: AppHandle,
+    shortcuts: State<'_, Shortcuts>,
+    path: String,
+) -> Result<Vec<Binding>> {
+    shortcuts.import(&PathBuf::from(path))?;
+    changed(&app, &shortcuts)
+}
+
+/// Registers the global bindings again after many changed at once.
+fn changed(app: &AppHandle, shortcuts: &Shortcuts) -> Result<Vec<Binding>> {
+    let bindings = shortcuts.list();
+    app.emit(CHANGED_EVENT, &bindings)?;
+    #[cfg(desktop)]
+    super::register_all(app)?;
+    Ok(bindings)
+}
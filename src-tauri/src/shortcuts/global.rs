@@ -0,0 +1,82 @@
+use tauri::{AppHandle, Manager};
+use tauri_plugin_global_shortcut::{GlobalShortcutExt, Shortcut, ShortcutState};
+
+use super::{Shortcuts, ACTIONS, QUICK_CAPTURE, TOGGLE_WINDOW};
+use crate::error::{Error, Result};
+use crate::navigation::{self, MAIN_WINDOW};
+use crate::quick_capture;
+
+/// The global shortcut plugin, running whichever action the pressed
+/// shortcut is bound to.
+pub fn plugin() -> tauri::plugin::TauriPlugin<tauri::Wry> {
+    tauri_plugin_global_shortcut::Builder::new()
+        .with_handler(|app, shortcut, event| {
+            if event.state != ShortcutState::Pressed {
+                return;
+            }
+            let shortcuts = app.state::<Shortcuts>();
+            let pressed = ACTIONS
+                .iter()
+                .filter(|action| action.global)
+                .find(|action| {
+                    shortcuts
+                        .get(action.id)
+                        .and_then(|keys| keys.parse::<Shortcut>().ok())
+                        .is_some_and(|keys| keys.id() == shortcut.id())
+                });
+            let _ = match pressed.map(|action| action.id) {
+                Some(QUICK_CAPTURE) => quick_capture::toggle(app),
+                Some(TOGGLE_WINDOW) => toggle_main(app),
+                _ => Ok(()),
+            };
+        })
+        .build()
+}
+
+fn toggle_main(app: &AppHandle) -> Result<()> {
+    if let Some(window) = app.get_webview_window(MAIN_WINDOW) {
+        if window.is_visible()? && window.is_focused()? {
+            return Ok(window.hide()?);
+        }
+    }
+    navigation::show_main(app).map(drop)
+}
+
+/// Registers every global binding anew. All that can be are, even when
+/// another program holds one of the others.
+pub fn register_all(app: &AppHandle) -> Result<()> {
+    let global = app.global_shortcut();
+    let _ = global.unregister_all();
+    let shortcuts = app.state::<Shortcuts>();
+    let mut failed = None;
+    for action in ACTIONS.iter().filter(|action| action.global) {
+        let Some(keys) = shortcuts.get(action.id) else {
+            continue;
+        };
+        if let Err(err) = global.register(keys.as_str()) {
+            failed.get_or_insert(Error::InvalidInput(format!(
+                "cannot use shortcut {keys:?} for {}: {err}",
+                action.name
+            )));
+        }
+    }
+    failed.map_or(Ok(()), Err)
+}
+
+/// Swaps the registered shortcut from `old` to `new`, keeping `old` if
+/// `new` is malformed or taken by another program.
+pub(super) fn swap(app: &AppHandle, old: Option<&str>, new: Option<&str>) -> Result<()> {
+    let global = app.global_shortcut();
+    if let Some(old) = old {
+        let _ = global.unregister(old);
+    }
+    let Some(new) = new else {
+        return Ok(());
+    };
+    global.register(new).map_err(|err| {
+        if let Some(old) = old {
+            let _ = global.register(old);
+        }
+        Error::InvalidInput(format!("cannot use shortcut {new:?}: {err}"))
+    })
+}
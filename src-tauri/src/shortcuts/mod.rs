@@ -0,0 +1,414 @@
+//! Keyboard shortcuts: every action the app binds a key to, its default
+//! for this platform, and the user's changes, kept in `keymap.json` next
+//! to the settings. The window handles most bindings itself; the few that
+//! must work while the app is in the background are registered with the
+//! OS as global shortcuts.
+
+pub mod commands;
+#[cfg(desktop)]
+mod global;
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::atomic;
+use crate::error::{Error, Result};
+
+#[cfg(desktop)]
+pub use global::{plugin, register_all};
+
+/// Event carrying every [`Binding`] after one changes.
+pub const CHANGED_EVENT: &str = "shortcuts-changed";
+
+pub const QUICK_CAPTURE: &str = "quick-capture";
+pub const TOGGLE_WINDOW: &str = "toggle-window";
+
+const KEYMAP_FILE: &str = "keymap.json";
+
+struct Action {
+    id: &'static str,
+    name: &'static str,
+    /// Works from anywhere, not just while a window has focus.
+    global: bool,
+    default: Option<&'static str>,
+}
+
+const fn platform(mac: &'static str, other: &'static str) -> Option<&'static str> {
+    if cfg!(target_os = "macos") {
+        Some(mac)
+    } else {
+        Some(other)
+    }
+}
+
+const ACTIONS: &[Action] = &[
+    Action {
+        id: QUICK_CAPTURE,
+        name: "Quick capture",
+        global: true,
+        default: Some("CommandOrControl+Shift+Space"),
+    },
+    Action {
+        id: TOGGLE_WINDOW,
+        name: "Show or hide the app",
+        global: true,
+        default: platform("Command+Option+N", "Control+Alt+N"),
+    },
+    Action {
+        id: "new-note",
+        name: "New note",
+        global: false,
+        default: Some("CommandOrControl+N"),
+    },
+    Action {
+        id: "daily-note",
+        name: "Today's daily note",
+        global: false,
+        default: Some("CommandOrControl+D"),
+    },
+    Action {
+        id: "quick-switcher",
+        name: "Go to note",
+        global: false,
+        default: Some("CommandOrControl+P"),
+    },
+    Action {
+        id: "command-palette",
+        name: "Command palette",
+        global: false,
+        default: Some("CommandOrControl+Shift+P"),
+    },
+    Action {
+        id: "search",
+        name: "Search all notes",
+        global: false,
+        default: Some("CommandOrControl+Shift+F"),
+    },
+    Action {
+        id: "toggle-preview",
+        name: "Toggle preview",
+        global: false,
+        default: Some("CommandOrControl+E"),
+    },
+    Action {
+        id: "toggle-sidebar",
+        name: "Toggle sidebar",
+        global: false,
+        default: platform("Command+Control+S", "Control+\\"),
+    },
+    Action {
+        id: "next-note",
+        name: "Next note",
+        global: false,
+        default: platform("Command+Option+Down", "Control+PageDown"),
+    },
+    Action {
+        id: "previous-note",
+        name: "Previous note",
+        global: false,
+        default: platform("Command+Option+Up", "Control+PageUp"),
+    },
+    Action {
+        id: "delete-note",
+        name: "Move note to trash",
+        global: false,
+        default: platform("Command+Backspace", "Control+Delete"),
+    },
+    Action {
+        id: "redo",
+        name: "Redo",
+        global: false,
+        default: platform("Command+Shift+Z", "Control+Y"),
+    },
+    Action {
+        id: "settings",
+        name: "Settings",
+        global: false,
+        default: Some("CommandOrControl+,"),
+    },
+];
+
+fn action(id: &str) -> Result<&'static Action> {
+    ACTIONS
+        .iter()
+        .find(|action| action.id == id)
+        .ok_or_else(|| Error::InvalidInput(format!("no shortcut action {id}")))
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Binding {
+    pub action: String,
+    pub name: String,
+    /// What the action is bound to, or `None` if unbound.
+    pub accelerator: Option<String>,
+    pub default: Option<String>,
+    pub global: bool,
+}
+
+/// Actions bound to the same keys.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Conflict {
+    pub accelerator: String,
+    pub actions: Vec<String>,
+}
+
+/// A keymap as saved and exported: accelerators by action id, with `null`
+/// for an action left unbound.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Keymap {
+    #[serde(default)]
+    bindings: BTreeMap<String, Option<String>>,
+}
+
+/// The user's changes to the default bindings, managed as app state.
+pub struct Shortcuts {
+    file: PathBuf,
+    changed: Mutex<BTreeMap<String, Option<String>>>,
+}
+
+impl Shortcuts {
+    pub fn load(config_dir: &Path) -> Result<Self> {
+        let file = config_dir.join(KEYMAP_FILE);
+        let keymap: Keymap = match fs::read_to_string(&file) {
+            Ok(json) => serde_json::from_str(&json)?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Keymap::default(),
+            Err(err) => return Err(err.into()),
+        };
+        Ok(Self {
+            file,
+            changed: Mutex::new(keymap.bindings),
+        })
+    }
+
+    pub fn list(&self) -> Vec<Binding> {
+        let changed = self.lock();
+        ACTIONS
+            .iter()
+            .map(|action| Binding {
+                action: action.id.to_owned(),
+                name: action.name.to_owned(),
+                accelerator: bound(&changed, action),
+                default: action.default.map(str::to_owned),
+                global: action.global,
+            })
+            .collect()
+    }
+
+    /// Whether no keymap has been saved yet.
+    pub fn is_fresh(&self) -> bool {
+        !self.file.exists()
+    }
+
+    pub fn get(&self, id: &str) -> Option<String> {
+        action(id)
+            .ok()
+            .and_then(|action| bound(&self.lock(), action))
+    }
+
+    /// Actions bound to the same keys as another. Only possible when the
+    /// defaults of a newer build clash with the user's changes.
+    pub fn conflicts(&self) -> Vec<Conflict> {
+        conflicts(&self.lock())
+    }
+
+    /// Binds action `id` to `accelerator`, or unbinds it with `None`.
+    /// Fails if another action already has those keys.
+    pub fn set(&self, id: &str, accelerator: Option<&str>) -> Result<()> {
+        let action = action(id)?;
+        let mut changed = self.lock();
+        let mut next = changed.clone();
+        match accelerator.map(str::trim) {
+            Some(accelerator) if Some(accelerator) != action.default => {
+                next.insert(id.to_owned(), Some(accelerator.to_owned()));
+            }
+            Some(_) => {
+                next.remove(id);
+            }
+            None => {
+                next.insert(id.to_owned(), None);
+            }
+        }
+        if let Some(accelerator) = accelerator {
+            let wanted = chord(accelerator)?;
+            for other in ACTIONS.iter().filter(|other| other.id != id) {
+                let taken = bound(&next, other)
+                    .is_some_and(|keys| chord(&keys).ok() == Some(wanted.clone()));
+                if taken {
+                    return Err(Error::InvalidInput(format!(
+                        "{accelerator} is already bound to {}",
+                        other.name
+                    )));
+                }
+            }
+        }
+        self.save(&next)?;
+        *changed = next;
+        Ok(())
+    }
+
+    /// Puts every action back on its default.
+    pub fn reset(&self) -> Result<()> {
+        let mut changed = self.lock();
+        self.save(&BTreeMap::new())?;
+        changed.clear();
+        Ok(())
+    }
+
+    /// Writes every binding, changed or not, to `dest`.
+    pub fn export(&self, dest: &Path) -> Result<()> {
+        let changed = self.lock();
+        let keymap = Keymap {
+            bindings: ACTIONS
+                .iter()
+                .map(|action| (action.id.to_owned(), bound(&changed, action)))
+                .collect(),
+        };
+        atomic::write(dest, serde_json::to_string_pretty(&keymap)?.as_bytes())?;
+        Ok(())
+    }
+
+    /// Takes on the keymap exported to `src`. Actions it leaves out keep
+    /// their current binding; ones this build does not have are skipped.
+    pub fn import(&self, src: &Path) -> Result<()> {
+        let keymap: Keymap = serde_json::from_str(&fs::read_to_string(src)?)
+            .map_err(|err| Error::InvalidInput(format!("not a keymap: {err}")))?;
+        let mut changed = self.lock();
+        let mut next = changed.clone();
+        for (id, accelerator) in keymap.bindings {
+            let Ok(action) = action(&id) else {
+                continue;
+            };
+            if let Some(accelerator) = &accelerator {
+                chord(accelerator)?;
+            }
+            match accelerator.as_deref() == action.default {
+                true => next.remove(&id),
+                false => next.insert(id, accelerator),
+            };
+        }
+        if let Some(conflict) = conflicts(&next).first() {
+            return Err(Error::InvalidInput(format!(
+                "the keymap binds {} to more than one action",
+                conflict.accelerator
+            )));
+        }
+        self.save(&next)?;
+        *changed = next;
+        Ok(())
+    }
+
+    fn save(&self, changed: &BTreeMap<String, Option<String>>) -> Result<()> {
+        if let Some(dir) = self.file.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let keymap = Keymap {
+            bindings: changed.clone(),
+        };
+        atomic::write(
+            &self.file,
+            serde_json::to_string_pretty(&keymap)?.as_bytes(),
+        )?;
+        Ok(())
+    }
+
+    fn lock(&self) -> MutexGuard<'_, BTreeMap<String, Option<String>>> {
+        self.changed.lock().expect("shortcuts poisoned")
+    }
+}
+
+/// Binds action `id` to `accelerator`, or unbinds it, and moves its global
+/// shortcut along. The old binding stays if the OS will not take the new
+/// one.
+pub fn rebind(app: &AppHandle, id: &str, accelerator: Option<&str>) -> Result<()> {
+    let shortcuts = app.state::<Shortcuts>();
+    let old = shortcuts.get(id);
+    shortcuts.set(id, accelerator)?;
+    #[cfg(desktop)]
+    if action(id)?.global {
+        if let Err(err) = global::swap(app, old.as_deref(), accelerator) {
+            shortcuts.set(id, old.as_deref())?;
+            return Err(err);
+        }
+    }
+    #[cfg(mobile)]
+    drop(old);
+    app.emit(CHANGED_EVENT, shortcuts.list())?;
+    Ok(())
+}
+
+fn bound(changed: &BTreeMap<String, Option<String>>, action: &Action) -> Option<String> {
+    match changed.get(action.id) {
+        Some(accelerator) => accelerator.clone(),
+        None => action.default.map(str::to_owned),
+    }
+}
+
+fn conflicts(changed: &BTreeMap<String, Option<String>>) -> Vec<Conflict> {
+    let mut by_chord: BTreeMap<String, Conflict> = BTreeMap::new();
+    for action in ACTIONS {
+        let Some(accelerator) = bound(changed, action) else {
+            continue;
+        };
+        let Ok(chord) = chord(&accelerator) else {
+            continue;
+        };
+        by_chord
+            .entry(chord)
+            .or_insert_with(|| Conflict {
+                accelerator,
+                actions: Vec::new(),
+            })
+            .actions
+            .push(action.id.to_owned());
+    }
+    by_chord
+        .into_values()
+        .filter(|conflict| conflict.actions.len() > 1)
+        .collect()
+}
+
+/// `accelerator` spelled one way, so that `Ctrl+Shift+K` and
+/// `shift+control+k` compare equal: its modifiers in a fixed order, then
+/// its key. `CommandOrControl` stands for whichever this platform uses.
+fn chord(accelerator: &str) -> Result<String> {
+    let invalid = || Error::InvalidInput(format!("not a shortcut: {accelerator:?}"));
+    let mut modifiers = [false; 4];
+    let mut key = None;
+    for part in accelerator.split('+').map(str::trim) {
+        let modifier = match part.to_lowercase().as_str() {
+            "commandorcontrol" | "commandorctrl" | "cmdorcontrol" | "cmdorctrl" => {
+                match cfg!(target_os = "macos") {
+                    true => 3,
+                    false => 0,
+                }
+            }
+            "control" | "ctrl" => 0,
+            "alt" | "option" => 1,
+            "shift" => 2,
+            "command" | "cmd" | "super" | "meta" => 3,
+            "" => return Err(invalid()),
+            _ => {
+                if key.replace(part.to_lowercase()).is_some() {
+                    return Err(invalid());
+                }
+                continue;
+            }
+        };
+        modifiers[modifier] = true;
+    }
+    let key = key.ok_or_else(invalid)?;
+    let mut chord: Vec<&str> = ["control", "alt", "shift", "super"]
+        .into_iter()
+        .zip(modifiers)
+        .filter_map(|(name, held)| held.then_some(name))
+        .collect();
+    chord.push(&key);
+    Ok(chord.join("+"))
+}
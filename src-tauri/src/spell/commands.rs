@@ -0,0 +1,45 @@
+use tauri::{AppHandle, Manager, State};
+
+use super::{Dictionaries, DictionaryInfo, SpellCheck};
+use crate::error::Result;
+use crate::vaults::Current;
+
+/// Misspelled words in `text` with suggestions. `lang` is a dictionary
+/// name like `en_US` or a language like `de`; without it the text's
+/// language is detected.
+#[tauri::command]
+pub async fn check_text(
+    app: AppHandle,
+    vault: Current,
+    text: String,
+    lang: Option<String>,
+) -> Result<SpellCheck> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let dictionaries = app.state::<Dictionaries>();
+        super::check(&dictionaries, &vault.storage, &text, lang.as_deref())
+    })
+    .await?
+}
+
+#[tauri::command]
+pub async fn list_dictionaries(
+    dictionaries: State<'_, Dictionaries>,
+) -> Result<Vec<DictionaryInfo>> {
+    Ok(dictionaries.available())
+}
+
+/// Adds `word` to the vault's dictionary. Returns all its words.
+#[tauri::command]
+pub async fn add_word(vault: Current, word: String) -> Result<Vec<String>> {
+    super::add_word(&vault.storage, &word)
+}
+
+#[tauri::command]
+pub async fn remove_word(vault: Current, word: String) -> Result<Vec<String>> {
+    super::remove_word(&vault.storage, &word)
+}
+
+#[tauri::command]
+pub async fn list_words(vault: Current) -> Result<Vec<String>> {
+    super::words(&vault.storage)
+}
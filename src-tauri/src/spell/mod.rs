@@ -0,0 +1,398 @@
+//! Spell checking with Hunspell dictionaries, so it behaves the same in
+//! every webview. Dictionaries come from the app's `dictionaries` folder
+//! and the places systems keep them; words added to a vault are kept in
+//! the vault.
+
+pub mod commands;
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use pulldown_cmark::{Event, LinkType, Options, Parser, Tag, TagEnd};
+use serde::Serialize;
+use spellbook::Dictionary;
+
+use crate::error::{Error, Result};
+use crate::metadata::FrontMatter;
+use crate::storage::Storage;
+
+/// Folder in the app data directory for dictionaries of one's own, each an
+/// `.aff` and `.dic` pair named by language, like `en_US.aff`.
+pub const DICTIONARIES_DIR: &str = "dictionaries";
+
+const WORDS_KEY: &str = "spell.words";
+const MAX_SUGGESTIONS: usize = 5;
+/// Misspellings past this many come without suggestions, which are the
+/// slow part.
+const MAX_SUGGESTED: usize = 100;
+/// Used when the language of a text cannot be told.
+const FALLBACK: &str = "en";
+const SYSTEM_DIRS: &[&str] = &[
+    "/usr/share/hunspell",
+    "/usr/share/myspell",
+    "/usr/share/myspell/dicts",
+    "/usr/local/share/hunspell",
+    "/opt/homebrew/share/hunspell",
+    "/Library/Spelling",
+];
+/// ISO 639-3 codes from language detection with the 639-1 codes Hunspell
+/// dictionaries are named by.
+const LANGUAGES: &[(&str, &str)] = &[
+    ("afr", "af"),
+    ("bul", "bg"),
+    ("cat", "ca"),
+    ("ces", "cs"),
+    ("dan", "da"),
+    ("deu", "de"),
+    ("ell", "el"),
+    ("eng", "en"),
+    ("epo", "eo"),
+    ("est", "et"),
+    ("fin", "fi"),
+    ("fra", "fr"),
+    ("hrv", "hr"),
+    ("hun", "hu"),
+    ("ind", "id"),
+    ("ita", "it"),
+    ("lav", "lv"),
+    ("lit", "lt"),
+    ("nld", "nl"),
+    ("nob", "nb"),
+    ("pol", "pl"),
+    ("por", "pt"),
+    ("ron", "ro"),
+    ("rus", "ru"),
+    ("slk", "sk"),
+    ("slv", "sl"),
+    ("spa", "es"),
+    ("srp", "sr"),
+    ("swe", "sv"),
+    ("tur", "tr"),
+    ("ukr", "uk"),
+    ("vie", "vi"),
+];
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpellCheck {
+    /// The dictionary used, like `en_US`.
+    pub language: String,
+    /// Whether `language` was detected rather than asked for.
+    pub detected: bool,
+    pub misspellings: Vec<Misspelling>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Misspelling {
+    /// Offsets into the text in UTF-16 code units, as JavaScript counts.
+    pub start: usize,
+    pub end: usize,
+    pub word: String,
+    pub suggestions: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DictionaryInfo {
+    pub language: String,
+    pub path: PathBuf,
+}
+
+/// The dictionaries found and those loaded so far, managed as app state.
+pub struct Dictionaries {
+    dirs: Vec<PathBuf>,
+    loaded: Mutex<HashMap<String, Arc<Dictionary>>>,
+}
+
+impl Dictionaries {
+    pub fn new(app_dir: &Path) -> Self {
+        let mut dirs = vec![app_dir.join(DICTIONARIES_DIR)];
+        if let Some(home) = dirs::home_dir() {
+            dirs.push(home.join("Library").join("Spelling"));
+        }
+        dirs.extend(SYSTEM_DIRS.iter().map(PathBuf::from));
+        Self {
+            dirs,
+            loaded: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn user_dir(&self) -> &Path {
+        &self.dirs[0]
+    }
+
+    /// Every language with both files somewhere, the app's own folder
+    /// winning over the system's.
+    pub fn available(&self) -> Vec<DictionaryInfo> {
+        let mut found: BTreeMap<String, PathBuf> = BTreeMap::new();
+        for dir in &self.dirs {
+            let Ok(entries) = fs::read_dir(dir) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                let path = entry.path();
+                let (Some(stem), Some("aff")) = (
+                    path.file_stem().and_then(|stem| stem.to_str()),
+                    path.extension().and_then(|ext| ext.to_str()),
+                ) else {
+                    continue;
+                };
+                if path.with_extension("dic").is_file() {
+                    found.entry(stem.to_owned()).or_insert(path.clone());
+                }
+            }
+        }
+        found
+            .into_iter()
+            .map(|(language, path)| DictionaryInfo { language, path })
+            .collect()
+    }
+
+    /// The dictionary for `language`, either a full name like `en_US` or
+    /// `en-us`, or just `en`, which prefers `en_US` over other regions.
+    fn find(&self, language: &str) -> Option<DictionaryInfo> {
+        let wanted = language.trim().replace('-', "_").to_lowercase();
+        let available = self.available();
+        let named = |name: &str| {
+            available
+                .iter()
+                .find(|info| info.language.to_lowercase() == name)
+                .cloned()
+        };
+        if let Some(info) = named(&wanted) {
+            return Some(info);
+        }
+        let preferred = match wanted.as_str() {
+            "en" => "en_us".to_owned(),
+            lang => format!("{lang}_{lang}"),
+        };
+        named(&preferred).or_else(|| {
+            available.into_iter().find(|info| {
+                info.language
+                    .to_lowercase()
+                    .starts_with(&format!("{wanted}_"))
+            })
+        })
+    }
+
+    fn load(&self, language: &str) -> Result<(String, Arc<Dictionary>)> {
+        let info = self.find(language).ok_or_else(|| {
+            Error::InvalidInput(format!(
+                "no {language} dictionary; put its Hunspell .aff and .dic files in {}",
+                self.user_dir().display()
+            ))
+        })?;
+        if let Some(dictionary) = self.lock().get(&info.language) {
+            return Ok((info.language, Arc::clone(dictionary)));
+        }
+        let aff = fs::read_to_string(&info.path)?;
+        let dic = fs::read_to_string(info.path.with_extension("dic"))?;
+        let dictionary = Dictionary::new(&aff, &dic).map_err(|err| {
+            Error::InvalidInput(format!("{} is damaged: {err}", info.path.display()))
+        })?;
+        let dictionary = Arc::new(dictionary);
+        self.lock()
+            .insert(info.language.clone(), Arc::clone(&dictionary));
+        Ok((info.language, dictionary))
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<String, Arc<Dictionary>>> {
+        self.loaded.lock().expect("dictionaries poisoned")
+    }
+}
+
+/// Words the vault's notes use that no dictionary has, sorted.
+pub fn words(storage: &Storage) -> Result<Vec<String>> {
+    match storage.meta(WORDS_KEY)? {
+        Some(json) => Ok(serde_json::from_str(&json)?),
+        None => Ok(Vec::new()),
+    }
+}
+
+pub fn add_word(storage: &Storage, word: &str) -> Result<Vec<String>> {
+    let word = word.trim();
+    if word.is_empty() || word.chars().any(char::is_whitespace) {
+        return Err(Error::InvalidInput(format!("not a single word: {word:?}")));
+    }
+    let mut list = words(storage)?;
+    if !list.iter().any(|w| w == word) {
+        list.push(word.to_owned());
+        list.sort_by_key(|w| w.to_lowercase());
+        storage.set_meta(WORDS_KEY, &serde_json::to_string(&list)?)?;
+    }
+    Ok(list)
+}
+
+pub fn remove_word(storage: &Storage, word: &str) -> Result<Vec<String>> {
+    let mut list = words(storage)?;
+    list.retain(|w| w != word.trim());
+    storage.set_meta(WORDS_KEY, &serde_json::to_string(&list)?)?;
+    Ok(list)
+}
+
+/// Checks the prose of `text`, a note body: code, links' destinations,
+/// front matter, URLs, tags and words with digits are left alone. With no
+/// `language`, the text's own is detected.
+pub fn check(
+    dictionaries: &Dictionaries,
+    storage: &Storage,
+    text: &str,
+    language: Option<&str>,
+) -> Result<SpellCheck> {
+    let (detected, language) = match language.map(str::trim).filter(|l| !l.is_empty()) {
+        Some(language) => (false, language.to_owned()),
+        None => (true, detect(text).unwrap_or(FALLBACK).to_owned()),
+    };
+    let (language, dictionary) = dictionaries.load(&language)?;
+    let custom: HashSet<String> = words(storage)?
+        .into_iter()
+        .map(|word| word.to_lowercase())
+        .collect();
+
+    let mut misspellings = Vec::new();
+    let mut utf16 = Utf16Offsets::new(text);
+    for range in prose_words(text) {
+        let word = &text[range.clone()];
+        let known = dictionary.check(word)
+            || custom.contains(&word.to_lowercase())
+            // Curly apostrophes are missing from most dictionaries.
+            || (word.contains('’') && dictionary.check(&word.replace('’', "'")));
+        if known {
+            continue;
+        }
+        let mut suggestions = Vec::new();
+        if misspellings.len() < MAX_SUGGESTED {
+            dictionary.suggest(word, &mut suggestions);
+            suggestions.truncate(MAX_SUGGESTIONS);
+        }
+        misspellings.push(Misspelling {
+            start: utf16.at(range.start),
+            end: utf16.at(range.end),
+            word: word.to_owned(),
+            suggestions,
+        });
+    }
+    Ok(SpellCheck {
+        language,
+        detected,
+        misspellings,
+    })
+}
+
+/// The 639-1 code of the language `text` is in, if it can be told.
+fn detect(text: &str) -> Option<&'static str> {
+    let (_, markdown) = FrontMatter::split(text);
+    let info = whatlang::detect(markdown).filter(|info| info.is_reliable())?;
+    let code = info.lang().code();
+    LANGUAGES
+        .iter()
+        .find(|(iso3, _)| *iso3 == code)
+        .map(|(_, iso1)| *iso1)
+}
+
+/// Byte ranges of the words to check in a note body.
+fn prose_words(body: &str) -> Vec<Range<usize>> {
+    let (_, markdown) = FrontMatter::split(body);
+    let offset = body.len() - markdown.len();
+    let options = Options::ENABLE_TABLES
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_TASKLISTS
+        | Options::ENABLE_FOOTNOTES;
+    let mut words = Vec::new();
+    let mut skipping = 0usize;
+    for (event, range) in Parser::new_ext(markdown, options).into_offset_iter() {
+        match event {
+            Event::Start(
+                Tag::CodeBlock(_)
+                | Tag::Link {
+                    link_type: LinkType::Autolink | LinkType::Email,
+                    ..
+                },
+            ) => skipping += 1,
+            Event::End(TagEnd::CodeBlock) => skipping = skipping.saturating_sub(1),
+            Event::End(TagEnd::Link) if skipping > 0 => skipping -= 1,
+            Event::Text(_) if skipping == 0 => {
+                scan(&markdown[range.clone()], offset + range.start, &mut words)
+            }
+            _ => {}
+        }
+    }
+    words
+}
+
+/// Adds the words of `text`, which starts at byte `base` of the body.
+fn scan(text: &str, base: usize, words: &mut Vec<Range<usize>>) {
+    let mut start = 0;
+    for chunk in text.split_inclusive(char::is_whitespace) {
+        let at = start;
+        start += chunk.len();
+        if chunk.contains("://") || chunk.starts_with("www.") || chunk.contains('@') {
+            continue;
+        }
+        // Where the current word starts, and whether it is a tag or mention.
+        let mut word: Option<(usize, bool)> = None;
+        let proper = |w: &str| {
+            w.chars().count() > 1
+                && !w.chars().any(|c| c.is_ascii_digit() || c == '_')
+                && !w.chars().all(|c| c.is_uppercase() || !c.is_alphabetic())
+        };
+        let chars: Vec<(usize, char)> = chunk.char_indices().collect();
+        for (i, &(pos, c)) in chars.iter().enumerate() {
+            // An apostrophe belongs to a word only between letters, as in "don't".
+            let inner = matches!(c, '\'' | '’')
+                && word.is_some()
+                && chars
+                    .get(i + 1)
+                    .is_some_and(|(_, next)| next.is_alphabetic());
+            let part = c.is_alphanumeric() || c == '_' || inner;
+            match (word, part) {
+                (None, true) => {
+                    word = Some((pos, i > 0 && matches!(chars[i - 1].1, '#' | '@')));
+                }
+                (Some((from, marked)), false) => {
+                    if !marked && proper(&chunk[from..pos]) {
+                        words.push(base + at + from..base + at + pos);
+                    }
+                    word = None;
+                }
+                _ => {}
+            }
+        }
+        if let Some((from, false)) = word {
+            if proper(&chunk[from..]) {
+                words.push(base + at + from..base + at + chunk.len());
+            }
+        }
+    }
+}
+
+/// Turns ascending byte offsets into UTF-16 ones in a single pass.
+struct Utf16Offsets<'a> {
+    text: &'a str,
+    byte: usize,
+    unit: usize,
+}
+
+impl<'a> Utf16Offsets<'a> {
+    fn new(text: &'a str) -> Self {
+        Self {
+            text,
+            byte: 0,
+            unit: 0,
+        }
+    }
+
+    fn at(&mut self, byte: usize) -> usize {
+        if byte < self.byte {
+            self.byte = 0;
+            self.unit = 0;
+        }
+        self.unit += self.text[self.byte..byte].encode_utf16().count();
+        self.byte = byte;
+        self.unit
+    }
+}
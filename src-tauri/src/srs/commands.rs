@@ -0,0 +1,55 @@
+use super::{DeckStats, Grade, SrsConfig, StudyCard};
+use crate::error::Result;
+use crate::storage::now_millis;
+use crate::vaults::Current;
+
+/// Cards shown in one study session when no limit is given.
+const DEFAULT_LIMIT: usize = 100;
+
+/// Cards to study now, optionally from just one deck.
+#[tauri::command]
+pub async fn due_cards(
+    vault: Current,
+    deck: Option<String>,
+    limit: Option<usize>,
+) -> Result<Vec<StudyCard>> {
+    tauri::async_runtime::spawn_blocking(move || {
+        super::due_cards(
+            &vault.storage,
+            &vault.cards,
+            deck.as_deref(),
+            limit.unwrap_or(DEFAULT_LIMIT),
+            now_millis(),
+        )
+    })
+    .await?
+}
+
+/// Grades an answer and returns the card with its next review.
+#[tauri::command]
+pub async fn review_card(vault: Current, id: String, grade: Grade) -> Result<StudyCard> {
+    tauri::async_runtime::spawn_blocking(move || {
+        super::review(&vault.storage, &vault.cards, &id, grade, now_millis())
+    })
+    .await?
+}
+
+#[tauri::command]
+pub async fn deck_stats(vault: Current) -> Result<Vec<DeckStats>> {
+    tauri::async_runtime::spawn_blocking(move || {
+        super::deck_stats(&vault.storage, &vault.cards, now_millis())
+    })
+    .await?
+}
+
+/// Changes how many new cards come up a day. Without arguments, returns
+/// the current settings.
+#[tauri::command]
+pub async fn configure_srs(vault: Current, new_per_day: Option<usize>) -> Result<SrsConfig> {
+    let mut config = super::config_of(&vault.storage)?;
+    if let Some(new_per_day) = new_per_day {
+        config.new_per_day = new_per_day;
+    }
+    super::set_config(&vault.storage, &config)?;
+    Ok(config)
+}
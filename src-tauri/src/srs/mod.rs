@@ -0,0 +1,621 @@
+pub mod commands;
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use chrono::{Local, TimeZone};
+use rusqlite::{params, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::error::{Error, Result};
+use crate::metadata::FrontMatter;
+use crate::storage::{Note, Storage};
+
+const CONFIG_KEY: &str = "srs.config";
+const DAY_MS: i64 = 24 * 60 * 60 * 1000;
+/// A card answered wrong comes back this soon, within the same session.
+const RELEARN_MS: i64 = 10 * 60 * 1000;
+const START_EASE: f64 = 2.5;
+const MIN_EASE: f64 = 1.3;
+const MAX_INTERVAL_DAYS: u32 = 100 * 365;
+/// Cards spaced this many days apart or more count as mature.
+const MATURE_DAYS: u32 = 21;
+/// Callout kinds that make a card: `> [!question] Front`.
+const CALLOUTS: [&str; 3] = ["question", "flashcard", "card"];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SrsConfig {
+    /// Cards seen for the first time per day, across decks.
+    pub new_per_day: usize,
+}
+
+impl Default for SrsConfig {
+    fn default() -> Self {
+        Self { new_per_day: 20 }
+    }
+}
+
+/// A flashcard found in a note.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Card {
+    /// Stays the same while the question does, so editing the answer keeps
+    /// the card's schedule.
+    pub id: String,
+    pub note_id: String,
+    pub note_title: String,
+    /// The note's `deck` front matter, else its folder.
+    pub deck: String,
+    /// 1-based line where the card starts, front matter included.
+    pub line: usize,
+    pub question: String,
+    pub answer: String,
+}
+
+/// When a card comes up next, by SM-2.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Schedule {
+    pub ease: f64,
+    /// Days until the next review; 0 while relearning a card just missed.
+    pub interval_days: u32,
+    /// Correct answers in a row.
+    pub repetitions: u32,
+    pub lapses: u32,
+    pub due_at: i64,
+    pub reviewed_at: i64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Grade {
+    /// Not remembered; the card starts over.
+    Again,
+    Hard,
+    Good,
+    Easy,
+}
+
+impl Grade {
+    /// SM-2's 0 to 5 response quality.
+    fn quality(self) -> f64 {
+        match self {
+            Grade::Again => 1.0,
+            Grade::Hard => 3.0,
+            Grade::Good => 4.0,
+            Grade::Easy => 5.0,
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Grade::Again => "again",
+            Grade::Hard => "hard",
+            Grade::Good => "good",
+            Grade::Easy => "easy",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CardState {
+    New,
+    Learning,
+    Young,
+    Mature,
+}
+
+/// A card with where it stands.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StudyCard {
+    #[serde(flatten)]
+    pub card: Card,
+    pub state: CardState,
+    /// `None` for a card never reviewed.
+    pub schedule: Option<Schedule>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeckStats {
+    pub deck: String,
+    pub total: usize,
+    pub new: usize,
+    pub learning: usize,
+    pub young: usize,
+    pub mature: usize,
+    /// Reviewed cards that are due now.
+    pub due: usize,
+    pub reviewed_today: usize,
+    /// Share of today's reviews not graded "again", from 0 to 1.
+    pub retention_today: Option<f64>,
+}
+
+/// Cards of every note, kept in memory like [`crate::tasks::TaskIndex`]
+/// since they come from note bodies. Their schedules are in the database.
+#[derive(Default)]
+pub struct CardIndex {
+    notes: RwLock<HashMap<String, Vec<Card>>>,
+}
+
+impl CardIndex {
+    pub fn index_note(&self, note: &Note) {
+        let cards = extract(note);
+        let mut notes = self.write();
+        if cards.is_empty() {
+            notes.remove(&note.id);
+        } else {
+            notes.insert(note.id.clone(), cards);
+        }
+    }
+
+    pub fn remove_note(&self, id: &str) {
+        self.write().remove(id);
+    }
+
+    pub fn rebuild(&self, notes: &[Note]) {
+        let index = notes
+            .iter()
+            .map(|note| (note.id.clone(), extract(note)))
+            .filter(|(_, cards)| !cards.is_empty())
+            .collect();
+        *self.write() = index;
+    }
+
+    pub fn clear(&self) {
+        self.write().clear();
+    }
+
+    fn get(&self, id: &str) -> Option<Card> {
+        self.read()
+            .values()
+            .flatten()
+            .find(|card| card.id == id)
+            .cloned()
+    }
+
+    /// Every card, in note title and line order.
+    fn all(&self) -> Vec<Card> {
+        let mut cards: Vec<Card> = self.read().values().flatten().cloned().collect();
+        cards.sort_by(|a, b| {
+            (a.note_title.to_lowercase(), &a.note_id, a.line).cmp(&(
+                b.note_title.to_lowercase(),
+                &b.note_id,
+                b.line,
+            ))
+        });
+        cards
+    }
+
+    fn read(&self) -> RwLockReadGuard<'_, HashMap<String, Vec<Card>>> {
+        self.notes.read().expect("card index poisoned")
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, HashMap<String, Vec<Card>>> {
+        self.notes.write().expect("card index poisoned")
+    }
+}
+
+pub fn config_of(storage: &Storage) -> Result<SrsConfig> {
+    match storage.meta(CONFIG_KEY)? {
+        Some(json) => Ok(serde_json::from_str(&json)?),
+        None => Ok(SrsConfig::default()),
+    }
+}
+
+pub fn set_config(storage: &Storage, config: &SrsConfig) -> Result<()> {
+    storage.set_meta(CONFIG_KEY, &serde_json::to_string(config)?)
+}
+
+/// Cards to study now, optionally from one deck: reviews that are due,
+/// longest overdue first, then new cards up to what is left of today's
+/// allowance.
+pub fn due_cards(
+    storage: &Storage,
+    index: &CardIndex,
+    deck: Option<&str>,
+    limit: usize,
+    now: i64,
+) -> Result<Vec<StudyCard>> {
+    let schedules = schedules(storage)?;
+    let mut due = Vec::new();
+    let mut new = Vec::new();
+    for card in index.all() {
+        if deck.is_some_and(|deck| card.deck != deck) {
+            continue;
+        }
+        match schedules.get(&card.id) {
+            Some(schedule) if schedule.due_at <= now => due.push(study(card, Some(schedule))),
+            Some(_) => {}
+            None => new.push(study(card, None)),
+        }
+    }
+    due.sort_by_key(|card| card.schedule.as_ref().map(|s| s.due_at));
+    let allowance = config_of(storage)?
+        .new_per_day
+        .saturating_sub(new_today(storage, now)?);
+    due.extend(new.into_iter().take(allowance));
+    due.truncate(limit);
+    Ok(due)
+}
+
+/// Records an answer to card `id` and schedules its next review.
+pub fn review(
+    storage: &Storage,
+    index: &CardIndex,
+    id: &str,
+    grade: Grade,
+    now: i64,
+) -> Result<StudyCard> {
+    let card = index
+        .get(id)
+        .ok_or_else(|| Error::InvalidInput(format!("no card {id}")))?;
+    let previous = schedule_of(storage, id)?;
+    let next = next_schedule(previous.as_ref(), grade, now);
+    let mut conn = storage.conn();
+    let tx = conn.transaction()?;
+    tx.execute(
+        "INSERT INTO srs_cards
+             (card_id, note_id, ease, interval_days, repetitions, lapses, due_at, reviewed_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+         ON CONFLICT(card_id) DO UPDATE SET
+             note_id = ?2, ease = ?3, interval_days = ?4, repetitions = ?5,
+             lapses = ?6, due_at = ?7, reviewed_at = ?8",
+        params![
+            card.id,
+            card.note_id,
+            next.ease,
+            next.interval_days,
+            next.repetitions,
+            next.lapses,
+            next.due_at,
+            next.reviewed_at
+        ],
+    )?;
+    tx.execute(
+        "INSERT INTO srs_reviews (card_id, note_id, grade, reviewed_at) VALUES (?1, ?2, ?3, ?4)",
+        params![card.id, card.note_id, grade.as_str(), now],
+    )?;
+    tx.commit()?;
+    Ok(study(card, Some(&next)))
+}
+
+/// Counts of each deck, by name.
+pub fn deck_stats(storage: &Storage, index: &CardIndex, now: i64) -> Result<Vec<DeckStats>> {
+    let schedules = schedules(storage)?;
+    let mut decks: BTreeMap<String, DeckStats> = BTreeMap::new();
+    let mut deck_of = HashMap::new();
+    for card in index.all() {
+        let stats = decks.entry(card.deck.clone()).or_insert_with(|| DeckStats {
+            deck: card.deck.clone(),
+            ..Default::default()
+        });
+        stats.total += 1;
+        let schedule = schedules.get(&card.id);
+        match state_of(schedule) {
+            CardState::New => stats.new += 1,
+            CardState::Learning => stats.learning += 1,
+            CardState::Young => stats.young += 1,
+            CardState::Mature => stats.mature += 1,
+        }
+        stats.due += usize::from(schedule.is_some_and(|s| s.due_at <= now));
+        deck_of.insert(card.id, card.deck);
+    }
+
+    let conn = storage.conn();
+    let mut stmt =
+        conn.prepare("SELECT card_id, grade FROM srs_reviews WHERE reviewed_at >= ?1")?;
+    let mut rows = stmt.query([start_of_day(now)])?;
+    let mut remembered: HashMap<String, usize> = HashMap::new();
+    while let Some(row) = rows.next()? {
+        let card_id: String = row.get(0)?;
+        let grade: String = row.get(1)?;
+        let Some(stats) = deck_of.get(&card_id).and_then(|deck| decks.get_mut(deck)) else {
+            continue;
+        };
+        stats.reviewed_today += 1;
+        if grade != Grade::Again.as_str() {
+            *remembered.entry(stats.deck.clone()).or_default() += 1;
+        }
+    }
+    for stats in decks.values_mut() {
+        if stats.reviewed_today > 0 {
+            let remembered = remembered.get(&stats.deck).copied().unwrap_or(0);
+            stats.retention_today = Some(remembered as f64 / stats.reviewed_today as f64);
+        }
+    }
+    Ok(decks.into_values().collect())
+}
+
+/// SM-2, with a missed card coming back within the session rather than
+/// the next day.
+fn next_schedule(previous: Option<&Schedule>, grade: Grade, now: i64) -> Schedule {
+    let (ease, interval, repetitions, lapses) = match previous {
+        Some(s) => (s.ease, s.interval_days, s.repetitions, s.lapses),
+        None => (START_EASE, 0, 0, 0),
+    };
+    let q = grade.quality();
+    let ease = (ease + 0.1 - (5.0 - q) * (0.08 + (5.0 - q) * 0.02)).max(MIN_EASE);
+    if grade == Grade::Again {
+        return Schedule {
+            ease,
+            interval_days: 0,
+            repetitions: 0,
+            lapses: lapses + u32::from(repetitions > 0),
+            due_at: now + RELEARN_MS,
+            reviewed_at: now,
+        };
+    }
+    let interval = match (repetitions, grade) {
+        (0, Grade::Easy) => 4,
+        (0, _) => 1,
+        (1, Grade::Hard) => 3,
+        (1, _) => 6,
+        (_, Grade::Hard) => (f64::from(interval) * 1.2).round() as u32,
+        (_, Grade::Easy) => (f64::from(interval) * ease * 1.3).round() as u32,
+        _ => (f64::from(interval) * ease).round() as u32,
+    }
+    .clamp(1, MAX_INTERVAL_DAYS);
+    Schedule {
+        ease,
+        interval_days: interval,
+        repetitions: repetitions + 1,
+        lapses,
+        due_at: now + i64::from(interval) * DAY_MS,
+        reviewed_at: now,
+    }
+}
+
+fn study(card: Card, schedule: Option<&Schedule>) -> StudyCard {
+    StudyCard {
+        card,
+        state: state_of(schedule),
+        schedule: schedule.cloned(),
+    }
+}
+
+fn state_of(schedule: Option<&Schedule>) -> CardState {
+    match schedule.map(|s| s.interval_days) {
+        None => CardState::New,
+        Some(0) => CardState::Learning,
+        Some(days) if days < MATURE_DAYS => CardState::Young,
+        Some(_) => CardState::Mature,
+    }
+}
+
+const COLUMNS: &str = "card_id, ease, interval_days, repetitions, lapses, due_at, reviewed_at";
+
+fn schedule_from_row(row: &Row) -> rusqlite::Result<(String, Schedule)> {
+    Ok((
+        row.get(0)?,
+        Schedule {
+            ease: row.get(1)?,
+            interval_days: row.get(2)?,
+            repetitions: row.get(3)?,
+            lapses: row.get(4)?,
+            due_at: row.get(5)?,
+            reviewed_at: row.get(6)?,
+        },
+    ))
+}
+
+fn schedules(storage: &Storage) -> Result<HashMap<String, Schedule>> {
+    let conn = storage.conn();
+    let mut stmt = conn.prepare(&format!("SELECT {COLUMNS} FROM srs_cards"))?;
+    let schedules = stmt
+        .query_map([], schedule_from_row)?
+        .collect::<rusqlite::Result<_>>()?;
+    Ok(schedules)
+}
+
+fn schedule_of(storage: &Storage, id: &str) -> Result<Option<Schedule>> {
+    let schedule = storage
+        .conn()
+        .query_row(
+            &format!("SELECT {COLUMNS} FROM srs_cards WHERE card_id = ?1"),
+            [id],
+            schedule_from_row,
+        )
+        .optional()?;
+    Ok(schedule.map(|(_, schedule)| schedule))
+}
+
+/// Cards first reviewed today.
+fn new_today(storage: &Storage, now: i64) -> Result<usize> {
+    let count: i64 = storage.conn().query_row(
+        "SELECT COUNT(*) FROM
+             (SELECT MIN(reviewed_at) AS first FROM srs_reviews GROUP BY card_id)
+         WHERE first >= ?1",
+        [start_of_day(now)],
+        |row| row.get(0),
+    )?;
+    Ok(count as usize)
+}
+
+/// Local midnight before `now`.
+fn start_of_day(now: i64) -> i64 {
+    Local
+        .timestamp_millis_opt(now)
+        .single()
+        .and_then(|time| time.date_naive().and_hms_opt(0, 0, 0))
+        .and_then(|midnight| midnight.and_local_timezone(Local).earliest())
+        .map_or(now - now.rem_euclid(DAY_MS), |midnight| {
+            midnight.timestamp_millis()
+        })
+}
+
+/// The cards in a note: `Q:` lines followed by `A:` lines, and question
+/// callouts whose title is the question and whose body is the answer.
+/// Front matter and code blocks are skipped.
+pub fn extract(note: &Note) -> Vec<Card> {
+    let (front_matter, markdown) = FrontMatter::split(&note.body);
+    let deck = front_matter
+        .as_ref()
+        .and_then(|fm| fm.str("deck"))
+        .map(|deck| deck.trim().to_owned())
+        .filter(|deck| !deck.is_empty())
+        .unwrap_or_else(|| note.folder.clone());
+    let skipped = note.body[..note.body.len() - markdown.len()]
+        .matches('\n')
+        .count();
+
+    let mut lines = Vec::new();
+    let mut fence: Option<&str> = None;
+    for (i, line) in markdown.lines().enumerate() {
+        let trimmed = line.trim_start();
+        let marker = ["```", "~~~"]
+            .into_iter()
+            .find(|marker| trimmed.starts_with(marker));
+        match (fence, marker) {
+            (None, Some(marker)) => fence = Some(marker),
+            (Some(open), Some(marker)) if open == marker => fence = None,
+            _ => {}
+        }
+        // Code becomes a blank line, so it ends a card rather than joining
+        // the lines around it into one.
+        let line = if fence.is_some() || marker.is_some() {
+            ""
+        } else {
+            line
+        };
+        lines.push((skipped + i + 1, line));
+    }
+
+    let mut found = Vec::new();
+    let mut i = 0;
+    while i < lines.len() {
+        let (number, line) = lines[i];
+        if let Some(question) = strip_label(line, 'Q') {
+            let (card, next) = question_answer(&lines, i, question);
+            if let Some((question, answer)) = card {
+                found.push((number, question, answer));
+            }
+            i = next;
+        } else if let Some(title) = callout_title(line) {
+            let mut body = Vec::new();
+            i += 1;
+            while let Some(rest) = lines.get(i).and_then(|(_, line)| quoted(line)) {
+                body.push(rest);
+                i += 1;
+            }
+            let (question, answer) = match title.is_empty() {
+                false => (title.to_owned(), body.join("\n")),
+                true => {
+                    let (first, rest) = body.split_first().map_or(("", &[][..]), |(f, r)| (*f, r));
+                    (first.trim().to_owned(), rest.join("\n"))
+                }
+            };
+            if !question.is_empty() && !answer.trim().is_empty() {
+                found.push((number, question, answer.trim().to_owned()));
+            }
+        } else {
+            i += 1;
+        }
+    }
+
+    let mut seen = HashSet::new();
+    found
+        .into_iter()
+        .map(|(line, question, answer)| {
+            // A question asked twice in one note still needs two ids.
+            let mut id = card_id(&note.id, &question, 0);
+            let mut copy = 0;
+            while !seen.insert(id.clone()) {
+                copy += 1;
+                id = card_id(&note.id, &question, copy);
+            }
+            Card {
+                id,
+                note_id: note.id.clone(),
+                note_title: note.title.clone(),
+                deck: deck.clone(),
+                line,
+                question,
+                answer,
+            }
+        })
+        .collect()
+}
+
+/// A `Q:` card starting at `lines[start]`, and the index after it. The
+/// question runs until the `A:` line and the answer until a blank line or
+/// the next question.
+fn question_answer(
+    lines: &[(usize, &str)],
+    start: usize,
+    first: &str,
+) -> (Option<(String, String)>, usize) {
+    let mut question = vec![first.trim()];
+    let mut i = start + 1;
+    let answer_start = loop {
+        match lines.get(i) {
+            Some((_, line)) if strip_label(line, 'A').is_some() => break Some(i),
+            Some((_, line)) if !line.trim().is_empty() && strip_label(line, 'Q').is_none() => {
+                question.push(line.trim());
+                i += 1;
+            }
+            _ => break None,
+        }
+    };
+    let Some(answer_start) = answer_start else {
+        return (None, i);
+    };
+    let mut answer = vec![strip_label(lines[answer_start].1, 'A')
+        .unwrap_or_default()
+        .trim()];
+    i = answer_start + 1;
+    while let Some((_, line)) = lines.get(i) {
+        if line.trim().is_empty() || strip_label(line, 'Q').is_some() {
+            break;
+        }
+        answer.push(line.trim_end());
+        i += 1;
+    }
+    let question = question.join("\n");
+    let answer = answer.join("\n").trim().to_owned();
+    match question.is_empty() || answer.is_empty() {
+        true => (None, i),
+        false => (Some((question, answer)), i),
+    }
+}
+
+/// The text after `Q:` or `A:`, either case.
+fn strip_label(line: &str, label: char) -> Option<&str> {
+    let line = line.trim_start();
+    let mut chars = line.chars();
+    match (chars.next(), chars.next()) {
+        (Some(first), Some(':')) if first.eq_ignore_ascii_case(&label) => Some(&line[2..]),
+        _ => None,
+    }
+}
+
+/// The title of `> [!question] Title`, possibly empty, for the card
+/// callout kinds.
+fn callout_title(line: &str) -> Option<&str> {
+    let rest = quoted(line)?.trim_start().strip_prefix("[!")?;
+    let (kind, title) = rest.split_once(']')?;
+    if !CALLOUTS
+        .iter()
+        .any(|callout| kind.eq_ignore_ascii_case(callout))
+    {
+        return None;
+    }
+    // `+` and `-` only say whether the callout starts folded.
+    Some(title.trim_start_matches(['+', '-']).trim())
+}
+
+/// A blockquote line without its `>`.
+fn quoted(line: &str) -> Option<&str> {
+    let rest = line.trim_start().strip_prefix('>')?;
+    Some(rest.strip_prefix(' ').unwrap_or(rest))
+}
+
+fn card_id(note_id: &str, question: &str, copy: usize) -> String {
+    let normalized = question.split_whitespace().collect::<Vec<_>>().join(" ");
+    let digest = Sha256::digest(format!("{note_id}\n{normalized}\n{copy}").as_bytes());
+    hex::encode(&digest[..12])
+}
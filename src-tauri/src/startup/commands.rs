@@ -0,0 +1,41 @@
+use serde::Serialize;
+use tauri::State;
+
+use super::Span;
+use crate::error::Result;
+use crate::vaults::Vaults;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StartupReport {
+    /// Milliseconds from launch until the main window had loaded.
+    pub ready_ms: Option<f64>,
+    pub spans: Vec<Span>,
+    pub vaults: Vec<VaultWarmth>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VaultWarmth {
+    pub vault_id: String,
+    /// Its in-memory indexes are filled and its search index checked.
+    pub warm: bool,
+}
+
+/// The steps of this launch and how long each took, for telling why it
+/// was slow.
+#[tauri::command]
+pub async fn startup_report(vaults: State<'_, Vaults>) -> Result<StartupReport> {
+    Ok(StartupReport {
+        ready_ms: super::ready_ms(),
+        spans: super::spans(),
+        vaults: vaults
+            .open_vaults()
+            .into_iter()
+            .map(|(vault_id, vault)| VaultWarmth {
+                vault_id,
+                warm: vault.is_warm(),
+            })
+            .collect(),
+    })
+}
@@ -0,0 +1,83 @@
+//! Where the time goes while the app starts. Each step records a span
+//! measured from launch, so a slow start on a large vault can be pinned
+//! down from [`commands::startup_report`] without a profiler.
+//!
+//! Opening the database and the memory-mapped search index costs little.
+//! What used to hold up the first window was reading every note to fill
+//! the in-memory indexes; that now runs as a job after the vault opens,
+//! see [`crate::vault::Vault::warm`].
+
+pub mod commands;
+
+use std::sync::{LazyLock, Mutex};
+use std::time::Instant;
+
+use serde::Serialize;
+#[cfg(desktop)]
+use tauri::webview::{PageLoadEvent, PageLoadPayload};
+#[cfg(desktop)]
+use tauri::Webview;
+
+#[cfg(desktop)]
+use crate::navigation;
+
+/// Spans kept; vaults opened much later add theirs too, up to this many.
+const MAX_SPANS: usize = 256;
+
+static LAUNCHED: LazyLock<Instant> = LazyLock::new(Instant::now);
+static SPANS: Mutex<Vec<Span>> = Mutex::new(Vec::new());
+static READY: Mutex<Option<f64>> = Mutex::new(None);
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Span {
+    pub name: String,
+    /// Milliseconds from launch to the start of the step.
+    pub start_ms: f64,
+    pub duration_ms: f64,
+}
+
+/// Starts the clock spans are measured from. Called first thing.
+pub fn launched() {
+    LazyLock::force(&LAUNCHED);
+}
+
+/// Runs `f` as the step `name`.
+pub fn time<T>(name: &str, f: impl FnOnce() -> T) -> T {
+    let start = Instant::now();
+    let result = f();
+    let span = Span {
+        name: name.to_owned(),
+        start_ms: millis(start.saturating_duration_since(*LAUNCHED)),
+        duration_ms: millis(start.elapsed()),
+    };
+    let mut spans = SPANS.lock().expect("startup spans poisoned");
+    if spans.len() < MAX_SPANS {
+        spans.push(span);
+    }
+    result
+}
+
+/// Records when the main window first finished loading.
+#[cfg(desktop)]
+pub fn page_loaded(webview: &Webview, payload: &PageLoadPayload<'_>) {
+    if payload.event() != PageLoadEvent::Finished || webview.label() != navigation::MAIN_WINDOW {
+        return;
+    }
+    READY
+        .lock()
+        .expect("startup spans poisoned")
+        .get_or_insert_with(|| millis(LAUNCHED.elapsed()));
+}
+
+fn millis(duration: std::time::Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+pub(crate) fn spans() -> Vec<Span> {
+    SPANS.lock().expect("startup spans poisoned").clone()
+}
+
+pub(crate) fn ready_ms() -> Option<f64> {
+    *READY.lock().expect("startup spans poisoned")
+}
@@ -0,0 +1,17 @@
+use super::{NoteStats, VaultStats};
+use crate::error::Result;
+use crate::vaults::Current;
+
+#[tauri::command]
+pub async fn note_stats(vault: Current, id: String) -> Result<NoteStats> {
+    match vault.stats.note(&id) {
+        Some(stats) => Ok(stats),
+        // Trashed notes are left out of the index.
+        None => Ok(super::of_body(&vault.storage.get_note(&id)?.body)),
+    }
+}
+
+#[tauri::command]
+pub async fn vault_stats(vault: Current) -> Result<VaultStats> {
+    Ok(vault.stats.vault(vault.tags.counts()))
+}
@@ -0,0 +1,171 @@
+pub mod commands;
+
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use chrono::{DateTime, Local};
+use pulldown_cmark::{Event, Parser};
+use serde::Serialize;
+
+use crate::metadata::FrontMatter;
+use crate::storage::Note;
+use crate::tags::TagCount;
+
+/// Silent reading speed the estimate assumes.
+const WORDS_PER_MINUTE: usize = 200;
+const LARGEST_NOTES: usize = 10;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NoteStats {
+    pub words: usize,
+    /// Characters of text, without Markdown syntax or front matter.
+    pub characters: usize,
+    /// Rounded up, so any text takes at least a minute.
+    pub reading_minutes: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MonthCount {
+    /// `YYYY-MM`, in local time.
+    pub month: String,
+    pub created: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LargeNote {
+    pub id: String,
+    pub title: String,
+    pub words: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VaultStats {
+    pub notes: usize,
+    pub words: usize,
+    pub characters: usize,
+    pub reading_minutes: usize,
+    /// Notes created each month, oldest first, for months with any.
+    pub notes_per_month: Vec<MonthCount>,
+    /// The notes with the most words, largest first.
+    pub largest: Vec<LargeNote>,
+    /// Every tag by how many notes carry it, most used first.
+    pub tags: Vec<TagCount>,
+}
+
+struct Entry {
+    title: String,
+    created_at: i64,
+    stats: NoteStats,
+}
+
+/// Counts for every note, kept in memory like [`crate::tags::TagIndex`]
+/// and updated a note at a time as notes are saved, so the statistics
+/// page never has to read every body.
+#[derive(Default)]
+pub struct StatsIndex {
+    notes: RwLock<HashMap<String, Entry>>,
+}
+
+impl StatsIndex {
+    pub fn index_note(&self, note: &Note) {
+        self.write().insert(note.id.clone(), entry(note));
+    }
+
+    pub fn remove_note(&self, id: &str) {
+        self.write().remove(id);
+    }
+
+    pub fn rebuild(&self, notes: &[Note]) {
+        let index = notes
+            .iter()
+            .map(|note| (note.id.clone(), entry(note)))
+            .collect();
+        *self.write() = index;
+    }
+
+    pub fn clear(&self) {
+        self.write().clear();
+    }
+
+    pub fn note(&self, id: &str) -> Option<NoteStats> {
+        self.read().get(id).map(|entry| entry.stats.clone())
+    }
+
+    /// Totals over the whole vault. `tags` comes from the tag index, which
+    /// already keeps those counts.
+    pub fn vault(&self, mut tags: Vec<TagCount>) -> VaultStats {
+        let notes = self.read();
+        let mut months: BTreeMap<String, usize> = BTreeMap::new();
+        let (mut words, mut characters) = (0, 0);
+        for entry in notes.values() {
+            words += entry.stats.words;
+            characters += entry.stats.characters;
+            if let Some(created) = DateTime::from_timestamp_millis(entry.created_at) {
+                let month = created.with_timezone(&Local).format("%Y-%m").to_string();
+                *months.entry(month).or_default() += 1;
+            }
+        }
+        let mut largest: Vec<(&String, &Entry)> = notes.iter().collect();
+        largest.sort_by_key(|(_, entry)| Reverse(entry.stats.words));
+        tags.sort_by_key(|tag| Reverse(tag.count));
+        VaultStats {
+            notes: notes.len(),
+            words,
+            characters,
+            reading_minutes: words.div_ceil(WORDS_PER_MINUTE),
+            notes_per_month: months
+                .into_iter()
+                .map(|(month, created)| MonthCount { month, created })
+                .collect(),
+            largest: largest
+                .into_iter()
+                .take(LARGEST_NOTES)
+                .map(|(id, entry)| LargeNote {
+                    id: id.clone(),
+                    title: entry.title.clone(),
+                    words: entry.stats.words,
+                })
+                .collect(),
+            tags,
+        }
+    }
+
+    fn read(&self) -> RwLockReadGuard<'_, HashMap<String, Entry>> {
+        self.notes.read().expect("stats index poisoned")
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, HashMap<String, Entry>> {
+        self.notes.write().expect("stats index poisoned")
+    }
+}
+
+/// Counts the text of `body` as a reader sees it: Markdown syntax, link
+/// targets and front matter are left out, code is counted.
+pub fn of_body(body: &str) -> NoteStats {
+    let (_, markdown) = FrontMatter::split(body);
+    let (mut words, mut characters) = (0, 0);
+    for event in Parser::new(markdown) {
+        if let Event::Text(text) | Event::Code(text) = event {
+            words += text.split_whitespace().count();
+            characters += text.chars().count();
+        }
+    }
+    NoteStats {
+        words,
+        characters,
+        reading_minutes: words.div_ceil(WORDS_PER_MINUTE),
+    }
+}
+
+fn entry(note: &Note) -> Entry {
+    Entry {
+        title: note.title.clone(),
+        created_at: note.created_at,
+        stats: of_body(&note.body),
+    }
+}
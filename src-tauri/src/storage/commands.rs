@@ -0,0 +1,201 @@
+use std::collections::HashSet;
+
+use super::{
+    BodyRange, FolderNode, LimitedNote, Note, NotePage, NotePatch, NoteQuery, NoteSummary,
+};
+use tauri::{AppHandle, Manager};
+
+use crate::activity::{self, ActivityKind};
+use crate::error::Result;
+use crate::settings::SettingsStore;
+use crate::vaults::Current;
+use crate::{places, protected, recents, tags};
+
+/// Creates a note, with where it was written in its front matter when
+/// geotagging is on and the device can tell.
+#[tauri::command]
+pub async fn create_note(
+    app: AppHandle,
+    vault: Current,
+    title: String,
+    body: Option<String>,
+    folder: Option<String>,
+) -> Result<Note> {
+    let mut body = body.unwrap_or_default();
+    if app.state::<SettingsStore>().get().editor.geotag {
+        let location = tauri::async_runtime::spawn_blocking({
+            let app = app.clone();
+            move || places::here(&app)
+        })
+        .await?;
+        if let Some(location) = location {
+            body = places::with_location(&body, location);
+        }
+    }
+    vault.create_note(&title, &body, folder.as_deref().unwrap_or_default())
+}
+
+/// Note `id`. With `bodyLimit`, only that many characters of the body come
+/// along, and `truncated` says so; huge notes can then be filled in with
+/// [`read_note_range`]. Saving one is refused until the rest was read.
+#[tauri::command]
+pub async fn get_note(
+    vault: Current,
+    id: String,
+    body_limit: Option<usize>,
+) -> Result<LimitedNote> {
+    let note = LimitedNote::of(protected::read(&vault, &id)?, body_limit);
+    match note.truncated {
+        true => vault.partial.cut(&id),
+        false => vault.partial.filled(&id),
+    }
+    recents::visited(&vault.storage, &id)?;
+    activity::record(&vault.storage, &id, ActivityKind::Opened)?;
+    Ok(note)
+}
+
+/// `len` characters of note `id`'s body from `offset`, for reading a huge
+/// note a piece at a time.
+#[tauri::command]
+pub async fn read_note_range(
+    vault: Current,
+    id: String,
+    offset: usize,
+    len: usize,
+) -> Result<BodyRange> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let range = match protected::status(&vault, &id)?.protected {
+            true => BodyRange::of(&protected::read(&vault, &id)?.body, offset, len),
+            false => vault.storage.body_range(&id, offset, len)?,
+        };
+        if offset.saturating_add(len) >= range.total {
+            vault.partial.filled(&id);
+        }
+        Ok(range)
+    })
+    .await?
+}
+
+/// Saves the editor's copy of a note, creating it if it has no id yet.
+/// Returns once the write is on disk. Pass `baseUpdatedAt`, the
+/// `updatedAt` the editor loaded, to have edits made elsewhere in the
+/// meantime caught as a conflict rather than overwritten.
+#[tauri::command]
+pub async fn save_note(
+    vault: Current,
+    id: Option<String>,
+    title: String,
+    body: String,
+    folder: Option<String>,
+    base_updated_at: Option<i64>,
+) -> Result<Note> {
+    tauri::async_runtime::spawn_blocking(move || {
+        if let Some(id) = &id {
+            vault.partial.check(id)?;
+        }
+        vault.save_note(
+            id.as_deref(),
+            &title,
+            &body,
+            folder.as_deref().unwrap_or_default(),
+            base_updated_at,
+        )
+    })
+    .await?
+}
+
+#[tauri::command]
+pub async fn update_note(vault: Current, id: String, patch: NotePatch) -> Result<Note> {
+    if patch.body.is_some() {
+        vault.partial.check(&id)?;
+    }
+    vault.edit_note(&id, patch)
+}
+
+#[tauri::command]
+pub async fn list_notes(
+    vault: Current,
+    folder: Option<String>,
+    include_archived: Option<bool>,
+) -> Result<Vec<NoteSummary>> {
+    let mut notes = vault.storage.list_notes(folder.as_deref())?;
+    if !include_archived.unwrap_or(false) {
+        notes.retain(|note| !note.archived);
+    }
+    Ok(notes)
+}
+
+/// The sidebar's folder tree, with every note outside the trash in it.
+#[tauri::command]
+pub async fn folder_tree(vault: Current, include_archived: Option<bool>) -> Result<FolderNode> {
+    vault.storage.folder_tree(include_archived.unwrap_or(false))
+}
+
+/// Archives a note, or with `archived: false` brings it back.
+#[tauri::command]
+pub async fn archive_note(vault: Current, id: String, archived: Option<bool>) -> Result<()> {
+    vault.storage.set_archived(&id, archived.unwrap_or(true))
+}
+
+#[tauri::command]
+pub async fn set_read_only(vault: Current, id: String, read_only: bool) -> Result<()> {
+    vault.storage.set_read_only(&id, read_only)
+}
+
+/// Renames, and with `folder` moves, a note. With `updateLinks`, links to
+/// it elsewhere that read its old title are retitled; with `keepAlias`, the
+/// old title stays on as an alias. Returns every note that changed.
+#[tauri::command]
+pub async fn rename_note(
+    vault: Current,
+    id: String,
+    new_title: String,
+    folder: Option<String>,
+    update_links: Option<bool>,
+    keep_alias: Option<bool>,
+) -> Result<Vec<Note>> {
+    tauri::async_runtime::spawn_blocking(move || {
+        vault.rename_note(
+            &id,
+            &new_title,
+            folder.as_deref(),
+            update_links.unwrap_or(true),
+            keep_alias.unwrap_or(false),
+        )
+    })
+    .await?
+}
+
+/// One page of notes, sorted and filtered as asked. Large vaults should
+/// page through this rather than call [`list_notes`].
+#[tauri::command]
+pub async fn query_notes(vault: Current, mut query: NoteQuery) -> Result<NotePage> {
+    let mut ids: Option<HashSet<String>> = None;
+    for tag in &query.filter.tags {
+        let tagged: HashSet<String> = vault
+            .tags
+            .notes_with(&tags::normalize(tag)?)
+            .into_iter()
+            .collect();
+        ids = Some(match ids {
+            Some(ids) => &ids & &tagged,
+            None => tagged,
+        });
+    }
+    for filter in &query.filter.fields {
+        let matching = vault.fields.matching(filter);
+        ids = Some(match ids {
+            Some(ids) => &ids & &matching,
+            None => matching,
+        });
+    }
+    query.ids = ids.map(|ids| ids.into_iter().collect());
+    vault.storage.query_notes(&query)
+}
+
+/// Moves the note to the trash, from which it is purged after the
+/// retention period.
+#[tauri::command]
+pub async fn delete_note(vault: Current, id: String) -> Result<()> {
+    vault.trash_note(&id)
+}
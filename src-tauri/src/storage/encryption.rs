@@ -0,0 +1,234 @@
+use rusqlite::{params, Connection, OptionalExtension};
+use zeroize::Zeroizing;
+
+use super::Storage;
+use crate::crypto::{self, KeyParams, VaultKey};
+use crate::error::{Error, Result};
+use crate::history;
+
+const PARAMS_KEY: &str = "crypto.params";
+const CHECK_KEY: &str = "crypto.check";
+/// Sealed with the vault key so a password can be verified without touching notes.
+const CHECK_PLAINTEXT: &str = "notesdesktop vault key check";
+
+/// Every `(table, column)` holding text that goes through [`Storage::seal_body`].
+const SEALED_COLUMNS: &[(&str, &str)] = &[
+    ("notes", "body"),
+    ("blobs", "content"),
+    ("secrets", "value"),
+    ("attachment_text", "text"),
+    ("embeddings", "vector"),
+    ("reminders", "message"),
+    ("reminders", "task"),
+    ("collab_docs", "state"),
+    ("folder_locks", "check_value"),
+];
+/// Every `(table, column)` holding bytes that go through [`Storage::seal_bytes`].
+const SEALED_BINARY_COLUMNS: &[(&str, &str)] = &[("blocks", "data")];
+
+pub(super) fn load_params(conn: &Connection) -> Result<Option<KeyParams>> {
+    let json: Option<String> = conn
+        .query_row(
+            "SELECT value FROM meta WHERE key = ?1",
+            [PARAMS_KEY],
+            |row| row.get(0),
+        )
+        .optional()?;
+    json.map(|json| {
+        serde_json::from_str(&json)
+            .map_err(|err| Error::Crypto(format!("corrupt key parameters: {err}")))
+    })
+    .transpose()
+}
+
+impl Storage {
+    pub fn is_encrypted(&self) -> bool {
+        self.crypto().params.is_some()
+    }
+
+    pub fn is_locked(&self) -> bool {
+        let crypto = self.crypto();
+        crypto.params.is_some() && crypto.key.is_none()
+    }
+
+    /// Sets or changes the vault password, re-sealing every column in
+    /// [`SEALED_COLUMNS`] and [`SEALED_BINARY_COLUMNS`] with the new key
+    /// in a single transaction, and filing history under hashes keyed with
+    /// it. Changing an existing password requires the vault to be unlocked.
+    pub fn set_password(&self, password: &str) -> Result<()> {
+        if password.is_empty() {
+            return Err(Error::InvalidInput(
+                "vault password must not be empty".into(),
+            ));
+        }
+        if self.is_locked() {
+            return Err(Error::VaultLocked);
+        }
+        let was_plain = !self.is_encrypted();
+
+        let params = KeyParams::generate();
+        let key = params.derive_key(password)?;
+
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+        history::rehash(&tx, self, &|data| key.digest(data))?;
+        for (table, column) in SEALED_COLUMNS {
+            let mut select = tx.prepare(&format!(
+                "SELECT rowid, {column} FROM {table} WHERE {column} IS NOT NULL"
+            ))?;
+            let rows = select
+                .query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get(1)?)))?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            let mut update = tx.prepare(&format!(
+                "UPDATE {table} SET {column} = ?2 WHERE rowid = ?1"
+            ))?;
+            for (rowid, stored) in rows {
+                let plain = self.open_body(stored)?;
+                update.execute(params![rowid, key.seal(&plain)?])?;
+            }
+        }
+        for (table, column) in SEALED_BINARY_COLUMNS {
+            let mut select = tx.prepare(&format!("SELECT rowid, {column} FROM {table}"))?;
+            let rows = select
+                .query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get(1)?)))?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            let mut update = tx.prepare(&format!(
+                "UPDATE {table} SET {column} = ?2 WHERE rowid = ?1"
+            ))?;
+            for (rowid, stored) in rows {
+                let plain = self.open_bytes(stored)?;
+                update.execute(params![rowid, key.seal_bytes(&plain)?])?;
+            }
+        }
+        let params_json =
+            serde_json::to_string(&params).map_err(|err| Error::Crypto(err.to_string()))?;
+        tx.execute(
+            "INSERT OR REPLACE INTO meta (key, value) VALUES (?1, ?2)",
+            params![PARAMS_KEY, params_json],
+        )?;
+        tx.execute(
+            "INSERT OR REPLACE INTO meta (key, value) VALUES (?1, ?2)",
+            params![CHECK_KEY, key.seal(CHECK_PLAINTEXT)?],
+        )?;
+        tx.commit()?;
+
+        if was_plain {
+            // Rewrite the file so no page still holds a plaintext body.
+            conn.execute_batch("VACUUM; PRAGMA wal_checkpoint(TRUNCATE);")?;
+        }
+        drop(conn);
+
+        let mut crypto = self.crypto_mut();
+        crypto.params = Some(params);
+        crypto.key = Some(key);
+        Ok(())
+    }
+
+    /// Derives the key from `password` and keeps it in memory until [`Storage::lock`].
+    pub fn unlock(&self, password: &str) -> Result<()> {
+        let params = self
+            .crypto()
+            .params
+            .clone()
+            .ok_or_else(|| Error::InvalidInput("vault is not encrypted".into()))?;
+        self.unlock_with_key(params.derive_key(password)?)
+    }
+
+    /// Unlocks with a key kept elsewhere, such as the OS keychain. Fails
+    /// with [`Error::WrongPassword`] if it is not this vault's key.
+    pub fn unlock_with_key(&self, key: VaultKey) -> Result<()> {
+        if !self.is_encrypted() {
+            return Err(Error::InvalidInput("vault is not encrypted".into()));
+        }
+        let check: String = self.conn().query_row(
+            "SELECT value FROM meta WHERE key = ?1",
+            [CHECK_KEY],
+            |row| row.get(0),
+        )?;
+        if !crypto::is_sealed(&check) || key.open(&check)? != CHECK_PLAINTEXT {
+            return Err(Error::WrongPassword);
+        }
+
+        self.crypto_mut().key = Some(key);
+        self.seal_reminders()
+    }
+
+    /// Reminders were kept in the clear before they were sealed; seals any
+    /// left from then.
+    fn seal_reminders(&self) -> Result<()> {
+        let conn = self.conn();
+        let mut select = conn.prepare("SELECT rowid, message, task FROM reminders")?;
+        let rows = select
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, Option<String>>(2)?,
+                ))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        for (rowid, message, task) in rows {
+            let clear = |text: &str| !crypto::is_sealed(text);
+            if !clear(&message) && !task.as_deref().is_some_and(clear) {
+                continue;
+            }
+            let task = task.map(|task| self.seal_existing(task)).transpose()?;
+            conn.execute(
+                "UPDATE reminders SET message = ?2, task = ?3 WHERE rowid = ?1",
+                params![rowid, self.seal_existing(message)?, task],
+            )?;
+        }
+        Ok(())
+    }
+
+    /// `stored` sealed, if it is not already.
+    fn seal_existing(&self, stored: String) -> Result<String> {
+        match crypto::is_sealed(&stored) {
+            true => Ok(stored),
+            false => self.seal_body(&stored),
+        }
+    }
+
+    /// Re-reads the key parameters after the database was replaced from a
+    /// backup. The key is kept only if it still opens the restored check.
+    pub(super) fn reload_crypto(&self) -> Result<()> {
+        let (params, check) = {
+            let conn = self.conn();
+            let check: Option<String> = conn
+                .query_row(
+                    "SELECT value FROM meta WHERE key = ?1",
+                    [CHECK_KEY],
+                    |row| row.get(0),
+                )
+                .optional()?;
+            (load_params(&conn)?, check)
+        };
+        let mut crypto = self.crypto_mut();
+        let key_fits = match (&crypto.key, check) {
+            (Some(key), Some(check)) => {
+                crypto::is_sealed(&check)
+                    && key.open(&check).is_ok_and(|text| text == CHECK_PLAINTEXT)
+            }
+            _ => false,
+        };
+        crypto.params = params;
+        if !key_fits {
+            crypto.key = None;
+        }
+        Ok(())
+    }
+
+    /// Forgets the vault key. It is zeroized as it is dropped.
+    pub fn lock(&self) {
+        self.crypto_mut().key = None;
+    }
+
+    /// A copy of the key in memory, for [`crate::security`] to remember.
+    pub fn key_bytes(&self) -> Result<Zeroizing<[u8; 32]>> {
+        match &self.crypto().key {
+            Some(key) => Ok(key.to_bytes()),
+            None if self.is_encrypted() => Err(Error::VaultLocked),
+            None => Err(Error::InvalidInput("vault is not encrypted".into())),
+        }
+    }
+}
@@ -0,0 +1,304 @@
+use rusqlite::Connection;
+
+use crate::error::{Error, Result};
+
+/// Schema migrations, applied in order. The database's `user_version` records
+/// how many of them have run, so entries must only ever be appended.
+const MIGRATIONS: &[&str] = &[
+    // 1: notes
+    "CREATE TABLE notes (
+        id          TEXT PRIMARY KEY,
+        title       TEXT NOT NULL,
+        body        TEXT NOT NULL DEFAULT '',
+        folder      TEXT NOT NULL DEFAULT '',
+        created_at  INTEGER NOT NULL,
+        updated_at  INTEGER NOT NULL
+    );
+    CREATE INDEX notes_folder ON notes(folder);
+    CREATE INDEX notes_updated_at ON notes(updated_at);",
+    // 2: vault-wide key/value metadata
+    "CREATE TABLE meta (
+        key    TEXT PRIMARY KEY,
+        value  TEXT NOT NULL
+    );",
+    // 3: revision history over content-addressed blobs
+    "CREATE TABLE blobs (
+        hash     TEXT PRIMARY KEY,
+        content  TEXT NOT NULL
+    );
+    CREATE TABLE revisions (
+        note_id     TEXT NOT NULL REFERENCES notes(id) ON DELETE CASCADE,
+        rev         INTEGER NOT NULL,
+        title       TEXT NOT NULL,
+        hash        TEXT NOT NULL REFERENCES blobs(hash),
+        size        INTEGER NOT NULL,
+        created_at  INTEGER NOT NULL,
+        PRIMARY KEY (note_id, rev)
+    );
+    CREATE INDEX revisions_hash ON revisions(hash);",
+    // 4: Markdown mirror paths and sealed credentials
+    "CREATE TABLE note_files (
+        note_id  TEXT PRIMARY KEY REFERENCES notes(id) ON DELETE CASCADE,
+        path     TEXT NOT NULL UNIQUE
+    );
+    CREATE TABLE secrets (
+        key    TEXT PRIMARY KEY,
+        value  TEXT NOT NULL
+    );",
+    // 5: WebDAV sync base state, one row per path as of the last sync
+    "CREATE TABLE webdav_state (
+        path            TEXT PRIMARY KEY,
+        local_hash      TEXT NOT NULL,
+        remote_version  TEXT NOT NULL
+    );",
+    // 6: notes in the trash, purged after the retention period
+    "CREATE TABLE trash (
+        note_id     TEXT PRIMARY KEY REFERENCES notes(id) ON DELETE CASCADE,
+        deleted_at  INTEGER NOT NULL
+    );
+    CREATE INDEX trash_deleted_at ON trash(deleted_at);",
+    // 7: reminders on notes or on a task line within one
+    "CREATE TABLE reminders (
+        id          TEXT PRIMARY KEY,
+        note_id     TEXT NOT NULL REFERENCES notes(id) ON DELETE CASCADE,
+        task        TEXT,
+        message     TEXT NOT NULL,
+        due_at      INTEGER NOT NULL,
+        fired_at    INTEGER
+    );
+    CREATE INDEX reminders_due_at ON reminders(due_at) WHERE fired_at IS NULL;",
+    // 8: attachments each note links to, so unused ones can be collected
+    "CREATE TABLE attachment_refs (
+        note_id  TEXT NOT NULL REFERENCES notes(id) ON DELETE CASCADE,
+        path     TEXT NOT NULL,
+        PRIMARY KEY (note_id, path)
+    );
+    CREATE INDEX attachment_refs_path ON attachment_refs(path);",
+    // 9: text read out of attachments, sealed like bodies
+    "CREATE TABLE attachment_text (
+        path        TEXT PRIMARY KEY,
+        text        TEXT NOT NULL,
+        error       TEXT,
+        updated_at  INTEGER NOT NULL
+    );",
+    // 10: sentence embeddings for semantic search, sealed like bodies
+    "CREATE TABLE embeddings (
+        note_id  TEXT PRIMARY KEY REFERENCES notes(id) ON DELETE CASCADE,
+        vector   TEXT NOT NULL
+    );",
+    // 11: edits that raced a change from elsewhere, kept as conflict copies
+    "CREATE TABLE conflicts (
+        note_id      TEXT PRIMARY KEY REFERENCES notes(id) ON DELETE CASCADE,
+        copy_id      TEXT NOT NULL REFERENCES notes(id) ON DELETE CASCADE,
+        base_at      INTEGER NOT NULL,
+        detected_at  INTEGER NOT NULL
+    );",
+    // 12: Yjs documents of note bodies, sealed like bodies
+    "CREATE TABLE collab_docs (
+        note_id  TEXT PRIMARY KEY REFERENCES notes(id) ON DELETE CASCADE,
+        state    TEXT NOT NULL
+    );",
+    // 13: S3 sync base state, the hash of each path as of the last sync
+    "CREATE TABLE s3_state (
+        path  TEXT PRIMARY KEY,
+        hash  TEXT NOT NULL
+    );",
+    // 14: keys the paged note listing sorts on
+    "CREATE INDEX notes_title ON notes(title COLLATE NOCASE, id);
+    CREATE INDEX notes_created_at ON notes(created_at, id);",
+    // 15: notes and folders protected with a passphrase of their own
+    "CREATE TABLE note_locks (
+        note_id  TEXT PRIMARY KEY REFERENCES notes(id) ON DELETE CASCADE,
+        params   TEXT NOT NULL,
+        folder   TEXT
+    );
+    CREATE TABLE folder_locks (
+        folder       TEXT PRIMARY KEY,
+        params       TEXT NOT NULL,
+        check_value  TEXT NOT NULL
+    );",
+    // 16: flashcard schedules and the reviews behind them
+    "CREATE TABLE srs_cards (
+        card_id        TEXT PRIMARY KEY,
+        note_id        TEXT NOT NULL REFERENCES notes(id) ON DELETE CASCADE,
+        ease           REAL NOT NULL,
+        interval_days  INTEGER NOT NULL,
+        repetitions    INTEGER NOT NULL,
+        lapses         INTEGER NOT NULL,
+        due_at         INTEGER NOT NULL,
+        reviewed_at    INTEGER NOT NULL
+    );
+    CREATE INDEX srs_cards_note ON srs_cards(note_id);
+    CREATE TABLE srs_reviews (
+        card_id      TEXT NOT NULL,
+        note_id      TEXT NOT NULL REFERENCES notes(id) ON DELETE CASCADE,
+        grade        TEXT NOT NULL,
+        reviewed_at  INTEGER NOT NULL
+    );
+    CREATE INDEX srs_reviews_time ON srs_reviews(reviewed_at);",
+    // 17: kanban boards, their columns and the notes on them
+    "CREATE TABLE boards (
+        id          TEXT PRIMARY KEY,
+        name        TEXT NOT NULL,
+        created_at  INTEGER NOT NULL,
+        updated_at  INTEGER NOT NULL
+    );
+    CREATE TABLE board_columns (
+        id        TEXT PRIMARY KEY,
+        board_id  TEXT NOT NULL REFERENCES boards(id) ON DELETE CASCADE,
+        name      TEXT NOT NULL,
+        position  INTEGER NOT NULL
+    );
+    CREATE INDEX board_columns_board ON board_columns(board_id, position);
+    CREATE TABLE board_cards (
+        id         TEXT PRIMARY KEY,
+        board_id   TEXT NOT NULL REFERENCES boards(id) ON DELETE CASCADE,
+        column_id  TEXT NOT NULL REFERENCES board_columns(id) ON DELETE CASCADE,
+        note_id    TEXT NOT NULL REFERENCES notes(id) ON DELETE CASCADE,
+        position   INTEGER NOT NULL,
+        UNIQUE (board_id, note_id)
+    );
+    CREATE INDEX board_cards_column ON board_cards(column_id, position);
+    CREATE INDEX board_cards_note ON board_cards(note_id);",
+    // 18: feed subscriptions and the items already turned into notes
+    "CREATE TABLE feeds (
+        id             TEXT PRIMARY KEY,
+        url            TEXT NOT NULL UNIQUE,
+        title          TEXT NOT NULL,
+        folder         TEXT,
+        added_at       INTEGER NOT NULL,
+        fetched_at     INTEGER,
+        etag           TEXT,
+        last_modified  TEXT,
+        error          TEXT
+    );
+    CREATE TABLE feed_items (
+        feed_id     TEXT NOT NULL REFERENCES feeds(id) ON DELETE CASCADE,
+        guid        TEXT NOT NULL,
+        note_id     TEXT REFERENCES notes(id) ON DELETE SET NULL,
+        fetched_at  INTEGER NOT NULL,
+        PRIMARY KEY (feed_id, guid)
+    );",
+    // 19: the last message taken from each mailbox of a mail account
+    "CREATE TABLE mail_cursors (
+        account_id    TEXT NOT NULL,
+        mailbox       TEXT NOT NULL,
+        uid_validity  INTEGER NOT NULL,
+        last_uid      INTEGER NOT NULL,
+        PRIMARY KEY (account_id, mailbox)
+    );",
+    // 20: rendered diagrams in the attachment store, by their source
+    "CREATE TABLE diagrams (
+        source_hash  TEXT PRIMARY KEY,
+        path         TEXT NOT NULL,
+        rendered_at  INTEGER NOT NULL
+    );",
+    // 21: folders made before any note is in them, and the sidebar's
+    // manual order
+    "CREATE TABLE folders (
+        path      TEXT PRIMARY KEY,
+        position  INTEGER
+    );
+    CREATE TABLE note_positions (
+        note_id   TEXT PRIMARY KEY REFERENCES notes(id) ON DELETE CASCADE,
+        position  INTEGER NOT NULL
+    );",
+    // 22: pinned notes and how often and lately each note was opened
+    "CREATE TABLE pinned (
+        note_id    TEXT PRIMARY KEY REFERENCES notes(id) ON DELETE CASCADE,
+        pinned_at  INTEGER NOT NULL
+    );
+    CREATE TABLE note_visits (
+        note_id     TEXT PRIMARY KEY REFERENCES notes(id) ON DELETE CASCADE,
+        score       REAL NOT NULL,
+        visited_at  INTEGER NOT NULL
+    );",
+    // 23: archived and read-only notes
+    "CREATE TABLE archived (
+        note_id      TEXT PRIMARY KEY REFERENCES notes(id) ON DELETE CASCADE,
+        archived_at  INTEGER NOT NULL
+    );
+    CREATE TABLE read_only (
+        note_id  TEXT PRIMARY KEY REFERENCES notes(id) ON DELETE CASCADE
+    );",
+    // 24: revision bodies as compressed blocks shared between revisions;
+    // blobs written before stay whole until repacked
+    "CREATE TABLE blocks (
+        hash  TEXT PRIMARY KEY,
+        data  BLOB NOT NULL,
+        size  INTEGER NOT NULL
+    );
+    CREATE TABLE blob_blocks (
+        blob   TEXT NOT NULL REFERENCES blobs(hash) ON DELETE CASCADE,
+        seq    INTEGER NOT NULL,
+        block  TEXT NOT NULL REFERENCES blocks(hash),
+        PRIMARY KEY (blob, seq)
+    );
+    CREATE INDEX blob_blocks_block ON blob_blocks(block);
+    ALTER TABLE blobs ADD COLUMN blocked INTEGER NOT NULL DEFAULT 0;",
+    // 25: ink drawings, by the SVG preview notes embed
+    "CREATE TABLE ink (
+        preview TEXT PRIMARY KEY,
+        source  TEXT NOT NULL
+    );",
+    // 26: imports and exports the job log keeps, for resuming
+    "CREATE TABLE job_log (
+        id         TEXT PRIMARY KEY,
+        kind       TEXT NOT NULL,
+        source     TEXT NOT NULL,
+        path       TEXT NOT NULL,
+        state      TEXT NOT NULL,
+        done       INTEGER NOT NULL DEFAULT 0,
+        total      INTEGER NOT NULL DEFAULT 0,
+        checkpoint TEXT,
+        error      TEXT,
+        started_at INTEGER NOT NULL,
+        updated_at INTEGER NOT NULL
+    );
+    CREATE INDEX job_log_started ON job_log(started_at);",
+    // 27: the read-later queue, and the files archived pages load
+    "CREATE TABLE read_later (
+        note_id   TEXT PRIMARY KEY REFERENCES notes(id) ON DELETE CASCADE,
+        url       TEXT NOT NULL UNIQUE,
+        archive   TEXT,
+        added_at  INTEGER NOT NULL,
+        read_at   INTEGER,
+        progress  REAL NOT NULL DEFAULT 0
+    );
+    CREATE TABLE archive_assets (
+        archive TEXT NOT NULL,
+        asset   TEXT NOT NULL,
+        PRIMARY KEY (archive, asset)
+    );",
+    // 28: who touched each note, and when
+    "CREATE TABLE note_activity (
+        id       INTEGER PRIMARY KEY,
+        note_id  TEXT NOT NULL,
+        title    TEXT NOT NULL,
+        kind     TEXT NOT NULL,
+        device   TEXT,
+        provider TEXT,
+        at       INTEGER NOT NULL
+    );
+    CREATE INDEX note_activity_note ON note_activity(note_id, at);
+    CREATE INDEX note_activity_at ON note_activity(at);",
+];
+
+pub fn run(conn: &mut Connection) -> Result<()> {
+    let applied: u32 = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    let applied = applied as usize;
+    if applied > MIGRATIONS.len() {
+        return Err(Error::InvalidInput(format!(
+            "database schema version {applied} is newer than this build supports ({})",
+            MIGRATIONS.len()
+        )));
+    }
+
+    let tx = conn.transaction()?;
+    for sql in &MIGRATIONS[applied..] {
+        tx.execute_batch(sql)?;
+    }
+    tx.pragma_update(None, "user_version", MIGRATIONS.len() as u32)?;
+    tx.commit()?;
+    Ok(())
+}
@@ -0,0 +1,532 @@
+pub mod commands;
+mod encryption;
+mod migrations;
+mod query;
+mod range;
+mod states;
+mod tree;
+
+use std::path::Path;
+use std::sync::{Mutex, MutexGuard, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rusqlite::{params, Connection, OptionalExtension, Row, Transaction};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::collation;
+use crate::crypto::{self, KeyParams, VaultKey};
+use crate::error::{Error, Result};
+
+pub use query::{FieldFilter, NotePage, NoteQuery};
+pub use range::{BodyRange, LimitedNote, PartialBodies};
+pub use tree::{FolderNode, TreeEntry};
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Note {
+    pub id: String,
+    pub title: String,
+    pub body: String,
+    pub folder: String,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+/// A note without its body, for listings.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NoteSummary {
+    pub id: String,
+    pub title: String,
+    pub folder: String,
+    pub created_at: i64,
+    pub updated_at: i64,
+    /// Protected with a passphrase of its own; listings show a placeholder.
+    pub locked: bool,
+    pub archived: bool,
+    pub read_only: bool,
+}
+
+/// Fields to change on an existing note; `None` leaves a field untouched.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NotePatch {
+    pub title: Option<String>,
+    pub body: Option<String>,
+    pub folder: Option<String>,
+}
+
+/// At-rest encryption state. `params` is set once a vault password exists;
+/// `key` only while the vault is unlocked.
+struct Crypto {
+    params: Option<KeyParams>,
+    key: Option<VaultKey>,
+}
+
+/// SQLite-backed note store.
+///
+/// When a vault password is set, note bodies are sealed with the vault key
+/// before they are written and opened again on read. Titles and folders stay
+/// in the clear so listings work while the vault is locked.
+pub struct Storage {
+    conn: Mutex<Connection>,
+    crypto: RwLock<Crypto>,
+}
+
+impl Storage {
+    pub fn open(path: &Path) -> Result<Self> {
+        let mut conn = Connection::open(path)?;
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        conn.pragma_update(None, "synchronous", "NORMAL")?;
+        conn.pragma_update(None, "foreign_keys", true)?;
+        conn.pragma_update(None, "secure_delete", true)?;
+        conn.create_collation(collation::SQL_NAME, collation::compare)?;
+        migrations::run(&mut conn)?;
+        let crypto = Crypto {
+            params: encryption::load_params(&conn)?,
+            key: None,
+        };
+        Ok(Self {
+            conn: Mutex::new(conn),
+            crypto: RwLock::new(crypto),
+        })
+    }
+
+    pub(crate) fn conn(&self) -> MutexGuard<'_, Connection> {
+        self.conn.lock().expect("storage connection poisoned")
+    }
+
+    fn crypto(&self) -> RwLockReadGuard<'_, Crypto> {
+        self.crypto.read().expect("storage crypto poisoned")
+    }
+
+    fn crypto_mut(&self) -> RwLockWriteGuard<'_, Crypto> {
+        self.crypto.write().expect("storage crypto poisoned")
+    }
+
+    pub fn create_note(&self, title: &str, body: &str, folder: &str) -> Result<Note> {
+        let now = now_millis();
+        let note = Note {
+            id: uuid::Uuid::new_v4().to_string(),
+            title: title.to_owned(),
+            body: body.to_owned(),
+            folder: folder.to_owned(),
+            created_at: now,
+            updated_at: now,
+        };
+        self.insert_note(&note)?;
+        Ok(note)
+    }
+
+    /// Stores a fully formed note as is, keeping its id and timestamps, for
+    /// notes brought in from elsewhere.
+    pub fn insert_note(&self, note: &Note) -> Result<()> {
+        self.conn().execute(
+            "INSERT INTO notes (id, title, body, folder, created_at, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                note.id,
+                note.title,
+                self.seal_body(&note.body)?,
+                note.folder,
+                note.created_at,
+                note.updated_at
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn get_note(&self, id: &str) -> Result<Note> {
+        let note = self
+            .conn()
+            .query_row(
+                "SELECT id, title, body, folder, created_at, updated_at FROM notes WHERE id = ?1",
+                [id],
+                note_from_row,
+            )
+            .optional()?
+            .ok_or_else(|| Error::NoteNotFound(id.to_owned()))?;
+        self.open_note(note)
+    }
+
+    pub fn has_note(&self, id: &str) -> Result<bool> {
+        let exists = self.conn().query_row(
+            "SELECT EXISTS(SELECT 1 FROM notes WHERE id = ?1)",
+            [id],
+            |row| row.get(0),
+        )?;
+        Ok(exists)
+    }
+
+    pub fn update_note(&self, id: &str, patch: NotePatch) -> Result<Note> {
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+        let note = self.apply_patch(&tx, id, patch)?;
+        tx.commit()?;
+        Ok(note)
+    }
+
+    /// Applies several patches in one transaction, so either every note
+    /// changes or none does.
+    pub fn update_notes(&self, patches: Vec<(String, NotePatch)>) -> Result<Vec<Note>> {
+        self.update_notes_with(patches, |_| Ok(()))
+    }
+
+    /// [`Self::update_notes`] with `also` run in the same transaction, for
+    /// rows that have to change along with the notes.
+    pub fn update_notes_with(
+        &self,
+        patches: Vec<(String, NotePatch)>,
+        also: impl FnOnce(&Transaction<'_>) -> Result<()>,
+    ) -> Result<Vec<Note>> {
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+        let notes = patches
+            .into_iter()
+            .map(|(id, patch)| self.apply_patch(&tx, &id, patch))
+            .collect::<Result<_>>()?;
+        also(&tx)?;
+        tx.commit()?;
+        Ok(notes)
+    }
+
+    fn apply_patch(&self, tx: &Transaction<'_>, id: &str, patch: NotePatch) -> Result<Note> {
+        let note = tx
+            .query_row(
+                "SELECT id, title, body, folder, created_at, updated_at FROM notes WHERE id = ?1",
+                [id],
+                note_from_row,
+            )
+            .optional()?
+            .ok_or_else(|| Error::NoteNotFound(id.to_owned()))?;
+        let mut note = self.open_note(note)?;
+        if let Some(title) = patch.title {
+            note.title = title;
+        }
+        if let Some(body) = patch.body {
+            note.body = body;
+        }
+        if let Some(folder) = patch.folder {
+            note.folder = folder;
+        }
+        note.updated_at = now_millis();
+
+        tx.execute(
+            "UPDATE notes SET title = ?2, body = ?3, folder = ?4, updated_at = ?5 WHERE id = ?1",
+            params![
+                note.id,
+                note.title,
+                self.seal_body(&note.body)?,
+                note.folder,
+                note.updated_at
+            ],
+        )?;
+        Ok(note)
+    }
+
+    /// Lists notes outside the trash, most recently edited first, optionally
+    /// restricted to one folder. Archived notes are in it; listings shown
+    /// to the user leave them out unless asked.
+    pub fn list_notes(&self, folder: Option<&str>) -> Result<Vec<NoteSummary>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(
+            "SELECT id, title, folder, created_at, updated_at,
+                    id IN (SELECT note_id FROM note_locks),
+                    id IN (SELECT note_id FROM archived),
+                    id IN (SELECT note_id FROM read_only) FROM notes
+             WHERE (?1 IS NULL OR folder = ?1) AND id NOT IN (SELECT note_id FROM trash)
+             ORDER BY updated_at DESC",
+        )?;
+        let notes = stmt
+            .query_map([folder], |row| {
+                Ok(NoteSummary {
+                    id: row.get(0)?,
+                    title: row.get(1)?,
+                    folder: row.get(2)?,
+                    created_at: row.get(3)?,
+                    updated_at: row.get(4)?,
+                    locked: row.get(5)?,
+                    archived: row.get(6)?,
+                    read_only: row.get(7)?,
+                })
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(notes)
+    }
+
+    /// Every note outside the trash with its body, for rebuilding derived
+    /// indexes.
+    pub fn all_notes(&self) -> Result<Vec<Note>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(
+            "SELECT id, title, body, folder, created_at, updated_at FROM notes
+             WHERE id NOT IN (SELECT note_id FROM trash)",
+        )?;
+        let notes = stmt
+            .query_map([], note_from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        notes.into_iter().map(|note| self.open_note(note)).collect()
+    }
+
+    /// Fails every write from here on, for a database about to be copied
+    /// elsewhere and left behind. [`Self::snapshot`] still works.
+    pub fn refuse_writes(&self) -> Result<()> {
+        self.conn().pragma_update(None, "query_only", true)?;
+        Ok(())
+    }
+
+    /// Writes a consistent, compacted copy of the database to `dest`, which
+    /// must not exist yet.
+    pub fn snapshot(&self, dest: &Path) -> Result<()> {
+        let conn = self.conn();
+        // SQLite counts writing the copy as a write, so a database refusing
+        // them lets this one through while no other can start.
+        let query_only: bool = conn.pragma_query_value(None, "query_only", |row| row.get(0))?;
+        if query_only {
+            conn.pragma_update(None, "query_only", false)?;
+        }
+        let copied = conn.execute("VACUUM INTO ?1", [dest.to_string_lossy()]);
+        if query_only {
+            conn.pragma_update(None, "query_only", true)?;
+        }
+        copied?;
+        Ok(())
+    }
+
+    /// Problems SQLite finds in its own file and in foreign keys, as it
+    /// words them. Empty when all is well.
+    pub fn integrity_check(&self) -> Result<Vec<String>> {
+        let conn = self.conn();
+        let mut problems: Vec<String> = conn
+            .prepare("PRAGMA integrity_check")?
+            .query_map([], |row| row.get(0))?
+            .collect::<rusqlite::Result<_>>()?;
+        problems.retain(|problem| problem != "ok");
+        let mut stmt = conn.prepare("PRAGMA foreign_key_check")?;
+        let dangling = stmt.query_map([], |row| {
+            Ok(format!(
+                "row {} of {} points at a missing row of {}",
+                row.get::<_, i64>(1)?,
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(2)?
+            ))
+        })?;
+        for problem in dangling {
+            problems.push(problem?);
+        }
+        Ok(problems)
+    }
+
+    /// Rebuilds the database file without free pages, returning the bytes
+    /// given back.
+    pub fn vacuum(&self) -> Result<u64> {
+        let conn = self.conn();
+        let size = |conn: &Connection| -> rusqlite::Result<i64> {
+            let pages: i64 = conn.pragma_query_value(None, "page_count", |row| row.get(0))?;
+            let page_size: i64 = conn.pragma_query_value(None, "page_size", |row| row.get(0))?;
+            Ok(pages * page_size)
+        };
+        let before = size(&conn)?;
+        conn.execute_batch("VACUUM; PRAGMA wal_checkpoint(TRUNCATE);")?;
+        Ok((before - size(&conn)?).max(0) as u64)
+    }
+
+    /// Replaces the whole database with the copy at `src`, upgrading it if it
+    /// predates the current schema. An encrypted copy made under another
+    /// password leaves the vault locked.
+    pub fn restore_from(&self, src: &Path) -> Result<()> {
+        {
+            let mut conn = self.conn();
+            conn.restore(
+                rusqlite::MAIN_DB,
+                src,
+                None::<fn(rusqlite::backup::Progress)>,
+            )?;
+            migrations::run(&mut conn)?;
+        }
+        self.reload_crypto()
+    }
+
+    /// When note `id` was last saved, if it exists.
+    pub fn updated_at(&self, id: &str) -> Result<Option<i64>> {
+        let updated_at = self
+            .conn()
+            .query_row("SELECT updated_at FROM notes WHERE id = ?1", [id], |row| {
+                row.get(0)
+            })
+            .optional()?;
+        Ok(updated_at)
+    }
+
+    /// Writes `note` as is, timestamps included, over the stored one or as
+    /// a new row.
+    pub fn put_note(&self, note: &Note) -> Result<()> {
+        let updated = self.conn().execute(
+            "UPDATE notes SET title = ?2, body = ?3, folder = ?4, updated_at = ?5 WHERE id = ?1",
+            params![
+                note.id,
+                note.title,
+                self.seal_body(&note.body)?,
+                note.folder,
+                note.updated_at
+            ],
+        )?;
+        if updated == 0 {
+            self.insert_note(note)?;
+        }
+        Ok(())
+    }
+
+    /// Moves everything committed so far from the write-ahead log into the
+    /// database file and syncs it, so it survives a power cut.
+    pub fn checkpoint(&self) -> Result<()> {
+        self.conn()
+            .query_row("PRAGMA wal_checkpoint(FULL)", [], |_| Ok(()))?;
+        Ok(())
+    }
+
+    pub fn delete_note(&self, id: &str) -> Result<()> {
+        let deleted = self
+            .conn()
+            .execute("DELETE FROM notes WHERE id = ?1", [id])?;
+        if deleted == 0 {
+            return Err(Error::NoteNotFound(id.to_owned()));
+        }
+        Ok(())
+    }
+
+    pub fn meta(&self, key: &str) -> Result<Option<String>> {
+        let value = self
+            .conn()
+            .query_row("SELECT value FROM meta WHERE key = ?1", [key], |row| {
+                row.get(0)
+            })
+            .optional()?;
+        Ok(value)
+    }
+
+    pub fn set_meta(&self, key: &str, value: &str) -> Result<()> {
+        self.conn().execute(
+            "INSERT OR REPLACE INTO meta (key, value) VALUES (?1, ?2)",
+            params![key, value],
+        )?;
+        Ok(())
+    }
+
+    pub fn delete_meta(&self, key: &str) -> Result<()> {
+        self.conn()
+            .execute("DELETE FROM meta WHERE key = ?1", [key])?;
+        Ok(())
+    }
+
+    /// Reads a credential. Secrets are sealed like note bodies, so this
+    /// fails with [`Error::VaultLocked`] while an encrypted vault is locked.
+    pub fn secret(&self, key: &str) -> Result<Option<String>> {
+        let stored: Option<String> = self
+            .conn()
+            .query_row("SELECT value FROM secrets WHERE key = ?1", [key], |row| {
+                row.get(0)
+            })
+            .optional()?;
+        stored.map(|stored| self.open_body(stored)).transpose()
+    }
+
+    /// Stores a credential, or removes it when `value` is `None`.
+    pub fn set_secret(&self, key: &str, value: Option<&str>) -> Result<()> {
+        match value {
+            Some(value) => {
+                let sealed = self.seal_body(value)?;
+                self.conn().execute(
+                    "INSERT OR REPLACE INTO secrets (key, value) VALUES (?1, ?2)",
+                    params![key, sealed],
+                )?;
+            }
+            None => {
+                self.conn()
+                    .execute("DELETE FROM secrets WHERE key = ?1", [key])?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Encrypts a note body (or any other note-derived text) for storage if
+    /// the vault has a password.
+    pub(crate) fn seal_body(&self, body: &str) -> Result<String> {
+        let crypto = self.crypto();
+        match (&crypto.params, &crypto.key) {
+            (None, _) => Ok(body.to_owned()),
+            (Some(_), Some(key)) => key.seal(body),
+            (Some(_), None) => Err(Error::VaultLocked),
+        }
+    }
+
+    /// Decrypts a body read from the database. Plain bodies pass through, so
+    /// rows written before encryption was enabled still read correctly.
+    pub(crate) fn open_body(&self, stored: String) -> Result<String> {
+        if !crypto::is_sealed(&stored) {
+            return Ok(stored);
+        }
+        self.crypto()
+            .key
+            .as_ref()
+            .ok_or(Error::VaultLocked)?
+            .open(&stored)
+    }
+
+    /// [`Storage::seal_body`] for bytes. Sealed bytes carry no marker, so
+    /// columns holding them are sealed all at once or not at all.
+    pub(crate) fn seal_bytes(&self, bytes: &[u8]) -> Result<Vec<u8>> {
+        let crypto = self.crypto();
+        match (&crypto.params, &crypto.key) {
+            (None, _) => Ok(bytes.to_owned()),
+            (Some(_), Some(key)) => key.seal_bytes(bytes),
+            (Some(_), None) => Err(Error::VaultLocked),
+        }
+    }
+
+    pub(crate) fn open_bytes(&self, stored: Vec<u8>) -> Result<Vec<u8>> {
+        let crypto = self.crypto();
+        match (&crypto.params, &crypto.key) {
+            (None, _) => Ok(stored),
+            (Some(_), Some(key)) => key.open_bytes(&stored),
+            (Some(_), None) => Err(Error::VaultLocked),
+        }
+    }
+
+    /// What history and the block store file `data` under: its SHA-256,
+    /// or with a vault password its [`VaultKey::digest`], so the names of
+    /// stored revisions cannot be matched against guessed text.
+    pub(crate) fn content_hash(&self, data: &[u8]) -> Result<String> {
+        let crypto = self.crypto();
+        match (&crypto.params, &crypto.key) {
+            (None, _) => Ok(hex::encode(Sha256::digest(data))),
+            (Some(_), Some(key)) => Ok(key.digest(data)),
+            (Some(_), None) => Err(Error::VaultLocked),
+        }
+    }
+
+    fn open_note(&self, mut note: Note) -> Result<Note> {
+        note.body = self.open_body(note.body)?;
+        Ok(note)
+    }
+}
+
+fn note_from_row(row: &Row<'_>) -> rusqlite::Result<Note> {
+    Ok(Note {
+        id: row.get(0)?,
+        title: row.get(1)?,
+        body: row.get(2)?,
+        folder: row.get(3)?,
+        created_at: row.get(4)?,
+        updated_at: row.get(5)?,
+    })
+}
+
+/// Milliseconds since the Unix epoch, the timestamp unit used throughout the database.
+pub fn now_millis() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or_default()
+}
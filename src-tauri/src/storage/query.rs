@@ -0,0 +1,222 @@
+use base64::engine::general_purpose::URL_SAFE_NO_PAD as BASE64;
+use base64::Engine;
+use rusqlite::params_from_iter;
+use rusqlite::types::Value;
+use serde::{Deserialize, Serialize};
+
+use super::Storage;
+use crate::error::{Error, Result};
+
+const DEFAULT_PAGE_SIZE: usize = 100;
+const MAX_PAGE_SIZE: usize = 1_000;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum NoteSort {
+    Title,
+    Created,
+    #[default]
+    Modified,
+    /// Stored size of the body, which is larger than the text in an
+    /// encrypted vault.
+    Size,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SortOrder {
+    Asc,
+    #[default]
+    Desc,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct NoteFilter {
+    pub folder: Option<String>,
+    /// Also notes in folders below `folder`.
+    pub include_subfolders: bool,
+    /// Tags a note needs all of. Resolved through the tag index, so the
+    /// storage layer only sees [`NoteQuery::ids`].
+    pub tags: Vec<String>,
+    /// Front matter fields a note needs all of, resolved like `tags`.
+    pub fields: Vec<FieldFilter>,
+    /// Archived notes too, which are left out otherwise.
+    pub include_archived: bool,
+}
+
+/// Notes with front matter field `key`, equal to `value` if there is one.
+#[derive(Debug, Clone, Deserialize)]
+pub struct FieldFilter {
+    pub key: String,
+    pub value: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct NoteQuery {
+    pub sort: NoteSort,
+    pub order: SortOrder,
+    pub filter: NoteFilter,
+    /// `nextCursor` of the previous page.
+    pub cursor: Option<String>,
+    pub page_size: Option<usize>,
+    /// Only these notes. Set by the caller, never by the frontend.
+    #[serde(skip)]
+    pub ids: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NoteListing {
+    pub id: String,
+    pub title: String,
+    pub folder: String,
+    pub created_at: i64,
+    pub updated_at: i64,
+    pub size: i64,
+    pub locked: bool,
+    pub archived: bool,
+    pub read_only: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NotePage {
+    pub notes: Vec<NoteListing>,
+    /// Pass back as `cursor` for the next page; `None` on the last one.
+    pub next_cursor: Option<String>,
+}
+
+impl Storage {
+    /// One page of notes outside the trash in the order asked for.
+    ///
+    /// Pages are keyed on the last row's sort value and id rather than an
+    /// offset, so each is an index range scan and notes saved in between
+    /// neither repeat nor go missing.
+    pub fn query_notes(&self, query: &NoteQuery) -> Result<NotePage> {
+        let page_size = query
+            .page_size
+            .unwrap_or(DEFAULT_PAGE_SIZE)
+            .clamp(1, MAX_PAGE_SIZE);
+        let key = match query.sort {
+            // Not served by the title index, as the order follows the
+            // settings and an index would have to be rebuilt along with them.
+            NoteSort::Title => "title COLLATE NOTES",
+            NoteSort::Created => "created_at",
+            NoteSort::Modified => "updated_at",
+            NoteSort::Size => "length(body)",
+        };
+        let (direction, after) = match query.order {
+            SortOrder::Asc => ("ASC", ">"),
+            SortOrder::Desc => ("DESC", "<"),
+        };
+
+        let mut sql = format!(
+            "SELECT id, title, folder, created_at, updated_at, length(body),
+                    id IN (SELECT note_id FROM note_locks),
+                    id IN (SELECT note_id FROM archived),
+                    id IN (SELECT note_id FROM read_only), {key} FROM notes
+             WHERE id NOT IN (SELECT note_id FROM trash)"
+        );
+        if !query.filter.include_archived {
+            sql.push_str(" AND id NOT IN (SELECT note_id FROM archived)");
+        }
+        let mut params: Vec<Value> = Vec::new();
+        if let Some(folder) = query
+            .filter
+            .folder
+            .as_deref()
+            .map(|folder| folder.trim_matches('/'))
+        {
+            // The root with its subfolders is every folder, so it needs no
+            // condition and no parameter.
+            if query.filter.include_subfolders && !folder.is_empty() {
+                params.push(Value::Text(folder.to_owned()));
+                let n = params.len();
+                sql.push_str(&format!(
+                    " AND (folder = ?{n} OR substr(folder, 1, length(?{n}) + 1) = ?{n} || '/')"
+                ));
+            } else if !query.filter.include_subfolders {
+                params.push(Value::Text(folder.to_owned()));
+                sql.push_str(&format!(" AND folder = ?{}", params.len()));
+            }
+        }
+        if let Some(ids) = &query.ids {
+            params.push(Value::Text(serde_json::to_string(ids)?));
+            sql.push_str(&format!(
+                " AND id IN (SELECT value FROM json_each(?{}))",
+                params.len()
+            ));
+        }
+        if let Some(cursor) = &query.cursor {
+            let (value, id) = decode_cursor(cursor, query.sort)?;
+            params.push(value);
+            params.push(Value::Text(id));
+            sql.push_str(&format!(
+                " AND ({key}, id) {after} (?{}, ?{})",
+                params.len() - 1,
+                params.len()
+            ));
+        }
+        sql.push_str(&format!(
+            " ORDER BY {key} {direction}, id {direction} LIMIT {}",
+            page_size + 1
+        ));
+
+        let conn = self.conn();
+        let mut stmt = conn.prepare(&sql)?;
+        let mut rows: Vec<(NoteListing, Value)> = stmt
+            .query_map(params_from_iter(params), |row| {
+                Ok((
+                    NoteListing {
+                        id: row.get(0)?,
+                        title: row.get(1)?,
+                        folder: row.get(2)?,
+                        created_at: row.get(3)?,
+                        updated_at: row.get(4)?,
+                        size: row.get(5)?,
+                        locked: row.get(6)?,
+                        archived: row.get(7)?,
+                        read_only: row.get(8)?,
+                    },
+                    row.get(9)?,
+                ))
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+        let next_cursor = if rows.len() > page_size {
+            rows.truncate(page_size);
+            rows.last()
+                .map(|(note, value)| encode_cursor(value, &note.id))
+                .transpose()?
+        } else {
+            None
+        };
+        Ok(NotePage {
+            notes: rows.into_iter().map(|(note, _)| note).collect(),
+            next_cursor,
+        })
+    }
+}
+
+fn encode_cursor(value: &Value, id: &str) -> Result<String> {
+    let value = match value {
+        Value::Integer(n) => serde_json::Value::from(*n),
+        Value::Text(text) => serde_json::Value::from(text.as_str()),
+        _ => serde_json::Value::Null,
+    };
+    Ok(BASE64.encode(serde_json::to_vec(&(value, id))?))
+}
+
+fn decode_cursor(cursor: &str, sort: NoteSort) -> Result<(Value, String)> {
+    let invalid = || Error::InvalidInput("the page cursor is not valid for this query".into());
+    let json = BASE64.decode(cursor).map_err(|_| invalid())?;
+    let (value, id): (serde_json::Value, String) =
+        serde_json::from_slice(&json).map_err(|_| invalid())?;
+    let value = match (sort, value) {
+        (NoteSort::Title, serde_json::Value::String(title)) => Value::Text(title),
+        (NoteSort::Title, _) => return Err(invalid()),
+        (_, value) => Value::Integer(value.as_i64().ok_or_else(invalid)?),
+    };
+    Ok((value, id))
+}
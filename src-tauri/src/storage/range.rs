@@ -0,0 +1,128 @@
+use std::collections::HashSet;
+use std::sync::Mutex;
+
+use rusqlite::OptionalExtension;
+use serde::Serialize;
+
+use super::{Note, Storage};
+use crate::error::{Error, Result};
+
+/// Part of a note body. Offsets and lengths count characters, not bytes,
+/// so a range never splits one.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BodyRange {
+    pub offset: usize,
+    pub text: String,
+    /// Length of the whole body.
+    pub total: usize,
+}
+
+impl BodyRange {
+    /// `len` characters of `body` from `offset`.
+    pub fn of(body: &str, offset: usize, len: usize) -> Self {
+        Self {
+            offset,
+            text: body.chars().skip(offset).take(len).collect(),
+            total: body.chars().count(),
+        }
+    }
+}
+
+/// A note whose body may have been cut short for loading.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LimitedNote {
+    #[serde(flatten)]
+    pub note: Note,
+    /// Only the start of the body came along.
+    pub truncated: bool,
+    /// Length of the whole body, in characters.
+    pub total_length: usize,
+}
+
+impl LimitedNote {
+    /// `note` with at most `limit` characters of its body.
+    pub fn of(mut note: Note, limit: Option<usize>) -> Self {
+        let total_length = note.body.chars().count();
+        let cut = limit
+            .and_then(|limit| note.body.char_indices().nth(limit))
+            .map(|(end, _)| end);
+        if let Some(end) = cut {
+            note.body.truncate(end);
+        }
+        Self {
+            note,
+            truncated: cut.is_some(),
+            total_length,
+        }
+    }
+}
+
+/// Notes handed to an editor with only part of their body, which it must
+/// not save until it has read the rest.
+#[derive(Default)]
+pub struct PartialBodies(Mutex<HashSet<String>>);
+
+impl PartialBodies {
+    pub fn cut(&self, id: &str) {
+        self.lock().insert(id.to_owned());
+    }
+
+    /// Note `id`'s body has been read to its end.
+    pub fn filled(&self, id: &str) {
+        self.lock().remove(id);
+    }
+
+    /// Refuses a save that would replace note `id`'s body with a cut one.
+    pub fn check(&self, id: &str) -> Result<()> {
+        match self.lock().contains(id) {
+            true => Err(Error::InvalidInput(format!(
+                "only part of note {id} was loaded; read the rest before saving it"
+            ))),
+            false => Ok(()),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashSet<String>> {
+        self.0.lock().expect("partial bodies poisoned")
+    }
+}
+
+impl Storage {
+    /// `len` characters of note `id`'s body from `offset`. Bodies are cut
+    /// in SQLite, so only the range leaves the database; encrypted vaults
+    /// have to open the whole body first.
+    pub fn body_range(&self, id: &str, offset: usize, len: usize) -> Result<BodyRange> {
+        if self.is_encrypted() {
+            let note = self.get_note(id)?;
+            return Ok(BodyRange::of(&note.body, offset, len));
+        }
+        let (text, total) = self
+            .conn()
+            .query_row(
+                "SELECT substr(body, ?2, ?3), length(body) FROM notes WHERE id = ?1",
+                (
+                    id,
+                    sql_offset(offset)?,
+                    i64::try_from(len).unwrap_or(i64::MAX),
+                ),
+                |row| Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?)),
+            )
+            .optional()?
+            .ok_or_else(|| Error::NoteNotFound(id.to_owned()))?;
+        Ok(BodyRange {
+            offset,
+            text,
+            total: total as usize,
+        })
+    }
+}
+
+/// `offset` as SQLite's `substr` counts, from one.
+fn sql_offset(offset: usize) -> Result<i64> {
+    i64::try_from(offset)
+        .ok()
+        .and_then(|offset| offset.checked_add(1))
+        .ok_or_else(|| Error::InvalidInput(format!("offset {offset} is out of range")))
+}
@@ -0,0 +1,94 @@
+use std::collections::HashSet;
+
+use rusqlite::{params, Connection};
+
+use super::{now_millis, NotePatch, Storage};
+use crate::error::{Error, Result};
+
+impl Storage {
+    /// Archives or unarchives a note. Archived notes stay where they are
+    /// but drop out of listings, the folder tree and search unless those
+    /// are asked to include them.
+    pub fn set_archived(&self, id: &str, archived: bool) -> Result<()> {
+        let conn = self.conn();
+        exists(&conn, id)?;
+        match archived {
+            true => conn.execute(
+                "INSERT OR IGNORE INTO archived (note_id, archived_at) VALUES (?1, ?2)",
+                params![id, now_millis()],
+            )?,
+            false => conn.execute("DELETE FROM archived WHERE note_id = ?1", [id])?,
+        };
+        Ok(())
+    }
+
+    pub fn archived_ids(&self) -> Result<HashSet<String>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare("SELECT note_id FROM archived")?;
+        let ids = stmt
+            .query_map([], |row| row.get(0))?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(ids)
+    }
+
+    /// Makes a note read-only, so saving a new title or body for it fails
+    /// with [`Error::ReadOnly`], or writable again.
+    pub fn set_read_only(&self, id: &str, read_only: bool) -> Result<()> {
+        let conn = self.conn();
+        exists(&conn, id)?;
+        match read_only {
+            true => conn.execute(
+                "INSERT OR IGNORE INTO read_only (note_id) VALUES (?1)",
+                [id],
+            )?,
+            false => conn.execute("DELETE FROM read_only WHERE note_id = ?1", [id])?,
+        };
+        Ok(())
+    }
+}
+
+impl Storage {
+    /// Fails with [`Error::ReadOnly`] if `patch` would change what
+    /// read-only note `id` says; moving one is fine. Only edits the user
+    /// makes are checked, not the rewrites the app makes across notes,
+    /// such as retitling links to a renamed note.
+    pub fn check_editable(&self, id: &str, patch: &NotePatch) -> Result<()> {
+        if patch.title.is_none() && patch.body.is_none() {
+            return Ok(());
+        }
+        if !is_read_only(&self.conn(), id)? {
+            return Ok(());
+        }
+        let note = self.get_note(id)?;
+        let edits = patch
+            .title
+            .as_ref()
+            .is_some_and(|title| *title != note.title)
+            || patch.body.as_ref().is_some_and(|body| *body != note.body);
+        match edits {
+            true => Err(Error::ReadOnly(id.to_owned())),
+            false => Ok(()),
+        }
+    }
+}
+
+fn is_read_only(conn: &Connection, id: &str) -> Result<bool> {
+    let read_only = conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM read_only WHERE note_id = ?1)",
+        [id],
+        |row| row.get(0),
+    )?;
+    Ok(read_only)
+}
+
+fn exists(conn: &Connection, id: &str) -> Result<()> {
+    let exists: bool = conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM notes WHERE id = ?1)",
+        [id],
+        |row| row.get(0),
+    )?;
+    match exists {
+        true => Ok(()),
+        false => Err(Error::NoteNotFound(id.to_owned())),
+    }
+}
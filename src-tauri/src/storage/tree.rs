@@ -0,0 +1,143 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use super::{NoteSummary, Storage};
+use crate::collation;
+use crate::error::Result;
+
+/// A folder of the sidebar tree: one some note is in, or one made empty
+/// with `create_folder`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FolderNode {
+    /// Last segment of `path`; empty for the root.
+    pub name: String,
+    pub path: String,
+    /// Notes in this folder and all below it.
+    pub note_count: usize,
+    pub children: Vec<TreeEntry>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase", tag = "kind")]
+pub enum TreeEntry {
+    Folder(FolderNode),
+    Note(NoteSummary),
+}
+
+impl TreeEntry {
+    fn name(&self) -> &str {
+        match self {
+            Self::Folder(folder) => &folder.name,
+            Self::Note(note) => &note.title,
+        }
+    }
+}
+
+#[derive(Default)]
+struct Building {
+    position: Option<i64>,
+    folders: HashMap<String, Building>,
+    notes: Vec<(Option<i64>, NoteSummary)>,
+}
+
+impl Building {
+    fn folder(&mut self, path: &str) -> &mut Building {
+        let mut folder = self;
+        for segment in path.split('/').filter(|s| !s.is_empty()) {
+            folder = folder.folders.entry(segment.to_owned()).or_default();
+        }
+        folder
+    }
+}
+
+impl Storage {
+    /// Every note outside the trash, and with `include_archived` the
+    /// archived ones too, arranged by folder. Entries put in order by hand
+    /// come first, in that order; the rest follow by title the way
+    /// [`collation`] says.
+    pub fn folder_tree(&self, include_archived: bool) -> Result<FolderNode> {
+        let mut root = Building::default();
+        let (folders, positions) = {
+            let conn = self.conn();
+            let folders = conn
+                .prepare("SELECT path, position FROM folders")?
+                .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get(1)?)))?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            let positions = conn
+                .prepare("SELECT note_id, position FROM note_positions")?
+                .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get(1)?)))?
+                .collect::<rusqlite::Result<HashMap<_, i64>>>()?;
+            (folders, positions)
+        };
+        for (path, position) in folders {
+            root.folder(&path).position = position;
+        }
+        for note in self.list_notes(None)? {
+            if note.archived && !include_archived {
+                continue;
+            }
+            let position = positions.get(&note.id).copied();
+            root.folder(&note.folder).notes.push((position, note));
+        }
+        Ok(finish(
+            String::new(),
+            String::new(),
+            root,
+            collation::folders_first(),
+        ))
+    }
+}
+
+fn finish(name: String, path: String, building: Building, folders_first: bool) -> FolderNode {
+    let mut children: Vec<(Option<i64>, TreeEntry)> = building
+        .folders
+        .into_iter()
+        .map(|(name, folder)| {
+            let path = match path.is_empty() {
+                true => name.clone(),
+                false => format!("{path}/{name}"),
+            };
+            let position = folder.position;
+            let node = finish(name, path, folder, folders_first);
+            (position, TreeEntry::Folder(node))
+        })
+        .chain(
+            building
+                .notes
+                .into_iter()
+                .map(|(position, note)| (position, TreeEntry::Note(note))),
+        )
+        .collect();
+    children.sort_by(|(a_pos, a), (b_pos, b)| {
+        let kind = match (a, b) {
+            (TreeEntry::Folder(_), TreeEntry::Note(_)) if folders_first => Ordering::Less,
+            (TreeEntry::Note(_), TreeEntry::Folder(_)) if folders_first => Ordering::Greater,
+            _ => Ordering::Equal,
+        };
+        let manual = match (a_pos, b_pos) {
+            (Some(a), Some(b)) => a.cmp(b),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        };
+        kind.then(manual)
+            .then_with(|| collation::compare(a.name(), b.name()))
+    });
+    let children: Vec<TreeEntry> = children.into_iter().map(|(_, entry)| entry).collect();
+    let note_count = children
+        .iter()
+        .map(|child| match child {
+            TreeEntry::Folder(folder) => folder.note_count,
+            TreeEntry::Note(_) => 1,
+        })
+        .sum();
+    FolderNode {
+        name,
+        path,
+        note_count,
+        children,
+    }
+}
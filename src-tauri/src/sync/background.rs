@@ -0,0 +1,92 @@
+use std::sync::Arc;
+
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager};
+
+use super::{s3, webdav, SyncReport, PROGRESS_EVENT};
+use crate::error::Result;
+use crate::settings::{BackgroundSync, SettingsStore};
+use crate::vault::Vault;
+use crate::vaults;
+
+/// What the platform scheduler is asked to wait for, as the mobile bridges
+/// read it.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Schedule {
+    pub enabled: bool,
+    pub interval_minutes: u32,
+    pub requires_charging: bool,
+    pub requires_battery_not_low: bool,
+    pub requires_unmetered: bool,
+}
+
+impl From<&BackgroundSync> for Schedule {
+    fn from(settings: &BackgroundSync) -> Self {
+        Self {
+            enabled: settings.enabled,
+            interval_minutes: settings.interval_minutes,
+            requires_charging: settings.requires_charging,
+            requires_battery_not_low: settings.requires_battery_not_low,
+            requires_unmetered: !settings.allow_metered,
+        }
+    }
+}
+
+/// The schedule from the settings, turned off while the primary vault has
+/// no provider to sync with.
+pub fn schedule(app: &AppHandle) -> Result<Schedule> {
+    let mut schedule = Schedule::from(&app.state::<SettingsStore>().get().background_sync);
+    let vault = vaults::primary(app)?;
+    schedule.enabled &= has_provider(&vault)?;
+    Ok(schedule)
+}
+
+/// Syncs the primary vault with each provider set up for it, one after
+/// the other, and adds up what they did. `None` when there was nothing to
+/// do: background sync is off, the vault is locked, or no provider is set
+/// up. A failing provider stops the run.
+pub async fn run(app: &AppHandle) -> Result<Option<SyncReport>> {
+    if !app.state::<SettingsStore>().get().background_sync.enabled {
+        return Ok(None);
+    }
+    let vault = vaults::primary(app)?;
+    if vault.storage.is_locked() {
+        return Ok(None);
+    }
+    let mut report: Option<SyncReport> = None;
+    let mut add = |part: SyncReport| {
+        let report = report.get_or_insert_with(SyncReport::default);
+        report.pulled += part.pulled;
+        report.pushed |= part.pushed;
+        report.conflicts.extend(part.conflicts);
+    };
+    let mut progress = |progress| {
+        let _ = app.emit(PROGRESS_EVENT, progress);
+    };
+
+    if vault.git.status()?.remote.is_some() {
+        let git = Arc::clone(&vault);
+        let emitter = app.clone();
+        let part = tauri::async_runtime::spawn_blocking(move || {
+            git.sync_git(&mut |progress| {
+                let _ = emitter.emit(PROGRESS_EVENT, progress);
+            })
+        })
+        .await??;
+        add(part);
+    }
+    if webdav::config_of(&vault.storage)?.is_some() {
+        add(vault.webdav.sync(&vault, &mut progress).await?);
+    }
+    if s3::config_of(&vault.storage)?.is_some() {
+        add(vault.s3.sync(&vault, &mut progress).await?);
+    }
+    Ok(report)
+}
+
+fn has_provider(vault: &Vault) -> Result<bool> {
+    Ok(vault.git.status()?.remote.is_some()
+        || webdav::config_of(&vault.storage)?.is_some()
+        || s3::config_of(&vault.storage)?.is_some())
+}
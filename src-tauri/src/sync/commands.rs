@@ -0,0 +1,187 @@
+use tauri::{AppHandle, Emitter, Manager};
+
+use super::git::{Credentials, GitStatus};
+use super::lan::{Lan, LanPeer, LanStatus};
+use super::rules::{self, SyncRule};
+use super::s3::S3Config;
+use super::webdav::WebDavConfig;
+use super::{SyncReport, PROGRESS_EVENT};
+use crate::error::Result;
+use crate::vaults::{self, Current};
+
+/// Sets up git sync in the notes directory (creating the repository on first
+/// use) and points it at `url`. `token` is used for HTTPS remotes; SSH
+/// remotes authenticate through the SSH agent.
+#[tauri::command]
+pub async fn configure_remote(
+    vault: Current,
+    url: String,
+    branch: Option<String>,
+    username: Option<String>,
+    token: Option<String>,
+) -> Result<GitStatus> {
+    let credentials = token.map(|token| Credentials {
+        username: username.unwrap_or_default(),
+        token,
+    });
+    vault.configure_git_remote(&url, branch.as_deref(), credentials)?;
+    vault.git.status()
+}
+
+/// Runs a full git sync, emitting `sync-progress` events along the way.
+#[tauri::command]
+pub async fn sync_now(app: AppHandle, vault: Current) -> Result<SyncReport> {
+    tauri::async_runtime::spawn_blocking(move || {
+        vault.sync_git(&mut |progress| {
+            let _ = app.emit(PROGRESS_EVENT, progress);
+        })
+    })
+    .await?
+}
+
+#[tauri::command]
+pub async fn sync_status(vault: Current) -> Result<GitStatus> {
+    vault.git.status()
+}
+
+/// Points WebDAV sync at the collection `url` after checking it can be read.
+/// A `password` of `null` keeps the stored one; `intervalMinutes` of 0 turns
+/// background sync off.
+#[tauri::command]
+pub async fn webdav_configure(
+    vault: Current,
+    url: String,
+    username: String,
+    password: Option<String>,
+    interval_minutes: Option<u64>,
+) -> Result<WebDavConfig> {
+    vault
+        .webdav
+        .configure(
+            &vault.storage,
+            &url,
+            &username,
+            password.as_deref(),
+            interval_minutes,
+        )
+        .await
+}
+
+/// Runs a WebDAV sync now, emitting `sync-progress` events along the way.
+#[tauri::command]
+pub async fn webdav_sync(app: AppHandle, vault: Current) -> Result<SyncReport> {
+    vault
+        .webdav
+        .sync(vault.shared(), &mut |progress| {
+            let _ = app.emit(PROGRESS_EVENT, progress);
+        })
+        .await
+}
+
+/// Points S3 sync at the bucket in `config` after checking it can be read.
+/// The bucket is encrypted with `passphrase`; a prefix used before needs
+/// the same one. `null` for `secretAccessKey` or `passphrase` keeps the
+/// stored one.
+#[tauri::command]
+pub async fn s3_configure(
+    vault: Current,
+    config: S3Config,
+    secret_access_key: Option<String>,
+    passphrase: Option<String>,
+) -> Result<S3Config> {
+    vault
+        .s3
+        .configure(
+            &vault.storage,
+            config,
+            secret_access_key.as_deref(),
+            passphrase.as_deref(),
+        )
+        .await
+}
+
+/// Runs an S3 sync now, emitting `sync-progress` events along the way.
+#[tauri::command]
+pub async fn s3_sync(app: AppHandle, vault: Current) -> Result<SyncReport> {
+    vault
+        .s3
+        .sync(&vault, &mut |progress| {
+            let _ = app.emit(PROGRESS_EVENT, progress);
+        })
+        .await
+}
+
+/// Starts LAN sync and keeps it on across restarts. `name` is what other
+/// devices see; `null` keeps the current one.
+#[tauri::command]
+pub async fn lan_enable(app: AppHandle, name: Option<String>) -> Result<LanStatus> {
+    let vault = vaults::primary(&app)?;
+    let mut config = super::lan::config_of(&vault.storage)?;
+    if let Some(name) = name {
+        config.name = name;
+    }
+    super::lan::set_config(&vault.storage, &config)?;
+    app.state::<Lan>().start(&app)?;
+    config.enabled = true;
+    super::lan::set_config(&vault.storage, &config)?;
+    super::lan::status(&app)
+}
+
+#[tauri::command]
+pub async fn lan_disable(app: AppHandle) -> Result<LanStatus> {
+    let vault = vaults::primary(&app)?;
+    let mut config = super::lan::config_of(&vault.storage)?;
+    config.enabled = false;
+    super::lan::set_config(&vault.storage, &config)?;
+    app.state::<Lan>().stop();
+    super::lan::status(&app)
+}
+
+#[tauri::command]
+pub async fn lan_status(app: AppHandle) -> Result<LanStatus> {
+    super::lan::status(&app)
+}
+
+/// Pairs with a discovered device. Both sides get a `lan-pairing` event
+/// with the code to compare; this returns once both answered through
+/// `lan_confirm_pairing`.
+#[tauri::command]
+pub async fn lan_pair(app: AppHandle, peer_id: String) -> Result<LanPeer> {
+    tauri::async_runtime::spawn_blocking(move || app.state::<Lan>().pair(&app, &peer_id)).await?
+}
+
+#[tauri::command]
+pub async fn lan_confirm_pairing(app: AppHandle, peer_id: String, accept: bool) -> Result<()> {
+    app.state::<Lan>().confirm_pairing(&peer_id, accept)
+}
+
+#[tauri::command]
+pub async fn lan_unpair(app: AppHandle, peer_id: String) -> Result<LanStatus> {
+    super::lan::unpair(&vaults::primary(&app)?.storage, &peer_id)?;
+    super::lan::status(&app)
+}
+
+/// Syncs with a paired device now, emitting `sync-progress` events along
+/// the way.
+#[tauri::command]
+pub async fn lan_sync(app: AppHandle, peer_id: String) -> Result<SyncReport> {
+    tauri::async_runtime::spawn_blocking(move || {
+        app.state::<Lan>().sync(&app, &peer_id, &mut |progress| {
+            let _ = app.emit(PROGRESS_EVENT, progress);
+        })
+    })
+    .await?
+}
+
+/// The include and exclude rules sync and backups go by, in order.
+#[tauri::command]
+pub async fn get_sync_rules(vault: Current) -> Result<Vec<SyncRule>> {
+    rules::rules_of(&vault.storage)
+}
+
+/// Replaces the sync rules. They apply from the next sync or backup on;
+/// nothing already synced is deleted.
+#[tauri::command]
+pub async fn set_sync_rules(vault: Current, rules: Vec<SyncRule>) -> Result<Vec<SyncRule>> {
+    rules::set_rules(&vault.storage, rules)
+}
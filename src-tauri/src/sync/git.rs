@@ -0,0 +1,457 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+
+use git2::build::CheckoutBuilder;
+use git2::{
+    Commit, Cred, CredentialType, Delta, FetchOptions, IndexAddOption, IndexEntry, MergeAnalysis,
+    PushOptions, RemoteCallbacks, Repository, RepositoryInitOptions, Signature, Status, Tree,
+};
+use serde::Serialize;
+
+use super::rules::SyncFilter;
+use super::{conflict_path, SyncProgress, SyncStage};
+use crate::error::{Error, Result};
+
+const REMOTE: &str = "origin";
+const DEFAULT_BRANCH: &str = "main";
+const BRANCH_KEY: &str = "notesdesktop.branch";
+const MAX_AUTH_ATTEMPTS: usize = 3;
+
+/// HTTPS credentials; SSH remotes authenticate through the running agent.
+pub struct Credentials {
+    pub username: String,
+    pub token: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CommitInfo {
+    pub id: String,
+    pub message: String,
+    pub time: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GitStatus {
+    pub initialized: bool,
+    pub remote: Option<String>,
+    pub branch: String,
+    /// Whether the working tree has changes not yet committed.
+    pub dirty: bool,
+    /// Commits ahead of / behind the remote as of the last fetch.
+    pub ahead: usize,
+    pub behind: usize,
+    pub last_commit: Option<CommitInfo>,
+}
+
+/// A path in the working tree touched by a pull.
+pub enum PathChange {
+    Written(String),
+    Removed(String),
+}
+
+pub struct PullOutcome {
+    pub changes: Vec<PathChange>,
+    pub conflicts: Vec<String>,
+}
+
+/// Git repository living in the note mirror directory.
+pub struct GitSync {
+    dir: PathBuf,
+    repo: Mutex<Option<Repository>>,
+}
+
+impl GitSync {
+    /// Opens the repository in `dir` if one has been set up there.
+    pub fn open(dir: &Path) -> Self {
+        let repo = dir
+            .join(".git")
+            .exists()
+            .then(|| Repository::open(dir).ok())
+            .flatten();
+        Self {
+            dir: dir.to_owned(),
+            repo: Mutex::new(repo),
+        }
+    }
+
+    /// Creates the repository with an initial commit of the current notes.
+    /// Does nothing if it already exists.
+    pub fn init(&self, filter: &SyncFilter) -> Result<()> {
+        {
+            let mut repo = self.repo();
+            if repo.is_some() {
+                return Ok(());
+            }
+            let mut options = RepositoryInitOptions::new();
+            options.initial_head(DEFAULT_BRANCH);
+            *repo = Some(Repository::init_opts(&self.dir, &options)?);
+        }
+        self.commit_all("Start syncing notes", filter).map(drop)
+    }
+
+    pub fn configure_remote(&self, url: &str, branch: Option<&str>) -> Result<()> {
+        let guard = self.repo();
+        let repo = require(&guard)?;
+        if repo.find_remote(REMOTE).is_ok() {
+            repo.remote_set_url(REMOTE, url)?;
+        } else {
+            repo.remote(REMOTE, url)?;
+        }
+        repo.config()?
+            .set_str(BRANCH_KEY, branch.unwrap_or(DEFAULT_BRANCH))?;
+        Ok(())
+    }
+
+    /// Stages everything in the working tree that `filter` lets through and
+    /// commits it. Excluded files keep what was last committed of them.
+    /// Returns `None` when nothing changed since the last commit or sync is
+    /// not set up.
+    pub fn commit_all(&self, message: &str, filter: &SyncFilter) -> Result<Option<String>> {
+        let guard = self.repo();
+        let Some(repo) = guard.as_ref() else {
+            return Ok(None);
+        };
+
+        let mut index = repo.index()?;
+        // Skipping a path leaves its index entry as it was.
+        let mut staged = |path: &Path, _: &[u8]| {
+            let rel = path.to_string_lossy().replace('\\', "/");
+            let size = fs::metadata(self.dir.join(path))
+                .ok()
+                .map(|meta| meta.len());
+            i32::from(!filter.allows_path(&rel, size))
+        };
+        index.add_all(["*"], IndexAddOption::DEFAULT, Some(&mut staged))?;
+        index.update_all(["*"], Some(&mut staged))?;
+        index.write()?;
+        let tree = repo.find_tree(index.write_tree()?)?;
+
+        let parent = head_commit(repo)?;
+        if parent.as_ref().is_some_and(|p| p.tree_id() == tree.id()) {
+            return Ok(None);
+        }
+        let signature = signature(repo)?;
+        let parents: Vec<&Commit> = parent.iter().collect();
+        let id = repo.commit(
+            Some("HEAD"),
+            &signature,
+            &signature,
+            message,
+            &tree,
+            &parents,
+        )?;
+        Ok(Some(id.to_string()))
+    }
+
+    /// Fetches the remote branch and merges it into the working tree.
+    pub fn pull(
+        &self,
+        credentials: Option<&Credentials>,
+        progress: &mut dyn FnMut(SyncProgress),
+    ) -> Result<PullOutcome> {
+        let guard = self.repo();
+        let repo = require(&guard)?;
+        let branch = branch(repo)?;
+
+        {
+            let mut callbacks = auth_callbacks(credentials);
+            callbacks.transfer_progress(|stats| {
+                progress(SyncProgress {
+                    provider: "git",
+                    stage: SyncStage::Fetch,
+                    current: stats.received_objects(),
+                    total: stats.total_objects(),
+                });
+                true
+            });
+            let mut options = FetchOptions::new();
+            options.remote_callbacks(callbacks);
+            let refspec = format!("+refs/heads/{branch}:refs/remotes/{REMOTE}/{branch}");
+            repo.find_remote(REMOTE)?
+                .fetch(&[refspec], Some(&mut options), None)?;
+        }
+
+        let mut outcome = PullOutcome {
+            changes: Vec::new(),
+            conflicts: Vec::new(),
+        };
+        let Ok(tracking) = repo.find_reference(&format!("refs/remotes/{REMOTE}/{branch}")) else {
+            // The remote branch does not exist yet; the next push creates it.
+            return Ok(outcome);
+        };
+        let theirs = tracking.peel_to_commit()?;
+        let ours = head_commit(repo)?;
+        let old_tree = ours.as_ref().map(Commit::tree).transpose()?;
+        progress(SyncProgress {
+            provider: "git",
+            stage: SyncStage::Merge,
+            current: 0,
+            total: 1,
+        });
+
+        match &ours {
+            None => {
+                repo.reference(
+                    &format!("refs/heads/{branch}"),
+                    theirs.id(),
+                    true,
+                    "notes sync: initial pull",
+                )?;
+                repo.set_head(&format!("refs/heads/{branch}"))?;
+            }
+            Some(ours) => {
+                let annotated = repo.find_annotated_commit(theirs.id())?;
+                let (analysis, _) = repo.merge_analysis(&[&annotated])?;
+                if analysis.contains(MergeAnalysis::ANALYSIS_UP_TO_DATE) {
+                    return Ok(outcome);
+                } else if analysis.contains(MergeAnalysis::ANALYSIS_FASTFORWARD) {
+                    repo.head()?
+                        .set_target(theirs.id(), "notes sync: fast-forward")?;
+                } else {
+                    outcome.conflicts = merge(repo, ours, &theirs)?;
+                }
+            }
+        }
+        repo.checkout_head(Some(CheckoutBuilder::new().force()))?;
+
+        let new_tree = head_commit(repo)?
+            .ok_or_else(|| Error::InvalidInput("repository has no commits".into()))?
+            .tree()?;
+        outcome.changes = changed_paths(repo, old_tree.as_ref(), &new_tree)?;
+        Ok(outcome)
+    }
+
+    /// Pushes the local branch. Returns false when there is nothing to push.
+    pub fn push(
+        &self,
+        credentials: Option<&Credentials>,
+        progress: &mut dyn FnMut(SyncProgress),
+    ) -> Result<bool> {
+        let guard = self.repo();
+        let repo = require(&guard)?;
+        if head_commit(repo)?.is_none() {
+            return Ok(false);
+        }
+        let branch = branch(repo)?;
+
+        let mut rejection = None;
+        {
+            let mut callbacks = auth_callbacks(credentials);
+            callbacks.push_transfer_progress(|current, total, _bytes| {
+                progress(SyncProgress {
+                    provider: "git",
+                    stage: SyncStage::Push,
+                    current,
+                    total,
+                });
+            });
+            callbacks.push_update_reference(|_, status| {
+                rejection = status.map(str::to_owned);
+                Ok(())
+            });
+            let mut options = PushOptions::new();
+            options.remote_callbacks(callbacks);
+            let refspec = format!("refs/heads/{branch}:refs/heads/{branch}");
+            repo.find_remote(REMOTE)?
+                .push(&[refspec], Some(&mut options))?;
+        }
+        match rejection {
+            Some(reason) => Err(Error::InvalidInput(format!("push rejected: {reason}"))),
+            None => Ok(true),
+        }
+    }
+
+    pub fn status(&self) -> Result<GitStatus> {
+        let guard = self.repo();
+        let Some(repo) = guard.as_ref() else {
+            return Ok(GitStatus {
+                initialized: false,
+                remote: None,
+                branch: DEFAULT_BRANCH.to_owned(),
+                dirty: false,
+                ahead: 0,
+                behind: 0,
+                last_commit: None,
+            });
+        };
+
+        let branch = branch(repo)?;
+        let remote = repo
+            .find_remote(REMOTE)
+            .ok()
+            .and_then(|remote| remote.url().ok().map(str::to_owned));
+        let dirty = repo
+            .statuses(None)?
+            .iter()
+            .any(|entry| !entry.status().intersects(Status::CURRENT | Status::IGNORED));
+
+        let head = head_commit(repo)?;
+        let tracking = repo
+            .find_reference(&format!("refs/remotes/{REMOTE}/{branch}"))
+            .ok()
+            .and_then(|reference| reference.target());
+        let (ahead, behind) = match (&head, tracking) {
+            (Some(head), Some(tracking)) => repo.graph_ahead_behind(head.id(), tracking)?,
+            _ => (0, 0),
+        };
+
+        Ok(GitStatus {
+            initialized: true,
+            remote,
+            branch,
+            dirty,
+            ahead,
+            behind,
+            last_commit: head
+                .map(|commit| -> Result<_> {
+                    Ok(CommitInfo {
+                        id: commit.id().to_string(),
+                        message: commit.summary()?.unwrap_or_default().to_owned(),
+                        time: commit.time().seconds() * 1000,
+                    })
+                })
+                .transpose()?,
+        })
+    }
+
+    pub fn is_set_up(&self) -> bool {
+        self.repo().is_some()
+    }
+
+    fn repo(&self) -> MutexGuard<'_, Option<Repository>> {
+        self.repo.lock().expect("git repository poisoned")
+    }
+}
+
+fn require<'a>(repo: &'a MutexGuard<'_, Option<Repository>>) -> Result<&'a Repository> {
+    repo.as_ref()
+        .ok_or_else(|| Error::InvalidInput("git sync is not set up".into()))
+}
+
+fn branch(repo: &Repository) -> Result<String> {
+    Ok(repo
+        .config()?
+        .get_string(BRANCH_KEY)
+        .unwrap_or_else(|_| DEFAULT_BRANCH.to_owned()))
+}
+
+fn head_commit(repo: &Repository) -> Result<Option<Commit<'_>>> {
+    match repo.head() {
+        Ok(head) => Ok(Some(head.peel_to_commit()?)),
+        Err(err) if err.code() == git2::ErrorCode::UnbornBranch => Ok(None),
+        Err(err) if err.code() == git2::ErrorCode::NotFound => Ok(None),
+        Err(err) => Err(err.into()),
+    }
+}
+
+fn signature(repo: &Repository) -> Result<Signature<'static>> {
+    Ok(repo
+        .signature()
+        .or_else(|_| Signature::now("Saentis Notes", "notes@localhost"))?)
+}
+
+fn auth_callbacks<'a>(credentials: Option<&'a Credentials>) -> RemoteCallbacks<'a> {
+    let mut callbacks = RemoteCallbacks::new();
+    let mut attempts = 0;
+    callbacks.credentials(move |url, username, allowed| {
+        // libgit2 keeps asking until a credential works; give up eventually.
+        attempts += 1;
+        if attempts > MAX_AUTH_ATTEMPTS {
+            return Err(git2::Error::from_str("authentication failed"));
+        }
+        if allowed.contains(CredentialType::SSH_KEY) {
+            Cred::ssh_key_from_agent(username.unwrap_or("git"))
+        } else if allowed.contains(CredentialType::USER_PASS_PLAINTEXT) {
+            match credentials {
+                Some(c) => Cred::userpass_plaintext(&c.username, &c.token),
+                None => Cred::credential_helper(&git2::Config::open_default()?, url, username),
+            }
+        } else {
+            Cred::default()
+        }
+    });
+    callbacks
+}
+
+/// Merges `theirs` into `ours` and commits the result. Both sides of a
+/// conflicting file are kept: ours at its path, theirs as a conflict copy.
+fn merge(repo: &Repository, ours: &Commit<'_>, theirs: &Commit<'_>) -> Result<Vec<String>> {
+    let mut index = repo.merge_commits(ours, theirs, None)?;
+    let mut conflicts = Vec::new();
+
+    if index.has_conflicts() {
+        let entries = index
+            .conflicts()?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        let suffix = format!(" (conflict {})", &theirs.id().to_string()[..7]);
+        for conflict in entries {
+            let Some(path) = conflict
+                .our
+                .as_ref()
+                .or(conflict.their.as_ref())
+                .map(|entry| String::from_utf8_lossy(&entry.path).into_owned())
+            else {
+                continue;
+            };
+            index.conflict_remove(Path::new(&path))?;
+            match (conflict.our, conflict.their) {
+                (Some(our), Some(their)) => {
+                    index.add(&resolved(our, None))?;
+                    index.add(&resolved(their, Some(conflict_path(&path, &suffix))))?;
+                }
+                (Some(entry), None) | (None, Some(entry)) => index.add(&resolved(entry, None))?,
+                (None, None) => {}
+            }
+            conflicts.push(path);
+        }
+    }
+
+    let tree = repo.find_tree(index.write_tree_to(repo)?)?;
+    let signature = signature(repo)?;
+    repo.commit(
+        Some("HEAD"),
+        &signature,
+        &signature,
+        "Merge synced changes",
+        &tree,
+        &[ours, theirs],
+    )?;
+    Ok(conflicts)
+}
+
+/// Turns a conflict-stage index entry into a normal one, optionally at a new path.
+fn resolved(mut entry: IndexEntry, path: Option<String>) -> IndexEntry {
+    if let Some(path) = path {
+        entry.path = path.into_bytes();
+    }
+    // Clears the stage bits; libgit2 recomputes the name length.
+    entry.flags = 0;
+    entry
+}
+
+fn changed_paths(
+    repo: &Repository,
+    old: Option<&Tree<'_>>,
+    new: &Tree<'_>,
+) -> Result<Vec<PathChange>> {
+    let diff = repo.diff_tree_to_tree(old, Some(new), None)?;
+    let path_of = |file: git2::DiffFile<'_>| {
+        file.path()
+            .map(|path| path.to_string_lossy().replace('\\', "/"))
+    };
+    let mut changes = Vec::new();
+    for delta in diff.deltas() {
+        match delta.status() {
+            Delta::Deleted => changes.extend(path_of(delta.old_file()).map(PathChange::Removed)),
+            Delta::Renamed => {
+                changes.extend(path_of(delta.old_file()).map(PathChange::Removed));
+                changes.extend(path_of(delta.new_file()).map(PathChange::Written));
+            }
+            _ => changes.extend(path_of(delta.new_file()).map(PathChange::Written)),
+        }
+    }
+    Ok(changes)
+}
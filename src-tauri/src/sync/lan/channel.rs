@@ -0,0 +1,272 @@
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use x25519_dalek::{PublicKey, StaticSecret};
+use zeroize::Zeroizing;
+
+use crate::error::{Error, Result};
+
+/// Frames larger than this are refused rather than read into memory.
+const MAX_FRAME: u32 = 64 * 1024 * 1024;
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Purpose {
+    Pair,
+    Sync,
+}
+
+/// The first frame each side sends, in the clear.
+///
+/// The side that connects only commits to its key at first, with its hash,
+/// and reveals it once it has the other's. Neither side can then pick its
+/// key after seeing the other one, so someone in the middle gets one guess
+/// at the pairing code rather than as many as they can compute.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Hello {
+    pub device_id: String,
+    pub name: String,
+    pub purpose: Purpose,
+    /// Ephemeral X25519 public key, hex-encoded; empty until revealed.
+    #[serde(default)]
+    key: String,
+    /// SHA-256 of the key, hex-encoded, sent by the connecting side.
+    #[serde(default)]
+    commitment: String,
+}
+
+/// The connecting side's key, sent once it has the other's.
+#[derive(Serialize, Deserialize)]
+struct Reveal {
+    key: String,
+}
+
+/// A connection that has swapped [`Hello`]s but not settled on keys yet.
+pub struct Handshake {
+    stream: TcpStream,
+    pub theirs: Hello,
+    shared: Zeroizing<[u8; 32]>,
+    /// Public keys as `(initiator, responder)`, binding every derived key
+    /// to this exchange.
+    transcript: ([u8; 32], [u8; 32]),
+    initiator: bool,
+}
+
+impl Handshake {
+    /// Opens the handshake on a connection this side made.
+    pub fn connect(
+        mut stream: TcpStream,
+        device_id: &str,
+        name: &str,
+        purpose: Purpose,
+    ) -> Result<Self> {
+        stream.set_read_timeout(Some(TIMEOUT))?;
+        let (secret, ours) = hello(device_id, name, purpose);
+        let committed = Hello {
+            key: String::new(),
+            commitment: commitment(&ours.key),
+            ..ours.clone()
+        };
+        write_frame(&mut stream, &serde_json::to_vec(&committed)?)?;
+        let theirs: Hello = serde_json::from_slice(&read_frame(&mut stream)?)?;
+        if theirs.purpose != purpose {
+            return Err(Error::Lan("the peer answered for something else".into()));
+        }
+        let reveal = Reveal {
+            key: ours.key.clone(),
+        };
+        write_frame(&mut stream, &serde_json::to_vec(&reveal)?)?;
+        Self::finish(stream, secret, &ours, theirs, true)
+    }
+
+    /// Answers the handshake on a connection a peer made.
+    pub fn accept(mut stream: TcpStream, device_id: &str, name: &str) -> Result<Self> {
+        stream.set_read_timeout(Some(TIMEOUT))?;
+        let mut theirs: Hello = serde_json::from_slice(&read_frame(&mut stream)?)?;
+        if theirs.commitment.is_empty() {
+            return Err(Error::Lan("the peer did not commit to its key".into()));
+        }
+        let (secret, ours) = hello(device_id, name, theirs.purpose);
+        write_frame(&mut stream, &serde_json::to_vec(&ours)?)?;
+        let reveal: Reveal = serde_json::from_slice(&read_frame(&mut stream)?)?;
+        if commitment(&reveal.key) != theirs.commitment {
+            return Err(Error::Lan(
+                "the peer's key does not match what it committed to".into(),
+            ));
+        }
+        theirs.key = reveal.key;
+        Self::finish(stream, secret, &ours, theirs, false)
+    }
+
+    fn finish(
+        stream: TcpStream,
+        secret: StaticSecret,
+        ours: &Hello,
+        theirs: Hello,
+        initiator: bool,
+    ) -> Result<Self> {
+        let their_key = decode_key(&theirs.key)?;
+        let shared = secret.diffie_hellman(&PublicKey::from(their_key));
+        if !shared.was_contributory() {
+            return Err(Error::Lan("the peer sent a degenerate key".into()));
+        }
+        let our_key = decode_key(&ours.key)?;
+        let transcript = if initiator {
+            (our_key, their_key)
+        } else {
+            (their_key, our_key)
+        };
+        Ok(Self {
+            stream,
+            theirs,
+            shared: Zeroizing::new(shared.to_bytes()),
+            transcript,
+            initiator,
+        })
+    }
+
+    /// Six digits both sides show while pairing. They only match if no one
+    /// sat in the middle of the exchange.
+    pub fn pairing_code(&self) -> String {
+        let digest = self.derive(b"pairing code", &[]);
+        let n = u32::from_be_bytes(digest[..4].try_into().expect("digest is 32 bytes"));
+        format!("{:06}", n % 1_000_000)
+    }
+
+    /// The long-term key both sides keep once the codes were confirmed.
+    pub fn pairing_key(&self) -> [u8; 32] {
+        *self.derive(b"pairing key", &[])
+    }
+
+    /// Keys the connection. Syncs mix in the pairing key, so a device that
+    /// was never paired cannot read or write the stream; pairing itself
+    /// relies on the confirmed code instead.
+    pub fn into_channel(self, pairing_key: Option<&[u8; 32]>) -> Result<Channel> {
+        let extra = pairing_key.map(|key| key.as_slice()).unwrap_or_default();
+        let initiator = self.derive(b"initiator", extra);
+        let responder = self.derive(b"responder", extra);
+        let (send, receive) = if self.initiator {
+            (initiator, responder)
+        } else {
+            (responder, initiator)
+        };
+        Ok(Channel {
+            stream: self.stream,
+            send: ChaCha20Poly1305::new(&Key::from(*send)),
+            receive: ChaCha20Poly1305::new(&Key::from(*receive)),
+            sent: 0,
+            received: 0,
+        })
+    }
+
+    fn derive(&self, label: &[u8], extra: &[u8]) -> Zeroizing<[u8; 32]> {
+        let mut hasher = Sha256::new();
+        hasher.update(b"notesdesktop lan ");
+        hasher.update(label);
+        hasher.update(*self.shared);
+        hasher.update(self.transcript.0);
+        hasher.update(self.transcript.1);
+        hasher.update(extra);
+        Zeroizing::new(hasher.finalize().into())
+    }
+}
+
+/// An encrypted stream of JSON messages.
+///
+/// Each direction has its own key, and frames are numbered, so a frame
+/// cannot be replayed, dropped or reordered without the next read failing.
+pub struct Channel {
+    stream: TcpStream,
+    send: ChaCha20Poly1305,
+    receive: ChaCha20Poly1305,
+    sent: u64,
+    received: u64,
+}
+
+impl Channel {
+    pub fn send<T: Serialize>(&mut self, message: &T) -> Result<()> {
+        let plaintext = serde_json::to_vec(message)?;
+        let frame = self
+            .send
+            .encrypt(&nonce(self.sent), plaintext.as_slice())
+            .map_err(|_| Error::Lan("could not encrypt a message".into()))?;
+        self.sent += 1;
+        write_frame(&mut self.stream, &frame)
+    }
+
+    pub fn receive<T: DeserializeOwned>(&mut self) -> Result<T> {
+        let frame = read_frame(&mut self.stream)?;
+        let plaintext = self
+            .receive
+            .decrypt(&nonce(self.received), frame.as_slice())
+            .map_err(|_| Error::Lan("the peer could not be authenticated".into()))?;
+        self.received += 1;
+        Ok(serde_json::from_slice(&plaintext)?)
+    }
+
+    /// How long to wait for the next message; pairing waits on people.
+    pub fn set_timeout(&self, timeout: Duration) -> Result<()> {
+        self.stream.set_read_timeout(Some(timeout))?;
+        Ok(())
+    }
+}
+
+fn hello(device_id: &str, name: &str, purpose: Purpose) -> (StaticSecret, Hello) {
+    let secret = StaticSecret::from(rand::random::<[u8; 32]>());
+    let hello = Hello {
+        device_id: device_id.to_owned(),
+        name: name.to_owned(),
+        purpose,
+        key: hex::encode(PublicKey::from(&secret).as_bytes()),
+        commitment: String::new(),
+    };
+    (secret, hello)
+}
+
+fn commitment(key: &str) -> String {
+    hex::encode(Sha256::digest(format!("notesdesktop lan commitment {key}")))
+}
+
+fn decode_key(key: &str) -> Result<[u8; 32]> {
+    hex::decode(key)
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| Error::Lan("the peer sent a malformed key".into()))
+}
+
+fn nonce(counter: u64) -> Nonce {
+    let mut nonce = [0; 12];
+    nonce[4..].copy_from_slice(&counter.to_be_bytes());
+    Nonce::from(nonce)
+}
+
+fn write_frame(stream: &mut TcpStream, frame: &[u8]) -> Result<()> {
+    let len = u32::try_from(frame.len())
+        .ok()
+        .filter(|len| *len <= MAX_FRAME)
+        .ok_or_else(|| Error::Lan("message too large".into()))?;
+    stream.write_all(&len.to_be_bytes())?;
+    stream.write_all(frame)?;
+    stream.flush()?;
+    Ok(())
+}
+
+fn read_frame(stream: &mut TcpStream) -> Result<Vec<u8>> {
+    let mut len = [0; 4];
+    stream.read_exact(&mut len)?;
+    let len = u32::from_be_bytes(len);
+    if len > MAX_FRAME {
+        return Err(Error::Lan("message too large".into()));
+    }
+    let mut frame = vec![0; len as usize];
+    stream.read_exact(&mut frame)?;
+    Ok(frame)
+}
@@ -0,0 +1,293 @@
+use std::collections::HashMap;
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::collab;
+use crate::error::{Error, Result};
+use crate::protected;
+use crate::storage::{Note, NotePatch};
+use crate::sync::rules::SyncFilter;
+use crate::trash;
+use crate::vault::Vault;
+
+/// What a device has of one note, so the other can work out what to send.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Entry {
+    id: String,
+    title: String,
+    folder: String,
+    updated_at: i64,
+    trashed_at: Option<i64>,
+    /// Base64 state vector of the body's document; empty for trashed and
+    /// protected notes.
+    vector: String,
+    /// SHA-256 of the body, hex-encoded.
+    digest: String,
+    /// Whether the body is sealed with a passphrase of the note's own.
+    #[serde(default)]
+    protected: bool,
+}
+
+/// A note as one device sends it to another that lacks some of it.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Change {
+    id: String,
+    title: String,
+    folder: String,
+    created_at: i64,
+    updated_at: i64,
+    trashed_at: Option<i64>,
+    /// Base64 Yjs update for the body, if the peer is missing any of it.
+    update: Option<String>,
+    /// The whole body, in place of an update, when either side has it
+    /// sealed with a passphrase of the note's own.
+    #[serde(default)]
+    body: Option<String>,
+}
+
+/// The notes `filter` lets through, as this device has them.
+pub fn manifest(vault: &Vault, filter: &SyncFilter) -> Result<Vec<Entry>> {
+    let mut entries = Vec::new();
+    for note in vault.storage.all_notes()? {
+        if !allowed(filter, &note) {
+            continue;
+        }
+        let protected = protected::is_protected(&note.body);
+        let vector = match protected {
+            true => String::new(),
+            false => BASE64.encode(collab::state_vector(vault, &note.id)?),
+        };
+        entries.push(Entry {
+            digest: digest(&note.body),
+            id: note.id,
+            title: note.title,
+            folder: note.folder,
+            updated_at: note.updated_at,
+            trashed_at: None,
+            vector,
+            protected,
+        });
+    }
+    for note in trash::list(&vault.storage)? {
+        if !filter.allows_note(&note.folder, None) {
+            continue;
+        }
+        entries.push(Entry {
+            id: note.id,
+            title: note.title,
+            folder: note.folder,
+            updated_at: note.updated_at,
+            trashed_at: Some(note.deleted_at),
+            vector: String::new(),
+            digest: String::new(),
+            protected: false,
+        });
+    }
+    Ok(entries)
+}
+
+/// What the peer with `theirs` is missing. Trashed notes go out without a
+/// body, only so the peer can trash them too. Protected bodies go out
+/// whole, as they have no document to update.
+pub fn changes_for(vault: &Vault, theirs: &[Entry], filter: &SyncFilter) -> Result<Vec<Change>> {
+    let theirs: HashMap<&str, &Entry> = theirs
+        .iter()
+        .map(|entry| (entry.id.as_str(), entry))
+        .collect();
+    let mut changes = Vec::new();
+    for note in vault.storage.all_notes()? {
+        if !allowed(filter, &note) {
+            continue;
+        }
+        let entry = theirs.get(note.id.as_str()).copied();
+        if protected::is_protected(&note.body) || entry.is_some_and(|entry| entry.protected) {
+            let body = match entry {
+                Some(entry) if entry.trashed_at.is_none() => {
+                    let behind = entry.digest != digest(&note.body);
+                    if !behind && entry.title == note.title && entry.folder == note.folder {
+                        continue;
+                    }
+                    behind.then(|| note.body.clone())
+                }
+                Some(Entry {
+                    trashed_at: Some(trashed_at),
+                    ..
+                }) if note.updated_at <= *trashed_at => continue,
+                _ => Some(note.body.clone()),
+            };
+            changes.push(Change {
+                body,
+                ..change(note, None, None)
+            });
+            continue;
+        }
+        let update = match theirs.get(note.id.as_str()) {
+            None => Some(collab::encode_update(vault, &note.id, None)?),
+            // Saved since the peer trashed it, so the peer brings it back.
+            Some(Entry {
+                trashed_at: Some(trashed_at),
+                ..
+            }) => {
+                if note.updated_at <= *trashed_at {
+                    continue;
+                }
+                Some(collab::encode_update(vault, &note.id, None)?)
+            }
+            Some(entry) => {
+                let vector = decode(&entry.vector)?;
+                // Deletions do not show in the vector, but in the body.
+                let behind = !collab::covers(vault, &note.id, &vector)?
+                    || entry.digest != digest(&note.body);
+                if !behind && entry.title == note.title && entry.folder == note.folder {
+                    continue;
+                }
+                behind
+                    .then(|| collab::encode_update(vault, &note.id, Some(&vector)))
+                    .transpose()?
+            }
+        };
+        changes.push(change(note, None, update));
+    }
+    for trashed in trash::list(&vault.storage)? {
+        let Some(entry) = theirs.get(trashed.id.as_str()) else {
+            continue;
+        };
+        if !filter.allows_note(&trashed.folder, None) {
+            continue;
+        }
+        if entry.trashed_at.is_none() {
+            let note = vault.storage.get_note(&trashed.id)?;
+            changes.push(change(note, Some(trashed.deleted_at), None));
+        }
+    }
+    Ok(changes)
+}
+
+/// Applies changes from a peer, returning how many notes it touched.
+/// Changes to notes `filter` leaves out, here or as the peer has them, are
+/// dropped.
+///
+/// Bodies merge through their documents, so neither side's edits are lost,
+/// except protected ones, which cannot merge and go to whichever side saved
+/// last. So do titles and folders, and the trash: a note trashed after its last save elsewhere is trashed here, and
+/// one saved after it was trashed here comes back.
+pub fn apply(vault: &Vault, changes: Vec<Change>, filter: &SyncFilter) -> Result<usize> {
+    let trashed: HashMap<String, i64> = trash::list(&vault.storage)?
+        .into_iter()
+        .map(|note| (note.id, note.deleted_at))
+        .collect();
+    let mut touched = 0;
+    for change in changes {
+        if !filter.allows_note(&change.folder, None) {
+            continue;
+        }
+        if !vault.storage.has_note(&change.id)? {
+            if change.trashed_at.is_some() {
+                continue;
+            }
+            let mut note = Note {
+                id: change.id,
+                title: change.title,
+                body: String::new(),
+                folder: change.folder,
+                created_at: change.created_at,
+                updated_at: change.updated_at,
+            };
+            match (change.body, &change.update) {
+                (Some(body), _) => {
+                    note.body = body;
+                    vault.import_note(&note)?;
+                }
+                (None, Some(update)) => {
+                    collab::adopt(vault, note, &decode(update)?)?;
+                }
+                (None, None) => continue,
+            }
+            touched += 1;
+            continue;
+        }
+
+        let local = vault.storage.get_note(&change.id)?;
+        if !allowed(filter, &local) {
+            continue;
+        }
+        match (trashed.get(&change.id), change.trashed_at) {
+            (Some(_), Some(_)) => continue,
+            (None, Some(trashed_at)) => {
+                if trashed_at > local.updated_at {
+                    vault.trash_note(&change.id)?;
+                    touched += 1;
+                }
+                continue;
+            }
+            (Some(&trashed_at), None) => {
+                if change.updated_at <= trashed_at {
+                    continue;
+                }
+                vault.restore_note(&change.id)?;
+            }
+            (None, None) => {}
+        }
+
+        let mut note = local.clone();
+        match (change.body, &change.update) {
+            (Some(body), _) if change.updated_at > local.updated_at && body != local.body => {
+                let patch = NotePatch {
+                    body: Some(body),
+                    ..Default::default()
+                };
+                note = vault.update_note_with(&change.id, patch, |_| Ok(()))?;
+            }
+            (None, Some(update)) if !protected::is_protected(&local.body) => {
+                note = collab::apply_update(vault, &change.id, &decode(update)?)?;
+            }
+            _ => {}
+        }
+        let renamed = change.updated_at > local.updated_at
+            && (change.title != note.title || change.folder != note.folder);
+        if renamed {
+            let patch = NotePatch {
+                title: Some(change.title),
+                folder: Some(change.folder),
+                ..Default::default()
+            };
+            note = vault.update_note(&change.id, patch)?;
+        }
+        if note.updated_at != local.updated_at || trashed.contains_key(&change.id) {
+            touched += 1;
+        }
+    }
+    Ok(touched)
+}
+
+fn change(note: Note, trashed_at: Option<i64>, update: Option<Vec<u8>>) -> Change {
+    Change {
+        id: note.id,
+        title: note.title,
+        folder: note.folder,
+        created_at: note.created_at,
+        updated_at: note.updated_at,
+        trashed_at,
+        update: update.map(|update| BASE64.encode(update)),
+        body: None,
+    }
+}
+
+fn allowed(filter: &SyncFilter, note: &Note) -> bool {
+    filter.allows_note(&note.folder, Some(note.body.len() as u64))
+}
+
+fn digest(body: &str) -> String {
+    hex::encode(Sha256::digest(body.as_bytes()))
+}
+
+fn decode(encoded: &str) -> Result<Vec<u8>> {
+    BASE64
+        .decode(encoded)
+        .map_err(|err| Error::Lan(err.to_string()))
+}
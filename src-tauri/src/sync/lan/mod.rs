@@ -0,0 +1,699 @@
+mod channel;
+mod exchange;
+
+use std::collections::HashMap;
+use std::io::ErrorKind;
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use mdns_sd::{ServiceDaemon, ServiceEvent, ServiceInfo};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager};
+
+use super::rules::{SyncFilter, SyncTarget};
+use super::{SyncProgress, SyncReport, SyncStage};
+use crate::activity;
+use crate::error::{Error, Result};
+use crate::storage::Storage;
+use crate::vault::Vault;
+use crate::vaults;
+use channel::{Channel, Handshake, Purpose};
+use exchange::{Change, Entry};
+
+/// Event carrying the [`LanPeer`] list whenever a device comes, goes or is
+/// paired.
+pub const PEERS_EVENT: &str = "lan-peers";
+/// Event carrying [`Pairing`] payloads while a pairing runs.
+pub const PAIRING_EVENT: &str = "lan-pairing";
+
+const SERVICE_TYPE: &str = "_notesdesktop._tcp.local.";
+const CONFIG_KEY: &str = "lan.config";
+const DEVICE_KEY: &str = "lan.device";
+/// Pairing keys, kept with the sealed secrets.
+const PEERS_KEY: &str = "lan.peers";
+const PROVIDER: &str = "lan";
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+/// How long a pairing waits for both people to compare the codes.
+const PAIRING_TIMEOUT: Duration = Duration::from_secs(120);
+const SYNC_INTERVAL: Duration = Duration::from_secs(5 * 60);
+/// How often the background threads check whether they should stop.
+const POLL: Duration = Duration::from_millis(250);
+/// Connections answered at once; more are closed as they come in.
+const MAX_CONNECTIONS: usize = 8;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct LanConfig {
+    pub enabled: bool,
+    /// Shown to other devices when they discover this one.
+    pub name: String,
+}
+
+impl Default for LanConfig {
+    fn default() -> Self {
+        let name = ["COMPUTERNAME", "HOSTNAME"]
+            .into_iter()
+            .find_map(|var| std::env::var(var).ok())
+            .unwrap_or_else(|| "Notes".to_owned());
+        Self {
+            enabled: false,
+            name,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LanPeer {
+    pub id: String,
+    pub name: String,
+    /// Seen on the network right now.
+    pub online: bool,
+    pub paired: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LanStatus {
+    pub running: bool,
+    pub device_id: String,
+    pub name: String,
+    pub peers: Vec<LanPeer>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PairingStage {
+    /// Both sides show `code`; each has to confirm it matches.
+    Confirm,
+    Paired,
+    Declined,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Pairing {
+    pub peer_id: String,
+    pub name: String,
+    pub code: String,
+    pub stage: PairingStage,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PairedPeer {
+    id: String,
+    name: String,
+    /// Hex-encoded key every session with the peer is keyed with.
+    key: String,
+}
+
+/// A device found through mDNS.
+struct Discovered {
+    fullname: String,
+    name: String,
+    addresses: Vec<SocketAddr>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+enum Message {
+    Confirm { accept: bool },
+    Manifest { entries: Vec<Entry> },
+    Changes { changes: Vec<Change> },
+    Done { pulled: usize },
+}
+
+/// Device-to-device sync of the primary vault over the local network,
+/// managed as app state.
+///
+/// Each running instance advertises itself over mDNS and listens on a TCP
+/// port. Devices pair once by confirming a six-digit code shown on both;
+/// after that they sync over a channel only the two of them can read, every
+/// few minutes while both are online, or on demand. Note bodies merge as
+/// CRDT documents, so nothing is lost to concurrent edits.
+#[derive(Default)]
+pub struct Lan {
+    running: Mutex<Option<Running>>,
+    shared: Arc<Shared>,
+}
+
+#[derive(Default)]
+struct Shared {
+    discovered: Mutex<HashMap<String, Discovered>>,
+    /// Pairings waiting for this side's answer, by peer.
+    pending: Mutex<HashMap<String, Sender<bool>>>,
+    /// One sync at a time; a peer calling in during one is turned away.
+    syncing: Mutex<()>,
+    /// Connections being answered.
+    connections: AtomicUsize,
+}
+
+/// Counts a connection being answered for as long as it lives.
+struct Answering(Arc<Shared>);
+
+impl Answering {
+    fn start(shared: &Arc<Shared>) -> Option<Self> {
+        let previous = shared.connections.fetch_add(1, Ordering::SeqCst);
+        let answering = Self(Arc::clone(shared));
+        (previous < MAX_CONNECTIONS).then_some(answering)
+    }
+}
+
+impl Drop for Answering {
+    fn drop(&mut self) {
+        self.0.connections.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+struct Running {
+    daemon: ServiceDaemon,
+    stop: Arc<AtomicBool>,
+    threads: Vec<JoinHandle<()>>,
+}
+
+impl Lan {
+    /// Starts advertising, browsing and listening, replacing any running
+    /// instance.
+    pub fn start(&self, app: &AppHandle) -> Result<()> {
+        self.stop();
+        let vault = vaults::primary(app)?;
+        let device_id = device_id(&vault.storage)?;
+        let config = config_of(&vault.storage)?;
+
+        let listener = TcpListener::bind(("0.0.0.0", 0))?;
+        listener.set_nonblocking(true)?;
+        let port = listener.local_addr()?.port();
+        let daemon = ServiceDaemon::new().map_err(mdns_error)?;
+        let properties = [("id", device_id.as_str()), ("name", config.name.as_str())];
+        let service = ServiceInfo::new(
+            SERVICE_TYPE,
+            &device_id,
+            &format!("{device_id}.local."),
+            "",
+            port,
+            &properties[..],
+        )
+        .map_err(mdns_error)?
+        .enable_addr_auto();
+        daemon.register(service).map_err(mdns_error)?;
+        let events = daemon.browse(SERVICE_TYPE).map_err(mdns_error)?;
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let mut threads = Vec::new();
+        {
+            let (app, shared, stop) = (app.clone(), Arc::clone(&self.shared), Arc::clone(&stop));
+            threads.push(thread::spawn(move || {
+                while !stop.load(Ordering::Relaxed) {
+                    let Ok(event) = events.recv_timeout(POLL) else {
+                        continue;
+                    };
+                    if shared.discovered_event(event, &device_id) {
+                        emit_peers(&app);
+                    }
+                }
+            }));
+        }
+        {
+            let (app, shared, stop) = (app.clone(), Arc::clone(&self.shared), Arc::clone(&stop));
+            threads.push(thread::spawn(move || {
+                while !stop.load(Ordering::Relaxed) {
+                    match listener.accept() {
+                        Ok((stream, _)) => {
+                            let Some(answering) = Answering::start(&shared) else {
+                                continue;
+                            };
+                            let app = app.clone();
+                            thread::spawn(move || {
+                                // A failed session only concerns the peer.
+                                let _ = serve(&app, &answering.0, stream);
+                            });
+                        }
+                        // Nothing waiting, or a connection that broke off
+                        // before it was accepted.
+                        Err(_) => thread::sleep(POLL),
+                    }
+                }
+            }));
+        }
+        {
+            let (app, stop) = (app.clone(), Arc::clone(&stop));
+            threads.push(thread::spawn(move || {
+                let mut last = Instant::now();
+                while !stop.load(Ordering::Relaxed) {
+                    thread::sleep(POLL);
+                    if last.elapsed() >= SYNC_INTERVAL {
+                        last = Instant::now();
+                        sync_all(&app);
+                    }
+                }
+            }));
+        }
+
+        *self.lock() = Some(Running {
+            daemon,
+            stop,
+            threads,
+        });
+        Ok(())
+    }
+
+    pub fn stop(&self) {
+        if let Some(running) = self.lock().take() {
+            running.stop.store(true, Ordering::Relaxed);
+            for thread in running.threads {
+                let _ = thread.join();
+            }
+            let _ = running.daemon.shutdown();
+        }
+        self.shared.discovered().clear();
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.lock().is_some()
+    }
+
+    /// Pairs with the discovered device `peer_id`. Returns once both sides
+    /// confirmed the code with [`Self::confirm_pairing`], or either
+    /// declined.
+    pub fn pair(&self, app: &AppHandle, peer_id: &str) -> Result<LanPeer> {
+        let vault = vaults::primary(app)?;
+        let stream = self.connect(peer_id)?;
+        let config = config_of(&vault.storage)?;
+        let handshake = Handshake::connect(
+            stream,
+            &device_id(&vault.storage)?,
+            &config.name,
+            Purpose::Pair,
+        )?;
+        pair(app, &self.shared, &vault, handshake)
+    }
+
+    /// Answers the pairing with `peer_id` waiting on this side.
+    pub fn confirm_pairing(&self, peer_id: &str, accept: bool) -> Result<()> {
+        let answer =
+            self.shared.pending().remove(peer_id).ok_or_else(|| {
+                Error::InvalidInput(format!("no pairing with {peer_id} is waiting"))
+            })?;
+        // The pairing may have just timed out.
+        let _ = answer.send(accept);
+        Ok(())
+    }
+
+    /// Syncs the primary vault with the paired device `peer_id`.
+    pub fn sync(
+        &self,
+        app: &AppHandle,
+        peer_id: &str,
+        progress: &mut dyn FnMut(SyncProgress),
+    ) -> Result<SyncReport> {
+        let vault = vaults::primary(app)?;
+        let key = pairing_key(&vault.storage, peer_id)?
+            .ok_or_else(|| Error::InvalidInput(format!("not paired with {peer_id}")))?;
+        let _syncing = self.shared.syncing.lock().expect("lan sync poisoned");
+        let stream = self.connect(peer_id)?;
+        let config = config_of(&vault.storage)?;
+        let handshake = Handshake::connect(
+            stream,
+            &device_id(&vault.storage)?,
+            &config.name,
+            Purpose::Sync,
+        )?;
+        let peer = handshake.theirs.name.clone();
+        let mut channel = handshake.into_channel(Some(&key))?;
+        pull_and_push(&vault, &mut channel, &peer, progress)
+    }
+
+    /// The devices on the network and those paired before, by name.
+    pub fn peers(&self, storage: &Storage) -> Result<Vec<LanPeer>> {
+        let discovered = self.shared.discovered();
+        let paired = paired_peers(storage)?;
+        let mut peers: Vec<LanPeer> = paired
+            .iter()
+            .map(|peer| LanPeer {
+                id: peer.id.clone(),
+                name: discovered
+                    .get(&peer.id)
+                    .map_or_else(|| peer.name.clone(), |found| found.name.clone()),
+                online: discovered.contains_key(&peer.id),
+                paired: true,
+            })
+            .collect();
+        for (id, found) in discovered.iter() {
+            if !paired.iter().any(|peer| &peer.id == id) {
+                peers.push(LanPeer {
+                    id: id.clone(),
+                    name: found.name.clone(),
+                    online: true,
+                    paired: false,
+                });
+            }
+        }
+        peers.sort_by(|a, b| a.name.cmp(&b.name).then_with(|| a.id.cmp(&b.id)));
+        Ok(peers)
+    }
+
+    fn connect(&self, peer_id: &str) -> Result<TcpStream> {
+        let addresses = self
+            .shared
+            .discovered()
+            .get(peer_id)
+            .map(|found| found.addresses.clone())
+            .ok_or_else(|| Error::Lan(format!("{peer_id} is not on the network")))?;
+        let mut last = None;
+        for address in addresses {
+            match TcpStream::connect_timeout(&address, CONNECT_TIMEOUT) {
+                Ok(stream) => return Ok(stream),
+                Err(err) => last = Some(err),
+            }
+        }
+        Err(last.map_or_else(
+            || Error::Lan(format!("{peer_id} has no reachable address")),
+            Error::from,
+        ))
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Option<Running>> {
+        self.running.lock().expect("lan state poisoned")
+    }
+}
+
+impl Shared {
+    /// Folds a browse event into the discovered devices, returning whether
+    /// they changed.
+    fn discovered_event(&self, event: ServiceEvent, own_id: &str) -> bool {
+        match event {
+            ServiceEvent::ServiceResolved(service) => {
+                let properties = &service.txt_properties;
+                let Some(id) = properties.get_property_val_str("id") else {
+                    return false;
+                };
+                if id == own_id {
+                    return false;
+                }
+                let mut addresses: Vec<SocketAddr> = service
+                    .addresses
+                    .iter()
+                    .map(|ip| SocketAddr::new(ip.to_ip_addr(), service.port))
+                    .collect();
+                // IPv4 first; link-local IPv6 often needs a scope to connect.
+                addresses.sort_by_key(|address| !address.is_ipv4());
+                let found = Discovered {
+                    fullname: service.fullname.clone(),
+                    name: properties
+                        .get_property_val_str("name")
+                        .unwrap_or(id)
+                        .to_owned(),
+                    addresses,
+                };
+                self.discovered().insert(id.to_owned(), found);
+                true
+            }
+            ServiceEvent::ServiceRemoved(_, fullname) => {
+                let mut discovered = self.discovered();
+                let before = discovered.len();
+                discovered.retain(|_, found| found.fullname != fullname);
+                discovered.len() != before
+            }
+            _ => false,
+        }
+    }
+
+    fn discovered(&self) -> MutexGuard<'_, HashMap<String, Discovered>> {
+        self.discovered.lock().expect("lan peers poisoned")
+    }
+
+    fn pending(&self) -> MutexGuard<'_, HashMap<String, Sender<bool>>> {
+        self.pending.lock().expect("lan pairings poisoned")
+    }
+}
+
+pub fn config_of(storage: &Storage) -> Result<LanConfig> {
+    match storage.meta(CONFIG_KEY)? {
+        Some(json) => Ok(serde_json::from_str(&json)?),
+        None => Ok(LanConfig::default()),
+    }
+}
+
+pub fn set_config(storage: &Storage, config: &LanConfig) -> Result<()> {
+    storage.set_meta(CONFIG_KEY, &serde_json::to_string(config)?)
+}
+
+pub fn status(app: &AppHandle) -> Result<LanStatus> {
+    let vault = vaults::primary(app)?;
+    let lan = app.state::<Lan>();
+    Ok(LanStatus {
+        running: lan.is_running(),
+        device_id: device_id(&vault.storage)?,
+        name: config_of(&vault.storage)?.name,
+        peers: lan.peers(&vault.storage)?,
+    })
+}
+
+/// Starts LAN sync at launch if it was left enabled.
+pub fn start_if_enabled(app: &AppHandle) -> Result<()> {
+    if config_of(&vaults::primary(app)?.storage)?.enabled {
+        app.state::<Lan>().start(app)?;
+    }
+    Ok(())
+}
+
+/// Forgets the pairing with `peer_id`. The peer keeps its side until it
+/// unpairs too, but can no longer sync with this device.
+pub fn unpair(storage: &Storage, peer_id: &str) -> Result<()> {
+    let mut peers = paired_peers(storage)?;
+    peers.retain(|peer| peer.id != peer_id);
+    storage.set_secret(PEERS_KEY, Some(&serde_json::to_string(&peers)?))
+}
+
+/// Answers a connection from a peer.
+fn serve(app: &AppHandle, shared: &Shared, stream: TcpStream) -> Result<()> {
+    stream.set_nonblocking(false)?;
+    let vault = vaults::primary(app)?;
+    let config = config_of(&vault.storage)?;
+    let handshake = Handshake::accept(stream, &device_id(&vault.storage)?, &config.name)?;
+    match handshake.theirs.purpose {
+        Purpose::Pair => pair(app, shared, &vault, handshake).map(drop),
+        Purpose::Sync => {
+            // Unknown devices, and any while the vault is locked, get the
+            // connection closed on them.
+            let key = pairing_key(&vault.storage, &handshake.theirs.device_id)?
+                .ok_or_else(|| Error::Lan("not paired".into()))?;
+            let Ok(_syncing) = shared.syncing.try_lock() else {
+                return Err(Error::Lan("a sync is already running".into()));
+            };
+            let peer = handshake.theirs.name.clone();
+            let mut channel = handshake.into_channel(Some(&key))?;
+            let Message::Manifest { entries } = channel.receive()? else {
+                return Err(unexpected());
+            };
+            let filter = SyncFilter::load(&vault.storage, SyncTarget::Lan)?;
+            let changes = exchange::changes_for(&vault, &entries, &filter)?;
+            channel.send(&Message::Changes { changes })?;
+            channel.send(&Message::Manifest {
+                entries: exchange::manifest(&vault, &filter)?,
+            })?;
+            let Message::Changes { changes } = channel.receive()? else {
+                return Err(unexpected());
+            };
+            let pulled = activity::from_sync(PROVIDER, Some(&peer), || {
+                exchange::apply(&vault, changes, &filter)
+            })?;
+            channel.send(&Message::Done { pulled })
+        }
+    }
+}
+
+/// The initiating side of a sync; [`serve`] has the other.
+fn pull_and_push(
+    vault: &Vault,
+    channel: &mut Channel,
+    peer: &str,
+    progress: &mut dyn FnMut(SyncProgress),
+) -> Result<SyncReport> {
+    let mut report = |stage, current, total| {
+        progress(SyncProgress {
+            provider: PROVIDER,
+            stage,
+            current,
+            total,
+        })
+    };
+    report(SyncStage::Fetch, 0, 0);
+    let filter = SyncFilter::load(&vault.storage, SyncTarget::Lan)?;
+    channel.send(&Message::Manifest {
+        entries: exchange::manifest(vault, &filter)?,
+    })?;
+    let Message::Changes { changes } = receive(channel)? else {
+        return Err(unexpected());
+    };
+    let Message::Manifest { entries } = receive(channel)? else {
+        return Err(unexpected());
+    };
+    report(SyncStage::Merge, 0, changes.len());
+    let pulled = activity::from_sync(PROVIDER, Some(peer), || {
+        exchange::apply(vault, changes, &filter)
+    })?;
+    let outgoing = exchange::changes_for(vault, &entries, &filter)?;
+    report(SyncStage::Push, 0, outgoing.len());
+    let pushed = !outgoing.is_empty();
+    channel.send(&Message::Changes { changes: outgoing })?;
+    let Message::Done { .. } = receive(channel)? else {
+        return Err(unexpected());
+    };
+    report(SyncStage::Done, 0, 0);
+    let _ = vault.synced.send(PROVIDER);
+    Ok(SyncReport {
+        pulled,
+        pushed,
+        conflicts: Vec::new(),
+    })
+}
+
+/// Runs a pairing from either end, keeping the peer's key if both people
+/// confirmed the code.
+fn pair(app: &AppHandle, shared: &Shared, vault: &Vault, handshake: Handshake) -> Result<LanPeer> {
+    // The key could not be kept anyway.
+    if vault.storage.is_locked() {
+        return Err(Error::VaultLocked);
+    }
+    let code = handshake.pairing_code();
+    let key = handshake.pairing_key();
+    let peer_id = handshake.theirs.device_id.clone();
+    let name = handshake.theirs.name.clone();
+    let mut channel = handshake.into_channel(None)?;
+    let emit = |stage| {
+        let pairing = Pairing {
+            peer_id: peer_id.clone(),
+            name: name.clone(),
+            code: code.clone(),
+            stage,
+        };
+        let _ = app.emit(PAIRING_EVENT, pairing);
+    };
+
+    let (answer, answered) = mpsc::channel();
+    {
+        // One code on screen at a time, so a device on the network cannot
+        // bury the real one under prompts of its own.
+        let mut pending = shared.pending();
+        if !pending.is_empty() {
+            return Err(Error::Lan(
+                "another pairing is waiting for an answer".into(),
+            ));
+        }
+        pending.insert(peer_id.clone(), answer);
+    }
+    emit(PairingStage::Confirm);
+    let accept = answered.recv_timeout(PAIRING_TIMEOUT).unwrap_or(false);
+    shared.pending().remove(&peer_id);
+    channel.set_timeout(PAIRING_TIMEOUT)?;
+    channel.send(&Message::Confirm { accept })?;
+    let confirmed = matches!(channel.receive(), Ok(Message::Confirm { accept: true }));
+    if !(accept && confirmed) {
+        emit(PairingStage::Declined);
+        return Err(Error::Lan("pairing was declined".into()));
+    }
+
+    let mut peers = paired_peers(&vault.storage)?;
+    peers.retain(|peer| peer.id != peer_id);
+    peers.push(PairedPeer {
+        id: peer_id.clone(),
+        name: name.clone(),
+        key: hex::encode(key),
+    });
+    vault
+        .storage
+        .set_secret(PEERS_KEY, Some(&serde_json::to_string(&peers)?))?;
+    emit(PairingStage::Paired);
+    emit_peers(app);
+    Ok(LanPeer {
+        id: peer_id,
+        name,
+        online: true,
+        paired: true,
+    })
+}
+
+/// The background sync with every paired device that is online. A peer
+/// that fails is tried again next round.
+fn sync_all(app: &AppHandle) {
+    let lan = app.state::<Lan>();
+    let Ok(vault) = vaults::primary(app) else {
+        return;
+    };
+    if vault.storage.is_locked() {
+        return;
+    }
+    let Ok(peers) = lan.peers(&vault.storage) else {
+        return;
+    };
+    for peer in peers.iter().filter(|peer| peer.paired && peer.online) {
+        let _ = lan.sync(app, &peer.id, &mut |progress| {
+            let _ = app.emit(super::PROGRESS_EVENT, progress);
+        });
+    }
+}
+
+fn emit_peers(app: &AppHandle) {
+    let Ok(vault) = vaults::primary(app) else {
+        return;
+    };
+    if let Ok(peers) = app.state::<Lan>().peers(&vault.storage) {
+        let _ = app.emit(PEERS_EVENT, peers);
+    }
+}
+
+/// A receive on the initiating side, where the peer hanging up means it
+/// turned the session down.
+fn receive(channel: &mut Channel) -> Result<Message> {
+    channel.receive().map_err(|err| match err {
+        Error::Io(err) if err.kind() == ErrorKind::UnexpectedEof => Error::Lan(
+            "the peer refused to sync; it may be busy, locked, or no longer paired".into(),
+        ),
+        err => err,
+    })
+}
+
+/// This device's id on the network, picked on first use.
+fn device_id(storage: &Storage) -> Result<String> {
+    if let Some(id) = storage.meta(DEVICE_KEY)? {
+        return Ok(id);
+    }
+    let id = uuid::Uuid::new_v4().to_string();
+    storage.set_meta(DEVICE_KEY, &id)?;
+    Ok(id)
+}
+
+/// Paired devices. Their keys are sealed, so none can be read while the
+/// vault is locked.
+fn paired_peers(storage: &Storage) -> Result<Vec<PairedPeer>> {
+    if storage.is_locked() {
+        return Ok(Vec::new());
+    }
+    match storage.secret(PEERS_KEY)? {
+        Some(json) => Ok(serde_json::from_str(&json)?),
+        None => Ok(Vec::new()),
+    }
+}
+
+fn pairing_key(storage: &Storage, peer_id: &str) -> Result<Option<[u8; 32]>> {
+    let peer = paired_peers(storage)?
+        .into_iter()
+        .find(|peer| peer.id == peer_id);
+    Ok(peer
+        .and_then(|peer| hex::decode(peer.key).ok())
+        .and_then(|key| key.try_into().ok()))
+}
+
+fn unexpected() -> Error {
+    Error::Lan("the peer sent an unexpected message".into())
+}
+
+fn mdns_error(err: mdns_sd::Error) -> Error {
+    Error::Lan(err.to_string())
+}
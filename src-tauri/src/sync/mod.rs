@@ -0,0 +1,52 @@
+#[cfg(mobile)]
+pub mod background;
+pub mod commands;
+pub mod git;
+pub mod lan;
+pub mod rules;
+pub mod s3;
+pub mod webdav;
+
+use serde::Serialize;
+
+/// Event carrying [`SyncProgress`] payloads while a sync runs.
+pub const PROGRESS_EVENT: &str = "sync-progress";
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SyncStage {
+    Fetch,
+    Merge,
+    Push,
+    Done,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncProgress {
+    pub provider: &'static str,
+    pub stage: SyncStage,
+    pub current: usize,
+    pub total: usize,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncReport {
+    /// Notes created, updated, or deleted locally by incoming changes.
+    pub pulled: usize,
+    pub pushed: bool,
+    /// Paths that changed on both sides; the remote version was kept as a
+    /// separate conflict copy next to the local one.
+    pub conflicts: Vec<String>,
+}
+
+/// Inserts `suffix` before the extension: `a/b.md` becomes `a/b<suffix>.md`.
+pub(crate) fn conflict_path(path: &str, suffix: &str) -> String {
+    match path.rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() && !ext.contains('/') => {
+            format!("{stem}{suffix}.{ext}")
+        }
+        _ => format!("{path}{suffix}"),
+    }
+}
@@ -0,0 +1,217 @@
+//! What each sync provider, and backups, leave out. Rules are tried in
+//! order and the last one that matches decides, as in a `.gitignore`, so an
+//! include after an exclude makes an exception to it. Anything no rule
+//! matches is synced.
+//!
+//! Excluded files are left alone on both sides: not sent, not fetched, and
+//! not deleted anywhere because the other side lacks them. An attachment
+//! counts as being in the folders of the notes that embed it, and a folder
+//! rule only excludes it when it excludes all of them. Size limits go by
+//! the copy on this device, so a file only the other side has is judged by
+//! where it is alone.
+//!
+//! Git merges whole branches, so for it the rules only decide what this
+//! device commits.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::collab;
+use crate::error::{Error, Result};
+use crate::files::NoteFiles;
+use crate::storage::Storage;
+
+const CONFIG_KEY: &str = "sync.rules";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SyncTarget {
+    Git,
+    Webdav,
+    S3,
+    Lan,
+    Backup,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RuleAction {
+    Include,
+    Exclude,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ItemKind {
+    /// A note, with its Markdown file and document.
+    Note,
+    Attachment,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncRule {
+    pub action: RuleAction,
+    /// The folder it covers, with its subfolders; every folder when empty.
+    #[serde(default)]
+    pub folder: String,
+    /// Notes or attachments only; both when unset.
+    #[serde(default)]
+    pub kind: Option<ItemKind>,
+    /// Only files larger than this many bytes.
+    #[serde(default)]
+    pub larger_than: Option<u64>,
+    /// Where it applies; everywhere when empty.
+    #[serde(default)]
+    pub targets: Vec<SyncTarget>,
+}
+
+impl SyncRule {
+    fn matches(&self, kind: ItemKind, size: Option<u64>, folder: Option<&str>) -> bool {
+        let in_folder = self.folder.is_empty()
+            || folder.is_some_and(|folder| {
+                folder
+                    .strip_prefix(self.folder.as_str())
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+            });
+        let large = match self.larger_than {
+            Some(limit) => size.is_some_and(|size| size > limit),
+            None => true,
+        };
+        in_folder && large && self.kind.is_none_or(|only| only == kind)
+    }
+}
+
+pub fn rules_of(storage: &Storage) -> Result<Vec<SyncRule>> {
+    match storage.meta(CONFIG_KEY)? {
+        Some(json) => Ok(serde_json::from_str(&json)?),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Replaces the rules, with folders written as the sidebar names them.
+pub fn set_rules(storage: &Storage, mut rules: Vec<SyncRule>) -> Result<Vec<SyncRule>> {
+    for rule in &mut rules {
+        rule.folder = rule.folder.trim().trim_matches('/').to_owned();
+        if rule.folder.split('/').any(|part| part == "..") {
+            return Err(Error::InvalidInput(format!(
+                "not a folder: {}",
+                rule.folder
+            )));
+        }
+    }
+    if rules.is_empty() {
+        storage.delete_meta(CONFIG_KEY)?;
+    } else {
+        storage.set_meta(CONFIG_KEY, &serde_json::to_string(&rules)?)?;
+    }
+    Ok(rules)
+}
+
+/// The rules for one provider, with what is needed to place mirror files
+/// in folders.
+pub struct SyncFilter {
+    rules: Vec<SyncRule>,
+    /// Folder of each note by id.
+    folders: HashMap<String, String>,
+    /// Folder of each note by its mirror path.
+    paths: HashMap<String, String>,
+    /// Folders of the notes embedding each attachment.
+    embedded_in: HashMap<String, Vec<String>>,
+}
+
+impl SyncFilter {
+    pub fn load(storage: &Storage, target: SyncTarget) -> Result<Self> {
+        let mut rules = rules_of(storage)?;
+        rules.retain(|rule| rule.targets.is_empty() || rule.targets.contains(&target));
+        let mut filter = Self {
+            rules,
+            folders: HashMap::new(),
+            paths: HashMap::new(),
+            embedded_in: HashMap::new(),
+        };
+        if filter.rules.is_empty() {
+            return Ok(filter);
+        }
+        let conn = storage.conn();
+        let mut stmt = conn.prepare(
+            "SELECT notes.id, notes.folder, note_files.path FROM notes
+             LEFT JOIN note_files ON note_files.note_id = notes.id",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, Option<String>>(2)?,
+            ))
+        })?;
+        for row in rows {
+            let (id, folder, path) = row?;
+            if let Some(path) = path {
+                filter.paths.insert(path, folder.clone());
+            }
+            filter.folders.insert(id, folder);
+        }
+        let mut stmt = conn.prepare(
+            "SELECT attachment_refs.path, notes.folder FROM attachment_refs
+             JOIN notes ON notes.id = attachment_refs.note_id",
+        )?;
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        for row in rows {
+            let (path, folder): (String, String) = row?;
+            filter.embedded_in.entry(path).or_default().push(folder);
+        }
+        Ok(filter)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Whether a rule goes by the size of notes, which then have to be read.
+    pub fn limits_note_size(&self) -> bool {
+        self.rules
+            .iter()
+            .any(|rule| rule.larger_than.is_some() && rule.kind != Some(ItemKind::Attachment))
+    }
+
+    /// Whether a note in `folder` is synced. `size` is its body's, in bytes.
+    pub fn allows_note(&self, folder: &str, size: Option<u64>) -> bool {
+        self.allows(ItemKind::Note, size, Some(folder))
+    }
+
+    /// Whether the mirror file `rel` is synced. `size` is the local copy's,
+    /// if there is one.
+    pub fn allows_path(&self, rel: &str, size: Option<u64>) -> bool {
+        if self.rules.is_empty() {
+            return true;
+        }
+        if NoteFiles::is_attachment_path(rel) {
+            return match self.embedded_in.get(rel) {
+                Some(folders) => folders
+                    .iter()
+                    .any(|folder| self.allows(ItemKind::Attachment, size, Some(folder))),
+                None => self.allows(ItemKind::Attachment, size, None),
+            };
+        }
+        if collab::is_state_path(rel) {
+            // `.collab/<note id>/<file>`; a document goes where its note does.
+            let folder = rel.split('/').nth(1).and_then(|id| self.folders.get(id));
+            return self.allows(ItemKind::Note, None, folder.map(String::as_str));
+        }
+        let folder = match self.paths.get(rel) {
+            Some(folder) => folder.as_str(),
+            None => rel.rsplit_once('/').map_or("", |(dir, _)| dir),
+        };
+        self.allows(ItemKind::Note, size, Some(folder))
+    }
+
+    fn allows(&self, kind: ItemKind, size: Option<u64>, folder: Option<&str>) -> bool {
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| rule.matches(kind, size, folder))
+            .is_none_or(|rule| rule.action == RuleAction::Include)
+    }
+}
@@ -0,0 +1,336 @@
+use chrono::Utc;
+use hmac::{Hmac, KeyInit, Mac};
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, AUTHORIZATION, ETAG, HOST};
+use reqwest::header::{IF_MATCH, IF_NONE_MATCH};
+use reqwest::{Client, Method, Response, StatusCode, Url};
+use sha2::{Digest, Sha256};
+
+use super::S3Config;
+use crate::error::{Error, Result};
+
+/// Objects larger than this go up in parts of this size; S3 wants every
+/// part but the last to be at least 5 MiB.
+const PART_SIZE: usize = 8 * 1024 * 1024;
+/// Everything but RFC 3986 unreserved characters is escaped, as SigV4
+/// expects.
+const UNRESERVED: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+/// What an upload requires of the object it replaces.
+#[derive(Clone, Copy)]
+pub enum Condition<'a> {
+    Always,
+    /// Still at this ETag.
+    Matches(&'a str),
+    /// Not there at all.
+    Absent,
+}
+
+/// Requests against one bucket, signed with AWS Signature Version 4.
+pub struct Bucket<'a> {
+    http: &'a Client,
+    endpoint: Url,
+    bucket: String,
+    path_style: bool,
+    region: String,
+    access_key_id: String,
+    secret_key: String,
+}
+
+impl<'a> Bucket<'a> {
+    pub fn new(http: &'a Client, config: &S3Config, secret_key: String) -> Result<Self> {
+        let endpoint = Url::parse(&config.endpoint)
+            .map_err(|err| Error::InvalidInput(format!("invalid S3 endpoint: {err}")))?;
+        if endpoint.host_str().is_none() {
+            return Err(Error::InvalidInput("the S3 endpoint has no host".into()));
+        }
+        Ok(Self {
+            http,
+            endpoint,
+            bucket: config.bucket.clone(),
+            path_style: config.path_style,
+            region: config.region.clone(),
+            access_key_id: config.access_key_id.clone(),
+            secret_key,
+        })
+    }
+
+    /// The object at `key` and its ETag, or `None` if there is none.
+    pub async fn get(&self, key: &str) -> Result<Option<(Vec<u8>, String)>> {
+        let response = self
+            .send(Method::GET, key, &[], HeaderMap::new(), Vec::new())
+            .await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let response = checked(response).await?;
+        let etag = etag(response.headers()).unwrap_or_default();
+        Ok(Some((response.bytes().await?.to_vec(), etag)))
+    }
+
+    /// Uploads `body` in one request if `condition` holds. Returns whether
+    /// it did.
+    pub async fn put(&self, key: &str, body: Vec<u8>, condition: Condition<'_>) -> Result<bool> {
+        let mut headers = HeaderMap::new();
+        match condition {
+            Condition::Always => {}
+            Condition::Matches(etag) => {
+                headers.insert(IF_MATCH, header_value(etag)?);
+            }
+            Condition::Absent => {
+                headers.insert(IF_NONE_MATCH, HeaderValue::from_static("*"));
+            }
+        }
+        let response = self.send(Method::PUT, key, &[], headers, body).await?;
+        if response.status() == StatusCode::PRECONDITION_FAILED {
+            return Ok(false);
+        }
+        checked(response).await?;
+        Ok(true)
+    }
+
+    /// Uploads `body`, in parts if it is large.
+    pub async fn upload(&self, key: &str, body: Vec<u8>) -> Result<()> {
+        if body.len() <= PART_SIZE {
+            self.put(key, body, Condition::Always).await?;
+            return Ok(());
+        }
+        let response = self
+            .send(
+                Method::POST,
+                key,
+                &[("uploads", "")],
+                HeaderMap::new(),
+                Vec::new(),
+            )
+            .await?;
+        let xml = checked(response).await?.text().await?;
+        let upload_id = xml_text(&xml, "UploadId")
+            .ok_or_else(|| Error::S3("no upload id in the multipart response".into()))?;
+        match self.upload_parts(key, &upload_id, &body).await {
+            Ok(()) => Ok(()),
+            Err(err) => {
+                // Parts of an abandoned upload are billed until aborted.
+                let _ = self
+                    .send(
+                        Method::DELETE,
+                        key,
+                        &[("uploadId", &upload_id)],
+                        HeaderMap::new(),
+                        Vec::new(),
+                    )
+                    .await;
+                Err(err)
+            }
+        }
+    }
+
+    pub async fn delete(&self, key: &str) -> Result<()> {
+        let response = self
+            .send(Method::DELETE, key, &[], HeaderMap::new(), Vec::new())
+            .await?;
+        if response.status() != StatusCode::NOT_FOUND {
+            checked(response).await?;
+        }
+        Ok(())
+    }
+
+    async fn upload_parts(&self, key: &str, upload_id: &str, body: &[u8]) -> Result<()> {
+        let mut complete = String::from("<CompleteMultipartUpload>");
+        for (i, part) in body.chunks(PART_SIZE).enumerate() {
+            let number = (i + 1).to_string();
+            let response = self
+                .send(
+                    Method::PUT,
+                    key,
+                    &[("partNumber", &number), ("uploadId", upload_id)],
+                    HeaderMap::new(),
+                    part.to_vec(),
+                )
+                .await?;
+            let etag = etag(checked(response).await?.headers())
+                .ok_or_else(|| Error::S3(format!("no ETag for part {number}")))?;
+            complete.push_str(&format!(
+                "<Part><PartNumber>{number}</PartNumber><ETag>{}</ETag></Part>",
+                escape(&etag)
+            ));
+        }
+        complete.push_str("</CompleteMultipartUpload>");
+        let response = self
+            .send(
+                Method::POST,
+                key,
+                &[("uploadId", upload_id)],
+                HeaderMap::new(),
+                complete.into_bytes(),
+            )
+            .await?;
+        // Completing can fail after the 200 has been sent.
+        let xml = checked(response).await?.text().await?;
+        match xml_text(&xml, "Code") {
+            Some(code) => Err(Error::S3(code)),
+            None => Ok(()),
+        }
+    }
+
+    async fn send(
+        &self,
+        method: Method,
+        key: &str,
+        query: &[(&str, &str)],
+        mut headers: HeaderMap,
+        body: Vec<u8>,
+    ) -> Result<Response> {
+        let url = self.url(key, query)?;
+        self.sign(&method, &url, &mut headers, &body)?;
+        let response = self
+            .http
+            .request(method, url)
+            .headers(headers)
+            .body(body)
+            .send()
+            .await?;
+        Ok(response)
+    }
+
+    fn url(&self, key: &str, query: &[(&str, &str)]) -> Result<Url> {
+        let key = key
+            .split('/')
+            .map(|part| utf8_percent_encode(part, UNRESERVED).to_string())
+            .collect::<Vec<_>>()
+            .join("/");
+        let mut url = self.endpoint.clone();
+        let base = url.path().trim_end_matches('/').to_owned();
+        if self.path_style {
+            url.set_path(&format!(
+                "{base}/{}/{key}",
+                utf8_percent_encode(&self.bucket, UNRESERVED)
+            ));
+        } else {
+            let host = format!("{}.{}", self.bucket, url.host_str().unwrap_or_default());
+            url.set_host(Some(&host))
+                .map_err(|err| Error::InvalidInput(format!("invalid S3 bucket name: {err}")))?;
+            url.set_path(&format!("{base}/{key}"));
+        }
+        let mut pairs: Vec<String> = query
+            .iter()
+            .map(|(name, value)| {
+                format!(
+                    "{}={}",
+                    utf8_percent_encode(name, UNRESERVED),
+                    utf8_percent_encode(value, UNRESERVED)
+                )
+            })
+            .collect();
+        pairs.sort();
+        url.set_query((!pairs.is_empty()).then(|| pairs.join("&")).as_deref());
+        Ok(url)
+    }
+
+    /// Adds the SigV4 headers for a request to `url`. Only the host, date
+    /// and payload hash are signed, which is all S3 requires.
+    fn sign(&self, method: &Method, url: &Url, headers: &mut HeaderMap, body: &[u8]) -> Result<()> {
+        let now = Utc::now();
+        let timestamp = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let date = now.format("%Y%m%d").to_string();
+        let payload_hash = hex::encode(Sha256::digest(body));
+        let host = match url.port() {
+            Some(port) => format!("{}:{port}", url.host_str().unwrap_or_default()),
+            None => url.host_str().unwrap_or_default().to_owned(),
+        };
+
+        let signed_headers = "host;x-amz-content-sha256;x-amz-date";
+        let canonical = format!(
+            "{method}\n{}\n{}\nhost:{host}\nx-amz-content-sha256:{payload_hash}\nx-amz-date:{timestamp}\n\n{signed_headers}\n{payload_hash}",
+            url.path(),
+            url.query().unwrap_or_default(),
+        );
+        let scope = format!("{date}/{}/s3/aws4_request", self.region);
+        let to_sign = format!(
+            "AWS4-HMAC-SHA256\n{timestamp}\n{scope}\n{}",
+            hex::encode(Sha256::digest(canonical.as_bytes()))
+        );
+        let mut key = hmac(
+            format!("AWS4{}", self.secret_key).as_bytes(),
+            date.as_bytes(),
+        );
+        for part in [self.region.as_str(), "s3", "aws4_request"] {
+            key = hmac(&key, part.as_bytes());
+        }
+        let signature = hex::encode(hmac(&key, to_sign.as_bytes()));
+
+        headers.insert(HOST, header_value(&host)?);
+        headers.insert(
+            HeaderName::from_static("x-amz-content-sha256"),
+            header_value(&payload_hash)?,
+        );
+        headers.insert(
+            HeaderName::from_static("x-amz-date"),
+            header_value(&timestamp)?,
+        );
+        headers.insert(
+            AUTHORIZATION,
+            header_value(&format!(
+                "AWS4-HMAC-SHA256 Credential={}/{scope}, SignedHeaders={signed_headers}, Signature={signature}",
+                self.access_key_id
+            ))?,
+        );
+        Ok(())
+    }
+}
+
+fn hmac(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC takes keys of any length");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// Passes successful responses through; anything else becomes an error
+/// carrying the S3 error code where the body has one.
+async fn checked(response: Response) -> Result<Response> {
+    if response.status().is_success() {
+        return Ok(response);
+    }
+    let status = response.status();
+    let path = response.url().path().to_owned();
+    let body = response.text().await.unwrap_or_default();
+    Err(Error::S3(match xml_text(&body, "Code") {
+        Some(code) => format!("{status} {code} {path}"),
+        None => format!("{status} {path}"),
+    }))
+}
+
+fn etag(headers: &HeaderMap) -> Option<String> {
+    headers.get(ETAG)?.to_str().ok().map(str::to_owned)
+}
+
+fn header_value(value: &str) -> Result<HeaderValue> {
+    HeaderValue::from_str(value).map_err(|err| Error::S3(err.to_string()))
+}
+
+/// Text of the first `tag` element in `xml`.
+fn xml_text(xml: &str, tag: &str) -> Option<String> {
+    let mut reader = Reader::from_str(xml);
+    let mut inside = false;
+    loop {
+        match reader.read_event().ok()? {
+            Event::Start(start) => inside = start.local_name().as_ref() == tag,
+            Event::Text(text) if inside => return Some(text.into_inner().into_owned()),
+            Event::End(_) => inside = false,
+            Event::Eof => return None,
+            _ => {}
+        }
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
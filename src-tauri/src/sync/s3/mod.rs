@@ -0,0 +1,577 @@
+mod client;
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fs;
+use std::io::ErrorKind;
+use std::sync::Arc;
+use std::time::Duration;
+
+use reqwest::Client;
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tauri::{AppHandle, Emitter};
+use tokio::sync::Mutex;
+
+use super::rules::{SyncFilter, SyncTarget};
+use super::webdav::ERROR_EVENT;
+use super::{conflict_path, SyncProgress, SyncReport, SyncStage, PROGRESS_EVENT};
+use crate::activity;
+use crate::atomic;
+use crate::collab;
+use crate::crypto::{KeyParams, VaultKey};
+use crate::error::{Error, Result};
+use crate::files::NoteFiles;
+use crate::storage::Storage;
+use crate::vault::Vault;
+use client::{Bucket, Condition};
+
+const CONFIG_KEY: &str = "s3.config";
+const SECRET_KEY: &str = "s3.secret";
+const PASSPHRASE_KEY: &str = "s3.passphrase";
+const PROVIDER: &str = "s3";
+/// Object holding the key derivation parameters, the only one in the clear.
+const KEYS_OBJECT: &str = "keys.json";
+const MANIFEST_OBJECT: &str = "manifest";
+const OBJECTS_DIR: &str = "objects";
+/// Sealed into [`KEYS_OBJECT`] to tell a wrong passphrase from a right one.
+const KEY_CHECK: &str = "notesdesktop";
+/// Syncs that lose the race for the manifest start over this many times.
+const ATTEMPTS: usize = 3;
+const DEFAULT_INTERVAL_MINUTES: u64 = 15;
+const IDLE_POLL: Duration = Duration::from_secs(60);
+const TIMEOUT: Duration = Duration::from_secs(120);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct S3Config {
+    /// Service URL, e.g. `https://s3.eu-central-1.amazonaws.com` or a MinIO
+    /// server's address.
+    pub endpoint: String,
+    pub region: String,
+    pub bucket: String,
+    /// Key prefix inside the bucket, so one bucket can hold several vaults.
+    #[serde(default)]
+    pub prefix: String,
+    pub access_key_id: String,
+    /// Address the bucket as `<endpoint>/<bucket>` rather than as a
+    /// subdomain. MinIO and most self-hosted servers need this.
+    #[serde(default)]
+    pub path_style: bool,
+    /// Minutes between background syncs; 0 turns them off.
+    #[serde(default = "default_interval")]
+    pub interval_minutes: u64,
+}
+
+/// [`KEYS_OBJECT`].
+#[derive(Serialize, Deserialize)]
+struct Keys {
+    params: KeyParams,
+    check: String,
+}
+
+/// Every synced path and the object holding its current content. The
+/// manifest is sealed like everything else, so the bucket shows neither
+/// names nor contents.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+struct Manifest {
+    files: BTreeMap<String, Object>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Object {
+    /// SHA-256 of the plaintext.
+    hash: String,
+    /// Object name under [`OBJECTS_DIR`]; random, and new for every upload.
+    name: String,
+}
+
+/// Mirrors the note files, their documents and the attachments to an
+/// S3-compatible bucket, end-to-end encrypted with a sync passphrase.
+///
+/// The manifest lists what the bucket holds. Each sync compares it and the
+/// local files with the hashes recorded at the last sync, like WebDAV sync
+/// compares versions, then uploads, downloads and deletes what changed and
+/// writes the manifest back only if nobody else did in the meantime.
+/// Objects are never overwritten, so a sync that loses that race leaves
+/// the bucket as it was.
+pub struct S3 {
+    http: Client,
+    running: Mutex<()>,
+}
+
+impl S3 {
+    pub fn new() -> Result<Self> {
+        Ok(Self {
+            http: Client::builder().timeout(TIMEOUT).build()?,
+            running: Mutex::new(()),
+        })
+    }
+
+    /// Checks that the bucket can be read and the passphrase opens it, or
+    /// sets the passphrase up on an empty prefix, then saves the settings.
+    /// `None` for either secret keeps the stored one.
+    pub async fn configure(
+        &self,
+        storage: &Storage,
+        mut config: S3Config,
+        secret_key: Option<&str>,
+        passphrase: Option<&str>,
+    ) -> Result<S3Config> {
+        config.endpoint = config.endpoint.trim().to_owned();
+        config.prefix = normalize_prefix(&config.prefix);
+        let secret_key = match secret_key {
+            Some(secret_key) => secret_key.to_owned(),
+            None => storage.secret(SECRET_KEY)?.unwrap_or_default(),
+        };
+        let passphrase = match passphrase {
+            Some(passphrase) => passphrase.to_owned(),
+            None => storage.secret(PASSPHRASE_KEY)?.ok_or_else(|| {
+                Error::InvalidInput("a passphrase is needed to encrypt the bucket".into())
+            })?,
+        };
+        let bucket = Bucket::new(&self.http, &config, secret_key.clone())?;
+        unlock(&bucket, &config.prefix, &passphrase, true).await?;
+
+        let _running = self.running.lock().await;
+        // Hashes recorded against another bucket mean nothing here.
+        let moved = config_of(storage)?.is_none_or(|old| {
+            old.endpoint != config.endpoint
+                || old.bucket != config.bucket
+                || old.prefix != config.prefix
+        });
+        if moved {
+            storage.conn().execute("DELETE FROM s3_state", [])?;
+        }
+        storage.set_meta(CONFIG_KEY, &serde_json::to_string(&config)?)?;
+        storage.set_secret(SECRET_KEY, Some(&secret_key))?;
+        storage.set_secret(PASSPHRASE_KEY, Some(&passphrase))?;
+        Ok(config)
+    }
+
+    /// Uploads local changes, downloads remote ones, and propagates
+    /// deletions in both directions.
+    pub async fn sync(
+        &self,
+        vault: &Vault,
+        progress: &mut (dyn FnMut(SyncProgress) + Send),
+    ) -> Result<SyncReport> {
+        let _running = self.running.lock().await;
+        let config = config_of(&vault.storage)?
+            .ok_or_else(|| Error::InvalidInput("S3 sync is not configured".into()))?;
+        let secret_key = vault.storage.secret(SECRET_KEY)?.unwrap_or_default();
+        let passphrase = vault
+            .storage
+            .secret(PASSPHRASE_KEY)?
+            .ok_or_else(|| Error::InvalidInput("S3 sync has no passphrase".into()))?;
+        let bucket = Bucket::new(&self.http, &config, secret_key)?;
+        let key = unlock(&bucket, &config.prefix, &passphrase, false).await?;
+
+        let mut report = SyncReport::default();
+        for _ in 0..ATTEMPTS {
+            let mut run = Run {
+                vault,
+                bucket: &bucket,
+                key: &key,
+                prefix: &config.prefix,
+                report: &mut report,
+                uploaded: Vec::new(),
+                replaced: Vec::new(),
+                settled: Vec::new(),
+            };
+            if run.sync(progress).await? {
+                report.pulled +=
+                    activity::from_sync(PROVIDER, None, || collab::merge_peers(vault))?;
+                progress(SyncProgress {
+                    provider: PROVIDER,
+                    stage: SyncStage::Done,
+                    current: 1,
+                    total: 1,
+                });
+                let _ = vault.synced.send(PROVIDER);
+                return Ok(report);
+            }
+        }
+        Err(Error::S3(
+            "the bucket kept changing during the sync; try again".into(),
+        ))
+    }
+}
+
+pub fn config_of(storage: &Storage) -> Result<Option<S3Config>> {
+    match storage.meta(CONFIG_KEY)? {
+        Some(json) => Ok(Some(serde_json::from_str(&json)?)),
+        None => Ok(None),
+    }
+}
+
+fn default_interval() -> u64 {
+    DEFAULT_INTERVAL_MINUTES
+}
+
+/// Runs a sync every configured interval for as long as the vault is open.
+/// Failures are reported through [`ERROR_EVENT`]; a locked vault is skipped.
+pub fn spawn_periodic(app: AppHandle, vault: Arc<Vault>) {
+    tauri::async_runtime::spawn(async move {
+        while !vault.is_closed() {
+            let interval = config_of(&vault.storage)
+                .ok()
+                .flatten()
+                .map(|config| config.interval_minutes)
+                .filter(|minutes| *minutes > 0);
+            let Some(minutes) = interval else {
+                tokio::time::sleep(IDLE_POLL).await;
+                continue;
+            };
+            tokio::time::sleep(Duration::from_secs(minutes * 60)).await;
+
+            if vault.is_closed()
+                || vault.storage.is_locked()
+                || !matches!(config_of(&vault.storage), Ok(Some(_)))
+            {
+                continue;
+            }
+            let result = vault
+                .s3
+                .sync(&vault, &mut |progress| {
+                    let _ = app.emit(PROGRESS_EVENT, progress);
+                })
+                .await;
+            if let Err(err) = result {
+                tracing::warn!(error = %err, "periodic S3 sync failed");
+                let _ = app.emit(ERROR_EVENT, err.to_string());
+            }
+        }
+    });
+}
+
+/// One attempt at a sync.
+struct Run<'a> {
+    vault: &'a Vault,
+    bucket: &'a Bucket<'a>,
+    key: &'a VaultKey,
+    prefix: &'a str,
+    report: &'a mut SyncReport,
+    /// Objects uploaded by this attempt, removed again if it loses the race.
+    uploaded: Vec<String>,
+    /// Objects the new manifest no longer points to.
+    replaced: Vec<String>,
+    /// Base hashes to record, or forget, once the manifest is written.
+    settled: Vec<(String, Option<String>)>,
+}
+
+impl Run<'_> {
+    /// Returns `false` if another device wrote the manifest first, in which
+    /// case nothing was committed and the sync has to start over.
+    async fn sync(&mut self, progress: &mut (dyn FnMut(SyncProgress) + Send)) -> Result<bool> {
+        progress(SyncProgress {
+            provider: PROVIDER,
+            stage: SyncStage::Fetch,
+            current: 0,
+            total: 1,
+        });
+        let manifest_key = format!("{}{MANIFEST_OBJECT}", self.prefix);
+        let (remote, etag) = match self.bucket.get(&manifest_key).await? {
+            Some((sealed, etag)) => {
+                let manifest: Manifest = serde_json::from_slice(&self.key.open_bytes(&sealed)?)?;
+                (manifest, Some(etag))
+            }
+            None => (Manifest::default(), None),
+        };
+        let filter = SyncFilter::load(&self.vault.storage, SyncTarget::S3)?;
+        let (local, skipped) = local_files(self.vault, &filter)?;
+        let base = load_state(&self.vault.storage)?;
+
+        let paths: BTreeSet<&String> = local
+            .keys()
+            .chain(remote.files.keys())
+            .chain(base.keys())
+            .filter(|rel| !skipped.contains(*rel) && filter.allows_path(rel, None))
+            .collect();
+        let total = paths.len();
+        let mut manifest = remote.clone();
+        for (i, rel) in paths.into_iter().enumerate() {
+            progress(SyncProgress {
+                provider: PROVIDER,
+                stage: SyncStage::Merge,
+                current: i,
+                total,
+            });
+            self.reconcile(
+                &mut manifest,
+                rel,
+                local.get(rel),
+                remote.files.get(rel),
+                base.get(rel),
+            )
+            .await?;
+        }
+
+        if manifest != remote {
+            progress(SyncProgress {
+                provider: PROVIDER,
+                stage: SyncStage::Push,
+                current: 0,
+                total: 1,
+            });
+            let sealed = self.key.seal_bytes(&serde_json::to_vec(&manifest)?)?;
+            let condition = match &etag {
+                Some(etag) => Condition::Matches(etag),
+                None => Condition::Absent,
+            };
+            if !self.bucket.put(&manifest_key, sealed, condition).await? {
+                for name in std::mem::take(&mut self.uploaded) {
+                    let _ = self.bucket.delete(&self.object_key(&name)).await;
+                }
+                return Ok(false);
+            }
+            self.report.pushed = true;
+        }
+        for (rel, hash) in std::mem::take(&mut self.settled) {
+            match hash {
+                Some(hash) => save_state(&self.vault.storage, &rel, &hash)?,
+                None => forget_state(&self.vault.storage, &rel)?,
+            }
+        }
+        // Left behind if this fails, but nothing points to them any more.
+        for name in std::mem::take(&mut self.replaced) {
+            let _ = self.bucket.delete(&self.object_key(&name)).await;
+        }
+        Ok(true)
+    }
+
+    async fn reconcile(
+        &mut self,
+        manifest: &mut Manifest,
+        rel: &str,
+        local: Option<&String>,
+        remote: Option<&Object>,
+        base: Option<&String>,
+    ) -> Result<()> {
+        let local_changed = base != local;
+        let remote_changed = base != remote.map(|object| &object.hash);
+        match (local, remote) {
+            (None, None) => forget_state(&self.vault.storage, rel),
+            _ if !local_changed && !remote_changed => Ok(()),
+            (Some(_), None) if base.is_some() && !local_changed => {
+                // Deleted elsewhere.
+                if activity::from_sync(PROVIDER, None, || self.vault.file_removed(rel))?.is_none() {
+                    match fs::remove_file(self.vault.files.absolute(rel)) {
+                        Err(err) if err.kind() != ErrorKind::NotFound => return Err(err.into()),
+                        _ => {}
+                    }
+                }
+                self.report.pulled += 1;
+                forget_state(&self.vault.storage, rel)
+            }
+            (None, Some(object)) if base.is_some() && !remote_changed => {
+                // Deleted here.
+                manifest.files.remove(rel);
+                self.replaced.push(object.name.clone());
+                self.settled.push((rel.to_owned(), None));
+                Ok(())
+            }
+            (Some(hash), None) => self.upload(manifest, rel, hash).await,
+            (None, Some(object)) => self.download(rel, object).await,
+            (Some(hash), Some(_)) if !remote_changed => self.upload(manifest, rel, hash).await,
+            (Some(_), Some(object)) if !local_changed => self.download(rel, object).await,
+            (Some(hash), Some(object)) if *hash == object.hash => {
+                save_state(&self.vault.storage, rel, hash)
+            }
+            (Some(hash), Some(object)) => {
+                let theirs = self.fetch(object).await?;
+                let copy = self.conflict_copy(rel);
+                write_file(self.vault, &copy, &theirs)?;
+                activity::from_sync(PROVIDER, None, || self.vault.import_file(&copy))?;
+                self.report.conflicts.push(rel.to_owned());
+                self.upload(manifest, rel, hash).await
+            }
+        }
+    }
+
+    async fn upload(&mut self, manifest: &mut Manifest, rel: &str, hash: &str) -> Result<()> {
+        let content = fs::read(self.vault.files.absolute(rel))?;
+        let name = uuid::Uuid::new_v4().simple().to_string();
+        self.bucket
+            .upload(&self.object_key(&name), self.key.seal_bytes(&content)?)
+            .await?;
+        self.uploaded.push(name.clone());
+        let object = Object {
+            hash: hash.to_owned(),
+            name,
+        };
+        if let Some(old) = manifest.files.insert(rel.to_owned(), object) {
+            self.replaced.push(old.name);
+        }
+        self.settled.push((rel.to_owned(), Some(hash.to_owned())));
+        Ok(())
+    }
+
+    async fn download(&mut self, rel: &str, object: &Object) -> Result<()> {
+        let content = self.fetch(object).await?;
+        write_file(self.vault, rel, &content)?;
+        if activity::from_sync(PROVIDER, None, || self.vault.import_file(rel))?.is_some() {
+            self.report.pulled += 1;
+        }
+        save_state(&self.vault.storage, rel, &object.hash)
+    }
+
+    async fn fetch(&self, object: &Object) -> Result<Vec<u8>> {
+        let (sealed, _) = self
+            .bucket
+            .get(&self.object_key(&object.name))
+            .await?
+            .ok_or_else(|| Error::S3(format!("object {} is missing", object.name)))?;
+        let content = self.key.open_bytes(&sealed)?;
+        if hash(&content) != object.hash {
+            return Err(Error::S3(format!("object {} is corrupt", object.name)));
+        }
+        Ok(content)
+    }
+
+    fn object_key(&self, name: &str) -> String {
+        format!("{}{OBJECTS_DIR}/{name}", self.prefix)
+    }
+
+    fn conflict_copy(&self, rel: &str) -> String {
+        (1..)
+            .map(|n| match n {
+                1 => conflict_path(rel, " (conflict s3)"),
+                n => conflict_path(rel, &format!(" (conflict s3 {n})")),
+            })
+            .find(|copy| !self.vault.files.absolute(copy).exists())
+            .expect("unbounded range")
+    }
+}
+
+/// The key everything in the bucket is sealed with. On a prefix that has
+/// none yet, `create` sets one up from `passphrase`.
+async fn unlock(
+    bucket: &Bucket<'_>,
+    prefix: &str,
+    passphrase: &str,
+    create: bool,
+) -> Result<VaultKey> {
+    let keys_key = format!("{prefix}{KEYS_OBJECT}");
+    if let Some((json, _)) = bucket.get(&keys_key).await? {
+        let keys: Keys = serde_json::from_slice(&json)?;
+        let key = keys.params.derive_key(passphrase)?;
+        if key.open(&keys.check)? != KEY_CHECK {
+            return Err(Error::WrongPassword);
+        }
+        return Ok(key);
+    }
+    if !create {
+        return Err(Error::S3(format!("{keys_key} is missing from the bucket")));
+    }
+    let params = KeyParams::generate();
+    let key = params.derive_key(passphrase)?;
+    let keys = Keys {
+        params,
+        check: key.seal(KEY_CHECK)?,
+    };
+    // Another device setting up the same prefix at once keeps its keys.
+    if !bucket
+        .put(&keys_key, serde_json::to_vec(&keys)?, Condition::Absent)
+        .await?
+    {
+        return Box::pin(unlock(bucket, prefix, passphrase, false)).await;
+    }
+    Ok(key)
+}
+
+/// Hashes of the note files, note documents and attachments the rules
+/// let through, and the paths of those they leave out. Attachments are
+/// named by their hash already, so they are not read.
+fn local_files(
+    vault: &Vault,
+    filter: &SyncFilter,
+) -> Result<(HashMap<String, String>, HashSet<String>)> {
+    let mut files = HashMap::new();
+    let mut skipped = HashSet::new();
+    for rel in vault
+        .files
+        .list()?
+        .into_iter()
+        .chain(collab::state_files(vault)?)
+    {
+        let path = vault.files.absolute(&rel);
+        if !filter.allows_path(&rel, Some(fs::metadata(&path)?.len())) {
+            skipped.insert(rel);
+            continue;
+        }
+        files.insert(rel, hash(&fs::read(path)?));
+    }
+    let attachments = match fs::read_dir(vault.files.attachments_dir()) {
+        Ok(attachments) => attachments,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok((files, skipped)),
+        Err(err) => return Err(err.into()),
+    };
+    for entry in attachments {
+        let entry = entry?;
+        let path = entry.path();
+        let Some(rel) = vault.files.relative(&path) else {
+            continue;
+        };
+        if !NoteFiles::is_attachment_path(&rel) {
+            continue;
+        }
+        if !filter.allows_path(&rel, Some(entry.metadata()?.len())) {
+            skipped.insert(rel);
+            continue;
+        }
+        let stem = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        files.insert(rel, stem);
+    }
+    Ok((files, skipped))
+}
+
+fn write_file(vault: &Vault, rel: &str, content: &[u8]) -> Result<()> {
+    let path = vault.files.absolute(rel);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    atomic::write(&path, content)?;
+    Ok(())
+}
+
+/// Without trailing slashes a prefix is a name, not a folder.
+fn normalize_prefix(prefix: &str) -> String {
+    let prefix = prefix.trim().trim_matches('/');
+    if prefix.is_empty() {
+        String::new()
+    } else {
+        format!("{prefix}/")
+    }
+}
+
+fn hash(content: &[u8]) -> String {
+    hex::encode(Sha256::digest(content))
+}
+
+fn load_state(storage: &Storage) -> Result<HashMap<String, String>> {
+    let conn = storage.conn();
+    let mut stmt = conn.prepare("SELECT path, hash FROM s3_state")?;
+    let state = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<rusqlite::Result<_>>()?;
+    Ok(state)
+}
+
+fn save_state(storage: &Storage, rel: &str, hash: &str) -> Result<()> {
+    storage.conn().execute(
+        "INSERT OR REPLACE INTO s3_state (path, hash) VALUES (?1, ?2)",
+        params![rel, hash],
+    )?;
+    Ok(())
+}
+
+fn forget_state(storage: &Storage, rel: &str) -> Result<()> {
+    storage
+        .conn()
+        .execute("DELETE FROM s3_state WHERE path = ?1", [rel])?;
+    Ok(())
+}
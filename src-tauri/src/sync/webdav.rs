@@ -0,0 +1,680 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fs;
+use std::sync::Arc;
+use std::time::Duration;
+
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use reqwest::header::{HeaderMap, CONTENT_TYPE, ETAG, IF_MATCH, IF_NONE_MATCH};
+use reqwest::{Client, Method, RequestBuilder, Response, StatusCode, Url};
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tauri::{AppHandle, Emitter};
+use tokio::sync::Mutex;
+
+use super::rules::{SyncFilter, SyncTarget};
+use super::{conflict_path, SyncProgress, SyncReport, SyncStage, PROGRESS_EVENT};
+use crate::activity;
+use crate::atomic;
+use crate::collab;
+use crate::error::{Error, Result};
+use crate::storage::Storage;
+use crate::vault::Vault;
+use crate::xml;
+
+/// Event carrying the error message when a background sync fails.
+pub const ERROR_EVENT: &str = "sync-error";
+
+const CONFIG_KEY: &str = "webdav.config";
+const PASSWORD_KEY: &str = "webdav.password";
+const PROVIDER: &str = "webdav";
+const DEFAULT_INTERVAL_MINUTES: u64 = 15;
+/// How often the background task looks again while sync is switched off.
+const IDLE_POLL: Duration = Duration::from_secs(60);
+const TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Everything but RFC 3986 unreserved characters is escaped in a path segment.
+const SEGMENT: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+const PROPFIND_BODY: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<d:propfind xmlns:d="DAV:">
+  <d:prop><d:resourcetype/><d:getetag/><d:getlastmodified/></d:prop>
+</d:propfind>"#;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WebDavConfig {
+    /// Collection the mirror is synced into, e.g. a Nextcloud folder's DAV URL.
+    pub url: String,
+    pub username: String,
+    /// Minutes between background syncs; 0 turns them off.
+    pub interval_minutes: u64,
+}
+
+/// Mirrors the Markdown note files to a WebDAV collection.
+///
+/// Every synced path remembers its content hash and remote version (the
+/// ETag, or Last-Modified for servers that send none) as of the last sync.
+/// A side whose value moved since then has changed; when both did, the
+/// remote file is kept as a conflict copy and the local one wins the path.
+pub struct WebDav {
+    http: Client,
+    running: Mutex<()>,
+}
+
+impl WebDav {
+    pub fn new() -> Result<Self> {
+        Ok(Self {
+            http: Client::builder().timeout(TIMEOUT).build()?,
+            running: Mutex::new(()),
+        })
+    }
+
+    /// Checks that `url` is a collection the credentials can read,
+    /// then saves it. `password` of `None` keeps the stored one.
+    pub async fn configure(
+        &self,
+        storage: &Storage,
+        url: &str,
+        username: &str,
+        password: Option<&str>,
+        interval_minutes: Option<u64>,
+    ) -> Result<WebDavConfig> {
+        let config = WebDavConfig {
+            url: url.trim().to_owned(),
+            username: username.to_owned(),
+            interval_minutes: interval_minutes.unwrap_or(DEFAULT_INTERVAL_MINUTES),
+        };
+        let password = match password {
+            Some(password) => Some(password.to_owned()),
+            None => storage.secret(PASSWORD_KEY)?,
+        };
+        let remote = Remote::new(&self.http, &config, password.clone())?;
+        let root = remote.propfind("", true, "0").await?;
+        if !root.first().is_some_and(|entry| entry.collection) {
+            return Err(Error::WebDav(format!("{} is not a collection", config.url)));
+        }
+
+        let _running = self.running.lock().await;
+        // Versions recorded against another server mean nothing here.
+        if config_of(storage)?.is_none_or(|old| old.url != config.url) {
+            storage.conn().execute("DELETE FROM webdav_state", [])?;
+        }
+        storage.set_meta(CONFIG_KEY, &serde_json::to_string(&config)?)?;
+        storage.set_secret(PASSWORD_KEY, password.as_deref())?;
+        Ok(config)
+    }
+
+    /// Uploads local changes, downloads remote ones, and propagates deletions
+    /// in both directions.
+    pub async fn sync(
+        &self,
+        vault: &Arc<Vault>,
+        progress: &mut (dyn FnMut(SyncProgress) + Send),
+    ) -> Result<SyncReport> {
+        let _running = self.running.lock().await;
+        let config = config_of(&vault.storage)?
+            .ok_or_else(|| Error::InvalidInput("WebDAV sync is not configured".into()))?;
+        let password = vault.storage.secret(PASSWORD_KEY)?;
+        let mut remote = Remote::new(&self.http, &config, password)?;
+
+        progress(SyncProgress {
+            provider: PROVIDER,
+            stage: SyncStage::Fetch,
+            current: 0,
+            total: 1,
+        });
+        let remote_files = remote.list().await?;
+        let filter = SyncFilter::load(&vault.storage, SyncTarget::Webdav)?;
+        let mut local_files = HashMap::new();
+        let mut skipped = HashSet::new();
+        for rel in vault
+            .files
+            .list()?
+            .into_iter()
+            .chain(collab::state_files(vault)?)
+        {
+            let path = vault.files.absolute(&rel);
+            if !filter.allows_path(&rel, Some(fs::metadata(&path)?.len())) {
+                skipped.insert(rel);
+                continue;
+            }
+            local_files.insert(rel, hash(&fs::read(path)?));
+        }
+        let base = load_state(&vault.storage)?;
+
+        let paths: BTreeSet<&String> = local_files
+            .keys()
+            .chain(remote_files.keys())
+            .chain(base.keys())
+            .filter(|rel| !skipped.contains(*rel) && filter.allows_path(rel, None))
+            .collect();
+        let total = paths.len();
+        let mut run = Run {
+            vault,
+            remote,
+            report: SyncReport::default(),
+        };
+        for (i, rel) in paths.into_iter().enumerate() {
+            progress(SyncProgress {
+                provider: PROVIDER,
+                stage: SyncStage::Merge,
+                current: i,
+                total,
+            });
+            run.reconcile(
+                rel,
+                local_files.get(rel),
+                remote_files.get(rel),
+                base.get(rel),
+            )
+            .await?;
+        }
+
+        run.report.pulled += run.blocking(collab::merge_peers).await?;
+
+        progress(SyncProgress {
+            provider: PROVIDER,
+            stage: SyncStage::Done,
+            current: 1,
+            total: 1,
+        });
+        let _ = vault.synced.send(PROVIDER);
+        Ok(run.report)
+    }
+}
+
+pub fn config_of(storage: &Storage) -> Result<Option<WebDavConfig>> {
+    match storage.meta(CONFIG_KEY)? {
+        Some(json) => Ok(Some(serde_json::from_str(&json)?)),
+        None => Ok(None),
+    }
+}
+
+/// Runs a sync every configured interval for as long as the app is up.
+/// Failures are reported through [`ERROR_EVENT`]; a locked vault is skipped.
+pub fn spawn_periodic(app: AppHandle, vault: Arc<Vault>) {
+    tauri::async_runtime::spawn(async move {
+        while !vault.is_closed() {
+            let interval = config_of(&vault.storage)
+                .ok()
+                .flatten()
+                .map(|config| config.interval_minutes)
+                .filter(|minutes| *minutes > 0);
+            let Some(minutes) = interval else {
+                tokio::time::sleep(IDLE_POLL).await;
+                continue;
+            };
+            tokio::time::sleep(Duration::from_secs(minutes * 60)).await;
+
+            if vault.is_closed()
+                || vault.storage.is_locked()
+                || !matches!(config_of(&vault.storage), Ok(Some(_)))
+            {
+                continue;
+            }
+            let result = vault
+                .webdav
+                .sync(&vault, &mut |progress| {
+                    let _ = app.emit(PROGRESS_EVENT, progress);
+                })
+                .await;
+            if let Err(err) = result {
+                tracing::warn!(error = %err, "periodic WebDAV sync failed");
+                let _ = app.emit(ERROR_EVENT, err.to_string());
+            }
+        }
+    });
+}
+
+/// What a path looked like on both sides after it last synced.
+struct Base {
+    local_hash: String,
+    remote_version: String,
+}
+
+struct Run<'a> {
+    vault: &'a Arc<Vault>,
+    remote: Remote<'a>,
+    report: SyncReport,
+}
+
+impl Run<'_> {
+    async fn reconcile(
+        &mut self,
+        rel: &str,
+        local: Option<&String>,
+        remote: Option<&String>,
+        base: Option<&Base>,
+    ) -> Result<()> {
+        let local_changed = base.map(|b| &b.local_hash) != local;
+        let remote_changed = base.map(|b| &b.remote_version) != remote;
+        match (local, remote) {
+            (None, None) => forget_state(&self.vault.storage, rel),
+            _ if !local_changed && !remote_changed => Ok(()),
+            (Some(_), None) if base.is_some() && !local_changed => {
+                // Deleted on the server.
+                let removed = rel.to_owned();
+                if self
+                    .blocking(move |vault| vault.file_removed(&removed))
+                    .await?
+                    .is_none()
+                {
+                    fs::remove_file(self.vault.files.absolute(rel))?;
+                }
+                self.report.pulled += 1;
+                forget_state(&self.vault.storage, rel)
+            }
+            (Some(hash), None) => self.upload(rel, hash, None).await,
+            (None, Some(version)) if base.is_some() && !remote_changed => {
+                // Deleted here.
+                if self.remote.delete(rel, version).await? {
+                    self.report.pushed = true;
+                    forget_state(&self.vault.storage, rel)?;
+                }
+                Ok(())
+            }
+            (None, Some(version)) => self.download(rel, version).await,
+            (Some(hash), Some(version)) if !remote_changed => {
+                self.upload(rel, hash, Some(version)).await
+            }
+            (Some(_), Some(version)) if !local_changed => self.download(rel, version).await,
+            (Some(hash), Some(version)) => {
+                let theirs = self.remote.get(rel).await?;
+                if self::hash(theirs.as_bytes()) == *hash {
+                    return save_state(&self.vault.storage, rel, hash, version);
+                }
+                let copy = self.conflict_copy(rel);
+                self.write_file(&copy, &theirs)?;
+                self.blocking(move |vault| vault.import_file(&copy)).await?;
+                self.report.conflicts.push(rel.to_owned());
+                self.upload(rel, hash, Some(version)).await
+            }
+        }
+    }
+
+    async fn upload(&mut self, rel: &str, hash: &str, expected: Option<&str>) -> Result<()> {
+        let content = fs::read(self.vault.files.absolute(rel))?;
+        // A version mismatch means the file changed on the server since it
+        // was listed; the next sync picks that up as a remote change.
+        if let Some(version) = self.remote.put(rel, content, expected).await? {
+            save_state(&self.vault.storage, rel, hash, &version)?;
+            self.report.pushed = true;
+        }
+        Ok(())
+    }
+
+    async fn download(&mut self, rel: &str, version: &str) -> Result<()> {
+        let content = self.remote.get(rel).await?;
+        self.write_file(rel, &content)?;
+        let imported = rel.to_owned();
+        if self
+            .blocking(move |vault| vault.import_file(&imported))
+            .await?
+            .is_some()
+        {
+            self.report.pulled += 1;
+        }
+        save_state(&self.vault.storage, rel, &hash(content.as_bytes()), version)
+    }
+
+    fn write_file(&self, rel: &str, content: &str) -> Result<()> {
+        let path = self.vault.files.absolute(rel);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        atomic::write(&path, content.as_bytes())?;
+        Ok(())
+    }
+
+    /// Runs `apply` as a change from this sync, on a thread of its own as
+    /// it writes to the store and may commit the mirror.
+    async fn blocking<T: Send + 'static>(
+        &self,
+        apply: impl FnOnce(&Vault) -> Result<T> + Send + 'static,
+    ) -> Result<T> {
+        let vault = Arc::clone(self.vault);
+        tauri::async_runtime::spawn_blocking(move || {
+            activity::from_sync(PROVIDER, None, || apply(&vault))
+        })
+        .await?
+    }
+
+    fn conflict_copy(&self, rel: &str) -> String {
+        (1..)
+            .map(|n| match n {
+                1 => conflict_path(rel, " (conflict webdav)"),
+                n => conflict_path(rel, &format!(" (conflict webdav {n})")),
+            })
+            .find(|copy| !self.vault.files.absolute(copy).exists())
+            .expect("unbounded range")
+    }
+}
+
+/// One sync's connection to the server.
+struct Remote<'a> {
+    http: &'a Client,
+    base: Url,
+    username: String,
+    password: Option<String>,
+    /// Collections known to exist, so uploads only create missing parents.
+    dirs: HashSet<String>,
+}
+
+impl<'a> Remote<'a> {
+    fn new(http: &'a Client, config: &WebDavConfig, password: Option<String>) -> Result<Self> {
+        let mut base = Url::parse(&config.url)
+            .map_err(|err| Error::InvalidInput(format!("invalid WebDAV URL: {err}")))?;
+        if !base.path().ends_with('/') {
+            let path = format!("{}/", base.path());
+            base.set_path(&path);
+        }
+        Ok(Self {
+            http,
+            base,
+            username: config.username.clone(),
+            password,
+            dirs: HashSet::new(),
+        })
+    }
+
+    fn request(&self, method: Method, rel: &str, collection: bool) -> Result<RequestBuilder> {
+        let mut path = rel
+            .split('/')
+            .filter(|part| !part.is_empty())
+            .map(|part| utf8_percent_encode(part, SEGMENT).to_string())
+            .collect::<Vec<_>>()
+            .join("/");
+        if collection && !path.is_empty() {
+            path.push('/');
+        }
+        let url = self
+            .base
+            .join(&path)
+            .map_err(|err| Error::WebDav(err.to_string()))?;
+        Ok(self
+            .http
+            .request(method, url)
+            .basic_auth(&self.username, self.password.as_deref()))
+    }
+
+    /// Note files and note documents below the base collection, mapped to
+    /// their versions.
+    async fn list(&mut self) -> Result<HashMap<String, String>> {
+        let mut files = HashMap::new();
+        let mut pending = vec![String::new()];
+        while let Some(dir) = pending.pop() {
+            for entry in self.propfind(&dir, true, "1").await? {
+                let Some(rel) = self.relative(&entry.href) else {
+                    continue;
+                };
+                let hidden = rel
+                    .split('/')
+                    .any(|part| part.starts_with('.') && part != collab::DIR);
+                if rel == dir || hidden {
+                    continue;
+                }
+                if entry.collection {
+                    self.dirs.insert(rel.clone());
+                    pending.push(rel);
+                } else if crate::files::NoteFiles::is_note_path(&rel) || collab::is_state_path(&rel)
+                {
+                    if let Some(version) = entry.version() {
+                        files.insert(rel, version);
+                    }
+                }
+            }
+        }
+        Ok(files)
+    }
+
+    async fn propfind(&self, rel: &str, collection: bool, depth: &str) -> Result<Vec<Entry>> {
+        let method = Method::from_bytes(b"PROPFIND").expect("valid method");
+        let response = self
+            .request(method, rel, collection)?
+            .header("Depth", depth)
+            .header(CONTENT_TYPE, "application/xml; charset=utf-8")
+            .body(PROPFIND_BODY)
+            .send()
+            .await?;
+        let xml = checked(response)?.text().await?;
+        parse_multistatus(&xml)
+    }
+
+    async fn get(&self, rel: &str) -> Result<String> {
+        let response = self.request(Method::GET, rel, false)?.send().await?;
+        Ok(checked(response)?.text().await?)
+    }
+
+    /// Uploads `content`, unless the remote file no longer matches
+    /// `expected` (or exists at all when `expected` is `None`). Returns the
+    /// new remote version.
+    async fn put(
+        &mut self,
+        rel: &str,
+        content: Vec<u8>,
+        expected: Option<&str>,
+    ) -> Result<Option<String>> {
+        self.create_parents(rel).await?;
+        let mut request = self.request(Method::PUT, rel, false)?.body(content);
+        request = match expected {
+            Some(version) if is_etag(version) => request.header(IF_MATCH, version),
+            Some(_) => request,
+            None => request.header(IF_NONE_MATCH, "*"),
+        };
+        let response = request.send().await?;
+        if response.status() == StatusCode::PRECONDITION_FAILED {
+            return Ok(None);
+        }
+        if let Some(etag) = strong_etag(checked(response)?.headers()) {
+            return Ok(Some(etag));
+        }
+        // Fall back to asking, so the version has the same form as listings.
+        let entries = self.propfind(rel, false, "0").await?;
+        Ok(entries.first().and_then(Entry::version))
+    }
+
+    /// Deletes the remote file if it is still at `expected`. Returns whether
+    /// it is gone.
+    async fn delete(&self, rel: &str, expected: &str) -> Result<bool> {
+        let mut request = self.request(Method::DELETE, rel, false)?;
+        if is_etag(expected) {
+            request = request.header(IF_MATCH, expected);
+        }
+        let response = request.send().await?;
+        match response.status() {
+            StatusCode::PRECONDITION_FAILED => Ok(false),
+            StatusCode::NOT_FOUND => Ok(true),
+            _ => checked(response).map(|_| true),
+        }
+    }
+
+    async fn create_parents(&mut self, rel: &str) -> Result<()> {
+        let Some((parent, _)) = rel.rsplit_once('/') else {
+            return Ok(());
+        };
+        let mut dir = String::new();
+        for part in parent.split('/') {
+            if !dir.is_empty() {
+                dir.push('/');
+            }
+            dir.push_str(part);
+            if self.dirs.contains(&dir) {
+                continue;
+            }
+            let method = Method::from_bytes(b"MKCOL").expect("valid method");
+            let response = self.request(method, &dir, true)?.send().await?;
+            // 405 means the collection is already there.
+            if response.status() != StatusCode::METHOD_NOT_ALLOWED {
+                checked(response)?;
+            }
+            self.dirs.insert(dir.clone());
+        }
+        Ok(())
+    }
+
+    /// Maps an `href` from a multistatus response to a path relative to the
+    /// base collection, or `None` if it lies outside it. Whatever the server
+    /// answers, the path has to stay below the collection: empty, `.` and
+    /// `..` segments are turned away rather than resolved.
+    fn relative(&self, href: &str) -> Option<String> {
+        let path = match Url::parse(href) {
+            Ok(url) => url.path().to_owned(),
+            Err(_) => href.to_owned(),
+        };
+        let path = percent_decode_str(&path).decode_utf8().ok()?;
+        let root = percent_decode_str(self.base.path()).decode_utf8().ok()?;
+        let mut rel = path.strip_prefix(root.as_ref())?;
+        if !root.ends_with('/') && !rel.is_empty() {
+            rel = rel.strip_prefix('/')?;
+        }
+        let rel = rel.strip_suffix('/').unwrap_or(rel);
+        let outside = rel.split('/').any(|segment| {
+            matches!(segment, "." | "..") || segment.contains('\\') || segment.contains('\0')
+        });
+        let empty = !rel.is_empty() && rel.split('/').any(str::is_empty);
+        (!outside && !empty).then(|| rel.to_owned())
+    }
+}
+
+/// One `<response>` of a PROPFIND.
+#[derive(Default)]
+struct Entry {
+    href: String,
+    collection: bool,
+    etag: String,
+    modified: String,
+}
+
+impl Entry {
+    fn version(&self) -> Option<String> {
+        [&self.etag, &self.modified]
+            .into_iter()
+            .map(|value| value.trim())
+            .find(|value| !value.is_empty())
+            .map(str::to_owned)
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Prop {
+    Href,
+    Etag,
+    Modified,
+}
+
+fn parse_multistatus(xml: &str) -> Result<Vec<Entry>> {
+    let xml_error = |err: quick_xml::Error| Error::WebDav(format!("bad PROPFIND response: {err}"));
+    let mut reader = Reader::from_str(xml);
+    let mut entries = Vec::new();
+    let mut entry: Option<Entry> = None;
+    let mut prop: Option<Prop> = None;
+    loop {
+        let text = match reader.read_event().map_err(xml_error)? {
+            Event::Start(tag) => {
+                match tag.local_name().as_ref() {
+                    "response" => entry = Some(Entry::default()),
+                    "href" => prop = Some(Prop::Href),
+                    "getetag" => prop = Some(Prop::Etag),
+                    "getlastmodified" => prop = Some(Prop::Modified),
+                    "collection" => entry.iter_mut().for_each(|e| e.collection = true),
+                    _ => {}
+                }
+                continue;
+            }
+            Event::Empty(tag) => {
+                if tag.local_name().as_ref() == "collection" {
+                    entry.iter_mut().for_each(|e| e.collection = true);
+                }
+                continue;
+            }
+            Event::End(tag) => {
+                if tag.local_name().as_ref() == "response" {
+                    entries.extend(entry.take());
+                }
+                prop = None;
+                continue;
+            }
+            Event::Text(text) => text.into_inner().into_owned(),
+            Event::CData(data) => data.into_inner().into_owned(),
+            Event::GeneralRef(reference) => xml::resolve_entity(&reference)
+                .map(String::from)
+                .unwrap_or_default(),
+            Event::Eof => break,
+            _ => continue,
+        };
+        if let (Some(entry), Some(prop)) = (entry.as_mut(), prop) {
+            match prop {
+                Prop::Href => entry.href.push_str(&text),
+                Prop::Etag => entry.etag.push_str(&text),
+                Prop::Modified => entry.modified.push_str(&text),
+            }
+        }
+    }
+    Ok(entries)
+}
+
+fn checked(response: Response) -> Result<Response> {
+    if response.status().is_success() {
+        Ok(response)
+    } else {
+        Err(Error::WebDav(format!(
+            "{} {}",
+            response.status(),
+            response.url().path()
+        )))
+    }
+}
+
+/// Weak ETags cannot be used in `If-Match`, and Last-Modified values never can.
+fn is_etag(version: &str) -> bool {
+    version.starts_with('"')
+}
+
+fn strong_etag(headers: &HeaderMap) -> Option<String> {
+    let etag = headers.get(ETAG)?.to_str().ok()?;
+    is_etag(etag).then(|| etag.to_owned())
+}
+
+fn hash(content: &[u8]) -> String {
+    hex::encode(Sha256::digest(content))
+}
+
+fn load_state(storage: &Storage) -> Result<HashMap<String, Base>> {
+    let conn = storage.conn();
+    let mut stmt = conn.prepare("SELECT path, local_hash, remote_version FROM webdav_state")?;
+    let state = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get(0)?,
+                Base {
+                    local_hash: row.get(1)?,
+                    remote_version: row.get(2)?,
+                },
+            ))
+        })?
+        .collect::<rusqlite::Result<_>>()?;
+    Ok(state)
+}
+
+fn save_state(storage: &Storage, rel: &str, local_hash: &str, remote_version: &str) -> Result<()> {
+    storage.conn().execute(
+        "INSERT OR REPLACE INTO webdav_state (path, local_hash, remote_version)
+         VALUES (?1, ?2, ?3)",
+        params![rel, local_hash, remote_version],
+    )?;
+    Ok(())
+}
+
+fn forget_state(storage: &Storage, rel: &str) -> Result<()> {
+    storage
+        .conn()
+        .execute("DELETE FROM webdav_state WHERE path = ?1", [rel])?;
+    Ok(())
+}
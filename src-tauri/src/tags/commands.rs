@@ -0,0 +1,46 @@
+use std::collections::HashSet;
+
+use super::TagCount;
+use crate::error::{Error, Result};
+use crate::storage::NoteSummary;
+use crate::vaults::Current;
+
+#[tauri::command]
+pub async fn list_tags(vault: Current) -> Result<Vec<TagCount>> {
+    Ok(vault.tags.counts())
+}
+
+/// Renames `from` to `to` in every note, nested tags included, returning
+/// how many notes changed.
+#[tauri::command]
+pub async fn rename_tag(vault: Current, from: String, to: String) -> Result<usize> {
+    let from = super::normalize(&from)?;
+    vault.rename_tags(&[from], &super::normalize(&to)?)
+}
+
+/// Folds every tag in `tags` into `into`, returning how many notes changed.
+#[tauri::command]
+pub async fn merge_tags(vault: Current, tags: Vec<String>, into: String) -> Result<usize> {
+    let into = super::normalize(&into)?;
+    let mut from = Vec::with_capacity(tags.len());
+    for tag in &tags {
+        let tag = super::normalize(tag)?;
+        if tag != into && !from.contains(&tag) {
+            from.push(tag);
+        }
+    }
+    if from.is_empty() {
+        return Err(Error::InvalidInput("no tags to merge".into()));
+    }
+    vault.rename_tags(&from, &into)
+}
+
+/// Notes tagged `tag` or one of its nested tags, most recently edited first.
+#[tauri::command]
+pub async fn notes_with_tag(vault: Current, tag: String) -> Result<Vec<NoteSummary>> {
+    let tag = super::normalize(&tag)?;
+    let ids: HashSet<String> = vault.tags.notes_with(&tag).into_iter().collect();
+    let mut notes = vault.storage.list_notes(None)?;
+    notes.retain(|note| ids.contains(&note.id));
+    Ok(notes)
+}
@@ -0,0 +1,217 @@
+pub mod commands;
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use serde::Serialize;
+
+use crate::error::{Error, Result};
+use crate::markdown::rewrite_prose;
+use crate::metadata::FrontMatter;
+use crate::storage::Note;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TagCount {
+    pub tag: String,
+    pub count: usize,
+}
+
+/// Tags of every note, from front matter and inline `#tags`.
+///
+/// Kept in memory only, like the search index of an encrypted vault, since
+/// tags come from note bodies. [`crate::vault::Vault`] keeps it in step.
+#[derive(Default)]
+pub struct TagIndex {
+    notes: RwLock<HashMap<String, Vec<String>>>,
+}
+
+impl TagIndex {
+    pub fn index_note(&self, note: &Note) {
+        let tags = extract(&note.body);
+        let mut notes = self.write();
+        if tags.is_empty() {
+            notes.remove(&note.id);
+        } else {
+            notes.insert(note.id.clone(), tags);
+        }
+    }
+
+    pub fn remove_note(&self, id: &str) {
+        self.write().remove(id);
+    }
+
+    pub fn rebuild(&self, notes: &[Note]) {
+        let index = notes
+            .iter()
+            .map(|note| (note.id.clone(), extract(&note.body)))
+            .filter(|(_, tags)| !tags.is_empty())
+            .collect();
+        *self.write() = index;
+    }
+
+    pub fn clear(&self) {
+        self.write().clear();
+    }
+
+    /// Every tag with the number of notes carrying it, sorted by name.
+    pub fn counts(&self) -> Vec<TagCount> {
+        let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+        let notes = self.read();
+        for tag in notes.values().flatten() {
+            *counts.entry(tag).or_default() += 1;
+        }
+        counts
+            .into_iter()
+            .map(|(tag, count)| TagCount {
+                tag: tag.to_owned(),
+                count,
+            })
+            .collect()
+    }
+
+    /// The tags of note `id`.
+    pub fn of(&self, id: &str) -> Vec<String> {
+        self.read().get(id).cloned().unwrap_or_default()
+    }
+
+    /// Ids of notes tagged `tag` or one of its nested `tag/...` tags.
+    pub fn notes_with(&self, tag: &str) -> Vec<String> {
+        self.read()
+            .iter()
+            .filter(|(_, tags)| tags.iter().any(|t| is_within(t, tag)))
+            .map(|(id, _)| id.clone())
+            .collect()
+    }
+
+    fn read(&self) -> RwLockReadGuard<'_, HashMap<String, Vec<String>>> {
+        self.notes.read().expect("tag index poisoned")
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, HashMap<String, Vec<String>>> {
+        self.notes.write().expect("tag index poisoned")
+    }
+}
+
+/// The tags of a note body, front matter first, each once.
+pub fn extract(body: &str) -> Vec<String> {
+    let (front_matter, markdown) = FrontMatter::split(body);
+    let mut tags = front_matter.map(|fm| fm.tags()).unwrap_or_default();
+    rewrite_prose(markdown, |text| {
+        for range in inline_tags(text) {
+            tags.push(text[range].to_owned());
+        }
+        String::new()
+    });
+    let mut seen = Vec::with_capacity(tags.len());
+    tags.retain(|tag| {
+        let new = !seen.contains(tag);
+        if new {
+            seen.push(tag.clone());
+        }
+        new
+    });
+    tags
+}
+
+/// `body` with `from` and its nested tags renamed to `to`, in the front
+/// matter and inline, or `None` if it does not use them.
+pub fn rename_in(body: &str, from: &str, to: &str) -> Option<String> {
+    let (front_matter, markdown) = FrontMatter::split(body);
+    let mut changed = false;
+
+    let front_matter = front_matter.map(|mut fm| {
+        let tags = fm.tags();
+        if tags.iter().any(|tag| is_within(tag, from)) {
+            let mut renamed: Vec<String> = Vec::with_capacity(tags.len());
+            for tag in tags {
+                let tag = rename(&tag, from, to).unwrap_or(tag);
+                if !renamed.contains(&tag) {
+                    renamed.push(tag);
+                }
+            }
+            fm.set_tags(&renamed);
+            changed = true;
+        }
+        fm
+    });
+
+    let markdown = rewrite_prose(markdown, |text| {
+        let mut out = String::with_capacity(text.len());
+        let mut last = 0;
+        for range in inline_tags(text) {
+            if let Some(renamed) = rename(&text[range.clone()], from, to) {
+                out.push_str(&text[last..range.start]);
+                out.push_str(&renamed);
+                last = range.end;
+                changed = true;
+            }
+        }
+        out.push_str(&text[last..]);
+        out
+    });
+
+    changed.then(|| {
+        let block = front_matter.map(|fm| fm.render()).unwrap_or_default();
+        format!("{block}{markdown}")
+    })
+}
+
+/// `tag` without a leading `#`, checked to be usable inline.
+pub fn normalize(tag: &str) -> Result<String> {
+    let tag = tag.trim().trim_start_matches('#').trim_end_matches('/');
+    if tag.is_empty() || !tag.chars().all(is_tag_char) || tag.chars().all(|c| c.is_ascii_digit()) {
+        return Err(Error::InvalidInput(format!("not a valid tag: {tag:?}")));
+    }
+    Ok(tag.to_owned())
+}
+
+fn rename(tag: &str, from: &str, to: &str) -> Option<String> {
+    if tag == from {
+        return Some(to.to_owned());
+    }
+    let rest = tag.strip_prefix(from)?.strip_prefix('/')?;
+    Some(format!("{to}/{rest}"))
+}
+
+fn is_within(tag: &str, parent: &str) -> bool {
+    tag == parent
+        || tag
+            .strip_prefix(parent)
+            .is_some_and(|rest| rest.starts_with('/'))
+}
+
+/// Byte ranges of the inline `#tags` in `text`, without the `#`. A tag
+/// starts a word, may nest with `/`, and is not just a number, so headings,
+/// issue numbers and URL fragments are left alone.
+fn inline_tags(text: &str) -> Vec<std::ops::Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut previous: Option<char> = None;
+    let mut chars = text.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        let starts_word = previous.is_none_or(|p| p.is_whitespace() || p == '(');
+        previous = Some(c);
+        if c != '#' || !starts_word {
+            continue;
+        }
+        let start = i + 1;
+        let mut end = start;
+        while let Some(&(j, next)) = chars.peek() {
+            if !is_tag_char(next) {
+                break;
+            }
+            end = j + next.len_utf8();
+            previous = Some(next);
+            chars.next();
+        }
+        let tag = text[start..end].trim_end_matches('/');
+        if !tag.is_empty() && !tag.chars().all(|c| c.is_ascii_digit()) {
+            ranges.push(start..start + tag.len());
+        }
+    }
+    ranges
+}
+
+fn is_tag_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '_' | '-' | '/')
+}
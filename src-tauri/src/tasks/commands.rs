@@ -0,0 +1,43 @@
+use chrono::NaiveDate;
+
+use super::{Task, TaskCount, TaskFilter, DUE_FORMAT};
+use crate::error::{Error, Result};
+use crate::storage::NotePatch;
+use crate::vaults::Current;
+
+/// Checklist items across all notes, for the "All tasks" view.
+#[tauri::command]
+pub async fn list_tasks(vault: Current, filter: Option<TaskFilter>) -> Result<Vec<Task>> {
+    let filter = filter.unwrap_or_default();
+    if let Some(before) = &filter.due_before {
+        NaiveDate::parse_from_str(before, DUE_FORMAT)
+            .map_err(|_| Error::InvalidInput(format!("not a date: {before}")))?;
+    }
+    Ok(vault.tasks.list(&filter))
+}
+
+/// Checks or unchecks the task on 1-based `line` of a note and returns it
+/// as it is now.
+#[tauri::command]
+pub async fn toggle_task(vault: Current, note_id: String, line: usize) -> Result<Task> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let note = vault.storage.get_note(&note_id)?;
+        let body = super::toggle(&note.body, line)?;
+        let patch = NotePatch {
+            body: Some(body),
+            ..Default::default()
+        };
+        let note = vault.edit_note(&note_id, patch)?;
+        super::extract(&note)
+            .into_iter()
+            .find(|task| task.line == line)
+            .ok_or_else(|| Error::InvalidInput(format!("line {line} is not a task")))
+    })
+    .await?
+}
+
+/// Open and completed task counts of every note that has tasks.
+#[tauri::command]
+pub async fn task_counts(vault: Current) -> Result<Vec<TaskCount>> {
+    Ok(vault.tasks.counts())
+}
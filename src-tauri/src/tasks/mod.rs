@@ -0,0 +1,241 @@
+pub mod commands;
+
+use std::collections::HashMap;
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+use crate::metadata::FrontMatter;
+use crate::storage::Note;
+
+const DUE_MARKER: &str = "@due(";
+pub(crate) const DUE_FORMAT: &str = "%Y-%m-%d";
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Task {
+    pub note_id: String,
+    pub note_title: String,
+    /// 1-based line of the item in the note body, front matter included.
+    pub line: usize,
+    /// The item's text without its checkbox or due date.
+    pub text: String,
+    pub done: bool,
+    /// From `@due(YYYY-MM-DD)`, in that form.
+    pub due: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct TaskFilter {
+    /// Only open (`false`) or only completed (`true`) tasks.
+    pub done: Option<bool>,
+    pub note_id: Option<String>,
+    /// Only tasks due on or before this day, as `YYYY-MM-DD`.
+    pub due_before: Option<String>,
+    /// Case-insensitive text the task has to contain.
+    pub query: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TaskCount {
+    pub note_id: String,
+    pub open: usize,
+    pub done: usize,
+}
+
+/// Checklist items of every note, kept in memory like [`crate::tags::TagIndex`]
+/// since they come from note bodies.
+#[derive(Default)]
+pub struct TaskIndex {
+    notes: RwLock<HashMap<String, Vec<Task>>>,
+}
+
+impl TaskIndex {
+    pub fn index_note(&self, note: &Note) {
+        let tasks = extract(note);
+        let mut notes = self.write();
+        if tasks.is_empty() {
+            notes.remove(&note.id);
+        } else {
+            notes.insert(note.id.clone(), tasks);
+        }
+    }
+
+    pub fn remove_note(&self, id: &str) {
+        self.write().remove(id);
+    }
+
+    pub fn rebuild(&self, notes: &[Note]) {
+        let index = notes
+            .iter()
+            .map(|note| (note.id.clone(), extract(note)))
+            .filter(|(_, tasks)| !tasks.is_empty())
+            .collect();
+        *self.write() = index;
+    }
+
+    pub fn clear(&self) {
+        self.write().clear();
+    }
+
+    /// Tasks matching `filter`: those with a due date first, soonest first,
+    /// then the rest by note and line.
+    pub fn list(&self, filter: &TaskFilter) -> Vec<Task> {
+        let query = filter.query.as_deref().map(str::to_lowercase);
+        let mut tasks: Vec<Task> = self
+            .read()
+            .values()
+            .flatten()
+            .filter(|task| filter.done.is_none_or(|done| task.done == done))
+            .filter(|task| filter.note_id.as_ref().is_none_or(|id| task.note_id == *id))
+            .filter(|task| {
+                filter
+                    .due_before
+                    .as_ref()
+                    .is_none_or(|before| task.due.as_ref().is_some_and(|due| due <= before))
+            })
+            .filter(|task| {
+                query
+                    .as_deref()
+                    .is_none_or(|query| task.text.to_lowercase().contains(query))
+            })
+            .cloned()
+            .collect();
+        tasks.sort_by(|a, b| {
+            (a.due.is_none(), &a.due, a.note_title.to_lowercase(), a.line).cmp(&(
+                b.due.is_none(),
+                &b.due,
+                b.note_title.to_lowercase(),
+                b.line,
+            ))
+        });
+        tasks
+    }
+
+    /// Open and completed tasks of each note that has any.
+    pub fn counts(&self) -> Vec<TaskCount> {
+        let mut counts: Vec<TaskCount> = self
+            .read()
+            .iter()
+            .map(|(id, tasks)| {
+                let done = tasks.iter().filter(|task| task.done).count();
+                TaskCount {
+                    note_id: id.clone(),
+                    open: tasks.len() - done,
+                    done,
+                }
+            })
+            .collect();
+        counts.sort_by(|a, b| a.note_id.cmp(&b.note_id));
+        counts
+    }
+
+    fn read(&self) -> RwLockReadGuard<'_, HashMap<String, Vec<Task>>> {
+        self.notes.read().expect("task index poisoned")
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, HashMap<String, Vec<Task>>> {
+        self.notes.write().expect("task index poisoned")
+    }
+}
+
+/// The checklist items in a note, skipping front matter and code blocks.
+pub fn extract(note: &Note) -> Vec<Task> {
+    let (_, markdown) = FrontMatter::split(&note.body);
+    let skipped = note.body[..note.body.len() - markdown.len()]
+        .matches('\n')
+        .count();
+    let mut tasks = Vec::new();
+    let mut fence: Option<&str> = None;
+    for (i, line) in markdown.lines().enumerate() {
+        let trimmed = line.trim_start();
+        let marker = ["```", "~~~"]
+            .into_iter()
+            .find(|marker| trimmed.starts_with(marker));
+        match (fence, marker) {
+            (None, Some(marker)) => fence = Some(marker),
+            (Some(open), Some(marker)) if open == marker => fence = None,
+            _ => {}
+        }
+        if fence.is_some() || marker.is_some() {
+            continue;
+        }
+        let Some((done, rest)) = checkbox(line) else {
+            continue;
+        };
+        let (text, due) = split_due(rest);
+        tasks.push(Task {
+            note_id: note.id.clone(),
+            note_title: note.title.clone(),
+            line: skipped + i + 1,
+            text,
+            done,
+            due,
+        });
+    }
+    tasks
+}
+
+/// `body` with the checkbox on 1-based `line` flipped.
+pub fn toggle(body: &str, line: usize) -> Result<String> {
+    let not_a_task = || Error::InvalidInput(format!("line {line} is not a task"));
+    let mut lines: Vec<&str> = body.split_inclusive('\n').collect();
+    let target = line
+        .checked_sub(1)
+        .and_then(|i| lines.get(i).copied())
+        .ok_or_else(not_a_task)?;
+    let (done, _) = checkbox(target).ok_or_else(not_a_task)?;
+    let mark = target.find('[').ok_or_else(not_a_task)? + 1;
+    let toggled = format!(
+        "{}{}{}",
+        &target[..mark],
+        if done { ' ' } else { 'x' },
+        &target[mark + 1..]
+    );
+    lines[line - 1] = &toggled;
+    Ok(lines.concat())
+}
+
+/// Whether `line` is a `- [ ]` or `- [x]` list item, and its text.
+fn checkbox(line: &str) -> Option<(bool, &str)> {
+    let rest = line.trim_start();
+    let rest = rest
+        .strip_prefix(['-', '*', '+'])
+        .or_else(|| {
+            let digits = rest.find(|c: char| !c.is_ascii_digit())?;
+            (digits > 0).then(|| rest[digits..].strip_prefix(['.', ')']))?
+        })?
+        .strip_prefix(' ')?
+        .trim_start();
+    let done = match rest.get(..3)? {
+        "[ ]" => false,
+        "[x]" | "[X]" => true,
+        _ => return None,
+    };
+    let text = &rest[3..];
+    if !text.is_empty() && !text.starts_with(char::is_whitespace) {
+        return None;
+    }
+    Some((done, text.trim()))
+}
+
+/// The text of a task without its `@due(...)` marker, and the date.
+fn split_due(text: &str) -> (String, Option<String>) {
+    let Some(start) = text.find(DUE_MARKER) else {
+        return (text.to_owned(), None);
+    };
+    let after = &text[start + DUE_MARKER.len()..];
+    let Some(end) = after.find(')') else {
+        return (text.to_owned(), None);
+    };
+    let Ok(due) = NaiveDate::parse_from_str(after[..end].trim(), DUE_FORMAT) else {
+        return (text.to_owned(), None);
+    };
+    let rest = format!("{}{}", &text[..start], &after[end + 1..]);
+    let words: Vec<&str> = rest.split_whitespace().collect();
+    (words.join(" "), Some(due.format(DUE_FORMAT).to_string()))
+}
@@ -0,0 +1,69 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use super::{Rendered, Template};
+use crate::error::Result;
+use crate::storage::Note;
+use crate::vaults::Current;
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TemplateNote {
+    pub note: Note,
+    pub cursor: Option<usize>,
+}
+
+#[tauri::command]
+pub async fn list_templates(vault: Current) -> Result<Vec<Template>> {
+    super::list(&vault.storage)
+}
+
+/// Adds a template, or replaces the one with `id`.
+#[tauri::command]
+pub async fn save_template(
+    vault: Current,
+    id: Option<String>,
+    name: String,
+    title: String,
+    body: String,
+    folder: Option<String>,
+) -> Result<Template> {
+    let template = Template {
+        id: id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string()),
+        name: name.trim().to_owned(),
+        title,
+        body,
+        folder: folder.unwrap_or_default(),
+    };
+    super::save(&vault.storage, template)
+}
+
+#[tauri::command]
+pub async fn delete_template(vault: Current, id: String) -> Result<()> {
+    super::delete(&vault.storage, &id)
+}
+
+/// Template `id` filled in with `vars`, for previewing or inserting.
+#[tauri::command]
+pub async fn render_template(
+    vault: Current,
+    id: String,
+    vars: Option<HashMap<String, String>>,
+) -> Result<Rendered> {
+    let template = super::get(&vault.storage, &id)?;
+    Ok(super::render(&template, &vars.unwrap_or_default()))
+}
+
+#[tauri::command]
+pub async fn create_note_from_template(
+    vault: Current,
+    id: String,
+    vars: Option<HashMap<String, String>>,
+) -> Result<TemplateNote> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let (note, cursor) = super::create_note(&vault, &id, &vars.unwrap_or_default())?;
+        Ok(TemplateNote { note, cursor })
+    })
+    .await?
+}
@@ -0,0 +1,145 @@
+pub mod commands;
+
+use std::collections::HashMap;
+
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+use crate::storage::{Note, Storage};
+use crate::vault::Vault;
+
+const TEMPLATES_KEY: &str = "templates";
+/// Where the editor's cursor goes once the template is filled in.
+const CURSOR: &str = "cursor";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Template {
+    pub id: String,
+    pub name: String,
+    /// Title of notes made from the template, with the same placeholders as
+    /// the body.
+    pub title: String,
+    pub body: String,
+    pub folder: String,
+}
+
+/// A template with its placeholders filled in.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Rendered {
+    pub title: String,
+    pub body: String,
+    /// Where `{{cursor}}` was in the body, in UTF-16 code units as the
+    /// editor counts them.
+    pub cursor: Option<usize>,
+}
+
+pub fn list(storage: &Storage) -> Result<Vec<Template>> {
+    match storage.meta(TEMPLATES_KEY)? {
+        Some(json) => Ok(serde_json::from_str(&json)?),
+        None => Ok(Vec::new()),
+    }
+}
+
+pub fn get(storage: &Storage, id: &str) -> Result<Template> {
+    list(storage)?
+        .into_iter()
+        .find(|template| template.id == id)
+        .ok_or_else(|| Error::InvalidInput(format!("no template {id}")))
+}
+
+/// Adds `template`, or replaces the one with its id.
+pub fn save(storage: &Storage, template: Template) -> Result<Template> {
+    if template.name.trim().is_empty() {
+        return Err(Error::InvalidInput("a template needs a name".into()));
+    }
+    let mut templates = list(storage)?;
+    match templates.iter_mut().find(|t| t.id == template.id) {
+        Some(existing) => *existing = template.clone(),
+        None => templates.push(template.clone()),
+    }
+    templates.sort_by_key(|t| t.name.to_lowercase());
+    storage.set_meta(TEMPLATES_KEY, &serde_json::to_string(&templates)?)?;
+    Ok(template)
+}
+
+pub fn delete(storage: &Storage, id: &str) -> Result<()> {
+    let mut templates = list(storage)?;
+    let before = templates.len();
+    templates.retain(|template| template.id != id);
+    if templates.len() == before {
+        return Err(Error::InvalidInput(format!("no template {id}")));
+    }
+    storage.set_meta(TEMPLATES_KEY, &serde_json::to_string(&templates)?)
+}
+
+/// Fills in `{{date}}`, `{{time}}` and `{{title}}`, then any of `vars`,
+/// which may also override those three. Unknown placeholders are left as
+/// they are.
+pub fn render(template: &Template, vars: &HashMap<String, String>) -> Rendered {
+    let now = Local::now();
+    let mut values: HashMap<&str, String> = HashMap::from([
+        ("date", now.format("%Y-%m-%d").to_string()),
+        ("time", now.format("%H:%M").to_string()),
+    ]);
+    values.extend(vars.iter().map(|(k, v)| (k.as_str(), v.clone())));
+    let title = match vars.get("title") {
+        Some(title) => title.clone(),
+        None => substitute(&template.title, &values).0,
+    };
+    values.insert("title", title.clone());
+    let (body, cursor) = substitute(&template.body, &values);
+    let cursor = cursor.map(|at| body[..at].encode_utf16().count());
+    Rendered {
+        title,
+        body,
+        cursor,
+    }
+}
+
+/// Creates a note from template `id`, returning it with where the cursor
+/// should go.
+pub fn create_note(
+    vault: &Vault,
+    id: &str,
+    vars: &HashMap<String, String>,
+) -> Result<(Note, Option<usize>)> {
+    let template = get(&vault.storage, id)?;
+    let rendered = render(&template, vars);
+    let title = if rendered.title.trim().is_empty() {
+        template.name.clone()
+    } else {
+        rendered.title
+    };
+    let note = vault.create_note(&title, &rendered.body, &template.folder)?;
+    Ok((note, rendered.cursor))
+}
+
+/// `text` with each `{{name}}` replaced in one pass, so values are never
+/// expanded themselves, and the byte offset of the first `{{cursor}}`,
+/// which is removed.
+fn substitute(text: &str, values: &HashMap<&str, String>) -> (String, Option<usize>) {
+    let mut out = String::with_capacity(text.len());
+    let mut cursor = None;
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        let Some(len) = rest[start + 2..].find("}}") else {
+            break;
+        };
+        out.push_str(&rest[..start]);
+        let placeholder = &rest[start..start + len + 4];
+        let name = placeholder[2..placeholder.len() - 2].trim();
+        if name == CURSOR {
+            cursor.get_or_insert(out.len());
+        } else if let Some(value) = values.get(name) {
+            out.push_str(value);
+        } else {
+            out.push_str(placeholder);
+        }
+        rest = &rest[start + len + 4..];
+    }
+    out.push_str(rest);
+    (out, cursor)
+}
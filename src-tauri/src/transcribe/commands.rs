@@ -0,0 +1,62 @@
+use tauri::AppHandle;
+
+use super::models::{self, ModelInfo};
+use super::{TranscribeConfig, TranscriptionStatus};
+use crate::error::Result;
+use crate::vaults::Current;
+
+#[tauri::command]
+pub async fn transcription_status(
+    app: AppHandle,
+    vault: Current,
+    note_id: String,
+) -> Result<TranscriptionStatus> {
+    super::status(&app, &vault, &note_id)
+}
+
+/// Turns transcription on or off or changes its model or language. Without
+/// arguments, returns the current settings.
+#[tauri::command]
+pub async fn configure_transcription(
+    vault: Current,
+    enabled: Option<bool>,
+    model: Option<String>,
+    language: Option<String>,
+) -> Result<TranscribeConfig> {
+    let mut config = super::config_of(&vault.storage)?;
+    if let Some(enabled) = enabled {
+        config.enabled = enabled;
+    }
+    if let Some(model) = model {
+        config.model = model;
+    }
+    if let Some(language) = language {
+        config.language = language;
+    }
+    super::set_config(&vault.storage, &config)?;
+    vault.transcribe.wake();
+    Ok(config)
+}
+
+#[tauri::command]
+pub async fn list_transcription_models(app: AppHandle) -> Result<Vec<ModelInfo>> {
+    models::list(&app)
+}
+
+/// Downloads a model. Progress arrives as `transcription-model-progress`
+/// events.
+#[tauri::command]
+pub async fn download_transcription_model(
+    app: AppHandle,
+    vault: Current,
+    name: String,
+) -> Result<ModelInfo> {
+    let model = models::download(&app, &name).await?;
+    vault.transcribe.wake();
+    Ok(model)
+}
+
+#[tauri::command]
+pub async fn delete_transcription_model(app: AppHandle, name: String) -> Result<()> {
+    models::delete(&app, &name)
+}
@@ -0,0 +1,250 @@
+pub mod commands;
+pub mod models;
+
+#[cfg_attr(feature = "transcribe", path = "whisper.rs")]
+#[cfg_attr(not(feature = "transcribe"), path = "unsupported.rs")]
+mod engine;
+
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use pulldown_cmark::{Event, Parser, Tag};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::attachments::text::{self, AttachmentText};
+use crate::error::{Error, Result};
+use crate::files::NoteFiles;
+use crate::jobs::{JobContext, JobKind, Jobs, Priority};
+use crate::metadata::FrontMatter;
+use crate::protected;
+use crate::storage::{Note, NotePatch, Storage};
+use crate::vault::Vault;
+
+/// Event carrying the [`AttachmentText`] of each recording once it is
+/// transcribed.
+pub const DONE_EVENT: &str = "transcription-done";
+/// Event carrying the message of a batch that could not run.
+pub const ERROR_EVENT: &str = "transcription-error";
+/// Event sent when a model is downloaded or deleted.
+pub const MODELS_EVENT: &str = "transcription-models-changed";
+/// Whether this build includes whisper.cpp.
+pub const AVAILABLE: bool = engine::AVAILABLE;
+
+const CONFIG_KEY: &str = "transcribe.config";
+const AUDIO_EXTENSIONS: &[&str] = &["ogg", "opus"];
+/// Opens the quote a transcript goes in, which also tells a recording that
+/// already has one.
+const CALLOUT: &str = "> [!transcript]";
+const IDLE: Duration = Duration::from_secs(10 * 60);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct TranscribeConfig {
+    pub enabled: bool,
+    /// One of the names [`models::list`] offers.
+    pub model: String,
+    /// Language code such as `en`, or `auto` to detect it per recording.
+    pub language: String,
+}
+
+impl Default for TranscribeConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            model: "base".into(),
+            language: "auto".into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TranscriptionStatus {
+    pub available: bool,
+    pub enabled: bool,
+    pub model_installed: bool,
+    /// Each recording the note uses and whether it has been transcribed.
+    pub recordings: Vec<AttachmentText>,
+}
+
+pub fn config_of(storage: &Storage) -> Result<TranscribeConfig> {
+    match storage.meta(CONFIG_KEY)? {
+        Some(json) => Ok(serde_json::from_str(&json)?),
+        None => Ok(TranscribeConfig::default()),
+    }
+}
+
+pub fn set_config(storage: &Storage, config: &TranscribeConfig) -> Result<()> {
+    models::check_known(&config.model)?;
+    storage.set_meta(CONFIG_KEY, &serde_json::to_string(config)?)
+}
+
+pub fn is_audio(path: &str) -> bool {
+    Path::new(path)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| AUDIO_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()))
+}
+
+pub fn status(app: &AppHandle, vault: &Vault, note_id: &str) -> Result<TranscriptionStatus> {
+    let note = vault.storage.get_note(note_id)?;
+    let config = config_of(&vault.storage)?;
+    let recordings = crate::attachments::references(&note)
+        .iter()
+        .filter(|path| is_audio(path))
+        .map(|path| text::status(&vault.storage, path))
+        .collect::<Result<_>>()?;
+    Ok(TranscriptionStatus {
+        available: AVAILABLE,
+        enabled: config.enabled,
+        model_installed: models::is_installed(app, &config.model)?,
+        recordings,
+    })
+}
+
+/// Transcribes every recording some note uses that has no text yet, and
+/// puts the transcript below the recording in each of those notes. Stops
+/// early if transcription is turned off, the vault locks or the job is
+/// cancelled.
+pub fn run_pending(app: &AppHandle, vault: &Vault, job: &JobContext) -> Result<usize> {
+    let runnable = |vault: &Vault| -> Result<Option<TranscribeConfig>> {
+        let config = config_of(&vault.storage)?;
+        Ok((config.enabled && !vault.storage.is_locked()).then_some(config))
+    };
+    let Some(config) = runnable(vault)? else {
+        return Ok(0);
+    };
+    let pending: Vec<String> = text::pending(&vault.storage)?
+        .into_iter()
+        .filter(|path| is_audio(path))
+        .collect();
+    if pending.is_empty() {
+        return Ok(0);
+    }
+    let model_file = models::path(app, &config.model)?;
+    if !model_file.is_file() {
+        return Err(Error::Transcription(format!(
+            "the {} model has not been downloaded",
+            config.model
+        )));
+    }
+    let model = engine::Model::load(&model_file)?;
+
+    let mut transcribed = 0;
+    let total = pending.len();
+    for (i, path) in pending.into_iter().enumerate() {
+        job.check()?;
+        job.progress(i, total);
+        let Some(config) = runnable(vault)? else {
+            break;
+        };
+        let file = vault.files.absolute(&path);
+        if !file.is_file() {
+            continue;
+        }
+        match model.transcribe(&file, &config.language) {
+            Ok(transcript) => {
+                text::set(&vault.storage, &path, Ok(transcript.trim()))?;
+                for note_id in text::notes_using(&vault.storage, &path)? {
+                    insert(vault, &note_id, &path, transcript.trim())?;
+                }
+            }
+            Err(err) => text::set(&vault.storage, &path, Err(&err))?,
+        }
+        vault.attachment_read(&path)?;
+        let _ = app.emit(DONE_EVENT, text::status(&vault.storage, &path)?);
+        transcribed += 1;
+    }
+    Ok(transcribed)
+}
+
+/// Runs [`run_pending`] whenever the queue is woken, and every [`IDLE`]
+/// otherwise. Failures are reported through [`ERROR_EVENT`].
+pub fn spawn_worker(app: AppHandle, vault: Arc<Vault>) {
+    if !AVAILABLE {
+        return;
+    }
+    tauri::async_runtime::spawn(async move {
+        while !vault.is_closed() {
+            let (handle, worker) = (app.clone(), Arc::clone(&vault));
+            let result = app
+                .state::<Jobs>()
+                .run(JobKind::Transcription, Priority::Background, move |job| {
+                    run_pending(&handle, &worker, job)
+                })
+                .await;
+            match result {
+                Ok(_) | Err(Error::Cancelled) => {}
+                Err(err) => {
+                    let _ = app.emit(ERROR_EVENT, err.to_string());
+                }
+            }
+            let _ = tokio::time::timeout(IDLE, vault.transcribe.notified()).await;
+        }
+    });
+}
+
+/// Puts `transcript` below each embed of `path` in note `note_id` that
+/// has none yet. Notes with a passphrase of their own are left alone, as
+/// their body cannot be changed without it.
+fn insert(vault: &Vault, note_id: &str, path: &str, transcript: &str) -> Result<()> {
+    if transcript.is_empty() {
+        return Ok(());
+    }
+    let note = vault.storage.get_note(note_id)?;
+    if protected::is_protected(&note.body) {
+        return Ok(());
+    }
+    if let Some(body) = with_transcript(&note, path, transcript) {
+        vault.update_note(
+            note_id,
+            NotePatch {
+                body: Some(body),
+                ..Default::default()
+            },
+        )?;
+    }
+    Ok(())
+}
+
+/// The body of `note` with the transcript quoted after every line that
+/// embeds `path`, or `None` if each already has one.
+fn with_transcript(note: &Note, path: &str, transcript: &str) -> Option<String> {
+    let (_, markdown) = FrontMatter::split(&note.body);
+    let offset = note.body.len() - markdown.len();
+    let mut line_ends = Vec::new();
+    for (event, range) in Parser::new(markdown).into_offset_iter() {
+        if let Event::Start(Tag::Image { dest_url, .. } | Tag::Link { dest_url, .. }) = event {
+            if NoteFiles::resolve_from(&note.folder, &dest_url).as_deref() == Some(path) {
+                let end = offset + range.end;
+                let line_end = note.body[end..]
+                    .find('\n')
+                    .map_or(note.body.len(), |i| end + i);
+                line_ends.push(line_end);
+            }
+        }
+    }
+    line_ends.dedup();
+    let quoted: String = transcript
+        .lines()
+        .map(|line| format!("\n> {line}").trim_end().to_owned())
+        .collect();
+    let mut body = note.body.clone();
+    let mut changed = false;
+    // From the end, so earlier positions stay valid.
+    for &line_end in line_ends.iter().rev() {
+        if body[line_end..].trim_start().starts_with(CALLOUT) {
+            continue;
+        }
+        // A line right below would otherwise run on into the quote.
+        let gap = body[line_end..]
+            .strip_prefix('\n')
+            .is_some_and(|next| !next.is_empty() && !next.starts_with('\n'));
+        let gap = if gap { "\n" } else { "" };
+        body.insert_str(line_end, &format!("\n\n{CALLOUT}{quoted}{gap}"));
+        changed = true;
+    }
+    changed.then_some(body)
+}
@@ -0,0 +1,173 @@
+//! The whisper.cpp models on this machine. They are shared by every vault,
+//! since the larger ones run to gigabytes.
+
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::error::{Error, Result};
+
+/// Event carrying a [`DownloadProgress`] as a model comes in.
+pub const PROGRESS_EVENT: &str = "transcription-model-progress";
+
+const DOWNLOAD_URL: &str = "https://huggingface.co/ggerganov/whisper.cpp/resolve/main";
+
+/// The models offered for download, with their approximate size in MB.
+/// Those ending in `.en` only understand English but are better at it.
+const KNOWN: &[(&str, u64)] = &[
+    ("tiny", 75),
+    ("tiny.en", 75),
+    ("base", 142),
+    ("base.en", 142),
+    ("small", 466),
+    ("small.en", 466),
+    ("medium", 1_500),
+    ("medium.en", 1_500),
+    ("large-v3-turbo", 1_600),
+];
+
+/// Models being downloaded, so a second click does not start another.
+static DOWNLOADING: Mutex<Option<HashSet<String>>> = Mutex::new(None);
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelInfo {
+    pub name: String,
+    pub size_mb: u64,
+    pub installed: bool,
+    pub downloading: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DownloadProgress {
+    pub name: String,
+    pub downloaded: u64,
+    /// Unknown when the server does not say.
+    pub total: Option<u64>,
+}
+
+pub fn dir(app: &AppHandle) -> Result<PathBuf> {
+    Ok(app.path().app_data_dir()?.join("models").join("whisper"))
+}
+
+/// The file of model `name`, whether or not it has been downloaded.
+pub fn path(app: &AppHandle, name: &str) -> Result<PathBuf> {
+    check_known(name)?;
+    Ok(dir(app)?.join(format!("ggml-{name}.bin")))
+}
+
+pub fn is_installed(app: &AppHandle, name: &str) -> Result<bool> {
+    Ok(path(app, name)?.is_file())
+}
+
+pub fn list(app: &AppHandle) -> Result<Vec<ModelInfo>> {
+    let downloading = downloading(|names| names.clone());
+    KNOWN
+        .iter()
+        .map(|&(name, size_mb)| {
+            Ok(ModelInfo {
+                name: name.to_owned(),
+                size_mb,
+                installed: is_installed(app, name)?,
+                downloading: downloading.contains(name),
+            })
+        })
+        .collect()
+}
+
+/// Fetches model `name`, reporting progress through [`PROGRESS_EVENT`].
+/// The model only takes its final name once complete, so an interrupted
+/// download is never mistaken for a usable one.
+pub async fn download(app: &AppHandle, name: &str) -> Result<ModelInfo> {
+    let target = path(app, name)?;
+    if !downloading(|names| names.insert(name.to_owned())) {
+        return Err(Error::InvalidInput(format!(
+            "model {name} is already downloading"
+        )));
+    }
+    let result = fetch(app, name, &target).await;
+    downloading(|names| names.remove(name));
+    result?;
+    let _ = app.emit(crate::transcribe::MODELS_EVENT, ());
+    info(app, name)
+}
+
+pub fn delete(app: &AppHandle, name: &str) -> Result<()> {
+    let target = path(app, name)?;
+    if target.is_file() {
+        fs::remove_file(target)?;
+    }
+    let _ = app.emit(crate::transcribe::MODELS_EVENT, ());
+    Ok(())
+}
+
+async fn fetch(app: &AppHandle, name: &str, target: &Path) -> Result<()> {
+    fs::create_dir_all(dir(app)?)?;
+    let mut response = reqwest::get(format!("{DOWNLOAD_URL}/ggml-{name}.bin"))
+        .await?
+        .error_for_status()?;
+    let total = response.content_length();
+    let partial = target.with_extension("bin.part");
+    let mut file = File::create(&partial)?;
+    let mut downloaded = 0u64;
+    let mut reported = 0u64;
+    while let Some(chunk) = response.chunk().await? {
+        file.write_all(&chunk)?;
+        downloaded += chunk.len() as u64;
+        // About every megabyte, which is plenty for a progress bar.
+        if downloaded - reported >= 1 << 20 {
+            reported = downloaded;
+            let _ = app.emit(
+                PROGRESS_EVENT,
+                DownloadProgress {
+                    name: name.to_owned(),
+                    downloaded,
+                    total,
+                },
+            );
+        }
+    }
+    file.sync_all()?;
+    drop(file);
+    if total.is_some_and(|total| total != downloaded) {
+        let _ = fs::remove_file(&partial);
+        return Err(Error::InvalidInput(format!(
+            "the download of model {name} was cut short"
+        )));
+    }
+    fs::rename(&partial, target)?;
+    Ok(())
+}
+
+fn info(app: &AppHandle, name: &str) -> Result<ModelInfo> {
+    list(app)?
+        .into_iter()
+        .find(|model| model.name == name)
+        .ok_or_else(|| unknown(name))
+}
+
+pub fn check_known(name: &str) -> Result<()> {
+    match KNOWN.iter().any(|&(known, _)| known == name) {
+        true => Ok(()),
+        false => Err(unknown(name)),
+    }
+}
+
+fn unknown(name: &str) -> Error {
+    Error::InvalidInput(format!("unknown transcription model: {name}"))
+}
+
+fn downloading<T>(change: impl FnOnce(&mut HashSet<String>) -> T) -> T {
+    change(
+        DOWNLOADING
+            .lock()
+            .expect("model downloads poisoned")
+            .get_or_insert_with(HashSet::new),
+    )
+}
@@ -0,0 +1,21 @@
+//! Builds without the `transcribe` feature cannot transcribe.
+
+use std::path::Path;
+
+use crate::error::{Error, Result};
+
+pub const AVAILABLE: bool = false;
+
+pub struct Model;
+
+impl Model {
+    pub fn load(_file: &Path) -> Result<Self> {
+        Err(Error::Transcription(
+            "this build does not include transcription".into(),
+        ))
+    }
+
+    pub fn transcribe(&self, _file: &Path, _language: &str) -> std::result::Result<String, String> {
+        Err("this build does not include transcription".into())
+    }
+}
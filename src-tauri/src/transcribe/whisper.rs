@@ -0,0 +1,117 @@
+//! whisper.cpp, fed from Ogg Opus decoded to the 16 kHz mono it expects.
+
+use std::fs;
+use std::io::Cursor;
+use std::path::Path;
+use std::thread;
+
+use ogg::reading::PacketReader;
+use opus::{Channels, Decoder};
+use whisper_rs::{FullParams, SamplingStrategy, WhisperContext, WhisperContextParameters};
+
+use crate::error::{Error, Result};
+
+pub const AVAILABLE: bool = true;
+
+/// Opus always decodes at this rate, whatever was recorded.
+const OPUS_RATE: usize = 48_000;
+const WHISPER_RATE: usize = 16_000;
+/// Samples per channel in the longest Opus packet, 120 ms.
+const MAX_FRAME: usize = OPUS_RATE * 120 / 1000;
+
+/// A loaded model. Loading takes a while, so one is kept for a whole batch.
+pub struct Model {
+    context: WhisperContext,
+}
+
+impl Model {
+    pub fn load(file: &Path) -> Result<Self> {
+        let path = file
+            .to_str()
+            .ok_or_else(|| Error::Transcription("model path is not valid UTF-8".into()))?;
+        let context = WhisperContext::new_with_params(path, WhisperContextParameters::default())
+            .map_err(|err| Error::Transcription(err.to_string()))?;
+        Ok(Self { context })
+    }
+
+    /// The words spoken in the recording at `file`, or why there are none.
+    /// `language` is a code such as `en`, or `auto` to detect it.
+    pub fn transcribe(&self, file: &Path, language: &str) -> std::result::Result<String, String> {
+        let audio = decode(&fs::read(file).map_err(|err| err.to_string())?)?;
+        if audio.is_empty() {
+            return Ok(String::new());
+        }
+        let mut state = self.context.create_state().map_err(|err| err.to_string())?;
+        let mut params = FullParams::new(SamplingStrategy::Greedy { best_of: 1 });
+        params.set_language(Some(language));
+        params.set_n_threads(threads());
+        params.set_print_special(false);
+        params.set_print_progress(false);
+        params.set_print_realtime(false);
+        params.set_print_timestamps(false);
+        state.full(params, &audio).map_err(|err| err.to_string())?;
+        let mut text = String::new();
+        for segment in state.as_iter() {
+            let words = segment.to_str_lossy().map_err(|err| err.to_string())?;
+            text.push_str(words.trim());
+            text.push(' ');
+        }
+        Ok(text.trim_end().to_owned())
+    }
+}
+
+/// Leaves a core or two to the UI.
+fn threads() -> i32 {
+    let cores = thread::available_parallelism().map_or(4, |n| n.get());
+    cores.saturating_sub(1).clamp(1, 8) as i32
+}
+
+/// Ogg Opus to 16 kHz mono.
+fn decode(ogg: &[u8]) -> std::result::Result<Vec<f32>, String> {
+    let mut reader = PacketReader::new(Cursor::new(ogg));
+    let head = reader
+        .read_packet()
+        .map_err(|err| err.to_string())?
+        .ok_or("empty recording")?;
+    if head.data.len() < 19 || !head.data.starts_with(b"OpusHead") {
+        return Err("not an Ogg Opus recording".into());
+    }
+    let channels = usize::from(head.data[9]);
+    let pre_skip = usize::from(u16::from_le_bytes([head.data[10], head.data[11]]));
+    let mut decoder = Decoder::new(
+        OPUS_RATE as u32,
+        match channels {
+            1 => Channels::Mono,
+            2 => Channels::Stereo,
+            _ => return Err(format!("{channels} channels are not supported")),
+        },
+    )
+    .map_err(|err| err.to_string())?;
+
+    let mut mono = Vec::new();
+    let mut frame = vec![0f32; MAX_FRAME * channels];
+    // The second packet holds the tags.
+    let mut packets = 0;
+    while let Some(packet) = reader.read_packet().map_err(|err| err.to_string())? {
+        packets += 1;
+        if packets == 1 {
+            continue;
+        }
+        let samples = decoder
+            .decode_float(&packet.data, &mut frame, false)
+            .map_err(|err| err.to_string())?;
+        mono.extend(
+            frame[..samples * channels]
+                .chunks(channels)
+                .map(|sample| sample.iter().sum::<f32>() / channels as f32),
+        );
+    }
+    let mono = mono.get(pre_skip..).unwrap_or_default();
+    // 48 kHz is three times 16 kHz, so averaging each three samples is
+    // both the filter and the resampler.
+    let step = OPUS_RATE / WHISPER_RATE;
+    Ok(mono
+        .chunks(step)
+        .map(|chunk| chunk.iter().sum::<f32>() / chunk.len() as f32)
+        .collect())
+}
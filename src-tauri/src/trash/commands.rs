@@ -0,0 +1,37 @@
+use super::{TrashConfig, TrashedNote};
+use crate::error::Result;
+use crate::storage::Note;
+use crate::vaults::Current;
+
+#[tauri::command]
+pub async fn trash_note(vault: Current, id: String) -> Result<()> {
+    vault.trash_note(&id)
+}
+
+#[tauri::command]
+pub async fn restore_note(vault: Current, id: String) -> Result<Note> {
+    vault.restore_note(&id)
+}
+
+#[tauri::command]
+pub async fn list_trash(vault: Current) -> Result<Vec<TrashedNote>> {
+    super::list(&vault.storage)
+}
+
+/// Deletes every trashed note for good, returning how many there were.
+#[tauri::command]
+pub async fn empty_trash(vault: Current) -> Result<usize> {
+    vault.purge_trash(None)
+}
+
+/// Sets how long notes stay in the trash. Without `retentionDays`, returns
+/// the current setting.
+#[tauri::command]
+pub async fn configure_trash(vault: Current, retention_days: Option<u32>) -> Result<TrashConfig> {
+    let Some(retention_days) = retention_days else {
+        return super::config_of(&vault.storage);
+    };
+    let config = TrashConfig { retention_days };
+    super::set_config(&vault.storage, &config)?;
+    Ok(config)
+}
@@ -0,0 +1,139 @@
+pub mod commands;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+
+use crate::error::Result;
+use crate::storage::{now_millis, Storage};
+use crate::vault::Vault;
+
+const CONFIG_KEY: &str = "trash.config";
+const DAY_MS: i64 = 24 * 60 * 60 * 1000;
+/// How often the purge job looks for expired notes.
+const PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrashConfig {
+    /// Days a note stays in the trash before it is purged; 0 keeps it
+    /// until the trash is emptied.
+    pub retention_days: u32,
+}
+
+impl Default for TrashConfig {
+    fn default() -> Self {
+        Self { retention_days: 30 }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrashedNote {
+    pub id: String,
+    pub title: String,
+    pub folder: String,
+    pub updated_at: i64,
+    pub deleted_at: i64,
+    /// When the purge job will delete it for good, if ever.
+    pub purge_at: Option<i64>,
+}
+
+/// Moves a note to the trash. Trashed notes keep their row and history but
+/// are left out of listings, the search index and the file mirror.
+pub fn mark(storage: &Storage, note_id: &str) -> Result<()> {
+    storage.conn().execute(
+        "INSERT OR IGNORE INTO trash (note_id, deleted_at) VALUES (?1, ?2)",
+        params![note_id, now_millis()],
+    )?;
+    Ok(())
+}
+
+/// Takes a note out of the trash, returning whether it was in there.
+pub fn unmark(storage: &Storage, note_id: &str) -> Result<bool> {
+    let removed = storage
+        .conn()
+        .execute("DELETE FROM trash WHERE note_id = ?1", [note_id])?;
+    Ok(removed > 0)
+}
+
+/// Trashed notes, most recently deleted first.
+pub fn list(storage: &Storage) -> Result<Vec<TrashedNote>> {
+    let retention = retention_ms(storage)?;
+    let conn = storage.conn();
+    let mut stmt = conn.prepare(
+        "SELECT n.id, n.title, n.folder, n.updated_at, t.deleted_at
+         FROM trash t JOIN notes n ON n.id = t.note_id
+         ORDER BY t.deleted_at DESC",
+    )?;
+    let notes = stmt
+        .query_map([], |row| {
+            let deleted_at: i64 = row.get(4)?;
+            Ok(TrashedNote {
+                id: row.get(0)?,
+                title: row.get(1)?,
+                folder: row.get(2)?,
+                updated_at: row.get(3)?,
+                deleted_at,
+                purge_at: retention.map(|ms| deleted_at.saturating_add(ms)),
+            })
+        })?
+        .collect::<rusqlite::Result<_>>()?;
+    Ok(notes)
+}
+
+/// Ids of trashed notes deleted before `before`, or all of them.
+pub fn ids(storage: &Storage, before: Option<i64>) -> Result<Vec<String>> {
+    let conn = storage.conn();
+    let mut stmt = conn.prepare("SELECT note_id FROM trash WHERE ?1 IS NULL OR deleted_at < ?1")?;
+    let ids = stmt
+        .query_map([before], |row| row.get(0))?
+        .collect::<rusqlite::Result<_>>()?;
+    Ok(ids)
+}
+
+pub fn is_trashed(storage: &Storage, note_id: &str) -> Result<bool> {
+    let trashed = storage.conn().query_row(
+        "SELECT EXISTS(SELECT 1 FROM trash WHERE note_id = ?1)",
+        [note_id],
+        |row| row.get(0),
+    )?;
+    Ok(trashed)
+}
+
+pub fn config_of(storage: &Storage) -> Result<TrashConfig> {
+    match storage.meta(CONFIG_KEY)? {
+        Some(json) => Ok(serde_json::from_str(&json)?),
+        None => Ok(TrashConfig::default()),
+    }
+}
+
+pub fn set_config(storage: &Storage, config: &TrashConfig) -> Result<()> {
+    storage.set_meta(CONFIG_KEY, &serde_json::to_string(config)?)
+}
+
+/// Deletes trashed notes past the retention period, returning how many.
+pub fn purge_expired(vault: &Vault) -> Result<usize> {
+    let Some(retention) = retention_ms(&vault.storage)? else {
+        return Ok(0);
+    };
+    vault.purge_trash(Some(now_millis().saturating_sub(retention)))
+}
+
+/// Purges expired notes once at startup and then every hour.
+pub fn spawn_periodic(vault: Arc<Vault>) {
+    tauri::async_runtime::spawn(async move {
+        while !vault.is_closed() {
+            let handle = Arc::clone(&vault);
+            let _ = tauri::async_runtime::spawn_blocking(move || purge_expired(&handle)).await;
+            tokio::time::sleep(PURGE_INTERVAL).await;
+        }
+    });
+}
+
+fn retention_ms(storage: &Storage) -> Result<Option<i64>> {
+    let days = config_of(storage)?.retention_days;
+    Ok((days > 0).then(|| i64::from(days) * DAY_MS))
+}
@@ -0,0 +1,158 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use tauri::menu::{Menu, MenuBuilder, MenuEvent};
+use tauri::tray::TrayIconBuilder;
+use tauri::{AppHandle, Emitter, Wry};
+
+use crate::error::Result;
+use crate::navigation::open_note;
+use crate::quick_capture;
+use crate::sync::{webdav, PROGRESS_EVENT};
+use crate::vaults;
+
+/// Event carrying the message of a menu action that failed.
+pub const ERROR_EVENT: &str = "tray-error";
+
+const TRAY_ID: &str = "main";
+const RECENT_NOTES: usize = 10;
+/// How often the recent notes in the menu are brought up to date.
+const REFRESH_INTERVAL: Duration = Duration::from_secs(10);
+const NOTE_PREFIX: &str = "note:";
+const NEW_NOTE: &str = "new-note";
+const QUICK_CAPTURE: &str = "quick-capture";
+const SYNC_NOW: &str = "sync-now";
+const QUIT: &str = "quit";
+
+/// Adds the tray icon and keeps its recent notes current.
+pub fn start(app: &AppHandle) -> Result<()> {
+    let recent = recent_notes(app)?;
+    let mut tray = TrayIconBuilder::with_id(TRAY_ID)
+        .tooltip(&app.package_info().name)
+        .menu(&menu(app, &recent)?)
+        .show_menu_on_left_click(true)
+        .on_menu_event(|app, event| {
+            if let Err(err) = handle(app, event) {
+                let _ = app.emit(ERROR_EVENT, err.to_string());
+            }
+        });
+    if let Some(icon) = app.default_window_icon() {
+        tray = tray.icon(icon.clone());
+    }
+    tray.build(app)?;
+
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let mut shown = recent;
+        loop {
+            tokio::time::sleep(REFRESH_INTERVAL).await;
+            let Ok(recent) = recent_notes(&app) else {
+                continue;
+            };
+            if recent == shown {
+                continue;
+            }
+            if let (Some(tray), Ok(menu)) = (app.tray_by_id(TRAY_ID), menu(&app, &recent)) {
+                let _ = tray.set_menu(Some(menu));
+            }
+            shown = recent;
+        }
+    });
+    Ok(())
+}
+
+fn recent_notes(app: &AppHandle) -> Result<Vec<(String, String)>> {
+    let notes = vaults::primary(app)?.storage.list_notes(None)?;
+    Ok(notes
+        .into_iter()
+        .take(RECENT_NOTES)
+        .map(|note| (note.id, note.title))
+        .collect())
+}
+
+fn menu(app: &AppHandle, recent: &[(String, String)]) -> Result<Menu<Wry>> {
+    let mut menu = MenuBuilder::new(app);
+    for (id, title) in recent {
+        let title = if title.trim().is_empty() {
+            "Untitled"
+        } else {
+            title
+        };
+        menu = menu.text(format!("{NOTE_PREFIX}{id}"), title);
+    }
+    if !recent.is_empty() {
+        menu = menu.separator();
+    }
+    Ok(menu
+        .text(NEW_NOTE, "New note")
+        .text(QUICK_CAPTURE, "Quick capture")
+        .text(SYNC_NOW, "Sync now")
+        .separator()
+        .text(QUIT, "Quit")
+        .build()?)
+}
+
+fn handle(app: &AppHandle, event: MenuEvent) -> Result<()> {
+    match event.id().as_ref() {
+        NEW_NOTE => {
+            let note = vaults::primary(app)?.create_note("Untitled", "", "")?;
+            open_note(app, &note.id)
+        }
+        QUICK_CAPTURE => quick_capture::toggle(app),
+        SYNC_NOW => {
+            spawn_sync(app.clone());
+            Ok(())
+        }
+        QUIT => {
+            app.exit(0);
+            Ok(())
+        }
+        id => match id.strip_prefix(NOTE_PREFIX) {
+            Some(note_id) => open_note(app, note_id),
+            None => Ok(()),
+        },
+    }
+}
+
+/// Syncs with whichever remotes are set up, git first. Progress and
+/// failures go out as the usual sync events.
+fn spawn_sync(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let vault = match vaults::primary(&app) {
+            Ok(vault) => vault,
+            Err(err) => {
+                let _ = app.emit(webdav::ERROR_EVENT, err.to_string());
+                return;
+            }
+        };
+        let (handle, git_vault) = (app.clone(), Arc::clone(&vault));
+        let git = tauri::async_runtime::spawn_blocking(move || {
+            if git_vault.git.status()?.remote.is_none() {
+                return Ok(());
+            }
+            git_vault
+                .sync_git(&mut |progress| {
+                    let _ = handle.emit(PROGRESS_EVENT, progress);
+                })
+                .map(drop)
+        })
+        .await
+        .unwrap_or_else(|err| Err(err.into()));
+        if let Err(err) = git {
+            let _ = app.emit(webdav::ERROR_EVENT, err.to_string());
+        }
+
+        if !matches!(webdav::config_of(&vault.storage), Ok(Some(_))) {
+            return;
+        }
+        let result = vault
+            .webdav
+            .sync(&vault, &mut |progress| {
+                let _ = app.emit(PROGRESS_EVENT, progress);
+            })
+            .await;
+        if let Err(err) = result {
+            let _ = app.emit(webdav::ERROR_EVENT, err.to_string());
+        }
+    });
+}
@@ -0,0 +1,23 @@
+use tauri::{AppHandle, State};
+
+use super::{Installer, UpdateInfo};
+use crate::error::Result;
+
+/// Checks for a newer release right away, whether or not automatic updates
+/// are on, and starts downloading it. A staged rollout does not hold back
+/// an update asked for this way.
+#[tauri::command]
+pub async fn check_for_update(app: AppHandle) -> Result<Option<UpdateInfo>> {
+    super::check(&app, false).await
+}
+
+/// The update waiting for a restart, if one finished downloading.
+#[tauri::command]
+pub async fn ready_update(installer: State<'_, Installer>) -> Result<Option<UpdateInfo>> {
+    Ok(installer.ready())
+}
+
+#[tauri::command]
+pub async fn install_update_and_restart(app: AppHandle) -> Result<()> {
+    super::install(&app)
+}
@@ -0,0 +1,276 @@
+//! Keeping the desktop app up to date from the releases of the channel
+//! picked in the settings. Updates are found and downloaded in the
+//! background; the user only chooses when to restart into one.
+//!
+//! Each release is a whole bundle signed with the key in
+//! `tauri.conf.json`; the updater has no delta format, so the download is
+//! the full app, fetched once per version while the app runs. A release's
+//! `latest.json` may carry a `rollout` percentage to reach only that share
+//! of installs at first.
+//!
+//! Until a signing key is set there, builds make no updater artifacts and
+//! the updater stays off: nothing is checked in the background, and asking
+//! for a check says why.
+
+pub mod commands;
+
+use std::fs;
+use std::path::Path;
+use std::sync::{Mutex, MutexGuard};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager};
+use tauri_plugin_updater::{Update, UpdaterExt};
+use url::Url;
+
+use crate::atomic;
+use crate::error::{Error, Result};
+use crate::settings::{Channel, SettingsStore};
+
+/// Event carrying the [`UpdateInfo`] of a newer release, as its download
+/// starts.
+pub const AVAILABLE_EVENT: &str = "update-available";
+/// Event carrying `{downloaded, total}` in bytes while an update downloads.
+pub const PROGRESS_EVENT: &str = "update-progress";
+/// Event carrying the [`UpdateInfo`] of a downloaded update, ready for
+/// [`commands::install_update_and_restart`].
+pub const READY_EVENT: &str = "update-ready";
+/// Event carrying the message of a check or download that failed.
+pub const ERROR_EVENT: &str = "update-error";
+
+const STABLE_ENDPOINT: &str =
+    "https://github.com/larsvonmars/notesdesktop/releases/latest/download/latest.json";
+const BETA_ENDPOINT: &str =
+    "https://github.com/larsvonmars/notesdesktop/releases/download/beta/latest.json";
+/// Where this install's place in staged rollouts is kept.
+const ROLLOUT_FILE: &str = "rollout.json";
+/// The first check waits until launch has settled.
+const FIRST_CHECK: Duration = Duration::from_secs(60);
+const CHECK_INTERVAL: Duration = Duration::from_secs(6 * 60 * 60);
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateInfo {
+    pub version: String,
+    pub current_version: String,
+    pub notes: Option<String>,
+    /// Milliseconds since the epoch.
+    pub published_at: Option<i64>,
+}
+
+impl UpdateInfo {
+    fn of(update: &Update) -> Self {
+        Self {
+            version: update.version.clone(),
+            current_version: update.current_version.clone(),
+            notes: update.body.clone(),
+            published_at: update
+                .date
+                .map(|date| (date.unix_timestamp_nanos() / 1_000_000) as i64),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Downloads {
+    /// Version being downloaded.
+    downloading: Option<String>,
+    ready: Option<(Update, Vec<u8>)>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Rollout {
+    /// 0 to 99, drawn once per install.
+    bucket: u8,
+}
+
+/// The downloaded update, managed as app state.
+pub struct Installer {
+    bucket: u8,
+    downloads: Mutex<Downloads>,
+}
+
+impl Installer {
+    pub fn load(app_dir: &Path) -> Result<Self> {
+        let file = app_dir.join(ROLLOUT_FILE);
+        let bucket = match fs::read_to_string(&file)
+            .ok()
+            .and_then(|json| serde_json::from_str::<Rollout>(&json).ok())
+        {
+            Some(rollout) => rollout.bucket,
+            None => {
+                let rollout = Rollout {
+                    bucket: uuid::Uuid::new_v4().as_bytes()[0] % 100,
+                };
+                fs::create_dir_all(app_dir)?;
+                atomic::write(&file, serde_json::to_string(&rollout)?.as_bytes())?;
+                rollout.bucket
+            }
+        };
+        Ok(Self {
+            bucket,
+            downloads: Mutex::new(Downloads::default()),
+        })
+    }
+
+    /// Whether a staged rollout of `update` has reached this install yet.
+    fn reached(&self, update: &Update) -> bool {
+        update
+            .raw_json
+            .get("rollout")
+            .and_then(|rollout| rollout.as_u64())
+            .is_none_or(|percent| u64::from(self.bucket) < percent)
+    }
+
+    /// The update downloaded and waiting for a restart.
+    pub fn ready(&self) -> Option<UpdateInfo> {
+        self.lock()
+            .ready
+            .as_ref()
+            .map(|(update, _)| UpdateInfo::of(update))
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Downloads> {
+        self.downloads.lock().expect("update downloads poisoned")
+    }
+}
+
+fn update_error(err: tauri_plugin_updater::Error) -> Error {
+    Error::Update(err.to_string())
+}
+
+fn endpoint(channel: Channel) -> &'static str {
+    match channel {
+        Channel::Stable => STABLE_ENDPOINT,
+        Channel::Beta => BETA_ENDPOINT,
+    }
+}
+
+/// Looks for a newer release on the chosen channel and starts downloading
+/// it. With `staged`, a release still rolling out to others is left alone
+/// until it reaches this install.
+pub async fn check(app: &AppHandle, staged: bool) -> Result<Option<UpdateInfo>> {
+    if !signed(app) {
+        return Err(Error::Update(
+            "this build has no key to verify updates with".into(),
+        ));
+    }
+    let channel = app.state::<SettingsStore>().get().updates.channel;
+    let url = Url::parse(endpoint(channel)).map_err(|err| Error::Update(err.to_string()))?;
+    let update = app
+        .updater_builder()
+        .endpoints(vec![url])
+        .map_err(update_error)?
+        .build()
+        .map_err(update_error)?
+        .check()
+        .await
+        .map_err(update_error)?;
+    let Some(update) = update else {
+        return Ok(None);
+    };
+    let installer = app.state::<Installer>();
+    if staged && !installer.reached(&update) {
+        return Ok(None);
+    }
+    let info = UpdateInfo::of(&update);
+    {
+        let mut downloads = installer.lock();
+        let known = downloads.downloading.as_ref() == Some(&info.version)
+            || downloads
+                .ready
+                .as_ref()
+                .is_some_and(|(ready, _)| ready.version == info.version);
+        if known {
+            return Ok(Some(info));
+        }
+        downloads.downloading = Some(info.version.clone());
+    }
+    app.emit(AVAILABLE_EVENT, &info)?;
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move { download(&app, update).await });
+    Ok(Some(info))
+}
+
+/// Whether this build has a key to verify updates with.
+fn signed(app: &AppHandle) -> bool {
+    app.config()
+        .plugins
+        .0
+        .get("updater")
+        .and_then(|updater| updater.get("pubkey"))
+        .and_then(|pubkey| pubkey.as_str())
+        .is_some_and(|pubkey| !pubkey.is_empty())
+}
+
+/// Downloads `update`, checking its signature, and holds on to it for
+/// [`install`].
+async fn download(app: &AppHandle, update: Update) {
+    let mut downloaded = 0u64;
+    let mut reported = 0u64;
+    let result = update
+        .download(
+            |chunk, total| {
+                downloaded += chunk as u64;
+                // About once a percent, not for every chunk.
+                let step = total.map_or(1 << 20, |total| (total / 100).max(1));
+                if downloaded - reported >= step || Some(downloaded) == total {
+                    reported = downloaded;
+                    let progress = serde_json::json!({ "downloaded": downloaded, "total": total });
+                    let _ = app.emit(PROGRESS_EVENT, progress);
+                }
+            },
+            || {},
+        )
+        .await;
+    let installer = app.state::<Installer>();
+    let mut downloads = installer.lock();
+    downloads.downloading = None;
+    match result {
+        Ok(bytes) => {
+            let info = UpdateInfo::of(&update);
+            tracing::info!(version = %info.version, "update downloaded");
+            downloads.ready = Some((update, bytes));
+            let _ = app.emit(READY_EVENT, info);
+        }
+        Err(err) => {
+            tracing::warn!(error = %err, "downloading an update failed");
+            let _ = app.emit(ERROR_EVENT, err.to_string());
+        }
+    }
+}
+
+/// Installs the downloaded update and restarts into it.
+pub fn install(app: &AppHandle) -> Result<()> {
+    let (update, bytes) = app
+        .state::<Installer>()
+        .lock()
+        .ready
+        .take()
+        .ok_or_else(|| Error::InvalidInput("no update has been downloaded".into()))?;
+    update.install(bytes).map_err(update_error)?;
+    app.request_restart();
+    Ok(())
+}
+
+/// Checks for updates now and then while automatic updates are on, in
+/// builds that can verify them.
+pub fn spawn(app: AppHandle) {
+    if !signed(&app) {
+        tracing::info!("no update signing key in this build, updates are off");
+        return;
+    }
+    tauri::async_runtime::spawn(async move {
+        tokio::time::sleep(FIRST_CHECK).await;
+        loop {
+            if app.state::<SettingsStore>().get().updates.automatic {
+                if let Err(err) = check(&app, true).await {
+                    tracing::warn!(error = %err, "checking for updates failed");
+                    let _ = app.emit(ERROR_EVENT, err.to_string());
+                }
+            }
+            tokio::time::sleep(CHECK_INTERVAL).await;
+        }
+    });
+}
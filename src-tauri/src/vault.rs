@@ -0,0 +1,976 @@
+use std::collections::HashSet;
+use std::fs::{self, File, TryLockError};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use rusqlite::Transaction;
+use serde::Serialize;
+use tokio::sync::broadcast;
+
+use crate::activity::{self, ActivityKind};
+use crate::attachments::text::{self, TextQueue};
+use crate::attachments::{self, Thumbnails};
+use crate::backup::Backups;
+use crate::collab;
+use crate::conflicts;
+use crate::crypto::VaultKey;
+use crate::drafts::Drafts;
+use crate::error::{Error, Result};
+use crate::files::NoteFiles;
+use crate::history;
+use crate::jobs;
+use crate::journal::Journal;
+use crate::links::{self, LinkGraph};
+use crate::metadata::FieldIndex;
+use crate::places::PlaceIndex;
+use crate::protected;
+use crate::quickswitch::SwitchIndex;
+use crate::render::RenderCache;
+use crate::search::replace::{NoteReplacement, Replacer};
+use crate::search::semantic::Semantic;
+use crate::search::{SearchHit, SearchIndex};
+use crate::srs::CardIndex;
+use crate::startup;
+use crate::stats::StatsIndex;
+use crate::storage::{Note, NotePatch, PartialBodies, Storage};
+use crate::sync::git::{Credentials, GitSync, PathChange};
+use crate::sync::rules::{SyncFilter, SyncTarget};
+use crate::sync::s3::S3;
+use crate::sync::webdav::WebDav;
+use crate::sync::{SyncProgress, SyncReport, SyncStage};
+use crate::tags::{self, TagIndex};
+use crate::tasks::TaskIndex;
+use crate::trash;
+
+const GIT_USERNAME_KEY: &str = "git.username";
+const GIT_TOKEN_KEY: &str = "git.token";
+/// Changes a slow listener may fall behind by before it starts missing them.
+/// Held locked for as long as the vault is open, so the app and the
+/// command line never have it open at once.
+pub(crate) const LOCK_FILE: &str = ".lock";
+const CHANGE_BACKLOG: usize = 256;
+const SYNC_BACKLOG: usize = 8;
+
+/// A note that was saved or went away, from any source: a command, an
+/// importer, sync or an outside edit.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NoteChange {
+    pub note_id: String,
+    /// Trashed or deleted rather than saved.
+    pub removed: bool,
+}
+
+/// Everything the backend owns for one notes directory.
+///
+/// Note writes go through the methods here rather than straight to
+/// [`Storage`] so that derived indexes stay in step with the database.
+pub struct Vault {
+    pub storage: Storage,
+    pub search: SearchIndex,
+    pub semantic: Semantic,
+    pub tags: TagIndex,
+    pub fields: FieldIndex,
+    pub places: PlaceIndex,
+    pub tasks: TaskIndex,
+    pub cards: CardIndex,
+    pub switcher: SwitchIndex,
+    pub stats: StatsIndex,
+    pub links: LinkGraph,
+    pub files: NoteFiles,
+    pub git: GitSync,
+    pub webdav: WebDav,
+    pub s3: S3,
+    pub backups: Backups,
+    pub thumbnails: Thumbnails,
+    /// Preview HTML of recently rendered blocks.
+    pub rendered: RenderCache,
+    pub drafts: Drafts,
+    /// Keys of protected notes unlocked in this session.
+    pub protected: protected::Unlocked,
+    /// Notes an editor holds only the start of.
+    pub partial: PartialBodies,
+    /// Wakes the OCR worker.
+    pub ocr: TextQueue,
+    /// Wakes the PDF text workers.
+    pub pdf_text: TextQueue,
+    /// Wakes the transcription worker.
+    pub transcribe: TextQueue,
+    journal: Journal,
+    /// Every [`NoteChange`], for whoever subscribes.
+    pub changes: broadcast::Sender<NoteChange>,
+    /// The provider of every sync that finished.
+    pub synced: broadcast::Sender<&'static str>,
+    /// Set once the vault is closed, telling its background tasks to stop.
+    closed: AtomicBool,
+    /// Set once [`Vault::warm`] has filled the in-memory indexes.
+    warm: AtomicBool,
+    /// [`LOCK_FILE`], released when the vault is dropped.
+    _lock: File,
+}
+
+impl Vault {
+    pub fn open(root: &Path) -> Result<Self> {
+        let vault = Self::open_cold(root)?;
+        vault.warm()?;
+        Ok(vault)
+    }
+
+    /// Opens the vault without filling its in-memory indexes, which on a
+    /// large vault means reading every note; [`Vault::warm`] does that
+    /// later. Until then tags, links, tasks and the like come up short.
+    pub fn open_cold(root: &Path) -> Result<Self> {
+        fs::create_dir_all(root)?;
+        let lock = take_lock(root)?;
+        let storage = startup::time("open database", || Storage::open(&root.join("notes.db")))?;
+        let encrypted = storage.is_encrypted();
+        let search = startup::time("open search index", || {
+            SearchIndex::open(&root.join("index"), !encrypted)
+        })?;
+        let files = NoteFiles::open(&root.join("notes"))?;
+        let git = GitSync::open(files.dir());
+        let vault = Self {
+            storage,
+            search,
+            semantic: Semantic::new(&root.join("models").join("embedding")),
+            tags: TagIndex::default(),
+            fields: FieldIndex::default(),
+            places: PlaceIndex::default(),
+            tasks: TaskIndex::default(),
+            cards: CardIndex::default(),
+            switcher: SwitchIndex::default(),
+            stats: StatsIndex::default(),
+            links: LinkGraph::default(),
+            files,
+            git,
+            webdav: WebDav::new()?,
+            s3: S3::new()?,
+            backups: Backups::open(&root.join("backups"))?,
+            thumbnails: Thumbnails::open(&root.join("thumbnails"))?,
+            rendered: RenderCache::default(),
+            drafts: Drafts::open(&root.join("drafts"))?,
+            protected: protected::Unlocked::default(),
+            partial: PartialBodies::default(),
+            ocr: TextQueue::default(),
+            pdf_text: TextQueue::default(),
+            transcribe: TextQueue::default(),
+            journal: Journal::open(root),
+            changes: broadcast::channel(CHANGE_BACKLOG).0,
+            synced: broadcast::channel(SYNC_BACKLOG).0,
+            closed: AtomicBool::new(false),
+            warm: AtomicBool::new(false),
+            _lock: lock,
+        };
+        // An encrypted vault starts locked; its index and files are caught up on unlock.
+        if !encrypted {
+            startup::time("recover unsaved changes", || vault.recover())?;
+        }
+        Ok(vault)
+    }
+
+    /// Reads the folder tree and the most recent notes, so the pages the
+    /// sidebar needs first are in the database cache when it asks.
+    pub fn preload(&self) -> Result<()> {
+        if self.storage.is_locked() {
+            return Ok(());
+        }
+        startup::time("preload sidebar", || {
+            self.storage.folder_tree(false)?;
+            self.storage.list_notes(None).map(drop)
+        })
+    }
+
+    /// Fills the in-memory indexes from every note, rebuilds the search
+    /// index if it does not hold as many notes as the database, and writes
+    /// out missing note files. Does nothing while the vault is locked.
+    ///
+    /// Commands keep running meanwhile, so notes that change after every
+    /// note was read are indexed again once the rebuild is done rather than
+    /// left as the snapshot had them.
+    pub fn warm(&self) -> Result<()> {
+        if self.storage.is_locked() {
+            return Ok(());
+        }
+        let mut changes = self.changes.subscribe();
+        let notes = startup::time("read every note", || self.storage.all_notes())?;
+        startup::time("fill in-memory indexes", || {
+            self.tags.rebuild(&notes);
+            self.fields.rebuild(&notes);
+            self.places.rebuild(&notes);
+            self.tasks.rebuild(&notes);
+            self.cards.rebuild(&notes);
+            self.switcher.rebuild(&notes);
+            self.stats.rebuild(&notes);
+            self.links.rebuild(&notes);
+            attachments::rebuild(&self.storage, &notes)
+        })?;
+        startup::time("verify search index", || {
+            if self.search.len() == notes.len() {
+                return Ok(0);
+            }
+            self.search.rebuild(&notes, |note| {
+                text::of_note(&self.storage, &note.id).unwrap_or_default()
+            })
+        })?;
+        startup::time("export missing files", || {
+            self.files.export_missing(&self.storage)
+        })?;
+        self.catch_up(&mut changes)?;
+        self.warm.store(true, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Indexes again the notes `changes` has seen change, as they are now.
+    fn catch_up(&self, changes: &mut broadcast::Receiver<NoteChange>) -> Result<()> {
+        let mut changed = HashSet::new();
+        loop {
+            match changes.try_recv() {
+                Ok(change) => {
+                    changed.insert(change.note_id);
+                }
+                // Too much changed to tell what; start over.
+                Err(broadcast::error::TryRecvError::Lagged(_)) => {
+                    self.reindex_all()?;
+                    return Ok(());
+                }
+                Err(_) => break,
+            }
+        }
+        for id in changed {
+            let note = match trash::is_trashed(&self.storage, &id)? {
+                true => None,
+                false => match self.storage.get_note(&id) {
+                    Ok(note) => Some(note),
+                    Err(Error::NoteNotFound(_)) => None,
+                    Err(err) => return Err(err),
+                },
+            };
+            let Some(note) = note else {
+                self.search.remove_note(&id)?;
+                self.tags.remove_note(&id);
+                self.fields.remove_note(&id);
+                self.places.remove_note(&id);
+                self.tasks.remove_note(&id);
+                self.cards.remove_note(&id);
+                self.switcher.remove_note(&id);
+                self.stats.remove_note(&id);
+                self.links.remove_note(&id);
+                continue;
+            };
+            self.tags.index_note(&note);
+            self.fields.index_note(&note);
+            self.places.index_note(&note);
+            self.tasks.index_note(&note);
+            self.cards.index_note(&note);
+            self.switcher.index_note(&note);
+            self.stats.index_note(&note);
+            self.links.index_note(&note);
+            attachments::track(&self.storage, &note)?;
+            self.search
+                .index_note(&note, &text::of_note(&self.storage, &note.id)?)?;
+        }
+        Ok(())
+    }
+
+    pub fn is_warm(&self) -> bool {
+        self.warm.load(Ordering::Relaxed)
+    }
+
+    /// Refuses rewrites that go by the in-memory indexes before they are
+    /// filled, when they would miss notes.
+    pub fn check_warm(&self) -> Result<()> {
+        match self.is_warm() {
+            true => Ok(()),
+            false => Err(Error::InvalidInput(
+                "the vault is still being indexed; try again in a moment".into(),
+            )),
+        }
+    }
+
+    pub fn create_note(&self, title: &str, body: &str, folder: &str) -> Result<Note> {
+        let note = self.storage.create_note(title, body, folder)?;
+        self.note_saved(&note, ActivityKind::Created)?;
+        self.autocommit(&format!("Create \"{}\"", note.title))?;
+        Ok(note)
+    }
+
+    /// Writes a whole note from the editor: creates it when `id` is `None`,
+    /// otherwise replaces its title, body and folder. `base_at` is when the
+    /// note was last saved as the editor loaded it; if it has changed
+    /// elsewhere since, the save goes to a conflict copy instead and fails
+    /// with [`Error::Conflict`].
+    pub fn save_note(
+        &self,
+        id: Option<&str>,
+        title: &str,
+        body: &str,
+        folder: &str,
+        base_at: Option<i64>,
+    ) -> Result<Note> {
+        let Some(id) = id else {
+            return self.create_note(title, body, folder);
+        };
+        if let Some(base_at) = base_at {
+            let current = self.storage.get_note(id)?;
+            if conflicts::conflicts_with(self, &current, base_at, body)? {
+                conflicts::record(self, &current, base_at, title, body)?;
+                return Err(Error::Conflict(id.to_owned()));
+            }
+        }
+        let patch = NotePatch {
+            title: Some(title.to_owned()),
+            body: Some(body.to_owned()),
+            folder: Some(folder.to_owned()),
+        };
+        self.edit_note(id, patch)
+    }
+
+    /// An edit the user makes: [`Self::update_note`], refused with
+    /// [`Error::ReadOnly`] for a read-only note.
+    pub fn edit_note(&self, id: &str, patch: NotePatch) -> Result<Note> {
+        self.storage.check_editable(id, &patch)?;
+        self.update_note(id, patch)
+    }
+
+    pub fn update_note(&self, id: &str, patch: NotePatch) -> Result<Note> {
+        if trash::is_trashed(&self.storage, id)? {
+            return Err(Error::InvalidInput("note is in the trash".into()));
+        }
+        let (patch, plain) = protected::seal_patch(self, id, patch)?;
+        let mut note = self.storage.update_note(id, patch)?;
+        self.note_saved(&note, ActivityKind::Edited)?;
+        self.autocommit(&format!("Update \"{}\"", note.title))?;
+        // The editor gets back what it saved, not the sealed body.
+        if let Some(plain) = plain {
+            note.body = plain;
+        }
+        Ok(note)
+    }
+
+    /// [`Self::update_note`] for bodies sealed or opened already, by
+    /// [`protected`] or on the peer they synced from: the patch is saved as
+    /// it is, with `also` run in the same transaction for the lock that goes
+    /// with it.
+    pub(crate) fn update_note_with(
+        &self,
+        id: &str,
+        patch: NotePatch,
+        also: impl FnOnce(&Transaction<'_>) -> Result<()>,
+    ) -> Result<Note> {
+        if trash::is_trashed(&self.storage, id)? {
+            return Err(Error::InvalidInput("note is in the trash".into()));
+        }
+        let notes = self
+            .storage
+            .update_notes_with(vec![(id.to_owned(), patch)], also)?;
+        let note = notes
+            .into_iter()
+            .next()
+            .ok_or_else(|| Error::NoteNotFound(id.to_owned()))?;
+        self.note_saved(&note, ActivityKind::Edited)?;
+        self.autocommit(&format!("Update \"{}\"", note.title))?;
+        Ok(note)
+    }
+
+    /// Renames a note and, with `folder`, moves it. Its own relative links
+    /// are adjusted to the new folder. With `update_links`, links in other
+    /// notes whose text is the old title get the new one. With `keep_alias`,
+    /// the old title becomes an alias, so links naming the note by it still
+    /// arrive; protected notes are renamed without one. Everything
+    /// changes in one transaction; the notes changed come back, this one
+    /// first.
+    pub fn rename_note(
+        &self,
+        id: &str,
+        title: &str,
+        folder: Option<&str>,
+        update_links: bool,
+        keep_alias: bool,
+    ) -> Result<Vec<Note>> {
+        if self.storage.is_locked() {
+            return Err(Error::VaultLocked);
+        }
+        if trash::is_trashed(&self.storage, id)? {
+            return Err(Error::InvalidInput("note is in the trash".into()));
+        }
+        let title = title.trim();
+        if title.is_empty() {
+            return Err(Error::InvalidInput("a note needs a title".into()));
+        }
+        let retitle = NotePatch {
+            title: Some(title.to_owned()),
+            ..Default::default()
+        };
+        self.storage.check_editable(id, &retitle)?;
+        let note = self.storage.get_note(id)?;
+        let folder = folder.map(|folder| folder.trim_matches('/').to_owned());
+        let moved = folder.as_ref().filter(|folder| **folder != note.folder);
+        let mut body = moved.and_then(|to| links::rebase_links(&note.body, &note.folder, to));
+        if keep_alias && title != note.title && !protected::is_protected(&note.body) {
+            let current = body.as_deref().unwrap_or(&note.body);
+            body = links::add_alias(current, &note.title, title).or(body);
+        }
+        let patch = NotePatch {
+            title: Some(title.to_owned()),
+            body,
+            folder: moved.cloned(),
+        };
+        let mut patches = vec![(note.id.clone(), patch)];
+        if update_links && title != note.title {
+            self.check_warm()?;
+            for source in self.links.backlinks(id) {
+                if source == id {
+                    continue;
+                }
+                let body = self.storage.get_note(&source)?.body;
+                if let Some(body) = links::retitle_links(&body, id, &note.title, title) {
+                    let patch = NotePatch {
+                        body: Some(body),
+                        ..Default::default()
+                    };
+                    patches.push((source, patch));
+                }
+            }
+        }
+        let message = match moved {
+            Some(to) => format!("Move \"{title}\" to {to}"),
+            None => format!("Rename \"{}\" to \"{title}\"", note.title),
+        };
+        self.update_notes(patches, &message)
+    }
+
+    /// Applies several patches in one transaction and one commit.
+    pub fn update_notes(
+        &self,
+        patches: Vec<(String, NotePatch)>,
+        message: &str,
+    ) -> Result<Vec<Note>> {
+        self.update_notes_with(patches, message, |_| Ok(()))
+    }
+
+    /// [`Self::update_notes`] with `also` run in the same transaction; see
+    /// [`Storage::update_notes_with`].
+    pub fn update_notes_with(
+        &self,
+        patches: Vec<(String, NotePatch)>,
+        message: &str,
+        also: impl FnOnce(&Transaction<'_>) -> Result<()>,
+    ) -> Result<Vec<Note>> {
+        let patches = patches
+            .into_iter()
+            .map(|(id, patch)| {
+                let (patch, _) = protected::seal_patch(self, &id, patch)?;
+                Ok((id, patch))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let notes = self.storage.update_notes_with(patches, also)?;
+        for note in &notes {
+            self.note_saved(note, ActivityKind::Edited)?;
+        }
+        self.autocommit(message)?;
+        Ok(notes)
+    }
+
+    /// Moves a note to the trash. Its file goes away like a deleted note's,
+    /// so sync backends propagate it, but it can be restored until purged.
+    pub fn trash_note(&self, id: &str) -> Result<()> {
+        if !self.storage.has_note(id)? {
+            return Err(Error::NoteNotFound(id.to_owned()));
+        }
+        let path = self.files.remove(&self.storage, id)?;
+        activity::record(&self.storage, id, ActivityKind::Trashed)?;
+        trash::mark(&self.storage, id)?;
+        self.search.remove_note(id)?;
+        self.tags.remove_note(id);
+        self.fields.remove_note(id);
+        self.places.remove_note(id);
+        self.tasks.remove_note(id);
+        self.cards.remove_note(id);
+        self.switcher.remove_note(id);
+        self.stats.remove_note(id);
+        self.links.remove_note(id);
+        self.changed(id, true);
+        if let Some(path) = path {
+            self.autocommit(&format!("Trash \"{path}\""))?;
+        }
+        Ok(())
+    }
+
+    pub fn restore_note(&self, id: &str) -> Result<Note> {
+        let note = self.storage.get_note(id)?;
+        if !trash::unmark(&self.storage, id)? {
+            return Err(Error::InvalidInput("note is not in the trash".into()));
+        }
+        self.note_saved(&note, ActivityKind::Restored)?;
+        self.autocommit(&format!("Restore \"{}\"", note.title))?;
+        Ok(note)
+    }
+
+    /// Deletes trashed notes for good, either all of them or those trashed
+    /// before `before`. Returns how many were deleted.
+    pub fn purge_trash(&self, before: Option<i64>) -> Result<usize> {
+        let ids = trash::ids(&self.storage, before)?;
+        for id in &ids {
+            self.delete_note(id)?;
+        }
+        Ok(ids.len())
+    }
+
+    /// Deletes a note for good, bypassing the trash.
+    pub fn delete_note(&self, id: &str) -> Result<()> {
+        let path = self.files.remove(&self.storage, id)?;
+        activity::record(&self.storage, id, ActivityKind::Deleted)?;
+        self.storage.delete_note(id)?;
+        self.journal.deleted(&self.storage, id)?;
+        self.drafts.discard(id)?;
+        collab::forget(self, id)?;
+        history::prune_blobs(&self.storage)?;
+        self.search.remove_note(id)?;
+        self.tags.remove_note(id);
+        self.fields.remove_note(id);
+        self.places.remove_note(id);
+        self.tasks.remove_note(id);
+        self.cards.remove_note(id);
+        self.switcher.remove_note(id);
+        self.stats.remove_note(id);
+        self.links.remove_note(id);
+        self.changed(id, true);
+        if let Some(path) = path {
+            self.autocommit(&format!("Delete \"{path}\""))?;
+        }
+        Ok(())
+    }
+
+    /// Adds a note brought in by an importer as is, keeping its id and
+    /// timestamps. Importers commit once when they are done rather than per
+    /// note.
+    pub fn import_note(&self, note: &Note) -> Result<()> {
+        self.storage.insert_note(note)?;
+        self.note_saved(note, ActivityKind::Created)
+    }
+
+    /// Brings a mirrored file that changed outside the app into the store:
+    /// updates the note it belongs to, or creates one for a new file.
+    pub fn import_file(&self, rel: &str) -> Result<Option<Note>> {
+        if !NoteFiles::is_note_path(rel) || !self.files.absolute(rel).is_file() {
+            return Ok(None);
+        }
+        let file = self.files.read(&self.storage, rel)?;
+        match self.files.note_at(&self.storage, rel)? {
+            Some(id) => {
+                if self.storage.get_note(&id)?.body == file.body {
+                    return Ok(None);
+                }
+                let patch = NotePatch {
+                    body: Some(file.body),
+                    ..Default::default()
+                };
+                self.update_note(&id, patch).map(Some)
+            }
+            None => {
+                let note = self
+                    .storage
+                    .create_note(&file.title, &file.body, &file.folder)?;
+                self.files.link(&self.storage, &note.id, rel)?;
+                self.note_saved(&note, ActivityKind::Created)?;
+                self.autocommit(&format!("Create \"{}\"", note.title))?;
+                Ok(Some(note))
+            }
+        }
+    }
+
+    /// Follows a mirrored file that was renamed or moved outside the app, so
+    /// its note keeps its id and history. A file that was not mirrored
+    /// before is imported as new.
+    pub fn file_moved(&self, from: &str, to: &str) -> Result<Option<Note>> {
+        let Some(id) = self.files.note_at(&self.storage, from)? else {
+            return self.import_file(to);
+        };
+        if !NoteFiles::is_note_path(to) || !self.files.absolute(to).is_file() {
+            return Ok(None);
+        }
+        let file = self.files.read(&self.storage, to)?;
+        self.files.link(&self.storage, &id, to)?;
+        let patch = NotePatch {
+            title: Some(file.title),
+            body: Some(file.body),
+            folder: Some(file.folder),
+        };
+        self.update_note(&id, patch).map(Some)
+    }
+
+    /// Trashes the note whose mirrored file disappeared, returning its id.
+    pub fn file_removed(&self, rel: &str) -> Result<Option<String>> {
+        let Some(id) = self.files.note_at(&self.storage, rel)? else {
+            return Ok(None);
+        };
+        self.trash_note(&id)?;
+        Ok(Some(id))
+    }
+
+    /// Reindexes the notes using the attachment `path` once its text has
+    /// been read.
+    pub fn attachment_read(&self, path: &str) -> Result<()> {
+        for id in text::notes_using(&self.storage, path)? {
+            if trash::is_trashed(&self.storage, &id)? {
+                continue;
+            }
+            let note = self.storage.get_note(&id)?;
+            self.search
+                .index_note(&note, &text::of_note(&self.storage, &id)?)?;
+        }
+        Ok(())
+    }
+
+    /// Full-text search. Archived notes are left out unless
+    /// `include_archived`.
+    pub fn search_notes(
+        &self,
+        query: &str,
+        limit: usize,
+        include_archived: bool,
+    ) -> Result<Vec<SearchHit>> {
+        let archived = match include_archived {
+            true => HashSet::new(),
+            false => self.storage.archived_ids()?,
+        };
+        let mut hits = self.search.search(query, limit + archived.len(), |id| {
+            if archived.contains(id) {
+                return None;
+            }
+            let body = self.storage.get_note(id).ok()?.body;
+            Some((body, text::of_note(&self.storage, id).unwrap_or_default()))
+        })?;
+        hits.retain(|hit| !archived.contains(&hit.id));
+        hits.truncate(limit);
+        Ok(hits)
+    }
+
+    pub fn reindex_all(&self) -> Result<usize> {
+        let notes = self.storage.all_notes()?;
+        self.tags.rebuild(&notes);
+        self.fields.rebuild(&notes);
+        self.places.rebuild(&notes);
+        self.tasks.rebuild(&notes);
+        self.cards.rebuild(&notes);
+        self.switcher.rebuild(&notes);
+        self.stats.rebuild(&notes);
+        self.links.rebuild(&notes);
+        attachments::rebuild(&self.storage, &notes)?;
+        self.search.rebuild(&notes, |note| {
+            text::of_note(&self.storage, &note.id).unwrap_or_default()
+        })
+    }
+
+    /// Renames each tag in `from`, nested tags included, to `to` in every
+    /// note using it. All notes change in one transaction and one commit.
+    /// Returns how many notes changed.
+    pub fn rename_tags(&self, from: &[String], to: &str) -> Result<usize> {
+        if self.storage.is_locked() {
+            return Err(Error::VaultLocked);
+        }
+        self.check_warm()?;
+        let mut ids: Vec<String> = from
+            .iter()
+            .flat_map(|tag| self.tags.notes_with(tag))
+            .collect();
+        ids.sort();
+        ids.dedup();
+
+        let mut patches = Vec::new();
+        for id in ids {
+            let mut body = self.storage.get_note(&id)?.body;
+            let mut changed = false;
+            for tag in from {
+                if let Some(renamed) = tags::rename_in(&body, tag, to) {
+                    body = renamed;
+                    changed = true;
+                }
+            }
+            if changed {
+                let patch = NotePatch {
+                    body: Some(body),
+                    ..Default::default()
+                };
+                patches.push((id, patch));
+            }
+        }
+        if patches.is_empty() {
+            return Ok(0);
+        }
+        let message = format!("Rename #{} to #{to}", from.join(", #"));
+        Ok(self.update_notes(patches, &message)?.len())
+    }
+
+    /// Runs `replacer` over every note, or only `note_ids`, in one
+    /// transaction and one commit. Returns the replacements made per note.
+    pub fn replace_text(
+        &self,
+        replacer: &Replacer,
+        note_ids: Option<&[String]>,
+    ) -> Result<Vec<NoteReplacement>> {
+        if self.storage.is_locked() {
+            return Err(Error::VaultLocked);
+        }
+        let mut patches = Vec::new();
+        let mut replaced = Vec::new();
+        for note in self.storage.all_notes()? {
+            // Sealed bodies are not text to replace in.
+            if note_ids.is_some_and(|ids| !ids.contains(&note.id))
+                || protected::is_protected(&note.body)
+            {
+                continue;
+            }
+            let Some((body, replacements)) = replacer.apply(&note.body) else {
+                continue;
+            };
+            replaced.push(NoteReplacement {
+                note_id: note.id.clone(),
+                note_title: note.title,
+                replacements,
+            });
+            let patch = NotePatch {
+                body: Some(body),
+                ..Default::default()
+            };
+            patches.push((note.id, patch));
+        }
+        if patches.is_empty() {
+            return Ok(replaced);
+        }
+        let total: usize = replaced.iter().map(|note| note.replacements).sum();
+        let message = format!("Replace {total} matches in {} notes", replaced.len());
+        self.update_notes(patches, &message)?;
+        Ok(replaced)
+    }
+
+    /// Sets or changes the vault password. The first time, this also moves
+    /// the search index off disk. Mirrored files are rewritten as sealed text.
+    pub fn set_password(&self, password: &str) -> Result<()> {
+        let was_encrypted = self.storage.is_encrypted();
+        self.drafts.reopen(&self.storage)?;
+        self.storage.set_password(password)?;
+        self.drafts.flush(&self.storage)?;
+        self.journal.roll(&self.storage)?;
+        if !was_encrypted {
+            self.search.set_persistent(false)?;
+            self.reindex_all()?;
+        }
+        for note in self.storage.all_notes()? {
+            self.files.write(&self.storage, &note)?;
+        }
+        collab::rewrite_own_files(self)?;
+        self.autocommit("Re-encrypt notes")
+    }
+
+    pub fn unlock(&self, password: &str) -> Result<()> {
+        self.storage.unlock(password)?;
+        self.unlocked()
+    }
+
+    pub fn unlock_with_key(&self, key: VaultKey) -> Result<()> {
+        self.storage.unlock_with_key(key)?;
+        self.unlocked()
+    }
+
+    /// Brings back what [`Vault::lock`] dropped.
+    fn unlocked(&self) -> Result<()> {
+        self.recover()?;
+        self.warm()
+    }
+
+    pub fn lock(&self) -> Result<()> {
+        // Staged drafts can only be sealed while the key is still there.
+        self.drafts.flush(&self.storage)?;
+        self.storage.lock();
+        self.warm.store(false, Ordering::Relaxed);
+        self.protected.clear();
+        self.tags.clear();
+        self.fields.clear();
+        self.places.clear();
+        self.tasks.clear();
+        self.cards.clear();
+        self.switcher.clear();
+        self.stats.clear();
+        self.links.clear();
+        self.rendered.clear();
+        self.search.clear()
+    }
+
+    /// Stops the background tasks working on this vault. They notice at
+    /// their next wake-up; queued ones are woken now.
+    pub fn close(&self) {
+        self.closed.store(true, Ordering::Relaxed);
+        self.ocr.wake();
+        self.pdf_text.wake();
+        self.transcribe.wake();
+        self.semantic.queue.wake();
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::Relaxed)
+    }
+
+    /// Swaps in a database restored from a backup and rebuilds everything
+    /// derived from it. If the copy was sealed under another password the
+    /// vault ends up locked, and the files follow on the next unlock.
+    pub fn restore_database(&self, src: &Path) -> Result<()> {
+        self.journal.roll(&self.storage)?;
+        self.storage.restore_from(src)?;
+        self.files.clear(&self.storage)?;
+        self.search.set_persistent(!self.storage.is_encrypted())?;
+        if self.storage.is_locked() {
+            self.search.clear()?;
+            self.tags.clear();
+            self.fields.clear();
+            self.places.clear();
+            self.tasks.clear();
+            self.cards.clear();
+            self.switcher.clear();
+            self.stats.clear();
+            self.links.clear();
+            self.rendered.clear();
+        } else {
+            self.files.export_missing(&self.storage)?;
+            self.reindex_all()?;
+        }
+        self.autocommit("Restore backup")
+    }
+
+    /// Sets up git sync in the note directory and points it at `url`.
+    pub fn configure_git_remote(
+        &self,
+        url: &str,
+        branch: Option<&str>,
+        credentials: Option<Credentials>,
+    ) -> Result<()> {
+        self.git
+            .init(&SyncFilter::load(&self.storage, SyncTarget::Git)?)?;
+        self.git.configure_remote(url, branch)?;
+        let (username, token) = match &credentials {
+            Some(c) => (c.username.as_str(), Some(c.token.as_str())),
+            None => ("", None),
+        };
+        self.storage.set_meta(GIT_USERNAME_KEY, username)?;
+        self.storage.set_secret(GIT_TOKEN_KEY, token)
+    }
+
+    /// Commits local changes, merges the remote branch into the store, and
+    /// pushes the result.
+    pub fn sync_git(&self, progress: &mut dyn FnMut(SyncProgress)) -> Result<SyncReport> {
+        let credentials = match self.storage.secret(GIT_TOKEN_KEY)? {
+            Some(token) => Some(Credentials {
+                username: self.storage.meta(GIT_USERNAME_KEY)?.unwrap_or_default(),
+                token,
+            }),
+            None => None,
+        };
+
+        let filter = SyncFilter::load(&self.storage, SyncTarget::Git)?;
+        self.git.commit_all("Sync local changes", &filter)?;
+        let outcome = self.git.pull(credentials.as_ref(), progress)?;
+        let mut report = SyncReport {
+            conflicts: outcome.conflicts,
+            ..Default::default()
+        };
+        report.pulled += activity::from_sync("git", None, || -> Result<usize> {
+            let mut pulled = 0;
+            for change in outcome.changes {
+                let applied = match change {
+                    PathChange::Written(rel) => self.import_file(&rel)?.is_some(),
+                    PathChange::Removed(rel) => self.file_removed(&rel)?.is_some(),
+                };
+                pulled += usize::from(applied);
+            }
+            Ok(pulled + collab::merge_peers(self)?)
+        })?;
+        report.pushed = self.git.push(credentials.as_ref(), progress)?;
+        progress(SyncProgress {
+            provider: "git",
+            stage: SyncStage::Done,
+            current: 1,
+            total: 1,
+        });
+        let _ = self.synced.send("git");
+        Ok(report)
+    }
+
+    /// Puts back the writes a crash or power cut kept from the database,
+    /// then starts the journal afresh and marks the logged jobs that were
+    /// still running as interrupted.
+    fn recover(&self) -> Result<()> {
+        let lost = self.journal.recover(&self.storage)?;
+        let recovered = lost.saved.len() + lost.deleted.len();
+        for note in &lost.saved {
+            self.storage.put_note(note)?;
+            self.note_saved(note, ActivityKind::Edited)?;
+        }
+        for id in &lost.deleted {
+            self.delete_note(id)?;
+        }
+        self.journal.roll(&self.storage)?;
+        jobs::log::interrupted(&self.storage)?;
+        if recovered > 0 {
+            self.autocommit("Recover unsaved changes")?;
+        }
+        Ok(())
+    }
+
+    fn note_saved(&self, note: &Note, kind: ActivityKind) -> Result<()> {
+        protected::track(self, note)?;
+        self.journal.saved(&self.storage, note)?;
+        self.drafts.saved(note)?;
+        collab::record_local(self, note)?;
+        history::record(&self.storage, note)?;
+        self.files.write(&self.storage, note)?;
+        self.tags.index_note(note);
+        self.fields.index_note(note);
+        self.places.index_note(note);
+        self.tasks.index_note(note);
+        self.cards.index_note(note);
+        self.switcher.index_note(note);
+        self.stats.index_note(note);
+        self.links.index_note(note);
+        attachments::track(&self.storage, note)?;
+        activity::record(&self.storage, &note.id, kind)?;
+        self.search
+            .index_note(note, &text::of_note(&self.storage, &note.id)?)?;
+        self.semantic.note_changed(&self.storage, &note.id)?;
+        self.ocr.wake();
+        self.pdf_text.wake();
+        self.transcribe.wake();
+        self.changed(&note.id, false);
+        Ok(())
+    }
+
+    fn changed(&self, id: &str, removed: bool) {
+        // No receivers is fine; nobody is listening yet.
+        let _ = self.changes.send(NoteChange {
+            note_id: id.to_owned(),
+            removed,
+        });
+    }
+
+    pub(crate) fn autocommit(&self, message: &str) -> Result<()> {
+        if !self.git.is_set_up() {
+            return Ok(());
+        }
+        let filter = SyncFilter::load(&self.storage, SyncTarget::Git)?;
+        self.git.commit_all(message, &filter).map(drop)
+    }
+}
+
+/// Locks the vault at `root` for this process, or fails with
+/// [`Error::VaultInUse`] if another has it.
+fn take_lock(root: &Path) -> Result<File> {
+    let file = File::options()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(root.join(LOCK_FILE))?;
+    match file.try_lock() {
+        Ok(()) => Ok(file),
+        Err(TryLockError::WouldBlock) => Err(Error::VaultInUse),
+        Err(TryLockError::Error(err)) => Err(err.into()),
+    }
+}
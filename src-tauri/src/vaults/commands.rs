@@ -0,0 +1,59 @@
+use std::path::Path;
+
+use tauri::{AppHandle, Manager, Window};
+
+use super::{RelocateReport, VaultInfo, Vaults};
+use crate::error::Result;
+use crate::jobs::{JobKind, Jobs, Priority};
+
+#[tauri::command]
+pub async fn list_vaults(app: AppHandle) -> Result<Vec<VaultInfo>> {
+    Ok(app.state::<Vaults>().list())
+}
+
+/// Adds a vault at `path`, starting an empty one or picking up one that is
+/// already there. Named after the directory unless `name` is given.
+#[tauri::command]
+pub async fn create_vault(app: AppHandle, path: String, name: Option<String>) -> Result<VaultInfo> {
+    tauri::async_runtime::spawn_blocking(move || {
+        app.state::<Vaults>().create(Path::new(&path), name)
+    })
+    .await?
+}
+
+/// Switches the calling window to vault `id`. Other windows keep theirs.
+#[tauri::command]
+pub async fn open_vault(app: AppHandle, window: Window, id: String) -> Result<VaultInfo> {
+    tauri::async_runtime::spawn_blocking(move || {
+        app.state::<Vaults>().bind(&app, window.label(), &id)
+    })
+    .await?
+}
+
+/// Forgets vault `id` without deleting anything on disk.
+#[tauri::command]
+pub async fn remove_vault(app: AppHandle, id: String) -> Result<()> {
+    tauri::async_runtime::spawn_blocking(move || app.state::<Vaults>().remove(&app, &id)).await?
+}
+
+/// Moves the calling window's vault, or vault `id`, to `new_path`, an empty
+/// or missing directory that may be on another drive. Runs as a job, so its
+/// progress shows and it can be cancelled; nothing changes unless it all
+/// copied.
+#[tauri::command]
+pub async fn relocate_vault(
+    app: AppHandle,
+    window: Window,
+    new_path: String,
+    id: Option<String>,
+) -> Result<RelocateReport> {
+    let id = id.unwrap_or_else(|| app.state::<Vaults>().id_of(window.label()));
+    let handle = app.clone();
+    app.state::<Jobs>()
+        .run(JobKind::Relocation, Priority::User, move |context| {
+            handle
+                .state::<Vaults>()
+                .relocate(&handle, &id, Path::new(&new_path), context)
+        })
+        .await
+}
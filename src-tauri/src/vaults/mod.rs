@@ -0,0 +1,485 @@
+pub mod commands;
+mod relocate;
+
+pub use relocate::RelocateReport;
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use serde::{Deserialize, Serialize};
+use tauri::ipc::{CommandArg, CommandItem, InvokeError};
+use tauri::{AppHandle, Emitter, Manager, Wry};
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::error::{Error, Result};
+use crate::jobs::{JobKind, Jobs, Priority};
+use crate::vault::{NoteChange, Vault};
+use crate::watcher::{self, Watcher};
+use crate::{
+    automation, backup, calendar, drafts, feeds, logging, mailin, ocr, os_index, pdf_text, plugins,
+    reminders, search, sync, transcribe, trash,
+};
+
+/// Event broadcast to every window with a [`VaultNoteChange`] whenever a
+/// note in an open vault is saved or removed, so windows showing the same
+/// note stay in step.
+pub const NOTE_CHANGED_EVENT: &str = "note-changed";
+/// Event sent to a window with the [`VaultInfo`] of the vault it now shows,
+/// when that changes without the window asking.
+pub const CHANGED_EVENT: &str = "vault-changed";
+/// Event carrying the id of a vault whose tags, links, tasks and other
+/// in-memory indexes have just been filled after it opened.
+pub const WARM_EVENT: &str = "vault-warm";
+
+/// The vault that lives directly in the app data directory, as it did
+/// before there could be more than one. It cannot be removed.
+pub const DEFAULT_ID: &str = "default";
+const DEFAULT_NAME: &str = "Notes";
+pub(crate) const REGISTRY_FILE: &str = "vaults.json";
+/// The window whose vault the tray, deep links and the local API act on.
+const PRIMARY_WINDOW: &str = "main";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Entry {
+    id: String,
+    name: String,
+    path: PathBuf,
+}
+
+/// What is kept in `vaults.json`: the known vaults and which window last
+/// showed which.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Registry {
+    vaults: Vec<Entry>,
+    windows: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VaultInfo {
+    pub id: String,
+    pub name: String,
+    pub path: String,
+    /// Whether the vault is loaded, with its sync and background work running.
+    pub open: bool,
+    /// Labels of the windows showing it.
+    pub windows: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VaultNoteChange {
+    pub vault_id: String,
+    #[serde(flatten)]
+    pub change: NoteChange,
+}
+
+struct OpenVault {
+    vault: Arc<Vault>,
+    _watcher: Watcher,
+}
+
+struct State {
+    registry: Registry,
+    open: HashMap<String, OpenVault>,
+    /// Vaults being relocated, which must not be opened meanwhile.
+    moving: HashSet<String>,
+}
+
+/// Every notes directory the app knows about, managed as app state. Each is
+/// a full [`Vault`] with its own database, index and settings, opened when a
+/// window first needs it and kept open, background tasks and all, until it
+/// is removed or the app quits.
+pub struct Vaults {
+    app_dir: PathBuf,
+    state: Mutex<State>,
+}
+
+impl Vaults {
+    pub fn load(app_dir: &Path) -> Result<Self> {
+        fs::create_dir_all(app_dir)?;
+        let mut registry: Registry = match fs::read_to_string(app_dir.join(REGISTRY_FILE)) {
+            Ok(json) => serde_json::from_str(&json)?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Registry::default(),
+            Err(err) => return Err(err.into()),
+        };
+        if !registry.vaults.iter().any(|entry| entry.id == DEFAULT_ID) {
+            registry.vaults.insert(
+                0,
+                Entry {
+                    id: DEFAULT_ID.into(),
+                    name: DEFAULT_NAME.into(),
+                    path: app_dir.to_owned(),
+                },
+            );
+        }
+        Ok(Self {
+            app_dir: app_dir.to_owned(),
+            state: Mutex::new(State {
+                registry,
+                open: HashMap::new(),
+                moving: HashSet::new(),
+            }),
+        })
+    }
+
+    pub fn list(&self) -> Vec<VaultInfo> {
+        let state = self.lock();
+        state
+            .registry
+            .vaults
+            .iter()
+            .map(|entry| info(&state, entry))
+            .collect()
+    }
+
+    /// The directory of vault `id`, without opening it.
+    pub fn path_of(&self, id: &str) -> Result<PathBuf> {
+        Ok(entry(&self.lock(), id)?.path.clone())
+    }
+
+    /// Adds the vault at `path`: a new one if the directory is empty or
+    /// missing, or an existing one, say synced from another machine.
+    pub fn create(&self, path: &Path, name: Option<String>) -> Result<VaultInfo> {
+        if !path.is_absolute() {
+            return Err(Error::InvalidInput(format!(
+                "vault path must be absolute: {}",
+                path.display()
+            )));
+        }
+        let existing = path.join("notes.db").is_file();
+        if !existing && path.exists() && fs::read_dir(path)?.next().is_some() {
+            return Err(Error::InvalidInput(format!(
+                "{} is not empty and holds no vault",
+                path.display()
+            )));
+        }
+        fs::create_dir_all(path)?;
+        let mut state = self.lock();
+        let path = path.canonicalize()?;
+        let taken = state
+            .registry
+            .vaults
+            .iter()
+            .any(|entry| entry.path.canonicalize().is_ok_and(|known| known == path));
+        if taken {
+            return Err(Error::InvalidInput(format!(
+                "{} is already a vault",
+                path.display()
+            )));
+        }
+        if !existing {
+            // Lays out the database and directories now rather than on
+            // first open.
+            Vault::open(&path)?;
+        }
+        let name = name
+            .filter(|name| !name.trim().is_empty())
+            .or_else(|| {
+                path.file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+            })
+            .unwrap_or_else(|| "Vault".into());
+        let entry = Entry {
+            id: uuid::Uuid::new_v4().to_string(),
+            name,
+            path,
+        };
+        state.registry.vaults.push(entry.clone());
+        self.save(&state)?;
+        Ok(info(&state, &entry))
+    }
+
+    /// Shows vault `id` in `window` from now on, opening it if needed.
+    pub fn bind(&self, app: &AppHandle, window: &str, id: &str) -> Result<VaultInfo> {
+        let mut state = self.lock();
+        let entry = entry(&state, id)?.clone();
+        open(app, &mut state, &entry)?;
+        state
+            .registry
+            .windows
+            .insert(window.to_owned(), entry.id.clone());
+        self.save(&state)?;
+        Ok(info(&state, &entry))
+    }
+
+    /// Switches the main window to vault `id`, telling it so, unless it
+    /// already shows it.
+    pub fn show_in_primary(&self, app: &AppHandle, id: &str) -> Result<()> {
+        if self.id_of(PRIMARY_WINDOW) == id {
+            return Ok(());
+        }
+        let info = self.bind(app, PRIMARY_WINDOW, id)?;
+        if let Some(window) = app.get_webview_window(PRIMARY_WINDOW) {
+            window.emit(CHANGED_EVENT, &info)?;
+        }
+        Ok(())
+    }
+
+    /// The vault shown in `window`; the default one until another is bound.
+    pub fn of_window(&self, app: &AppHandle, window: &str) -> Result<Arc<Vault>> {
+        let mut state = self.lock();
+        let entry = state
+            .registry
+            .windows
+            .get(window)
+            .and_then(|id| entry(&state, id).ok())
+            .or_else(|| entry(&state, DEFAULT_ID).ok())
+            .expect("the default vault is always registered")
+            .clone();
+        open(app, &mut state, &entry)
+    }
+
+    /// The id of the vault shown in `window`.
+    pub fn id_of(&self, window: &str) -> String {
+        let state = self.lock();
+        match state.registry.windows.get(window) {
+            Some(id) if entry(&state, id).is_ok() => id.clone(),
+            _ => DEFAULT_ID.to_owned(),
+        }
+    }
+
+    /// The vaults that are loaded, with their ids.
+    pub fn open_vaults(&self) -> Vec<(String, Arc<Vault>)> {
+        self.lock()
+            .open
+            .iter()
+            .map(|(id, open)| (id.clone(), Arc::clone(&open.vault)))
+            .collect()
+    }
+
+    /// Vault `id`, opening it if needed.
+    pub fn get(&self, app: &AppHandle, id: &str) -> Result<Arc<Vault>> {
+        let mut state = self.lock();
+        let entry = entry(&state, id)?.clone();
+        open(app, &mut state, &entry)
+    }
+
+    /// Drops the binding of a window that closed for good.
+    pub fn unbind(&self, window: &str) -> Result<()> {
+        let mut state = self.lock();
+        if state.registry.windows.remove(window).is_some() {
+            self.save(&state)?;
+        }
+        Ok(())
+    }
+
+    /// Forgets vault `id` and stops its background work. Its files stay on
+    /// disk; windows that showed it go back to the default vault.
+    pub fn remove(&self, app: &AppHandle, id: &str) -> Result<()> {
+        if id == DEFAULT_ID {
+            return Err(Error::InvalidInput(
+                "the default vault cannot be removed".into(),
+            ));
+        }
+        let mut state = self.lock();
+        entry(&state, id)?;
+        state.registry.vaults.retain(|entry| entry.id != id);
+        let windows: Vec<String> = state
+            .registry
+            .windows
+            .iter()
+            .filter(|(_, vault)| *vault == id)
+            .map(|(window, _)| window.clone())
+            .collect();
+        for window in &windows {
+            state.registry.windows.remove(window);
+        }
+        if let Some(open) = state.open.remove(id) {
+            open.vault.close();
+        }
+        logging::detach_vault(id);
+        self.save(&state)?;
+
+        let default = entry(&state, DEFAULT_ID)?.clone();
+        open(app, &mut state, &default)?;
+        let default = info(&state, &default);
+        for window in windows {
+            if let Some(window) = app.get_webview_window(&window) {
+                let _ = window.emit(CHANGED_EVENT, &default);
+            }
+        }
+        Ok(())
+    }
+
+    fn save(&self, state: &State) -> Result<()> {
+        let json = serde_json::to_string_pretty(&state.registry)?;
+        fs::write(self.app_dir.join(REGISTRY_FILE), json)?;
+        Ok(())
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().expect("vaults poisoned")
+    }
+}
+
+/// The vault of the main window, which app-wide entry points (tray, links,
+/// the local API) work on.
+pub fn primary(app: &AppHandle) -> Result<Arc<Vault>> {
+    app.state::<Vaults>().of_window(app, PRIMARY_WINDOW)
+}
+
+/// The vault of the window a command came from. Taking this as a command
+/// argument in place of `State<Vault>` makes the command act on whichever
+/// vault that window shows.
+pub struct Current(Arc<Vault>);
+
+impl Current {
+    /// The vault as the app shares it, for work handed to other threads.
+    pub fn shared(&self) -> &Arc<Vault> {
+        &self.0
+    }
+}
+
+impl Deref for Current {
+    type Target = Vault;
+
+    fn deref(&self) -> &Vault {
+        &self.0
+    }
+}
+
+impl<'de> CommandArg<'de, Wry> for Current {
+    fn from_command(command: CommandItem<'de, Wry>) -> std::result::Result<Self, InvokeError> {
+        let webview = command.message.webview_ref();
+        let app = webview.app_handle();
+        app.state::<Vaults>()
+            .of_window(app, webview.window().label())
+            .map(Current)
+            .map_err(|err| InvokeError::from(err.to_string()))
+    }
+}
+
+fn entry<'a>(state: &'a State, id: &str) -> Result<&'a Entry> {
+    state
+        .registry
+        .vaults
+        .iter()
+        .find(|entry| entry.id == id)
+        .ok_or_else(|| Error::VaultNotFound(id.to_owned()))
+}
+
+fn info(state: &State, entry: &Entry) -> VaultInfo {
+    let mut windows: Vec<String> = state
+        .registry
+        .windows
+        .iter()
+        .filter(|(_, id)| **id == entry.id)
+        .map(|(window, _)| window.clone())
+        .collect();
+    windows.sort();
+    VaultInfo {
+        id: entry.id.clone(),
+        name: entry.name.clone(),
+        path: entry.path.to_string_lossy().into_owned(),
+        open: state.open.contains_key(&entry.id),
+        windows,
+    }
+}
+
+fn open(app: &AppHandle, state: &mut State, entry: &Entry) -> Result<Arc<Vault>> {
+    if let Some(open) = state.open.get(&entry.id) {
+        return Ok(Arc::clone(&open.vault));
+    }
+    if state.moving.contains(&entry.id) {
+        return Err(Error::InvalidInput(format!(
+            "{} is being moved",
+            entry.name
+        )));
+    }
+    logging::attach_vault(&entry.id, &entry.path);
+    let vault = match Vault::open_cold(&entry.path) {
+        Ok(vault) => Arc::new(vault),
+        Err(err) => {
+            tracing::error!(vault = %entry.id, error = %err, "opening the vault failed");
+            return Err(err);
+        }
+    };
+    tracing::info!(vault = %entry.id, encrypted = vault.storage.is_encrypted(), "vault opened");
+    let watcher = watcher::start(app, Arc::clone(&vault))?;
+    sync::webdav::spawn_periodic(app.clone(), Arc::clone(&vault));
+    sync::s3::spawn_periodic(app.clone(), Arc::clone(&vault));
+    backup::spawn_periodic(app.clone(), Arc::clone(&vault));
+    trash::spawn_periodic(Arc::clone(&vault));
+    drafts::spawn_periodic(Arc::clone(&vault));
+    reminders::spawn_periodic(app.clone(), Arc::clone(&vault));
+    calendar::spawn_periodic(app.clone(), Arc::clone(&vault));
+    automation::spawn(app.clone(), entry.id.clone(), Arc::clone(&vault));
+    plugins::spawn_events(app.clone(), Arc::clone(&vault));
+    feeds::spawn_periodic(app.clone(), Arc::clone(&vault));
+    mailin::spawn_periodic(app.clone(), Arc::clone(&vault));
+    ocr::spawn_worker(app.clone(), Arc::clone(&vault));
+    pdf_text::spawn_worker(app.clone(), Arc::clone(&vault));
+    transcribe::spawn_worker(app.clone(), Arc::clone(&vault));
+    search::semantic::spawn_worker(app.clone(), Arc::clone(&vault));
+    os_index::spawn_indexer(app.clone(), entry.id.clone(), Arc::clone(&vault));
+    forward_changes(app.clone(), entry.id.clone(), &vault);
+    warm_up(app.clone(), entry.id.clone(), Arc::clone(&vault));
+    log_syncs(entry.id.clone(), &vault);
+    state.open.insert(
+        entry.id.clone(),
+        OpenVault {
+            vault: Arc::clone(&vault),
+            _watcher: watcher,
+        },
+    );
+    Ok(vault)
+}
+
+/// Loads what the sidebar shows first right away, then fills the vault's
+/// indexes on the job pool and tells every window once it has.
+fn warm_up(app: AppHandle, vault_id: String, vault: Arc<Vault>) {
+    tauri::async_runtime::spawn(async move {
+        let preloading = Arc::clone(&vault);
+        let _ = tauri::async_runtime::spawn_blocking(move || preloading.preload()).await;
+        let warmed = app
+            .state::<Jobs>()
+            .run(JobKind::Reindex, Priority::Normal, move |_| vault.warm())
+            .await;
+        match warmed {
+            Ok(()) => {
+                tracing::info!(vault = %vault_id, "indexes warm");
+                let _ = app.emit(WARM_EVENT, vault_id);
+            }
+            Err(err) => tracing::warn!(vault = %vault_id, error = %err, "warming indexes failed"),
+        }
+    });
+}
+
+/// Logs each sync of the vault that finished, until it is dropped.
+fn log_syncs(vault_id: String, vault: &Vault) {
+    let mut synced = vault.synced.subscribe();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            match synced.recv().await {
+                Ok(provider) => tracing::info!(vault = %vault_id, provider, "sync finished"),
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => break,
+            }
+        }
+    });
+}
+
+/// Relays the vault's note changes to every window until the vault is
+/// dropped.
+fn forward_changes(app: AppHandle, vault_id: String, vault: &Vault) {
+    let mut changes = vault.changes.subscribe();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            match changes.recv().await {
+                Ok(change) => {
+                    let change = VaultNoteChange {
+                        vault_id: vault_id.clone(),
+                        change,
+                    };
+                    let _ = app.emit(NOTE_CHANGED_EVENT, change);
+                }
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => break,
+            }
+        }
+    });
+}
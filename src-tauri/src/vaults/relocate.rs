@@ -0,0 +1,497 @@
+//! Moving a vault to another directory or drive. Everything is copied and
+//! checked before the registry points at the new place, and the old copy is
+//! only deleted after that, so a failure or cancel at any step leaves the
+//! vault where it was.
+//!
+//! Sync clients such as OneDrive and Dropbox lock files for a moment while
+//! they upload them, so a target inside one gets more patience when a file
+//! is busy, and files are written under a temporary name so half-copied
+//! ones are never uploaded.
+
+use std::fs::{self, File};
+use std::io::{self, ErrorKind, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+use rusqlite::Connection;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use tauri::{AppHandle, Emitter, Manager};
+
+use super::{
+    entry, info, open, State, VaultInfo, Vaults, CHANGED_EVENT, DEFAULT_ID, REGISTRY_FILE,
+};
+use crate::error::{Error, Result};
+use crate::jobs::JobContext;
+use crate::logging;
+use crate::storage::Storage;
+use crate::vault;
+
+const DATABASE: &str = "notes.db";
+/// What of the app data directory belongs to the app rather than the
+/// default vault, which lives there too. Everything else there moves with
+/// the vault.
+const APP_ENTRIES: &[&str] = &[
+    REGISTRY_FILE,
+    "windows.json",
+    "rollout.json",
+    "themes",
+    "plugins",
+    "dictionaries",
+    "logs",
+    "models/whisper",
+];
+/// Suffix of a file still being copied.
+const PARTIAL: &str = ".moving";
+/// Tries at a busy file, with waits doubling up to [`MAX_WAIT`].
+const ATTEMPTS: u32 = 5;
+const CLOUD_ATTEMPTS: u32 = 12;
+const MAX_WAIT: Duration = Duration::from_secs(5);
+
+/// Folder names sync clients keep their files under.
+const CLOUD_FOLDERS: &[(&str, &str)] = &[
+    ("dropbox", "Dropbox"),
+    ("onedrive", "OneDrive"),
+    ("icloud drive", "iCloud Drive"),
+    ("icloud~", "iCloud Drive"),
+    ("mobile documents", "iCloud Drive"),
+    ("google drive", "Google Drive"),
+    ("googledrive", "Google Drive"),
+    ("my drive", "Google Drive"),
+    ("pcloud drive", "pCloud"),
+    ("nextcloud", "Nextcloud"),
+    ("owncloud", "ownCloud"),
+    ("megasync", "MEGA"),
+    ("box sync", "Box"),
+];
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RelocateReport {
+    pub vault: VaultInfo,
+    pub files: usize,
+    pub bytes: u64,
+    /// The sync client whose folder the vault is now in. Its database must
+    /// then not be open on two machines at once.
+    pub cloud: Option<String>,
+    /// Old files that could not be deleted, usually because something still
+    /// had them open. The vault no longer uses them.
+    pub leftovers: Vec<String>,
+}
+
+impl Vaults {
+    /// Moves vault `id` to `target`, a directory that is empty or missing,
+    /// and opens it there again if it was open. Windows showing it are told
+    /// the new path.
+    pub fn relocate(
+        &self,
+        app: &AppHandle,
+        id: &str,
+        target: &Path,
+        context: &JobContext,
+    ) -> Result<RelocateReport> {
+        let (entry, vault, kept) = {
+            let mut state = self.lock();
+            let entry = entry(&state, id)?.clone();
+            check_target(&state, &entry.path, target)?;
+            if !state.moving.insert(id.to_owned()) {
+                return Err(Error::InvalidInput(format!(
+                    "{} is already being moved",
+                    entry.name
+                )));
+            }
+            // Its watcher stops with it; new commands for it fail until
+            // the move is done.
+            let vault = state.open.remove(id).map(|open| {
+                open.vault.close();
+                open.vault
+            });
+            // Another vault kept inside this one's directory stays put.
+            let mut kept: Vec<PathBuf> = state
+                .registry
+                .vaults
+                .iter()
+                .filter(|other| other.id != id)
+                .filter_map(|other| other.path.strip_prefix(&entry.path).ok())
+                .map(Path::to_owned)
+                .collect();
+            if id == DEFAULT_ID {
+                kept.extend(APP_ENTRIES.iter().map(PathBuf::from));
+            }
+            (entry, vault, kept)
+        };
+        let was_open = vault.is_some();
+        logging::detach_vault(id);
+        let cloud = cloud_provider(target);
+        let target_existed = target.exists();
+        let moved = (|| {
+            let storage = match &vault {
+                // Commands that got hold of the vault before it closed
+                // could still write to it, and what they wrote would be
+                // lost with the old copy.
+                Some(vault) => {
+                    vault.storage.refuse_writes()?;
+                    &vault.storage
+                }
+                None => &Storage::open(&entry.path.join(DATABASE))?,
+            };
+            copy_vault(
+                storage,
+                &entry.path,
+                &kept,
+                target,
+                cloud.is_some(),
+                context,
+            )
+        })();
+        drop(vault);
+
+        let mut state = self.lock();
+        state.moving.remove(id);
+        let result = moved.and_then(|copied| {
+            let path = target.canonicalize()?;
+            set_path(&mut state, id, path);
+            self.save(&state)
+                .inspect_err(|_| set_path(&mut state, id, entry.path.clone()))?;
+            Ok(copied)
+        });
+        let (copied, bytes) = match result {
+            Ok(copied) => copied,
+            Err(err) => {
+                tracing::warn!(vault = %id, error = %err, "moving the vault failed");
+                remove_copy(target, target_existed);
+                if was_open {
+                    let _ = open(app, &mut state, &entry);
+                }
+                return Err(err);
+            }
+        };
+        // The database is counted along with the files.
+        let files = copied.len() + 1;
+        tracing::info!(vault = %id, files, bytes, cloud = ?cloud, "vault moved");
+
+        let leftovers = match kept.is_empty() {
+            true => remove(&entry.path)
+                .err()
+                .map(|_| entry.path.to_string_lossy().into_owned())
+                .into_iter()
+                .collect(),
+            false => remove_copied(&entry.path, &copied),
+        };
+        let entry = super::entry(&state, id)?.clone();
+        if was_open {
+            open(app, &mut state, &entry)?;
+        }
+        let vault = info(&state, &entry);
+        for window in &vault.windows {
+            if let Some(window) = app.get_webview_window(window) {
+                let _ = window.emit(CHANGED_EVENT, &vault);
+            }
+        }
+        Ok(RelocateReport {
+            vault,
+            files,
+            bytes,
+            cloud: cloud.map(str::to_owned),
+            leftovers,
+        })
+    }
+}
+
+fn set_path(state: &mut State, id: &str, path: PathBuf) {
+    if let Some(entry) = state
+        .registry
+        .vaults
+        .iter_mut()
+        .find(|entry| entry.id == id)
+    {
+        entry.path = path;
+    }
+}
+
+fn check_target(state: &State, source: &Path, target: &Path) -> Result<()> {
+    if !target.is_absolute() {
+        return Err(Error::InvalidInput(format!(
+            "vault path must be absolute: {}",
+            target.display()
+        )));
+    }
+    if target.exists() && fs::read_dir(target)?.next().is_some() {
+        return Err(Error::InvalidInput(format!(
+            "{} is not empty",
+            target.display()
+        )));
+    }
+    // Compared by the nearest ancestor that exists, since the target may
+    // not yet.
+    let resolved = target
+        .ancestors()
+        .find_map(|dir| {
+            let rest = target.strip_prefix(dir).ok()?;
+            Some(dir.canonicalize().ok()?.join(rest))
+        })
+        .unwrap_or_else(|| target.to_owned());
+    let source = source.canonicalize()?;
+    if resolved.starts_with(&source) {
+        return Err(Error::InvalidInput(
+            "a vault cannot be moved into itself".into(),
+        ));
+    }
+    let taken = state.registry.vaults.iter().any(|entry| {
+        entry
+            .path
+            .canonicalize()
+            .is_ok_and(|known| resolved.starts_with(known))
+    });
+    if taken {
+        return Err(Error::InvalidInput(format!(
+            "{} is inside another vault",
+            target.display()
+        )));
+    }
+    Ok(())
+}
+
+/// The sync client whose folder `path` is in, going by folder names.
+fn cloud_provider(path: &Path) -> Option<&'static str> {
+    path.components().find_map(|component| {
+        let Component::Normal(name) = component else {
+            return None;
+        };
+        let name = name.to_string_lossy().to_lowercase();
+        CLOUD_FOLDERS
+            .iter()
+            .find(|(folder, _)| name.starts_with(folder))
+            .map(|(_, provider)| *provider)
+            // macOS puts every File Provider client under ~/Library/CloudStorage.
+            .or_else(|| (name == "cloudstorage").then_some("a cloud drive"))
+    })
+}
+
+/// Copies the files in `root` but those under `kept` to `target`, and the
+/// database as a consistent snapshot, checking each. Returns the files
+/// copied besides the database, and the bytes copied with it.
+fn copy_vault(
+    storage: &Storage,
+    root: &Path,
+    kept: &[PathBuf],
+    target: &Path,
+    cloud: bool,
+    context: &JobContext,
+) -> Result<(Vec<PathBuf>, u64)> {
+    let mut files = Vec::new();
+    walk(root, Path::new(""), kept, &mut files)?;
+    // The database goes last so it misses as little as possible.
+    let total = files.len() + 1;
+    let mut bytes = 0;
+    retry(cloud, || fs::create_dir_all(target))?;
+    for (done, (rel, size)) in files.iter().enumerate() {
+        context.check()?;
+        context.progress(done, total);
+        copy_verified(&root.join(rel), &target.join(rel), cloud)?;
+        bytes += size;
+    }
+    context.check()?;
+    context.progress(files.len(), total);
+    let db = target.join(DATABASE);
+    let partial = partial(&db);
+    let _ = fs::remove_file(&partial);
+    storage.snapshot(&partial)?;
+    check_database(storage, &partial)?;
+    retry(cloud, || fs::rename(&partial, &db))?;
+    bytes += fs::metadata(&db)?.len();
+    context.progress(total, total);
+    Ok((files.into_iter().map(|(rel, _)| rel).collect(), bytes))
+}
+
+/// Files under `rel` in `root` with their sizes, leaving out those under
+/// `kept`, the live database and the search index's locks. Links are not
+/// followed.
+fn walk(
+    root: &Path,
+    rel: &Path,
+    kept: &[PathBuf],
+    files: &mut Vec<(PathBuf, u64)>,
+) -> io::Result<()> {
+    if kept.iter().any(|kept| kept == rel) {
+        return Ok(());
+    }
+    let meta = match fs::symlink_metadata(root.join(rel)) {
+        Ok(meta) => meta,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(err),
+    };
+    if meta.is_dir() {
+        for item in fs::read_dir(root.join(rel))? {
+            walk(root, &rel.join(item?.file_name()), kept, files)?;
+        }
+    } else if meta.is_file() && !skipped(rel) {
+        files.push((rel.to_owned(), meta.len()));
+    }
+    Ok(())
+}
+
+fn skipped(rel: &Path) -> bool {
+    let name = rel
+        .file_name()
+        .map(|name| name.to_string_lossy())
+        .unwrap_or_default();
+    let top = rel.parent() == Some(Path::new(""));
+    let database = top && name.starts_with(DATABASE);
+    let lock = top && name == vault::LOCK_FILE;
+    database || lock || (name.starts_with(".tantivy-") && name.ends_with(".lock"))
+}
+
+fn partial(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_owned();
+    name.push(PARTIAL);
+    path.with_file_name(name)
+}
+
+/// Copies `src` to `dest` under a temporary name, reads it back to compare
+/// hashes and only then gives it its name. Keeps the modification time,
+/// which the notes mirror goes by.
+fn copy_verified(src: &Path, dest: &Path, cloud: bool) -> Result<()> {
+    if let Some(parent) = dest.parent() {
+        retry(cloud, || fs::create_dir_all(parent))?;
+    }
+    let part = partial(dest);
+    let written = retry(cloud, || {
+        let mut reader = File::open(src)?;
+        let modified = reader.metadata()?.modified()?;
+        let mut writer = File::create(&part)?;
+        let hash = hash_copy(&mut reader, &mut writer)?;
+        writer.sync_all()?;
+        writer.set_modified(modified)?;
+        Ok(hash)
+    })?;
+    let read_back = retry(cloud, || {
+        hash_copy(&mut File::open(&part)?, &mut io::sink())
+    })?;
+    if read_back != written {
+        let _ = fs::remove_file(&part);
+        return Err(Error::Relocation(format!(
+            "{} changed on the way",
+            src.display()
+        )));
+    }
+    retry(cloud, || fs::rename(&part, dest))?;
+    Ok(())
+}
+
+fn hash_copy(reader: &mut impl Read, writer: &mut impl Write) -> io::Result<Vec<u8>> {
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0; 1 << 16];
+    loop {
+        let read = match reader.read(&mut buf) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        };
+        hasher.update(&buf[..read]);
+        writer.write_all(&buf[..read])?;
+    }
+    Ok(hasher.finalize().to_vec())
+}
+
+/// That the snapshot at `copy` is sound and holds as many notes.
+fn check_database(storage: &Storage, copy: &Path) -> Result<()> {
+    let count = |conn: &Connection| -> rusqlite::Result<i64> {
+        conn.query_row("SELECT COUNT(*) FROM notes", [], |row| row.get(0))
+    };
+    let expected = count(&storage.conn())?;
+    let conn = Connection::open(copy)?;
+    let check: String = conn.query_row("PRAGMA integrity_check", [], |row| row.get(0))?;
+    if check != "ok" {
+        return Err(Error::Relocation(format!(
+            "the copied database is damaged: {check}"
+        )));
+    }
+    let copied = count(&conn)?;
+    if copied != expected {
+        return Err(Error::Relocation(format!(
+            "the copied database has {copied} notes, not {expected}"
+        )));
+    }
+    Ok(())
+}
+
+/// Runs `op` again while the file it works on is busy.
+fn retry<T>(cloud: bool, mut op: impl FnMut() -> io::Result<T>) -> io::Result<T> {
+    let attempts = if cloud { CLOUD_ATTEMPTS } else { ATTEMPTS };
+    let mut wait = Duration::from_millis(100);
+    for _ in 1..attempts {
+        match op() {
+            Err(err) if busy(&err) => {
+                thread::sleep(wait);
+                wait = (wait * 2).min(MAX_WAIT);
+            }
+            result => return result,
+        }
+    }
+    op()
+}
+
+fn busy(err: &io::Error) -> bool {
+    // Windows reports a file another process holds as a sharing or lock
+    // violation.
+    let locked = cfg!(windows) && matches!(err.raw_os_error(), Some(32 | 33));
+    locked
+        || matches!(
+            err.kind(),
+            ErrorKind::PermissionDenied | ErrorKind::WouldBlock | ErrorKind::ResourceBusy
+        )
+}
+
+/// Takes away what a failed move copied. The target was empty before.
+fn remove_copy(target: &Path, existed: bool) {
+    let result = match existed {
+        true => fs::read_dir(target)
+            .and_then(|mut items| items.try_for_each(|item| remove(&item?.path()))),
+        false => fs::remove_dir_all(target),
+    };
+    if let Err(err) = result {
+        if err.kind() != ErrorKind::NotFound {
+            tracing::warn!(path = %target.display(), error = %err, "removing a partial copy failed");
+        }
+    }
+}
+
+/// Deletes the files of a moved vault that shares its directory with
+/// others, then the folders they leave empty, returning those it could not
+/// delete.
+fn remove_copied(root: &Path, copied: &[PathBuf]) -> Vec<String> {
+    let database = [DATABASE, "notes.db-wal", "notes.db-shm"].map(PathBuf::from);
+    let leftovers = copied
+        .iter()
+        .chain(&database)
+        .filter(|rel| remove(&root.join(rel)).is_err())
+        .map(|rel| rel.to_string_lossy().into_owned())
+        .collect();
+    let mut folders: Vec<&Path> = copied
+        .iter()
+        .flat_map(|rel| rel.ancestors().skip(1))
+        .filter(|folder| !folder.as_os_str().is_empty())
+        .collect();
+    // Deepest first, so each is empty by the time it comes up.
+    folders.sort_by_key(|folder| std::cmp::Reverse(folder.components().count()));
+    folders.dedup();
+    for folder in folders {
+        let _ = fs::remove_dir(root.join(folder));
+    }
+    leftovers
+}
+
+fn remove(path: &Path) -> io::Result<()> {
+    let result = match fs::symlink_metadata(path) {
+        Ok(meta) if meta.is_dir() => retry(false, || fs::remove_dir_all(path)),
+        Ok(_) => retry(false, || fs::remove_file(path)),
+        Err(err) => Err(err),
+    };
+    match result {
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
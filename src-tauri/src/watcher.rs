@@ -0,0 +1,184 @@
+use std::collections::BTreeSet;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use notify_debouncer_full::notify::event::{ModifyKind, RenameMode};
+use notify_debouncer_full::notify::{EventKind, RecommendedWatcher, RecursiveMode};
+use notify_debouncer_full::{new_debouncer, DebounceEventResult, Debouncer, RecommendedCache};
+use serde::Serialize;
+use tauri::{AppHandle, Emitter};
+
+use crate::error::Result;
+use crate::files::NoteFiles;
+use crate::vault::Vault;
+
+/// Event carrying an [`ExternalChange`] for each note another program
+/// changed, so open editors can offer to reload.
+pub const CHANGED_EVENT: &str = "note-changed-externally";
+/// Event carrying the message of a change that could not be brought in.
+pub const ERROR_EVENT: &str = "watcher-error";
+
+/// Editors tend to save in bursts (temp file, rename, touch); this is how
+/// long the directory has to be quiet before a burst is handled.
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeKind {
+    Created,
+    Modified,
+    Moved,
+    Removed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExternalChange {
+    pub note_id: String,
+    /// Mirror path the note now has, or had if it was removed.
+    pub path: String,
+    pub kind: ChangeKind,
+}
+
+/// Watches a vault's note mirror for as long as it is kept.
+pub struct Watcher {
+    _debouncer: Debouncer<RecommendedWatcher, RecommendedCache>,
+}
+
+enum Touched {
+    Path(String),
+    Moved { from: String, to: String },
+}
+
+/// Starts watching the note directory. Files the app writes itself come
+/// back as events too, but match the store and are ignored.
+pub fn start(app: &AppHandle, vault: Arc<Vault>) -> Result<Watcher> {
+    let root = vault.files.dir().to_owned();
+    let handle = app.clone();
+    let events_root = root.clone();
+    let mut debouncer = new_debouncer(DEBOUNCE, None, move |result: DebounceEventResult| {
+        let events = match result {
+            Ok(events) => events,
+            Err(errors) => {
+                for err in errors {
+                    tracing::warn!(error = %err, "watching note files failed");
+                    let _ = handle.emit(ERROR_EVENT, err.to_string());
+                }
+                return;
+            }
+        };
+        let mut touched = Vec::new();
+        for event in events {
+            let paths: Vec<String> = event
+                .paths
+                .iter()
+                .filter_map(|path| relative(&events_root, path))
+                .collect();
+            match (event.kind, paths.as_slice()) {
+                (EventKind::Access(_), _) => {}
+                (EventKind::Modify(ModifyKind::Name(RenameMode::Both)), [from, to]) => {
+                    touched.push(Touched::Moved {
+                        from: from.clone(),
+                        to: to.clone(),
+                    });
+                }
+                _ => touched.extend(paths.into_iter().map(Touched::Path)),
+            }
+        }
+        reconcile(&handle, &vault, touched);
+    })?;
+    debouncer.watch(&root, RecursiveMode::Recursive)?;
+    Ok(Watcher {
+        _debouncer: debouncer,
+    })
+}
+
+fn reconcile(app: &AppHandle, vault: &Vault, touched: Vec<Touched>) {
+    // Sealed files cannot be read back while locked; the next unlock
+    // rewrites them from the store anyway.
+    if vault.storage.is_locked() {
+        return;
+    }
+    let mut paths = BTreeSet::new();
+    let mut changes = Vec::new();
+    for touch in touched {
+        match touch {
+            Touched::Path(rel) => {
+                paths.insert(rel);
+            }
+            Touched::Moved { from, to } if vault.files.absolute(&to).is_dir() => {
+                // A renamed folder only reports itself, not the notes in it.
+                let prefix = format!("{to}/");
+                let moved = vault.files.list().unwrap_or_default();
+                for rel in moved.iter().filter(|rel| rel.starts_with(&prefix)) {
+                    let old = format!("{from}/{}", &rel[prefix.len()..]);
+                    changes.push(moved_note(vault, &old, rel));
+                }
+            }
+            Touched::Moved { from, to } => changes.push(moved_note(vault, &from, &to)),
+        }
+    }
+    for rel in paths.iter().filter(|rel| NoteFiles::is_note_path(rel)) {
+        changes.push(changed_note(vault, rel));
+    }
+
+    for change in changes {
+        match change {
+            Ok(Some(change)) => {
+                let _ = app.emit(CHANGED_EVENT, change);
+            }
+            Ok(None) => {}
+            Err(err) => {
+                tracing::warn!(error = %err, "applying an outside edit failed");
+                let _ = app.emit(ERROR_EVENT, err.to_string());
+            }
+        }
+    }
+}
+
+fn changed_note(vault: &Vault, rel: &str) -> Result<Option<ExternalChange>> {
+    if !vault.files.absolute(rel).is_file() {
+        let id = vault.file_removed(rel)?;
+        return Ok(id.map(|note_id| change(note_id, rel, ChangeKind::Removed)));
+    }
+    let known = vault.files.note_at(&vault.storage, rel)?.is_some();
+    let kind = if known {
+        ChangeKind::Modified
+    } else {
+        ChangeKind::Created
+    };
+    Ok(vault
+        .import_file(rel)?
+        .map(|note| change(note.id, rel, kind)))
+}
+
+fn moved_note(vault: &Vault, from: &str, to: &str) -> Result<Option<ExternalChange>> {
+    if !NoteFiles::is_note_path(to) {
+        return changed_note(vault, from);
+    }
+    let note = vault.file_moved(from, to)?;
+    Ok(note.map(|note| {
+        let path = vault
+            .files
+            .path_of(&vault.storage, &note.id)
+            .ok()
+            .flatten()
+            .unwrap_or_else(|| to.to_owned());
+        change(note.id, &path, ChangeKind::Moved)
+    }))
+}
+
+fn change(note_id: String, path: &str, kind: ChangeKind) -> ExternalChange {
+    ExternalChange {
+        note_id,
+        path: path.to_owned(),
+        kind,
+    }
+}
+
+fn relative(root: &Path, path: &Path) -> Option<String> {
+    let rel = path.strip_prefix(root).ok()?;
+    let rel = rel.to_string_lossy().replace('\\', "/");
+    (!rel.is_empty()).then_some(rel)
+}
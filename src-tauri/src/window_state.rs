@@ -0,0 +1,75 @@
+use tauri::plugin::TauriPlugin;
+use tauri::{AppHandle, PhysicalPosition, PhysicalSize, WebviewWindow, Wry};
+use tauri_plugin_window_state::{AppHandleExt, StateFlags};
+
+use crate::error::{Error, Result};
+
+/// Visibility is left out: the main window may be hidden in the tray at
+/// quit, and should still come up on the next launch.
+const FLAGS: StateFlags = StateFlags::all().difference(StateFlags::VISIBLE);
+
+/// Windows whose geometry is not remembered. Quick capture is placed fresh
+/// every time it is summoned.
+const UNTRACKED: &[&str] = &["quick-capture"];
+
+/// Remembers the size, position, maximized state and monitor of every
+/// window by label, in `.window-state.json` under the app config directory,
+/// and puts windows back that way when they are created again.
+pub fn plugin() -> TauriPlugin<Wry> {
+    tauri_plugin_window_state::Builder::default()
+        .with_state_flags(FLAGS)
+        .with_denylist(UNTRACKED)
+        .build()
+}
+
+/// Writes the state of the open windows to disk now rather than at exit.
+pub fn save(app: &AppHandle) -> Result<()> {
+    app.save_window_state(FLAGS)
+        .map_err(|err| Error::InvalidInput(err.to_string()))
+}
+
+/// Keeps a restored window reachable when the monitors changed since it was
+/// saved: a window off every screen is centered, and one larger than the
+/// screen it is on is shrunk to fit.
+pub fn fit(window: &WebviewWindow) -> Result<()> {
+    if window.is_maximized()? || window.is_fullscreen()? {
+        return Ok(());
+    }
+    let position = window.outer_position()?;
+    let size = window.outer_size()?;
+    let monitors = window.available_monitors()?;
+    let on_screen = monitors
+        .iter()
+        .any(|monitor| overlaps(*monitor.position(), *monitor.size(), position, size));
+    let Some(monitor) = window
+        .current_monitor()?
+        .or_else(|| monitors.first().cloned())
+    else {
+        return Ok(());
+    };
+    let screen = monitor.size();
+    if size.width > screen.width || size.height > screen.height {
+        let inner = window.inner_size()?;
+        window.set_size(PhysicalSize::new(
+            inner.width.min(screen.width * 9 / 10),
+            inner.height.min(screen.height * 9 / 10),
+        ))?;
+        window.center()?;
+    } else if !on_screen {
+        window.center()?;
+    }
+    Ok(())
+}
+
+fn overlaps(
+    origin: PhysicalPosition<i32>,
+    area: PhysicalSize<u32>,
+    position: PhysicalPosition<i32>,
+    size: PhysicalSize<u32>,
+) -> bool {
+    let (right, bottom) = (origin.x + area.width as i32, origin.y + area.height as i32);
+    position.x < right
+        && position.x + size.width as i32 > origin.x
+        && position.y < bottom
+        && position.y + size.height as i32 > origin.y
+}
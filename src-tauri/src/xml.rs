@@ -0,0 +1,18 @@
+use quick_xml::events::BytesRef;
+
+/// The character an entity or character reference stands for. quick-xml
+/// reports these separately from the surrounding text; only the predefined
+/// XML entities are known, since none of the formats read here declare more.
+pub fn resolve_entity(reference: &BytesRef<'_>) -> Option<char> {
+    if let Ok(Some(c)) = reference.resolve_char_ref() {
+        return Some(c);
+    }
+    match reference.as_ref() {
+        "amp" => Some('&'),
+        "lt" => Some('<'),
+        "gt" => Some('>'),
+        "quot" => Some('"'),
+        "apos" => Some('\''),
+        _ => None,
+    }
+}